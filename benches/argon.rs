@@ -12,7 +12,7 @@ pub fn benchmark_nve(c: &mut Criterion) {
             let system = test_utils::argon_system();
             let potentials = test_utils::argon_potentials();
             let mut sim = test_utils::nve_simulation(system, potentials);
-            sim.run(ITERATIONS);
+            sim.run(ITERATIONS).unwrap();
         })
     });
 }
@@ -24,7 +24,7 @@ pub fn benchmark_nvt(c: &mut Criterion) {
             let system = test_utils::argon_system();
             let potentials = test_utils::argon_potentials();
             let mut sim = test_utils::nvt_simulation(system, potentials);
-            sim.run(ITERATIONS);
+            sim.run(ITERATIONS).unwrap();
         })
     });
 }
@@ -34,7 +34,7 @@ pub fn benchmark_properties(c: &mut Criterion) {
     let system: System = test_utils::argon_system();
     let potentials: Potentials = test_utils::argon_potentials();
     let mut sim = test_utils::nve_simulation(system, potentials);
-    sim.run(ITERATIONS);
+    sim.run(ITERATIONS).unwrap();
     let (system, potentials) = sim.consume();
 
     let mut group = c.benchmark_group("argon-properties");
@@ -0,0 +1,27 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use nalgebra::Vector3;
+
+use velvet::prelude::*;
+
+// compare minimum image distances in orthorhombic and triclinic cells
+pub fn benchmark_distance(c: &mut Criterion) {
+    let v1 = Vector3::new(0.5, 9.0, 1.0);
+    let v2 = Vector3::new(8.5, 1.0, 7.5);
+
+    let mut group = c.benchmark_group("cell-distance");
+
+    let cell = Cell::triclinic(10.0, 11.0, 12.0, 90.0, 90.0, 90.0);
+    group.bench_function("orthorhombic", |b| {
+        b.iter(|| cell.distance(black_box(&v1), black_box(&v2)))
+    });
+
+    let cell = Cell::triclinic(10.0, 11.0, 12.0, 80.0, 95.0, 105.0);
+    group.bench_function("triclinic", |b| {
+        b.iter(|| cell.distance(black_box(&v1), black_box(&v2)))
+    });
+
+    group.finish();
+}
+
+criterion_group!(cell, benchmark_distance);
+criterion_main!(cell);
@@ -0,0 +1,24 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use velvet::prelude::*;
+
+// compare exact and tabulated evaluations of the Ewald real space pair term
+pub fn benchmark_real_space(c: &mut Criterion) {
+    let ewald = Ewald::new(0.3, 6);
+    let tabulated = Tabulated::new(ewald, 1.0, 10.0, 1e-4);
+
+    let mut group = c.benchmark_group("ewald-real-space");
+
+    group.bench_function("exact", |b| {
+        b.iter(|| ewald.force(black_box(1.0), black_box(-1.0), black_box(3.7)))
+    });
+
+    group.bench_function("tabulated", |b| {
+        b.iter(|| tabulated.force(black_box(1.0), black_box(-1.0), black_box(3.7)))
+    });
+
+    group.finish();
+}
+
+criterion_group!(coulomb, benchmark_real_space);
+criterion_main!(coulomb);
@@ -0,0 +1,33 @@
+// Pair force and energy kernels on fcc argon crystals of increasing size.
+//
+// Compare the scalar and SIMD kernels by saving a baseline without the `simd` feature:
+//
+//     cargo bench --bench simd-benchmarks -- --save-baseline scalar
+//     cargo bench --bench simd-benchmarks --features simd -- --baseline scalar
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use velvet::prelude::*;
+use velvet_test_utils as test_utils;
+
+pub fn benchmark_pair_kernels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lennard-jones");
+
+    for &repeats in [4, 6, 8].iter() {
+        let system = test_utils::argon_crystal(repeats, 0.1);
+        let mut potentials = test_utils::argon_crystal_potentials(8.5);
+        potentials.setup(&system);
+
+        group.bench_with_input(BenchmarkId::new("forces", system.size), &system, |b, system| {
+            b.iter(|| PairForces.calculate(black_box(system), &potentials))
+        });
+
+        group.bench_with_input(BenchmarkId::new("energy", system.size), &system, |b, system| {
+            b.iter(|| PairEnergy.calculate(black_box(system), &potentials))
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(simd, benchmark_pair_kernels);
+criterion_main!(simd);
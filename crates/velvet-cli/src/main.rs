@@ -1,68 +1,128 @@
-// use std::fs::File;
-// use std::io::prelude::*;
-// use std::io::BufReader;
+use std::path::Path;
+use std::process;
+use std::time::Instant;
 
-// use clap::{arg_enum, value_t, App, Arg, ArgMatches, SubCommand};
-// use ron::ser::{to_string_pretty, PrettyConfig};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 
-// use velvet_external_data::poscar::import_poscar;
+use velvet_core::errors::VelvetError;
+use velvet_core::simulation::Simulation;
+use velvet_external_data::bundle::RunBundle;
 
-// arg_enum! {
-//     #[derive(PartialEq, Debug)]
-//     pub enum FileFormat {
-//         Poscar,
-//     }
-// }
+fn main() {
+    let matches = App::new("Velvet CLI")
+        .version(env!("CARGO_PKG_VERSION"))
+        .author("Seaton Ullberg <seatonullberg@gmail.com>")
+        .about("Command line tool built on top of the Velvet API")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("run the simulation described by a TOML run file or a run bundle")
+                .arg(
+                    Arg::with_name("input")
+                        .index(1)
+                        .takes_value(true)
+                        .required(true)
+                        .help("run file (.toml) or run bundle filepath"),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .long("dry-run")
+                        .help("set up the simulation and evaluate the forces once without running any steps"),
+                )
+                .arg(
+                    Arg::with_name("restart")
+                        .long("restart")
+                        .takes_value(true)
+                        .value_name("CHECKPOINT")
+                        .help("resume the run from a checkpoint"),
+                )
+                .arg(
+                    Arg::with_name("checkpoint")
+                        .long("checkpoint")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("periodically write a checkpoint to FILE"),
+                )
+                .arg(
+                    Arg::with_name("checkpoint-interval")
+                        .long("checkpoint-interval")
+                        .takes_value(true)
+                        .value_name("STEPS")
+                        .default_value("1000")
+                        .validator(|value| match value.parse::<usize>() {
+                            Ok(interval) if interval > 0 => Ok(()),
+                            _ => Err("expected a positive integer".to_string()),
+                        })
+                        .help("number of steps between checkpoints"),
+                ),
+        )
+        .get_matches();
 
-// fn main() {
-//     let matches = App::new("Velvet CLI")
-//         .version("0.1.0")
-//         .author("Seaton Ullberg <seatonullberg@gmail.com>")
-//         .about("Command line tool built on top of the Velvet API")
-//         .subcommand(
-//             SubCommand::with_name("convert")
-//                 .about("convert external data formats into Velvet's internal format")
-//                 .arg(
-//                     Arg::with_name("src")
-//                         .index(1)
-//                         .takes_value(true)
-//                         .required(true)
-//                         .help("source filepath"),
-//                 )
-//                 .arg(
-//                     Arg::with_name("dst")
-//                         .index(2)
-//                         .takes_value(true)
-//                         .required(true)
-//                         .help("destination filepath"),
-//                 )
-//                 .arg(
-//                     Arg::with_name("format")
-//                         .short("f")
-//                         .long("format")
-//                         .takes_value(true)
-//                         .possible_values(&FileFormat::variants())
-//                         .case_insensitive(true),
-//                 ),
-//         )
-//         .get_matches();
+    if let Some(matches) = matches.subcommand_matches("run") {
+        handle_run(matches)
+    }
+}
 
-//     if let Some(matches) = matches.subcommand_matches("convert") {
-//         handle_convert(matches)
-//     }
-// }
+fn handle_run(matches: &ArgMatches) {
+    let path = matches.value_of("input").unwrap();
+    if let Err(err) = run(path, matches) {
+        eprintln!("error: {}: {}", path, err);
+        process::exit(1);
+    }
+}
 
-// fn handle_convert(matches: &ArgMatches) {
-//     let file = File::open(matches.value_of("src").unwrap()).unwrap();
-//     let buf = BufReader::new(file);
-//     let fmt = value_t!(matches, "format", FileFormat).unwrap();
-//     let system = match fmt {
-//         FileFormat::Poscar => load_poscar(buf),
-//     };
-//     let pretty = PrettyConfig::new().with_decimal_floats(true);
-//     let res = to_string_pretty(&system, pretty).unwrap();
-//     let mut file = File::create(matches.value_of("dst").unwrap()).unwrap();
-//     file.write_all(res.as_bytes()).unwrap();
-// }
+fn run(path: &str, matches: &ArgMatches) -> Result<(), VelvetError> {
+    let is_toml = Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.to_string_lossy().eq_ignore_ascii_case("toml"));
+    let bundle = if is_toml {
+        RunBundle::read_toml_file(path)?
+    } else {
+        RunBundle::read_file(path)?
+    };
+    eprintln!("read {} atoms from {}", bundle.system.size, path);
 
-fn main() {}
+    let mut builder = bundle.configuration_builder()?;
+    if let Some(checkpoint) = matches.value_of("checkpoint") {
+        let interval: usize = matches.value_of("checkpoint-interval").unwrap().parse().unwrap();
+        eprintln!("writing a checkpoint to {} every {} steps", checkpoint, interval);
+        builder = builder.checkpoint(checkpoint, interval);
+    }
+    let config = builder.build();
+
+    let mut simulation = match matches.value_of("restart") {
+        Some(checkpoint) => {
+            let simulation = bundle.resume(checkpoint, config)?;
+            eprintln!("resuming from step {} of {}", simulation.step(), checkpoint);
+            simulation
+        }
+        None => Simulation::new(
+            bundle.system.clone(),
+            bundle.potentials.build(),
+            bundle.propagator.build()?,
+            config,
+        ),
+    };
+
+    if matches.is_present("dry-run") {
+        let report = simulation.dry_run()?;
+        print!("{}{}", simulation.potentials().summary(), report);
+        return Ok(());
+    }
+
+    eprint!(
+        "{}{}",
+        simulation.potentials().summary(),
+        simulation.potentials().coverage(simulation.system())
+    );
+    let steps = bundle.steps.saturating_sub(simulation.step());
+    let clock = Instant::now();
+    simulation.run(steps)?;
+    eprintln!(
+        "completed step {} of {} in {:.2?}",
+        simulation.step(),
+        bundle.steps,
+        clock.elapsed()
+    );
+    Ok(())
+}
@@ -0,0 +1,257 @@
+//! Algorithms which control the pressure of a system.
+
+use nalgebra::Matrix3;
+
+use crate::checkpoint::PropagatorState;
+use crate::errors::VelvetError;
+use crate::internal::consts::PI;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::pressure::{Pressure, StressTensor};
+use crate::properties::Property;
+use crate::system::cell::Cell;
+use crate::system::System;
+
+/// Shared behavior for algorithms which control the pressure of a system.
+pub trait Barostat: Send + Sync {
+    /// Prepares the barostat to run.
+    fn setup(&mut self, _: &System, _: &Potentials) {}
+    /// Fires before the integration step.
+    fn pre_integrate(&mut self, _: &mut System, _: &Potentials) {}
+    /// Fires after the integration step.
+    fn post_integrate(&mut self, _: &mut System, _: &Potentials) {}
+    /// Returns the internal state of the barostat to store in a checkpoint.
+    fn state(&self) -> PropagatorState {
+        PropagatorState::default()
+    }
+    /// Restores internal state previously returned by [`state`](Barostat::state), or returns an
+    /// error if the state belongs to a different barostat.
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 0, 0)
+    }
+}
+
+impl<T: Barostat + ?Sized> Barostat for Box<T> {
+    fn setup(&mut self, system: &System, potentials: &Potentials) {
+        (**self).setup(system, potentials)
+    }
+
+    fn pre_integrate(&mut self, system: &mut System, potentials: &Potentials) {
+        (**self).pre_integrate(system, potentials)
+    }
+
+    fn post_integrate(&mut self, system: &mut System, potentials: &Potentials) {
+        (**self).post_integrate(system, potentials)
+    }
+
+    fn state(&self) -> PropagatorState {
+        (**self).state()
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        (**self).restore(state)
+    }
+}
+
+// Maps the cell and every position through the linear transformation `deformation`.
+pub(crate) fn deform(system: &mut System, deformation: &Matrix3<Float>) {
+    system.cell = Cell::from_matrix(deformation * system.cell.matrix());
+    system
+        .positions
+        .iter_mut()
+        .for_each(|pos| *pos = deformation * *pos);
+}
+
+/// Berendsen weak coupling barostat.
+///
+/// Isotropically rescales the cell and atomic positions toward the target pressure.
+///
+/// # References
+///
+/// [1] Berendsen, Herman JC, et al. "Molecular dynamics with coupling to an external bath." The Journal of chemical physics 81.8 (1984): 3684-3690.
+#[derive(Clone, Debug)]
+pub struct BerendsenBarostat {
+    target: Float,
+    compressibility: Float,
+    tau: Float,
+}
+
+impl BerendsenBarostat {
+    /// Returns a new Berendsen style barostat.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Target pressure.
+    /// * `compressibility` - Isothermal compressibility of the system in inverse atmospheres.
+    /// * `tau` - Timestep of the barostat expressed as a multiple of the integrator's timestep.
+    pub fn new(target: Float, compressibility: Float, tau: Float) -> BerendsenBarostat {
+        BerendsenBarostat {
+            target,
+            compressibility,
+            tau,
+        }
+    }
+}
+
+impl Barostat for BerendsenBarostat {
+    fn post_integrate(&mut self, system: &mut System, potentials: &Potentials) {
+        let pressure = Pressure.calculate(system, potentials);
+        let factor =
+            Float::cbrt(1.0 - self.compressibility * (self.target - pressure) / self.tau);
+        deform(system, &(Matrix3::identity() * factor));
+    }
+}
+
+/// Anisotropic Parrinello-Rahman barostat.
+///
+/// Each component of the cell evolves under its own equation of motion driven by the
+/// difference between the stress tensor and the target pressure. Atomic positions follow the
+/// cell and velocities are coupled to the rate of deformation. Components of the cell which
+/// would rotate the lattice are held fixed.
+///
+/// # References
+///
+/// [1] Parrinello, Michele, and Aneesur Rahman. "Polymorphic transitions in single crystals: A new molecular dynamics method." Journal of Applied physics 52.12 (1981): 7182-7190.
+///
+/// [2] Nosé, S., and M. L. Klein. "Constant pressure molecular dynamics for molecular systems." Molecular Physics 50.5 (1983): 1055-1076.
+#[derive(Clone, Debug)]
+pub struct ParrinelloRahman {
+    target: Float,
+    compressibility: Float,
+    tau: Float,
+    timestep: Float,
+    velocity: Matrix3<Float>,
+}
+
+impl ParrinelloRahman {
+    /// Returns a new Parrinello-Rahman style barostat.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Target pressure.
+    /// * `compressibility` - Isothermal compressibility of the system in inverse atmospheres.
+    /// * `tau` - Period of the cell oscillations.
+    /// * `timestep` - Timestep of the integrator.
+    pub fn new(target: Float, compressibility: Float, tau: Float, timestep: Float) -> ParrinelloRahman {
+        ParrinelloRahman {
+            target,
+            compressibility,
+            tau,
+            timestep,
+            velocity: Matrix3::zeros(),
+        }
+    }
+}
+
+impl Barostat for ParrinelloRahman {
+    fn setup(&mut self, _: &System, _: &Potentials) {
+        self.velocity = Matrix3::zeros();
+    }
+
+    fn post_integrate(&mut self, system: &mut System, potentials: &Potentials) {
+        let dt = self.timestep;
+        let matrix = system.cell.matrix();
+        let volume = system.cell.volume();
+        let length = matrix.diagonal().max();
+
+        // inverse mass of the cell
+        let w_inv = 4.0 * PI.powi(2) * self.compressibility / (3.0 * self.tau.powi(2) * length);
+        let imbalance = StressTensor.calculate(system, potentials)
+            - Matrix3::identity() * self.target;
+        let mut acceleration =
+            volume * w_inv * imbalance * system.cell.inverse_matrix().transpose();
+
+        // lattice vectors are stored upper triangular so lower elements would rotate the cell
+        acceleration[(1, 0)] = 0.0;
+        acceleration[(2, 0)] = 0.0;
+        acceleration[(2, 1)] = 0.0;
+        self.velocity += acceleration * dt;
+
+        // couple the velocities to the rate of deformation
+        let strain_rate = self.velocity * system.cell.inverse_matrix();
+        system
+            .velocities
+            .iter_mut()
+            .for_each(|vel| *vel -= dt * strain_rate * *vel);
+
+        let deformation = (matrix + self.velocity * dt) * system.cell.inverse_matrix();
+        deform(system, &deformation);
+    }
+
+    fn state(&self) -> PropagatorState {
+        PropagatorState::from_floats(self.velocity.as_slice().to_vec())
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 9, 0)?;
+        self.velocity = Matrix3::from_column_slice(&state.floats);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Barostat, BerendsenBarostat, ParrinelloRahman};
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::pressure::Pressure;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    // hot ideal gas well above atmospheric pressure
+    fn compressed_gas() -> System {
+        let argon = Species::from_element(Element::Ar);
+        System {
+            size: 2,
+            cell: Cell::triclinic(6.0, 7.0, 8.0, 90.0, 90.0, 90.0),
+            species: vec![argon, argon],
+            positions: vec![Vector3::new(1.0, 1.0, 1.0), Vector3::new(4.0, 4.0, 4.0)],
+            velocities: vec![Vector3::new(0.1, 0.05, 0.0), Vector3::new(-0.1, -0.05, 0.0)],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn berendsen_expands() {
+        let mut system = compressed_gas();
+        let potentials = PotentialsBuilder::new().build();
+        let volume = system.cell.volume();
+        let fractional = system.cell.fractional(&system.positions[1]);
+        let before = Pressure.calculate(&system, &potentials);
+        let mut barostat = BerendsenBarostat::new(1.0, 4.5e-5, 1.0);
+        barostat.setup(&system, &potentials);
+        barostat.post_integrate(&mut system, &potentials);
+        assert!(system.cell.volume() > volume);
+        assert!(Pressure.calculate(&system, &potentials) < before);
+        // isotropic scaling preserves the shape of the cell and the fractional coordinates
+        assert_relative_eq!(system.cell.alpha(), 90.0, epsilon = 1e-4);
+        assert_relative_eq!(system.cell.b() / system.cell.a(), 7.0 / 6.0, epsilon = 1e-5);
+        let after = system.cell.fractional(&system.positions[1]);
+        assert_relative_eq!(after, fractional, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn parrinello_rahman_anisotropic() {
+        let mut system = compressed_gas();
+        let potentials = PotentialsBuilder::new().build();
+        let matrix = system.cell.matrix();
+        let mut barostat = ParrinelloRahman::new(1.0, 4.5e-5, 100.0, 1.0);
+        barostat.setup(&system, &potentials);
+        for _ in 0..10 {
+            barostat.post_integrate(&mut system, &potentials);
+        }
+        let deformed = system.cell.matrix();
+        // only the loaded directions expand and the cell does not rotate
+        assert!(deformed[(0, 0)] > matrix[(0, 0)]);
+        assert!(deformed[(1, 1)] > matrix[(1, 1)]);
+        assert_relative_eq!(deformed[(2, 2)], matrix[(2, 2)], epsilon = 1e-3);
+        assert_eq!(deformed[(1, 0)], 0.0);
+        assert_eq!(deformed[(2, 0)], 0.0);
+        assert_eq!(deformed[(2, 1)], 0.0);
+    }
+}
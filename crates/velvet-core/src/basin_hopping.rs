@@ -0,0 +1,223 @@
+//! Basin hopping global optimization of the potential energy landscape.
+
+use nalgebra::Vector3;
+use rand::Rng;
+
+use crate::errors::VelvetError;
+use crate::internal::consts::BOLTZMANN;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::properties::energy::PotentialEnergy;
+use crate::properties::Property;
+use crate::random::shared_rng;
+use crate::system::System;
+
+/// Basin hopping search for low energy structures such as the global minimum of a cluster.
+///
+/// Each hop displaces every atom randomly within a cube of half width `step_size` and relaxes the
+/// perturbed structure into its local minimum, which transforms the potential energy landscape
+/// into a staircase of basins. The new minimum is accepted with the Metropolis criterion at
+/// `temperature`, otherwise the search continues from the previous minimum. The lowest minimum
+/// found over every hop is kept separately from the current one.
+///
+/// # References
+///
+/// [1] Wales, David J., and Jonathan PK Doye. "Global optimization by basin-hopping and the lowest energy structures of Lennard-Jones clusters containing up to 110 atoms." The Journal of Physical Chemistry A 101.28 (1997): 5111-5116.
+#[derive(Clone, Debug)]
+pub struct BasinHopping {
+    temperature: Float,
+    step_size: Float,
+    max_steps: usize,
+    energy: Float,
+    best: Option<(System, Float)>,
+    hops: usize,
+    accepted: usize,
+}
+
+impl BasinHopping {
+    /// Returns a new [`BasinHopping`] driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `temperature` - Temperature of the Metropolis criterion in Kelvin.
+    /// * `step_size` - Largest displacement of each atom along each axis in angstroms.
+    pub fn new(temperature: Float, step_size: Float) -> BasinHopping {
+        BasinHopping {
+            temperature,
+            step_size,
+            max_steps: 1000,
+            energy: 0.0,
+            best: None,
+            hops: 0,
+            accepted: 0,
+        }
+    }
+
+    /// Sets the largest number of minimizer steps used to relax each structure. Defaults to 1000.
+    pub fn max_steps(mut self, steps: usize) -> BasinHopping {
+        self.max_steps = steps;
+        self
+    }
+
+    /// Returns the potential energy of the current minimum in kcal/mol.
+    pub fn energy(&self) -> Float {
+        self.energy
+    }
+
+    /// Returns the lowest energy structure found so far and its potential energy in kcal/mol.
+    pub fn best(&self) -> Option<(&System, Float)> {
+        self.best.as_ref().map(|(system, energy)| (system, *energy))
+    }
+
+    /// Returns the number of hops attempted so far.
+    pub fn hops(&self) -> usize {
+        self.hops
+    }
+
+    /// Returns the fraction of attempted hops which were accepted.
+    pub fn acceptance(&self) -> Float {
+        if self.hops == 0 {
+            0.0
+        } else {
+            self.accepted as Float / self.hops as Float
+        }
+    }
+
+    /// Runs `hops` basin hopping steps.
+    ///
+    /// The starting configuration is relaxed before the first hop. On return `system` holds the
+    /// current minimum of the search.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - Starting configuration.
+    /// * `potentials` - Potentials which define the energy landscape.
+    /// * `minimizer` - Constructor for the minimizer which relaxes each structure.
+    /// * `hops` - Number of hops to attempt.
+    pub fn run<G, P>(
+        &mut self,
+        system: &mut System,
+        potentials: &mut Potentials,
+        minimizer: G,
+        hops: usize,
+    ) -> Result<(), VelvetError>
+    where
+        G: Fn() -> P,
+        P: Propagator,
+    {
+        if self.best.is_none() {
+            self.energy = self.relax(system, potentials, minimizer())?;
+            self.best = Some((system.clone(), self.energy));
+        }
+        let mut rng = shared_rng();
+        let beta = 1.0 / (BOLTZMANN * self.temperature);
+        for _ in 0..hops {
+            let mut trial = system.clone();
+            for pos in trial.positions.iter_mut() {
+                *pos += Vector3::new(
+                    rng.gen_range(-self.step_size, self.step_size),
+                    rng.gen_range(-self.step_size, self.step_size),
+                    rng.gen_range(-self.step_size, self.step_size),
+                );
+                trial.cell.wrap_vector(pos);
+            }
+            let energy = self.relax(&mut trial, potentials, minimizer())?;
+            self.hops += 1;
+
+            // metropolis criterion between the previous and the new minimum
+            let change = energy - self.energy;
+            if change <= 0.0 || rng.gen::<Float>() < Float::exp(-beta * change) {
+                self.accepted += 1;
+                self.energy = energy;
+                *system = trial;
+                if self.best.as_ref().is_none_or(|(_, best)| energy < *best) {
+                    self.best = Some((system.clone(), energy));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Relaxes `system` into its local minimum and returns the minimum energy.
+    fn relax<P: Propagator>(
+        &self,
+        system: &mut System,
+        potentials: &mut Potentials,
+        mut minimizer: P,
+    ) -> Result<Float, VelvetError> {
+        potentials.setup(system);
+        minimizer.setup(system, potentials)?;
+        for i in 0..self.max_steps {
+            if minimizer.converged() {
+                break;
+            }
+            minimizer.propagate(system, potentials);
+            potentials.update(system, i);
+        }
+        Ok(PotentialEnergy.calculate(system, potentials))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BasinHopping;
+    use crate::internal::Float;
+    use crate::minimizers::Fire;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // seven atoms in a loose chain with reduced Lennard-Jones units
+    fn cluster() -> (System, Potentials) {
+        let atom = Species::new(1.0, 0.0);
+        let positions: Vec<Vector3<Float>> = (0..7)
+            .map(|i| Vector3::new(10.0 + 1.1 * i as Float, 10.0 + 0.3 * (i % 2) as Float, 10.0))
+            .collect();
+        let system = System {
+            size: 7,
+            cell: Cell::cubic(20.0),
+            species: vec![atom; 7],
+            velocities: vec![Vector3::zeros(); 7],
+            positions,
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(1.0, 1.0), (atom, atom), 5.0, 0.5)
+            .build();
+        (system, potentials)
+    }
+
+    #[test]
+    fn lennard_jones_heptamer() {
+        let (mut system, mut potentials) = cluster();
+        // thermal energy of about 0.8 epsilon
+        let mut search = BasinHopping::new(400.0, 0.4).max_steps(2000);
+        search.run(&mut system, &mut potentials, || Fire::new(0.01, 0.05), 100).unwrap();
+        assert_eq!(search.hops(), 100);
+        assert!(search.acceptance() > 0.0 && search.acceptance() < 1.0);
+
+        // the pentagonal bipyramid is the global minimum at -16.505 epsilon
+        let (best, energy) = search.best().unwrap();
+        assert!((energy + 16.505).abs() < 1e-2, "{}", energy);
+        assert_eq!(best.size, 7);
+        assert!(search.energy() >= energy);
+    }
+
+    #[test]
+    fn downhill_only() {
+        let (mut system, mut potentials) = cluster();
+        // at zero temperature only lower minima are accepted
+        let mut search = BasinHopping::new(0.0, 0.3);
+        search.run(&mut system, &mut potentials, || Fire::new(0.01, 0.05), 10).unwrap();
+        let first = search.energy();
+        search.run(&mut system, &mut potentials, || Fire::new(0.01, 0.05), 10).unwrap();
+        assert_eq!(search.hops(), 20);
+        assert!(search.energy() <= first);
+        assert_eq!(search.best().unwrap().1, search.energy());
+    }
+}
@@ -0,0 +1,499 @@
+//! Binary checkpoints which allow an interrupted simulation to resume.
+//!
+//! A checkpoint stores the complete [`System`], the number of completed steps, and the internal
+//! [`PropagatorState`] such as the variables of a Nose-Hoover thermostat. Potentials and
+//! propagators are arbitrary user types so they are not stored and must be constructed again
+//! when resuming, after which the saved state of the propagator is restored. The state of the
+//! potentials is not part of a checkpoint: neighbor lists are rebuilt when the simulation resumes,
+//! and biases with internal state, such as the fictitious particle of
+//! [`TemperatureAccelerated`](crate::colvars::TemperatureAccelerated), start over.
+//!
+//! All floating point values are stored as little-endian `f64` regardless of the storage
+//! type Velvet was compiled with, and all lengths and counters as little-endian `u64`.
+
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::system::cell::Cell;
+use crate::system::species::Species;
+use crate::system::topology::{Group, Residue, Topology};
+use crate::system::System;
+
+/// Leading bytes of every checkpoint.
+const MAGIC: &[u8; 4] = b"VLVC";
+
+/// Version of the checkpoint layout.
+///
+/// 1. Initial layout.
+/// 2. Groups of the topology.
+/// 3. Structured propagator states with integer lengths and counters.
+/// 4. Partial charges of the topology.
+const VERSION: u64 = 4;
+
+/// Deepest nesting of propagator states accepted when reading a checkpoint.
+const MAX_DEPTH: usize = 16;
+
+/// Internal state of a propagator, or of one of its components, stored in a checkpoint.
+///
+/// Counters are kept as integers so they survive any number of steps exactly, and propagators
+/// made of several components store the state of each component as one of the parts. Each
+/// `restore` method checks the shape of the state it is given with
+/// [`expect`](PropagatorState::expect) and returns an error, rather than panicking, if the state
+/// does not belong to it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PropagatorState {
+    /// Integer values such as step counters.
+    pub integers: Vec<u64>,
+    /// Floating point values.
+    pub floats: Vec<Float>,
+    /// States of the components.
+    pub parts: Vec<PropagatorState>,
+}
+
+impl PropagatorState {
+    /// Returns a state holding only the floating point `values`.
+    pub fn from_floats(values: Vec<Float>) -> PropagatorState {
+        PropagatorState {
+            floats: values,
+            ..PropagatorState::default()
+        }
+    }
+
+    /// Returns a state holding only the components of `vectors` as floating point values.
+    pub fn from_vectors(vectors: &[Vector3<Float>]) -> PropagatorState {
+        PropagatorState::from_floats(vectors.iter().flat_map(|v| v.iter().copied()).collect())
+    }
+
+    /// Returns an error unless the state holds exactly `integers` integers, `floats` floating
+    /// point values, and `parts` parts.
+    pub fn expect(&self, integers: usize, floats: usize, parts: usize) -> Result<(), VelvetError> {
+        let mut mismatches = [
+            ("integers", integers, self.integers.len()),
+            ("floating point values", floats, self.floats.len()),
+            ("parts", parts, self.parts.len()),
+        ]
+        .iter()
+        .filter(|(_, expected, found)| expected != found)
+        .map(|(what, expected, found)| format!("expected {} {}, found {}", expected, what, found))
+        .collect::<Vec<String>>();
+        match mismatches.len() {
+            0 => Ok(()),
+            _ => {
+                mismatches.insert(0, "Propagator state does not match the propagator".to_string());
+                Err(VelvetError::parse("checkpoint", mismatches.join(", ")))
+            }
+        }
+    }
+
+    /// Returns the floating point values as `len` vectors, or an error unless the state holds
+    /// only the components of `len` vectors.
+    pub fn vectors(&self, len: usize) -> Result<Vec<Vector3<Float>>, VelvetError> {
+        self.expect(0, 3 * len, 0)?;
+        Ok(self.floats.chunks(3).map(Vector3::from_column_slice).collect())
+    }
+}
+
+/// Contents of a checkpoint file.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    /// Complete state of the simulated system.
+    pub system: System,
+    /// Number of steps completed by the simulation.
+    pub step: usize,
+    /// Internal state of the propagator.
+    pub state: PropagatorState,
+}
+
+impl Checkpoint {
+    /// Writes the checkpoint to `path`.
+    ///
+    /// The checkpoint is first written to a temporary file beside `path` and then renamed so an
+    /// interruption while writing never corrupts a previous checkpoint.
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> Result<(), VelvetError> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        self.write(BufWriter::new(File::create(&temporary)?))?;
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+
+    /// Reads a checkpoint from `path`.
+    pub fn read_file<P: AsRef<Path>>(path: P) -> Result<Checkpoint, VelvetError> {
+        Checkpoint::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the checkpoint in binary format.
+    pub fn write<W: Write>(&self, writer: W) -> Result<(), VelvetError> {
+        let mut enc = Encoder(writer);
+        enc.0.write_all(MAGIC)?;
+        enc.u64(VERSION)?;
+        enc.u64(self.step as u64)?;
+
+        // species table
+        let system = &self.system;
+        let mut table: Vec<Species> = Vec::new();
+        for species in &system.species {
+            if !table.contains(species) {
+                table.push(*species);
+            }
+        }
+        enc.u64(table.len() as u64)?;
+        for species in &table {
+            enc.0.write_all(&species.id().to_le_bytes())?;
+            enc.float(species.mass())?;
+            enc.float(species.charge())?;
+        }
+
+        // atoms
+        enc.floats(system.cell.matrix().as_slice())?;
+        enc.u64(system.size as u64)?;
+        for ((species, pos), vel) in system
+            .species
+            .iter()
+            .zip(system.positions.iter())
+            .zip(system.velocities.iter())
+        {
+            enc.u64(table.iter().position(|s| s == species).unwrap() as u64)?;
+            enc.floats(pos.as_slice())?;
+            enc.floats(vel.as_slice())?;
+        }
+
+        // topology
+        let topology = &system.topology;
+        enc.indices(topology.bonds.iter().flatten(), topology.bonds.len())?;
+        enc.indices(topology.angles.iter().flatten(), topology.angles.len())?;
+        enc.indices(topology.dihedrals.iter().flatten(), topology.dihedrals.len())?;
+        enc.u64(topology.residues.len() as u64)?;
+        for residue in &topology.residues {
+            enc.string(&residue.name)?;
+            enc.indices(residue.atoms.iter(), residue.atoms.len())?;
+        }
+        enc.u64(topology.groups.len() as u64)?;
+        for group in &topology.groups {
+            enc.string(&group.name)?;
+            enc.indices(group.atoms.iter(), group.atoms.len())?;
+        }
+        enc.u64(topology.charges.len() as u64)?;
+        enc.floats(&topology.charges)?;
+
+        // propagator state
+        enc.state(&self.state)?;
+        enc.0.flush()?;
+        Ok(())
+    }
+
+    /// Reads a checkpoint from binary format.
+    ///
+    /// Returns an error if the data is not a checkpoint, was written by a newer version of Velvet,
+    /// or is malformed. The propagator state of checkpoints written before version 3 is read as
+    /// the floating point values of a single [`PropagatorState`].
+    pub fn read<R: Read>(reader: R) -> Result<Checkpoint, VelvetError> {
+        let mut dec = Decoder(reader);
+        let mut magic = [0u8; 4];
+        dec.0.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("Not a velvet checkpoint"));
+        }
+        let version = dec.u64()?;
+        if version == 0 || version > VERSION {
+            return Err(invalid(format!("Unsupported checkpoint version {}", version)));
+        }
+        let step = dec.usize()?;
+
+        // species table
+        let mut table = Vec::new();
+        for _ in 0..dec.usize()? {
+            let mut id = [0u8; 16];
+            dec.0.read_exact(&mut id)?;
+            let mass = dec.float()?;
+            let charge = dec.float()?;
+            table.push(Species::from_id(u128::from_le_bytes(id), mass, charge));
+        }
+
+        // atoms
+        let matrix = Matrix3::from_column_slice(&dec.floats(9)?);
+        if matrix.iter().any(|x| !x.is_finite()) || matrix.try_inverse().is_none() {
+            return Err(invalid("Lattice vectors are not linearly independent"));
+        }
+        let cell = Cell::from_matrix(matrix);
+        let size = dec.usize()?;
+        let mut species = Vec::new();
+        let mut positions = Vec::new();
+        let mut velocities = Vec::new();
+        for _ in 0..size {
+            let index = dec.usize()?;
+            species.push(*table.get(index).ok_or_else(|| invalid("Species index out of range"))?);
+            positions.push(Vector3::from_column_slice(&dec.floats(3)?));
+            velocities.push(Vector3::from_column_slice(&dec.floats(3)?));
+        }
+
+        // topology
+        let bonds = dec.indices(2, size)?;
+        let angles = dec.indices(3, size)?;
+        let dihedrals = dec.indices(4, size)?;
+        let mut residues = Vec::new();
+        for _ in 0..dec.usize()? {
+            residues.push(Residue {
+                name: dec.string()?,
+                atoms: dec.indices(1, size)?.into_iter().flatten().collect(),
+            });
+        }
+        let mut groups = Vec::new();
+        // version 1 predates groups
+        for _ in 0..if version > 1 { dec.usize()? } else { 0 } {
+            groups.push(Group {
+                name: dec.string()?,
+                atoms: dec.indices(1, size)?.into_iter().flatten().collect(),
+            });
+        }
+        // versions before 4 predate partial charges
+        let charges = if version > 3 {
+            let len = dec.usize()?;
+            if len != 0 && len != size {
+                return Err(invalid("Partial charges do not match the number of atoms"));
+            }
+            dec.floats(len)?
+        } else {
+            Vec::new()
+        };
+        let topology = Topology {
+            bonds: bonds.iter().map(|b| [b[0], b[1]]).collect(),
+            angles: angles.iter().map(|a| [a[0], a[1], a[2]]).collect(),
+            dihedrals: dihedrals.iter().map(|d| [d[0], d[1], d[2], d[3]]).collect(),
+            residues,
+            groups,
+            charges,
+            // recorded again when the propagator is set up
+            constraints: Vec::new(),
+            frozen: Vec::new(),
+        };
+
+        // propagator state
+        let state = if version > 2 {
+            dec.state(0)?
+        } else {
+            let len = dec.usize()?;
+            PropagatorState::from_floats(dec.floats(len)?)
+        };
+
+        Ok(Checkpoint {
+            system: System {
+                size,
+                cell,
+                species,
+                positions,
+                velocities,
+                topology,
+            },
+            step,
+            state,
+        })
+    }
+}
+
+fn invalid<T: Into<String>>(msg: T) -> VelvetError {
+    VelvetError::parse("checkpoint", msg)
+}
+
+// Little-endian primitive writer.
+struct Encoder<W: Write>(W);
+
+impl<W: Write> Encoder<W> {
+    fn u64(&mut self, value: u64) -> Result<(), VelvetError> {
+        Ok(self.0.write_all(&value.to_le_bytes())?)
+    }
+
+    // double precision is stored regardless of `Float`
+    #[allow(clippy::unnecessary_cast)]
+    fn float(&mut self, value: Float) -> Result<(), VelvetError> {
+        Ok(self.0.write_all(&(value as f64).to_le_bytes())?)
+    }
+
+    fn floats(&mut self, values: &[Float]) -> Result<(), VelvetError> {
+        values.iter().try_for_each(|value| self.float(*value))
+    }
+
+    fn string(&mut self, value: &str) -> Result<(), VelvetError> {
+        self.u64(value.len() as u64)?;
+        Ok(self.0.write_all(value.as_bytes())?)
+    }
+
+    fn indices<'a, I: Iterator<Item = &'a usize>>(&mut self, indices: I, len: usize) -> Result<(), VelvetError> {
+        self.u64(len as u64)?;
+        indices.into_iter().try_for_each(|index| self.u64(*index as u64))
+    }
+
+    fn state(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.u64(state.integers.len() as u64)?;
+        state.integers.iter().try_for_each(|value| self.u64(*value))?;
+        self.u64(state.floats.len() as u64)?;
+        self.floats(&state.floats)?;
+        self.u64(state.parts.len() as u64)?;
+        state.parts.iter().try_for_each(|part| self.state(part))
+    }
+}
+
+// Little-endian primitive reader.
+//
+// Lengths read from the data are never used to preallocate, so a corrupted length fails at the
+// end of the data instead of exhausting memory.
+struct Decoder<R: Read>(R);
+
+impl<R: Read> Decoder<R> {
+    fn u64(&mut self) -> Result<u64, VelvetError> {
+        let mut buf = [0u8; 8];
+        self.0.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn usize(&mut self) -> Result<usize, VelvetError> {
+        usize::try_from(self.u64()?).map_err(|_| invalid("Length out of range"))
+    }
+
+    fn float(&mut self) -> Result<Float, VelvetError> {
+        let mut buf = [0u8; 8];
+        self.0.read_exact(&mut buf)?;
+        Ok(f64::from_le_bytes(buf) as Float)
+    }
+
+    fn floats(&mut self, len: usize) -> Result<Vec<Float>, VelvetError> {
+        (0..len).map(|_| self.float()).collect()
+    }
+
+    fn string(&mut self) -> Result<String, VelvetError> {
+        let len = self.u64()?;
+        let mut buf = Vec::new();
+        if (&mut self.0).take(len).read_to_end(&mut buf)? as u64 != len {
+            return Err(invalid("Truncated string"));
+        }
+        String::from_utf8(buf).map_err(|_| invalid("String is not valid UTF-8"))
+    }
+
+    // Reads `len` entries of `width` atom indices which must be smaller than `size`.
+    fn indices(&mut self, width: usize, size: usize) -> Result<Vec<Vec<usize>>, VelvetError> {
+        let len = self.usize()?;
+        (0..len)
+            .map(|_| {
+                (0..width)
+                    .map(|_| {
+                        let index = self.usize()?;
+                        if index < size {
+                            Ok(index)
+                        } else {
+                            Err(invalid("Topology index out of range"))
+                        }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Reads a propagator state nested `depth` parts deep.
+    fn state(&mut self, depth: usize) -> Result<PropagatorState, VelvetError> {
+        if depth > MAX_DEPTH {
+            return Err(invalid("Propagator state is nested too deeply"));
+        }
+        let integers = (0..self.usize()?).map(|_| self.u64()).collect::<Result<_, _>>()?;
+        let len = self.usize()?;
+        let floats = self.floats(len)?;
+        let parts = (0..self.usize()?).map(|_| self.state(depth + 1)).collect::<Result<_, _>>()?;
+        Ok(PropagatorState {
+            integers,
+            floats,
+            parts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Checkpoint, PropagatorState};
+    use crate::errors::VelvetError;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::{Group, Topology};
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    fn checkpoint() -> Checkpoint {
+        let argon = Species::new(39.948, 0.0);
+        Checkpoint {
+            system: System {
+                size: 2,
+                cell: Cell::cubic(10.0),
+                species: vec![argon; 2],
+                positions: vec![Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0)],
+                velocities: vec![Vector3::new(0.1, 0.0, 0.0), Vector3::zeros()],
+                topology: Topology {
+                    bonds: vec![[0, 1]],
+                    groups: vec![Group {
+                        name: "pair".to_string(),
+                        atoms: vec![0, 1],
+                    }],
+                    charges: vec![0.25, -0.25],
+                    ..Topology::default()
+                },
+            },
+            step: 1 << 40,
+            state: PropagatorState {
+                integers: vec![7],
+                floats: vec![0.5],
+                parts: vec![PropagatorState::from_floats(vec![1.0, 2.0]), PropagatorState::default()],
+            },
+        }
+    }
+
+    fn bytes(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let checkpoint = checkpoint();
+        let restored = Checkpoint::read(bytes(&checkpoint).as_slice()).unwrap();
+        assert_eq!(restored.step, checkpoint.step);
+        assert_eq!(restored.state, checkpoint.state);
+        assert_eq!(restored.system.positions, checkpoint.system.positions);
+        assert_eq!(restored.system.topology, checkpoint.system.topology);
+    }
+
+    #[test]
+    fn reject_malformed() {
+        let checkpoint = checkpoint();
+        let bytes = bytes(&checkpoint);
+        // truncated data
+        assert!(matches!(Checkpoint::read(&bytes[..bytes.len() - 4]), Err(VelvetError::Io(_))));
+        // versions written by a newer Velvet
+        let mut newer = bytes.clone();
+        newer[4] = 5;
+        assert!(matches!(Checkpoint::read(newer.as_slice()), Err(VelvetError::Parse { .. })));
+        // a huge number of atoms runs out of data instead of memory
+        let offset = 4 + 8 + 8 + 8 + 32 + 72;
+        let mut huge = bytes.clone();
+        huge[offset..offset + 8].copy_from_slice(&(u64::MAX >> 1).to_le_bytes());
+        assert!(Checkpoint::read(huge.as_slice()).is_err());
+        // a singular cell
+        let mut singular = bytes;
+        singular[offset - 72..offset].iter_mut().for_each(|byte| *byte = 0);
+        assert!(matches!(Checkpoint::read(singular.as_slice()), Err(VelvetError::Parse { .. })));
+    }
+
+    #[test]
+    fn expect_shape() {
+        let state = checkpoint().state;
+        assert!(state.expect(1, 1, 2).is_ok());
+        assert!(state.expect(0, 1, 2).is_err());
+        assert!(state.parts[0].vectors(1).is_err());
+        let vectors = PropagatorState::from_vectors(&[Vector3::new(1.0, 2.0, 3.0)]);
+        assert_eq!(vectors.vectors(1).unwrap(), vec![Vector3::new(1.0, 2.0, 3.0)]);
+    }
+}
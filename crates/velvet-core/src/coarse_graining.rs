@@ -0,0 +1,449 @@
+//! Coarse-graining of atomistic systems into beads and derivation of effective pair potentials.
+
+use nalgebra::{DMatrix, DVector, Vector3};
+
+use crate::internal::consts::BOLTZMANN;
+use crate::internal::Float;
+use crate::potentials::types::TabulatedPair;
+use crate::system::species::Species;
+use crate::system::topology::{Residue, Topology};
+use crate::system::System;
+
+/// Center of mass mapping of groups of atoms onto coarse-grained beads.
+///
+/// Each bead is located at the center of mass of its atoms and moves with their center of mass
+/// velocity, and the force on a bead is the sum of the forces on its atoms. Atoms which belong to
+/// no bead are dropped from the mapped system. Mapping every frame of an atomistic trajectory
+/// gives the coarse-grained trajectory used to derive effective potentials.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let water = Species::new(18.015, 0.0);
+/// // two water molecules whose atoms are listed in order
+/// let mapping = BeadMapping::new()
+///     .bead(vec![0, 1, 2], water)
+///     .bead(vec![3, 4, 5], water);
+/// assert_eq!(mapping.beads(), 2);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BeadMapping {
+    beads: Vec<(Vec<usize>, Species)>,
+}
+
+impl BeadMapping {
+    /// Returns a new [`BeadMapping`] without any beads.
+    pub fn new() -> BeadMapping {
+        BeadMapping { beads: Vec::new() }
+    }
+
+    /// Adds a bead of the given species located at the center of mass of `atoms`.
+    pub fn bead(mut self, atoms: Vec<usize>, species: Species) -> BeadMapping {
+        self.beads.push((atoms, species));
+        self
+    }
+
+    /// Returns a [`BeadMapping`] with one bead for each residue in the topology.
+    ///
+    /// # Arguments
+    ///
+    /// * `topology` - Topology whose residues define the beads.
+    /// * `species` - Returns the species of the bead which represents a residue.
+    pub fn from_residues<F>(topology: &Topology, mut species: F) -> BeadMapping
+    where
+        F: FnMut(&Residue) -> Species,
+    {
+        BeadMapping {
+            beads: topology
+                .residues
+                .iter()
+                .map(|residue| (residue.atoms.clone(), species(residue)))
+                .collect(),
+        }
+    }
+
+    /// Returns the number of beads.
+    pub fn beads(&self) -> usize {
+        self.beads.len()
+    }
+
+    /// Returns the coarse-grained system of beads which represents `system`.
+    ///
+    /// Bonds between atoms of different beads become bonds between those beads.
+    pub fn map(&self, system: &System) -> System {
+        let mut positions = Vec::with_capacity(self.beads.len());
+        let mut velocities = Vec::with_capacity(self.beads.len());
+        for (atoms, _) in &self.beads {
+            let origin = system.positions[atoms[0]];
+            let mut mass = 0.0;
+            let mut offset = Vector3::zeros();
+            let mut momentum = Vector3::zeros();
+            for &i in atoms {
+                let m = system.species[i].mass();
+                // atoms of a bead may be split across the periodic boundary
+                let mut displacement = system.positions[i] - origin;
+                system.cell.vector_image(&mut displacement);
+                mass += m;
+                offset += m * displacement;
+                momentum += m * system.velocities[i];
+            }
+            let mut position = origin + offset / mass;
+            system.cell.wrap_vector(&mut position);
+            positions.push(position);
+            velocities.push(momentum / mass);
+        }
+
+        // bead of each atom
+        let mut owners = vec![None; system.size];
+        for (index, (atoms, _)) in self.beads.iter().enumerate() {
+            atoms.iter().for_each(|&i| owners[i] = Some(index));
+        }
+        let mut bonds: Vec<[usize; 2]> = system
+            .topology
+            .bonds
+            .iter()
+            .filter_map(|&[i, j]| match (owners[i], owners[j]) {
+                (Some(a), Some(b)) if a != b => Some([a.min(b), a.max(b)]),
+                _ => None,
+            })
+            .collect();
+        bonds.sort_unstable();
+        bonds.dedup();
+
+        System {
+            size: self.beads.len(),
+            cell: system.cell.clone(),
+            species: self.beads.iter().map(|(_, species)| *species).collect(),
+            positions,
+            velocities,
+            topology: Topology {
+                bonds,
+                ..Topology::default()
+            },
+        }
+    }
+
+    /// Returns the force on each bead given the force on each atom.
+    pub fn map_forces(&self, forces: &[Vector3<Float>]) -> Vec<Vector3<Float>> {
+        self.beads
+            .iter()
+            .map(|(atoms, _)| atoms.iter().map(|&i| forces[i]).sum())
+            .collect()
+    }
+}
+
+/// Force matching of a tabulated pair potential to the forces on coarse-grained beads.
+///
+/// The pair force is represented by linear interpolation between uniformly spaced nodes which
+/// reaches zero at the cutoff, and the node values are fit by linear least squares to the
+/// reference forces on the beads over every added frame. Only beads of the two species take part
+/// in the fit, so the reference forces should contain no other interactions, as is the case for a
+/// system with a single type of bead. Nodes at separations which never occurred take the force of
+/// the closest sampled node. Every pair of beads is visited for each frame.
+///
+/// # References
+///
+/// [1] Izvekov, Sergei, and Gregory A. Voth. "A multiscale coarse-graining method for biomolecular systems." The Journal of Physical Chemistry B 109.7 (2005): 2469-2473.
+#[derive(Clone, Debug)]
+pub struct ForceMatching {
+    species: (Species, Species),
+    cutoff: Float,
+    nodes: usize,
+    normal: DMatrix<Float>,
+    rhs: DVector<Float>,
+    frames: usize,
+}
+
+impl ForceMatching {
+    /// Returns a new [`ForceMatching`] fit.
+    ///
+    /// # Arguments
+    ///
+    /// * `species` - Species of the pair of beads.
+    /// * `cutoff` - Cutoff radius of the pair potential.
+    /// * `intervals` - Number of intervals between the nodes of the tabulated force.
+    pub fn new(species: (Species, Species), cutoff: Float, intervals: usize) -> ForceMatching {
+        let nodes = intervals.max(1);
+        ForceMatching {
+            species,
+            cutoff,
+            nodes,
+            normal: DMatrix::zeros(nodes, nodes),
+            rhs: DVector::zeros(nodes),
+            frames: 0,
+        }
+    }
+
+    /// Returns the number of frames added to the fit.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Adds a frame of the coarse-grained system along with the reference force on each bead.
+    pub fn add_frame(&mut self, system: &System, forces: &[Vector3<Float>]) {
+        let (a, b) = self.species;
+        let matches = |i: usize, j: usize| {
+            let (si, sj) = (system.species[i], system.species[j]);
+            (si == a && sj == b) || (si == b && sj == a)
+        };
+        let step = self.cutoff / self.nodes as Float;
+        // derivative of the force on each bead with respect to each node
+        let mut design = vec![vec![Vector3::<Float>::zeros(); self.nodes]; system.size];
+        for i in 0..system.size {
+            for j in (i + 1)..system.size {
+                if !matches(i, j) {
+                    continue;
+                }
+                let mut separation = system.positions[j] - system.positions[i];
+                system.cell.vector_image(&mut separation);
+                let r = separation.norm();
+                if r >= self.cutoff {
+                    continue;
+                }
+                let dir = separation / r;
+                let position = r / step;
+                let index = position as usize;
+                let fraction = position - index as Float;
+                // the node at the cutoff is fixed at zero
+                let weights = [(index, 1.0 - fraction), (index + 1, fraction)];
+                for &(k, weight) in weights.iter().filter(|(k, _)| *k < self.nodes) {
+                    design[i][k] += weight * dir;
+                    design[j][k] -= weight * dir;
+                }
+            }
+        }
+        for (i, row) in design.iter().enumerate() {
+            if system.species[i] != a && system.species[i] != b {
+                continue;
+            }
+            for c in 0..3 {
+                for (k, dk) in row.iter().enumerate() {
+                    if dk[c] == 0.0 {
+                        continue;
+                    }
+                    self.rhs[k] += dk[c] * forces[i][c];
+                    for (l, dl) in row.iter().enumerate() {
+                        self.normal[(k, l)] += dk[c] * dl[c];
+                    }
+                }
+            }
+        }
+        self.frames += 1;
+    }
+
+    /// Returns the tabulated pair potential which best reproduces the reference forces.
+    pub fn potential(&self) -> TabulatedPair {
+        let step = self.cutoff / self.nodes as Float;
+        let sampled: Vec<bool> = (0..self.nodes).map(|k| self.normal[(k, k)] > 0.0).collect();
+        // small ridge which keeps nodes without samples from making the system singular
+        let scale = (0..self.nodes)
+            .map(|k| self.normal[(k, k)])
+            .fold(0.0, Float::max)
+            .max(1.0);
+        let mut normal = self.normal.clone();
+        for k in 0..self.nodes {
+            normal[(k, k)] += if sampled[k] { 1e-6 * scale } else { scale };
+        }
+        let solution = normal
+            .lu()
+            .solve(&self.rhs)
+            .unwrap_or_else(|| DVector::zeros(self.nodes));
+
+        let mut forces: Vec<Float> = solution.iter().copied().collect();
+        let first = sampled.iter().position(|&s| s).unwrap_or(0);
+        for k in (0..first).rev() {
+            forces[k] = forces[k + 1];
+        }
+        for k in (first + 1)..self.nodes {
+            if !sampled[k] {
+                forces[k] = forces[k - 1];
+            }
+        }
+        forces.push(0.0);
+        let forces = forces.split_off(first);
+        TabulatedPair::from_forces(first as Float * step, step, forces)
+    }
+}
+
+/// Returns the pair potential of mean force `-kT ln g(r)` derived from a radial distribution function.
+///
+/// The potential is shifted to zero at the last distance. At distances where the radial
+/// distribution function vanishes, such as within the repulsive core, the potential continues
+/// linearly from the first two nonzero samples. Boltzmann inversion is exact only at low density
+/// where the potential of mean force reduces to the pair potential.
+///
+/// # Arguments
+///
+/// * `distances` - Uniformly spaced distances of each sample, such as the bins of an [`Rdf`](crate::properties::rdf::Rdf).
+/// * `rdf` - Value of the radial distribution function at each distance.
+/// * `temperature` - Temperature at which the radial distribution function was sampled in Kelvin.
+pub fn boltzmann_inversion(distances: &[Float], rdf: &[Float], temperature: Float) -> TabulatedPair {
+    assert!(distances.len() >= 2 && distances.len() == rdf.len(), "Expected a radial distribution function with at least two samples");
+    let kt = BOLTZMANN * temperature;
+    let step = distances[1] - distances[0];
+    let mut energies: Vec<Option<Float>> = rdf
+        .iter()
+        .map(|&g| if g > 0.0 { Some(-kt * Float::ln(g)) } else { None })
+        .collect();
+
+    // extrapolate into the unsampled core with the slope of the first two samples
+    if let Some(first) = energies.iter().position(|e| e.is_some()) {
+        let value = energies[first].unwrap();
+        let slope = match energies.get(first + 1) {
+            Some(Some(next)) => (next - value) / step,
+            _ => 0.0,
+        };
+        for (k, energy) in energies.iter_mut().enumerate().take(first) {
+            *energy = Some(value + slope * (k as Float - first as Float) * step);
+        }
+    }
+    // fill any interior gaps with the previous sample
+    let mut previous = 0.0;
+    let mut energies: Vec<Float> = energies
+        .into_iter()
+        .map(|energy| {
+            previous = energy.unwrap_or(previous);
+            previous
+        })
+        .collect();
+    let shift = *energies.last().unwrap();
+    energies.iter_mut().for_each(|energy| *energy -= shift);
+    TabulatedPair::from_energies(distances[0], step, energies)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{boltzmann_inversion, BeadMapping, ForceMatching};
+    use crate::internal::consts::BOLTZMANN;
+    use crate::internal::Float;
+    use crate::potentials::pair::PairPotential;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::forces::Forces;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::{Residue, Topology};
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn center_of_mass_mapping() {
+        let (heavy, light) = (Species::new(12.0, 0.0), Species::new(1.0, 0.0));
+        // two diatomic molecules, the first split across the periodic boundary
+        let system = System {
+            size: 4,
+            cell: Cell::cubic(10.0),
+            species: vec![heavy, light, heavy, light],
+            positions: vec![
+                Vector3::new(9.5, 5.0, 5.0),
+                Vector3::new(0.8, 5.0, 5.0),
+                Vector3::new(3.0, 3.0, 3.0),
+                Vector3::new(3.0, 4.3, 3.0),
+            ],
+            velocities: vec![
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(-12.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(0.0, 0.0, 13.0),
+            ],
+            topology: Topology {
+                bonds: vec![[0, 1], [2, 3], [1, 2]],
+                residues: vec![
+                    Residue { name: "CH".to_string(), atoms: vec![0, 1] },
+                    Residue { name: "CH".to_string(), atoms: vec![2, 3] },
+                ],
+                ..Topology::default()
+            },
+        };
+        let bead = Species::new(13.0, 0.0);
+        let mapping = BeadMapping::from_residues(&system.topology, |_| bead);
+        assert_eq!(mapping.beads(), 2);
+        let mapped = mapping.map(&system);
+        assert_eq!(mapped.size, 2);
+        assert!((mapped.positions[0] - Vector3::new(9.6, 5.0, 5.0)).norm() < 1e-5);
+        assert!((mapped.positions[1] - Vector3::new(3.0, 3.1, 3.0)).norm() < 1e-5);
+        assert!(mapped.velocities[0].norm() < 1e-6);
+        assert!((mapped.velocities[1] - Vector3::new(0.0, 12.0, 13.0) / 13.0).norm() < 1e-6);
+        assert_eq!(mapped.topology.bonds, vec![[0, 1]]);
+        assert_eq!(mapped.species[1], bead);
+
+        let forces = vec![Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 2.0, 0.0), Vector3::zeros(), Vector3::new(0.0, 0.0, 3.0)];
+        assert_eq!(mapping.map_forces(&forces), vec![Vector3::new(1.0, 2.0, 0.0), Vector3::new(0.0, 0.0, 3.0)]);
+    }
+
+    #[test]
+    fn force_matching() {
+        // recover a Lennard-Jones pair force from random dense configurations
+        let mut rng = StdRng::seed_from_u64(5);
+        let bead = Species::new(40.0, 0.0);
+        let lj = LennardJones::new(0.238, 3.4);
+        let mut potentials = PotentialsBuilder::new().pair(lj, (bead, bead), 8.0, 1.0).build();
+        let mut matching = ForceMatching::new((bead, bead), 8.0, 80);
+        for _ in 0..10 {
+            // random sequential addition with a minimum separation
+            let cell = Cell::cubic(20.0);
+            let mut positions: Vec<Vector3<Float>> = Vec::new();
+            while positions.len() < 120 {
+                let candidate = Vector3::new(rng.gen_range(0.0, 20.0), rng.gen_range(0.0, 20.0), rng.gen_range(0.0, 20.0));
+                if positions.iter().all(|p| cell.distance(p, &candidate) > 3.2) {
+                    positions.push(candidate);
+                }
+            }
+            let system = System {
+                size: positions.len(),
+                cell,
+                species: vec![bead; positions.len()],
+                velocities: vec![Vector3::zeros(); positions.len()],
+                positions,
+                topology: Topology::default(),
+            };
+            potentials.setup(&system);
+            let forces = Forces.calculate(&system, &potentials);
+            matching.add_frame(&system, &forces);
+        }
+        assert_eq!(matching.frames(), 10);
+
+        let table = matching.potential();
+        assert!(table.distances()[0] >= 3.1 && table.distances()[0] <= 3.3);
+        for r in [3.4, 3.8, 4.5, 6.0, 7.0].iter() {
+            let (fitted, exact) = (table.force(*r), lj.force(*r));
+            assert!((fitted - exact).abs() < 0.01 + 0.05 * exact.abs(), "{} {} {}", r, fitted, exact);
+        }
+        // the energy is the integral of the force from the cutoff
+        assert!((table.energy(3.8) - (lj.energy(3.8) - lj.energy(8.0))).abs() < 0.02);
+        // the core is repulsive below the sampled separations
+        assert!(table.energy(2.5) > table.energy(3.2));
+    }
+
+    #[test]
+    fn dilute_boltzmann_inversion() {
+        // radial distribution function of a dilute Lennard-Jones gas
+        let lj = LennardJones::new(0.238, 3.4);
+        let temperature = 150.0;
+        let distances: Vec<Float> = (0..120).map(|i| 0.025 + 0.1 * i as Float).collect();
+        let rdf: Vec<Float> = distances
+            .iter()
+            .map(|&r| {
+                let g = Float::exp(-lj.energy(r) / (BOLTZMANN * temperature));
+                if g < 1e-12 {
+                    0.0
+                } else {
+                    g
+                }
+            })
+            .collect();
+        let table = boltzmann_inversion(&distances, &rdf, temperature);
+        let shift = lj.energy(*distances.last().unwrap());
+        for r in [3.4, 3.8, 5.0, 8.0].iter() {
+            assert!((table.energy(*r) - lj.energy(*r) + shift).abs() < 0.01, "{} {}", r, table.energy(*r));
+        }
+        // linear continuation into the core
+        assert!(table.energy(1.0) > table.energy(2.5));
+        assert!(table.force(1.0) < 0.0);
+    }
+}
@@ -0,0 +1,628 @@
+//! Collective variables and the biases which act on them.
+
+use nalgebra::{Complex, Matrix3, Vector3};
+use rand_distr::{Distribution, Normal};
+
+use crate::internal::consts::{BOLTZMANN, PI};
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::{IntrinsicProperty, Property};
+use crate::random::shared_rng;
+use crate::system::System;
+
+/// Shared behavior for low dimensional functions of the atomic positions.
+pub trait CollectiveVariable: Send + Sync {
+    /// Returns the value of the collective variable and its gradient with respect to the position of each atom.
+    fn evaluate(&self, system: &System) -> (Float, Vec<Vector3<Float>>);
+
+    /// Returns the value and gradient of the collective variable along with the sum of the outer
+    /// product of each position and its gradient, which is the response of the collective variable to
+    /// a homogeneous deformation of the cell.
+    ///
+    /// Defaults to the outer products of the absolute positions, which holds for collective variables
+    /// that do not depend on periodic images. Collective variables of separations should override it.
+    fn evaluate_with_virial(&self, system: &System) -> (Float, Vec<Vector3<Float>>, Matrix3<Float>) {
+        let (value, gradient) = self.evaluate(system);
+        let virial = system
+            .positions
+            .iter()
+            .zip(gradient.iter())
+            .fold(Matrix3::zeros(), |virial, (pos, grad)| virial + pos * grad.transpose());
+        (value, gradient, virial)
+    }
+}
+
+/// Energy, forces, and virial of a bias.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BiasEvaluation {
+    /// Bias energy.
+    pub energy: Float,
+    /// Force exerted by the bias on each atom.
+    pub forces: Vec<Vector3<Float>>,
+    /// Virial tensor of the forces.
+    pub virial: Matrix3<Float>,
+}
+
+impl BiasEvaluation {
+    /// Returns a [`BiasEvaluation`] with no energy, forces, or virial.
+    pub fn zeros(size: usize) -> BiasEvaluation {
+        BiasEvaluation {
+            energy: 0.0,
+            forces: vec![Vector3::zeros(); size],
+            virial: Matrix3::zeros(),
+        }
+    }
+
+    // Returns the evaluation of a harmonic spring of stiffness kappa stretched by delta.
+    fn harmonic(kappa: Float, delta: Float, gradient: &[Vector3<Float>], virial: &Matrix3<Float>) -> BiasEvaluation {
+        BiasEvaluation {
+            energy: 0.5 * kappa * delta.powi(2),
+            forces: gradient.iter().map(|grad| -kappa * delta * grad).collect(),
+            virial: -kappa * delta * virial,
+        }
+    }
+}
+
+/// Shared behavior for external potentials which act on collective variables.
+pub trait Bias: Send + Sync {
+    /// Returns the bias energy, the force it exerts on each atom, and the virial of those forces.
+    fn evaluate(&self, system: &System) -> BiasEvaluation;
+
+    /// Returns the current value of the biased collective variable.
+    fn colvar(&self, system: &System) -> Float;
+
+    /// Advances any internal state of the bias after an integration step.
+    fn update(&mut self, _: &System) {}
+}
+
+/// Harmonic restraint on a collective variable.
+///
+/// Applies the energy `0.5 * kappa * (s - center)^2` where `s` is the value of the collective
+/// variable. Combined with a [`SteinhardtOrder`] parameter this is the interface pinning
+/// method where the average deviation of the order parameter from the center measures the
+/// chemical potential difference between the solid and liquid phases.
+///
+/// # References
+///
+/// [1] Pedersen, Ulf R. "Direct calculation of the solid-liquid Gibbs free energy difference in a single equilibrium simulation." The Journal of chemical physics 139.10 (2013): 104102.
+pub struct HarmonicBias<C: CollectiveVariable> {
+    colvar: C,
+    kappa: Float,
+    center: Float,
+}
+
+impl<C: CollectiveVariable> HarmonicBias<C> {
+    /// Returns a new [`HarmonicBias`].
+    ///
+    /// # Arguments
+    ///
+    /// * `colvar` - Collective variable to restrain.
+    /// * `kappa` - Spring constant of the restraint.
+    /// * `center` - Value of the collective variable at the minimum of the restraint.
+    pub fn new(colvar: C, kappa: Float, center: Float) -> HarmonicBias<C> {
+        HarmonicBias {
+            colvar,
+            kappa,
+            center,
+        }
+    }
+
+    /// Returns the value of the collective variable at the minimum of the restraint.
+    pub fn center(&self) -> Float {
+        self.center
+    }
+
+    /// Sets the value of the collective variable at the minimum of the restraint.
+    pub fn set_center(&mut self, center: Float) {
+        self.center = center;
+    }
+}
+
+impl<C: CollectiveVariable> Bias for HarmonicBias<C> {
+    fn evaluate(&self, system: &System) -> BiasEvaluation {
+        let (value, gradient, virial) = self.colvar.evaluate_with_virial(system);
+        BiasEvaluation::harmonic(self.kappa, value - self.center, &gradient, &virial)
+    }
+
+    fn colvar(&self, system: &System) -> Float {
+        self.colvar.evaluate(system).0
+    }
+}
+
+/// Temperature accelerated molecular dynamics (TAMD) coupling of a collective variable.
+///
+/// The collective variable `s` is tethered by the energy `0.5 * kappa * (s - z)^2` to a heavy
+/// fictitious particle at `z` which follows Langevin dynamics at its own, typically much
+/// higher, temperature. While the atoms stay at the physical temperature the fictitious
+/// particle drags the collective variable across free energy barriers, and for a large mass and
+/// spring constant `z` samples the free energy surface at the fictitious temperature.
+///
+/// The fictitious particle starts at the value of the collective variable and advances one
+/// timestep on each call to [`update`](Bias::update), which occurs once per step of a
+/// [`Simulation`](crate::simulation::Simulation).
+///
+/// # References
+///
+/// [1] Maragliano, Luca, and Eric Vanden-Eijnden. "A temperature accelerated method for sampling free energy and determining reaction pathways in rare events simulations." Chemical physics letters 426.1-3 (2006): 168-175.
+pub struct TemperatureAccelerated<C: CollectiveVariable> {
+    colvar: C,
+    kappa: Float,
+    mass: Float,
+    friction: Float,
+    timestep: Float,
+    distr: Normal<Float>,
+    position: Option<Float>,
+    velocity: Float,
+}
+
+impl<C: CollectiveVariable> TemperatureAccelerated<C> {
+    /// Returns a new [`TemperatureAccelerated`] coupling.
+    ///
+    /// # Arguments
+    ///
+    /// * `colvar` - Collective variable to accelerate.
+    /// * `kappa` - Spring constant between the collective variable and the fictitious particle.
+    /// * `mass` - Mass of the fictitious particle.
+    /// * `temperature` - Temperature of the fictitious particle.
+    /// * `friction` - Langevin friction coefficient of the fictitious particle in inverse femtoseconds.
+    /// * `timestep` - Timestep of the simulation.
+    pub fn new(
+        colvar: C,
+        kappa: Float,
+        mass: Float,
+        temperature: Float,
+        friction: Float,
+        timestep: Float,
+    ) -> TemperatureAccelerated<C> {
+        // stationary velocity distribution of the Ornstein-Uhlenbeck step
+        let decay = Float::exp(-friction * timestep);
+        let sigma = Float::sqrt((1.0 - decay.powi(2)) * BOLTZMANN * temperature / mass);
+        TemperatureAccelerated {
+            colvar,
+            kappa,
+            mass,
+            friction,
+            timestep,
+            distr: Normal::new(0.0, sigma).unwrap(),
+            position: None,
+            velocity: 0.0,
+        }
+    }
+
+    /// Returns the position of the fictitious particle, if it has been initialized.
+    pub fn position(&self) -> Option<Float> {
+        self.position
+    }
+}
+
+impl<C: CollectiveVariable> Bias for TemperatureAccelerated<C> {
+    fn evaluate(&self, system: &System) -> BiasEvaluation {
+        let (value, gradient, virial) = self.colvar.evaluate_with_virial(system);
+        let delta = value - self.position.unwrap_or(value);
+        BiasEvaluation::harmonic(self.kappa, delta, &gradient, &virial)
+    }
+
+    fn colvar(&self, system: &System) -> Float {
+        self.colvar.evaluate(system).0
+    }
+
+    fn update(&mut self, system: &System) {
+        let value = self.colvar(system);
+        let position = *self.position.get_or_insert(value);
+        // velocity Verlet half kicks around an exact Ornstein-Uhlenbeck velocity update
+        let dt = self.timestep;
+        self.velocity += 0.5 * dt * self.kappa * (value - position) / self.mass;
+        let decay = Float::exp(-self.friction * dt);
+        self.velocity = decay * self.velocity + self.distr.sample(&mut shared_rng());
+        let position = position + dt * self.velocity;
+        self.velocity += 0.5 * dt * self.kappa * (value - position) / self.mass;
+        self.position = Some(position);
+    }
+}
+
+/// Current value of the collective variable acted on by each bias.
+#[derive(Clone, Copy, Debug)]
+pub struct Colvars;
+
+impl Property for Colvars {
+    type Res = Vec<Float>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        potentials
+            .biases
+            .iter()
+            .map(|bias| bias.colvar(system))
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "colvars".to_string()
+    }
+}
+
+/// Global Steinhardt bond orientational order parameter.
+///
+/// The spherical harmonics of degree `l` are averaged over every bond in the system, weighted
+/// by a switching function which decays smoothly from one at the inner cutoff to zero at the
+/// outer cutoff. The parameter is the rotationally invariant norm of the average and is large
+/// for crystals and small for liquids. Bonds are counted once so the degree must be even.
+///
+/// All pairs of atoms are visited on each evaluation.
+///
+/// # References
+///
+/// [1] Steinhardt, Paul J., David R. Nelson, and Marco Ronchetti. "Bond-orientational order in liquids and glasses." Physical Review B 28.2 (1983): 784.
+#[derive(Clone, Debug)]
+pub struct SteinhardtOrder {
+    degree: usize,
+    inner: Float,
+    outer: Float,
+    // coefficients of the m-th derivative of the Legendre polynomial for m = 0..=degree+1
+    derivatives: Vec<Vec<Float>>,
+    // weight of each order m in the rotational invariant
+    weights: Vec<Float>,
+}
+
+impl SteinhardtOrder {
+    /// Returns a new [`SteinhardtOrder`] parameter.
+    ///
+    /// # Arguments
+    ///
+    /// * `degree` - Even degree `l` of the spherical harmonics.
+    /// * `inner` - Distance below which bonds have full weight.
+    /// * `outer` - Distance beyond which bonds are ignored.
+    pub fn new(degree: usize, inner: Float, outer: Float) -> SteinhardtOrder {
+        assert!(degree.is_multiple_of(2), "Steinhardt order parameters require an even degree.");
+        // Legendre polynomial coefficients from Bonnet's recursion
+        let mut previous = vec![1.0f64];
+        let mut current = vec![0.0f64, 1.0];
+        let legendre = match degree {
+            0 => previous.clone(),
+            _ => {
+                for n in 1..degree {
+                    let mut next = vec![0.0; n + 2];
+                    for (k, c) in current.iter().enumerate() {
+                        next[k + 1] += (2 * n + 1) as f64 * c;
+                    }
+                    for (k, c) in previous.iter().enumerate() {
+                        next[k] -= n as f64 * c;
+                    }
+                    next.iter_mut().for_each(|c| *c /= (n + 1) as f64);
+                    previous = std::mem::replace(&mut current, next);
+                }
+                current
+            }
+        };
+        let mut derivatives = vec![legendre];
+        for m in 0..=degree {
+            let derivative: Vec<f64> = derivatives[m]
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(k, c)| k as f64 * c)
+                .collect();
+            derivatives.push(derivative);
+        }
+        // (l - m)! / (l + m)! counted twice for m > 0 to include negative orders
+        let weights = (0..=degree)
+            .map(|m| {
+                let ratio: f64 = ((degree - m + 1)..=(degree + m)).map(|k| 1.0 / k as f64).product();
+                (if m == 0 { ratio } else { 2.0 * ratio }) as Float
+            })
+            .collect();
+        SteinhardtOrder {
+            degree,
+            inner,
+            outer,
+            derivatives: derivatives
+                .iter()
+                .map(|coefficients| coefficients.iter().map(|c| *c as Float).collect())
+                .collect(),
+            weights,
+        }
+    }
+
+    // Returns the switching function and its derivative at distance r.
+    fn switch(&self, r: Float) -> (Float, Float) {
+        if r <= self.inner {
+            (1.0, 0.0)
+        } else {
+            let width = self.outer - self.inner;
+            let phase = PI * (r - self.inner) / width;
+            (0.5 * (1.0 + phase.cos()), -0.5 * PI * phase.sin() / width)
+        }
+    }
+}
+
+// Evaluates a polynomial with ascending coefficients.
+fn polynomial(coefficients: &[Float], x: Float) -> Float {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+// Bond between atoms i and j with separation vector d, distance r, and switching function f.
+struct Bond {
+    i: usize,
+    j: usize,
+    d: Vector3<Float>,
+    r: Float,
+    f: Float,
+    df: Float,
+}
+
+impl CollectiveVariable for SteinhardtOrder {
+    fn evaluate(&self, system: &System) -> (Float, Vec<Vector3<Float>>) {
+        let (value, gradient, _) = self.evaluate_with_virial(system);
+        (value, gradient)
+    }
+
+    fn evaluate_with_virial(&self, system: &System) -> (Float, Vec<Vector3<Float>>, Matrix3<Float>) {
+        let l = self.degree;
+        let zero = Complex::new(0.0, 0.0);
+        let mut gradient = vec![Vector3::zeros(); system.size];
+        let mut virial = Matrix3::zeros();
+
+        // collect bonds within the outer cutoff
+        let mut bonds = Vec::new();
+        for i in 0..system.size {
+            for j in (i + 1)..system.size {
+                let mut d = system.positions[j] - system.positions[i];
+                system.cell.vector_image(&mut d);
+                let r = d.norm();
+                if r < self.outer && r > 0.0 {
+                    let (f, df) = self.switch(r);
+                    bonds.push(Bond { i, j, d, r, f, df });
+                }
+            }
+        }
+
+        // harmonics are polynomials of u = z / r and w = (x + iy) / r for non-negative orders
+        let harmonics = |bond: &Bond, m: usize| -> (Float, Float, Complex<Float>) {
+            let u = bond.d[2] / bond.r;
+            let w = Complex::new(bond.d[0], bond.d[1]) / bond.r;
+            let p = polynomial(&self.derivatives[m], u);
+            let dp = polynomial(&self.derivatives[m + 1], u);
+            (p, dp, w)
+        };
+
+        let mut sums = vec![zero; l + 1];
+        let mut total = 0.0;
+        for bond in &bonds {
+            for (m, sum) in sums.iter_mut().enumerate() {
+                let (p, _, w) = harmonics(bond, m);
+                *sum += w.powi(m as i32) * (bond.f * p);
+            }
+            total += bond.f;
+        }
+        if total == 0.0 {
+            return (0.0, gradient, virial);
+        }
+        let norm: Float = sums
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(sum, weight)| weight * sum.norm_sqr())
+            .sum();
+        let root = norm.sqrt();
+        let value = root / total;
+        if root == 0.0 {
+            return (value, gradient, virial);
+        }
+
+        for bond in &bonds {
+            let unit = bond.d / bond.r;
+            let u = unit[2];
+            let w = Complex::new(unit[0], unit[1]);
+            // gradients of u and w with respect to the bond vector
+            let du = (Vector3::z() - u * unit) / bond.r;
+            let dw = [
+                (Complex::new(1.0, 0.0) - w * unit[0]) / bond.r,
+                (Complex::new(0.0, 1.0) - w * unit[1]) / bond.r,
+                (-w * unit[2]) / bond.r,
+            ];
+            let mut dnorm = Vector3::zeros();
+            for (m, (sum, weight)) in sums.iter().zip(self.weights.iter()).enumerate() {
+                let (p, dp, w) = harmonics(bond, m);
+                let wm = w.powi(m as i32);
+                let wm1 = if m == 0 { zero } else { w.powi(m as i32 - 1) * m as Float };
+                for k in 0..3 {
+                    let term = wm * (bond.df * unit[k] * p + bond.f * dp * du[k])
+                        + wm1 * dw[k] * (bond.f * p);
+                    dnorm[k] += weight * 2.0 * (sum.conj() * term).re;
+                }
+            }
+            let grad = dnorm / (2.0 * root * total) - root / total.powi(2) * bond.df * unit;
+            gradient[bond.j] += grad;
+            gradient[bond.i] -= grad;
+            virial += bond.d * grad.transpose();
+        }
+        (value, gradient, virial)
+    }
+}
+
+impl IntrinsicProperty for SteinhardtOrder {
+    type Res = Float;
+
+    fn calculate_intrinsic(&self, system: &System) -> Self::Res {
+        self.evaluate(system).0
+    }
+
+    fn name(&self) -> String {
+        format!("steinhardt_q{}", self.degree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Bias, CollectiveVariable, HarmonicBias, SteinhardtOrder, TemperatureAccelerated};
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::pressure::Virial;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::{Matrix3, Vector3};
+
+    // face centered cubic crystal with 4x4x4 conventional cells
+    fn fcc() -> System {
+        let a = 4.0;
+        let basis = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.5, 0.5, 0.0),
+            Vector3::new(0.5, 0.0, 0.5),
+            Vector3::new(0.0, 0.5, 0.5),
+        ];
+        let mut positions = Vec::new();
+        for i in 0..4 {
+            for j in 0..4 {
+                for k in 0..4 {
+                    let shift = Vector3::new(i as Float, j as Float, k as Float);
+                    positions.extend(basis.iter().map(|b| (b + shift) * a));
+                }
+            }
+        }
+        let size = positions.len();
+        System {
+            size,
+            cell: Cell::cubic(4.0 * a),
+            species: vec![Species::from_element(Element::Ar); size],
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn fcc_q6() {
+        // nearest neighbors sit at a / sqrt(2) ~ 2.83 while second neighbors sit at 4.0
+        let q6 = SteinhardtOrder::new(6, 3.0, 3.5);
+        let (value, gradient) = q6.evaluate(&fcc());
+        assert!((value - 0.5745).abs() < 1e-3);
+        // a perfect crystal is a stationary point of the order parameter
+        assert!(gradient.iter().all(|g| g.norm() < 1e-4));
+        let q4 = SteinhardtOrder::new(4, 3.0, 3.5);
+        assert!((q4.evaluate(&fcc()).0 - 0.1909).abs() < 1e-3);
+    }
+
+    // crystal perturbed so that bonds cross the switching region between 2.8 and 3.6
+    fn perturbed() -> System {
+        let mut system = fcc();
+        for (n, pos) in system.positions.iter_mut().enumerate() {
+            let phase = n as Float;
+            *pos += Vector3::new(phase.sin(), phase.cos(), (2.0 * phase).sin()) * 0.3;
+        }
+        system
+    }
+
+    #[test]
+    fn gradient_matches_finite_difference() {
+        let system = perturbed();
+        let q6 = SteinhardtOrder::new(6, 2.8, 3.6);
+        let (_, gradient) = q6.evaluate(&system);
+        let h = 1e-2;
+        for &(atom, k) in [(0, 0), (5, 1), (17, 2)].iter() {
+            let mut forward = system.clone();
+            forward.positions[atom][k] += h;
+            let mut backward = system.clone();
+            backward.positions[atom][k] -= h;
+            let numeric = (q6.evaluate(&forward).0 - q6.evaluate(&backward).0) / (2.0 * h);
+            assert!((numeric - gradient[atom][k]).abs() < 2e-3 * (1.0 + numeric.abs()));
+        }
+    }
+
+    #[test]
+    fn harmonic_bias() {
+        let system = fcc();
+        let bias = HarmonicBias::new(SteinhardtOrder::new(6, 3.0, 3.5), 100.0, 0.4);
+        let evaluation = bias.evaluate(&system);
+        let q = bias.colvar(&system);
+        assert!((evaluation.energy - 50.0 * (q - 0.4).powi(2)).abs() < 1e-3);
+        assert_eq!(evaluation.forces.len(), system.size);
+
+        // the forces are the negative gradient of the energy
+        let system = perturbed();
+        let bias = HarmonicBias::new(SteinhardtOrder::new(6, 2.8, 3.6), 100.0, 0.4);
+        let forces = bias.evaluate(&system).forces;
+        let h = 1e-2;
+        for &(atom, k) in [(0, 0), (5, 1), (17, 2)].iter() {
+            let mut forward = system.clone();
+            forward.positions[atom][k] += h;
+            let mut backward = system.clone();
+            backward.positions[atom][k] -= h;
+            let numeric = -(bias.evaluate(&forward).energy - bias.evaluate(&backward).energy) / (2.0 * h);
+            assert!((numeric - forces[atom][k]).abs() < 2e-3 * (1.0 + numeric.abs()));
+        }
+    }
+
+    #[test]
+    fn harmonic_bias_virial() {
+        let system = perturbed();
+        let bias = HarmonicBias::new(SteinhardtOrder::new(6, 2.8, 3.6), 100.0, 0.4);
+        let virial = bias.evaluate(&system).virial;
+
+        // each component is the negative response of the energy to a deformation of the cell
+        let deform = |a: usize, b: usize, strain: Float| {
+            let mut gradient = Matrix3::identity();
+            gradient[(a, b)] += strain;
+            let mut deformed = system.clone();
+            deformed.cell = Cell::from_matrix(gradient * system.cell.matrix());
+            deformed.positions.iter_mut().for_each(|pos| *pos = gradient * *pos);
+            deformed
+        };
+        let h = 1e-3;
+        for &(a, b) in [(0, 0), (1, 1), (2, 2), (0, 1), (2, 0)].iter() {
+            let energy = |strain| bias.evaluate(&deform(a, b, strain)).energy;
+            let numeric = -(energy(h) - energy(-h)) / (2.0 * h);
+            assert!((numeric - virial[(b, a)]).abs() < 1e-2 * (1.0 + numeric.abs()));
+        }
+
+        // the virial of the system includes the bias
+        let potentials = PotentialsBuilder::new().bias(bias).build();
+        assert!((Virial.calculate(&system, &potentials) - virial).norm() < 1e-4);
+    }
+
+    // x coordinate of the first atom
+    struct FirstX;
+
+    impl CollectiveVariable for FirstX {
+        fn evaluate(&self, system: &System) -> (Float, Vec<Vector3<Float>>) {
+            let mut gradient = vec![Vector3::zeros(); system.size];
+            gradient[0] = Vector3::x();
+            (system.positions[0][0], gradient)
+        }
+    }
+
+    #[test]
+    fn temperature_accelerated() {
+        let mut system = fcc();
+        system.positions[0][0] = 1.0;
+        let (kappa, temperature) = (10.0, 1000.0);
+        let mut tamd = TemperatureAccelerated::new(FirstX, kappa, 100.0, temperature, 0.1, 1.0);
+
+        // the bias vanishes until the fictitious particle is initialized
+        assert_eq!(tamd.evaluate(&system).energy, 0.0);
+
+        // with the atom held fixed the fictitious particle samples a Gaussian about it
+        let steps = 50000;
+        let mut samples = Vec::with_capacity(steps);
+        for _ in 0..steps {
+            tamd.update(&system);
+            samples.push(tamd.position().unwrap());
+        }
+        let mean = samples.iter().sum::<Float>() / steps as Float;
+        let variance = samples.iter().map(|z| (z - mean).powi(2)).sum::<Float>() / steps as Float;
+        let expected = 0.001985875 * temperature / kappa;
+        assert!((mean - 1.0).abs() < 0.05);
+        assert!((variance - expected).abs() < 0.2 * expected);
+
+        // the atom is pulled toward the fictitious particle
+        let evaluation = tamd.evaluate(&system);
+        let delta = 1.0 - tamd.position().unwrap();
+        assert!((evaluation.energy - 0.5 * kappa * delta.powi(2)).abs() < 1e-4);
+        assert!((evaluation.forces[0][0] + kappa * delta).abs() < 1e-4);
+        assert_eq!(evaluation.forces[1], Vector3::zeros());
+        // the virial of the absolute coordinate is the outer product of the position and force
+        assert!((evaluation.virial[(0, 0)] + kappa * delta).abs() < 1e-4);
+    }
+}
@@ -1,5 +1,9 @@
 //! User defined configuration options.
 
+use std::path::{Path, PathBuf};
+
+use crate::convergence::ConvergenceMonitor;
+
 #[cfg(feature = "hdf5-output")]
 use crate::outputs::hdf5::Hdf5OutputGroup;
 use crate::outputs::raw::RawOutputGroup;
@@ -9,6 +13,9 @@ pub struct Configuration {
     raw_output_groups: Vec<RawOutputGroup>,
     #[cfg(feature = "hdf5-output")]
     hdf5_output_groups: Vec<Hdf5OutputGroup>,
+    checkpoint: Option<(PathBuf, usize)>,
+    convergence: Option<ConvergenceMonitor>,
+    seed: Option<u64>,
 }
 
 impl Configuration {
@@ -22,6 +29,28 @@ impl Configuration {
     pub fn hdf5_output_groups(&mut self) -> impl Iterator<Item = &mut Hdf5OutputGroup> {
         self.hdf5_output_groups.iter_mut()
     }
+
+    /// Returns the path and interval of periodic checkpoints if they are enabled.
+    pub fn checkpoint(&self) -> Option<(&Path, usize)> {
+        self.checkpoint
+            .as_ref()
+            .map(|(path, interval)| (path.as_path(), *interval))
+    }
+
+    /// Returns the convergence monitor if one is set.
+    pub fn convergence(&self) -> Option<&ConvergenceMonitor> {
+        self.convergence.as_ref()
+    }
+
+    /// Returns a mutable reference to the convergence monitor if one is set.
+    pub fn convergence_mut(&mut self) -> Option<&mut ConvergenceMonitor> {
+        self.convergence.as_mut()
+    }
+
+    /// Returns the seed of the random number generator if one is set.
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
 }
 
 /// Constructor for the [`Configuration`](velvet_core::config::Configuration) type.
@@ -29,6 +58,9 @@ pub struct ConfigurationBuilder {
     raw_output_groups: Vec<RawOutputGroup>,
     #[cfg(feature = "hdf5-output")]
     hdf5_output_groups: Vec<Hdf5OutputGroup>,
+    checkpoint: Option<(PathBuf, usize)>,
+    convergence: Option<ConvergenceMonitor>,
+    seed: Option<u64>,
 }
 
 impl ConfigurationBuilder {
@@ -38,6 +70,9 @@ impl ConfigurationBuilder {
             raw_output_groups: Vec::new(),
             #[cfg(feature = "hdf5-output")]
             hdf5_output_groups: Vec::new(),
+            checkpoint: None,
+            convergence: None,
+            seed: None,
         }
     }
 
@@ -54,12 +89,44 @@ impl ConfigurationBuilder {
         self
     }
 
+    /// Writes a checkpoint to `path` every `interval` steps, replacing the previous checkpoint.
+    pub fn checkpoint<P: Into<PathBuf>>(mut self, path: P, interval: usize) -> ConfigurationBuilder {
+        self.checkpoint = Some((path.into(), interval));
+        self
+    }
+
+    /// Ends each run as soon as every property of `monitor` has converged.
+    pub fn convergence(mut self, monitor: ConvergenceMonitor) -> ConfigurationBuilder {
+        self.convergence = Some(monitor);
+        self
+    }
+
+    /// Seeds the random number generator at the start of each run for reproducible trajectories.
+    ///
+    /// The generator is reseeded from `seed` and the number of steps already completed, so two
+    /// simulations with the same seed draw the same random numbers on every run while
+    /// consecutive runs of one simulation draw different ones. See
+    /// [`seed_rng`](crate::random::seed_rng) for the limits of reproducibility across threads.
+    pub fn seed(mut self, seed: u64) -> ConfigurationBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
     /// Returns an initialized [`Configuration`].
     pub fn build(self) -> Configuration {
         Configuration {
             raw_output_groups: self.raw_output_groups,
             #[cfg(feature = "hdf5-output")]
             hdf5_output_groups: self.hdf5_output_groups,
+            checkpoint: self.checkpoint,
+            convergence: self.convergence,
+            seed: self.seed,
         }
     }
 }
+
+impl Default for ConfigurationBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
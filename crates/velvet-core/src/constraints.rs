@@ -0,0 +1,499 @@
+//! Holonomic constraints which hold bond lengths fixed during integration.
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::system::elements::Element;
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Fixed distance between a pair of atoms.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DistanceConstraint {
+    /// Index of the first atom.
+    pub i: usize,
+    /// Index of the second atom.
+    pub j: usize,
+    /// Constrained distance between the atoms.
+    pub length: Float,
+}
+
+/// Bond length constraints enforced with the SHAKE and RATTLE algorithms.
+///
+/// Constraints are taken from the bonds in the system's [`Topology`](crate::system::topology::Topology)
+/// when the integrator is set up, with the length of each bond fixed at its current value unless
+/// a length is given for the species of its atoms. Angles can be made rigid as well by
+/// constraining the distance between the outer atoms of each angle whose bonds are both
+/// constrained, as for rigid water models.
+///
+/// Integrators apply SHAKE after each position update, moving the atoms along their previous
+/// bond vectors until every length is within the relative tolerance, and velocity Verlet applies
+/// RATTLE after each velocity update to remove the velocity components along each bond.
+/// Constraining the bonds to hydrogen removes the fastest vibrations from biomolecular systems
+/// and permits timesteps of around 2 fs.
+///
+/// Each constrained distance removes one degree of freedom from the
+/// [`Temperature`](crate::properties::temperature::Temperature) of the system once a
+/// [`MolecularDynamics`](crate::propagators::MolecularDynamics) propagator records them in the
+/// topology, and the constraint forces of every step contribute to the
+/// [`Virial`](crate::properties::pressure::Virial). A simulation stops with an error if SHAKE or
+/// RATTLE fails to converge.
+///
+/// # References
+///
+/// [1] Ryckaert, Jean-Paul, Giovanni Ciccotti, and Herman JC Berendsen. "Numerical integration of the cartesian equations of motion of a system with constraints: molecular dynamics of n-alkanes." Journal of computational physics 23.3 (1977): 327-341.
+///
+/// [2] Andersen, Hans C. "Rattle: A "velocity" version of the shake algorithm for molecular dynamics calculations." Journal of computational Physics 52.1 (1983): 24-34.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let oxygen = Species::from_element(Element::O);
+/// let hydrogen = Species::from_element(Element::H);
+/// let constraints = Constraints::new()
+///     .hydrogen_bonds()
+///     .angles()
+///     .length((oxygen, hydrogen), 0.9572);
+/// let integrator = VelocityVerlet::new(2.0).with_constraints(constraints);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Constraints {
+    hydrogen_only: bool,
+    angles: bool,
+    lengths: Vec<(Species, Species, Float)>,
+    tolerance: Float,
+    max_iterations: usize,
+    constraints: Vec<DistanceConstraint>,
+}
+
+impl Constraints {
+    /// Returns new [`Constraints`] on every bond in the system.
+    pub fn new() -> Constraints {
+        Constraints {
+            hydrogen_only: false,
+            angles: false,
+            lengths: Vec::new(),
+            tolerance: 1e-5,
+            max_iterations: 500,
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Only constrains bonds to hydrogen atoms.
+    pub fn hydrogen_bonds(mut self) -> Constraints {
+        self.hydrogen_only = true;
+        self
+    }
+
+    /// Also constrains each angle whose bonds are both constrained.
+    pub fn angles(mut self) -> Constraints {
+        self.angles = true;
+        self
+    }
+
+    /// Sets the length of bonds between atoms of species `a` and `b` instead of measuring it.
+    pub fn length(mut self, (a, b): (Species, Species), length: Float) -> Constraints {
+        self.lengths.push((a, b, length));
+        self
+    }
+
+    /// Sets the relative tolerance of each constrained distance.
+    pub fn tolerance(mut self, tolerance: Float) -> Constraints {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets the number of iterations after which SHAKE and RATTLE give up.
+    pub fn max_iterations(mut self, max_iterations: usize) -> Constraints {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// Returns the distance constraints found during setup.
+    pub fn constraints(&self) -> &[DistanceConstraint] {
+        &self.constraints
+    }
+
+    /// Finds the constrained distances from the topology of the system.
+    pub fn setup(&mut self, system: &System) {
+        let is_hydrogen = |i: usize| system.species[i].element() == Some(Element::H);
+        let length_of = |i: usize, j: usize| {
+            let (a, b) = (system.species[i], system.species[j]);
+            self.lengths
+                .iter()
+                .find(|(x, y, _)| (*x == a && *y == b) || (*x == b && *y == a))
+                .map(|(_, _, length)| *length)
+                .unwrap_or_else(|| system.cell.distance(&system.positions[i], &system.positions[j]))
+        };
+        let bonds: Vec<[usize; 2]> = system
+            .topology
+            .bonds
+            .iter()
+            .filter(|[i, j]| !self.hydrogen_only || is_hydrogen(*i) || is_hydrogen(*j))
+            .copied()
+            .collect();
+        let mut constraints: Vec<DistanceConstraint> = bonds
+            .iter()
+            .map(|&[i, j]| DistanceConstraint { i, j, length: length_of(i, j) })
+            .collect();
+        if self.angles {
+            let bonded = |i: usize, j: usize| bonds.iter().any(|b| *b == [i, j] || *b == [j, i]);
+            for &[i, j, k] in &system.topology.angles {
+                if bonded(i, j) && bonded(j, k) {
+                    // the law of cosines gives the distance between the outer atoms
+                    let (a, b) = (length_of(i, j), length_of(j, k));
+                    let theta = system.cell.angle(&system.positions[i], &system.positions[j], &system.positions[k]);
+                    let length = Float::sqrt(a * a + b * b - 2.0 * a * b * theta.cos());
+                    constraints.push(DistanceConstraint { i, j: k, length });
+                }
+            }
+        }
+        self.constraints = constraints;
+    }
+
+    /// Returns the pairs of atoms whose distance is constrained.
+    pub fn pairs(&self) -> Vec<[usize; 2]> {
+        self.constraints.iter().map(|c| [c.i, c.j]).collect()
+    }
+
+    /// Moves the atoms along their bond vectors in `reference` until every distance is satisfied
+    /// and returns the virial of the constraint forces.
+    ///
+    /// Velocities are corrected by the displacement of each atom divided by `timestep`, so the
+    /// positions should have just been advanced from `reference` over one timestep. Each
+    /// displacement is taken as the result of a constant force over the step as in the position
+    /// update of velocity Verlet, `2 m dr / dt^2`, which gives the virial about the bond vectors
+    /// in `reference`. Returns an error if the distances are not satisfied within the iteration
+    /// limit.
+    pub fn shake(
+        &self,
+        system: &mut System,
+        reference: &[Vector3<Float>],
+        timestep: Float,
+    ) -> Result<Matrix3<Float>, VelvetError> {
+        let bonds: Vec<Vector3<Float>> = self
+            .constraints
+            .iter()
+            .map(|c| {
+                let mut bond = reference[c.j] - reference[c.i];
+                system.cell.vector_image(&mut bond);
+                bond
+            })
+            .collect();
+        // total multiplier of each constraint force along its bond
+        let mut multipliers: Vec<Float> = vec![0.0; bonds.len()];
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            for ((c, bond), multiplier) in self.constraints.iter().zip(bonds.iter()).zip(multipliers.iter_mut()) {
+                let mut separation = system.positions[c.j] - system.positions[c.i];
+                system.cell.vector_image(&mut separation);
+                let target = c.length * c.length;
+                let difference = target - separation.norm_squared();
+                if difference.abs() <= 2.0 * self.tolerance * target {
+                    continue;
+                }
+                converged = false;
+                let (wi, wj) = (1.0 / system.species[c.i].mass(), 1.0 / system.species[c.j].mass());
+                let g = difference / (2.0 * (wi + wj) * separation.dot(bond));
+                system.positions[c.i] -= bond * (g * wi);
+                system.positions[c.j] += bond * (g * wj);
+                system.velocities[c.i] -= bond * (g * wi / timestep);
+                system.velocities[c.j] += bond * (g * wj / timestep);
+                *multiplier += g;
+            }
+            if converged {
+                let scale = 2.0 / (timestep * timestep);
+                let virial = bonds
+                    .iter()
+                    .zip(multipliers.iter())
+                    .fold(Matrix3::zeros(), |virial, (bond, g)| virial + bond * bond.transpose() * (g * scale));
+                return Ok(virial);
+            }
+        }
+        Err(VelvetError::Convergence {
+            algorithm: "SHAKE",
+            iterations: self.max_iterations,
+        })
+    }
+
+    /// Removes the component of the relative velocity along each constrained distance.
+    ///
+    /// Relative velocities along each distance are reduced below the tolerance times the length
+    /// per `timestep`. Returns an error if they are not within the iteration limit.
+    pub fn rattle(&self, system: &mut System, timestep: Float) -> Result<(), VelvetError> {
+        let separations: Vec<Vector3<Float>> = self
+            .constraints
+            .iter()
+            .map(|c| {
+                let mut separation = system.positions[c.j] - system.positions[c.i];
+                system.cell.vector_image(&mut separation);
+                separation
+            })
+            .collect();
+        for _ in 0..self.max_iterations {
+            let mut converged = true;
+            for (c, separation) in self.constraints.iter().zip(separations.iter()) {
+                let relative = system.velocities[c.j] - system.velocities[c.i];
+                let projection = separation.dot(&relative);
+                let target = c.length * c.length;
+                if projection.abs() * timestep <= self.tolerance * target {
+                    continue;
+                }
+                converged = false;
+                let (wi, wj) = (1.0 / system.species[c.i].mass(), 1.0 / system.species[c.j].mass());
+                let k = projection / ((wi + wj) * separation.norm_squared());
+                system.velocities[c.i] += separation * (k * wi);
+                system.velocities[c.j] -= separation * (k * wj);
+            }
+            if converged {
+                return Ok(());
+            }
+        }
+        Err(VelvetError::Convergence {
+            algorithm: "RATTLE",
+            iterations: self.max_iterations,
+        })
+    }
+
+    /// Restores every constrained distance to its exact length and returns the largest relative
+    /// deviation found beforehand.
+    ///
+    /// SHAKE leaves each distance anywhere within the tolerance of its length, which lets the
+    /// error accumulate in round-off over very long runs. Atoms are moved along their current
+    /// separations with mass weighting, so the center of mass stays in place, until each distance
+    /// is as close to its length as the floating point precision allows or the iteration limit
+    /// is reached. Velocities are not changed and should be corrected with
+    /// [`rattle`](Constraints::rattle) afterwards.
+    pub fn renormalize(&self, system: &mut System) -> Float {
+        let deviation = |system: &System, c: &DistanceConstraint| {
+            let mut separation = system.positions[c.j] - system.positions[c.i];
+            system.cell.vector_image(&mut separation);
+            (separation, separation.norm())
+        };
+        let largest = self
+            .constraints
+            .iter()
+            .map(|c| (deviation(system, c).1 - c.length).abs() / c.length)
+            .fold(0.0, Float::max);
+        let mut previous = Float::MAX;
+        for _ in 0..self.max_iterations {
+            let mut worst: Float = 0.0;
+            for c in self.constraints.iter() {
+                let (separation, distance) = deviation(system, c);
+                let difference = c.length - distance;
+                worst = worst.max(difference.abs() / c.length);
+                if difference.abs() <= 4.0 * Float::EPSILON * c.length {
+                    continue;
+                }
+                let (wi, wj) = (1.0 / system.species[c.i].mass(), 1.0 / system.species[c.j].mass());
+                let shift = separation * (difference / (distance * (wi + wj)));
+                system.positions[c.i] -= shift * wi;
+                system.positions[c.j] += shift * wj;
+            }
+            // stop once the remaining error is at the resolution of the coordinates
+            if worst <= 4.0 * Float::EPSILON || worst >= previous {
+                break;
+            }
+            previous = worst;
+        }
+        largest
+    }
+}
+
+impl Default for Constraints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Constraints;
+    use crate::errors::VelvetError;
+    use crate::integrators::{Integrator, Leapfrog, VelocityVerlet};
+    use crate::internal::Float;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::properties::pressure::{KineticTensor, Virial};
+    use crate::properties::{IntrinsicProperty, Property};
+    use crate::propagators::{MolecularDynamics, Propagator};
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::thermostats::NullThermostat;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    // Two water molecules with random velocities interacting through their oxygen atoms.
+    fn waters() -> (System, Potentials) {
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        let molecule = |origin: Vector3<Float>| {
+            vec![
+                origin,
+                origin + Vector3::new(0.9572, 0.0, 0.0),
+                origin + Vector3::new(-0.2400, 0.9266, 0.0),
+            ]
+        };
+        let mut positions = molecule(Vector3::new(5.0, 5.0, 5.0));
+        positions.extend(molecule(Vector3::new(7.8, 5.5, 5.3)));
+        let velocities = [
+            [0.004, -0.002, 0.001],
+            [0.03, 0.01, -0.02],
+            [-0.01, 0.02, 0.03],
+            [-0.003, 0.001, 0.0],
+            [0.02, -0.03, 0.01],
+            [0.0, 0.01, -0.03],
+        ];
+        let system = System {
+            size: 6,
+            cell: Cell::cubic(15.0),
+            species: vec![oxygen, hydrogen, hydrogen, oxygen, hydrogen, hydrogen],
+            positions,
+            velocities: velocities.iter().map(|v| Vector3::new(v[0], v[1], v[2])).collect(),
+            topology: Topology {
+                bonds: vec![[0, 1], [0, 2], [3, 4], [3, 5]],
+                angles: vec![[1, 0, 2], [4, 3, 5]],
+                ..Topology::default()
+            },
+        };
+        let mut potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.1553, 3.166), (oxygen, oxygen), 7.0, 1.0)
+            .build();
+        potentials.setup(&system);
+        (system, potentials)
+    }
+
+    fn assert_rigid(system: &System, constraints: &Constraints) {
+        for c in constraints.constraints() {
+            let r = system.cell.distance(&system.positions[c.i], &system.positions[c.j]);
+            assert_relative_eq!(r, c.length, max_relative = 1e-4);
+        }
+    }
+
+    #[test]
+    fn rigid_water() {
+        let (mut system, potentials) = waters();
+        let constraints = Constraints::new().angles();
+        let mut integrator = VelocityVerlet::new(2.0).with_constraints(constraints.clone());
+        integrator.setup(&system, &potentials);
+        for _ in 0..200 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+        }
+        let mut constraints = constraints;
+        constraints.setup(&waters().0);
+        assert_eq!(constraints.constraints().len(), 6);
+        assert_rigid(&system, &constraints);
+
+        // no velocity remains along the constrained distances
+        for c in constraints.constraints() {
+            let separation = system.positions[c.j] - system.positions[c.i];
+            let relative = system.velocities[c.j] - system.velocities[c.i];
+            assert!(separation.dot(&relative).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn leapfrog_hydrogen_bonds() {
+        let (mut system, potentials) = waters();
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        let constraints = Constraints::new().hydrogen_bonds().length((hydrogen, oxygen), 1.0);
+        let mut integrator = Leapfrog::new(1.0).with_constraints(constraints.clone());
+        integrator.setup(&system, &potentials);
+        for _ in 0..200 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+        }
+        let mut constraints = constraints;
+        constraints.setup(&system);
+        assert_eq!(constraints.constraints().len(), 4);
+        assert!(constraints.constraints().iter().all(|c| c.length == 1.0));
+        assert_rigid(&system, &constraints);
+    }
+
+    #[test]
+    fn renormalize() {
+        let (mut system, _) = waters();
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        let mut constraints = Constraints::new().length((oxygen, hydrogen), 1.0);
+        constraints.setup(&system);
+        let center = |system: &System| -> Vector3<Float> {
+            system
+                .species
+                .iter()
+                .zip(system.positions.iter())
+                .map(|(species, pos)| species.mass() * *pos)
+                .sum()
+        };
+        let before = center(&system);
+
+        let error = constraints.renormalize(&mut system);
+        assert_relative_eq!(error, 1.0 - 0.9572, max_relative = 1e-3);
+        for c in constraints.constraints() {
+            let r = system.cell.distance(&system.positions[c.i], &system.positions[c.j]);
+            assert_relative_eq!(r, c.length, max_relative = 1e-5);
+        }
+        assert_relative_eq!(center(&system), before, max_relative = 1e-5);
+        assert!(constraints.renormalize(&mut system) < 1e-5);
+    }
+
+    #[test]
+    fn degrees_of_freedom() {
+        let (mut system, potentials) = waters();
+        let integrator = VelocityVerlet::new(2.0).with_constraints(Constraints::new().angles());
+        let mut md = MolecularDynamics::new(integrator, NullThermostat);
+        md.setup(&mut system, &potentials).unwrap();
+        assert_eq!(system.topology.constraints.len(), 6);
+        assert_eq!(system.degrees_of_freedom(), 12);
+    }
+
+    #[test]
+    fn rigid_rotor_virial() {
+        // the constraint force of a rotating dimer cancels the pressure of its internal motion
+        let species = Species::new(1.0, 0.0);
+        let mut system = System {
+            size: 2,
+            cell: Cell::cubic(20.0),
+            species: vec![species; 2],
+            positions: vec![Vector3::new(9.5, 10.0, 10.0), Vector3::new(10.5, 10.0, 10.0)],
+            velocities: vec![Vector3::new(0.0, -0.01, 0.0), Vector3::new(0.0, 0.01, 0.0)],
+            topology: Topology {
+                bonds: vec![[0, 1]],
+                ..Topology::default()
+            },
+        };
+        let mut potentials = PotentialsBuilder::new().build();
+        potentials.setup(&system);
+        let integrator = VelocityVerlet::new(1.0).with_constraints(Constraints::new());
+        let mut md = MolecularDynamics::new(integrator, NullThermostat);
+        md.setup(&mut system, &potentials).unwrap();
+        for _ in 0..10 {
+            md.propagate(&mut system, &potentials);
+        }
+        let virial = Virial.calculate(&system, &potentials);
+        let kinetic = KineticTensor.calculate_intrinsic(&system);
+        assert_relative_eq!(virial.trace(), -kinetic.trace(), max_relative = 1e-2);
+    }
+
+    #[test]
+    fn convergence_error() {
+        let (mut system, potentials) = waters();
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        let constraints = Constraints::new().length((oxygen, hydrogen), 1.0).max_iterations(1);
+        let mut md = MolecularDynamics::new(VelocityVerlet::new(1.0).with_constraints(constraints), NullThermostat);
+        md.setup(&mut system, &potentials).unwrap();
+        md.propagate(&mut system, &potentials);
+        assert!(matches!(
+            md.take_error(),
+            Some(VelvetError::Convergence { algorithm: "SHAKE", iterations: 1 })
+        ));
+        assert!(md.take_error().is_none());
+    }
+}
@@ -0,0 +1,272 @@
+//! On-the-fly convergence monitoring of properties for adaptive-length runs.
+
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::Property;
+use crate::system::System;
+
+/// Running estimate of the mean of a monitored property.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Estimate {
+    /// Name of the property.
+    pub name: String,
+    /// Number of samples taken.
+    pub samples: usize,
+    /// Mean of the samples.
+    pub mean: Float,
+    /// Statistical inefficiency, the number of samples per statistically independent sample.
+    pub inefficiency: Float,
+    /// Standard error of the mean corrected for correlation between samples.
+    pub standard_error: Float,
+    /// Standard error below which the property is converged.
+    pub target: Float,
+}
+
+impl Estimate {
+    /// Returns true if the standard error of the mean has fallen below its target.
+    pub fn converged(&self) -> bool {
+        self.standard_error < self.target
+    }
+}
+
+struct Monitored {
+    property: Box<dyn Property<Res = Float>>,
+    target: Float,
+    samples: Vec<Float>,
+}
+
+/// Tracks the running mean and statistical inefficiency of selected properties.
+///
+/// The properties are sampled every `interval` steps of a run. Consecutive samples of a molecular
+/// dynamics trajectory are correlated, so the standard error of each mean is corrected by the
+/// statistical inefficiency estimated from the integrated autocorrelation of the samples. Once
+/// every property has at least the minimum number of samples and a standard error below its
+/// target the monitor reports convergence, which ends a run of a
+/// [`Simulation`](crate::simulation::Simulation) configured with
+/// [`ConfigurationBuilder::convergence`](crate::config::ConfigurationBuilder::convergence).
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // run until the mean potential energy is known to within 0.01 kcal/mol
+/// let monitor = ConvergenceMonitor::new(10)
+///     .property(PotentialEnergy, 0.01)
+///     .min_samples(100);
+/// let config = ConfigurationBuilder::new().convergence(monitor).build();
+/// ```
+///
+/// # References
+///
+/// [1] Chodera, John D., et al. "Use of the weighted histogram analysis method for the analysis of simulated and parallel tempering simulations." Journal of Chemical Theory and Computation 3.1 (2007): 26-41.
+pub struct ConvergenceMonitor {
+    interval: usize,
+    min_samples: usize,
+    monitored: Vec<Monitored>,
+    converged: bool,
+}
+
+impl ConvergenceMonitor {
+    /// Returns a new [`ConvergenceMonitor`] which samples every `interval` steps.
+    pub fn new(interval: usize) -> ConvergenceMonitor {
+        ConvergenceMonitor {
+            interval: interval.max(1),
+            min_samples: 20,
+            monitored: Vec::new(),
+            converged: false,
+        }
+    }
+
+    /// Monitors `property` until the standard error of its mean falls below `target`.
+    pub fn property<P>(mut self, property: P, target: Float) -> ConvergenceMonitor
+    where
+        P: Property<Res = Float> + 'static,
+    {
+        self.monitored.push(Monitored {
+            property: Box::new(property),
+            target,
+            samples: Vec::new(),
+        });
+        self
+    }
+
+    /// Sets the fewest samples of each property before convergence is reported. Defaults to 20.
+    pub fn min_samples(mut self, samples: usize) -> ConvergenceMonitor {
+        self.min_samples = samples.max(2);
+        self
+    }
+
+    /// Returns the number of steps between samples.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Samples every monitored property and updates the convergence of their means.
+    pub fn observe(&mut self, system: &System, potentials: &Potentials) {
+        for monitored in self.monitored.iter_mut() {
+            monitored.samples.push(monitored.property.calculate(system, potentials));
+        }
+        self.converged = !self.monitored.is_empty()
+            && self
+                .monitored
+                .iter()
+                .all(|m| m.samples.len() >= self.min_samples && estimate(m).converged());
+    }
+
+    /// Returns true once every monitored property has converged.
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+
+    /// Returns the current estimate of each monitored property.
+    pub fn estimates(&self) -> Vec<Estimate> {
+        self.monitored.iter().map(estimate).collect()
+    }
+
+    /// Discards every sample taken so far.
+    pub fn reset(&mut self) {
+        self.monitored.iter_mut().for_each(|m| m.samples.clear());
+        self.converged = false;
+    }
+}
+
+fn estimate(monitored: &Monitored) -> Estimate {
+    let samples = &monitored.samples;
+    let n = samples.len();
+    let mean = if n == 0 { 0.0 } else { samples.iter().sum::<Float>() / n as Float };
+    let inefficiency = statistical_inefficiency(samples, mean);
+    let variance = if n < 2 {
+        0.0
+    } else {
+        samples.iter().map(|x| (x - mean).powi(2)).sum::<Float>() / (n - 1) as Float
+    };
+    let standard_error = if n < 2 {
+        Float::INFINITY
+    } else {
+        Float::sqrt(variance * inefficiency / n as Float)
+    };
+    Estimate {
+        name: monitored.property.name(),
+        samples: n,
+        mean,
+        inefficiency,
+        standard_error,
+        target: monitored.target,
+    }
+}
+
+// Returns 1 + 2 sum (1 - t/n) C(t) / C(0) over lags until the autocorrelation first drops to zero.
+pub(crate) fn statistical_inefficiency(samples: &[Float], mean: Float) -> Float {
+    let n = samples.len();
+    let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<Float>() / n.max(1) as Float;
+    if n < 2 || variance <= Float::EPSILON * mean.abs().max(1.0) {
+        return 1.0;
+    }
+    let mut g = 1.0;
+    for t in 1..n - 1 {
+        let c = samples
+            .iter()
+            .zip(samples[t..].iter())
+            .map(|(a, b)| (a - mean) * (b - mean))
+            .sum::<Float>()
+            / ((n - t) as Float * variance);
+        if c <= 0.0 {
+            break;
+        }
+        g += 2.0 * c * (1.0 - t as Float / n as Float);
+    }
+    g
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{statistical_inefficiency, ConvergenceMonitor};
+    use crate::internal::Float;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::sync::{Arc, Mutex};
+
+    // Property which replays a fixed series of values.
+    struct Series(Arc<Mutex<Vec<Float>>>);
+
+    impl Property for Series {
+        type Res = Float;
+
+        fn calculate(&self, _: &System, _: &Potentials) -> Float {
+            self.0.lock().unwrap().remove(0)
+        }
+
+        fn name(&self) -> String {
+            "series".to_string()
+        }
+    }
+
+    // AR(1) process with the given correlation between consecutive values.
+    fn correlated(n: usize, phi: Float, seed: u64) -> Vec<Float> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut x = 0.0;
+        (0..n)
+            .map(|_| {
+                x = phi * x + rng.gen_range(-1.0, 1.0);
+                x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn inefficiency() {
+        // uncorrelated samples are all independent
+        let independent = correlated(20000, 0.0, 1);
+        let g = statistical_inefficiency(&independent, 0.0);
+        assert!((g - 1.0).abs() < 0.1, "{}", g);
+
+        // an AR(1) process has an inefficiency of (1 + phi) / (1 - phi)
+        let samples = correlated(20000, 0.8, 2);
+        let mean = samples.iter().sum::<Float>() / samples.len() as Float;
+        let g = statistical_inefficiency(&samples, mean);
+        assert!((g - 9.0).abs() < 2.0, "{}", g);
+
+        // constant samples
+        assert_eq!(statistical_inefficiency(&[2.0; 10], 2.0), 1.0);
+    }
+
+    #[test]
+    fn converges() {
+        let system = System {
+            size: 0,
+            cell: Cell::cubic(10.0),
+            species: Vec::new(),
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        let values = Arc::new(Mutex::new(correlated(5000, 0.5, 3)));
+        let mut monitor = ConvergenceMonitor::new(1)
+            .property(Series(values.clone()), 0.05)
+            .min_samples(50);
+
+        let mut steps = 0;
+        while !monitor.converged() && steps < 5000 {
+            monitor.observe(&system, &potentials);
+            steps += 1;
+        }
+        let estimate = &monitor.estimates()[0];
+        assert!(monitor.converged());
+        assert_eq!(estimate.samples, steps);
+        assert!(estimate.standard_error < 0.05);
+        // the standard error shrinks with the square root of the samples
+        assert!(steps > 200 && steps < 2000, "{}", steps);
+        assert!(estimate.mean.abs() < 0.2, "{}", estimate.mean);
+
+        monitor.reset();
+        assert!(!monitor.converged());
+        assert_eq!(monitor.estimates()[0].samples, 0);
+    }
+}
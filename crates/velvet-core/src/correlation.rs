@@ -0,0 +1,547 @@
+//! Time correlation functions of per-frame quantities.
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::Property;
+use crate::system::System;
+
+/// Quantity whose components can be fed into a [`MultiTauCorrelator`].
+///
+/// The correlation of two values is the sum of the products of their components, which is the
+/// dot product for vectors and the sum over every atom for per-atom quantities.
+pub trait Correlatable {
+    /// Appends the components of the quantity to `components`.
+    fn components(&self, components: &mut Vec<Float>);
+}
+
+impl Correlatable for f32 {
+    fn components(&self, components: &mut Vec<Float>) {
+        components.push(*self as Float);
+    }
+}
+
+impl Correlatable for f64 {
+    fn components(&self, components: &mut Vec<Float>) {
+        components.push(*self as Float);
+    }
+}
+
+impl Correlatable for Vector3<Float> {
+    fn components(&self, components: &mut Vec<Float>) {
+        components.extend(self.iter());
+    }
+}
+
+impl Correlatable for Matrix3<Float> {
+    fn components(&self, components: &mut Vec<Float>) {
+        components.extend(self.iter());
+    }
+}
+
+impl<T: Correlatable> Correlatable for [T] {
+    fn components(&self, components: &mut Vec<Float>) {
+        for value in self {
+            value.components(components);
+        }
+    }
+}
+
+impl<T: Correlatable> Correlatable for Vec<T> {
+    fn components(&self, components: &mut Vec<Float>) {
+        self.as_slice().components(components);
+    }
+}
+
+// Lags of one time resolution of the correlator.
+#[derive(Clone, Debug, Default)]
+struct Level {
+    // most recent values first
+    history: VecDeque<Vec<Float>>,
+    sums: Vec<Float>,
+    counts: Vec<usize>,
+    accumulator: Vec<Float>,
+    accumulated: usize,
+}
+
+/// Multiple-tau correlator which averages the time correlation function of a quantity over
+/// every time origin.
+///
+/// The first level correlates each sample with the previous `points` samples. Every `averaging`
+/// samples of a level are averaged into one sample of the next level, which covers lags
+/// `averaging` times longer with the same number of points. The memory therefore grows with the
+/// logarithm of the longest lag rather than with the length of the run, at the cost of a coarser
+/// resolution at long lags.
+///
+/// # References
+///
+/// [1] Ramírez, Jorge, et al. "Efficient on the fly calculation of time correlation functions in
+/// computer simulations." The Journal of Chemical Physics 133.15 (2010): 154103.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let mut correlator = MultiTauCorrelator::new(16, 2);
+/// for n in 0..1000 {
+///     correlator.push(&(n as f64 * 0.1).cos());
+/// }
+/// assert_eq!(&correlator.lags()[..3], &[0, 1, 2]);
+/// assert!((correlator.normalized()[0] - 1.0).abs() < 1e-5);
+/// ```
+#[derive(Clone, Debug)]
+pub struct MultiTauCorrelator {
+    points: usize,
+    averaging: usize,
+    max_levels: usize,
+    levels: Vec<Level>,
+    dimension: usize,
+    samples: usize,
+}
+
+impl MultiTauCorrelator {
+    /// Returns a new [`MultiTauCorrelator`].
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - Number of lags stored at each level.
+    /// * `averaging` - Number of samples of a level averaged into one sample of the next level.
+    pub fn new(points: usize, averaging: usize) -> MultiTauCorrelator {
+        let averaging = averaging.max(2);
+        MultiTauCorrelator {
+            points: points.max(averaging),
+            averaging,
+            max_levels: 16,
+            levels: Vec::new(),
+            dimension: 0,
+            samples: 0,
+        }
+    }
+
+    /// Sets the maximum number of levels. Defaults to 16.
+    ///
+    /// The longest lag is about `points * averaging.pow(levels - 1)` samples. A single level
+    /// gives evenly spaced lags up to `points - 1` samples.
+    pub fn levels(mut self, levels: usize) -> MultiTauCorrelator {
+        self.max_levels = levels.max(1);
+        self
+    }
+
+    /// Adds the next sample of the quantity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of components differs from that of the first sample.
+    pub fn push<T: Correlatable + ?Sized>(&mut self, value: &T) {
+        let mut values = Vec::with_capacity(self.dimension);
+        value.components(&mut values);
+        if self.samples == 0 {
+            self.dimension = values.len();
+        }
+        assert_eq!(
+            values.len(),
+            self.dimension,
+            "sample has {} components instead of {}",
+            values.len(),
+            self.dimension
+        );
+        self.samples += 1;
+        self.add(0, values);
+    }
+
+    fn add(&mut self, index: usize, values: Vec<Float>) {
+        if index == self.levels.len() {
+            if index == self.max_levels {
+                return;
+            }
+            self.levels.push(Level {
+                sums: vec![0.0; self.points],
+                counts: vec![0; self.points],
+                accumulator: vec![0.0; self.dimension],
+                ..Level::default()
+            });
+        }
+        let first = self.first_point(index);
+        let level = &mut self.levels[index];
+        level.history.push_front(values);
+        level.history.truncate(self.points);
+        let newest = &level.history[0];
+        let points = level
+            .history
+            .iter()
+            .zip(level.sums.iter_mut())
+            .zip(level.counts.iter_mut());
+        for ((previous, sum), count) in points.skip(first) {
+            *sum += newest.iter().zip(previous.iter()).map(|(a, b)| a * b).sum::<Float>();
+            *count += 1;
+        }
+        for (a, v) in level.accumulator.iter_mut().zip(newest.iter()) {
+            *a += v;
+        }
+        level.accumulated += 1;
+        if level.accumulated == self.averaging {
+            let n = self.averaging as Float;
+            let average = level.accumulator.iter().map(|a| a / n).collect();
+            level.accumulator.iter_mut().for_each(|a| *a = 0.0);
+            level.accumulated = 0;
+            self.add(index + 1, average);
+        }
+    }
+
+    // Index of the first point of a level which is not covered by the previous level.
+    fn first_point(&self, index: usize) -> usize {
+        if index == 0 {
+            0
+        } else {
+            self.points.div_ceil(self.averaging)
+        }
+    }
+
+    // Lag in samples and averaged correlation of every point with at least one time origin.
+    fn entries(&self) -> Vec<(usize, Float)> {
+        let mut entries = Vec::new();
+        for (index, level) in self.levels.iter().enumerate() {
+            let spacing = self.averaging.pow(index as u32);
+            let points = level.sums.iter().zip(level.counts.iter()).enumerate();
+            for (j, (sum, &count)) in points.skip(self.first_point(index)) {
+                if count > 0 {
+                    entries.push((j * spacing, sum / count as Float));
+                }
+            }
+        }
+        entries
+    }
+
+    /// Returns the number of samples added.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Returns the number of components of each sample.
+    pub fn dimension(&self) -> usize {
+        self.dimension
+    }
+
+    /// Returns the lag of each point of the correlation function in samples.
+    pub fn lags(&self) -> Vec<usize> {
+        self.entries().iter().map(|&(lag, _)| lag).collect()
+    }
+
+    /// Returns the time of each lag given the `timestep` between samples.
+    pub fn times(&self, timestep: Float) -> Vec<Float> {
+        self.lags().iter().map(|&lag| lag as Float * timestep).collect()
+    }
+
+    /// Returns the correlation function averaged over every time origin.
+    pub fn correlation(&self) -> Vec<Float> {
+        self.entries().iter().map(|&(_, c)| c).collect()
+    }
+
+    /// Returns the correlation function normalized to one at zero lag.
+    pub fn normalized(&self) -> Vec<Float> {
+        let mut correlation = self.correlation();
+        if let Some(&zero) = correlation.first() {
+            if zero > 0.0 {
+                correlation.iter_mut().for_each(|c| *c /= zero);
+            }
+        }
+        correlation
+    }
+
+    /// Returns the running integral of the correlation function from zero lag to each lag given
+    /// the `timestep` between samples, as needed by Green-Kubo relations.
+    pub fn integral(&self, timestep: Float) -> Vec<Float> {
+        let entries = self.entries();
+        let mut total = 0.0;
+        let mut integral = Vec::with_capacity(entries.len());
+        for (i, &(lag, c)) in entries.iter().enumerate() {
+            if i > 0 {
+                let (previous_lag, previous) = entries[i - 1];
+                total += 0.5 * (c + previous) * (lag - previous_lag) as Float * timestep;
+            }
+            integral.push(total);
+        }
+        integral
+    }
+
+    /// Discards every sample.
+    pub fn reset(&mut self) {
+        self.levels.clear();
+        self.dimension = 0;
+        self.samples = 0;
+    }
+}
+
+impl Default for MultiTauCorrelator {
+    fn default() -> Self {
+        MultiTauCorrelator::new(16, 2)
+    }
+}
+
+/// Time correlation function of any property whose result is [`Correlatable`].
+///
+/// Each calculation feeds the value of the property to a [`MultiTauCorrelator`] and returns the
+/// mean square of the property so far, the correlation at zero lag. The same property gives
+/// autocorrelation functions of velocities, stresses, dipoles, or collective variables, and
+/// their running integrals for Green-Kubo transport coefficients.
+///
+/// Clones share the same correlator, so a clone kept aside before the property is added to an
+/// output group can be analyzed once the simulation ends.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // stress autocorrelation sampled every 5 steps of 1 fs
+/// let correlation = TimeCorrelation::new(StressTensor, 5.0);
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(5)
+///     .output(correlation.clone())
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct TimeCorrelation<P> {
+    property: P,
+    interval: Float,
+    correlator: Arc<Mutex<MultiTauCorrelator>>,
+}
+
+impl<P> TimeCorrelation<P>
+where
+    P: Property,
+    P::Res: Correlatable,
+{
+    /// Returns a new [`TimeCorrelation`] with the default [`MultiTauCorrelator`].
+    ///
+    /// # Arguments
+    ///
+    /// * `property` - Property to correlate.
+    /// * `interval` - Time between consecutive calculations in femtoseconds.
+    pub fn new(property: P, interval: Float) -> TimeCorrelation<P> {
+        TimeCorrelation {
+            property,
+            interval,
+            correlator: Arc::new(Mutex::new(MultiTauCorrelator::default())),
+        }
+    }
+
+    /// Sets the correlator which accumulates the samples.
+    pub fn correlator(mut self, correlator: MultiTauCorrelator) -> TimeCorrelation<P> {
+        self.correlator = Arc::new(Mutex::new(correlator));
+        self
+    }
+
+    /// Returns the number of samples so far.
+    pub fn samples(&self) -> usize {
+        self.correlator.lock().unwrap().samples()
+    }
+
+    /// Returns the time of each lag in femtoseconds.
+    pub fn times(&self) -> Vec<Float> {
+        self.correlator.lock().unwrap().times(self.interval)
+    }
+
+    /// Returns the correlation function averaged over every time origin.
+    pub fn correlation(&self) -> Vec<Float> {
+        self.correlator.lock().unwrap().correlation()
+    }
+
+    /// Returns the correlation function normalized to one at zero lag.
+    pub fn normalized(&self) -> Vec<Float> {
+        self.correlator.lock().unwrap().normalized()
+    }
+
+    /// Returns the running integral of the correlation function in femtoseconds times the
+    /// squared units of the property.
+    pub fn integral(&self) -> Vec<Float> {
+        self.correlator.lock().unwrap().integral(self.interval)
+    }
+
+    /// Discards every sample.
+    pub fn reset(&self) {
+        self.correlator.lock().unwrap().reset();
+    }
+
+    /// Writes the correlation function as columns of time, value, normalized value, and running
+    /// integral.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "# time correlation normalized integral")?;
+        let correlator = self.correlator.lock().unwrap();
+        let rows = correlator
+            .times(self.interval)
+            .into_iter()
+            .zip(correlator.correlation())
+            .zip(correlator.normalized())
+            .zip(correlator.integral(self.interval));
+        for (((time, c), normalized), integral) in rows {
+            writeln!(writer, "{} {} {} {}", time, c, normalized, integral)?;
+        }
+        writer.flush()
+    }
+}
+
+impl<P> Property for TimeCorrelation<P>
+where
+    P: Property,
+    P::Res: Correlatable,
+{
+    type Res = Float;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let value = self.property.calculate(system, potentials);
+        let mut correlator = self.correlator.lock().unwrap();
+        correlator.push(&value);
+        correlator.correlation()[0]
+    }
+
+    fn name(&self) -> String {
+        format!("{}_correlation", self.property.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MultiTauCorrelator, TimeCorrelation};
+    use crate::internal::Float;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // Property which returns the velocities of the system.
+    #[derive(Clone, Debug)]
+    struct Velocities;
+
+    impl Property for Velocities {
+        type Res = Vec<Vector3<Float>>;
+
+        fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
+            system.velocities.clone()
+        }
+
+        fn name(&self) -> String {
+            "velocities".to_string()
+        }
+    }
+
+    #[test]
+    fn matches_direct_average() {
+        // every lag of the first level is averaged over every time origin
+        let mut rng = StdRng::seed_from_u64(5);
+        let samples: Vec<Vec<Vector3<Float>>> = (0..12)
+            .map(|_| {
+                (0..4)
+                    .map(|_| {
+                        Vector3::new(
+                            rng.gen_range(-1.0, 1.0),
+                            rng.gen_range(-1.0, 1.0),
+                            rng.gen_range(-1.0, 1.0),
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        let mut correlator = MultiTauCorrelator::new(16, 2).levels(1);
+        for sample in &samples {
+            correlator.push(sample);
+        }
+        assert_eq!(correlator.dimension(), 12);
+        assert_eq!(correlator.lags(), (0..12).collect::<Vec<usize>>());
+        for (lag, c) in correlator.correlation().iter().enumerate() {
+            let origins = samples.len() - lag;
+            let direct: Float = (0..origins)
+                .map(|t| {
+                    samples[t]
+                        .iter()
+                        .zip(samples[t + lag].iter())
+                        .map(|(a, b)| a.dot(b))
+                        .sum::<Float>()
+                })
+                .sum::<Float>()
+                / origins as Float;
+            assert!((c - direct).abs() < 1e-4, "{} {} {}", lag, c, direct);
+        }
+    }
+
+    #[test]
+    fn long_lags() {
+        // AR(1) process whose normalized autocorrelation is phi^lag
+        let phi: Float = 0.8;
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut correlator = MultiTauCorrelator::default();
+        let mut x = 0.0;
+        for _ in 0..50000 {
+            x = phi * x + rng.gen_range(-1.0, 1.0);
+            correlator.push(&x);
+        }
+        assert_eq!(correlator.samples(), 50000);
+
+        let lags = correlator.lags();
+        let normalized = correlator.normalized();
+        // 16 lags on the first level and 8 on each of the others
+        assert_eq!(&lags[14..20], &[14, 15, 16, 18, 20, 22]);
+        assert!(*lags.last().unwrap() > 10000);
+        assert!(lags.len() <= 16 + 15 * 8);
+        for (&lag, c) in lags.iter().zip(normalized.iter()) {
+            if lag <= 64 {
+                assert!((c - phi.powi(lag as i32)).abs() < 0.05, "{} {}", lag, c);
+            }
+        }
+
+        // integral of phi^t with the trapezoidal rule is (1 + phi) / (2 (1 - phi))
+        let position = lags.iter().position(|&lag| lag == 32).unwrap();
+        let variance = correlator.correlation()[0];
+        let integral = correlator.integral(1.0)[position] / variance;
+        assert!((integral - 4.5).abs() < 0.5, "{}", integral);
+
+        correlator.reset();
+        assert!(correlator.lags().is_empty());
+        correlator.push(&Vector3::new(1.0, 2.0, 2.0));
+        assert_eq!(correlator.correlation(), vec![9.0]);
+    }
+
+    #[test]
+    fn time_correlation() {
+        let mut system = System {
+            size: 2,
+            cell: Cell::cubic(10.0),
+            species: Vec::new(),
+            positions: vec![Vector3::zeros(); 2],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        let correlation = TimeCorrelation::new(Velocities, 2.0).correlator(MultiTauCorrelator::new(8, 2));
+        let shared = correlation.clone();
+        for n in 0..100 {
+            // velocities which reverse every other calculation
+            let sign = if n % 2 == 0 { 1.0 } else { -1.0 };
+            system.velocities = vec![Vector3::new(sign, 0.0, 0.0), Vector3::new(0.0, sign, 0.0)];
+            assert_eq!(correlation.calculate(&system, &potentials), 2.0);
+        }
+        assert_eq!(shared.samples(), 100);
+        assert_eq!(shared.name(), "velocities_correlation");
+        assert_eq!(&shared.times()[..3], &[0.0, 2.0, 4.0]);
+        assert_eq!(&shared.normalized()[..3], &[1.0, -1.0, 1.0]);
+        assert_eq!(shared.integral()[1], 0.0);
+
+        let mut buffer = Vec::new();
+        shared.write(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().count(), shared.times().len() + 1);
+        shared.reset();
+        assert_eq!(correlation.samples(), 0);
+    }
+}
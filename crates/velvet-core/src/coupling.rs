@@ -0,0 +1,162 @@
+//! Exchange of data with external solvers during a co-simulation.
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::colvars::{Bias, BiasEvaluation};
+use crate::internal::Float;
+use crate::system::System;
+
+/// Buffers exchanged with a coupled solver between blocks of steps of a co-simulation.
+///
+/// On each exchange the simulation fills every field except `external`, which the coupled
+/// solver writes with the force it exerts on each atom. External forces are held constant over
+/// the following block of steps and persist until they are overwritten, so a solver which only
+/// reads the state of the atoms may leave them untouched.
+#[derive(Clone, Debug, Default)]
+pub struct ExchangeBuffers {
+    /// Total number of steps completed by the simulation.
+    pub step: usize,
+    /// Number of steps advanced since the previous exchange.
+    pub steps: usize,
+    /// Wall clock time spent advancing the previous block of steps.
+    pub elapsed: Duration,
+    /// Position of each atom.
+    pub positions: Vec<Vector3<Float>>,
+    /// Velocity of each atom.
+    pub velocities: Vec<Vector3<Float>>,
+    /// Force on each atom due to the potentials, excluding the external forces.
+    pub forces: Vec<Vector3<Float>>,
+    /// External force on each atom applied over the next block of steps.
+    pub external: Vec<Vector3<Float>>,
+}
+
+impl ExchangeBuffers {
+    /// Returns the average wall clock time per step of the previous block.
+    pub fn time_per_step(&self) -> Duration {
+        if self.steps == 0 {
+            Duration::default()
+        } else {
+            self.elapsed / self.steps as u32
+        }
+    }
+}
+
+// Forces and the positions where they were last exchanged.
+#[derive(Debug, Default)]
+struct Field {
+    forces: Vec<Vector3<Float>>,
+    reference: Vec<Vector3<Float>>,
+}
+
+/// Constant external force on each atom set by a coupled solver.
+///
+/// The energy is the negative work done by the forces on the displacements of the atoms since the
+/// last exchange, and the biased collective variable is that work. Forces which do not depend on the
+/// positions of the atoms have no virial in a periodic cell, so the external forces add none.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ExternalForces {
+    field: Arc<Mutex<Field>>,
+}
+
+impl ExternalForces {
+    // Replaces the external forces and moves the reference to the current positions.
+    pub(crate) fn set(&self, system: &System, forces: &[Vector3<Float>]) {
+        assert_eq!(
+            forces.len(),
+            system.size,
+            "Expected an external force for each of the {} atoms",
+            system.size
+        );
+        let mut field = self.field.lock().unwrap();
+        field.forces = forces.to_vec();
+        field.reference = system.positions.clone();
+    }
+
+    // Returns the current external force on each atom.
+    pub(crate) fn forces(&self, system: &System) -> Vec<Vector3<Float>> {
+        let field = self.field.lock().unwrap();
+        if field.forces.len() == system.size {
+            field.forces.clone()
+        } else {
+            vec![Vector3::zeros(); system.size]
+        }
+    }
+
+    fn work(&self, system: &System) -> Float {
+        let field = self.field.lock().unwrap();
+        field
+            .forces
+            .iter()
+            .zip(field.reference.iter())
+            .zip(system.positions.iter())
+            .map(|((force, reference), position)| {
+                let mut displacement = position - reference;
+                system.cell.vector_image(&mut displacement);
+                force.dot(&displacement)
+            })
+            .sum()
+    }
+}
+
+impl Bias for ExternalForces {
+    fn evaluate(&self, system: &System) -> BiasEvaluation {
+        BiasEvaluation {
+            energy: -self.work(system),
+            forces: self.forces(system),
+            virial: Matrix3::zeros(),
+        }
+    }
+
+    fn colvar(&self, system: &System) -> Float {
+        self.work(system)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExchangeBuffers, ExternalForces};
+    use crate::colvars::Bias;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use std::time::Duration;
+
+    #[test]
+    fn external_forces() {
+        let mut system = System {
+            size: 2,
+            cell: Cell::cubic(10.0),
+            species: vec![Species::new(1.0, 0.0); 2],
+            positions: vec![Vector3::new(1.0, 1.0, 1.0), Vector3::new(9.5, 5.0, 5.0)],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        };
+        let external = ExternalForces::default();
+        let evaluation = external.evaluate(&system);
+        assert_eq!(evaluation.energy, 0.0);
+        assert_eq!(evaluation.forces, vec![Vector3::zeros(); 2]);
+
+        external.set(&system, &[Vector3::new(1.0, 0.0, 0.0), Vector3::new(2.0, 0.0, 0.0)]);
+        // the second atom crosses the periodic boundary
+        system.positions[0].x += 0.5;
+        system.positions[1].x = 0.5;
+        let evaluation = external.evaluate(&system);
+        assert!((evaluation.energy + 2.5).abs() < 1e-5);
+        assert_eq!(evaluation.forces[1], Vector3::new(2.0, 0.0, 0.0));
+        assert!((external.colvar(&system) - 2.5).abs() < 1e-5);
+
+        let mut buffers = ExchangeBuffers {
+            steps: 5,
+            elapsed: Duration::from_millis(10),
+            ..ExchangeBuffers::default()
+        };
+        assert_eq!(buffers.time_per_step(), Duration::from_millis(2));
+        buffers.steps = 0;
+        assert_eq!(buffers.time_per_step(), Duration::default());
+    }
+}
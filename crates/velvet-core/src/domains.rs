@@ -0,0 +1,361 @@
+//! Spatial domain decomposition for distributing force evaluation across processes.
+
+use nalgebra::Vector3;
+
+#[cfg(feature = "mpi-communicator")]
+use mpi::collective::SystemOperation;
+#[cfg(feature = "mpi-communicator")]
+use mpi::topology::SystemCommunicator;
+#[cfg(feature = "mpi-communicator")]
+use mpi::traits::{Communicator as _, CommunicatorCollectives as _};
+
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::forces::Forces;
+use crate::properties::per_atom::PerAtomPotentialEnergy;
+use crate::properties::Property;
+use crate::system::topology::{Group, Residue, Topology};
+use crate::system::System;
+
+/// Collective communication between the processes of a distributed run.
+///
+/// Implementations wrap a message passing layer such as MPI, which the `MpiCommunicator` of the
+/// `mpi-communicator` feature provides. Every process holds the full system, evaluates the
+/// domains assigned to its rank, and reduces the partial results with
+/// [`sum`](Communicator::sum).
+pub trait Communicator {
+    /// Returns the index of this process.
+    fn rank(&self) -> usize;
+    /// Returns the number of processes.
+    fn size(&self) -> usize;
+    /// Replaces `values` with their elementwise sum over every process.
+    fn sum(&self, values: &mut [Float]);
+}
+
+/// Communicator of a run on a single process which evaluates every domain itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SerialCommunicator;
+
+impl Communicator for SerialCommunicator {
+    fn rank(&self) -> usize {
+        0
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn sum(&self, _: &mut [Float]) {}
+}
+
+/// Communicator over the processes of an MPI world.
+///
+/// Partial results are reduced with an `MPI_Allreduce` so every rank receives the sum.
+///
+/// # Examples
+///
+/// ```no_run
+/// use velvet_core::prelude::*;
+/// # fn system() -> System { unimplemented!() }
+/// # fn potentials() -> Potentials { unimplemented!() }
+///
+/// let universe = mpi::initialize().unwrap();
+/// let communicator = MpiCommunicator::new(&universe);
+/// let system = system();
+/// let mut potentials = potentials();
+/// let mut decomposition = DomainDecomposition::new([2, 2, 2], 10.0);
+/// decomposition.decompose(&system);
+/// let forces = decomposition.forces(&system, &mut potentials, &communicator);
+/// ```
+#[cfg(feature = "mpi-communicator")]
+pub struct MpiCommunicator {
+    world: SystemCommunicator,
+}
+
+#[cfg(feature = "mpi-communicator")]
+impl MpiCommunicator {
+    /// Returns a communicator over every process of the world of `universe`.
+    pub fn new(universe: &mpi::environment::Universe) -> MpiCommunicator {
+        MpiCommunicator {
+            world: universe.world(),
+        }
+    }
+}
+
+#[cfg(feature = "mpi-communicator")]
+impl Communicator for MpiCommunicator {
+    fn rank(&self) -> usize {
+        self.world.rank() as usize
+    }
+
+    fn size(&self) -> usize {
+        self.world.size() as usize
+    }
+
+    fn sum(&self, values: &mut [Float]) {
+        let partial = values.to_vec();
+        self.world.all_reduce_into(&partial[..], values, SystemOperation::sum());
+    }
+}
+
+/// Region of the cell and the atoms it holds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Domain {
+    /// Position of the domain in the grid along each cell vector.
+    pub index: [usize; 3],
+    /// Indices of the atoms inside the domain.
+    pub owned: Vec<usize>,
+    /// Indices of the atoms outside the domain but within the halo of its boundaries.
+    pub ghosts: Vec<usize>,
+}
+
+/// Decomposition of a periodic cell into a grid of domains with ghost atom halos.
+///
+/// The cell is divided evenly along each of its vectors in fractional coordinates. Each domain
+/// owns the atoms inside it and copies as ghosts the atoms within `halo` of its faces, so the
+/// owned atoms see every neighbor they interact with. The halo must be at least the largest
+/// cutoff of the pair and Coulombic potentials, and twice that of an embedded atom potential
+/// whose densities are summed over the neighbors of the ghosts.
+///
+/// Domains are dealt out to the processes of a [`Communicator`] in turn. Each process sets up
+/// its potentials for the owned and ghost atoms of a domain, keeps the results of the owned
+/// atoms, and the partial results are summed over the processes, so every process ends up with
+/// the full forces. The topology of the system is carried into the domains, so the partial
+/// charges and the exclusions between bonded atoms apply as in the whole system, and the halo
+/// must reach every atom which shares a bond, angle, dihedral, or constraint with an owned atom.
+/// Terms without a local decomposition such as long range Coulombic energy are divided by
+/// [`PerAtomPotentialEnergy`] over the atoms of each domain rather than the system.
+///
+/// # Examples
+///
+/// ```no_run
+/// use velvet_core::prelude::*;
+/// # fn system() -> System { unimplemented!() }
+/// # fn potentials() -> Potentials { unimplemented!() }
+///
+/// let system = system();
+/// let mut potentials = potentials();
+/// let mut decomposition = DomainDecomposition::new([2, 2, 2], 10.0);
+/// decomposition.decompose(&system);
+/// let forces = decomposition.forces(&system, &mut potentials, &SerialCommunicator);
+/// ```
+#[derive(Clone, Debug)]
+pub struct DomainDecomposition {
+    grid: [usize; 3],
+    halo: Float,
+    domains: Vec<Domain>,
+}
+
+impl DomainDecomposition {
+    /// Returns a new [`DomainDecomposition`].
+    ///
+    /// # Arguments
+    ///
+    /// * `grid` - Number of domains along each cell vector.
+    /// * `halo` - Thickness of the ghost atom halo around each domain in angstroms.
+    pub fn new(grid: [usize; 3], halo: Float) -> DomainDecomposition {
+        assert!(grid.iter().all(|&n| n > 0), "The domain grid must be at least 1 along each axis.");
+        DomainDecomposition {
+            grid,
+            halo,
+            domains: Vec::new(),
+        }
+    }
+
+    /// Returns the number of domains along each cell vector.
+    pub fn grid(&self) -> [usize; 3] {
+        self.grid
+    }
+
+    /// Returns the domains of the most recent decomposition.
+    pub fn domains(&self) -> &[Domain] {
+        &self.domains
+    }
+
+    /// Returns the indices of the domains evaluated by the process of rank `rank` out of `size`.
+    pub fn assigned(&self, rank: usize, size: usize) -> Vec<usize> {
+        (rank..self.domains.len()).step_by(size.max(1)).collect()
+    }
+
+    /// Assigns the owned and ghost atoms of every domain from the current positions of `system`.
+    pub fn decompose(&mut self, system: &System) {
+        let [nx, ny, nz] = self.grid;
+        self.domains = (0..nx * ny * nz)
+            .map(|d| Domain {
+                index: [d / (ny * nz), (d / nz) % ny, d % nz],
+                ..Domain::default()
+            })
+            .collect();
+
+        // perpendicular distance between opposite faces of the cell along each cell vector
+        let inverse = system.cell.inverse_matrix();
+        let widths: Vec<Float> = (0..3).map(|k| 1.0 / inverse.row(k).norm()).collect();
+        let fractional: Vec<Vector3<Float>> = system
+            .positions
+            .iter()
+            .map(|pos| system.cell.fractional(pos).map(|s| s - s.floor()))
+            .collect();
+
+        let mut owners = vec![0; system.size];
+        for (i, s) in fractional.iter().enumerate() {
+            let index: Vec<usize> = (0..3)
+                .map(|k| ((s[k] * self.grid[k] as Float) as usize).min(self.grid[k] - 1))
+                .collect();
+            let d = (index[0] * ny + index[1]) * nz + index[2];
+            self.domains[d].owned.push(i);
+            owners[i] = d;
+        }
+
+        let (grid, halo) = (self.grid, self.halo);
+        for (d, domain) in self.domains.iter_mut().enumerate() {
+            for (i, s) in fractional.iter().enumerate() {
+                let within = (0..3).all(|k| {
+                    let n = grid[k] as Float;
+                    let low = domain.index[k] as Float / n;
+                    let high = (domain.index[k] + 1) as Float / n;
+                    // periodic distance from the fractional coordinate to the slab of the domain
+                    let distance = if s[k] >= low && s[k] < high {
+                        0.0
+                    } else {
+                        let below = (low - s[k]).rem_euclid(1.0);
+                        let above = (s[k] - high).rem_euclid(1.0);
+                        below.min(above)
+                    };
+                    distance * widths[k] < halo
+                });
+                if within && owners[i] != d {
+                    domain.ghosts.push(i);
+                }
+            }
+        }
+    }
+
+    /// Returns a system holding the owned atoms of a domain followed by its ghost atoms.
+    ///
+    /// The local system shares the cell of `system` so distances follow the same periodic images.
+    /// Its topology holds the terms of the system's topology whose atoms are all local, along with
+    /// the partial charges, residues, groups, and frozen atoms of the local atoms.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a bond, angle, dihedral, or constraint of an owned atom reaches an atom outside
+    /// the halo, since the exclusions of the owned atom would otherwise be lost.
+    pub fn local_system(&self, system: &System, domain: &Domain) -> System {
+        let atoms: Vec<usize> = domain.owned.iter().chain(domain.ghosts.iter()).copied().collect();
+        let mut index = vec![None; system.size];
+        for (local, &i) in atoms.iter().enumerate() {
+            index[i] = Some(local);
+        }
+        let owned = domain.owned.len();
+        // maps a term to local indices, or drops it if an atom is missing and no atom is owned
+        let local_term = |entry: &str, term: &[usize]| -> Option<Vec<usize>> {
+            let mapped: Option<Vec<usize>> = term.iter().map(|&i| index[i]).collect();
+            if mapped.is_none() {
+                if let Some(&i) = term.iter().find(|&&i| matches!(index[i], Some(local) if local < owned)) {
+                    panic!(
+                        "A {} of atom {} reaches beyond the halo of {} angstroms around its domain.",
+                        entry, i, self.halo
+                    );
+                }
+            }
+            mapped
+        };
+        let topology = &system.topology;
+        System {
+            size: atoms.len(),
+            cell: system.cell.clone(),
+            species: atoms.iter().map(|&i| system.species[i]).collect(),
+            positions: atoms.iter().map(|&i| system.positions[i]).collect(),
+            velocities: atoms.iter().map(|&i| system.velocities[i]).collect(),
+            topology: Topology {
+                bonds: topology
+                    .bonds
+                    .iter()
+                    .filter_map(|bond| local_term("bond", bond))
+                    .map(|t| [t[0], t[1]])
+                    .collect(),
+                angles: topology
+                    .angles
+                    .iter()
+                    .filter_map(|angle| local_term("angle", angle))
+                    .map(|t| [t[0], t[1], t[2]])
+                    .collect(),
+                dihedrals: topology
+                    .dihedrals
+                    .iter()
+                    .filter_map(|dihedral| local_term("dihedral", dihedral))
+                    .map(|t| [t[0], t[1], t[2], t[3]])
+                    .collect(),
+                residues: topology
+                    .residues
+                    .iter()
+                    .map(|residue| Residue {
+                        name: residue.name.clone(),
+                        atoms: residue.atoms.iter().filter_map(|&i| index[i]).collect(),
+                    })
+                    .filter(|residue| !residue.atoms.is_empty())
+                    .collect(),
+                groups: topology
+                    .groups
+                    .iter()
+                    .map(|group| Group {
+                        name: group.name.clone(),
+                        atoms: group.atoms.iter().filter_map(|&i| index[i]).collect(),
+                    })
+                    .collect(),
+                charges: if topology.charges.is_empty() {
+                    Vec::new()
+                } else {
+                    atoms.iter().map(|&i| topology.charges[i]).collect()
+                },
+                constraints: topology
+                    .constraints
+                    .iter()
+                    .filter_map(|pair| local_term("constraint", pair))
+                    .map(|t| [t[0], t[1]])
+                    .collect(),
+                frozen: topology.frozen.iter().filter_map(|&i| index[i]).collect(),
+            },
+        }
+    }
+
+    /// Returns the force on every atom, evaluated domain by domain and summed over the processes.
+    pub fn forces<C: Communicator>(
+        &self,
+        system: &System,
+        potentials: &mut Potentials,
+        communicator: &C,
+    ) -> Vec<Vector3<Float>> {
+        let mut buffer = vec![0.0; 3 * system.size];
+        for d in self.assigned(communicator.rank(), communicator.size()) {
+            let domain = &self.domains[d];
+            let local = self.local_system(system, domain);
+            potentials.setup(&local);
+            let forces = Forces.calculate(&local, potentials);
+            for (&i, force) in domain.owned.iter().zip(forces.iter()) {
+                buffer[3 * i..3 * i + 3].copy_from_slice(force.as_slice());
+            }
+        }
+        communicator.sum(&mut buffer);
+        buffer.chunks(3).map(Vector3::from_column_slice).collect()
+    }
+
+    /// Returns the potential energy of the system, summed over the owned atoms of every domain.
+    pub fn potential_energy<C: Communicator>(
+        &self,
+        system: &System,
+        potentials: &mut Potentials,
+        communicator: &C,
+    ) -> Float {
+        let mut buffer = [0.0];
+        for d in self.assigned(communicator.rank(), communicator.size()) {
+            let domain = &self.domains[d];
+            let local = self.local_system(system, domain);
+            potentials.setup(&local);
+            let energies = PerAtomPotentialEnergy.calculate(&local, potentials);
+            buffer[0] += energies[..domain.owned.len()].iter().sum::<Float>();
+        }
+        communicator.sum(&mut buffer);
+        buffer[0]
+    }
+}
@@ -0,0 +1,400 @@
+//! Constant potential electrodes whose charges respond to the electrolyte under an applied voltage.
+
+#[cfg(feature = "f64")]
+use libm::erfc;
+
+#[cfg(not(feature = "f64"))]
+use libm::erfcf as erfc;
+
+use nalgebra::{DMatrix, DVector, Vector3};
+
+use crate::checkpoint::PropagatorState;
+use crate::errors::VelvetError;
+use crate::internal::consts::{COULOMB, FRAC_1_SQRT_2, FRAC_2_SQRT_PI, PI};
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::system::System;
+
+/// Electrostatic potential of one volt in kcal/mol/e.
+pub const VOLT: Float = 23.0605;
+
+// Reciprocal vector of the cell with its Gaussian weight.
+struct Wave {
+    m: Vector3<Float>,
+    weight: Float,
+}
+
+/// Propagator wrapper which holds groups of electrode atoms at fixed electrostatic potentials.
+///
+/// Each electrode atom carries a Gaussian charge whose value fluctuates so that the electrostatic
+/// energy is minimal with every electrode at its applied potential, the constant potential method
+/// of Siepmann and Sprik and of Reed, Lanning, and Madden. The charges solve the linear system
+/// `A q = psi - b` subject to the neutrality of the whole cell, where `A` couples the electrode
+/// charges through periodic Ewald interactions and the self energy of their Gaussians, `psi`
+/// holds the applied potentials, and `b` is the potential of the electrolyte at each electrode
+/// atom. The electrode atoms are expected to be held fixed, so the matrix is factored once and
+/// only rebuilt if they move. The charges are solved on setup and after every step of the wrapped
+/// propagator, and the forces on the electrolyte follow from the [`Ewald`] Coulomb potential of
+/// the system with the solved charges as point charges.
+///
+/// The charges are written to the partial charges of the system's topology and the electrode atoms
+/// keep their species, so every potential defined for the electrode species still applies. The
+/// Coulomb potential only pairs atoms which are charged when it is set up, so the electrode atoms
+/// should start with a small nonzero charge.
+///
+/// # Examples
+///
+/// ```no_run
+/// use velvet_core::prelude::*;
+/// # fn system() -> System { unimplemented!() }
+/// # fn potentials() -> Potentials { unimplemented!() }
+///
+/// let mut system = system();
+/// let potentials = potentials();
+/// // carbon walls at the bottom and top of the cell with a 1 V difference between them
+/// let bottom = Slab::new(2, 0.0, 0.1).atoms(&system);
+/// let top = Slab::new(2, 0.5, 0.6).atoms(&system);
+/// let md = MolecularDynamics::new(VelocityVerlet::new(1.0), Bussi::new(300.0, 100.0));
+/// let mut capacitor = ConstantPotential::new(md, 0.3, 6)
+///     .electrode(bottom, -0.5)
+///     .electrode(top, 0.5);
+/// capacitor.setup(&mut system, &potentials)?;
+/// for _ in 0..1000 {
+///     capacitor.propagate(&mut system, &potentials);
+/// }
+/// println!("charge on the positive electrode: {} e", capacitor.electrode_charge(1));
+/// # Ok::<(), VelvetError>(())
+/// ```
+///
+/// [`Ewald`]: crate::potentials::types::Ewald
+pub struct ConstantPotential<P: Propagator> {
+    propagator: P,
+    alpha: Float,
+    kmax: usize,
+    width: Float,
+    cutoff: Option<Float>,
+    electrodes: Vec<(Vec<usize>, Float)>,
+    positions: Vec<Vector3<Float>>,
+    inverse: Option<DMatrix<Float>>,
+    charges: Vec<Float>,
+}
+
+impl<P: Propagator> ConstantPotential<P> {
+    /// Returns a new [`ConstantPotential`] propagator wrapping `propagator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `propagator` - Propagator of the electrolyte.
+    /// * `alpha` - Splitting parameter between real and reciprocal space.
+    /// * `kmax` - Largest multiple of each reciprocal cell vector in the reciprocal sum.
+    pub fn new(propagator: P, alpha: Float, kmax: usize) -> ConstantPotential<P> {
+        ConstantPotential {
+            propagator,
+            alpha,
+            kmax,
+            width: 1.979,
+            cutoff: None,
+            electrodes: Vec::new(),
+            positions: Vec::new(),
+            inverse: None,
+            charges: Vec::new(),
+        }
+    }
+
+    /// Adds an electrode of the atoms at `indices` held at `potential` in volts.
+    pub fn electrode(mut self, indices: Vec<usize>, potential: Float) -> ConstantPotential<P> {
+        self.electrodes.push((indices, potential));
+        self.inverse = None;
+        self
+    }
+
+    /// Sets the inverse width of the Gaussian electrode charges in inverse angstroms.
+    /// Defaults to 1.979, the value used for graphite electrodes.
+    pub fn width(mut self, width: Float) -> ConstantPotential<P> {
+        self.width = width;
+        self.inverse = None;
+        self
+    }
+
+    /// Sets the cutoff radius of the real space sum. Defaults to half the shortest cell length.
+    pub fn cutoff(mut self, cutoff: Float) -> ConstantPotential<P> {
+        self.cutoff = Some(cutoff);
+        self.inverse = None;
+        self
+    }
+
+    /// Returns the wrapped propagator.
+    pub fn propagator(&self) -> &P {
+        &self.propagator
+    }
+
+    /// Returns the charge of every electrode atom from the last solution, electrode by electrode.
+    pub fn charges(&self) -> &[Float] {
+        &self.charges
+    }
+
+    /// Returns the total charge of electrode `index` from the last solution.
+    pub fn electrode_charge(&self, index: usize) -> Float {
+        let start: usize = self.electrodes[..index].iter().map(|(atoms, _)| atoms.len()).sum();
+        let end = start + self.electrodes[index].0.len();
+        self.charges.get(start..end).map_or(0.0, |charges| charges.iter().sum())
+    }
+
+    // Returns every electrode atom in order and whether each atom of the system is one.
+    fn atoms(&self, system: &System) -> (Vec<usize>, Vec<bool>) {
+        let atoms: Vec<usize> = self.electrodes.iter().flat_map(|(atoms, _)| atoms.iter().copied()).collect();
+        let mut fixed = vec![false; system.size];
+        atoms.iter().for_each(|&i| fixed[i] = true);
+        (atoms, fixed)
+    }
+
+    fn waves(&self, system: &System) -> Vec<Wave> {
+        let inv = system.cell.inverse_matrix();
+        let kmax = self.kmax as i64;
+        let mut waves = Vec::new();
+        for n1 in -kmax..=kmax {
+            for n2 in -kmax..=kmax {
+                for n3 in -kmax..=kmax {
+                    if n1 == 0 && n2 == 0 && n3 == 0 {
+                        continue;
+                    }
+                    let n = Vector3::new(n1 as Float, n2 as Float, n3 as Float);
+                    let m = inv.transpose() * n;
+                    let m2 = m.norm_squared();
+                    waves.push(Wave {
+                        m,
+                        weight: Float::exp(-PI.powi(2) * m2 / self.alpha.powi(2)) / m2,
+                    });
+                }
+            }
+        }
+        waves
+    }
+
+    fn cutoff_of(&self, system: &System) -> Float {
+        self.cutoff
+            .unwrap_or_else(|| 0.5 * system.cell.a().min(system.cell.b()).min(system.cell.c()))
+    }
+
+    // Returns the periodic potential in kcal/mol/e at separation `r` from a unit point charge.
+    fn green(&self, system: &System, waves: &[Wave], mut separation: Vector3<Float>) -> Float {
+        let prefactor = COULOMB / (PI * system.cell.volume());
+        let reciprocal: Float = waves
+            .iter()
+            .map(|wave| wave.weight * Float::cos(2.0 * PI * wave.m.dot(&separation)))
+            .sum();
+        system.cell.vector_image(&mut separation);
+        let r = separation.norm();
+        let direct = if r < self.cutoff_of(system) {
+            COULOMB * erfc(self.alpha * r) / r
+        } else {
+            0.0
+        };
+        prefactor * reciprocal + direct
+    }
+
+    // Inverts the bordered matrix of the electrode interactions and the neutrality constraint.
+    fn factor(&mut self, system: &System, atoms: &[usize]) {
+        let waves = self.waves(system);
+        let n = atoms.len();
+        let prefactor = COULOMB / (PI * system.cell.volume());
+        let diagonal = prefactor * waves.iter().map(|wave| wave.weight).sum::<Float>()
+            - COULOMB * FRAC_2_SQRT_PI * self.alpha
+            + COULOMB * FRAC_2_SQRT_PI * FRAC_1_SQRT_2 * self.width;
+        let mut matrix = DMatrix::zeros(n + 1, n + 1);
+        for (a, &i) in atoms.iter().enumerate() {
+            matrix[(a, a)] = diagonal;
+            for (b, &j) in atoms.iter().enumerate().skip(a + 1) {
+                let value = self.green(system, &waves, system.positions[i] - system.positions[j]);
+                matrix[(a, b)] = value;
+                matrix[(b, a)] = value;
+            }
+            matrix[(a, n)] = 1.0;
+            matrix[(n, a)] = 1.0;
+        }
+        let inverse = matrix
+            .try_inverse()
+            .expect("The electrode interaction matrix must be invertible.");
+        self.inverse = Some(inverse);
+        self.positions = atoms.iter().map(|&i| system.positions[i]).collect();
+    }
+
+    // Solves the electrode charges for the current electrolyte and writes them to the system.
+    fn solve(&mut self, system: &mut System) {
+        let (atoms, fixed) = self.atoms(system);
+        if atoms.is_empty() {
+            return;
+        }
+        let moved = self.positions.len() != atoms.len()
+            || atoms
+                .iter()
+                .zip(self.positions.iter())
+                .any(|(&i, pos)| (system.positions[i] - pos).norm() > 1e-4);
+        if self.inverse.is_none() || moved {
+            self.factor(system, &atoms);
+        }
+
+        let electrolyte: Vec<usize> = (0..system.size)
+            .filter(|&i| !fixed[i] && system.charge(i).abs() > Float::EPSILON)
+            .collect();
+        let waves = self.waves(system);
+        let n = atoms.len();
+        let mut rhs = DVector::zeros(n + 1);
+        let mut a = 0;
+        for (indices, potential) in &self.electrodes {
+            for &i in indices {
+                let b: Float = electrolyte
+                    .iter()
+                    .map(|&k| {
+                        system.charge(k)
+                            * self.green(system, &waves, system.positions[i] - system.positions[k])
+                    })
+                    .sum();
+                rhs[a] = potential * VOLT - b;
+                a += 1;
+            }
+        }
+        // the electrodes carry the opposite of the net charge of the electrolyte
+        rhs[n] = -electrolyte.iter().map(|&k| system.charge(k)).sum::<Float>();
+
+        let solution = self.inverse.as_ref().unwrap() * rhs;
+        self.charges = solution.iter().take(n).copied().collect();
+        for (&i, &q) in atoms.iter().zip(self.charges.iter()) {
+            system.set_charge(i, q);
+        }
+    }
+}
+
+impl<P: Propagator> Propagator for ConstantPotential<P> {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        self.solve(system);
+        self.propagator.setup(system, potentials)
+    }
+
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        self.propagator.propagate(system, potentials);
+        self.solve(system);
+    }
+
+    fn converged(&self) -> bool {
+        self.propagator.converged()
+    }
+
+    fn take_error(&mut self) -> Option<VelvetError> {
+        self.propagator.take_error()
+    }
+
+    fn state(&self) -> PropagatorState {
+        self.propagator.state()
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.propagator.restore(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConstantPotential;
+    use crate::integrators::VelocityVerlet;
+    use crate::internal::Float;
+    use crate::potentials::types::Ewald;
+    use crate::potentials::PotentialsBuilder;
+    use crate::propagators::{MolecularDynamics, Propagator};
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::thermostats::NullThermostat;
+    use nalgebra::Vector3;
+
+    // Two square walls of carbon atoms normal to z with an optional sodium ion between them.
+    fn capacitor(ion: Option<Vector3<Float>>) -> System {
+        let carbon = Species::from_id(Element::C.number() as u128, Element::C.mass(), 1e-3);
+        let sodium = Species::from_id(Element::Na.number() as u128, Element::Na.mass(), 1.0);
+        let mut positions = Vec::new();
+        for &z in [2.0, 12.0].iter() {
+            for i in 0..4 {
+                for j in 0..4 {
+                    positions.push(Vector3::new(2.5 * i as Float, 2.5 * j as Float, z));
+                }
+            }
+        }
+        let mut species = vec![carbon; positions.len()];
+        if let Some(position) = ion {
+            positions.push(position);
+            species.push(sodium);
+        }
+        System {
+            size: positions.len(),
+            cell: Cell::from_matrix(nalgebra::Matrix3::from_diagonal(&Vector3::new(10.0, 10.0, 30.0))),
+            species,
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    fn md() -> MolecularDynamics {
+        MolecularDynamics::new(VelocityVerlet::new(0.05), NullThermostat)
+    }
+
+    #[test]
+    fn applied_voltage() {
+        let mut system = capacitor(None);
+        let potentials = PotentialsBuilder::new().build();
+        let mut electrodes = ConstantPotential::new(md(), 0.35, 6)
+            .electrode((0..16).collect(), -0.5)
+            .electrode((16..32).collect(), 0.5);
+        electrodes.setup(&mut system, &potentials).unwrap();
+
+        let negative = electrodes.electrode_charge(0);
+        let positive = electrodes.electrode_charge(1);
+        assert!(positive > 0.0 && negative < 0.0);
+        assert!((positive + negative).abs() < 1e-4, "{} {}", positive, negative);
+        // every atom of a wall is equivalent
+        let charges = electrodes.charges();
+        assert!(charges[16..].iter().all(|q| (q - charges[16]).abs() < 1e-4 * positive));
+        // the solved charges are written to the atoms without changing their species
+        assert_eq!(system.charge(20), charges[20]);
+        assert_eq!(system.species[20].id(), Element::C.number() as u128);
+
+        // the charge is proportional to the voltage
+        let mut system = capacitor(None);
+        let mut doubled = ConstantPotential::new(md(), 0.35, 6)
+            .electrode((0..16).collect(), -1.0)
+            .electrode((16..32).collect(), 1.0);
+        doubled.setup(&mut system, &potentials).unwrap();
+        let ratio = doubled.electrode_charge(1) / positive;
+        assert!((ratio - 2.0).abs() < 1e-3, "{}", ratio);
+    }
+
+    #[test]
+    fn image_charge() {
+        let mut system = capacitor(Some(Vector3::new(3.75, 3.75, 4.0)));
+        let potentials = PotentialsBuilder::new()
+            .coulomb(Ewald::new(0.35, 6), 5.0, 0.5)
+            .build();
+        let mut electrodes = ConstantPotential::new(md(), 0.35, 6)
+            .electrode((0..16).collect(), 0.0)
+            .electrode((16..32).collect(), 0.0);
+        electrodes.setup(&mut system, &potentials).unwrap();
+
+        // the grounded walls screen the ion, mostly the wall it sits next to
+        let near = electrodes.electrode_charge(0);
+        let far = electrodes.electrode_charge(1);
+        assert!((near + far + 1.0).abs() < 1e-3, "{} {}", near, far);
+        assert!(near < far && near < -0.5);
+        let closest = electrodes.charges()[5];
+        assert!(electrodes.charges()[..16].iter().all(|&q| q >= closest - 1e-6));
+
+        // the charges follow the ion as it moves
+        system.positions[32].z = 7.0;
+        electrodes.propagate(&mut system, &potentials);
+        assert!(electrodes.electrode_charge(0) > near);
+        let total: Float = system.charges()[..32].iter().sum();
+        assert!((total + 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,245 @@
+//! Averages of observables over independent replicas of a simulation.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::parallel_replica::Replica;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::properties::Property;
+use crate::system::System;
+use crate::velocity_distributions::VelocityDistribution;
+
+/// Ensemble average of a property over independent replicas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnsembleEstimate {
+    /// Name of the property.
+    pub name: String,
+    /// Mean of the replica averages.
+    pub mean: Float,
+    /// Standard error of the mean from the spread of the replica averages.
+    pub standard_error: Float,
+    /// Time average of the property in each replica.
+    pub replica_means: Vec<Float>,
+}
+
+/// Runs independent replicas of a simulation and averages properties across them.
+///
+/// Each replica starts from the same configuration with velocities drawn independently from the
+/// velocity distribution, is equilibrated, and then samples the properties every `interval`
+/// steps of a production run. The time averages of separate replicas are statistically
+/// independent, so their spread gives an honest standard error of the ensemble average without
+/// correcting for the correlation between samples of a single trajectory.
+///
+/// Each replica owns potentials and a propagator constructed by the closures passed to
+/// [`run`](EnsembleAverage::run) and replicas run on separate threads with the `rayon` feature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use velvet_core::prelude::*;
+/// # fn system() -> System { unimplemented!() }
+/// # fn potentials() -> Potentials { unimplemented!() }
+///
+/// let mut ensemble = EnsembleAverage::new(8, Boltzmann::new(300.0))
+///     .equilibration(1000)
+///     .property(PotentialEnergy)
+///     .property(Pressure);
+/// let md = || MolecularDynamics::new(VelocityVerlet::new(1.0), Bussi::new(300.0, 100.0));
+/// for estimate in ensemble.run(&system(), potentials, md, 10000)? {
+///     println!("{}: {} +/- {}", estimate.name, estimate.mean, estimate.standard_error);
+/// }
+/// # Ok::<(), VelvetError>(())
+/// ```
+pub struct EnsembleAverage<V: VelocityDistribution> {
+    replicas: usize,
+    distribution: V,
+    equilibration: usize,
+    interval: usize,
+    properties: Vec<Box<dyn Property<Res = Float> + Send + Sync>>,
+}
+
+impl<V: VelocityDistribution> EnsembleAverage<V> {
+    /// Returns a new [`EnsembleAverage`] driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `replicas` - Number of independent replicas.
+    /// * `distribution` - Velocity distribution which decorrelates the replicas.
+    pub fn new(replicas: usize, distribution: V) -> EnsembleAverage<V> {
+        EnsembleAverage {
+            replicas,
+            distribution,
+            equilibration: 0,
+            interval: 10,
+            properties: Vec::new(),
+        }
+    }
+
+    /// Sets the number of steps each replica runs before sampling begins. Defaults to 0.
+    pub fn equilibration(mut self, steps: usize) -> EnsembleAverage<V> {
+        self.equilibration = steps;
+        self
+    }
+
+    /// Sets the number of steps between samples of the properties. Defaults to 10.
+    pub fn interval(mut self, steps: usize) -> EnsembleAverage<V> {
+        self.interval = steps.max(1);
+        self
+    }
+
+    /// Adds a property to average across the replicas.
+    pub fn property<P>(mut self, property: P) -> EnsembleAverage<V>
+    where
+        P: Property<Res = Float> + Send + Sync + 'static,
+    {
+        self.properties.push(Box::new(property));
+        self
+    }
+
+    /// Runs every replica for `steps` production steps and returns the ensemble average of each
+    /// property in the order they were added.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - Starting configuration shared by the replicas.
+    /// * `potentials` - Constructor for the potentials of each replica.
+    /// * `propagator` - Constructor for the propagator of each replica.
+    /// * `steps` - Number of production steps run by each replica.
+    pub fn run<F, G, P>(
+        &self,
+        system: &System,
+        potentials: F,
+        propagator: G,
+        steps: usize,
+    ) -> Result<Vec<EnsembleEstimate>, VelvetError>
+    where
+        F: Fn() -> Potentials,
+        G: Fn() -> P,
+        P: Propagator + 'static,
+    {
+        let mut replicas: Vec<Replica> = (0..self.replicas)
+            .map(|_| {
+                let mut copy = system.clone();
+                self.distribution.apply(&mut copy);
+                Replica::new(copy, potentials(), Box::new(propagator()))
+            })
+            .collect::<Result<_, VelvetError>>()?;
+
+        #[cfg(not(feature = "rayon"))]
+        let means: Vec<Vec<Float>> = replicas.iter_mut().map(|replica| self.sample(replica, steps)).collect();
+        #[cfg(feature = "rayon")]
+        let means: Vec<Vec<Float>> = replicas.par_iter_mut().map(|replica| self.sample(replica, steps)).collect();
+
+        Ok(self
+            .properties
+            .iter()
+            .enumerate()
+            .map(|(k, property)| {
+                let replica_means: Vec<Float> = means.iter().map(|m| m[k]).collect();
+                let m = replica_means.len() as Float;
+                let mean = replica_means.iter().sum::<Float>() / m;
+                let standard_error = if replica_means.len() < 2 {
+                    Float::INFINITY
+                } else {
+                    let variance = replica_means.iter().map(|x| (x - mean).powi(2)).sum::<Float>() / (m - 1.0);
+                    Float::sqrt(variance / m)
+                };
+                EnsembleEstimate {
+                    name: property.name(),
+                    mean,
+                    standard_error,
+                    replica_means,
+                }
+            })
+            .collect())
+    }
+
+    // Equilibrates a replica and returns the time average of each property over its production run.
+    fn sample(&self, replica: &mut Replica, steps: usize) -> Vec<Float> {
+        replica.advance(self.equilibration);
+        let mut sums = vec![0.0; self.properties.len()];
+        let mut samples = 0;
+        let mut remaining = steps;
+        while remaining > 0 {
+            let block = remaining.min(self.interval);
+            replica.advance(block);
+            remaining -= block;
+            for (sum, property) in sums.iter_mut().zip(self.properties.iter()) {
+                *sum += property.calculate(&replica.system, &replica.potentials);
+            }
+            samples += 1;
+        }
+        sums.iter().map(|sum| sum / samples.max(1) as Float).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EnsembleAverage;
+    use crate::integrators::VelocityVerlet;
+    use crate::internal::Float;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::PotentialsBuilder;
+    use crate::propagators::MolecularDynamics;
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::temperature::Temperature;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::thermostats::Berendsen;
+    use crate::velocity_distributions::Boltzmann;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn dilute_gas() {
+        let argon = Species::new(39.948, 0.0);
+        let mut positions = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    positions.push(Vector3::new(i as Float, j as Float, k as Float) * 6.0);
+                }
+            }
+        }
+        let system = System {
+            size: positions.len(),
+            cell: Cell::cubic(18.0),
+            species: vec![argon; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        };
+        let potentials = || {
+            PotentialsBuilder::new()
+                .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+                .build()
+        };
+        let md = || MolecularDynamics::new(VelocityVerlet::new(0.05), Berendsen::new(300.0, 10.0));
+        let ensemble = EnsembleAverage::new(4, Boltzmann::new(300.0))
+            .equilibration(20)
+            .interval(5)
+            .property(Temperature)
+            .property(PotentialEnergy);
+        let estimates = ensemble.run(&system, potentials, md, 100).unwrap();
+
+        assert_eq!(estimates.len(), 2);
+        assert_eq!(estimates[0].name, "temperature");
+        assert_eq!(estimates[0].replica_means.len(), 4);
+        // the replicas start from independent velocities
+        assert!(estimates[0].replica_means.windows(2).any(|w| w[0] != w[1]));
+        assert!(estimates[0].standard_error > 0.0);
+        // the thermostat holds every replica near its target
+        assert!((estimates[0].mean - 300.0).abs() < 30.0, "{}", estimates[0].mean);
+        assert!(estimates[1].mean < 0.0);
+
+        let means = &estimates[1].replica_means;
+        let mean = means.iter().sum::<Float>() / 4.0;
+        let variance = means.iter().map(|x| (x - mean).powi(2)).sum::<Float>() / 3.0;
+        assert!((estimates[1].mean - mean).abs() < 1e-5);
+        assert!((estimates[1].standard_error - (variance / 4.0).sqrt()).abs() < 1e-5);
+    }
+}
@@ -0,0 +1,118 @@
+//! Errors reported by fallible operations across the crate.
+
+use std::io;
+
+use thiserror::Error;
+
+/// Error returned by readers, writers, validation, simulation setup, and simulation runs.
+#[derive(Debug, Error)]
+pub enum VelvetError {
+    /// Failure to read or write a file or stream.
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    /// Malformed data in an external format.
+    #[error("{message} in {format} data")]
+    Parse {
+        /// Name of the format being read.
+        format: &'static str,
+        /// Description of the problem.
+        message: String,
+    },
+    /// Atom index which does not exist in a system.
+    #[error("{entry} references atom {index} of a system with {size} atoms")]
+    IndexOutOfRange {
+        /// Kind of entry which holds the index, such as `bond` or `angle`.
+        entry: &'static str,
+        /// The offending index.
+        index: usize,
+        /// Number of atoms in the system.
+        size: usize,
+    },
+    /// Per-atom data whose length differs from the number of atoms.
+    #[error("expected {expected} {field} for the atoms of the system, found {found}")]
+    LengthMismatch {
+        /// Name of the per-atom data.
+        field: &'static str,
+        /// Number of atoms in the system.
+        expected: usize,
+        /// Length of the data.
+        found: usize,
+    },
+    /// Iterative algorithm which did not converge during a simulation step.
+    #[error("{algorithm} failed to converge in {iterations} iterations")]
+    Convergence {
+        /// Name of the algorithm, such as `SHAKE` or `RATTLE`.
+        algorithm: &'static str,
+        /// Number of iterations performed.
+        iterations: usize,
+    },
+    /// Group name which is not defined in the topology of a system.
+    #[error("the system has no group named `{0}`")]
+    UnknownGroup(String),
+    /// Parameter of a builder or constructor outside of its valid range.
+    #[error("invalid {parameter}: {message}")]
+    InvalidParameter {
+        /// Name of the parameter.
+        parameter: &'static str,
+        /// Description of the valid range.
+        message: String,
+    },
+}
+
+impl VelvetError {
+    /// Returns a [`VelvetError::Parse`] error for `format` with `message`.
+    pub fn parse<T: Into<String>>(format: &'static str, message: T) -> VelvetError {
+        VelvetError::Parse {
+            format,
+            message: message.into(),
+        }
+    }
+
+    /// Returns a [`VelvetError::InvalidParameter`] error for `parameter` with `message`.
+    pub fn invalid<T: Into<String>>(parameter: &'static str, message: T) -> VelvetError {
+        VelvetError::InvalidParameter {
+            parameter,
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(feature = "hdf5-output")]
+impl From<hdf5::Error> for VelvetError {
+    fn from(err: hdf5::Error) -> VelvetError {
+        VelvetError::Io(io::Error::new(io::ErrorKind::Other, err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VelvetError;
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn display() {
+        let err = VelvetError::parse("XYZ", "Invalid value `x`");
+        assert_eq!(err.to_string(), "Invalid value `x` in XYZ data");
+        let err = VelvetError::IndexOutOfRange {
+            entry: "bond",
+            index: 7,
+            size: 3,
+        };
+        assert_eq!(err.to_string(), "bond references atom 7 of a system with 3 atoms");
+        let err = VelvetError::Convergence {
+            algorithm: "SHAKE",
+            iterations: 500,
+        };
+        assert_eq!(err.to_string(), "SHAKE failed to converge in 500 iterations");
+        let err = VelvetError::UnknownGroup("fixed".to_string());
+        assert_eq!(err.to_string(), "the system has no group named `fixed`");
+        let err = VelvetError::invalid("chain length", "a Nose-Hoover chain needs at least one thermostat");
+        assert_eq!(
+            err.to_string(),
+            "invalid chain length: a Nose-Hoover chain needs at least one thermostat"
+        );
+        let err = VelvetError::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert!(err.source().is_some());
+    }
+}
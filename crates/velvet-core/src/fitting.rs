@@ -0,0 +1,502 @@
+//! Fitting of potential parameters to reference energies and forces.
+
+use nalgebra::{DMatrix, DVector, SymmetricEigen, Vector3};
+use rand_distr::{Distribution, StandardNormal};
+
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::energy::PotentialEnergy;
+use crate::properties::forces::Forces;
+use crate::properties::Property;
+use crate::random::shared_rng;
+use crate::system::System;
+
+/// Configuration of a system with its reference potential energy and forces, such as a frame
+/// computed with an electronic structure method.
+#[derive(Clone, Debug)]
+pub struct Reference {
+    /// Configuration of the atoms.
+    pub system: System,
+    /// Reference potential energy of the configuration, if it is fit.
+    pub energy: Option<Float>,
+    /// Reference force on each atom, if they are fit.
+    pub forces: Option<Vec<Vector3<Float>>>,
+}
+
+/// Outcome of a fit.
+#[derive(Clone, Debug)]
+pub struct FitResult {
+    /// Best parameters found.
+    pub parameters: Vec<Float>,
+    /// Sum of the squared residuals at the best parameters.
+    pub cost: Float,
+    /// Number of iterations or generations of the optimizer.
+    pub iterations: usize,
+    /// Number of times the residuals were evaluated.
+    pub evaluations: usize,
+}
+
+/// Shared behavior for algorithms which minimize the sum of squared residuals.
+pub trait Optimizer {
+    /// Returns the parameters which minimize the sum of the squares of `residuals`, starting
+    /// from `initial`.
+    fn minimize(&self, residuals: &dyn Fn(&[Float]) -> Vec<Float>, initial: &[Float]) -> FitResult;
+}
+
+/// Least squares problem which fits the parameters of a model to reference data.
+///
+/// The model is a function from a slice of parameters to the [`Potentials`] they define, so any
+/// combination of potentials can be fit and parameters can be shared between them or mapped,
+/// e.g. through an exponential to keep them positive. The residuals are the differences between
+/// the model and reference energies per atom and each component of the model and reference
+/// forces, scaled by the square roots of their weights. Reference energies must be measured
+/// from the same zero as the model, e.g. as cohesive energies for pair potentials.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// // argon dimers labeled with their energies at three separations
+/// let references: Vec<Reference> = [(3.6, -0.196), (4.0, -0.224), (5.0, -0.085)]
+///     .iter()
+///     .map(|&(r, energy)| Reference {
+///         system: System {
+///             size: 2,
+///             cell: Cell::cubic(30.0),
+///             species: vec![argon; 2],
+///             positions: vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new(5.0 + r, 5.0, 5.0)],
+///             velocities: vec![Vector3::zeros(); 2],
+///             topology: Topology::default(),
+///         },
+///         energy: Some(energy),
+///         forces: None,
+///     })
+///     .collect();
+/// let problem = FitProblem::new(references, move |p: &[_]| {
+///     PotentialsBuilder::new()
+///         .pair(LennardJones::new(p[0], p[1]), (argon, argon), 8.5, 1.0)
+///         .build()
+/// });
+/// let fit = problem.fit(&LevenbergMarquardt::new(), &[0.2, 3.5]);
+/// ```
+pub struct FitProblem<M> {
+    references: Vec<Reference>,
+    model: M,
+    energy_weight: Float,
+    force_weight: Float,
+}
+
+impl<M: Fn(&[Float]) -> Potentials> FitProblem<M> {
+    /// Returns a new [`FitProblem`] with unit weights.
+    ///
+    /// # Arguments
+    ///
+    /// * `references` - Configurations with their reference energies and forces.
+    /// * `model` - Function which builds the potentials from a slice of parameters.
+    pub fn new(references: Vec<Reference>, model: M) -> FitProblem<M> {
+        FitProblem {
+            references,
+            model,
+            energy_weight: 1.0,
+            force_weight: 1.0,
+        }
+    }
+
+    /// Sets the weight of the squared energy residuals.
+    pub fn energy_weight(mut self, weight: Float) -> FitProblem<M> {
+        self.energy_weight = weight;
+        self
+    }
+
+    /// Sets the weight of the squared force residuals.
+    pub fn force_weight(mut self, weight: Float) -> FitProblem<M> {
+        self.force_weight = weight;
+        self
+    }
+
+    /// Returns the weighted residuals of the model with `parameters`.
+    pub fn residuals(&self, parameters: &[Float]) -> Vec<Float> {
+        let mut potentials = (self.model)(parameters);
+        let (energy_scale, force_scale) = (self.energy_weight.sqrt(), self.force_weight.sqrt());
+        let mut residuals = Vec::new();
+        for reference in &self.references {
+            let system = &reference.system;
+            potentials.setup(system);
+            if let Some(energy) = reference.energy {
+                let model = PotentialEnergy.calculate(system, &potentials);
+                residuals.push(energy_scale * (model - energy) / system.size as Float);
+            }
+            if let Some(forces) = &reference.forces {
+                let model = Forces.calculate(system, &potentials);
+                for (model, force) in model.iter().zip(forces.iter()) {
+                    residuals.extend((model - force).iter().map(|difference| force_scale * difference));
+                }
+            }
+        }
+        residuals
+    }
+
+    /// Returns the sum of the squared residuals of the model with `parameters`.
+    pub fn cost(&self, parameters: &[Float]) -> Float {
+        self.residuals(parameters).iter().map(|r| r * r).sum()
+    }
+
+    /// Fits the parameters with `optimizer` starting from `initial`.
+    pub fn fit<O: Optimizer>(&self, optimizer: &O, initial: &[Float]) -> FitResult {
+        optimizer.minimize(&|parameters: &[Float]| self.residuals(parameters), initial)
+    }
+}
+
+/// Levenberg-Marquardt nonlinear least squares.
+///
+/// Each iteration takes a damped Gauss-Newton step with the Jacobian of the residuals estimated
+/// by central differences, so every iteration costs two evaluations per parameter. The damping
+/// grows until a step lowers the cost and shrinks after each accepted step. The fit ends when an
+/// accepted step lowers the cost by less than the relative tolerance. Convergence is fast near
+/// the minimum but only to the nearest local minimum of the initial parameters.
+///
+/// # References
+///
+/// [1] Marquardt, Donald W. "An algorithm for least-squares estimation of nonlinear parameters." Journal of the Society for Industrial and Applied Mathematics 11.2 (1963): 431-441.
+#[derive(Clone, Copy, Debug)]
+pub struct LevenbergMarquardt {
+    max_iterations: usize,
+    tolerance: Float,
+    step: Float,
+}
+
+impl LevenbergMarquardt {
+    /// Returns a new [`LevenbergMarquardt`] optimizer with up to 100 iterations.
+    pub fn new() -> LevenbergMarquardt {
+        LevenbergMarquardt {
+            max_iterations: 100,
+            tolerance: 1e-6,
+            step: 1e-3,
+        }
+    }
+
+    /// Sets the maximum number of iterations.
+    pub fn max_iterations(mut self, iterations: usize) -> LevenbergMarquardt {
+        self.max_iterations = iterations;
+        self
+    }
+
+    /// Sets the relative decrease of the cost below which the fit has converged.
+    pub fn tolerance(mut self, tolerance: Float) -> LevenbergMarquardt {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Sets the finite difference step relative to the magnitude of each parameter.
+    pub fn step(mut self, step: Float) -> LevenbergMarquardt {
+        self.step = step;
+        self
+    }
+}
+
+impl Default for LevenbergMarquardt {
+    fn default() -> LevenbergMarquardt {
+        LevenbergMarquardt::new()
+    }
+}
+
+impl Optimizer for LevenbergMarquardt {
+    fn minimize(&self, residuals: &dyn Fn(&[Float]) -> Vec<Float>, initial: &[Float]) -> FitResult {
+        let n = initial.len();
+        let mut parameters = initial.to_vec();
+        let mut current = DVector::from_vec(residuals(&parameters));
+        let mut cost = current.norm_squared();
+        let mut evaluations = 1;
+        let mut damping: Float = 1e-3;
+        let mut iterations = 0;
+        while iterations < self.max_iterations && cost > 0.0 {
+            iterations += 1;
+            let mut jacobian = DMatrix::zeros(current.len(), n);
+            for j in 0..n {
+                let h = self.step * parameters[j].abs().max(1.0);
+                let mut forward = parameters.clone();
+                forward[j] += h;
+                let mut backward = parameters.clone();
+                backward[j] -= h;
+                let difference = DVector::from_vec(residuals(&forward)) - DVector::from_vec(residuals(&backward));
+                jacobian.set_column(j, &(difference / (2.0 * h)));
+                evaluations += 2;
+            }
+            let normal = jacobian.transpose() * &jacobian;
+            let gradient = jacobian.transpose() * &current;
+
+            // raise the damping until a step lowers the cost
+            let mut decrease = None;
+            while decrease.is_none() && damping < 1e10 {
+                let mut damped = normal.clone();
+                for j in 0..n {
+                    damped[(j, j)] += damping * normal[(j, j)].max(Float::EPSILON);
+                }
+                let step = match damped.cholesky() {
+                    Some(cholesky) => cholesky.solve(&-gradient.clone()),
+                    None => {
+                        damping *= 10.0;
+                        continue;
+                    }
+                };
+                let trial: Vec<Float> = parameters.iter().zip(step.iter()).map(|(p, s)| p + s).collect();
+                let trial_residuals = DVector::from_vec(residuals(&trial));
+                evaluations += 1;
+                let trial_cost = trial_residuals.norm_squared();
+                if trial_cost < cost {
+                    decrease = Some((cost - trial_cost) / cost);
+                    parameters = trial;
+                    current = trial_residuals;
+                    cost = trial_cost;
+                    damping = (damping / 10.0).max(1e-12);
+                } else {
+                    damping *= 10.0;
+                }
+            }
+            match decrease {
+                Some(decrease) if decrease > self.tolerance => {}
+                _ => break,
+            }
+        }
+        FitResult {
+            parameters,
+            cost,
+            iterations,
+            evaluations,
+        }
+    }
+}
+
+/// Covariance matrix adaptation evolution strategy.
+///
+/// A population of parameters is sampled each generation from a multivariate normal
+/// distribution whose mean moves toward the best samples and whose covariance and step size
+/// adapt to the shape of the cost function. No derivatives are needed and the search is robust
+/// to noisy or rugged cost functions and to poor initial parameters, at the price of many more
+/// evaluations than [`LevenbergMarquardt`]. The fit ends when the step size along every
+/// direction falls below the tolerance.
+///
+/// # References
+///
+/// [1] Hansen, Nikolaus, and Andreas Ostermeier. "Completely derandomized self-adaptation in evolution strategies." Evolutionary Computation 9.2 (2001): 159-195.
+#[derive(Clone, Copy, Debug)]
+pub struct CmaEs {
+    sigma: Float,
+    population: Option<usize>,
+    max_generations: usize,
+    tolerance: Float,
+}
+
+impl CmaEs {
+    /// Returns a new [`CmaEs`] optimizer with up to 1000 generations.
+    ///
+    /// # Arguments
+    ///
+    /// * `sigma` - Initial standard deviation of the samples around the initial parameters.
+    pub fn new(sigma: Float) -> CmaEs {
+        CmaEs {
+            sigma,
+            population: None,
+            max_generations: 1000,
+            tolerance: 1e-6,
+        }
+    }
+
+    /// Sets the number of samples per generation, which defaults to `4 + 3 ln(n)` for `n` parameters.
+    pub fn population(mut self, population: usize) -> CmaEs {
+        self.population = Some(population.max(2));
+        self
+    }
+
+    /// Sets the maximum number of generations.
+    pub fn max_generations(mut self, generations: usize) -> CmaEs {
+        self.max_generations = generations;
+        self
+    }
+
+    /// Sets the step size below which the fit has converged.
+    pub fn tolerance(mut self, tolerance: Float) -> CmaEs {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl Optimizer for CmaEs {
+    fn minimize(&self, residuals: &dyn Fn(&[Float]) -> Vec<Float>, initial: &[Float]) -> FitResult {
+        // failed evaluations rank below every other sample
+        let cost = |x: &DVector<Float>| {
+            let cost: Float = residuals(x.as_slice()).iter().map(|r| r * r).sum();
+            if cost.is_nan() {
+                Float::INFINITY
+            } else {
+                cost
+            }
+        };
+        let n = initial.len();
+        let nf = n as Float;
+        let lambda = self.population.unwrap_or(4 + (3.0 * nf.ln()).floor() as usize).max(2);
+        let mu = lambda / 2;
+        let mut weights: Vec<Float> = (0..mu)
+            .map(|i| (mu as Float + 0.5).ln() - ((i + 1) as Float).ln())
+            .collect();
+        let total: Float = weights.iter().sum();
+        weights.iter_mut().for_each(|w| *w /= total);
+        let mueff = 1.0 / weights.iter().map(|w| w * w).sum::<Float>();
+
+        // learning rates of the evolution paths, the covariance, and the step size
+        let cc = (4.0 + mueff / nf) / (nf + 4.0 + 2.0 * mueff / nf);
+        let cs = (mueff + 2.0) / (nf + mueff + 5.0);
+        let c1 = 2.0 / ((nf + 1.3).powi(2) + mueff);
+        let cmu = (1.0 - c1).min(2.0 * (mueff - 2.0 + 1.0 / mueff) / ((nf + 2.0).powi(2) + mueff));
+        let damps = 1.0 + 2.0 * (((mueff - 1.0) / (nf + 1.0)).sqrt() - 1.0).max(0.0) + cs;
+        // expected norm of a standard normal vector
+        let chi = nf.sqrt() * (1.0 - 1.0 / (4.0 * nf) + 1.0 / (21.0 * nf * nf));
+
+        let mut rng = shared_rng();
+        let mut mean = DVector::from_column_slice(initial);
+        let mut sigma = self.sigma;
+        let mut covariance: DMatrix<Float> = DMatrix::identity(n, n);
+        let mut basis: DMatrix<Float> = DMatrix::identity(n, n);
+        let mut scales: DVector<Float> = DVector::from_element(n, 1.0);
+        let mut path_c: DVector<Float> = DVector::zeros(n);
+        let mut path_s: DVector<Float> = DVector::zeros(n);
+        let mut best = (mean.clone(), cost(&mean));
+        let mut evaluations = 1;
+        let mut generations = 0;
+        while generations < self.max_generations {
+            generations += 1;
+            let mut samples: Vec<(Float, DVector<Float>)> = (0..lambda)
+                .map(|_| {
+                    let z: DVector<Float> = DVector::from_fn(n, |_, _| StandardNormal.sample(&mut rng));
+                    let y = &basis * z.component_mul(&scales);
+                    (cost(&(&mean + sigma * &y)), y)
+                })
+                .collect();
+            evaluations += lambda;
+            samples.sort_by(|a, b| a.0.total_cmp(&b.0));
+            if samples[0].0 < best.1 {
+                best = (&mean + sigma * &samples[0].1, samples[0].0);
+            }
+
+            let step = samples
+                .iter()
+                .zip(weights.iter())
+                .fold(DVector::zeros(n), |sum, ((_, y), w)| sum + *w * y);
+            mean += sigma * &step;
+
+            // cumulate the evolution paths
+            let inverse_sqrt = &basis * DMatrix::from_diagonal(&scales.map(|d| 1.0 / d)) * basis.transpose();
+            path_s = (1.0 - cs) * &path_s + (cs * (2.0 - cs) * mueff).sqrt() * (&inverse_sqrt * &step);
+            let norm = path_s.norm();
+            let stalled = norm / (1.0 - (1.0 - cs).powi(2 * generations as i32)).sqrt() / chi >= 1.4 + 2.0 / (nf + 1.0);
+            let h = if stalled { 0.0 } else { 1.0 };
+            path_c = (1.0 - cc) * &path_c + h * (cc * (2.0 - cc) * mueff).sqrt() * &step;
+
+            // adapt the covariance and step size
+            let mut rank_mu = DMatrix::zeros(n, n);
+            for ((_, y), w) in samples.iter().zip(weights.iter()) {
+                rank_mu += *w * y * y.transpose();
+            }
+            let rank_one = &path_c * path_c.transpose() + (1.0 - h) * cc * (2.0 - cc) * &covariance;
+            covariance = (1.0 - c1 - cmu) * &covariance + c1 * rank_one + cmu * rank_mu;
+            covariance = (&covariance + covariance.transpose()) * 0.5;
+            sigma *= ((cs / damps) * (norm / chi - 1.0)).exp();
+            let eigen = SymmetricEigen::new(covariance.clone());
+            scales = eigen.eigenvalues.map(|e| e.max(Float::EPSILON).sqrt());
+            basis = eigen.eigenvectors;
+
+            if sigma * scales.max() < self.tolerance {
+                break;
+            }
+        }
+        let final_cost = cost(&mean);
+        evaluations += 1;
+        if final_cost < best.1 {
+            best = (mean, final_cost);
+        }
+        FitResult {
+            parameters: best.0.iter().copied().collect(),
+            cost: best.1,
+            iterations: generations,
+            evaluations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CmaEs, FitProblem, LevenbergMarquardt, Optimizer, Reference};
+    use crate::internal::Float;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::forces::Forces;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // argon dimers and trimers labeled with the energies and forces of the true potential
+    fn problem() -> FitProblem<impl Fn(&[Float]) -> Potentials> {
+        let argon = Species::new(39.948, 0.0);
+        let model = move |p: &[Float]| {
+            PotentialsBuilder::new()
+                .pair(LennardJones::new(p[0], p[1]), (argon, argon), 8.5, 1.0)
+                .build()
+        };
+        let mut truth = model(&[0.238, 3.4]);
+        let references = [3.5, 3.9, 4.4, 5.2]
+            .iter()
+            .map(|&r: &Float| {
+                let positions = vec![
+                    Vector3::new(5.0, 5.0, 5.0),
+                    Vector3::new(5.0 + r, 5.0, 5.0),
+                    Vector3::new(5.0, 5.0 + 1.1 * r, 5.0),
+                ];
+                let system = System {
+                    size: 3,
+                    cell: Cell::cubic(30.0),
+                    species: vec![argon; 3],
+                    velocities: vec![Vector3::zeros(); 3],
+                    positions,
+                    topology: Topology::default(),
+                };
+                truth.setup(&system);
+                Reference {
+                    energy: Some(PotentialEnergy.calculate(&system, &truth)),
+                    forces: Some(Forces.calculate(&system, &truth)),
+                    system,
+                }
+            })
+            .collect();
+        FitProblem::new(references, model).force_weight(0.1)
+    }
+
+    #[test]
+    fn levenberg_marquardt() {
+        let problem = problem();
+        assert!(problem.cost(&[0.238, 3.4]) < 1e-10);
+        let fit = problem.fit(&LevenbergMarquardt::new(), &[0.3, 3.2]);
+        assert!((fit.parameters[0] - 0.238).abs() < 1e-3, "{:?}", fit);
+        assert!((fit.parameters[1] - 3.4).abs() < 1e-3, "{:?}", fit);
+        assert!(fit.cost < problem.cost(&[0.3, 3.2]));
+    }
+
+    #[test]
+    fn cma_es() {
+        let problem = problem();
+        let fit = problem.fit(&CmaEs::new(0.1).tolerance(1e-5), &[0.3, 3.2]);
+        assert!((fit.parameters[0] - 0.238).abs() < 1e-3, "{:?}", fit);
+        assert!((fit.parameters[1] - 3.4).abs() < 1e-2, "{:?}", fit);
+
+        // the optimizers minimize any residuals, here of the Rosenbrock function
+        let rosenbrock = |x: &[Float]| vec![1.0 - x[0], 10.0 * (x[1] - x[0] * x[0])];
+        let fit = CmaEs::new(0.5).minimize(&rosenbrock, &[-1.0, 1.0]);
+        assert!((fit.parameters[0] - 1.0).abs() < 1e-2 && (fit.parameters[1] - 1.0).abs() < 1e-2);
+    }
+}
@@ -0,0 +1,381 @@
+//! Boundary-driven flows through momentum sources in slabs of the cell.
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::checkpoint::PropagatorState;
+use crate::colvars::{Bias, BiasEvaluation};
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::properties::IntrinsicProperty;
+use crate::system::System;
+
+/// Slab of the cell between two fractional coordinates along one of its vectors.
+///
+/// The slab wraps around the periodic boundary when `low` is greater than `high`, and spans
+/// the whole cell when `low` equals `high`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slab {
+    axis: usize,
+    low: Float,
+    high: Float,
+}
+
+impl Slab {
+    /// Returns a new [`Slab`].
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - Cell vector normal to the slab, 0 for `a`, 1 for `b`, and 2 for `c`.
+    /// * `low` - Fractional coordinate of the lower face.
+    /// * `high` - Fractional coordinate of the upper face.
+    pub fn new(axis: usize, low: Float, high: Float) -> Slab {
+        assert!(axis < 3, "The slab axis must be 0, 1, or 2.");
+        Slab {
+            axis,
+            low: low - low.floor(),
+            high: high - high.floor(),
+        }
+    }
+
+    /// Returns true if `position` lies inside the slab.
+    pub fn contains(&self, system: &System, position: &Vector3<Float>) -> bool {
+        let s = system.cell.fractional(position)[self.axis];
+        let s = s - s.floor();
+        if self.low < self.high {
+            s >= self.low && s < self.high
+        } else if self.low > self.high {
+            s >= self.low || s < self.high
+        } else {
+            true
+        }
+    }
+
+    /// Returns the indices of the atoms inside the slab.
+    pub fn atoms(&self, system: &System) -> Vec<usize> {
+        (0..system.size)
+            .filter(|&i| self.contains(system, &system.positions[i]))
+            .collect()
+    }
+}
+
+/// Constant force on every atom inside a slab which pumps fluid through a channel.
+///
+/// A pump spanning the whole cell is a uniform body force which drives Poiseuille flow between
+/// walls, while a pump confined to a slab drives flow through the rest of the channel from a
+/// reservoir. The force is not conservative in a periodic cell, so the pump contributes no
+/// energy or virial and its collective variable is the number of atoms it acts on. Pumps are added to
+/// [`Potentials`] as a [`Bias`] and combine with any thermostat, which removes the heat produced
+/// by the flow.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// // push atoms in the first tenth of the cell along x
+/// let pump = Pump::new(Slab::new(0, 0.0, 0.1), Vector3::new(0.01, 0.0, 0.0));
+/// let potentials = PotentialsBuilder::new().bias(pump).build();
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Pump {
+    slab: Slab,
+    force: Vector3<Float>,
+}
+
+impl Pump {
+    /// Returns a new [`Pump`] which applies `force` in kcal/mol/angstrom to each atom inside `slab`.
+    pub fn new(slab: Slab, force: Vector3<Float>) -> Pump {
+        Pump { slab, force }
+    }
+}
+
+impl Bias for Pump {
+    fn evaluate(&self, system: &System) -> BiasEvaluation {
+        let forces = system
+            .positions
+            .iter()
+            .map(|pos| {
+                if self.slab.contains(system, pos) {
+                    self.force
+                } else {
+                    Vector3::zeros()
+                }
+            })
+            .collect();
+        BiasEvaluation {
+            energy: 0.0,
+            forces,
+            virial: Matrix3::zeros(),
+        }
+    }
+
+    fn colvar(&self, system: &System) -> Float {
+        self.slab.atoms(system).len() as Float
+    }
+}
+
+/// Propagator which holds the streaming velocity of atoms in slabs at target values.
+///
+/// After each step of the wrapped propagator the center of mass velocity of the atoms inside
+/// each slab is relaxed toward its target by shifting every atom in the slab by the same
+/// velocity, which leaves their thermal motion untouched. Targets on walls moving in opposite
+/// directions drive Couette flow, and a target on a slab of fluid drives flow through the rest of
+/// the channel. A relaxation of 1 sets the streaming velocity exactly on every step.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// // shear the fluid between walls near z = 0 and z = 0.5 moving in opposite directions
+/// let md = MolecularDynamics::new(VelocityVerlet::new(1.0), Bussi::new(300.0, 100.0));
+/// let couette = Driven::new(md)
+///     .target(Slab::new(2, 0.95, 0.05), Vector3::new(-0.005, 0.0, 0.0), 1.0)
+///     .target(Slab::new(2, 0.45, 0.55), Vector3::new(0.005, 0.0, 0.0), 1.0);
+/// ```
+pub struct Driven<P: Propagator> {
+    propagator: P,
+    targets: Vec<(Slab, Vector3<Float>, Float)>,
+}
+
+impl<P: Propagator> Driven<P> {
+    /// Returns a new [`Driven`] propagator wrapping `propagator`.
+    pub fn new(propagator: P) -> Driven<P> {
+        Driven {
+            propagator,
+            targets: Vec::new(),
+        }
+    }
+
+    /// Relaxes the streaming velocity of the atoms in `slab` toward `velocity` in angstrom/femtosecond.
+    ///
+    /// The `relaxation` is the fraction of the difference removed on each step.
+    pub fn target(mut self, slab: Slab, velocity: Vector3<Float>, relaxation: Float) -> Driven<P> {
+        self.targets.push((slab, velocity, relaxation.clamp(0.0, 1.0)));
+        self
+    }
+
+    /// Returns the wrapped propagator.
+    pub fn propagator(&self) -> &P {
+        &self.propagator
+    }
+
+    fn drive(&self, system: &mut System) {
+        for (slab, target, relaxation) in &self.targets {
+            let atoms = slab.atoms(system);
+            let mass: Float = atoms.iter().map(|&i| system.species[i].mass()).sum();
+            if mass <= 0.0 {
+                continue;
+            }
+            let momentum: Vector3<Float> = atoms
+                .iter()
+                .map(|&i| system.species[i].mass() * system.velocities[i])
+                .sum();
+            let shift = (target - momentum / mass) * *relaxation;
+            atoms.iter().for_each(|&i| system.velocities[i] += shift);
+        }
+    }
+}
+
+impl<P: Propagator> Propagator for Driven<P> {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        self.propagator.setup(system, potentials)?;
+        self.drive(system);
+        Ok(())
+    }
+
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        self.propagator.propagate(system, potentials);
+        self.drive(system);
+    }
+
+    fn converged(&self) -> bool {
+        self.propagator.converged()
+    }
+
+    fn take_error(&mut self) -> Option<VelvetError> {
+        self.propagator.take_error()
+    }
+
+    fn state(&self) -> PropagatorState {
+        self.propagator.state()
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.propagator.restore(state)
+    }
+}
+
+/// Streaming velocity and density in bins across the cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Profile {
+    /// Fractional coordinate of the center of each bin.
+    pub centers: Vec<Float>,
+    /// Center of mass velocity of the atoms in each bin in angstrom/femtosecond.
+    pub velocities: Vec<Vector3<Float>>,
+    /// Number of atoms in each bin.
+    pub counts: Vec<usize>,
+    /// Mass density of each bin in grams/mole/cubic angstrom.
+    pub densities: Vec<Float>,
+}
+
+/// Instantaneous profile of the streaming velocity and density along one cell vector.
+///
+/// The cell is divided into equal bins along the axis, and each bin reports the center of mass
+/// velocity and the density of the atoms inside it. Averaged over a run this measures the
+/// velocity field of a flow, such as the parabolic profile of Poiseuille flow or the linear
+/// profile of Couette flow.
+#[derive(Clone, Copy, Debug)]
+pub struct VelocityProfile {
+    axis: usize,
+    bins: usize,
+}
+
+impl VelocityProfile {
+    /// Returns a new [`VelocityProfile`] with `bins` bins along cell vector `axis`.
+    pub fn new(axis: usize, bins: usize) -> VelocityProfile {
+        assert!(axis < 3, "The profile axis must be 0, 1, or 2.");
+        VelocityProfile {
+            axis,
+            bins: bins.max(1),
+        }
+    }
+}
+
+impl IntrinsicProperty for VelocityProfile {
+    type Res = Profile;
+
+    fn calculate_intrinsic(&self, system: &System) -> Self::Res {
+        let mut momenta = vec![Vector3::zeros(); self.bins];
+        let mut masses = vec![0.0; self.bins];
+        let mut counts = vec![0; self.bins];
+        for i in 0..system.size {
+            let s = system.cell.fractional(&system.positions[i])[self.axis];
+            let s = s - s.floor();
+            let bin = ((s * self.bins as Float) as usize).min(self.bins - 1);
+            let mass = system.species[i].mass();
+            momenta[bin] += mass * system.velocities[i];
+            masses[bin] += mass;
+            counts[bin] += 1;
+        }
+        let volume = system.cell.volume() / self.bins as Float;
+        Profile {
+            centers: (0..self.bins).map(|k| (k as Float + 0.5) / self.bins as Float).collect(),
+            velocities: momenta
+                .iter()
+                .zip(masses.iter())
+                .map(|(p, &m)| if m > 0.0 { p / m } else { Vector3::zeros() })
+                .collect(),
+            counts,
+            densities: masses.iter().map(|m| m / volume).collect(),
+        }
+    }
+
+    fn name(&self) -> String {
+        "velocity_profile".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Driven, Pump, Slab, VelocityProfile};
+    use crate::colvars::Bias;
+    use crate::integrators::VelocityVerlet;
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::propagators::{MolecularDynamics, Propagator};
+    use crate::properties::IntrinsicProperty;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::thermostats::NullThermostat;
+    use nalgebra::Vector3;
+
+    // ideal gas on a grid along z with a shear velocity along x
+    fn gas() -> System {
+        let argon = Species::new(39.948, 0.0);
+        let positions: Vec<Vector3<Float>> = (0..20)
+            .map(|i| Vector3::new(0.5 * i as Float, 1.0, 0.25 + 0.5 * i as Float))
+            .collect();
+        System {
+            size: 20,
+            cell: Cell::cubic(10.0),
+            species: vec![argon; 20],
+            velocities: positions.iter().map(|p| Vector3::new(0.001 * p.z, 0.0, 0.0)).collect(),
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn slabs() {
+        let system = gas();
+        assert_eq!(Slab::new(2, 0.0, 0.1).atoms(&system), vec![0, 1]);
+        // slabs wrap around the periodic boundary
+        assert_eq!(Slab::new(2, 0.95, 1.05).atoms(&system), vec![0, 19]);
+        assert_eq!(Slab::new(2, 0.3, 0.3).atoms(&system).len(), 20);
+    }
+
+    #[test]
+    fn pump() {
+        let system = gas();
+        let pump = Pump::new(Slab::new(2, 0.0, 0.5), Vector3::new(0.1, 0.0, 0.0));
+        let evaluation = pump.evaluate(&system);
+        assert_eq!(evaluation.energy, 0.0);
+        assert_eq!(pump.colvar(&system), 10.0);
+        assert_eq!(evaluation.forces[3], Vector3::new(0.1, 0.0, 0.0));
+        assert_eq!(evaluation.forces[15], Vector3::zeros());
+
+        // a body force accelerates the center of mass of a free gas uniformly
+        let mut system = gas();
+        let mass = system.species[0].mass();
+        let mut potentials = PotentialsBuilder::new()
+            .bias(Pump::new(Slab::new(0, 0.0, 0.0), Vector3::new(0.0, 0.5, 0.0)))
+            .build();
+        potentials.setup(&system);
+        let mut md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+        md.setup(&mut system, &potentials).unwrap();
+        for i in 0..10 {
+            md.propagate(&mut system, &potentials);
+            potentials.update(&system, i);
+        }
+        assert!(system.velocities.iter().all(|v| (v.y - 10.0 * 0.5 / mass).abs() < 1e-5));
+    }
+
+    #[test]
+    fn couette() {
+        let mut system = gas();
+        let potentials = PotentialsBuilder::new().build();
+        let md = MolecularDynamics::new(VelocityVerlet::new(0.01), NullThermostat);
+        let wall = Slab::new(2, 0.0, 0.2);
+        let mut driven = Driven::new(md).target(wall, Vector3::new(-0.01, 0.0, 0.0), 1.0);
+        driven.setup(&mut system, &potentials).unwrap();
+        driven.propagate(&mut system, &potentials);
+
+        let atoms = wall.atoms(&system);
+        let mean = atoms.iter().map(|&i| system.velocities[i]).sum::<Vector3<Float>>() / atoms.len() as Float;
+        assert!((mean - Vector3::new(-0.01, 0.0, 0.0)).norm() < 1e-6);
+        // the shear within the wall and the atoms outside it are untouched
+        assert!((system.velocities[1].x - system.velocities[0].x - 0.0005).abs() < 1e-6);
+        assert!((system.velocities[10].x - 0.001 * 5.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn profile() {
+        let system = gas();
+        let profile = VelocityProfile::new(2, 5).calculate_intrinsic(&system);
+        assert_eq!(profile.counts, vec![4; 5]);
+        assert!((profile.centers[2] - 0.5).abs() < 1e-6);
+        // the linear shear profile is recovered at the bin centers
+        for (center, velocity) in profile.centers.iter().zip(profile.velocities.iter()) {
+            assert!((velocity.x - 0.01 * center).abs() < 1e-3, "{} {}", center, velocity);
+        }
+        let density = 4.0 * system.species[0].mass() / 200.0;
+        assert!((profile.densities[0] - density).abs() < 1e-4);
+    }
+}
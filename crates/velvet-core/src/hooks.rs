@@ -0,0 +1,494 @@
+//! User defined logic which runs around every step of a simulation.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use crate::colvars::CollectiveVariable;
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::outputs::raw::RawOutput;
+use crate::outputs::trajectory::XyzTrajectory;
+use crate::potentials::Potentials;
+use crate::properties::forces::{Forces, MaxForceUncertainty};
+use crate::properties::Property;
+use crate::system::System;
+
+/// Shared behavior for custom logic injected into the iteration loop of a
+/// [`Simulation`](crate::simulation::Simulation).
+///
+/// Hooks are added with [`add_hook`](crate::simulation::Simulation::add_hook) and run in the
+/// order they were added. The `step` passed to each method is the number of steps the simulation
+/// has completed, counted across every run. A hook may modify the system, for example to apply
+/// an impulse to some atoms before a step, gather custom analysis after a step, or end the run
+/// early through [`stop`](Hook::stop). A hook which fails, such as one which writes to a file,
+/// reports the error through [`take_error`](Hook::take_error) and the run ends with it. Forces
+/// which act on every step are better expressed as a bias or external potential so they are
+/// included in the energies and the virial.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // ends the run once any atom leaves the lower half of the cell along z
+/// struct Escape {
+///     escaped: bool,
+/// }
+///
+/// impl Hook for Escape {
+///     fn post_step(&mut self, _: usize, system: &mut System, _: &Potentials) {
+///         let height = system.cell.c();
+///         self.escaped = system.positions.iter().any(|position| position.z > 0.5 * height);
+///     }
+///
+///     fn stop(&self) -> bool {
+///         self.escaped
+///     }
+/// }
+/// ```
+pub trait Hook {
+    /// Prepares the hook at the start of each run.
+    fn setup(&mut self, _: &System, _: &Potentials) {}
+    /// Runs before the system is advanced by one step.
+    fn pre_step(&mut self, _step: usize, _: &mut System, _: &Potentials) {}
+    /// Runs after the system is advanced by one step and before the outputs are written.
+    fn post_step(&mut self, _step: usize, _: &mut System, _: &Potentials) {}
+    /// Returns `true` once the hook requests the current run to end.
+    fn stop(&self) -> bool {
+        false
+    }
+    /// Returns and clears the error raised by the last call to the hook, which ends the current
+    /// run with that error.
+    fn take_error(&mut self) -> Option<VelvetError> {
+        None
+    }
+}
+
+/// Hook which flags the steps where an ensemble of models disagrees and can end the run.
+///
+/// Every `interval` steps the [`MaxForceUncertainty`] of the system is compared against a
+/// threshold, and steps above it are recorded with their uncertainty in a log which remains
+/// readable through [`flagged`](UncertaintyMonitor::flagged) after the hook is added to a
+/// simulation. These are the configurations to label and add to the training data of a
+/// machine-learned potential. With [`stop_above`](UncertaintyMonitor::stop_above) the run also
+/// ends once the uncertainty exceeds a second, typically larger, limit beyond which the
+/// trajectory cannot be trusted. Each check evaluates the external potential again.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let monitor = UncertaintyMonitor::new(0.5).stop_above(2.0).interval(10);
+/// let flagged = monitor.flagged();
+/// // once the monitor is added to a simulation and the run ends
+/// for (step, uncertainty) in flagged.lock().unwrap().iter() {
+///     println!("step {} has a force uncertainty of {}", step, uncertainty);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct UncertaintyMonitor {
+    threshold: Float,
+    limit: Option<Float>,
+    interval: usize,
+    flagged: Arc<Mutex<Vec<(usize, Float)>>>,
+    stopped: bool,
+}
+
+impl UncertaintyMonitor {
+    /// Returns a new [`UncertaintyMonitor`] which flags every step whose maximum force
+    /// uncertainty exceeds `threshold`.
+    pub fn new(threshold: Float) -> UncertaintyMonitor {
+        UncertaintyMonitor {
+            threshold,
+            limit: None,
+            interval: 1,
+            flagged: Arc::new(Mutex::new(Vec::new())),
+            stopped: false,
+        }
+    }
+
+    /// Ends the run once the maximum force uncertainty exceeds `limit`.
+    pub fn stop_above(mut self, limit: Float) -> UncertaintyMonitor {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Sets the number of steps between checks.
+    pub fn interval(mut self, interval: usize) -> UncertaintyMonitor {
+        self.interval = interval.max(1);
+        self
+    }
+
+    /// Returns a shared log of each flagged step with its maximum force uncertainty.
+    pub fn flagged(&self) -> Arc<Mutex<Vec<(usize, Float)>>> {
+        Arc::clone(&self.flagged)
+    }
+}
+
+impl Hook for UncertaintyMonitor {
+    fn setup(&mut self, _: &System, _: &Potentials) {
+        self.stopped = false;
+    }
+
+    fn post_step(&mut self, step: usize, system: &mut System, potentials: &Potentials) {
+        if !step.is_multiple_of(self.interval) {
+            return;
+        }
+        let uncertainty = MaxForceUncertainty.calculate(system, potentials);
+        if uncertainty > self.threshold {
+            self.flagged.lock().unwrap().push((step, uncertainty));
+        }
+        self.stopped = self.limit.is_some_and(|limit| uncertainty > limit);
+    }
+
+    fn stop(&self) -> bool {
+        self.stopped
+    }
+}
+
+/// Shared behavior for conditions which mark a configuration as worth labeling.
+pub trait Trigger {
+    /// Returns the name recorded with each snapshot taken because of this trigger.
+    fn name(&self) -> String;
+    /// Returns `true` if the current configuration satisfies the condition.
+    fn fires(&mut self, system: &System, potentials: &Potentials) -> bool;
+}
+
+/// Fires when the force on any atom exceeds a threshold.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxForceTrigger {
+    threshold: Float,
+}
+
+impl MaxForceTrigger {
+    /// Returns a new [`MaxForceTrigger`] which fires above a force norm of `threshold`.
+    pub fn new(threshold: Float) -> MaxForceTrigger {
+        MaxForceTrigger { threshold }
+    }
+}
+
+impl Trigger for MaxForceTrigger {
+    fn name(&self) -> String {
+        "max_force".to_string()
+    }
+
+    fn fires(&mut self, system: &System, potentials: &Potentials) -> bool {
+        Forces
+            .calculate(system, potentials)
+            .iter()
+            .any(|force| force.norm() > self.threshold)
+    }
+}
+
+/// Fires when the [`MaxForceUncertainty`] of an ensemble of models exceeds a threshold.
+#[derive(Clone, Copy, Debug)]
+pub struct UncertaintyTrigger {
+    threshold: Float,
+}
+
+impl UncertaintyTrigger {
+    /// Returns a new [`UncertaintyTrigger`] which fires above an uncertainty of `threshold`.
+    pub fn new(threshold: Float) -> UncertaintyTrigger {
+        UncertaintyTrigger { threshold }
+    }
+}
+
+impl Trigger for UncertaintyTrigger {
+    fn name(&self) -> String {
+        "uncertainty".to_string()
+    }
+
+    fn fires(&mut self, system: &System, potentials: &Potentials) -> bool {
+        MaxForceUncertainty.calculate(system, potentials) > self.threshold
+    }
+}
+
+/// Fires when a collective variable leaves the range of values it usually samples.
+pub struct ColvarTrigger<C: CollectiveVariable> {
+    colvar: C,
+    low: Float,
+    high: Float,
+}
+
+impl<C: CollectiveVariable> ColvarTrigger<C> {
+    /// Returns a new [`ColvarTrigger`] which fires when `colvar` is below `low` or above `high`.
+    pub fn outside(colvar: C, low: Float, high: Float) -> ColvarTrigger<C> {
+        ColvarTrigger { colvar, low, high }
+    }
+}
+
+impl<C: CollectiveVariable> fmt::Debug for ColvarTrigger<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ColvarTrigger")
+            .field("low", &self.low)
+            .field("high", &self.high)
+            .finish()
+    }
+}
+
+impl<C: CollectiveVariable> Trigger for ColvarTrigger<C> {
+    fn name(&self) -> String {
+        "colvar".to_string()
+    }
+
+    fn fires(&mut self, system: &System, _: &Potentials) -> bool {
+        let (value, _) = self.colvar.evaluate(system);
+        value < self.low || value > self.high
+    }
+}
+
+/// Hook which writes the full system to an extended XYZ file whenever a [`Trigger`] fires.
+///
+/// This is the data collection half of an active learning loop: the snapshots are labeled with
+/// a reference method and added to the training data of a machine-learned potential. Each frame
+/// holds the lattice, species, positions, and velocities of every atom, and its comment line
+/// records the step and the name of the first trigger which fired. Triggers are checked every
+/// `interval` steps, at least `spacing` steps separate consecutive snapshots so a single event
+/// does not fill the file with near duplicates, and the dumper stops writing after
+/// `max_snapshots` snapshots. A snapshot which cannot be written ends the run with the error.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let path = std::env::temp_dir().join("velvet-snapshots.xyz");
+/// let dumper = SnapshotDumper::new(create_destination(&path).unwrap())
+///     .trigger(MaxForceTrigger::new(10.0))
+///     .trigger(UncertaintyTrigger::new(0.5))
+///     .trigger(ColvarTrigger::outside(SteinhardtOrder::new(6, 3.0, 3.5), 0.2, 0.6))
+///     .interval(10)
+///     .spacing(100)
+///     .max_snapshots(500);
+/// # drop(dumper);
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub struct SnapshotDumper {
+    destination: Box<dyn Write>,
+    triggers: Vec<Box<dyn Trigger>>,
+    interval: usize,
+    spacing: usize,
+    max_snapshots: Option<usize>,
+    snapshots: usize,
+    last: Option<usize>,
+    error: Option<VelvetError>,
+}
+
+impl SnapshotDumper {
+    /// Returns a new [`SnapshotDumper`] which writes snapshots to `destination`.
+    pub fn new<W: Write + 'static>(destination: W) -> SnapshotDumper {
+        SnapshotDumper {
+            destination: Box::new(destination),
+            triggers: Vec::new(),
+            interval: 1,
+            spacing: 1,
+            max_snapshots: None,
+            snapshots: 0,
+            last: None,
+            error: None,
+        }
+    }
+
+    /// Adds a condition which takes a snapshot when it fires.
+    pub fn trigger<T: Trigger + 'static>(mut self, trigger: T) -> SnapshotDumper {
+        self.triggers.push(Box::new(trigger));
+        self
+    }
+
+    /// Sets the number of steps between checks of the triggers.
+    pub fn interval(mut self, interval: usize) -> SnapshotDumper {
+        self.interval = interval.max(1);
+        self
+    }
+
+    /// Sets the minimum number of steps between two snapshots.
+    pub fn spacing(mut self, spacing: usize) -> SnapshotDumper {
+        self.spacing = spacing.max(1);
+        self
+    }
+
+    /// Sets the largest number of snapshots to write.
+    pub fn max_snapshots(mut self, max_snapshots: usize) -> SnapshotDumper {
+        self.max_snapshots = Some(max_snapshots);
+        self
+    }
+
+    /// Returns the number of snapshots written so far.
+    pub fn snapshots(&self) -> usize {
+        self.snapshots
+    }
+
+    // Writes an extended XYZ frame of the system whose comment line records the step and trigger.
+    fn write(
+        &mut self,
+        step: usize,
+        system: &System,
+        potentials: &Potentials,
+        trigger: &str,
+    ) -> Result<(), VelvetError> {
+        let mut frame = Vec::new();
+        XyzTrajectory::extended().output_raw(system, potentials, &mut frame);
+        // tag the comment line, which ends at the second newline of the frame
+        let end = frame
+            .iter()
+            .enumerate()
+            .filter(|&(_, &byte)| byte == b'\n')
+            .nth(1)
+            .map(|(index, _)| index)
+            .ok_or_else(|| VelvetError::parse("XYZ", "frame without a comment line"))?;
+        let tag = format!(" step={} trigger={}", step, trigger);
+        frame.splice(end..end, tag.bytes());
+        self.destination.write_all(&frame)?;
+        self.destination.flush()?;
+        Ok(())
+    }
+}
+
+impl fmt::Debug for SnapshotDumper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let triggers: Vec<String> = self.triggers.iter().map(|trigger| trigger.name()).collect();
+        f.debug_struct("SnapshotDumper")
+            .field("triggers", &triggers)
+            .field("interval", &self.interval)
+            .field("spacing", &self.spacing)
+            .field("max_snapshots", &self.max_snapshots)
+            .field("snapshots", &self.snapshots)
+            .finish()
+    }
+}
+
+impl Hook for SnapshotDumper {
+    fn post_step(&mut self, step: usize, system: &mut System, potentials: &Potentials) {
+        if !step.is_multiple_of(self.interval) || self.max_snapshots.is_some_and(|max| self.snapshots >= max) {
+            return;
+        }
+        if self.last.is_some_and(|last| step < last + self.spacing) {
+            return;
+        }
+        let fired = match self
+            .triggers
+            .iter_mut()
+            .position(|trigger| trigger.fires(system, potentials))
+        {
+            Some(index) => index,
+            None => return,
+        };
+        let trigger = self.triggers[fired].name();
+        if let Err(err) = self.write(step, system, potentials, &trigger) {
+            self.error = Some(err);
+            return;
+        }
+        self.snapshots += 1;
+        self.last = Some(step);
+    }
+
+    fn take_error(&mut self) -> Option<VelvetError> {
+        self.error.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hook, MaxForceTrigger, SnapshotDumper};
+    use crate::errors::VelvetError;
+    use crate::potentials::external::{ExternalCallback, ExternalEvaluation, Neighborhood};
+    use crate::potentials::PotentialsBuilder;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use std::io::{self, Write};
+
+    // Tethers each atom to the plane x = 5.
+    fn tether(system: &System, _: &Neighborhood) -> ExternalEvaluation {
+        ExternalEvaluation {
+            forces: system
+                .positions
+                .iter()
+                .map(|position| Vector3::new(5.0 - position.x, 0.0, 0.0))
+                .collect(),
+            ..ExternalEvaluation::default()
+        }
+    }
+
+    #[test]
+    fn snapshot_dumper() {
+        let atom = Species::new(1.0, 0.0);
+        let mut system = System {
+            size: 2,
+            cell: Cell::cubic(10.0),
+            species: vec![atom; 2],
+            positions: vec![Vector3::new(5.5, 5.0, 5.0), Vector3::new(5.0, 2.0, 5.0)],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        };
+        let mut potentials = PotentialsBuilder::new()
+            .external(ExternalCallback::new(1.0, tether), 0.5)
+            .build();
+        potentials.setup(&system);
+
+        let path = std::env::temp_dir().join(format!("velvet-snapshots-{}.xyz", std::process::id()));
+        let mut dumper = SnapshotDumper::new(std::fs::File::create(&path).unwrap())
+            .trigger(MaxForceTrigger::new(1.0))
+            .spacing(2)
+            .max_snapshots(2);
+        dumper.setup(&system, &potentials);
+        // the force stays below the threshold
+        dumper.post_step(1, &mut system, &potentials);
+        assert_eq!(dumper.snapshots(), 0);
+        // then every step fires but snapshots are spaced and capped
+        system.positions[0].x = 7.0;
+        for step in 2..=6 {
+            dumper.post_step(step, &mut system, &potentials);
+        }
+        assert_eq!(dumper.snapshots(), 2);
+        drop(dumper);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let comments: Vec<&str> = contents.lines().filter(|line| line.starts_with("Lattice=")).collect();
+        assert_eq!(comments.len(), 2);
+        assert!(comments[0].ends_with("pbc=\"T T T\" step=2 trigger=max_force"));
+        assert!(comments[1].ends_with("step=4 trigger=max_force"));
+        assert_eq!(contents.lines().count(), 2 * (2 + system.size));
+    }
+
+    // Destination which rejects every write.
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn snapshot_dumper_error() {
+        let atom = Species::new(1.0, 0.0);
+        let mut system = System {
+            size: 1,
+            cell: Cell::cubic(10.0),
+            species: vec![atom],
+            positions: vec![Vector3::new(8.0, 5.0, 5.0)],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let mut potentials = PotentialsBuilder::new()
+            .external(ExternalCallback::new(1.0, tether), 0.5)
+            .build();
+        potentials.setup(&system);
+
+        let mut dumper = SnapshotDumper::new(Closed).trigger(MaxForceTrigger::new(1.0));
+        dumper.post_step(1, &mut system, &potentials);
+        assert_eq!(dumper.snapshots(), 0);
+        assert!(matches!(dumper.take_error(), Some(VelvetError::Io(_))));
+        assert!(dumper.take_error().is_none());
+    }
+}
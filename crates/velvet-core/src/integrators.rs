@@ -1,23 +1,86 @@
 //! Algorithms which integrate the classical equations of motion.
 
 use nalgebra::Vector3;
+use rand::Rng;
+use rand_distr::StandardNormal;
 
+use crate::checkpoint::PropagatorState;
+use crate::constraints::Constraints;
+use crate::errors::VelvetError;
+use crate::flow::Slab;
+use crate::internal::consts::BOLTZMANN;
 use crate::internal::Float;
 use crate::potentials::Potentials;
 use crate::properties::forces::Forces;
 use crate::properties::Property;
+use crate::random::shared_rng;
+use crate::system::species::Species;
 use crate::system::System;
 
 /// Shared behavior for algorithms which integrate the classical equations of motion.
 pub trait Integrator: Send + Sync {
     /// Prepares the integrator to run.
     fn setup(&mut self, _: &System, _: &Potentials) {}
-    /// Integrates one time step.
-    fn integrate(&mut self, system: &mut System, potentials: &Potentials);
+    /// Integrates one time step, or returns an error if the step could not be completed.
+    fn integrate(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError>;
+    /// Returns the pairs of atoms whose distance the integrator holds fixed.
+    fn constrained_pairs(&self) -> Vec<[usize; 2]> {
+        Vec::new()
+    }
+    /// Returns the internal state of the integrator to store in a checkpoint.
+    fn state(&self) -> PropagatorState {
+        PropagatorState::default()
+    }
+    /// Restores internal state previously returned by [`state`](Integrator::state), or returns an
+    /// error if the state belongs to a different integrator.
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 0, 0)
+    }
+}
+
+impl<T: Integrator + ?Sized> Integrator for Box<T> {
+    fn setup(&mut self, system: &System, potentials: &Potentials) {
+        (**self).setup(system, potentials)
+    }
+
+    fn integrate(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        (**self).integrate(system, potentials)
+    }
+
+    fn constrained_pairs(&self) -> Vec<[usize; 2]> {
+        (**self).constrained_pairs()
+    }
+
+    fn state(&self) -> PropagatorState {
+        (**self).state()
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        (**self).restore(state)
+    }
+}
+
+/// Record of a periodic drift correction applied by [`VelocityVerlet`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DriftCorrection {
+    /// Number of steps integrated before the correction.
+    pub step: usize,
+    /// Center of mass velocity which was removed.
+    pub drift: Vector3<Float>,
+    /// Kinetic energy of the center of mass motion which was removed.
+    pub drift_energy: Float,
+    /// Largest relative deviation of a constrained distance from its length before it was restored.
+    pub constraint_error: Float,
+    /// Factor applied to the velocities to restore the kinetic energy of the internal motion.
+    pub scale: Float,
 }
 
 /// Velocity Verlet integration algorithm.
 ///
+/// Long NVE runs can enable a periodic [`drift_correction`](VelocityVerlet::drift_correction)
+/// which removes the center of mass motion built up by round-off error and restores the exact
+/// lengths of any constraints.
+///
 /// # References
 ///
 /// [1] Swope, William C., et al. "A computer simulation method for the calculation of equilibrium constants for the formation of physical clusters of molecules: Application to small water clusters." The Journal of chemical physics 76.1 (1982): 637-649.
@@ -25,6 +88,10 @@ pub trait Integrator: Send + Sync {
 pub struct VelocityVerlet {
     timestep: Float,
     accelerations: Vec<Vector3<Float>>,
+    constraints: Option<Constraints>,
+    correction_interval: Option<usize>,
+    steps: usize,
+    corrections: Vec<DriftCorrection>,
 }
 
 impl VelocityVerlet {
@@ -37,43 +104,695 @@ impl VelocityVerlet {
         VelocityVerlet {
             timestep,
             accelerations: Vec::new(),
+            constraints: None,
+            correction_interval: None,
+            steps: 0,
+            corrections: Vec::new(),
+        }
+    }
+
+    /// Holds the distances in `constraints` fixed with SHAKE after each position update and
+    /// RATTLE after each velocity update.
+    pub fn with_constraints(mut self, constraints: Constraints) -> VelocityVerlet {
+        self.constraints = Some(constraints);
+        self
+    }
+
+    /// Re-projects the system every `interval` steps to undo the slow drift of very long runs.
+    ///
+    /// Each correction restores the exact length of every constrained distance and removes the
+    /// velocity components along them, then removes the center of mass velocity. The remaining
+    /// velocities are scaled back to the kinetic energy of the internal motion before the
+    /// correction, so the temperature and the conserved energy of the run are not disturbed
+    /// beyond the change in potential energy from moving the constrained atoms. Every
+    /// correction is logged and can be inspected with [`corrections`](VelocityVerlet::corrections).
+    pub fn drift_correction(mut self, interval: usize) -> VelocityVerlet {
+        self.correction_interval = Some(interval.max(1));
+        self
+    }
+
+    /// Returns the log of drift corrections applied so far.
+    pub fn corrections(&self) -> &[DriftCorrection] {
+        &self.corrections
+    }
+
+    // Removes accumulated drift and records what was changed.
+    fn correct(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        let kinetic = |system: &System| -> Float {
+            system
+                .species
+                .iter()
+                .zip(system.velocities.iter())
+                .map(|(species, vel)| 0.5 * species.mass() * vel.norm_squared())
+                .sum()
+        };
+        let mass: Float = system.species.iter().map(|species| species.mass()).sum();
+        let momentum: Vector3<Float> = system
+            .species
+            .iter()
+            .zip(system.velocities.iter())
+            .map(|(species, vel)| species.mass() * *vel)
+            .sum();
+        let drift = momentum / mass;
+        let drift_energy = 0.5 * mass * drift.norm_squared();
+        let target = kinetic(system) - drift_energy;
+
+        let mut constraint_error = 0.0;
+        if let Some(constraints) = &self.constraints {
+            constraint_error = constraints.renormalize(system);
+            constraints.rattle(system, self.timestep)?;
+            // moving the constrained atoms changes the forces
+            self.accelerations = accelerations(system, potentials);
         }
+        system.velocities.iter_mut().for_each(|vel| *vel -= drift);
+        for &i in &system.topology.frozen {
+            system.velocities[i] = Vector3::zeros();
+        }
+        let current = kinetic(system);
+        let scale = if current > 0.0 && target > 0.0 {
+            Float::sqrt(target / current)
+        } else {
+            1.0
+        };
+        system.velocities.iter_mut().for_each(|vel| *vel *= scale);
+
+        self.corrections.push(DriftCorrection {
+            step: self.steps,
+            drift,
+            drift_energy,
+            constraint_error,
+            scale,
+        });
+        Ok(())
     }
 }
 
 impl Integrator for VelocityVerlet {
-    fn setup(&mut self, system: &System, _: &Potentials) {
-        self.accelerations = vec![Vector3::zeros(); system.size];
+    fn setup(&mut self, system: &System, potentials: &Potentials) {
+        if let Some(constraints) = &mut self.constraints {
+            constraints.setup(system);
+        }
+        // accelerations must reflect the current potentials which may have changed between runs
+        self.accelerations = accelerations(system, potentials);
     }
 
-    fn integrate(&mut self, system: &mut System, potentials: &Potentials) {
+    fn integrate(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
         let dt = self.timestep;
+        let reference = self.constraints.as_ref().map(|_| system.positions.clone());
 
+        // velocities carry the first half kick so SHAKE can correct them with the positions
         system
             .positions
             .iter_mut()
-            .zip(system.velocities.iter())
+            .zip(system.velocities.iter_mut())
             .zip(self.accelerations.iter())
             .for_each(|((pos, vel), acc)| {
-                *pos += (vel * dt) + (0.5 * acc * dt.powi(2));
+                *vel += 0.5 * acc * dt;
+                *pos += *vel * dt;
             });
+        let mut virial = None;
+        if let (Some(constraints), Some(reference)) = (&self.constraints, reference) {
+            virial = Some(constraints.shake(system, &reference, dt)?);
+        }
 
-        let forces = Forces.calculate(system, potentials);
-        let new_accelerations: Vec<Vector3<Float>> = forces
-            .iter()
-            .zip(system.species.iter())
-            .map(|(f, species)| f / species.mass())
-            .collect();
+        let new_accelerations = accelerations(system, potentials);
 
         system
             .velocities
             .iter_mut()
-            .zip(self.accelerations.iter())
             .zip(new_accelerations.iter())
-            .for_each(|((vel, acc), new_acc)| {
-                *vel += 0.5 * dt * (acc + new_acc);
+            .for_each(|(vel, acc)| {
+                *vel += 0.5 * acc * dt;
             });
+        if let Some(constraints) = &self.constraints {
+            constraints.rattle(system, dt)?;
+        }
 
         self.accelerations = new_accelerations;
+
+        self.steps += 1;
+        if let Some(interval) = self.correction_interval {
+            if self.steps.is_multiple_of(interval) {
+                self.correct(system, potentials)?;
+            }
+        }
+        if let Some(virial) = virial {
+            potentials.record_constraint_virial(virial);
+        }
+        Ok(())
+    }
+
+    fn constrained_pairs(&self) -> Vec<[usize; 2]> {
+        self.constraints.as_ref().map(Constraints::pairs).unwrap_or_default()
+    }
+
+    // accelerations may differ from the current forces once a barostat deforms the system
+    fn state(&self) -> PropagatorState {
+        PropagatorState::from_vectors(&self.accelerations)
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.accelerations = state.vectors(self.accelerations.len())?;
+        Ok(())
+    }
+}
+
+/// Leapfrog integration algorithm.
+///
+/// Velocities are staggered half a timestep behind the positions, so the velocities of the
+/// system are read as `v(t - dt/2)` at the start of each step and hold `v(t + dt/2)` after it.
+/// Initial velocities are used as the half step velocities without correction, as in GROMACS.
+/// Properties derived from the velocities, such as the kinetic energy, therefore lag the
+/// positions by half a timestep. The trajectory is otherwise identical to velocity Verlet with
+/// one force evaluation per step and no stored accelerations.
+///
+/// # References
+///
+/// [1] Hockney, Roger W. "The potential calculation and some applications." Methods in Computational Physics 9 (1970): 136-211.
+#[derive(Clone, Debug)]
+pub struct Leapfrog {
+    timestep: Float,
+    constraints: Option<Constraints>,
+}
+
+impl Leapfrog {
+    /// Returns a new [`Leapfrog`] algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestep` - Timestep duration.
+    pub fn new(timestep: Float) -> Leapfrog {
+        Leapfrog {
+            timestep,
+            constraints: None,
+        }
+    }
+
+    /// Holds the distances in `constraints` fixed with SHAKE after each position update.
+    pub fn with_constraints(mut self, constraints: Constraints) -> Leapfrog {
+        self.constraints = Some(constraints);
+        self
+    }
+}
+
+impl Integrator for Leapfrog {
+    fn setup(&mut self, system: &System, _: &Potentials) {
+        if let Some(constraints) = &mut self.constraints {
+            constraints.setup(system);
+        }
+    }
+
+    fn integrate(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        let dt = self.timestep;
+        let reference = self.constraints.as_ref().map(|_| system.positions.clone());
+        let accelerations = accelerations(system, potentials);
+        system
+            .positions
+            .iter_mut()
+            .zip(system.velocities.iter_mut())
+            .zip(accelerations.iter())
+            .for_each(|((pos, vel), acc)| {
+                *vel += acc * dt;
+                *pos += *vel * dt;
+            });
+        if let (Some(constraints), Some(reference)) = (&self.constraints, reference) {
+            // the full kick of the leapfrog step displaces the atoms twice as far for the same force
+            let virial = constraints.shake(system, &reference, dt)?;
+            potentials.record_constraint_virial(virial * 0.5);
+        }
+        Ok(())
+    }
+
+    fn constrained_pairs(&self) -> Vec<[usize; 2]> {
+        self.constraints.as_ref().map(Constraints::pairs).unwrap_or_default()
+    }
+}
+
+/// Reversible reference system propagator algorithm (r-RESPA) with two timescales.
+///
+/// Forces are split between fast potentials owned by the integrator, such as stiff
+/// intramolecular or short-ranged pair interactions, and the slow potentials of the
+/// simulation, such as long-ranged electrostatics. The slow forces are evaluated once per
+/// outer timestep and applied as half kicks at its start and end, while the fast forces are
+/// integrated with velocity Verlet over `inner_steps` shorter steps in between. When the slow
+/// potentials are the expensive part of the force field this cuts the number of their
+/// evaluations by a factor of `inner_steps` for the same stability as a short timestep.
+///
+/// Neighbor lists of the fast potentials are updated on every inner step according to their
+/// own update frequency or adaptive skin.
+///
+/// # References
+///
+/// [1] Tuckerman, M. B. B. J., Bruce J. Berne, and Glenn J. Martyna. "Reversible multiple time scale molecular dynamics." The Journal of chemical physics 97.3 (1992): 1990-2001.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let oxygen = Species::from_element(Element::O);
+/// let fast = PotentialsBuilder::new()
+///     .pair(Harmonic::new(1000.0, 1.2), (oxygen, oxygen), 1.5, 0.5)
+///     .build();
+/// let integrator = Respa::new(2.0, 4, fast);
+/// ```
+pub struct Respa {
+    timestep: Float,
+    inner_steps: usize,
+    fast: Potentials,
+    fast_accelerations: Vec<Vector3<Float>>,
+    slow_accelerations: Vec<Vector3<Float>>,
+    iteration: usize,
+}
+
+impl Respa {
+    /// Returns a new [`Respa`] algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestep` - Outer timestep duration at which the slow forces are evaluated.
+    /// * `inner_steps` - Number of inner timesteps at which the fast forces are evaluated per outer timestep.
+    /// * `fast` - Potentials which supply the fast forces.
+    pub fn new(timestep: Float, inner_steps: usize, fast: Potentials) -> Respa {
+        assert!(inner_steps > 0, "Expected at least one inner step");
+        Respa {
+            timestep,
+            inner_steps,
+            fast,
+            fast_accelerations: Vec::new(),
+            slow_accelerations: Vec::new(),
+            iteration: 0,
+        }
+    }
+}
+
+// Returns the acceleration of each atom due to the forces of `potentials`, which is zero for the
+// frozen atoms of the topology.
+fn accelerations(system: &System, potentials: &Potentials) -> Vec<Vector3<Float>> {
+    let mut accelerations: Vec<Vector3<Float>> = Forces
+        .calculate(system, potentials)
+        .iter()
+        .zip(system.species.iter())
+        .map(|(f, species)| f / species.mass())
+        .collect();
+    for &i in &system.topology.frozen {
+        accelerations[i] = Vector3::zeros();
+    }
+    accelerations
+}
+
+// Adds `dt` times each acceleration to the velocities of the system.
+fn kick(system: &mut System, accelerations: &[Vector3<Float>], dt: Float) {
+    system
+        .velocities
+        .iter_mut()
+        .zip(accelerations.iter())
+        .for_each(|(vel, acc)| *vel += acc * dt);
+}
+
+impl Integrator for Respa {
+    fn setup(&mut self, system: &System, potentials: &Potentials) {
+        self.fast.setup(system);
+        self.fast_accelerations = accelerations(system, &self.fast);
+        self.slow_accelerations = accelerations(system, potentials);
+    }
+
+    fn integrate(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        let dt = self.timestep;
+        let inner = dt / self.inner_steps as Float;
+
+        kick(system, &self.slow_accelerations, 0.5 * dt);
+        for _ in 0..self.inner_steps {
+            kick(system, &self.fast_accelerations, 0.5 * inner);
+            system
+                .positions
+                .iter_mut()
+                .zip(system.velocities.iter())
+                .for_each(|(pos, vel)| *pos += vel * inner);
+            self.fast.update(system, self.iteration);
+            self.iteration += 1;
+            self.fast_accelerations = accelerations(system, &self.fast);
+            kick(system, &self.fast_accelerations, 0.5 * inner);
+        }
+        self.slow_accelerations = accelerations(system, potentials);
+        kick(system, &self.slow_accelerations, 0.5 * dt);
+        Ok(())
+    }
+
+    fn state(&self) -> PropagatorState {
+        PropagatorState {
+            parts: vec![
+                PropagatorState::from_vectors(&self.fast_accelerations),
+                PropagatorState::from_vectors(&self.slow_accelerations),
+            ],
+            ..PropagatorState::default()
+        }
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 0, 2)?;
+        self.fast_accelerations = state.parts[0].vectors(self.fast_accelerations.len())?;
+        self.slow_accelerations = state.parts[1].vectors(self.slow_accelerations.len())?;
+        Ok(())
+    }
+}
+
+/// Langevin dynamics integrated with the BAOAB splitting of Leimkuhler and Matthews.
+///
+/// Each atom feels a friction force `-m gamma v` and a random force which together hold it at
+/// the target temperature. The friction coefficient of an atom is the one of the last damping
+/// layer containing it, otherwise the one set for its species, and otherwise the default. A zero
+/// default with damping layers near the faces of the cell gives Newtonian dynamics in the
+/// interior while the layers absorb the shock waves and heat of an impact or radiation cascade
+/// before they cross the periodic boundary.
+///
+/// The integrator already samples the canonical ensemble, so it is used with a
+/// [`NullThermostat`](crate::thermostats::NullThermostat).
+///
+/// # References
+///
+/// [1] Leimkuhler, Benedict, and Charles Matthews. "Rational construction of stochastic numerical methods for molecular sampling." Applied Mathematics Research eXpress 2013.1 (2013): 34-56.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // heavy solute atoms damped more strongly than the rest and absorbing layers along z
+/// let tungsten = Species::from_element(Element::W);
+/// let integrator = Langevin::new(1.0, 300.0, 0.0)
+///     .species_friction(tungsten, 0.01)
+///     .damping_layer(Slab::new(2, 0.0, 0.05), 0.1)
+///     .damping_layer(Slab::new(2, 0.95, 1.0), 0.1);
+/// let md = MolecularDynamics::new(integrator, NullThermostat);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Langevin {
+    timestep: Float,
+    target: Float,
+    friction: Float,
+    species: Vec<(Species, Float)>,
+    layers: Vec<(Slab, Float)>,
+    accelerations: Vec<Vector3<Float>>,
+}
+
+impl Langevin {
+    /// Returns a new [`Langevin`] algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestep` - Timestep duration.
+    /// * `target` - Target temperature.
+    /// * `friction` - Default friction coefficient in inverse femtoseconds.
+    pub fn new(timestep: Float, target: Float, friction: Float) -> Langevin {
+        Langevin {
+            timestep,
+            target,
+            friction,
+            species: Vec::new(),
+            layers: Vec::new(),
+            accelerations: Vec::new(),
+        }
+    }
+
+    /// Sets the friction coefficient of atoms of `species` in inverse femtoseconds.
+    pub fn species_friction(mut self, species: Species, friction: Float) -> Langevin {
+        self.species.retain(|(s, _)| *s != species);
+        self.species.push((species, friction));
+        self
+    }
+
+    /// Applies a friction coefficient in inverse femtoseconds to atoms inside `slab`.
+    pub fn damping_layer(mut self, slab: Slab, friction: Float) -> Langevin {
+        self.layers.push((slab, friction));
+        self
+    }
+
+    // Returns the friction coefficient of atom `i` at its current position.
+    fn friction_of(&self, system: &System, i: usize) -> Float {
+        self.layers
+            .iter()
+            .rev()
+            .find(|(slab, _)| slab.contains(system, &system.positions[i]))
+            .map(|&(_, friction)| friction)
+            .or_else(|| self.species.iter().find(|(s, _)| *s == system.species[i]).map(|&(_, friction)| friction))
+            .unwrap_or(self.friction)
+    }
+}
+
+impl Integrator for Langevin {
+    fn setup(&mut self, system: &System, potentials: &Potentials) {
+        self.accelerations = accelerations(system, potentials);
+    }
+
+    fn integrate(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        let dt = self.timestep;
+        kick(system, &self.accelerations, 0.5 * dt);
+        system
+            .positions
+            .iter_mut()
+            .zip(system.velocities.iter())
+            .for_each(|(pos, vel)| *pos += vel * 0.5 * dt);
+
+        // the friction is evaluated at the midpoint positions
+        let mut rng = shared_rng();
+        for (i, frozen) in system.frozen_mask().into_iter().enumerate() {
+            let friction = self.friction_of(system, i);
+            if friction <= 0.0 || frozen {
+                continue;
+            }
+            let c1 = Float::exp(-friction * dt);
+            let sigma = Float::sqrt((1.0 - c1 * c1) * BOLTZMANN * self.target / system.species[i].mass());
+            let mut sample = || sigma * rng.sample::<Float, _>(StandardNormal);
+            let noise = Vector3::new(sample(), sample(), sample());
+            system.velocities[i] = c1 * system.velocities[i] + noise;
+        }
+
+        system
+            .positions
+            .iter_mut()
+            .zip(system.velocities.iter())
+            .for_each(|(pos, vel)| *pos += vel * 0.5 * dt);
+        self.accelerations = accelerations(system, potentials);
+        kick(system, &self.accelerations, 0.5 * dt);
+        Ok(())
+    }
+
+    fn state(&self) -> PropagatorState {
+        PropagatorState::from_vectors(&self.accelerations)
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.accelerations = state.vectors(self.accelerations.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DriftCorrection, Integrator, Langevin, Leapfrog, Respa, VelocityVerlet};
+    use crate::flow::Slab;
+    use crate::internal::Float;
+    use crate::potentials::types::Harmonic;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::forces::Forces;
+    use crate::properties::temperature::Temperature;
+    use crate::properties::{IntrinsicProperty, Property};
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    // Stiff diatomic molecule next to a third atom bound by a soft spring.
+    fn triatomic() -> (System, Species, Species) {
+        let (a, b) = (Species::new(1.0, 0.0), Species::new(2.0, 0.0));
+        let system = System {
+            size: 3,
+            cell: Cell::cubic(20.0),
+            species: vec![a, a, b],
+            positions: vec![
+                Vector3::new(5.0, 5.0, 5.0),
+                Vector3::new(6.1, 5.0, 5.0),
+                Vector3::new(5.5, 8.0, 5.0),
+            ],
+            velocities: vec![
+                Vector3::new(0.01, 0.0, 0.0),
+                Vector3::new(-0.01, 0.002, 0.0),
+                Vector3::new(0.0, -0.002, 0.0),
+            ],
+            topology: Topology::default(),
+        };
+        (system, a, b)
+    }
+
+    fn energy(system: &System, potentials: &[&Potentials]) -> Float {
+        let kinetic: Float = system
+            .species
+            .iter()
+            .zip(system.velocities.iter())
+            .map(|(species, vel)| 0.5 * species.mass() * vel.norm_squared())
+            .sum();
+        kinetic + potentials.iter().map(|p| PotentialEnergy.calculate(system, p)).sum::<Float>()
+    }
+
+    #[test]
+    fn leapfrog_matches_velocity_verlet() {
+        let (mut leapfrog, a, _) = triatomic();
+        let mut potentials = PotentialsBuilder::new()
+            .pair(Harmonic::new(10.0, 1.0), (a, a), 3.0, 1.0)
+            .build();
+        potentials.setup(&leapfrog);
+        let mut verlet = leapfrog.clone();
+
+        // staggering the initial velocities makes both trajectories identical
+        let mut integrator = VelocityVerlet::new(0.01);
+        integrator.setup(&verlet, &potentials);
+        let forces = Forces.calculate(&leapfrog, &potentials);
+        for ((vel, force), species) in leapfrog.velocities.iter_mut().zip(forces.iter()).zip(leapfrog.species.iter()) {
+            *vel -= 0.005 * force / species.mass();
+        }
+        let mut staggered = Leapfrog::new(0.01);
+        for _ in 0..200 {
+            integrator.integrate(&mut verlet, &potentials).unwrap();
+            staggered.integrate(&mut leapfrog, &potentials).unwrap();
+        }
+        for (x, y) in leapfrog.positions.iter().zip(verlet.positions.iter()) {
+            assert_relative_eq!(x, y, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn respa_conserves_energy() {
+        let (mut system, a, b) = triatomic();
+        let build_fast = || PotentialsBuilder::new().pair(Harmonic::new(50.0, 1.0), (a, a), 3.0, 1.0).build();
+        let mut slow = PotentialsBuilder::new()
+            .pair(Harmonic::new(0.5, 3.0), (a, b), 5.0, 1.0)
+            .build();
+        slow.setup(&system);
+        let mut fast = build_fast();
+        fast.setup(&system);
+        let initial = energy(&system, &[&fast, &slow]);
+
+        let mut integrator = Respa::new(0.05, 10, build_fast());
+        integrator.setup(&system, &slow);
+        for _ in 0..400 {
+            integrator.integrate(&mut system, &slow).unwrap();
+        }
+        fast.setup(&system);
+        assert_relative_eq!(energy(&system, &[&fast, &slow]), initial, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn velocity_verlet_drift_correction() {
+        let (mut system, a, b) = triatomic();
+        for vel in system.velocities.iter_mut() {
+            vel.z += 0.003;
+        }
+        let mut potentials = PotentialsBuilder::new()
+            .pair(Harmonic::new(10.0, 1.0), (a, a), 3.0, 1.0)
+            .pair(Harmonic::new(0.5, 3.0), (a, b), 5.0, 1.0)
+            .build();
+        potentials.setup(&system);
+        // the fixture already drifts along y at -0.002 / 4.0
+        let drift = Vector3::new(0.0, -0.0005, 0.003);
+        let drift_energy = 0.5 * 4.0 * drift.norm_squared();
+        let mut uncorrected = system.clone();
+
+        let mut integrator = VelocityVerlet::new(0.01).drift_correction(50);
+        integrator.setup(&system, &potentials);
+        let mut reference = VelocityVerlet::new(0.01);
+        reference.setup(&uncorrected, &potentials);
+        for _ in 0..120 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+            reference.integrate(&mut uncorrected, &potentials).unwrap();
+        }
+        let corrections: Vec<DriftCorrection> = integrator.corrections().to_vec();
+        assert_eq!(corrections.iter().map(|c| c.step).collect::<Vec<usize>>(), vec![50, 100]);
+        assert_relative_eq!(corrections[0].drift, drift, epsilon = 1e-6);
+        assert_relative_eq!(corrections[0].drift_energy, drift_energy, epsilon = 1e-7);
+        assert_relative_eq!(corrections[0].scale, 1.0, epsilon = 1e-4);
+        assert!(corrections[1].drift.norm() < 1e-6);
+
+        // only the energy of the center of mass motion is removed
+        let momentum: Vector3<Float> = system
+            .species
+            .iter()
+            .zip(system.velocities.iter())
+            .map(|(species, vel)| species.mass() * *vel)
+            .sum();
+        assert!(momentum.norm() < 1e-5);
+        assert_relative_eq!(
+            energy(&system, &[&potentials]),
+            energy(&uncorrected, &[&potentials]) - drift_energy,
+            epsilon = 1e-6
+        );
+    }
+
+    // Gas of two species without interactions.
+    fn gas(size: usize) -> (System, Species, Species) {
+        let (a, b) = (Species::new(39.948, 0.0), Species::new(131.29, 0.0));
+        let system = System {
+            size,
+            cell: Cell::cubic(20.0),
+            species: (0..size).map(|i| if i % 2 == 0 { a } else { b }).collect(),
+            positions: (0..size).map(|i| Vector3::new(0.1 * i as Float, 0.05 * i as Float, 10.0)).collect(),
+            velocities: vec![Vector3::new(0.05, 0.0, 0.0); size],
+            topology: Topology::default(),
+        };
+        (system, a, b)
+    }
+
+    #[test]
+    fn langevin_temperature() {
+        let (mut system, _, _) = gas(200);
+        system.velocities = vec![Vector3::zeros(); 200];
+        let potentials = PotentialsBuilder::new().build();
+        let mut integrator = Langevin::new(1.0, 300.0, 0.05);
+        integrator.setup(&system, &potentials);
+        let mut mean = 0.0;
+        for i in 0..2000 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+            if i >= 500 {
+                mean += Temperature.calculate_intrinsic(&system) / 1500.0;
+            }
+        }
+        assert!((mean - 300.0).abs() < 15.0, "{}", mean);
+    }
+
+    #[test]
+    fn langevin_species_and_layers() {
+        let (mut system, a, b) = gas(20);
+        let potentials = PotentialsBuilder::new().build();
+        // only the heavier species is damped toward zero temperature
+        let mut integrator = Langevin::new(1.0, 0.0, 0.0).species_friction(b, 0.5).species_friction(a, 0.0);
+        integrator.setup(&system, &potentials);
+        for _ in 0..20 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+        }
+        for (vel, species) in system.velocities.iter().zip(system.species.iter()) {
+            if *species == a {
+                assert_relative_eq!(vel.x, 0.05);
+            } else {
+                assert!(vel.norm() < 1e-5);
+            }
+        }
+
+        // a layer overrides the species friction of the atoms inside it
+        let (mut system, _, b) = gas(20);
+        system.positions[0].z = 1.0;
+        system.positions[1].z = 1.0;
+        let mut integrator = Langevin::new(1.0, 0.0, 0.0)
+            .species_friction(b, 0.5)
+            .damping_layer(Slab::new(2, 0.0, 0.1), 0.0);
+        integrator.setup(&system, &potentials);
+        for _ in 0..20 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+        }
+        assert_relative_eq!(system.velocities[0].x, 0.05);
+        assert_relative_eq!(system.velocities[1].x, 0.05);
+        assert!(system.velocities[3].norm() < 1e-5);
     }
 }
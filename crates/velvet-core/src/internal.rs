@@ -6,10 +6,12 @@ pub type Float = f32;
 
 pub mod consts {
     #[cfg(not(feature = "f64"))]
-    pub use std::f32::consts::{FRAC_2_SQRT_PI, PI};
+    pub use std::f32::consts::{FRAC_1_SQRT_2, FRAC_2_SQRT_PI, PI};
     #[cfg(feature = "f64")]
-    pub use std::f64::consts::{FRAC_2_SQRT_PI, PI};
+    pub use std::f64::consts::{FRAC_1_SQRT_2, FRAC_2_SQRT_PI, PI};
 
     pub const BOLTZMANN: super::Float = 0.001985875;
     pub const COULOMB: super::Float = 332.0636;
+    /// Conversion from Kcal/mole-angstrom^3 to atmospheres.
+    pub const PRESSURE: super::Float = 68568.415;
 }
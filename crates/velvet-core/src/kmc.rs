@@ -0,0 +1,374 @@
+//! Lattice kinetic Monte Carlo for long timescale diffusion.
+//!
+//! Atoms occupy the sites of a fixed [`Lattice`] and move by hopping into vacant neighboring
+//! sites. Lattices are built from a [`System`] and the occupied sites can be converted back
+//! into a [`System`], so configurations move freely between kinetic Monte Carlo and molecular
+//! dynamics.
+
+use nalgebra::Vector3;
+use rand::Rng;
+
+use crate::internal::consts::BOLTZMANN;
+use crate::internal::Float;
+use crate::random::shared_rng;
+use crate::system::cell::Cell;
+use crate::system::species::Species;
+use crate::system::topology::Topology;
+use crate::system::System;
+
+/// Periodic lattice of sites with precomputed neighbors.
+#[derive(Clone, Debug)]
+pub struct Lattice {
+    cell: Cell,
+    sites: Vec<Vector3<Float>>,
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl Lattice {
+    /// Returns a new [`Lattice`] whose sites are the positions of `system`.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - Configuration which defines the cell and the lattice sites.
+    /// * `cutoff` - Largest distance between neighboring sites.
+    pub fn from_system(system: &System, cutoff: Float) -> Lattice {
+        let sites = system.positions.clone();
+        let neighbors = (0..sites.len())
+            .map(|i| {
+                (0..sites.len())
+                    .filter(|&j| j != i && system.cell.distance(&sites[i], &sites[j]) < cutoff)
+                    .collect()
+            })
+            .collect();
+        Lattice {
+            cell: system.cell.clone(),
+            sites,
+            neighbors,
+        }
+    }
+
+    /// Returns the number of sites.
+    pub fn size(&self) -> usize {
+        self.sites.len()
+    }
+
+    /// Returns the cartesian position of each site.
+    pub fn sites(&self) -> &[Vector3<Float>] {
+        &self.sites
+    }
+
+    /// Returns the neighbors of `site`.
+    pub fn neighbors(&self, site: usize) -> &[usize] {
+        &self.neighbors[site]
+    }
+
+    // Returns the minimum image vector from site `a` to site `b`.
+    fn hop_vector(&self, a: usize, b: usize) -> Vector3<Float> {
+        let mut d = self.sites[b] - self.sites[a];
+        self.cell.vector_image(&mut d);
+        d
+    }
+}
+
+/// Shared behavior for models which assign rates to hops between lattice sites.
+pub trait RateModel: Send + Sync {
+    /// Returns the rate in inverse femtoseconds at which the atom of `species` on site `from`
+    /// hops into the vacant site `to`, or `None` if the hop is forbidden.
+    fn rate(&self, species: Species, from: usize, to: usize, occupancy: &[Option<usize>]) -> Option<Float>;
+}
+
+/// Arrhenius rates with a prefactor and barrier for each species.
+///
+/// The rate of a hop is `prefactor * exp(-barrier / (kB * T))`. Hops of species without an entry are forbidden.
+#[derive(Clone, Debug, Default)]
+pub struct ArrheniusRates {
+    temperature: Float,
+    entries: Vec<(Species, Float)>,
+}
+
+impl ArrheniusRates {
+    /// Returns a new [`ArrheniusRates`] model at `temperature`.
+    pub fn new(temperature: Float) -> ArrheniusRates {
+        ArrheniusRates {
+            temperature,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Adds the hops of `species`.
+    ///
+    /// # Arguments
+    ///
+    /// * `species` - Species which hops.
+    /// * `prefactor` - Attempt frequency in inverse femtoseconds.
+    /// * `barrier` - Migration energy barrier in kcal/mol.
+    pub fn hop(mut self, species: Species, prefactor: Float, barrier: Float) -> ArrheniusRates {
+        let rate = prefactor * Float::exp(-barrier / (BOLTZMANN * self.temperature));
+        self.entries.retain(|(s, _)| *s != species);
+        self.entries.push((species, rate));
+        self
+    }
+}
+
+impl RateModel for ArrheniusRates {
+    fn rate(&self, species: Species, _: usize, _: usize, _: &[Option<usize>]) -> Option<Float> {
+        self.entries
+            .iter()
+            .find(|(s, _)| *s == species)
+            .map(|(_, rate)| *rate)
+    }
+}
+
+/// Hop of an atom from an occupied site into a vacant neighboring site.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Event {
+    /// Site the atom leaves.
+    pub from: usize,
+    /// Site the atom moves into.
+    pub to: usize,
+    /// Rate of the hop in inverse femtoseconds.
+    pub rate: Float,
+}
+
+/// Lattice kinetic Monte Carlo with the BKL (residence time) algorithm.
+///
+/// The catalog of possible hops is kept per site. Every step selects one hop with a
+/// probability proportional to its rate, advances the clock by an exponentially distributed
+/// residence time with the total rate as its parameter, and refreshes the catalog around the
+/// two sites involved. The unwrapped displacement of each atom is tracked for diffusion
+/// analysis.
+///
+/// # References
+///
+/// [1] Bortz, Alfred B., Malvin H. Kalos, and Joel L. Lebowitz. "A new algorithm for Monte Carlo simulation of Ising spin systems." Journal of Computational physics 17.1 (1975): 10-18.
+pub struct KineticMonteCarlo<R: RateModel> {
+    lattice: Lattice,
+    rates: R,
+    species: Vec<Species>,
+    occupancy: Vec<Option<usize>>,
+    displacements: Vec<Vector3<Float>>,
+    catalog: Vec<Vec<Event>>,
+    time: Float,
+}
+
+impl<R: RateModel> KineticMonteCarlo<R> {
+    /// Returns a new [`KineticMonteCarlo`] simulation.
+    ///
+    /// # Arguments
+    ///
+    /// * `lattice` - Lattice of sites.
+    /// * `occupancy` - Species on each site or `None` for a vacancy.
+    /// * `rates` - Model which assigns the rate of each hop.
+    pub fn new(lattice: Lattice, occupancy: Vec<Option<Species>>, rates: R) -> KineticMonteCarlo<R> {
+        assert_eq!(
+            lattice.size(),
+            occupancy.len(),
+            "Occupancy must list every site of the lattice."
+        );
+        let mut species = Vec::new();
+        let occupancy = occupancy
+            .into_iter()
+            .map(|site| {
+                site.map(|s| {
+                    species.push(s);
+                    species.len() - 1
+                })
+            })
+            .collect();
+        let mut kmc = KineticMonteCarlo {
+            catalog: vec![Vec::new(); lattice.size()],
+            displacements: vec![Vector3::zeros(); species.len()],
+            lattice,
+            rates,
+            species,
+            occupancy,
+            time: 0.0,
+        };
+        for site in 0..kmc.lattice.size() {
+            kmc.refresh(site);
+        }
+        kmc
+    }
+
+    /// Returns the elapsed time in femtoseconds.
+    pub fn time(&self) -> Float {
+        self.time
+    }
+
+    /// Returns the index of the atom on each site or `None` for a vacancy.
+    pub fn occupancy(&self) -> &[Option<usize>] {
+        &self.occupancy
+    }
+
+    /// Returns the sum of the rates of every possible hop.
+    pub fn total_rate(&self) -> Float {
+        self.catalog.iter().flatten().map(|event| event.rate).sum()
+    }
+
+    /// Returns the unwrapped displacement of each atom since the start of the simulation.
+    pub fn displacements(&self) -> &[Vector3<Float>] {
+        &self.displacements
+    }
+
+    /// Returns the mean squared displacement of every atom of `species`.
+    pub fn mean_squared_displacement(&self, species: Species) -> Float {
+        let (sum, count) = self
+            .species
+            .iter()
+            .zip(self.displacements.iter())
+            .filter(|(s, _)| **s == species)
+            .fold((0.0, 0), |(sum, count), (_, d)| (sum + d.norm_squared(), count + 1));
+        if count == 0 {
+            0.0
+        } else {
+            sum / count as Float
+        }
+    }
+
+    /// Executes one hop and returns it, or `None` if no hop is possible.
+    pub fn step(&mut self) -> Option<Event> {
+        let total = self.total_rate();
+        if total <= 0.0 {
+            return None;
+        }
+        let mut rng = shared_rng();
+        let target = total * rng.gen::<Float>();
+        let mut cumulative = 0.0;
+        let mut chosen = None;
+        for event in self.catalog.iter().flatten() {
+            chosen = Some(*event);
+            cumulative += event.rate;
+            if cumulative > target {
+                break;
+            }
+        }
+        let event = chosen?;
+
+        // residence time of the current configuration
+        let uniform: Float = rng.gen_range(Float::EPSILON, 1.0);
+        self.time -= uniform.ln() / total;
+
+        let atom = self.occupancy[event.from].take().unwrap();
+        self.occupancy[event.to] = Some(atom);
+        self.displacements[atom] += self.lattice.hop_vector(event.from, event.to);
+
+        // only hops which start or end next to the two changed sites are affected
+        let mut affected = vec![event.from, event.to];
+        affected.extend_from_slice(self.lattice.neighbors(event.from));
+        affected.extend_from_slice(self.lattice.neighbors(event.to));
+        affected.sort_unstable();
+        affected.dedup();
+        for site in affected {
+            self.refresh(site);
+        }
+        Some(event)
+    }
+
+    /// Executes up to `steps` hops and returns the number executed.
+    pub fn run(&mut self, steps: usize) -> usize {
+        (0..steps).take_while(|_| self.step().is_some()).count()
+    }
+
+    /// Returns a [`System`] of the atoms on their current sites.
+    pub fn system(&self) -> System {
+        let (species, positions): (Vec<Species>, Vec<Vector3<Float>>) = self
+            .occupancy
+            .iter()
+            .zip(self.lattice.sites.iter())
+            .filter_map(|(site, pos)| site.map(|atom| (self.species[atom], *pos)))
+            .unzip();
+        System {
+            size: species.len(),
+            cell: self.lattice.cell.clone(),
+            velocities: vec![Vector3::zeros(); species.len()],
+            species,
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    // Rebuilds the hops out of `site`.
+    fn refresh(&mut self, site: usize) {
+        self.catalog[site].clear();
+        let atom = match self.occupancy[site] {
+            Some(atom) => atom,
+            None => return,
+        };
+        for &to in self.lattice.neighbors(site) {
+            if self.occupancy[to].is_none() {
+                if let Some(rate) = self.rates.rate(self.species[atom], site, to, &self.occupancy) {
+                    self.catalog[site].push(Event { from: site, to, rate });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArrheniusRates, KineticMonteCarlo, Lattice};
+    use crate::internal::Float;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // simple cubic lattice with 5x5x5 sites
+    fn simple_cubic() -> System {
+        let species = Species::new(63.546, 0.0);
+        let mut positions = Vec::new();
+        for i in 0..5 {
+            for j in 0..5 {
+                for k in 0..5 {
+                    positions.push(Vector3::new(i as Float, j as Float, k as Float) * 2.5);
+                }
+            }
+        }
+        System {
+            size: positions.len(),
+            cell: Cell::cubic(12.5),
+            species: vec![species; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn vacancy_diffusion() {
+        let system = simple_cubic();
+        let species = system.species[0];
+        let lattice = Lattice::from_system(&system, 3.0);
+        assert!((0..lattice.size()).all(|site| lattice.neighbors(site).len() == 6));
+
+        let mut occupancy: Vec<Option<Species>> = system.species.iter().map(|s| Some(*s)).collect();
+        occupancy[62] = None;
+        let rates = ArrheniusRates::new(600.0).hop(species, 1e-2, 10.0);
+        let mut kmc = KineticMonteCarlo::new(lattice, occupancy, rates);
+
+        // six neighbors may hop into the single vacancy
+        let rate = 1e-2 * Float::exp(-10.0 / (0.001985875 * 600.0));
+        assert!((kmc.total_rate() - 6.0 * rate).abs() < 1e-6 * rate);
+
+        let steps = 20000;
+        let mut vacancy = 62;
+        for _ in 0..steps {
+            let event = kmc.step().unwrap();
+            // the hop always fills the vacancy
+            assert_eq!(event.to, vacancy);
+            vacancy = event.from;
+        }
+        assert!((kmc.total_rate() - 6.0 * rate).abs() < 1e-6 * rate);
+
+        // the mean residence time is the inverse of the total rate
+        let expected = steps as Float / (6.0 * rate);
+        assert!((kmc.time() - expected).abs() < 0.05 * expected);
+
+        // every hop displaces one atom by a lattice spacing
+        let msd = kmc.mean_squared_displacement(species) * system.size as Float;
+        assert!(msd > 0.0 && msd <= steps as Float * 2.5 * 2.5 + 1e-3);
+        let final_system = kmc.system();
+        assert_eq!(final_system.size, system.size - 1);
+    }
+}
@@ -8,49 +8,134 @@
 //! * `energy` - Kcal/mole
 //! * `force` - Kcal/mole-angstrom
 //! * `temperature` - Kelvin
+//! * `pressure` - atmospheres
 
 #![warn(missing_docs)]
 #![warn(clippy::all)]
+// Literals are shared between the `f32` and `f64` storage types.
+#![allow(clippy::excessive_precision)]
 
 #[macro_use]
 extern crate strum_macros;
 
+pub mod barostats;
+pub mod basin_hopping;
+pub mod checkpoint;
+pub mod coarse_graining;
+pub mod colvars;
 pub mod config;
+pub mod constraints;
+pub mod convergence;
+pub mod correlation;
+pub mod coupling;
+pub mod domains;
+pub mod electrodes;
+pub mod ensemble;
+pub mod errors;
+pub mod fitting;
+pub mod flow;
+pub mod hooks;
 pub mod integrators;
 mod internal;
+pub mod kmc;
+pub mod minimizers;
+pub mod monte_carlo;
+pub mod neb;
 pub mod outputs;
+pub mod parallel_replica;
 pub mod potentials;
+pub mod precision;
 pub mod propagators;
+pub mod rigid_bodies;
 pub mod properties;
+pub mod random;
 pub mod selection;
 pub mod simulation;
+pub mod stopping;
 pub mod system;
+pub mod tensile;
+pub mod thermal_expansion;
 pub mod thermostats;
+pub mod trajectory;
 pub mod velocity_distributions;
 
 /// User facing exports.
 pub mod prelude {
+    pub use super::barostats::*;
+    pub use super::basin_hopping::*;
+    pub use super::checkpoint::*;
+    pub use super::coarse_graining::*;
+    pub use super::colvars::*;
     pub use super::config::*;
+    pub use super::constraints::*;
+    pub use super::convergence::*;
+    pub use super::correlation::*;
+    pub use super::coupling::*;
+    pub use super::domains::*;
+    pub use super::electrodes::*;
+    pub use super::ensemble::*;
+    pub use super::errors::*;
+    pub use super::fitting::*;
+    pub use super::flow::*;
+    pub use super::hooks::*;
     pub use super::integrators::*;
+    pub use super::kmc::*;
+    pub use super::minimizers::*;
+    pub use super::monte_carlo::*;
+    pub use super::neb::*;
+    pub use super::outputs::compression::*;
     #[cfg(feature = "hdf5-output")]
     pub use super::outputs::hdf5::*;
     pub use super::outputs::raw::*;
+    pub use super::outputs::thermo::*;
+    pub use super::outputs::trajectory::*;
     pub use super::outputs::*;
+    pub use super::parallel_replica::*;
+    pub use super::potentials::adaptive::*;
     pub use super::potentials::coulomb::*;
+    pub use super::potentials::cutoffs::*;
+    pub use super::potentials::eam::*;
+    pub use super::potentials::external::*;
     pub use super::potentials::pair::*;
+    pub use super::potentials::restraints::*;
     pub use super::potentials::types::*;
     pub use super::potentials::*;
+    pub use super::precision::*;
     pub use super::propagators::*;
+    pub use super::properties::electrostatics::*;
     pub use super::properties::energy::*;
     pub use super::properties::forces::*;
+    pub use super::properties::lattice::*;
+    pub use super::properties::msd::*;
+    pub use super::properties::per_atom::*;
+    pub use super::properties::pressure::*;
+    pub use super::properties::rdf::*;
+    pub use super::properties::rmsd::*;
+    pub use super::properties::shape::*;
     pub use super::properties::temperature::*;
+    pub use super::properties::vacf::*;
+    pub use super::properties::velocities::*;
     pub use super::properties::*;
+    pub use super::random::*;
+    pub use super::rigid_bodies::*;
     pub use super::selection::*;
     pub use super::simulation::*;
+    pub use super::stopping::*;
     pub use super::system::cell::*;
+    pub use super::system::charges::*;
+    pub use super::system::cleanup::*;
+    pub use super::system::coexistence::*;
+    pub use super::system::defects::*;
+    pub use super::system::diff::*;
     pub use super::system::elements::*;
+    pub use super::system::select::*;
+    pub use super::system::soa::*;
     pub use super::system::species::*;
+    pub use super::system::topology::*;
     pub use super::system::*;
+    pub use super::tensile::*;
+    pub use super::thermal_expansion::*;
     pub use super::thermostats::*;
+    pub use super::trajectory::*;
     pub use super::velocity_distributions::*;
 }
@@ -0,0 +1,710 @@
+//! Energy minimization algorithms which relax the positions of a system.
+//!
+//! Minimizers are [`Propagator`]s, so a structure is relaxed by running a
+//! [`Simulation`](crate::simulation::Simulation) which stops early once the minimizer converges.
+//! The relaxed system can then be handed to [`MolecularDynamics`](crate::propagators::MolecularDynamics).
+//! The forces on frozen atoms of the topology are taken to be zero, so they stay in place.
+
+use nalgebra::{DVector, Vector3};
+use rand::Rng;
+
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::properties::energy::PotentialEnergy;
+use crate::properties::forces::Forces;
+use crate::properties::Property;
+use crate::random::shared_rng;
+use crate::system::System;
+
+/// Number of consecutive downhill steps before the FIRE timestep may grow.
+const FIRE_DELAY: usize = 5;
+
+/// Maximum number of times a conjugate gradient line search halves its step.
+const MAX_BACKTRACKS: usize = 40;
+
+/// Fraction of the linearly predicted decrease a line search step must achieve.
+const ARMIJO: Float = 1e-4;
+
+/// Multiple of the minimum distance overlapping atoms are pushed apart to, which keeps pairs
+/// from settling just inside the minimum distance.
+const OVERLAP_MARGIN: Float = 1.01;
+
+/// Convergence criteria shared by the minimizers.
+///
+/// Minimization stops when the largest force on any atom falls below `force_tolerance` or the
+/// energy decreases by less than `energy_tolerance` in an accepted step. An energy tolerance
+/// of zero disables the energy criterion.
+#[derive(Clone, Copy, Debug)]
+pub struct Convergence {
+    /// Largest magnitude of the force on any atom in kcal/mol/angstrom.
+    pub force_tolerance: Float,
+    /// Smallest change in the potential energy in kcal/mol.
+    pub energy_tolerance: Float,
+}
+
+impl Default for Convergence {
+    fn default() -> Convergence {
+        Convergence {
+            force_tolerance: 1e-3,
+            energy_tolerance: 0.0,
+        }
+    }
+}
+
+impl Convergence {
+    fn reached(&self, forces: &[Vector3<Float>], change: Float) -> bool {
+        max_force(forces) < self.force_tolerance || change.abs() < self.energy_tolerance
+    }
+}
+
+// Returns the force on each atom, which is zero for the frozen atoms of the topology so they stay
+// in place.
+fn mobile_forces(system: &System, potentials: &Potentials) -> Vec<Vector3<Float>> {
+    let mut forces = Forces.calculate(system, potentials);
+    for &i in &system.topology.frozen {
+        forces[i] = Vector3::zeros();
+    }
+    forces
+}
+
+// Returns the largest magnitude of any atomic force.
+fn max_force(forces: &[Vector3<Float>]) -> Float {
+    forces.iter().map(|f| f.norm()).fold(0.0, Float::max)
+}
+
+/// Steepest descent minimization with an adaptive step size.
+///
+/// Atoms move along the forces such that the atom under the largest force moves by the current
+/// step size. The step size grows by 20% after a step which lowers the energy, while a step
+/// which raises the energy is rejected and the step size is reduced to 20%.
+#[derive(Clone, Debug)]
+pub struct SteepestDescent {
+    step_size: Float,
+    criteria: Convergence,
+    energy: Float,
+    forces: Vec<Vector3<Float>>,
+    converged: bool,
+}
+
+impl SteepestDescent {
+    /// Returns a new [`SteepestDescent`] minimizer.
+    ///
+    /// # Arguments
+    ///
+    /// * `step_size` - Initial displacement in angstroms of the atom under the largest force.
+    pub fn new(step_size: Float) -> SteepestDescent {
+        SteepestDescent {
+            step_size,
+            criteria: Convergence::default(),
+            energy: 0.0,
+            forces: Vec::new(),
+            converged: false,
+        }
+    }
+
+    /// Sets the criteria which end the minimization.
+    pub fn convergence(mut self, criteria: Convergence) -> SteepestDescent {
+        self.criteria = criteria;
+        self
+    }
+}
+
+impl Propagator for SteepestDescent {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        self.energy = PotentialEnergy.calculate(system, potentials);
+        self.forces = mobile_forces(system, potentials);
+        self.converged = max_force(&self.forces) < self.criteria.force_tolerance;
+        Ok(())
+    }
+
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        if self.converged {
+            return;
+        }
+        let scale = self.step_size / max_force(&self.forces);
+        let previous = system.positions.clone();
+        system
+            .positions
+            .iter_mut()
+            .zip(self.forces.iter())
+            .for_each(|(pos, f)| *pos += scale * f);
+
+        let energy = PotentialEnergy.calculate(system, potentials);
+        if energy <= self.energy {
+            let change = energy - self.energy;
+            self.energy = energy;
+            self.forces = mobile_forces(system, potentials);
+            self.step_size *= 1.2;
+            self.converged = self.criteria.reached(&self.forces, change);
+        } else {
+            system.positions = previous;
+            self.step_size *= 0.2;
+        }
+    }
+
+    fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
+// Velocity mixing and adaptive timestep of the FIRE algorithm for unit masses.
+#[derive(Clone, Debug)]
+pub(crate) struct FireState {
+    timestep: Float,
+    max_timestep: Float,
+    alpha: Float,
+    downhill: usize,
+}
+
+impl FireState {
+    pub(crate) fn new(timestep: Float) -> FireState {
+        FireState {
+            timestep,
+            max_timestep: 10.0 * timestep,
+            alpha: 0.1,
+            downhill: 0,
+        }
+    }
+
+    // Returns `true` if the last step moved along the forces.
+    pub(crate) fn downhill(&self) -> bool {
+        self.downhill > 0
+    }
+
+    // Updates `velocities` from `forces` and returns the displacement of each atom, scaled
+    // down such that no atom moves farther than `max_step`.
+    pub(crate) fn step(
+        &mut self,
+        velocities: &mut [Vector3<Float>],
+        forces: &[Vector3<Float>],
+        max_step: Float,
+    ) -> Vec<Vector3<Float>> {
+        // mix the velocity toward the force while moving downhill
+        let power: Float = velocities.iter().zip(forces.iter()).map(|(v, f)| v.dot(f)).sum();
+        if power > 0.0 {
+            let v_norm = velocities.iter().map(|v| v.norm_squared()).sum::<Float>().sqrt();
+            let f_norm = forces.iter().map(|f| f.norm_squared()).sum::<Float>().sqrt();
+            for (v, f) in velocities.iter_mut().zip(forces.iter()) {
+                *v = (1.0 - self.alpha) * *v + self.alpha * v_norm / f_norm * f;
+            }
+            if self.downhill > FIRE_DELAY {
+                self.timestep = Float::min(self.timestep * 1.1, self.max_timestep);
+                self.alpha *= 0.99;
+            }
+            self.downhill += 1;
+        } else {
+            velocities.iter_mut().for_each(|v| *v = Vector3::zeros());
+            self.timestep *= 0.5;
+            self.alpha = 0.1;
+            self.downhill = 0;
+        }
+
+        // explicit Euler step limited by the largest atomic displacement
+        let dt = self.timestep;
+        for (v, f) in velocities.iter_mut().zip(forces.iter()) {
+            *v += dt * f;
+        }
+        let largest = velocities.iter().map(|v| v.norm() * dt).fold(0.0, Float::max);
+        let scale = if largest > max_step { max_step / largest } else { 1.0 };
+        velocities.iter().map(|v| scale * dt * v).collect()
+    }
+}
+
+/// Fast inertial relaxation engine (FIRE) minimization.
+///
+/// Atoms follow damped dynamics with unit masses whose velocities are continuously mixed
+/// toward the direction of the forces. The timestep grows while the system moves downhill and
+/// the velocities are reset whenever the motion turns uphill. The velocities of the system are
+/// not modified.
+///
+/// # References
+///
+/// [1] Bitzek, Erik, et al. "Structural relaxation made simple." Physical review letters 97.17 (2006): 170201.
+#[derive(Clone, Debug)]
+pub struct Fire {
+    timestep: Float,
+    max_step: Float,
+    criteria: Convergence,
+    state: FireState,
+    velocities: Vec<Vector3<Float>>,
+    energy: Float,
+    forces: Vec<Vector3<Float>>,
+    converged: bool,
+}
+
+impl Fire {
+    /// Returns a new [`Fire`] minimizer.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestep` - Initial timestep, the timestep grows to at most ten times this value.
+    /// * `max_step` - Largest displacement in angstroms of any atom in one step.
+    pub fn new(timestep: Float, max_step: Float) -> Fire {
+        Fire {
+            timestep,
+            max_step,
+            criteria: Convergence::default(),
+            state: FireState::new(timestep),
+            velocities: Vec::new(),
+            energy: 0.0,
+            forces: Vec::new(),
+            converged: false,
+        }
+    }
+
+    /// Sets the criteria which end the minimization.
+    pub fn convergence(mut self, criteria: Convergence) -> Fire {
+        self.criteria = criteria;
+        self
+    }
+}
+
+impl Propagator for Fire {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        self.state = FireState::new(self.timestep);
+        self.velocities = vec![Vector3::zeros(); system.size];
+        self.energy = PotentialEnergy.calculate(system, potentials);
+        self.forces = mobile_forces(system, potentials);
+        self.converged = max_force(&self.forces) < self.criteria.force_tolerance;
+        Ok(())
+    }
+
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        if self.converged {
+            return;
+        }
+        let displacements = self.state.step(&mut self.velocities, &self.forces, self.max_step);
+        system
+            .positions
+            .iter_mut()
+            .zip(displacements.iter())
+            .for_each(|(pos, d)| *pos += d);
+
+        let energy = PotentialEnergy.calculate(system, potentials);
+        let change = energy - self.energy;
+        self.energy = energy;
+        self.forces = mobile_forces(system, potentials);
+        // the energy barely changes right after the velocities are reset
+        self.converged = if self.state.downhill() {
+            self.criteria.reached(&self.forces, change)
+        } else {
+            max_force(&self.forces) < self.criteria.force_tolerance
+        };
+    }
+
+    fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
+/// Nonlinear conjugate gradient minimization with a backtracking line search.
+///
+/// The positions of every atom are treated as one flattened vector whose gradient is the
+/// negative of the [`Forces`] property. Search directions are updated with the Polak-Ribiere
+/// formula, restarting along the steepest descent direction whenever `beta` becomes negative,
+/// the direction points uphill, or after as many iterations as there are degrees of freedom.
+/// Each step backtracks from a trial step, which moves the atom farthest along the search
+/// direction by `max_step`, until the Armijo sufficient decrease condition holds.
+///
+/// The minimization also ends when the line search can no longer lower the energy, which
+/// usually happens once the forces approach the numerical precision of the energy.
+///
+/// # References
+///
+/// [1] Nocedal, Jorge, and Stephen J. Wright. Numerical optimization. Springer, 2006.
+#[derive(Clone, Debug)]
+pub struct ConjugateGradient {
+    max_step: Float,
+    criteria: Convergence,
+    energy: Float,
+    gradient: DVector<Float>,
+    direction: DVector<Float>,
+    iterations: usize,
+    converged: bool,
+}
+
+impl ConjugateGradient {
+    /// Returns a new [`ConjugateGradient`] minimizer.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_step` - Largest displacement in angstroms of any atom in a trial step.
+    pub fn new(max_step: Float) -> ConjugateGradient {
+        ConjugateGradient {
+            max_step,
+            criteria: Convergence::default(),
+            energy: 0.0,
+            gradient: DVector::zeros(0),
+            direction: DVector::zeros(0),
+            iterations: 0,
+            converged: false,
+        }
+    }
+
+    /// Sets the criteria which end the minimization.
+    pub fn convergence(mut self, criteria: Convergence) -> ConjugateGradient {
+        self.criteria = criteria;
+        self
+    }
+}
+
+// Returns the gradient of the energy as a flattened vector.
+fn gradient(forces: &[Vector3<Float>]) -> DVector<Float> {
+    -DVector::from_iterator(3 * forces.len(), forces.iter().flat_map(|f| f.iter().copied()))
+}
+
+impl Propagator for ConjugateGradient {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        let forces = mobile_forces(system, potentials);
+        self.energy = PotentialEnergy.calculate(system, potentials);
+        self.gradient = gradient(&forces);
+        self.direction = -&self.gradient;
+        self.iterations = 0;
+        self.converged = max_force(&forces) < self.criteria.force_tolerance;
+        Ok(())
+    }
+
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        if self.converged {
+            return;
+        }
+        let mut slope = self.gradient.dot(&self.direction);
+        if slope >= 0.0 {
+            self.direction = -&self.gradient;
+            slope = self.gradient.dot(&self.direction);
+        }
+
+        // backtrack from a step limited by the largest atomic displacement
+        let longest = self
+            .direction
+            .as_slice()
+            .chunks(3)
+            .map(|d| Vector3::from_column_slice(d).norm())
+            .fold(0.0, Float::max);
+        let mut alpha = self.max_step / longest;
+        let previous = system.positions.clone();
+        let mut accepted = None;
+        for _ in 0..MAX_BACKTRACKS {
+            let steps = self.direction.as_slice().chunks(3);
+            for ((pos, prev), d) in system.positions.iter_mut().zip(previous.iter()).zip(steps) {
+                *pos = prev + alpha * Vector3::from_column_slice(d);
+            }
+            let energy = PotentialEnergy.calculate(system, potentials);
+            if energy <= self.energy + ARMIJO * alpha * slope {
+                accepted = Some(energy);
+                break;
+            }
+            alpha *= 0.5;
+        }
+        let energy = match accepted {
+            Some(energy) => energy,
+            None => {
+                system.positions = previous;
+                self.converged = true;
+                return;
+            }
+        };
+
+        let forces = mobile_forces(system, potentials);
+        let gradient = gradient(&forces);
+        let beta = gradient.dot(&(&gradient - &self.gradient)) / self.gradient.norm_squared();
+        self.iterations += 1;
+        self.direction = if beta > 0.0 && !self.iterations.is_multiple_of(gradient.len()) {
+            -&gradient + beta * &self.direction
+        } else {
+            -&gradient
+        };
+        let change = energy - self.energy;
+        self.energy = energy;
+        self.gradient = gradient;
+        self.converged = self.criteria.reached(&forces, change);
+    }
+
+    fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
+/// Removal of overlapping atoms by pushing apart every pair closer than a minimum distance.
+///
+/// Each step finds the pairs of atoms closer than `distance` using a cell list, so the cost of
+/// a step grows linearly with the number of atoms, and moves both atoms of each pair apart along
+/// their separation by half of the overlap. The displacement of each atom in one step is capped
+/// at `max_step`, and frozen atoms of the topology are not moved. Atoms at the same position are
+/// pushed apart in a random direction. The potentials are never evaluated, so unlike energy
+/// minimization this copes with the huge forces of the nearly coincident atoms left by random
+/// insertion. The result is a starting point for one of the energy minimizers rather than a
+/// relaxed structure.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // separate atoms closer than 2 angstroms, then relax with FIRE
+/// let overlaps = OverlapRemoval::new(2.0).max_step(0.5);
+/// let fire = Fire::new(0.1, 0.2);
+/// ```
+#[derive(Clone, Debug)]
+pub struct OverlapRemoval {
+    distance: Float,
+    max_step: Float,
+    overlaps: usize,
+}
+
+impl OverlapRemoval {
+    /// Returns a new [`OverlapRemoval`] minimizer.
+    ///
+    /// # Arguments
+    ///
+    /// * `distance` - Minimum distance in angstroms between any two atoms.
+    pub fn new(distance: Float) -> OverlapRemoval {
+        OverlapRemoval {
+            distance,
+            max_step: 0.5 * distance,
+            overlaps: 0,
+        }
+    }
+
+    /// Sets the largest displacement in angstroms of any atom in one step, which defaults to half the minimum distance.
+    pub fn max_step(mut self, max_step: Float) -> OverlapRemoval {
+        self.max_step = max_step;
+        self
+    }
+
+    /// Returns the number of pairs closer than the minimum distance before the most recent step.
+    pub fn overlaps(&self) -> usize {
+        self.overlaps
+    }
+}
+
+impl Propagator for OverlapRemoval {
+    fn setup(&mut self, system: &mut System, _: &Potentials) -> Result<(), VelvetError> {
+        self.overlaps = overlapping_pairs(system, self.distance).len();
+        Ok(())
+    }
+
+    fn propagate(&mut self, system: &mut System, _: &Potentials) {
+        let pairs = overlapping_pairs(system, self.distance);
+        self.overlaps = pairs.len();
+        if pairs.is_empty() {
+            return;
+        }
+        let mut rng = shared_rng();
+        let mut displacements = vec![Vector3::zeros(); system.size];
+        for (i, j, separation) in pairs {
+            let r = separation.norm();
+            let direction = if r > Float::EPSILON {
+                separation / r
+            } else {
+                let v: Vector3<Float> = Vector3::from_fn(|_, _| rng.gen_range(-1.0, 1.0));
+                v.normalize()
+            };
+            let push = 0.5 * (OVERLAP_MARGIN * self.distance - r) * direction;
+            displacements[i] -= push;
+            displacements[j] += push;
+        }
+        for &i in &system.topology.frozen {
+            displacements[i] = Vector3::zeros();
+        }
+        for (position, displacement) in system.positions.iter_mut().zip(displacements.iter()) {
+            let norm = displacement.norm();
+            if norm > self.max_step {
+                *position += displacement * (self.max_step / norm);
+            } else {
+                *position += displacement;
+            }
+        }
+    }
+
+    fn converged(&self) -> bool {
+        self.overlaps == 0
+    }
+}
+
+// Returns the indices and separation vector of every pair of atoms closer than `distance`.
+fn overlapping_pairs(system: &System, distance: Float) -> Vec<(usize, usize, Vector3<Float>)> {
+    let cell = &system.cell;
+    let (a, b, c) = (cell.a_vector(), cell.b_vector(), cell.c_vector());
+    let volume = cell.volume();
+    // the bins along each lattice vector are at least as wide as the distance
+    let widths = [volume / b.cross(&c).norm(), volume / a.cross(&c).norm(), volume / a.cross(&b).norm()];
+    let shape: Vec<usize> = widths
+        .iter()
+        .map(|width| ((width / distance).floor() as usize).max(1))
+        .collect();
+    let flat = |bin: [usize; 3]| (bin[0] * shape[1] + bin[1]) * shape[2] + bin[2];
+
+    let mut bins: Vec<Vec<usize>> = vec![Vec::new(); shape.iter().product()];
+    let mut locations = Vec::with_capacity(system.size);
+    for (i, position) in system.positions.iter().enumerate() {
+        let fractional = cell.fractional(position);
+        let mut bin = [0; 3];
+        for k in 0..3 {
+            let s = fractional[k] - fractional[k].floor();
+            bin[k] = ((s * shape[k] as Float) as usize).min(shape[k] - 1);
+        }
+        bins[flat(bin)].push(i);
+        locations.push(bin);
+    }
+
+    // every bin along an axis with fewer than three bins is a neighbor
+    let neighbors = |bin: usize, n: usize| -> Vec<usize> {
+        if n < 3 {
+            (0..n).collect()
+        } else {
+            vec![(bin + n - 1) % n, bin, (bin + 1) % n]
+        }
+    };
+    let mut pairs = Vec::new();
+    for (i, bin) in locations.iter().enumerate() {
+        for x in neighbors(bin[0], shape[0]) {
+            for y in neighbors(bin[1], shape[1]) {
+                for z in neighbors(bin[2], shape[2]) {
+                    for &j in bins[flat([x, y, z])].iter().filter(|&&j| j > i) {
+                        let mut separation = system.positions[j] - system.positions[i];
+                        cell.vector_image(&mut separation);
+                        if separation.norm() < distance {
+                            pairs.push((i, j, separation));
+                        }
+                    }
+                }
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConjugateGradient, Convergence, Fire, OverlapRemoval, SteepestDescent};
+    use crate::config::ConfigurationBuilder;
+    use crate::internal::Float;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::PotentialsBuilder;
+    use crate::propagators::Propagator;
+    use crate::properties::forces::Forces;
+    use crate::properties::Property;
+    use crate::simulation::Simulation;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // distorted argon trimer whose minimum is an equilateral triangle with sides of 2^(1/6) sigma
+    fn trimer<P: Propagator + 'static>(propagator: P) -> Simulation {
+        let argon = Species::new(39.948, 0.0);
+        let system = System {
+            size: 3,
+            cell: Cell::cubic(30.0),
+            species: vec![argon; 3],
+            positions: vec![
+                Vector3::new(10.0, 10.0, 10.0),
+                Vector3::new(14.2, 10.3, 10.0),
+                Vector3::new(12.0, 13.0, 10.4),
+            ],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (argon, argon), 12.0, 1.0)
+            .build();
+        Simulation::new(system, potentials, propagator, ConfigurationBuilder::new().build())
+    }
+
+    fn assert_relaxed(simulation: &Simulation) {
+        let system = simulation.system();
+        let r0 = 3.4 * Float::powf(2.0, 1.0 / 6.0);
+        for (i, j) in [(0, 1), (1, 2), (0, 2)].iter() {
+            let r = system.cell.distance(&system.positions[*i], &system.positions[*j]);
+            assert!((r - r0).abs() < 1e-2, "distance {} differs from {}", r, r0);
+        }
+        let forces = Forces.calculate(system, simulation.potentials());
+        assert!(forces.iter().all(|f| f.norm() < 1e-3));
+    }
+
+    #[test]
+    fn steepest_descent() {
+        let mut simulation = trimer(SteepestDescent::new(0.1));
+        simulation.run(5000).unwrap();
+        assert_relaxed(&simulation);
+        assert!(simulation.step() < 5000);
+    }
+
+    #[test]
+    fn fire() {
+        let mut simulation = trimer(Fire::new(0.1, 0.2));
+        simulation.run(5000).unwrap();
+        assert_relaxed(&simulation);
+        assert!(simulation.step() < 5000);
+    }
+
+    #[test]
+    fn energy_tolerance() {
+        let criteria = Convergence {
+            force_tolerance: 0.0,
+            energy_tolerance: 1e-3,
+        };
+        let mut loose = trimer(SteepestDescent::new(0.1).convergence(criteria));
+        loose.run(5000).unwrap();
+        let mut tight = trimer(SteepestDescent::new(0.1));
+        tight.run(5000).unwrap();
+        assert!(loose.step() < tight.step());
+    }
+
+    #[test]
+    fn conjugate_gradient() {
+        let criteria = Convergence {
+            force_tolerance: 1e-4,
+            energy_tolerance: 0.0,
+        };
+        let mut cg = trimer(ConjugateGradient::new(0.2).convergence(criteria));
+        cg.run(5000).unwrap();
+        assert_relaxed(&cg);
+        let mut sd = trimer(SteepestDescent::new(0.1).convergence(criteria));
+        sd.run(5000).unwrap();
+        assert!(cg.step() < sd.step());
+    }
+
+    #[test]
+    fn overlap_removal() {
+        // a dense clump of atoms with two coincident pairs
+        let argon = Species::new(39.948, 0.0);
+        let mut positions: Vec<Vector3<Float>> = (0..64)
+            .map(|i| {
+                let (x, y, z) = ((i % 4) as Float, ((i / 4) % 4) as Float, (i / 16) as Float);
+                Vector3::new(5.0 + 0.3 * x, 5.0 + 0.3 * y, 5.0 + 0.3 * z)
+            })
+            .collect();
+        positions[1] = positions[0];
+        positions[63] = positions[62];
+        let system = System {
+            size: positions.len(),
+            cell: Cell::cubic(12.0),
+            species: vec![argon; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        let mut simulation = Simulation::new(
+            system,
+            potentials,
+            OverlapRemoval::new(2.0),
+            ConfigurationBuilder::new().build(),
+        );
+        simulation.run(5000).unwrap();
+        assert!(simulation.step() < 5000);
+
+        let system = simulation.system();
+        for i in 0..system.size {
+            for j in (i + 1)..system.size {
+                let r = system.cell.distance(&system.positions[i], &system.positions[j]);
+                assert!(r >= 2.0, "atoms {} and {} are {} apart", i, j, r);
+            }
+        }
+    }
+}
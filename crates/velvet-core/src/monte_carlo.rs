@@ -0,0 +1,562 @@
+//! Metropolis Monte Carlo sampling of the canonical, isothermal-isobaric, and Gibbs ensembles.
+
+use std::sync::{Arc, Mutex};
+
+use nalgebra::{Matrix3, Vector3};
+use rand::Rng;
+
+use crate::barostats::deform;
+use crate::checkpoint::PropagatorState;
+use crate::errors::VelvetError;
+use crate::internal::consts::{BOLTZMANN, PRESSURE};
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::properties::energy::PotentialEnergy;
+use crate::properties::Property;
+use crate::random::shared_rng;
+use crate::system::cleanup::remove_atoms;
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Counts of the trial moves made by a [`MonteCarlo`] propagator.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MoveStatistics {
+    /// Number of attempted trial moves.
+    pub attempted: usize,
+    /// Number of accepted trial moves.
+    pub accepted: usize,
+    /// Current largest displacement along each axis in angstroms.
+    pub max_displacement: Float,
+    /// Number of attempted volume changes.
+    pub volume_attempted: usize,
+    /// Number of accepted volume changes.
+    pub volume_accepted: usize,
+}
+
+impl MoveStatistics {
+    /// Returns the fraction of attempted moves which were accepted.
+    pub fn acceptance(&self) -> Float {
+        if self.attempted == 0 {
+            0.0
+        } else {
+            self.accepted as Float / self.attempted as Float
+        }
+    }
+
+    /// Returns the fraction of attempted volume changes which were accepted.
+    pub fn volume_acceptance(&self) -> Float {
+        if self.volume_attempted == 0 {
+            0.0
+        } else {
+            self.volume_accepted as Float / self.volume_attempted as Float
+        }
+    }
+}
+
+/// Property which reports the [`MoveStatistics`] of a [`MonteCarlo`] propagator.
+///
+/// Returned by [`MonteCarlo::moves`] so the statistics can be written with the other outputs of
+/// a [`Simulation`](crate::simulation::Simulation) which owns the propagator.
+#[derive(Clone, Debug)]
+pub struct MonteCarloMoves {
+    statistics: Arc<Mutex<MoveStatistics>>,
+}
+
+impl Property for MonteCarloMoves {
+    type Res = MoveStatistics;
+
+    fn calculate(&self, _: &System, _: &Potentials) -> Self::Res {
+        *self.statistics.lock().unwrap()
+    }
+
+    fn name(&self) -> String {
+        "monte_carlo_moves".to_string()
+    }
+}
+
+/// Metropolis Monte Carlo propagation in the canonical (NVT) ensemble.
+///
+/// Every step is a sweep which attempts to translate each atom in turn, other than the frozen
+/// atoms of the topology, by a random displacement within a cube of half width `max_displacement`. A move is accepted with the Metropolis
+/// criterion on the change in [`PotentialEnergy`], otherwise the atom returns to its previous
+/// position. Velocities are left unchanged.
+///
+/// The largest displacement is tuned every few sweeps towards a target acceptance ratio, which
+/// balances the cost of rejected moves against the slow decorrelation of small ones. Atoms move
+/// by at most the largest displacement on each sweep, which should stay below the thickness of
+/// the neighbor lists unless they are rebuilt on every step.
+///
+/// With [`with_volume_moves`](MonteCarlo::with_volume_moves) every sweep ends with an attempt to
+/// rescale the cell and the positions isotropically, which samples the isothermal-isobaric (NPT)
+/// ensemble. The potentials are not set up again for the trial volume, so potentials which
+/// precompute terms from the cell such as Ewald summation are not suited to volume moves.
+///
+/// # References
+///
+/// [1] Metropolis, Nicholas, et al. "Equation of state calculations by fast computing machines." The Journal of Chemical Physics 21.6 (1953): 1087-1092.
+///
+/// [2] Frenkel, Daan, and Berend Smit. "Understanding molecular simulation: from algorithms to applications." Academic Press (2002).
+#[derive(Clone, Debug)]
+pub struct MonteCarlo {
+    temperature: Float,
+    target_acceptance: Float,
+    tuning_interval: usize,
+    displacement_limit: Float,
+    statistics: Arc<Mutex<MoveStatistics>>,
+    window: (usize, usize),
+    sweeps: usize,
+    volume: Option<(Float, Float)>,
+}
+
+impl MonteCarlo {
+    /// Returns a new [`MonteCarlo`] propagator.
+    ///
+    /// # Arguments
+    ///
+    /// * `temperature` - Temperature of the Metropolis criterion in Kelvin.
+    /// * `max_displacement` - Initial largest displacement of an atom along each axis in angstroms.
+    pub fn new(temperature: Float, max_displacement: Float) -> MonteCarlo {
+        MonteCarlo {
+            temperature,
+            target_acceptance: 0.5,
+            tuning_interval: 10,
+            displacement_limit: 1.0,
+            statistics: Arc::new(Mutex::new(MoveStatistics {
+                max_displacement,
+                ..MoveStatistics::default()
+            })),
+            window: (0, 0),
+            sweeps: 0,
+            volume: None,
+        }
+    }
+
+    /// Adds a volume change after every sweep to sample the isothermal-isobaric ensemble.
+    ///
+    /// # Arguments
+    ///
+    /// * `pressure` - Target pressure in atmospheres.
+    /// * `max_change` - Largest change in the logarithm of the volume.
+    pub fn with_volume_moves(mut self, pressure: Float, max_change: Float) -> MonteCarlo {
+        self.volume = Some((pressure, max_change));
+        self
+    }
+
+    /// Sets the acceptance ratio the largest displacement is tuned towards. Defaults to 0.5.
+    pub fn target_acceptance(mut self, ratio: Float) -> MonteCarlo {
+        self.target_acceptance = ratio;
+        self
+    }
+
+    /// Sets the number of sweeps between adjustments of the largest displacement, or disables
+    /// tuning if zero. Defaults to 10.
+    pub fn tuning_interval(mut self, sweeps: usize) -> MonteCarlo {
+        self.tuning_interval = sweeps;
+        self
+    }
+
+    /// Sets the upper bound on the tuned largest displacement in angstroms. Defaults to 1.
+    pub fn displacement_limit(mut self, limit: Float) -> MonteCarlo {
+        self.displacement_limit = limit;
+        self
+    }
+
+    /// Returns the current move statistics.
+    pub fn statistics(&self) -> MoveStatistics {
+        *self.statistics.lock().unwrap()
+    }
+
+    /// Returns a property which reports the move statistics of this propagator.
+    pub fn moves(&self) -> MonteCarloMoves {
+        MonteCarloMoves {
+            statistics: self.statistics.clone(),
+        }
+    }
+
+    // Scales the largest displacement by the ratio of the recent acceptance to the target.
+    fn tune(&mut self) {
+        let (attempted, accepted) = self.window;
+        self.window = (0, 0);
+        if attempted == 0 {
+            return;
+        }
+        let ratio = (accepted as Float / attempted as Float) / self.target_acceptance;
+        let mut statistics = self.statistics.lock().unwrap();
+        statistics.max_displacement =
+            (statistics.max_displacement * ratio.clamp(0.5, 1.5)).clamp(1e-4, self.displacement_limit);
+    }
+}
+
+impl Propagator for MonteCarlo {
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        let mut rng = shared_rng();
+        let beta = 1.0 / (BOLTZMANN * self.temperature);
+        let delta = self.statistics().max_displacement;
+        // the neighbor lists may have been rebuilt since the last sweep
+        let mut energy = PotentialEnergy.calculate(system, potentials);
+        let mut accepted = 0;
+        let frozen = system.frozen_mask();
+        let mobile: Vec<usize> = (0..system.size).filter(|&i| !frozen[i]).collect();
+        for &i in &mobile {
+            let previous = system.positions[i];
+            let mut trial = previous
+                + Vector3::new(
+                    rng.gen_range(-delta, delta),
+                    rng.gen_range(-delta, delta),
+                    rng.gen_range(-delta, delta),
+                );
+            system.cell.wrap_vector(&mut trial);
+            system.positions[i] = trial;
+            let trial_energy = PotentialEnergy.calculate(system, potentials);
+            let change = trial_energy - energy;
+            if change <= 0.0 || rng.gen::<Float>() < Float::exp(-beta * change) {
+                energy = trial_energy;
+                accepted += 1;
+            } else {
+                system.positions[i] = previous;
+            }
+        }
+
+        {
+            let mut statistics = self.statistics.lock().unwrap();
+            statistics.attempted += mobile.len();
+            statistics.accepted += accepted;
+        }
+        self.window.0 += mobile.len();
+        self.window.1 += accepted;
+        self.sweeps += 1;
+        if self.tuning_interval > 0 && self.sweeps.is_multiple_of(self.tuning_interval) {
+            self.tune();
+        }
+
+        if let Some((pressure, max_change)) = self.volume {
+            // random walk in the logarithm of the volume
+            let volume = system.cell.volume();
+            let ratio = Float::exp(rng.gen_range(-max_change, max_change));
+            let previous = (system.cell.clone(), system.positions.clone());
+            scale_volume(system, ratio);
+            let change = PotentialEnergy.calculate(system, potentials) - energy
+                + pressure / PRESSURE * volume * (ratio - 1.0);
+            let weight = -beta * change + (system.size + 1) as Float * ratio.ln();
+            let accepted = weight >= 0.0 || rng.gen::<Float>() < Float::exp(weight);
+            if !accepted {
+                system.cell = previous.0;
+                system.positions = previous.1;
+            }
+            let mut statistics = self.statistics.lock().unwrap();
+            statistics.volume_attempted += 1;
+            statistics.volume_accepted += accepted as usize;
+        }
+    }
+
+    // the move counters are stored as integers beside the largest displacement
+    fn state(&self) -> PropagatorState {
+        let statistics = self.statistics();
+        PropagatorState {
+            integers: [
+                statistics.attempted,
+                statistics.accepted,
+                statistics.volume_attempted,
+                statistics.volume_accepted,
+            ]
+            .iter()
+            .map(|&count| count as u64)
+            .collect(),
+            floats: vec![statistics.max_displacement],
+            ..PropagatorState::default()
+        }
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(4, 1, 0)?;
+        let counts = state.integers.iter().map(|&count| count as usize).collect::<Vec<usize>>();
+        *self.statistics.lock().unwrap() = MoveStatistics {
+            max_displacement: state.floats[0],
+            attempted: counts[0],
+            accepted: counts[1],
+            volume_attempted: counts[2],
+            volume_accepted: counts[3],
+        };
+        Ok(())
+    }
+}
+
+// Rescales the cell and positions isotropically so the volume changes by a factor of `ratio`.
+fn scale_volume(system: &mut System, ratio: Float) {
+    deform(system, &(Matrix3::identity() * Float::cbrt(ratio)));
+}
+
+/// Counts of the moves between the boxes of a [`GibbsEnsemble`].
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct GibbsStatistics {
+    /// Number of attempted volume exchanges.
+    pub volume_attempted: usize,
+    /// Number of accepted volume exchanges.
+    pub volume_accepted: usize,
+    /// Number of attempted particle transfers.
+    pub transfer_attempted: usize,
+    /// Number of accepted particle transfers.
+    pub transfer_accepted: usize,
+}
+
+/// Gibbs ensemble Monte Carlo for vapor-liquid coexistence without an interface.
+///
+/// Two boxes at the same temperature exchange volume at a fixed total volume and exchange atoms
+/// of one species at a fixed total number of atoms, which equalizes their pressures and chemical
+/// potentials. Each cycle runs a displacement sweep in both boxes with their own tuned
+/// [`MonteCarlo`] propagators, one volume exchange, and a number of particle transfers. Starting
+/// from two boxes of the same fluid at different densities, the boxes separate into the
+/// coexisting liquid and vapor.
+///
+/// Transferred atoms are inserted with zero velocity at random positions and must not be part of
+/// the topology. The potentials of both boxes are set up again after every volume exchange and
+/// particle transfer.
+///
+/// # References
+///
+/// [1] Panagiotopoulos, Athanassios Z. "Direct determination of phase coexistence properties of fluids by Monte Carlo simulation in a new ensemble." Molecular Physics 61.4 (1987): 813-826.
+pub struct GibbsEnsemble {
+    temperature: Float,
+    species: Species,
+    max_volume_change: Float,
+    transfers: usize,
+    boxes: [System; 2],
+    potentials: [Potentials; 2],
+    sweeps: [MonteCarlo; 2],
+    statistics: GibbsStatistics,
+}
+
+impl GibbsEnsemble {
+    /// Returns a new [`GibbsEnsemble`] driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `temperature` - Temperature of both boxes in Kelvin.
+    /// * `species` - Species of the atoms transferred between the boxes.
+    /// * `boxes` - Starting configurations of the two boxes.
+    /// * `potentials` - Potentials applied to each box.
+    pub fn new(temperature: Float, species: Species, boxes: [System; 2], potentials: [Potentials; 2]) -> GibbsEnsemble {
+        let mut potentials = potentials;
+        potentials[0].setup(&boxes[0]);
+        potentials[1].setup(&boxes[1]);
+        GibbsEnsemble {
+            temperature,
+            species,
+            max_volume_change: 0.05,
+            transfers: 10,
+            boxes,
+            potentials,
+            sweeps: [MonteCarlo::new(temperature, 0.1), MonteCarlo::new(temperature, 0.1)],
+            statistics: GibbsStatistics::default(),
+        }
+    }
+
+    /// Sets the largest change in the logarithm of the ratio of the box volumes. Defaults to 0.05.
+    pub fn max_volume_change(mut self, change: Float) -> GibbsEnsemble {
+        self.max_volume_change = change;
+        self
+    }
+
+    /// Sets the number of particle transfers attempted in each cycle. Defaults to 10.
+    pub fn transfers(mut self, transfers: usize) -> GibbsEnsemble {
+        self.transfers = transfers;
+        self
+    }
+
+    /// Returns the current configurations of the two boxes.
+    pub fn boxes(&self) -> &[System; 2] {
+        &self.boxes
+    }
+
+    /// Returns the number density of the transferred species in each box in atoms per cubic angstrom.
+    pub fn densities(&self) -> [Float; 2] {
+        let density = |system: &System| self.count(system) as Float / system.cell.volume();
+        [density(&self.boxes[0]), density(&self.boxes[1])]
+    }
+
+    /// Returns the counts of volume exchanges and particle transfers.
+    pub fn statistics(&self) -> GibbsStatistics {
+        self.statistics
+    }
+
+    /// Returns the displacement statistics of each box.
+    pub fn move_statistics(&self) -> [MoveStatistics; 2] {
+        [self.sweeps[0].statistics(), self.sweeps[1].statistics()]
+    }
+
+    /// Runs `cycles` cycles of displacements, volume exchanges, and particle transfers.
+    pub fn run(&mut self, cycles: usize) {
+        let mut rng = shared_rng();
+        let beta = 1.0 / (BOLTZMANN * self.temperature);
+        for _ in 0..cycles {
+            for k in 0..2 {
+                self.sweeps[k].propagate(&mut self.boxes[k], &self.potentials[k]);
+                self.potentials[k].update(&self.boxes[k], 0);
+            }
+
+            // volume exchange as a random walk in the logarithm of the volume ratio
+            let before = self.boxes.clone();
+            let energy = self.energy();
+            let (va, vb) = (before[0].cell.volume(), before[1].cell.volume());
+            let total = va + vb;
+            let ratio = Float::exp(Float::ln(va / vb) + rng.gen_range(-self.max_volume_change, self.max_volume_change));
+            let trial = total * ratio / (1.0 + ratio);
+            scale_volume(&mut self.boxes[0], trial / va);
+            scale_volume(&mut self.boxes[1], (total - trial) / vb);
+            let weight = -beta * (self.energy() - energy)
+                + (self.boxes[0].size + 1) as Float * Float::ln(trial / va)
+                + (self.boxes[1].size + 1) as Float * Float::ln((total - trial) / vb);
+            let accepted = weight >= 0.0 || rng.gen::<Float>() < Float::exp(weight);
+            if !accepted {
+                self.restore(before);
+            }
+            self.statistics.volume_attempted += 1;
+            self.statistics.volume_accepted += accepted as usize;
+
+            for _ in 0..self.transfers {
+                self.transfer(&mut rng, beta);
+            }
+        }
+    }
+
+    // Attempts to move a random atom of the transferred species to a random position in the other box.
+    fn transfer<R: Rng>(&mut self, rng: &mut R, beta: Float) {
+        let source = rng.gen_range(0, 2);
+        let target = 1 - source;
+        let candidates: Vec<usize> = (0..self.boxes[source].size)
+            .filter(|&i| self.boxes[source].species[i] == self.species)
+            .collect();
+        self.statistics.transfer_attempted += 1;
+        if candidates.is_empty() {
+            return;
+        }
+        let before = self.boxes.clone();
+        let energy = self.energy();
+        let (n_source, n_target) = (candidates.len(), self.count(&self.boxes[target]));
+        let (v_source, v_target) = (self.boxes[source].cell.volume(), self.boxes[target].cell.volume());
+
+        let atom = candidates[rng.gen_range(0, candidates.len())];
+        let charge = self.boxes[source].charge(atom);
+        remove_atoms(&mut self.boxes[source], &[atom]);
+        let fractional = Vector3::new(rng.gen::<Float>(), rng.gen::<Float>(), rng.gen::<Float>());
+        let system = &mut self.boxes[target];
+        system.positions.push(system.cell.cartesian(&fractional));
+        system.velocities.push(Vector3::zeros());
+        system.species.push(self.species);
+        system.size += 1;
+        if !system.topology.charges.is_empty() {
+            system.topology.charges.push(charge);
+        } else if charge != self.species.charge() {
+            system.set_charge(system.size - 1, charge);
+        }
+        self.potentials[0].setup(&self.boxes[0]);
+        self.potentials[1].setup(&self.boxes[1]);
+
+        let weight = -beta * (self.energy() - energy)
+            + Float::ln((n_source as Float * v_target) / ((n_target + 1) as Float * v_source));
+        if weight >= 0.0 || rng.gen::<Float>() < Float::exp(weight) {
+            self.statistics.transfer_accepted += 1;
+        } else {
+            self.restore(before);
+        }
+    }
+
+    // Returns the total potential energy of both boxes.
+    fn energy(&self) -> Float {
+        PotentialEnergy.calculate(&self.boxes[0], &self.potentials[0])
+            + PotentialEnergy.calculate(&self.boxes[1], &self.potentials[1])
+    }
+
+    // Returns the number of atoms of the transferred species in `system`.
+    fn count(&self, system: &System) -> usize {
+        system.species.iter().filter(|&&s| s == self.species).count()
+    }
+
+    // Replaces both boxes with an earlier configuration.
+    fn restore(&mut self, boxes: [System; 2]) {
+        self.boxes = boxes;
+        self.potentials[0].setup(&self.boxes[0]);
+        self.potentials[1].setup(&self.boxes[1]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GibbsEnsemble, MonteCarlo};
+    use crate::internal::consts::{BOLTZMANN, PRESSURE};
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::propagators::Propagator;
+    use crate::random::seed_rng;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // Atoms of a gas without any interactions.
+    fn ideal_gas(size: usize, length: Float) -> System {
+        let atom = Species::new(39.948, 0.0);
+        System {
+            size,
+            cell: Cell::cubic(length),
+            species: vec![atom; size],
+            velocities: vec![Vector3::zeros(); size],
+            positions: (0..size).map(|i| Vector3::new(i as Float, 0.5 * i as Float, 0.0)).collect(),
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn ideal_gas_volume() {
+        seed_rng(42);
+        // the volume of an ideal gas is gamma distributed with mean (N + 1) kT / P
+        let mut system = ideal_gas(10, 10.0);
+        let potentials = PotentialsBuilder::new().build();
+        let temperature = 300.0;
+        let pressure = 450.0;
+        let expected = 11.0 * BOLTZMANN * temperature / (pressure / PRESSURE);
+        let mut mc = MonteCarlo::new(temperature, 0.5).with_volume_moves(pressure, 0.3);
+        let mut volume = 0.0;
+        for i in 0..20000 {
+            mc.propagate(&mut system, &potentials);
+            if i >= 2000 {
+                volume += system.cell.volume() / 18000.0;
+            }
+        }
+        assert!((volume - expected).abs() < 0.05 * expected, "{} {}", volume, expected);
+        let statistics = mc.statistics();
+        assert_eq!(statistics.volume_attempted, 20000);
+        assert!(statistics.volume_acceptance() > 0.2 && statistics.volume_acceptance() < 1.0);
+    }
+
+    #[test]
+    fn gibbs_ideal_gas() {
+        seed_rng(42);
+        // every atom starts in the first box
+        let boxes = [ideal_gas(20, 10.0), ideal_gas(0, 10.0)];
+        let atom = boxes[0].species[0];
+        let potentials = [PotentialsBuilder::new().build(), PotentialsBuilder::new().build()];
+        let mut gibbs = GibbsEnsemble::new(300.0, atom, boxes, potentials).max_volume_change(0.2);
+        gibbs.run(100);
+        let mut densities = [0.0; 2];
+        for _ in 0..1000 {
+            gibbs.run(1);
+            let [a, b] = gibbs.densities();
+            densities[0] += a / 1000.0;
+            densities[1] += b / 1000.0;
+        }
+        let [a, b] = gibbs.boxes();
+        assert_eq!(a.size + b.size, 20);
+        assert!((a.cell.volume() + b.cell.volume() - 2000.0).abs() < 1e-2);
+        // an ideal gas has the same density in both boxes
+        let overall = 20.0 / 2000.0;
+        assert!((densities[0] - overall).abs() < 0.2 * overall, "{:?}", densities);
+        assert!((densities[1] - overall).abs() < 0.2 * overall, "{:?}", densities);
+        let statistics = gibbs.statistics();
+        assert_eq!(statistics.volume_attempted, 1100);
+        assert_eq!(statistics.transfer_attempted, 11000);
+        assert!(statistics.transfer_accepted > 0);
+    }
+}
@@ -0,0 +1,314 @@
+//! Nudged elastic band calculations of minimum energy paths and transition states.
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+use crate::minimizers::FireState;
+use crate::potentials::Potentials;
+use crate::properties::energy::PotentialEnergy;
+use crate::properties::forces::Forces;
+use crate::properties::Property;
+use crate::system::System;
+
+/// Nudged elastic band method for the minimum energy path between two configurations.
+///
+/// A chain of replicas (images) of the system is linearly interpolated between two minimized
+/// endpoints. Each intermediate image feels the component of the true force perpendicular to
+/// the path and a spring force along it, so the band relaxes onto the minimum energy path
+/// while the images stay evenly spaced. With a climbing image the highest energy image
+/// instead feels no spring force and the component of the true force along the path is
+/// inverted, driving it to the saddle point.
+///
+/// The band is relaxed with the FIRE algorithm and the endpoints are held fixed. Every image
+/// shares the cell of the initial configuration.
+///
+/// # References
+///
+/// [1] Henkelman, Graeme, and Hannes Jónsson. "Improved tangent estimate in the nudged elastic band method for finding minimum energy paths and saddle points." The Journal of chemical physics 113.22 (2000): 9978-9985.
+///
+/// [2] Henkelman, Graeme, Blas P. Uberuaga, and Hannes Jónsson. "A climbing image nudged elastic band method for finding saddle points and minimum energy paths." The Journal of chemical physics 113.22 (2000): 9901-9904.
+#[derive(Clone, Debug)]
+pub struct NudgedElasticBand {
+    images: Vec<System>,
+    energies: Vec<Float>,
+    spring_constant: Float,
+    climbing: bool,
+    timestep: Float,
+    max_step: Float,
+}
+
+impl NudgedElasticBand {
+    /// Returns a new [`NudgedElasticBand`] interpolated between two configurations.
+    ///
+    /// # Arguments
+    ///
+    /// * `initial` - Minimized configuration at the start of the path.
+    /// * `last` - Minimized configuration at the end of the path with the same atom ordering.
+    /// * `images` - Number of intermediate images.
+    pub fn new(initial: System, last: System, images: usize) -> NudgedElasticBand {
+        assert_eq!(
+            initial.size, last.size,
+            "The endpoints of a nudged elastic band must have the same number of atoms."
+        );
+        let displacements: Vec<Vector3<Float>> = initial
+            .positions
+            .iter()
+            .zip(last.positions.iter())
+            .map(|(a, b)| {
+                let mut d = b - a;
+                initial.cell.vector_image(&mut d);
+                d
+            })
+            .collect();
+        let mut band = Vec::with_capacity(images + 2);
+        band.push(initial.clone());
+        for n in 1..=images {
+            let t = n as Float / (images + 1) as Float;
+            let mut image = initial.clone();
+            image
+                .positions
+                .iter_mut()
+                .zip(displacements.iter())
+                .for_each(|(pos, d)| *pos += t * d);
+            band.push(image);
+        }
+        band.push(System {
+            cell: initial.cell.clone(),
+            ..last
+        });
+        NudgedElasticBand {
+            energies: vec![0.0; band.len()],
+            images: band,
+            spring_constant: 1.0,
+            climbing: false,
+            timestep: 0.1,
+            max_step: 0.2,
+        }
+    }
+
+    /// Sets the spring constant between neighboring images in kcal/mol/angstrom^2.
+    pub fn spring_constant(mut self, spring_constant: Float) -> NudgedElasticBand {
+        self.spring_constant = spring_constant;
+        self
+    }
+
+    /// Enables or disables the climbing image.
+    pub fn climbing_image(mut self, climbing: bool) -> NudgedElasticBand {
+        self.climbing = climbing;
+        self
+    }
+
+    /// Sets the initial FIRE timestep and the largest displacement of any atom in one step.
+    pub fn step_size(mut self, timestep: Float, max_step: Float) -> NudgedElasticBand {
+        self.timestep = timestep;
+        self.max_step = max_step;
+        self
+    }
+
+    /// Returns every image of the band including both endpoints.
+    pub fn images(&self) -> &[System] {
+        &self.images
+    }
+
+    /// Returns the potential energy of each image as of the last call to [`run`](NudgedElasticBand::run).
+    pub fn energies(&self) -> &[Float] {
+        &self.energies
+    }
+
+    /// Returns the index of the intermediate image with the highest energy.
+    pub fn saddle_image(&self) -> usize {
+        let last = self.images.len() - 1;
+        (1..last)
+            .max_by(|&a, &b| self.energies[a].total_cmp(&self.energies[b]))
+            .unwrap_or(0)
+    }
+
+    /// Returns the energy barrier of the path measured from the initial configuration.
+    pub fn barrier(&self) -> Float {
+        self.energies[self.saddle_image()] - self.energies[0]
+    }
+
+    /// Relaxes the band and returns `true` if it converged.
+    ///
+    /// # Arguments
+    ///
+    /// * `potentials` - Potentials applied to every image.
+    /// * `max_steps` - Largest number of optimization steps.
+    /// * `tolerance` - Largest magnitude of the projected force on any atom at convergence.
+    pub fn run(&mut self, potentials: &mut Potentials, max_steps: usize, tolerance: Float) -> bool {
+        let last = self.images.len() - 1;
+        for &n in [0, last].iter() {
+            potentials.setup(&self.images[n]);
+            self.energies[n] = PotentialEnergy.calculate(&self.images[n], potentials);
+        }
+
+        // the band is relaxed as one system of every image's atoms
+        let size = self.images[0].size;
+        let mut velocities = vec![Vector3::zeros(); size * (last + 1)];
+        let mut fire = FireState::new(self.timestep);
+        for _ in 0..max_steps {
+            let forces = self.band_forces(potentials).concat();
+            let largest = forces.iter().map(|f| f.norm()).fold(0.0, Float::max);
+            if largest < tolerance {
+                return true;
+            }
+            let displacements = fire.step(&mut velocities, &forces, self.max_step);
+            for (image, d) in self.images[1..last].iter_mut().zip(displacements.chunks(size).skip(1)) {
+                image
+                    .positions
+                    .iter_mut()
+                    .zip(d.iter())
+                    .for_each(|(pos, d)| *pos += d);
+            }
+        }
+        false
+    }
+
+    // Returns the projected forces on each image, which are zero for the endpoints.
+    fn band_forces(&mut self, potentials: &mut Potentials) -> Vec<Vec<Vector3<Float>>> {
+        let last = self.images.len() - 1;
+        let size = self.images[0].size;
+        let mut forces = vec![vec![Vector3::zeros(); size]; last + 1];
+        for (n, image) in self.images.iter().enumerate().take(last).skip(1) {
+            potentials.setup(image);
+            self.energies[n] = PotentialEnergy.calculate(image, potentials);
+            forces[n] = Forces.calculate(image, potentials);
+        }
+        let saddle = self.saddle_image();
+
+        for (n, force) in forces.iter_mut().enumerate().take(last).skip(1) {
+            let forward = self.displacements(n, n + 1);
+            let backward = self.displacements(n - 1, n);
+            let tangent = self.tangent(n, &forward, &backward);
+            let parallel: Float = force.iter().zip(tangent.iter()).map(|(f, t)| f.dot(t)).sum();
+            if self.climbing && n == saddle {
+                // invert the true force along the path
+                force
+                    .iter_mut()
+                    .zip(tangent.iter())
+                    .for_each(|(f, t)| *f -= 2.0 * parallel * t);
+            } else {
+                let stretch = norm(&forward) - norm(&backward);
+                let spring = self.spring_constant * stretch;
+                force
+                    .iter_mut()
+                    .zip(tangent.iter())
+                    .for_each(|(f, t)| *f += (spring - parallel) * t);
+            }
+        }
+        forces
+    }
+
+    // Returns the minimum image displacements of each atom from image `a` to image `b`.
+    fn displacements(&self, a: usize, b: usize) -> Vec<Vector3<Float>> {
+        let cell = &self.images[a].cell;
+        self.images[a]
+            .positions
+            .iter()
+            .zip(self.images[b].positions.iter())
+            .map(|(pa, pb)| {
+                let mut d = pb - pa;
+                cell.vector_image(&mut d);
+                d
+            })
+            .collect()
+    }
+
+    // Returns the normalized upwind tangent of image `n`.
+    fn tangent(&self, n: usize, forward: &[Vector3<Float>], backward: &[Vector3<Float>]) -> Vec<Vector3<Float>> {
+        let (prev, energy, next) = (self.energies[n - 1], self.energies[n], self.energies[n + 1]);
+        let (wf, wb) = if next > energy && energy > prev {
+            (1.0, 0.0)
+        } else if next < energy && energy < prev {
+            (0.0, 1.0)
+        } else {
+            // energy extremum, weight the tangents by the energy differences
+            let large = Float::max((next - energy).abs(), (prev - energy).abs());
+            let small = Float::min((next - energy).abs(), (prev - energy).abs());
+            if next > prev {
+                (large, small)
+            } else {
+                (small, large)
+            }
+        };
+        let tangent: Vec<Vector3<Float>> = forward
+            .iter()
+            .zip(backward.iter())
+            .map(|(f, b)| wf * f + wb * b)
+            .collect();
+        let length = norm(&tangent);
+        if length > 0.0 {
+            tangent.iter().map(|t| t / length).collect()
+        } else {
+            tangent
+        }
+    }
+}
+
+// Euclidean norm of a set of atomic vectors treated as one vector.
+fn norm(v: &[Vector3<Float>]) -> Float {
+    v.iter().map(|x| x.norm_squared()).sum::<Float>().sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NudgedElasticBand;
+    use crate::internal::Float;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::PotentialsBuilder;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // 2x2x2 fcc argon crystal with the atom at the origin removed
+    fn vacancy() -> System {
+        let a: Float = 5.26;
+        let basis = [[0.0, 0.0, 0.0], [0.5, 0.5, 0.0], [0.5, 0.0, 0.5], [0.0, 0.5, 0.5]];
+        let mut positions = Vec::new();
+        for i in 0..2 {
+            for j in 0..2 {
+                for k in 0..2 {
+                    for b in basis.iter() {
+                        let frac = Vector3::new(b[0] + i as Float, b[1] + j as Float, b[2] + k as Float);
+                        positions.push(frac * a);
+                    }
+                }
+            }
+        }
+        positions.remove(0);
+        let size = positions.len();
+        System {
+            size,
+            cell: Cell::cubic(2.0 * a),
+            species: vec![Species::new(39.948, 0.0); size],
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn vacancy_hop() {
+        let initial = vacancy();
+        let argon = initial.species[0];
+        // the nearest neighbor at (a/2, a/2, 0) hops into the vacancy
+        let mut last = initial.clone();
+        last.positions[0] = Vector3::zeros();
+
+        let mut potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (argon, argon), 5.0, 0.0)
+            .build();
+        let mut neb = NudgedElasticBand::new(initial, last, 5).climbing_image(true);
+        assert!(neb.run(&mut potentials, 2000, 0.01));
+
+        // the path is symmetric so the saddle point is halfway along the hop
+        let energies = neb.energies();
+        assert_eq!(neb.saddle_image(), 3);
+        assert!(neb.barrier() > 0.0);
+        assert!((energies[1] - energies[5]).abs() < 1e-3);
+        let saddle = neb.images()[3].positions[0];
+        assert!((saddle - Vector3::new(1.315, 1.315, 0.0)).norm() < 0.05);
+    }
+}
@@ -0,0 +1,115 @@
+//! Transparent compression of text output destinations.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use flate2::write::GzEncoder;
+
+/// Compression applied to a text output destination.
+///
+/// Long runs easily produce text trajectories of many gigabytes, which compress well because
+/// consecutive frames share most of their formatting. Compressed streams are finished when the
+/// destination is dropped at the end of the simulation.
+///
+/// Zstandard compression requires the `zstd-output` feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Uncompressed output.
+    None,
+    /// Gzip compression, as written by `gzip`.
+    Gzip,
+    /// Zstandard compression, as written by `zstd`.
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the compression implied by the extension of `path`, `.gz` for gzip and `.zst` for zstandard.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Compression {
+        match path.as_ref().extension().and_then(|extension| extension.to_str()) {
+            Some("gz") => Compression::Gzip,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+
+    /// Wraps `writer` in an encoder which compresses everything written to it.
+    pub fn encoder<W: Write + 'static>(self, writer: W) -> io::Result<Box<dyn Write>> {
+        match self {
+            Compression::None => Ok(Box::new(writer)),
+            Compression::Gzip => Ok(Box::new(GzEncoder::new(writer, flate2::Compression::default()))),
+            #[cfg(feature = "zstd-output")]
+            Compression::Zstd => Ok(Box::new(zstd::Encoder::new(writer, 0)?.auto_finish())),
+            #[cfg(not(feature = "zstd-output"))]
+            Compression::Zstd => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "zstandard compression requires the `zstd-output` feature",
+            )),
+        }
+    }
+}
+
+/// Creates the file at `path` as a buffered output destination compressed according to its extension.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let path = std::env::temp_dir().join("velvet-trajectory.xyz.gz");
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(create_destination(&path).unwrap())
+///     .interval(100)
+///     .output(XyzTrajectory::extended())
+///     .build();
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub fn create_destination<P: AsRef<Path>>(path: P) -> io::Result<Box<dyn Write>> {
+    let file = BufWriter::new(File::create(&path)?);
+    Compression::from_path(&path).encoder(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{create_destination, Compression};
+    use flate2::read::GzDecoder;
+    use std::io::{Read, Write};
+
+    fn round_trip(extension: &str) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("velvet-compression-{}.{}", std::process::id(), extension));
+        {
+            let mut destination = create_destination(&path).unwrap();
+            for i in 0..1000 {
+                writeln!(destination, "frame {}", i).unwrap();
+            }
+        }
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn from_path() {
+        assert_eq!(Compression::from_path("traj.xyz.gz"), Compression::Gzip);
+        assert_eq!(Compression::from_path("results.csv.zst"), Compression::Zstd);
+        assert_eq!(Compression::from_path("traj.xyz"), Compression::None);
+    }
+
+    #[test]
+    fn gzip() {
+        let bytes = round_trip("txt.gz");
+        let mut text = String::new();
+        GzDecoder::new(bytes.as_slice()).read_to_string(&mut text).unwrap();
+        assert_eq!(text.lines().count(), 1000);
+        assert_eq!(text.lines().last(), Some("frame 999"));
+        assert!(bytes.len() < text.len() / 2);
+    }
+
+    #[cfg(feature = "zstd-output")]
+    #[test]
+    fn zstd() {
+        let bytes = round_trip("txt.zst");
+        let text = String::from_utf8(zstd::decode_all(bytes.as_slice()).unwrap()).unwrap();
+        assert_eq!(text.lines().last(), Some("frame 999"));
+    }
+}
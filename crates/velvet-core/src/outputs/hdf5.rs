@@ -1,63 +1,483 @@
-//! HDF5 formatted outputs.
+//! HDF5 formatted outputs following the [H5MD](https://www.nongnu.org/h5md/h5md.html) 1.1 specification.
+//!
+//! Files written by an [`Hdf5OutputGroup`] can be read directly by MDAnalysis and other
+//! H5MD-aware tools. Every frame is appended along the first axis of chunked, extendable
+//! datasets. Each time-dependent element is a group holding its `step`, `time`, and `value`
+//! datasets.
+//!
+//! | Path | Shape | Description |
+//! |------|-------|-------------|
+//! | `h5md` | | `version` attribute with `author` and `creator` groups. |
+//! | `particles/all/position` | `[frames][atoms][3]` | Cartesian coordinates of each atom. |
+//! | `particles/all/velocity` | `[frames][atoms][3]` | Velocity of each atom (optional). |
+//! | `particles/all/box/edges` | `[frames][3][3]` | Lattice vectors 'a', 'b', and 'c' as rows. |
+//! | `particles/all/species` | `[atoms]` | Index of the species of each atom. |
+//! | `particles/all/mass` | `[atoms]` | Mass of each atom. |
+//! | `particles/all/charge` | `[atoms]` | Charge of each atom. |
+//! | `parameters/species_ids` | `[types][2]` | High and low 64 bits of the identifier of each species. |
+//!
+//! Each [`Hdf5Output`] adds elements to the `observables` group named after its property,
+//! such as `observables/potential_energy` with values of shape `[frames]`. Per-atom forces are
+//! stored in `particles/all/force` as H5MD prescribes, and
+//! [`CellParameters`](crate::properties::lattice::CellParameters) adds the derived
+//! `observables/cell_lengths`, `observables/cell_angles`, and `observables/volume` time series
+//! next to the box edges. Lengths are in angstroms, times in femtoseconds, and energies in
+//! kcal/mol, with the units of the particle data stored in `unit` attributes. The number of atoms
+//! must not change between frames.
 
-use crate::internal::Float;
+use hdf5::types::VarLenUnicode;
+use hdf5::{Dataset, Group, H5Type, Location};
+use nalgebra::Vector3;
+use ndarray::{ArrayViewD, IxDyn, SliceInfo, SliceOrIndex};
 
-use crate::potentials::collections::Potentials;
+use crate::correlation::{Correlatable, TimeCorrelation};
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::potentials::Potentials;
 use crate::properties::energy::{KineticEnergy, PairEnergy, PotentialEnergy, TotalEnergy};
 use crate::properties::forces::Forces;
+use crate::properties::lattice::CellParameters;
+use crate::properties::msd::Msd;
+use crate::properties::per_atom::{PerAtomPotentialEnergy, PerAtomStress};
+use crate::properties::rdf::Rdf;
 use crate::properties::temperature::Temperature;
+use crate::properties::vacf::VelocityAutocorrelation;
 use crate::properties::Property;
+use crate::system::cell::Cell;
+use crate::system::species::Species;
+use crate::system::topology::Topology;
 use crate::system::System;
 
+/// Group of the particles which make up the system.
+const PARTICLES: &str = "particles/all";
+
+/// Values of a single time-dependent H5MD element for one frame.
+#[derive(Clone, Debug)]
+pub struct Hdf5Frame {
+    /// Path of the element relative to the root of the file, such as `observables/temperature`.
+    pub path: String,
+    /// Shape of the values in one frame, empty for scalars.
+    pub shape: Vec<usize>,
+    /// Values in row-major order.
+    pub data: Vec<Float>,
+    /// Unit of the values in the notation of the H5MD specification.
+    pub unit: Option<&'static str>,
+}
+
+impl Hdf5Frame {
+    /// Returns the values of the element `name` in the `observables` group.
+    pub fn observable(name: &str, shape: Vec<usize>, data: Vec<Float>) -> Hdf5Frame {
+        Hdf5Frame {
+            path: format!("observables/{}", name),
+            shape,
+            data,
+            unit: None,
+        }
+    }
+
+    fn scalar(name: &str, value: Float) -> Hdf5Frame {
+        Hdf5Frame::observable(name, Vec::new(), vec![value])
+    }
+
+    fn vectors(path: String, vectors: &[Vector3<Float>], unit: &'static str) -> Hdf5Frame {
+        Hdf5Frame {
+            path,
+            shape: vec![vectors.len(), 3],
+            data: vectors.iter().flat_map(|v| v.iter().copied()).collect(),
+            unit: Some(unit),
+        }
+    }
+}
+
 /// Shared behavior to write a simulation result to an HDF5 file.
 pub trait Hdf5Output {
-    /// Writes the HDF5 formatted output.
-    fn output_hdf5(&self, system: &System, potentials: &Potentials, group: &hdf5::Group);
+    /// Returns the values of one frame for each element of the output.
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame>;
 }
 
+/// Collection of outputs appended to a single H5MD file at a regular interval.
 pub struct Hdf5OutputGroup {
-    pub file_handle: hdf5::File,
-    pub interval: usize,
-    pub outputs: Vec<Box<dyn Hdf5Output>>,
+    file: hdf5::File,
+    interval: usize,
+    timestep: Float,
+    chunk_frames: usize,
+    compression: Option<u8>,
+    velocities: bool,
+    outputs: Vec<Box<dyn Hdf5Output>>,
+    frames: usize,
 }
 
+impl Hdf5OutputGroup {
+    /// Returns the number of steps between frames.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Returns the number of frames written so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Appends the state of `system` and the results of each output as a new frame.
+    pub fn write_frame(&mut self, step: usize, system: &System, potentials: &Potentials) -> Result<(), VelvetError> {
+        if self.frames == 0 {
+            self.write_header(system)?;
+        }
+        let frame = self.frames;
+        let position = Hdf5Frame::vectors(format!("{}/position", PARTICLES), &system.positions, "Angstrom");
+        self.write_element(frame, step, &position)?;
+        if self.velocities {
+            let velocity = Hdf5Frame::vectors(format!("{}/velocity", PARTICLES), &system.velocities, "Angstrom fs-1");
+            self.write_element(frame, step, &velocity)?;
+        }
+        let matrix = system.cell.matrix();
+        let edges = Hdf5Frame {
+            path: format!("{}/box/edges", PARTICLES),
+            shape: vec![3, 3],
+            data: (0..3)
+                .flat_map(|col| (0..3).map(move |row| matrix[(row, col)]))
+                .collect(),
+            unit: Some("Angstrom"),
+        };
+        self.write_element(frame, step, &edges)?;
+        for output in self.outputs.iter() {
+            for values in output.output_hdf5(system, potentials) {
+                self.write_element(frame, step, &values)?;
+            }
+        }
+        self.frames += 1;
+        self.file.flush()?;
+        Ok(())
+    }
+
+    // Writes the metadata and the time-independent particle data shared by every frame.
+    fn write_header(&self, system: &System) -> Result<(), VelvetError> {
+        let h5md = self.group("h5md")?;
+        write_attr(&h5md, "version", &[1i32, 1])?;
+        write_string_attr(&self.group("h5md/author")?, "name", "velvet")?;
+        let creator = self.group("h5md/creator")?;
+        write_string_attr(&creator, "name", "velvet")?;
+        write_string_attr(&creator, "version", env!("CARGO_PKG_VERSION"))?;
+
+        let simulation_box = self.group(&format!("{}/box", PARTICLES))?;
+        write_scalar_attr(&simulation_box, "dimension", &3i32)?;
+        let periodic: VarLenUnicode = "periodic".parse().unwrap();
+        write_attr(
+            &simulation_box,
+            "boundary",
+            &[periodic.clone(), periodic.clone(), periodic],
+        )?;
+
+        let mut types: Vec<Species> = Vec::new();
+        let mut indices: Vec<i32> = Vec::with_capacity(system.size);
+        for species in system.species.iter() {
+            let index = types
+                .iter()
+                .position(|t| t.id() == species.id() && t.mass() == species.mass() && t.charge() == species.charge())
+                .unwrap_or_else(|| {
+                    types.push(*species);
+                    types.len() - 1
+                });
+            indices.push(index as i32);
+        }
+        let ids: Vec<[u64; 2]> = types.iter().map(|t| [(t.id() >> 64) as u64, t.id() as u64]).collect();
+        let masses: Vec<Float> = system.species.iter().map(|s| s.mass()).collect();
+        let charges = system.charges();
+        let particles = self.group(PARTICLES)?;
+        write_dataset(&particles, "species", &indices)?;
+        write_dataset(&particles, "mass", &masses)?;
+        write_dataset(&particles, "charge", &charges)?;
+        write_dataset(&self.group("parameters")?, "species_ids", &ids)
+    }
+
+    // Opens the group at `path`, creating any missing groups along the way.
+    fn group(&self, path: &str) -> Result<Group, VelvetError> {
+        let mut group = self.file.group("/")?;
+        for name in path.split('/') {
+            group = if group.link_exists(name) {
+                group.group(name)?
+            } else {
+                group.create_group(name)?
+            };
+        }
+        Ok(group)
+    }
+
+    // Appends `step`, `time`, and `value` at index `frame` of a time-dependent element.
+    fn write_element(&self, frame: usize, step: usize, values: &Hdf5Frame) -> Result<(), VelvetError> {
+        let element = self.group(&values.path)?;
+        let created = !element.link_exists("value");
+        self.append(&element, "step", frame, &[], &[step as i64])?;
+        self.append(&element, "time", frame, &[], &[step as Float * self.timestep])?;
+        let value = self.append(&element, "value", frame, &values.shape, &values.data)?;
+        if created {
+            write_string_attr(&element.dataset("time")?, "unit", "fs")?;
+            if let Some(unit) = values.unit {
+                write_string_attr(&value, "unit", unit)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes `data` with the given `shape` at index `frame` of the extendable dataset `name`.
+    fn append<T: H5Type>(
+        &self,
+        group: &Group,
+        name: &str,
+        frame: usize,
+        shape: &[usize],
+        data: &[T],
+    ) -> Result<Dataset, VelvetError> {
+        let dataset = if group.link_exists(name) {
+            group.dataset(name)?
+        } else {
+            self.create_extendable::<T>(group, name, shape)?
+        };
+        let mut extent = dataset.shape();
+        if extent[1..] != *shape {
+            return Err(VelvetError::LengthMismatch {
+                field: "values in an HDF5 frame",
+                expected: extent[1..].iter().product(),
+                found: data.len(),
+            });
+        }
+        extent[0] = frame + 1;
+        dataset.resize(extent)?;
+
+        let mut dims = vec![1];
+        dims.extend_from_slice(shape);
+        let view = ArrayViewD::from_shape(IxDyn(&dims), data).expect("frame data must match its shape");
+        let mut indices = vec![SliceOrIndex::from(frame..frame + 1)];
+        indices.extend(shape.iter().map(|_| SliceOrIndex::from(..)));
+        let slice = SliceInfo::<_, IxDyn>::new(indices).expect("slice must match the rank of the dataset");
+        dataset.write_slice(view, &slice)?;
+        Ok(dataset)
+    }
+
+    fn create_extendable<T: H5Type>(&self, group: &Group, name: &str, shape: &[usize]) -> Result<Dataset, VelvetError> {
+        let mut chunk = vec![self.chunk_frames];
+        chunk.extend(shape.iter().map(|&n| n.max(1)));
+        let mut empty = vec![0];
+        empty.extend_from_slice(shape);
+        let mut builder = group.new_dataset::<T>();
+        builder.chunk(chunk).resizable(true);
+        if let Some(level) = self.compression {
+            builder.gzip(level);
+        }
+        Ok(builder.create(name, empty)?)
+    }
+}
+
+fn write_dataset<T: H5Type>(group: &Group, name: &str, data: &[T]) -> Result<(), VelvetError> {
+    group.new_dataset::<T>().create(name, data.len())?.write(data)?;
+    Ok(())
+}
+
+fn write_attr<T: H5Type>(location: &Location, name: &str, values: &[T]) -> Result<(), VelvetError> {
+    location.new_attr::<T>().create(name, values.len())?.write(values)?;
+    Ok(())
+}
+
+fn write_scalar_attr<T: H5Type>(location: &Location, name: &str, value: &T) -> Result<(), VelvetError> {
+    location.new_attr::<T>().create(name, ())?.write_scalar(value)?;
+    Ok(())
+}
+
+fn write_string_attr(location: &Location, name: &str, value: &str) -> Result<(), VelvetError> {
+    let value: VarLenUnicode = value.parse().unwrap();
+    write_scalar_attr(location, name, &value)
+}
+
+/// Constructor for the [`Hdf5OutputGroup`] type.
+///
+/// # Examples
+///
+/// ```no_run
+/// use velvet_core::prelude::*;
+///
+/// let group = Hdf5OutputGroupBuilder::new()
+///     .filename("argon.h5")
+///     .interval(100)
+///     .timestep(1.0)
+///     .compression(4)
+///     .output(PotentialEnergy)
+///     .output(Temperature)
+///     .build()
+///     .unwrap();
+/// ```
 pub struct Hdf5OutputGroupBuilder {
     filename: String,
     interval: usize,
+    timestep: Float,
+    chunk_frames: usize,
+    compression: Option<u8>,
+    velocities: bool,
     outputs: Vec<Box<dyn Hdf5Output>>,
 }
 
 impl Hdf5OutputGroupBuilder {
+    /// Returns a new builder which writes every step to `velvet.h5` without compression.
     pub fn new() -> Hdf5OutputGroupBuilder {
         Hdf5OutputGroupBuilder {
             filename: "velvet.h5".to_string(),
             interval: 1,
+            timestep: 1.0,
+            chunk_frames: 64,
+            compression: None,
+            velocities: true,
             outputs: Vec::new(),
         }
     }
 
-    pub fn filename(&mut self, filename: Into<String>) -> Hdf5OutputGroupBuilder {
+    /// Sets the path of the file, which is overwritten if it exists.
+    pub fn filename<T: Into<String>>(mut self, filename: T) -> Hdf5OutputGroupBuilder {
         self.filename = filename.into();
         self
     }
 
-    pub fn interval(&mut self, interval: usize) -> Hdf5OutputGroupBuilder {
+    /// Sets the number of steps between frames.
+    pub fn interval(mut self, interval: usize) -> Hdf5OutputGroupBuilder {
         self.interval = interval;
         self
     }
 
-    pub fn output(&mut self, output: T) -> Hdf5OutputGroupBuilder {
+    /// Sets the timestep of the simulation used to record the time of each frame.
+    pub fn timestep(mut self, timestep: Float) -> Hdf5OutputGroupBuilder {
+        self.timestep = timestep;
+        self
+    }
+
+    /// Sets the number of frames stored in each chunk of the extendable datasets.
+    pub fn chunk_frames(mut self, frames: usize) -> Hdf5OutputGroupBuilder {
+        self.chunk_frames = frames.max(1);
+        self
+    }
+
+    /// Compresses every dataset with gzip at a `level` from 0 (fastest) to 9 (smallest).
+    pub fn compression(mut self, level: u8) -> Hdf5OutputGroupBuilder {
+        self.compression = Some(level.min(9));
+        self
+    }
+
+    /// Sets whether the velocities of each frame are written.
+    pub fn velocities(mut self, velocities: bool) -> Hdf5OutputGroupBuilder {
+        self.velocities = velocities;
+        self
+    }
+
+    /// Adds an output to the group.
+    pub fn output<T: Hdf5Output + 'static>(mut self, output: T) -> Hdf5OutputGroupBuilder {
         self.outputs.push(Box::new(output));
         self
     }
 
-    pub fn build(self) -> Hdf5OutputGroup {
-        Hdf5OutputGroup {
-            file_handle: hdf5::File::create(self.filename).unwrap(),
+    /// Creates the file and returns the group.
+    pub fn build(self) -> Result<Hdf5OutputGroup, VelvetError> {
+        Ok(Hdf5OutputGroup {
+            file: hdf5::File::create(&self.filename)?,
             interval: self.interval,
+            timestep: self.timestep,
+            chunk_frames: self.chunk_frames,
+            compression: self.compression,
+            velocities: self.velocities,
             outputs: self.outputs,
+            frames: 0,
+        })
+    }
+}
+
+impl Default for Hdf5OutputGroupBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Constructs a [`System`] from frame `frame` of the H5MD file at `filename`.
+///
+/// Atoms start at rest if the file does not contain velocities.
+pub fn load_hdf5_frame<T: AsRef<str>>(filename: T, frame: usize) -> Result<System, VelvetError> {
+    let file = hdf5::File::open(filename.as_ref())?;
+    let particles = file.group(PARTICLES)?;
+    let positions = particles.dataset("position/value")?;
+    let frames = positions.shape()[0];
+    if frame >= frames {
+        return Err(VelvetError::parse(
+            "HDF5",
+            format!("Frame {} does not exist in a file with {} frames", frame, frames),
+        ));
+    }
+    let positions = read_vectors(&positions, frame)?;
+    let size = positions.len();
+    let velocities = if particles.link_exists("velocity") {
+        read_vectors(&particles.dataset("velocity/value")?, frame)?
+    } else {
+        vec![Vector3::zeros(); size]
+    };
+    let vectors = read_vectors(&particles.dataset("box/edges/value")?, frame)?;
+    if vectors.len() != 3 {
+        return Err(VelvetError::parse("HDF5", "Expected 3 box edge vectors"));
+    }
+    let cell = Cell::from_vectors(vectors[0], vectors[1], vectors[2]);
+
+    let ids = file.dataset("parameters/species_ids")?.read_raw::<[u64; 2]>()?;
+    let indices = particles.dataset("species")?.read_raw::<i32>()?;
+    let masses = particles.dataset("mass")?.read_raw::<Float>()?;
+    let charges = particles.dataset("charge")?.read_raw::<Float>()?;
+    for &(field, found) in [
+        ("species", indices.len()),
+        ("masses", masses.len()),
+        ("charges", charges.len()),
+    ]
+    .iter()
+    {
+        if found != size {
+            return Err(VelvetError::LengthMismatch {
+                field,
+                expected: size,
+                found,
+            });
         }
     }
+    // each species takes the charge of its first atom and differing atoms keep partial charges
+    let mut types: Vec<Option<Species>> = vec![None; ids.len()];
+    let species = indices
+        .iter()
+        .zip(masses.iter().zip(charges.iter()))
+        .map(|(&index, (&mass, &charge))| {
+            let id = ids
+                .get(index as usize)
+                .ok_or_else(|| VelvetError::parse("HDF5", format!("Invalid species index `{}`", index)))?;
+            let species = types[index as usize]
+                .get_or_insert_with(|| Species::from_id(((id[0] as u128) << 64) | id[1] as u128, mass, charge));
+            Ok(*species)
+        })
+        .collect::<Result<Vec<Species>, VelvetError>>()?;
+    let partial = species.iter().zip(charges.iter()).any(|(s, &q)| s.charge() != q);
+
+    Ok(System {
+        size,
+        cell,
+        species,
+        positions,
+        velocities,
+        topology: Topology {
+            charges: if partial { charges } else { Vec::new() },
+            ..Topology::default()
+        },
+    })
+}
+
+// Reads frame `frame` of a `[frames][n][3]` dataset.
+fn read_vectors(dataset: &Dataset, frame: usize) -> Result<Vec<Vector3<Float>>, VelvetError> {
+    let indices = vec![
+        SliceOrIndex::from(frame..frame + 1),
+        SliceOrIndex::from(..),
+        SliceOrIndex::from(..),
+    ];
+    let slice = SliceInfo::<_, IxDyn>::new(indices).expect("slice must match the rank of the dataset");
+    let values = dataset.read_slice::<Float, _, IxDyn>(&slice)?;
+    let values: Vec<Float> = values.iter().copied().collect();
+    Ok(values.chunks_exact(3).map(|v| Vector3::new(v[0], v[1], v[2])).collect())
 }
 
 // This issue: https://github.com/rust-lang/rust/issues/20400
@@ -66,53 +486,206 @@ impl Hdf5OutputGroupBuilder {
 // in order to make the formatting more appropriate.
 
 impl Hdf5Output for Forces {
-    fn output_hdf5(&self, system: &System, potentials: &Potentials, group: &hdf5::Group) {
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
         let forces = self.calculate(system, potentials);
-        let dataset = group
-            .new_dataset::<[Float; 3]>()
-            .create(self.name(), system.size)
-            .unwrap();
-        let arr: Vec<[Float; 3]> = forces.iter().map(|x| [x[0], x[1], x[2]]).collect();
-        dataset.write(arr.as_slice()).unwrap()
+        vec![Hdf5Frame::vectors(
+            format!("{}/force", PARTICLES),
+            &forces,
+            "kcal mol-1 Angstrom-1",
+        )]
+    }
+}
+
+impl Hdf5Output for PerAtomPotentialEnergy {
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        let energies = self.calculate(system, potentials);
+        vec![Hdf5Frame::observable(&self.name(), vec![energies.len()], energies)]
+    }
+}
+
+impl Hdf5Output for PerAtomStress {
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        let stresses = self.calculate(system, potentials);
+        let shape = vec![stresses.len(), 3, 3];
+        let data = stresses
+            .iter()
+            .flat_map(|x| (0..3).flat_map(move |row| (0..3).map(move |col| x[(row, col)])))
+            .collect();
+        vec![Hdf5Frame::observable(&self.name(), shape, data)]
+    }
+}
+
+impl Hdf5Output for Msd {
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        let msd = self.calculate(system, potentials);
+        vec![Hdf5Frame::observable(&self.name(), vec![msd.len()], msd)]
+    }
+}
+
+impl Hdf5Output for Rdf {
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        let rdf = self.calculate(system, potentials);
+        vec![Hdf5Frame::observable(&self.name(), vec![rdf.len()], rdf)]
+    }
+}
+
+impl Hdf5Output for VelocityAutocorrelation {
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        vec![Hdf5Frame::scalar(&self.name(), self.calculate(system, potentials))]
+    }
+}
+
+impl<P> Hdf5Output for TimeCorrelation<P>
+where
+    P: Property,
+    P::Res: Correlatable,
+{
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        vec![Hdf5Frame::scalar(&self.name(), self.calculate(system, potentials))]
     }
 }
 
 impl Hdf5Output for KineticEnergy {
-    fn output_hdf5(&self, system: &System, potentials: &Potentials, group: &hdf5::Group) {
-        let energy = self.calculate(system, potentials);
-        let dataset = group.new_dataset::<Float>().create(self.name(), 1).unwrap();
-        dataset.write(&[energy]).unwrap();
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        vec![Hdf5Frame::scalar(&self.name(), self.calculate(system, potentials))]
     }
 }
 
 impl Hdf5Output for PotentialEnergy {
-    fn output_hdf5(&self, system: &System, potentials: &Potentials, group: &hdf5::Group) {
-        let energy = self.calculate(system, potentials);
-        let dataset = group.new_dataset::<Float>().create(self.name(), 1).unwrap();
-        dataset.write(&[energy]).unwrap();
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        vec![Hdf5Frame::scalar(&self.name(), self.calculate(system, potentials))]
     }
 }
 
 impl Hdf5Output for TotalEnergy {
-    fn output_hdf5(&self, system: &System, potentials: &Potentials, group: &hdf5::Group) {
-        let energy = self.calculate(system, potentials);
-        let dataset = group.new_dataset::<Float>().create(self.name(), 1).unwrap();
-        dataset.write(&[energy]).unwrap();
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        vec![Hdf5Frame::scalar(&self.name(), self.calculate(system, potentials))]
     }
 }
 
 impl Hdf5Output for PairEnergy {
-    fn output_hdf5(&self, system: &System, potentials: &Potentials, group: &hdf5::Group) {
-        let energy = self.calculate(system, potentials);
-        let dataset = group.new_dataset::<Float>().create(self.name(), 1).unwrap();
-        dataset.write(&[energy]).unwrap();
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        vec![Hdf5Frame::scalar(&self.name(), self.calculate(system, potentials))]
+    }
+}
+
+impl Hdf5Output for CellParameters {
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        let parameters = self.calculate(system, potentials);
+        vec![
+            Hdf5Frame::observable("cell_lengths", vec![3], parameters.lengths.iter().copied().collect()),
+            Hdf5Frame::observable("cell_angles", vec![3], parameters.angles.iter().copied().collect()),
+            Hdf5Frame::scalar("volume", parameters.volume),
+        ]
     }
 }
 
 impl Hdf5Output for Temperature {
-    fn output_hdf5(&self, system: &System, potentials: &Potentials, group: &hdf5::Group) {
-        let temperature = self.calculate(system, potentials);
-        let dataset = group.new_dataset::<Float>().create(self.name(), 1).unwrap();
-        dataset.write(&[temperature]).unwrap();
+    fn output_hdf5(&self, system: &System, potentials: &Potentials) -> Vec<Hdf5Frame> {
+        vec![Hdf5Frame::scalar(&self.name(), self.calculate(system, potentials))]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_hdf5_frame, Hdf5OutputGroupBuilder};
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::energy::KineticEnergy;
+    use crate::properties::forces::Forces;
+    use crate::properties::lattice::CellParameters;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use hdf5::types::VarLenUnicode;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn round_trip() {
+        let mut system = System {
+            size: 2,
+            cell: Cell::triclinic(6.0, 7.0, 8.0, 90.0, 90.0, 120.0),
+            species: vec![Species::new(2.0, 0.5), Species::new(3.0, -0.5)],
+            positions: vec![Vector3::new(1.0, 2.0, 3.0), Vector3::new(2.0, 3.0, 4.0)],
+            velocities: vec![Vector3::new(0.1, 0.0, 0.0), Vector3::new(0.0, -0.2, 0.0)],
+            topology: Topology::default(),
+        };
+        let mut potentials = PotentialsBuilder::new().build();
+        potentials.setup(&system);
+
+        let path = std::env::temp_dir().join(format!("velvet-hdf5-{}.h5", std::process::id()));
+        let mut group = Hdf5OutputGroupBuilder::new()
+            .filename(path.to_str().unwrap())
+            .timestep(0.5)
+            .chunk_frames(2)
+            .compression(4)
+            .output(KineticEnergy)
+            .output(Forces)
+            .output(CellParameters)
+            .build()
+            .unwrap();
+        for step in 0..3 {
+            system.positions[0].x = step as Float;
+            group.write_frame(step * 10, &system, &potentials).unwrap();
+        }
+        assert_eq!(group.frames(), 3);
+        drop(group);
+
+        let loaded = load_hdf5_frame(path.to_str().unwrap(), 1).unwrap();
+        assert_eq!(loaded.size, 2);
+        assert!((loaded.positions[0].x - 1.0).abs() < 1e-6);
+        assert!((loaded.positions[1] - system.positions[1]).norm() < 1e-6);
+        assert!((loaded.velocities[1] - system.velocities[1]).norm() < 1e-6);
+        assert!((loaded.cell.gamma() - 120.0).abs() < 1e-3);
+        assert_eq!(loaded.species[1].id(), system.species[1].id());
+        assert!((loaded.species[1].charge() + 0.5).abs() < 1e-6);
+        assert!(load_hdf5_frame(path.to_str().unwrap(), 3).is_err());
+
+        // the layout follows the H5MD specification
+        let file = hdf5::File::open(path.to_str().unwrap()).unwrap();
+        let version = file
+            .group("h5md")
+            .unwrap()
+            .attr("version")
+            .unwrap()
+            .read_raw::<i32>()
+            .unwrap();
+        assert_eq!(version, vec![1, 1]);
+        let position = file.group("particles/all/position").unwrap();
+        assert_eq!(position.dataset("value").unwrap().shape(), vec![3, 2, 3]);
+        assert_eq!(
+            position.dataset("step").unwrap().read_raw::<i64>().unwrap(),
+            vec![0, 10, 20]
+        );
+        assert_eq!(
+            position.dataset("time").unwrap().read_raw::<Float>().unwrap(),
+            vec![0.0, 5.0, 10.0]
+        );
+        let unit: VarLenUnicode = position
+            .dataset("value")
+            .unwrap()
+            .attr("unit")
+            .unwrap()
+            .read_scalar()
+            .unwrap();
+        assert_eq!(unit.as_str(), "Angstrom");
+        assert_eq!(
+            file.dataset("particles/all/box/edges/value").unwrap().shape(),
+            vec![3, 3, 3]
+        );
+        assert_eq!(
+            file.dataset("particles/all/force/value").unwrap().shape(),
+            vec![3, 2, 3]
+        );
+        assert_eq!(
+            file.dataset("observables/kinetic_energy/value").unwrap().shape(),
+            vec![3]
+        );
+        assert_eq!(
+            file.dataset("observables/cell_angles/value").unwrap().shape(),
+            vec![3, 3]
+        );
+        std::fs::remove_file(path).unwrap();
     }
 }
@@ -1,5 +1,8 @@
 //! Properties which can be output as a result from the simulation.
 
+pub mod compression;
 #[cfg(feature = "hdf5-output")]
 pub mod hdf5;
 pub mod raw;
+pub mod thermo;
+pub mod trajectory;
@@ -12,12 +12,17 @@ pub trait RawOutput {
     fn output_raw(&self, system: &System, potentials: &Potentials, writer: &mut dyn Write);
 }
 
+/// Collection of raw outputs which share a destination and output interval.
 pub struct RawOutputGroup {
+    /// Destination the outputs are written to.
     pub destination: Box<dyn Write>,
+    /// Number of iterations between each output.
     pub interval: usize,
+    /// Outputs to be written.
     pub outputs: Vec<Box<dyn RawOutput>>,
 }
 
+/// Constructor for the [`RawOutputGroup`] type.
 pub struct RawOutputGroupBuilder {
     destination: Box<dyn Write>,
     interval: usize,
@@ -25,6 +30,7 @@ pub struct RawOutputGroupBuilder {
 }
 
 impl RawOutputGroupBuilder {
+    /// Returns a new `RawOutputGroupBuilder` which writes to stderr every iteration.
     pub fn new() -> RawOutputGroupBuilder {
         RawOutputGroupBuilder {
             destination: Box::new(std::io::stderr()),
@@ -33,21 +39,25 @@ impl RawOutputGroupBuilder {
         }
     }
 
+    /// Sets the destination of the output group.
     pub fn destination<T: Write + 'static>(mut self, destination: T) -> RawOutputGroupBuilder {
         self.destination = Box::new(destination);
         self
     }
 
+    /// Sets the number of iterations between each output.
     pub fn interval(mut self, interval: usize) -> RawOutputGroupBuilder {
         self.interval = interval;
         self
     }
 
+    /// Adds an output to the group.
     pub fn output<T: RawOutput + 'static>(mut self, output: T) -> RawOutputGroupBuilder {
         self.outputs.push(Box::new(output));
         self
     }
 
+    /// Returns an initialized [`RawOutputGroup`].
     pub fn build(self) -> RawOutputGroup {
         RawOutputGroup {
             destination: self.destination,
@@ -57,6 +67,12 @@ impl RawOutputGroupBuilder {
     }
 }
 
+impl Default for RawOutputGroupBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // This issue: https://github.com/rust-lang/rust/issues/20400
 // prevents me from specializing the impl block by the trait's associated type.
 // Ideally I will have separate impl blocks for Property<Res=Float> and Property<Res=Vector3<Float>>
@@ -0,0 +1,240 @@
+//! Thermodynamic tables with one row per output.
+
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::internal::Float;
+use crate::outputs::raw::RawOutput;
+use crate::potentials::Potentials;
+use crate::properties::energy::{KineticEnergy, PotentialEnergy, TotalEnergy};
+use crate::properties::lattice::CellParameter;
+use crate::properties::pressure::Pressure;
+use crate::properties::temperature::Temperature;
+use crate::properties::Property;
+use crate::system::System;
+
+/// Minimum width of each column of the table.
+const WIDTH: usize = 16;
+
+/// Table of scalar properties written as one row per output, like the thermo output of LAMMPS.
+///
+/// The first column is the step, counted as the number of rows already written times the
+/// `interval`, which should match the interval of the output group. A header naming each column
+/// is written before the first row. Every row can also be mirrored to a second writer as CSV,
+/// which keeps a human readable log on the console and a machine readable file for plotting.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let path = std::env::temp_dir().join("velvet-thermo.csv");
+/// let thermo = ThermoOutput::standard(100).csv(std::fs::File::create(&path).unwrap());
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(std::io::stdout())
+///     .interval(100)
+///     .output(thermo)
+///     .build();
+/// # std::fs::remove_file(path).unwrap();
+/// ```
+pub struct ThermoOutput {
+    interval: usize,
+    columns: Vec<Box<dyn Property<Res = Float> + Send + Sync>>,
+    csv: Option<Mutex<Box<dyn Write + Send>>>,
+    rows: AtomicUsize,
+}
+
+impl ThermoOutput {
+    /// Returns a new [`ThermoOutput`] with only the step column.
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - Number of iterations between rows, which should match the interval of the output group.
+    pub fn new(interval: usize) -> ThermoOutput {
+        ThermoOutput {
+            interval,
+            columns: Vec::new(),
+            csv: None,
+            rows: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns a new [`ThermoOutput`] with columns of the temperature, the kinetic, potential,
+    /// and total energies, and the pressure.
+    pub fn standard(interval: usize) -> ThermoOutput {
+        ThermoOutput::new(interval)
+            .column(Temperature)
+            .column(KineticEnergy)
+            .column(PotentialEnergy)
+            .column(TotalEnergy)
+            .column(Pressure)
+    }
+
+    /// Adds a column with the value of `property`.
+    pub fn column<T: Property<Res = Float> + Send + Sync + 'static>(mut self, property: T) -> ThermoOutput {
+        self.columns.push(Box::new(property));
+        self
+    }
+
+    /// Adds columns with the components of the lattice vectors followed by the lengths, the
+    /// angles, and the volume of the cell, as listed by [`CellParameter::all`].
+    pub fn cell(mut self) -> ThermoOutput {
+        for parameter in CellParameter::all() {
+            self = self.column(parameter);
+        }
+        self
+    }
+
+    /// Mirrors every row to `writer` in CSV format.
+    pub fn csv<W: Write + Send + 'static>(mut self, writer: W) -> ThermoOutput {
+        self.csv = Some(Mutex::new(Box::new(writer)));
+        self
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names = vec![String::from("step")];
+        names.extend(self.columns.iter().map(|column| column.name()));
+        names
+    }
+}
+
+impl RawOutput for ThermoOutput {
+    fn output_raw(&self, system: &System, potentials: &Potentials, writer: &mut dyn Write) {
+        let row = self.rows.fetch_add(1, Ordering::Relaxed);
+        let names = self.names();
+        let widths: Vec<usize> = names.iter().map(|name| name.len().max(WIDTH)).collect();
+        let values: Vec<Float> = self
+            .columns
+            .iter()
+            .map(|column| column.calculate(system, potentials))
+            .collect();
+        let step = row * self.interval;
+
+        let mut table = String::new();
+        if row == 0 {
+            for (name, width) in names.iter().zip(widths.iter()) {
+                write!(table, "{:>width$}", name, width = width + 1).unwrap();
+            }
+            table.push('\n');
+        }
+        write!(table, "{:>width$}", step, width = widths[0] + 1).unwrap();
+        for (value, width) in values.iter().zip(widths[1..].iter()) {
+            write!(table, "{:>width$.6}", value, width = width + 1).unwrap();
+        }
+        table.push('\n');
+        writer.write_all(table.as_bytes()).unwrap();
+
+        if let Some(csv) = &self.csv {
+            let mut lines = String::new();
+            if row == 0 {
+                lines.push_str(&names.join(","));
+                lines.push('\n');
+            }
+            lines.push_str(&step.to_string());
+            for value in &values {
+                write!(lines, ",{}", value).unwrap();
+            }
+            lines.push('\n');
+            csv.lock().unwrap().write_all(lines.as_bytes()).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ThermoOutput;
+    use crate::outputs::raw::RawOutput;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::energy::KineticEnergy;
+    use crate::properties::temperature::Temperature;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    // writer whose contents remain readable after it is moved into the output
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn table_and_csv() {
+        let argon = Species::new(39.948, 0.0);
+        let system = System {
+            size: 2,
+            cell: Cell::cubic(10.0),
+            species: vec![argon; 2],
+            positions: vec![Vector3::new(1.0, 1.0, 1.0), Vector3::new(5.0, 5.0, 5.0)],
+            velocities: vec![Vector3::new(0.01, 0.0, 0.0), Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        let csv = Shared::default();
+        let thermo = ThermoOutput::new(10)
+            .column(Temperature)
+            .column(KineticEnergy)
+            .csv(csv.clone());
+        let mut table = Vec::new();
+        thermo.output_raw(&system, &potentials, &mut table);
+        thermo.output_raw(&system, &potentials, &mut table);
+
+        // the header is written once and the steps advance by the interval
+        let table = String::from_utf8(table).unwrap();
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0].split_whitespace().collect::<Vec<_>>(),
+            vec!["step", "temperature", "kinetic_energy"]
+        );
+        assert_eq!(lines[2].split_whitespace().next(), Some("10"));
+        assert_eq!(lines[1].len(), lines[0].len());
+
+        let csv = String::from_utf8(csv.0.lock().unwrap().clone()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(rows[0], "step,temperature,kinetic_energy");
+        let kinetic: f64 = rows[1].split(',').nth(2).unwrap().parse().unwrap();
+        assert!((kinetic - 0.5 * 39.948 * 0.0001).abs() < 1e-6);
+        assert!(rows[2].starts_with("10,"));
+    }
+
+    #[test]
+    fn cell_columns() {
+        let system = System {
+            size: 1,
+            cell: Cell::triclinic(4.0, 5.0, 6.0, 90.0, 90.0, 120.0),
+            species: vec![Species::new(1.0, 0.0)],
+            positions: vec![Vector3::zeros()],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        let csv = Shared::default();
+        let thermo = ThermoOutput::new(1).cell().csv(csv.clone());
+        thermo.output_raw(&system, &potentials, &mut Vec::new());
+
+        let csv = String::from_utf8(csv.0.lock().unwrap().clone()).unwrap();
+        let rows: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            rows[0],
+            "step,a_x,a_y,a_z,b_x,b_y,b_z,c_x,c_y,c_z,a,b,c,alpha,beta,gamma,volume"
+        );
+        let values: Vec<f64> = rows[1].split(',').map(|value| value.parse().unwrap()).collect();
+        assert!((values[1] - 4.0).abs() < 1e-4);
+        assert!((values[4] + 2.5).abs() < 1e-4);
+        assert!((values[15] - 120.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,650 @@
+//! Trajectory formatted outputs which can be read by visualization software.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::internal::Float;
+use crate::outputs::raw::RawOutput;
+use crate::potentials::Potentials;
+use crate::properties::PerAtomProperty;
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Atoms recorded in each frame of a trajectory.
+///
+/// Recording only the solute, or a sparse sample of the solvent, reduces the size of the
+/// trajectories of solvated systems by orders of magnitude. Atoms are chosen by index and by the
+/// names of the residues in the system's [`Topology`](crate::system::topology::Topology), and
+/// are recorded in order of their index in the system. The selection is resolved when the first
+/// frame is written so every frame records the same atoms even if the topology changes. Clones
+/// share the resolved selection.
+///
+/// Trajectories store the index of each recorded atom in the system, as an `id` column of the
+/// extended XYZ format, in the comment line of the plain XYZ format, and in the title of the DCD
+/// format. Ranges of consecutive indices are written as `first-last`.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // record the ligand and every 10th water molecule
+/// let selection = FrameSelection::new().residues("LIG").every_nth_residue("SOL", 10);
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(100)
+///     .output(XyzTrajectory::extended().selection(selection))
+///     .build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FrameSelection {
+    atoms: Vec<usize>,
+    residues: Vec<(String, usize)>,
+    resolved: Arc<Mutex<Option<Vec<usize>>>>,
+}
+
+impl FrameSelection {
+    /// Returns a new [`FrameSelection`] of no atoms.
+    pub fn new() -> FrameSelection {
+        FrameSelection::default()
+    }
+
+    /// Selects the atoms with the given indices.
+    pub fn atoms(mut self, atoms: Vec<usize>) -> FrameSelection {
+        self.atoms.extend(atoms);
+        self
+    }
+
+    /// Selects the atoms of every residue named `name`.
+    pub fn residues(self, name: &str) -> FrameSelection {
+        self.every_nth_residue(name, 1)
+    }
+
+    /// Selects the atoms of the first and every `n`th following residue named `name`.
+    pub fn every_nth_residue(mut self, name: &str, n: usize) -> FrameSelection {
+        self.residues.push((name.to_string(), n.max(1)));
+        self
+    }
+
+    /// Returns the sorted indices of the selected atoms, resolving the selection on the first call.
+    pub fn indices(&self, system: &System) -> Vec<usize> {
+        let mut resolved = self.resolved.lock().unwrap();
+        resolved
+            .get_or_insert_with(|| {
+                let mut indices = self.atoms.clone();
+                for (name, n) in &self.residues {
+                    let atoms = system
+                        .topology
+                        .residues
+                        .iter()
+                        .filter(|residue| &residue.name == name)
+                        .step_by(*n)
+                        .flat_map(|residue| residue.atoms.iter().copied());
+                    indices.extend(atoms);
+                }
+                indices.sort_unstable();
+                indices.dedup();
+                indices
+            })
+            .clone()
+    }
+}
+
+// Formats sorted indices with runs of consecutive indices collapsed into ranges.
+fn index_ranges(indices: &[usize]) -> Vec<String> {
+    let mut ranges: Vec<String> = Vec::new();
+    let mut k = 0;
+    while k < indices.len() {
+        let first = indices[k];
+        while k + 1 < indices.len() && indices[k + 1] == indices[k] + 1 {
+            k += 1;
+        }
+        if indices[k] == first {
+            ranges.push(first.to_string());
+        } else {
+            ranges.push(format!("{}-{}", first, indices[k]));
+        }
+        k += 1;
+    }
+    ranges
+}
+
+/// Writes one frame of the system per output in the XYZ or extended XYZ format.
+///
+/// Trajectories are added to a [`RawOutputGroup`](crate::outputs::raw::RawOutputGroup) which
+/// sets their destination file and the number of iterations between frames. Species constructed
+/// from an [`Element`](crate::system::elements::Element) are labeled by their chemical symbol
+/// while custom species are labeled `X1`, `X2`, etc. in order of appearance.
+///
+/// The extended format additionally records the lattice vectors and the velocity of each atom
+/// in angstrom/femtosecond, which allows tools such as OVITO to display the periodic cell.
+/// Any [`PerAtomProperty`] such as the forces or per-atom energies can be appended to each atom
+/// of the extended format as additional columns. A [`FrameSelection`] limits each frame to
+/// some of the atoms.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(100)
+///     .output(XyzTrajectory::extended().property(Forces).property(PerAtomPotentialEnergy))
+///     .build();
+/// ```
+#[derive(Clone)]
+pub struct XyzTrajectory {
+    extended: bool,
+    properties: Vec<Arc<dyn Columns>>,
+    selection: Option<FrameSelection>,
+}
+
+impl XyzTrajectory {
+    /// Returns a new [`XyzTrajectory`] which writes positions in the plain XYZ format.
+    pub fn plain() -> XyzTrajectory {
+        XyzTrajectory {
+            extended: false,
+            properties: Vec::new(),
+            selection: None,
+        }
+    }
+
+    /// Returns a new [`XyzTrajectory`] which writes the lattice, positions, and velocities in the extended XYZ format.
+    pub fn extended() -> XyzTrajectory {
+        XyzTrajectory {
+            extended: true,
+            properties: Vec::new(),
+            selection: None,
+        }
+    }
+
+    /// Appends a per-atom property to each atom of the extended format.
+    ///
+    /// Properties are ignored by the plain format, which has no way to label extra columns.
+    pub fn property<T: PerAtomProperty + Send + Sync + 'static>(mut self, property: T) -> XyzTrajectory {
+        self.properties.push(Arc::new(property));
+        self
+    }
+
+    /// Records only the atoms in `selection` instead of every atom.
+    pub fn selection(mut self, selection: FrameSelection) -> XyzTrajectory {
+        self.selection = Some(selection);
+        self
+    }
+}
+
+impl fmt::Debug for XyzTrajectory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let properties: Vec<String> = self.properties.iter().map(|p| p.label()).collect();
+        f.debug_struct("XyzTrajectory")
+            .field("extended", &self.extended)
+            .field("properties", &properties)
+            .field("selection", &self.selection)
+            .finish()
+    }
+}
+
+// Object safe view of a per-atom property written as columns of an extended XYZ frame.
+trait Columns: Send + Sync {
+    // Returns the entry of the property in the `Properties` key of the comment line.
+    fn label(&self) -> String;
+
+    fn columns(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>>;
+}
+
+impl<T: PerAtomProperty + Send + Sync> Columns for T {
+    fn label(&self) -> String {
+        let kind = if self.integral() { "I" } else { "R" };
+        format!("{}:{}:{}", self.name(), kind, self.components())
+    }
+
+    fn columns(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate_per_atom(system, potentials)
+    }
+}
+
+/// Returns a text label for each atom in the system.
+///
+/// Species constructed from an [`Element`](crate::system::elements::Element) are labeled by
+/// their chemical symbol while custom species are labeled `X1`, `X2`, etc. in order of appearance.
+pub fn species_labels(system: &System) -> Vec<String> {
+    let mut custom: Vec<Species> = Vec::new();
+    system
+        .species
+        .iter()
+        .map(|species| match species.element() {
+            Some(element) => element.symbol().to_string(),
+            None => {
+                let index = match custom.iter().position(|s| s == species) {
+                    Some(index) => index,
+                    None => {
+                        custom.push(*species);
+                        custom.len() - 1
+                    }
+                };
+                format!("X{}", index + 1)
+            }
+        })
+        .collect()
+}
+
+impl RawOutput for XyzTrajectory {
+    fn output_raw(&self, system: &System, potentials: &Potentials, writer: &mut dyn Write) {
+        let indices = self.selection.as_ref().map(|selection| selection.indices(system));
+        let atoms = indices.clone().unwrap_or_else(|| (0..system.size).collect());
+        let mut frame = format!("{}\n", atoms.len());
+        let mut columns = Vec::new();
+        if self.extended {
+            let matrix = system.cell.matrix();
+            let lattice: Vec<String> = (0..3)
+                .flat_map(|col| (0..3).map(move |row| matrix[(row, col)].to_string()))
+                .collect();
+            let mut properties = String::from("species:S:1:pos:R:3:velo:R:3");
+            if indices.is_some() {
+                properties.push_str(":id:I:1");
+            }
+            for property in &self.properties {
+                properties.push_str(&format!(":{}", property.label()));
+                columns.push(property.columns(system, potentials));
+            }
+            frame.push_str(&format!(
+                "Lattice=\"{}\" Properties={} pbc=\"T T T\"\n",
+                lattice.join(" "),
+                properties
+            ));
+        } else if let Some(indices) = &indices {
+            frame.push_str(&format!("Velvet trajectory selection={}\n", index_ranges(indices).join(",")));
+        } else {
+            frame.push_str("Velvet trajectory\n");
+        }
+        let labels = species_labels(system);
+        for &i in &atoms {
+            let (pos, vel) = (system.positions[i], system.velocities[i]);
+            frame.push_str(&format!("{} {} {} {}", labels[i], pos[0], pos[1], pos[2]));
+            if self.extended {
+                frame.push_str(&format!(" {} {} {}", vel[0], vel[1], vel[2]));
+                if indices.is_some() {
+                    frame.push_str(&format!(" {}", i));
+                }
+            }
+            for value in columns.iter().flat_map(|column| column[i].iter()) {
+                frame.push_str(&format!(" {}", value));
+            }
+            frame.push('\n');
+        }
+        writer.write_all(frame.as_bytes()).unwrap()
+    }
+}
+
+/// Length of one AKMA time unit in femtoseconds.
+const AKMA_TIME: Float = 48.88821;
+
+/// Writes one frame of the system per output in the binary CHARMM/NAMD DCD format.
+///
+/// Frames include the unit cell record so the trajectory can be read by VMD and MDAnalysis.
+/// The number of frames is not known ahead of time so the header records zero frames and
+/// readers infer the number of frames from the size of the file. A [`FrameSelection`] limits
+/// each frame to some of the atoms.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(50)
+///     .output(DcdTrajectory::new(1.0, 50))
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct DcdTrajectory {
+    timestep: Float,
+    stride: usize,
+    selection: Option<FrameSelection>,
+    header_written: AtomicBool,
+}
+
+impl DcdTrajectory {
+    /// Returns a new [`DcdTrajectory`].
+    ///
+    /// # Arguments
+    ///
+    /// * `timestep` - Timestep of the integrator recorded in the header.
+    /// * `stride` - Number of iterations between frames recorded in the header, which should match the interval of the output group.
+    pub fn new(timestep: Float, stride: usize) -> DcdTrajectory {
+        DcdTrajectory {
+            timestep,
+            stride,
+            selection: None,
+            header_written: AtomicBool::new(false),
+        }
+    }
+
+    /// Records only the atoms in `selection` instead of every atom.
+    pub fn selection(mut self, selection: FrameSelection) -> DcdTrajectory {
+        self.selection = Some(selection);
+        self
+    }
+
+    // single precision is required by the format regardless of `Float`
+    #[allow(clippy::unnecessary_cast)]
+    fn header(&self, size: usize, indices: Option<&[usize]>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        // control record
+        let mut control = [0i32; 20];
+        control[2] = self.stride as i32;
+        control[10] = 1;
+        control[19] = 24;
+        record(&mut bytes, |buf| {
+            buf.extend_from_slice(b"CORD");
+            for (i, value) in control.iter().enumerate() {
+                if i == 9 {
+                    buf.extend_from_slice(&((self.timestep / AKMA_TIME) as f32).to_le_bytes());
+                } else {
+                    buf.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+        });
+        // title record with the selected indices split across as many lines as needed
+        let mut lines = vec![String::from("Velvet trajectory")];
+        if let Some(indices) = indices {
+            let mut line = String::from("REMARKS SELECTION");
+            for range in index_ranges(indices) {
+                if line.len() + range.len() + 1 > 80 {
+                    lines.push(line);
+                    line = String::from("REMARKS SELECTION");
+                }
+                line.push(' ');
+                line.push_str(&range);
+            }
+            lines.push(line);
+        }
+        record(&mut bytes, |buf| {
+            buf.extend_from_slice(&(lines.len() as i32).to_le_bytes());
+            for line in &lines {
+                let mut title = [b' '; 80];
+                title[..line.len()].copy_from_slice(line.as_bytes());
+                buf.extend_from_slice(&title);
+            }
+        });
+        // atom count record
+        record(&mut bytes, |buf| {
+            buf.extend_from_slice(&(size as i32).to_le_bytes())
+        });
+        bytes
+    }
+}
+
+// Appends a Fortran unformatted record with its leading and trailing length markers.
+fn record<F: FnOnce(&mut Vec<u8>)>(bytes: &mut Vec<u8>, contents: F) {
+    let mut buf = Vec::new();
+    contents(&mut buf);
+    let len = (buf.len() as i32).to_le_bytes();
+    bytes.extend_from_slice(&len);
+    bytes.extend_from_slice(&buf);
+    bytes.extend_from_slice(&len);
+}
+
+impl RawOutput for DcdTrajectory {
+    #[allow(clippy::unnecessary_cast)]
+    fn output_raw(&self, system: &System, _: &Potentials, writer: &mut dyn Write) {
+        let indices = self.selection.as_ref().map(|selection| selection.indices(system));
+        let atoms = indices.clone().unwrap_or_else(|| (0..system.size).collect());
+        let mut bytes = Vec::new();
+        if !self.header_written.swap(true, Ordering::Relaxed) {
+            bytes = self.header(atoms.len(), indices.as_deref());
+        }
+        // unit cell in the order a, gamma, b, beta, alpha, c
+        let cell = &system.cell;
+        record(&mut bytes, |buf| {
+            for value in [
+                cell.a(),
+                cell.gamma(),
+                cell.b(),
+                cell.beta(),
+                cell.alpha(),
+                cell.c(),
+            ]
+            .iter()
+            {
+                buf.extend_from_slice(&(*value as f64).to_le_bytes());
+            }
+        });
+        for k in 0..3 {
+            record(&mut bytes, |buf| {
+                for &i in &atoms {
+                    buf.extend_from_slice(&(system.positions[i][k] as f32).to_le_bytes());
+                }
+            });
+        }
+        writer.write_all(&bytes).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{index_ranges, DcdTrajectory, FrameSelection, XyzTrajectory};
+    use crate::internal::Float;
+    use crate::outputs::raw::RawOutput;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::forces::Forces;
+    use crate::properties::per_atom::{
+        CommonNeighborAnalysis, Coordination, PerAtomPotentialEnergy, PerAtomStress, VonMisesStress,
+    };
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::{Residue, Topology};
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    fn mixed_system() -> System {
+        let argon = Species::from_element(Element::Ar);
+        let bead = Species::new(10.0, 0.0);
+        System {
+            size: 3,
+            cell: Cell::triclinic(10.0, 10.0, 12.0, 90.0, 90.0, 90.0),
+            species: vec![argon, bead, argon],
+            positions: vec![
+                Vector3::new(1.0, 2.0, 3.0),
+                Vector3::new(4.0, 5.0, 6.0),
+                Vector3::new(7.0, 8.0, 9.0),
+            ],
+            velocities: vec![Vector3::new(0.5, 0.0, -0.5); 3],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn plain_frame() {
+        let system = mixed_system();
+        let potentials = PotentialsBuilder::new().build();
+        let mut buffer = Vec::new();
+        XyzTrajectory::plain().output_raw(&system, &potentials, &mut buffer);
+        XyzTrajectory::plain().output_raw(&system, &potentials, &mut buffer);
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 10);
+        assert_eq!(lines[0], "3");
+        assert_eq!(lines[2], "Ar 1 2 3");
+        assert_eq!(lines[3], "X1 4 5 6");
+        assert_eq!(lines[5], "3");
+    }
+
+    #[test]
+    fn extended_frame() {
+        let system = mixed_system();
+        let potentials = PotentialsBuilder::new().build();
+        let mut buffer = Vec::new();
+        XyzTrajectory::extended().output_raw(&system, &potentials, &mut buffer);
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].starts_with("Lattice=\"10 0 0 0 10 0 0 0 12\""));
+        assert!(lines[1].contains("velo:R:3"));
+        assert_eq!(lines[4], "Ar 7 8 9 0.5 0 -0.5");
+    }
+
+    #[test]
+    fn per_atom_columns() {
+        let system = mixed_system();
+        let argon = system.species[0];
+        let mut potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+        let trajectory = XyzTrajectory::extended()
+            .property(Forces)
+            .property(PerAtomPotentialEnergy)
+            .property(PerAtomStress);
+        let mut buffer = Vec::new();
+        trajectory.output_raw(&system, &potentials, &mut buffer);
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines[1].contains(
+            "Properties=species:S:1:pos:R:3:velo:R:3:forces:R:3:per_atom_potential_energy:R:1:per_atom_stress:R:9 "
+        ));
+        // species, position, velocity, force, energy, and stress
+        assert!(lines[2..].iter().all(|line| line.split_whitespace().count() == 20));
+        let columns: Vec<&str> = lines[3].split_whitespace().collect();
+        assert_eq!(&columns[7..10], &["0", "0", "0"]);
+
+        // the plain format has no columns for properties
+        let mut buffer = Vec::new();
+        XyzTrajectory::plain().property(Forces).output_raw(&system, &potentials, &mut buffer);
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().nth(2).unwrap().split_whitespace().count(), 4);
+    }
+
+    #[test]
+    fn structure_columns() {
+        let system = mixed_system();
+        let potentials = PotentialsBuilder::new().build();
+        let trajectory = XyzTrajectory::extended()
+            .property(VonMisesStress)
+            .property(Coordination::new(4.0))
+            .property(CommonNeighborAnalysis::new(4.0));
+        let mut buffer = Vec::new();
+        trajectory.output_raw(&system, &potentials, &mut buffer);
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        // counts and structure labels are integer columns which OVITO can color by type
+        assert!(lines[1].contains(":von_mises_stress:R:1:coordination:I:1:structure_type:I:1 "));
+        let columns: Vec<&str> = lines[2].split_whitespace().collect();
+        assert_eq!(columns.len(), 10);
+        assert!(columns[8].parse::<usize>().is_ok());
+        assert_eq!(columns[9], "0");
+    }
+
+    #[test]
+    fn dcd_frames() {
+        let system = mixed_system();
+        let potentials = PotentialsBuilder::new().build();
+        let dcd = DcdTrajectory::new(2.0, 10);
+        let mut buffer = Vec::new();
+        dcd.output_raw(&system, &potentials, &mut buffer);
+        let first = buffer.len();
+        dcd.output_raw(&system, &potentials, &mut buffer);
+
+        let int = |offset: usize| {
+            i32::from_le_bytes([
+                buffer[offset],
+                buffer[offset + 1],
+                buffer[offset + 2],
+                buffer[offset + 3],
+            ])
+        };
+        // control record
+        assert_eq!(int(0), 84);
+        assert_eq!(&buffer[4..8], b"CORD");
+        assert_eq!(int(4 + 4 + 2 * 4), 10);
+        assert_eq!(int(88), 84);
+        // title and atom count records
+        assert_eq!(int(92), 84);
+        assert_eq!(int(184), 4);
+        assert_eq!(int(188), 3);
+        // each frame holds the unit cell and 3 coordinate records without repeating the header
+        let frame = (8 + 48) + 3 * (8 + 4 * 3);
+        assert_eq!(first, 196 + frame);
+        assert_eq!(buffer.len(), first + frame);
+        assert_eq!(int(first), 48);
+        let a = f64::from_le_bytes([
+            buffer[first + 4],
+            buffer[first + 5],
+            buffer[first + 6],
+            buffer[first + 7],
+            buffer[first + 8],
+            buffer[first + 9],
+            buffer[first + 10],
+            buffer[first + 11],
+        ]);
+        assert!((a - 10.0).abs() < 1e-4);
+    }
+
+    // Solute atom followed by five single atom solvent residues.
+    fn solvated_system() -> System {
+        let argon = Species::from_element(Element::Ar);
+        let xenon = Species::from_element(Element::Xe);
+        let residue = |name: &str, i: usize| Residue {
+            name: name.to_string(),
+            atoms: vec![i],
+        };
+        System {
+            size: 6,
+            cell: Cell::cubic(10.0),
+            species: vec![xenon, argon, argon, argon, argon, argon],
+            positions: (0..6).map(|i| Vector3::new(i as Float, 0.0, 0.0)).collect(),
+            velocities: vec![Vector3::zeros(); 6],
+            topology: Topology {
+                residues: (0..6).map(|i| residue(if i == 0 { "LIG" } else { "SOL" }, i)).collect(),
+                ..Topology::default()
+            },
+        }
+    }
+
+    #[test]
+    fn frame_selection() {
+        let mut system = solvated_system();
+        let selection = FrameSelection::new().residues("LIG").every_nth_residue("SOL", 2).atoms(vec![5, 1]);
+        assert_eq!(selection.indices(&system), vec![0, 1, 3, 5]);
+        // the first resolution is kept by every clone
+        system.topology.residues.clear();
+        assert_eq!(selection.clone().indices(&system), vec![0, 1, 3, 5]);
+        assert_eq!(index_ranges(&[0, 1, 3, 5, 6, 7]), vec!["0-1", "3", "5-7"]);
+    }
+
+    #[test]
+    fn selected_frames() {
+        let system = solvated_system();
+        let potentials = PotentialsBuilder::new().build();
+        let selection = FrameSelection::new().residues("LIG").every_nth_residue("SOL", 2);
+        let mut buffer = Vec::new();
+        let trajectory = XyzTrajectory::extended().property(Forces).selection(selection.clone());
+        trajectory.output_raw(&system, &potentials, &mut buffer);
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "4");
+        assert!(lines[1].contains("Properties=species:S:1:pos:R:3:velo:R:3:id:I:1:forces:R:3 "));
+        assert_eq!(lines[4], "Ar 3 0 0 0 0 0 3 0 0 0");
+
+        let mut buffer = Vec::new();
+        XyzTrajectory::plain().selection(selection).output_raw(&system, &potentials, &mut buffer);
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().nth(1).unwrap(), "Velvet trajectory selection=0-1,3,5");
+
+        let dcd = DcdTrajectory::new(2.0, 10).selection(FrameSelection::new().atoms(vec![2, 4]));
+        let mut buffer = Vec::new();
+        dcd.output_raw(&system, &potentials, &mut buffer);
+        // two title lines and two atoms per coordinate record
+        assert_eq!(&buffer[92..96], &164i32.to_le_bytes());
+        let title = String::from_utf8(buffer[180..260].to_vec()).unwrap();
+        assert_eq!(title.trim_end(), "REMARKS SELECTION 2 4");
+        assert_eq!(&buffer[268..272], &2i32.to_le_bytes());
+        assert_eq!(buffer.len(), 276 + (8 + 48) + 3 * (8 + 4 * 2));
+    }
+}
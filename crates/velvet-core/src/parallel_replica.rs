@@ -0,0 +1,347 @@
+//! Parallel replica dynamics for the kinetics of rare events.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::colvars::CollectiveVariable;
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::system::System;
+use crate::velocity_distributions::VelocityDistribution;
+
+/// Maximum number of attempts to dephase each replica without leaving the state.
+const MAX_ATTEMPTS: usize = 100;
+
+/// Shared behavior for criteria which decide if a system has left its current state.
+pub trait TransitionDetector: Send + Sync {
+    /// Returns `true` if `system` has left the state represented by `reference`.
+    fn detect(&self, reference: &System, system: &System) -> bool;
+}
+
+/// Detects a transition when any atom moves farther than a threshold from its reference position.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplacementDetector {
+    threshold: Float,
+}
+
+impl DisplacementDetector {
+    /// Returns a new [`DisplacementDetector`].
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Displacement in angstroms which must exceed thermal vibrations.
+    pub fn new(threshold: Float) -> DisplacementDetector {
+        DisplacementDetector { threshold }
+    }
+}
+
+impl TransitionDetector for DisplacementDetector {
+    fn detect(&self, reference: &System, system: &System) -> bool {
+        reference
+            .positions
+            .iter()
+            .zip(system.positions.iter())
+            .any(|(a, b)| system.cell.distance(a, b) > self.threshold)
+    }
+}
+
+/// Detects a transition when a collective variable changes by more than a threshold.
+pub struct OrderParameterDetector<C: CollectiveVariable> {
+    colvar: C,
+    threshold: Float,
+}
+
+impl<C: CollectiveVariable> OrderParameterDetector<C> {
+    /// Returns a new [`OrderParameterDetector`].
+    ///
+    /// # Arguments
+    ///
+    /// * `colvar` - Collective variable which distinguishes the states.
+    /// * `threshold` - Change in the collective variable which marks a transition.
+    pub fn new(colvar: C, threshold: Float) -> OrderParameterDetector<C> {
+        OrderParameterDetector { colvar, threshold }
+    }
+}
+
+impl<C: CollectiveVariable> TransitionDetector for OrderParameterDetector<C> {
+    fn detect(&self, reference: &System, system: &System) -> bool {
+        let (before, _) = self.colvar.evaluate(reference);
+        let (after, _) = self.colvar.evaluate(system);
+        (after - before).abs() > self.threshold
+    }
+}
+
+/// Transition observed during parallel replica dynamics.
+#[derive(Clone, Copy, Debug)]
+pub struct Transition {
+    /// Accumulated simulation time in femtoseconds at which the transition was detected.
+    pub time: Float,
+    /// Index of the replica which made the transition.
+    pub replica: usize,
+}
+
+// Independent copy of the system with its own potentials and propagator.
+pub(crate) struct Replica {
+    pub system: System,
+    pub potentials: Potentials,
+    propagator: Box<dyn Propagator>,
+}
+
+impl Replica {
+    pub fn new(
+        system: System,
+        mut potentials: Potentials,
+        mut propagator: Box<dyn Propagator>,
+    ) -> Result<Replica, VelvetError> {
+        let mut system = system;
+        potentials.setup(&system);
+        propagator.setup(&mut system, &potentials)?;
+        Ok(Replica {
+            system,
+            potentials,
+            propagator,
+        })
+    }
+
+    pub fn advance(&mut self, steps: usize) {
+        for i in 0..steps {
+            self.propagator.propagate(&mut self.system, &self.potentials);
+            self.potentials.update(&self.system, i);
+        }
+    }
+}
+
+/// Parallel replica dynamics driver.
+///
+/// Copies of the system are dephased with random velocities and then run simultaneously. The
+/// time simulated by every replica is accumulated until any of them leaves the current state,
+/// which for transitions obeying first order kinetics yields the correct escape time
+/// distribution with a speedup proportional to the number of replicas. After a transition the
+/// replica which escaped runs alone for a correlation period, becomes the new reference state,
+/// and the cycle repeats.
+///
+/// Replicas are advanced in blocks of `interval` steps and checked for transitions at the end of
+/// each block. Each replica owns potentials and a propagator constructed by the closures passed
+/// to [`run`](ParallelReplica::run) and replicas run on separate threads with the `rayon` feature.
+///
+/// # References
+///
+/// [1] Voter, Arthur F. "Parallel replica method for dynamics of infrequent events." Physical Review B 57.22 (1998): R13985.
+pub struct ParallelReplica<D: TransitionDetector, V: VelocityDistribution> {
+    replicas: usize,
+    timestep: Float,
+    detector: D,
+    distribution: V,
+    dephasing: usize,
+    correlation: usize,
+    interval: usize,
+    time: Float,
+    transitions: Vec<Transition>,
+}
+
+impl<D: TransitionDetector, V: VelocityDistribution> ParallelReplica<D, V> {
+    /// Returns a new [`ParallelReplica`] driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `replicas` - Number of replicas run simultaneously.
+    /// * `timestep` - Timestep of the propagators in femtoseconds.
+    /// * `detector` - Criterion which detects transitions out of the current state.
+    /// * `distribution` - Velocity distribution which decorrelates the replicas.
+    pub fn new(replicas: usize, timestep: Float, detector: D, distribution: V) -> ParallelReplica<D, V> {
+        ParallelReplica {
+            replicas,
+            timestep,
+            detector,
+            distribution,
+            dephasing: 100,
+            correlation: 100,
+            interval: 10,
+            time: 0.0,
+            transitions: Vec::new(),
+        }
+    }
+
+    /// Sets the number of steps each replica runs to lose memory of the others.
+    pub fn dephasing(mut self, steps: usize) -> ParallelReplica<D, V> {
+        self.dephasing = steps;
+        self
+    }
+
+    /// Sets the number of steps run after a transition before the new state is accepted.
+    pub fn correlation(mut self, steps: usize) -> ParallelReplica<D, V> {
+        self.correlation = steps;
+        self
+    }
+
+    /// Sets the number of steps between checks for transitions.
+    pub fn interval(mut self, steps: usize) -> ParallelReplica<D, V> {
+        self.interval = steps;
+        self
+    }
+
+    /// Returns the accumulated simulation time in femtoseconds.
+    pub fn time(&self) -> Float {
+        self.time
+    }
+
+    /// Returns every transition observed so far.
+    pub fn transitions(&self) -> &[Transition] {
+        &self.transitions
+    }
+
+    /// Runs parallel replica dynamics for `blocks` blocks of `interval` steps.
+    ///
+    /// On return `system` holds the state reached after the last accepted transition.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - Starting configuration.
+    /// * `potentials` - Constructor for the potentials of each replica.
+    /// * `propagator` - Constructor for the propagator of each replica.
+    /// * `blocks` - Number of parallel blocks to run.
+    pub fn run<F, G, P>(
+        &mut self,
+        system: &mut System,
+        potentials: F,
+        propagator: G,
+        blocks: usize,
+    ) -> Result<(), VelvetError>
+    where
+        F: Fn() -> Potentials,
+        G: Fn() -> P,
+        P: Propagator + 'static,
+    {
+        let new_replica = |system: System| Replica::new(system, potentials(), Box::new(propagator()));
+        let mut remaining = blocks;
+        while remaining > 0 {
+            // dephase each replica, discarding those which leave the state
+            let mut replicas: Vec<Replica> = (0..self.replicas)
+                .map(|_| {
+                    for _ in 0..MAX_ATTEMPTS {
+                        let mut copy = system.clone();
+                        self.distribution.apply(&mut copy);
+                        let mut replica = new_replica(copy)?;
+                        replica.advance(self.dephasing);
+                        if !self.detector.detect(system, &replica.system) {
+                            return Ok(replica);
+                        }
+                    }
+                    panic!("Unable to dephase a replica within {} attempts.", MAX_ATTEMPTS)
+                })
+                .collect::<Result<_, VelvetError>>()?;
+
+            // run every replica until one of them escapes
+            let mut escaped = None;
+            while remaining > 0 && escaped.is_none() {
+                let interval = self.interval;
+                #[cfg(not(feature = "rayon"))]
+                replicas.iter_mut().for_each(|replica| replica.advance(interval));
+                #[cfg(feature = "rayon")]
+                replicas.par_iter_mut().for_each(|replica| replica.advance(interval));
+                remaining -= 1;
+                self.time += (self.replicas * interval) as Float * self.timestep;
+                escaped = replicas
+                    .iter()
+                    .position(|replica| self.detector.detect(system, &replica.system));
+            }
+
+            // the escaped replica relaxes into its new state alone
+            if let Some(index) = escaped {
+                self.transitions.push(Transition {
+                    time: self.time,
+                    replica: index,
+                });
+                let mut replica = replicas.swap_remove(index);
+                replica.advance(self.correlation);
+                self.time += self.correlation as Float * self.timestep;
+                *system = replica.system;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DisplacementDetector, ParallelReplica, TransitionDetector};
+    use crate::integrators::VelocityVerlet;
+    use crate::internal::Float;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::propagators::MolecularDynamics;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::thermostats::NullThermostat;
+    use crate::velocity_distributions::Boltzmann;
+    use nalgebra::Vector3;
+
+    // dilute argon gas on a simple cubic grid
+    fn gas() -> (System, impl Fn() -> Potentials) {
+        let argon = Species::new(39.948, 0.0);
+        let mut positions = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    positions.push(Vector3::new(i as Float, j as Float, k as Float) * 6.0);
+                }
+            }
+        }
+        let system = System {
+            size: positions.len(),
+            cell: Cell::cubic(18.0),
+            species: vec![argon; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        };
+        let potentials = move || {
+            PotentialsBuilder::new()
+                .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+                .build()
+        };
+        (system, potentials)
+    }
+
+    fn md() -> MolecularDynamics {
+        MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat)
+    }
+
+    #[test]
+    fn accumulates_replica_time() {
+        let (mut system, potentials) = gas();
+        let reference = system.clone();
+        let mut prd = ParallelReplica::new(4, 1.0, DisplacementDetector::new(100.0), Boltzmann::new(300.0))
+            .dephasing(5)
+            .interval(10);
+        prd.run(&mut system, potentials, md, 3).unwrap();
+
+        // without transitions every replica contributes every block
+        assert!(prd.transitions().is_empty());
+        assert!((prd.time() - 120.0).abs() < 1e-3);
+        assert_eq!(system.positions, reference.positions);
+    }
+
+    #[test]
+    fn detects_transitions() {
+        let (mut system, potentials) = gas();
+        let reference = system.clone();
+        // gas atoms move more than an angstrom within a few blocks
+        let mut prd = ParallelReplica::new(3, 1.0, DisplacementDetector::new(1.0), Boltzmann::new(50.0))
+            .dephasing(1)
+            .correlation(5)
+            .interval(5);
+        prd.run(&mut system, potentials, md, 20).unwrap();
+
+        let transitions = prd.transitions();
+        assert!(!transitions.is_empty());
+        assert!(transitions.windows(2).all(|pair| pair[0].time < pair[1].time));
+        assert!(transitions.iter().all(|transition| transition.replica < 3));
+        assert!(prd.time() >= transitions.last().unwrap().time + 5.0);
+        assert!(DisplacementDetector::new(0.5).detect(&reference, &system));
+    }
+}
@@ -0,0 +1,200 @@
+//! Adaptive resolution coupling between atomistic and coarse-grained regions.
+
+use std::fmt;
+
+use nalgebra::Vector3;
+
+use crate::internal::consts::PI;
+use crate::internal::Float;
+use crate::system::System;
+
+/// Level of detail at which a pair potential acts under adaptive resolution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Resolution {
+    /// Acts between atoms inside the atomistic region and fades out across the hybrid region.
+    #[default]
+    Atomistic,
+    /// Acts between atoms outside the atomistic region and fades out across the hybrid region.
+    CoarseGrained,
+}
+
+impl Resolution {
+    /// Returns the weight of a pair potential at this resolution between atoms with resolution weights `wi` and `wj`.
+    pub fn weight(&self, wi: Float, wj: Float) -> Float {
+        match self {
+            Resolution::Atomistic => wi * wj,
+            Resolution::CoarseGrained => 1.0 - wi * wj,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Geometry {
+    Slab(usize),
+    Sphere,
+}
+
+/// Region of the simulation cell treated atomistically while the rest is coarse-grained.
+///
+/// Each atom has a resolution weight of one within `atomistic` of the center, zero beyond the
+/// hybrid region of width `hybrid` which surrounds it, and decays smoothly as the squared cosine
+/// of the distance in between. The weights are evaluated from the current positions on every
+/// force calculation, so atoms switch resolution on the fly as they cross the hybrid region.
+///
+/// Each pair potential is marked with a [`Resolution`], and the force between two atoms with
+/// weights `wi` and `wj` interpolates between the atomistic and coarse-grained potentials as
+/// `wi * wj * F_at + (1 - wi * wj) * F_cg`. The energy is interpolated the same way. Force
+/// interpolation does not conserve energy within the hybrid region, so a thermostat is required.
+/// Coulombic interactions and biases act at full strength everywhere.
+///
+/// # References
+///
+/// [1] Praprotnik, Matej, Luigi Delle Site, and Kurt Kremer. "Adaptive resolution molecular-dynamics simulation: Changing the degrees of freedom on the fly." The Journal of chemical physics 123.22 (2005): 224106.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// // atomistic slab of 10 angstroms with hybrid regions of 5 angstroms on either side
+/// let region = AdaptiveResolution::slab(Vector3::new(20.0, 0.0, 0.0), 0, 5.0, 5.0);
+/// let potentials = PotentialsBuilder::new()
+///     .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+///     .coarse_pair(LennardJones::new(0.1, 3.8), (argon, argon), 8.5, 1.0)
+///     .adaptive_resolution(region)
+///     .build();
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveResolution {
+    center: Vector3<Float>,
+    atomistic: Float,
+    hybrid: Float,
+    geometry: Geometry,
+}
+
+impl AdaptiveResolution {
+    /// Returns an atomistic slab normal to a Cartesian axis.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - Point on the midplane of the slab.
+    /// * `axis` - Index of the Cartesian axis normal to the slab.
+    /// * `atomistic` - Half width of the atomistic region.
+    /// * `hybrid` - Width of the hybrid region on either side of the atomistic region.
+    pub fn slab(center: Vector3<Float>, axis: usize, atomistic: Float, hybrid: Float) -> AdaptiveResolution {
+        assert!(axis < 3, "The slab axis must be 0, 1, or 2");
+        AdaptiveResolution {
+            center,
+            atomistic,
+            hybrid,
+            geometry: Geometry::Slab(axis),
+        }
+    }
+
+    /// Returns an atomistic sphere.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - Center of the sphere.
+    /// * `atomistic` - Radius of the atomistic region.
+    /// * `hybrid` - Width of the hybrid shell around the atomistic region.
+    pub fn sphere(center: Vector3<Float>, atomistic: Float, hybrid: Float) -> AdaptiveResolution {
+        AdaptiveResolution {
+            center,
+            atomistic,
+            hybrid,
+            geometry: Geometry::Sphere,
+        }
+    }
+
+    /// Returns the resolution weight at `position`, one in the atomistic region and zero in the coarse-grained region.
+    pub fn weight(&self, system: &System, position: &Vector3<Float>) -> Float {
+        let mut offset = position - self.center;
+        system.cell.vector_image(&mut offset);
+        let distance = match self.geometry {
+            Geometry::Slab(axis) => offset[axis].abs(),
+            Geometry::Sphere => offset.norm(),
+        };
+        if distance <= self.atomistic {
+            1.0
+        } else if distance >= self.atomistic + self.hybrid {
+            0.0
+        } else {
+            Float::cos(0.5 * PI * (distance - self.atomistic) / self.hybrid).powi(2)
+        }
+    }
+
+    /// Returns the resolution weight of each atom in the system.
+    pub fn weights(&self, system: &System) -> Vec<Float> {
+        system
+            .positions
+            .iter()
+            .map(|position| self.weight(system, position))
+            .collect()
+    }
+}
+
+impl fmt::Display for AdaptiveResolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let center = format!("[{}, {}, {}]", self.center[0], self.center[1], self.center[2]);
+        match self.geometry {
+            Geometry::Slab(axis) => write!(
+                f,
+                "slab normal to axis {} through {} with atomistic half width {} and hybrid width {}",
+                axis, center, self.atomistic, self.hybrid
+            ),
+            Geometry::Sphere => write!(
+                f,
+                "sphere centered at {} with atomistic radius {} and hybrid width {}",
+                center, self.atomistic, self.hybrid
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveResolution, Resolution};
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn weights() {
+        let positions = vec![
+            Vector3::new(11.0, 3.0, 8.0),
+            Vector3::new(17.5, 0.0, 0.0),
+            Vector3::new(2.5, 0.0, 0.0),
+            Vector3::new(30.0, 0.0, 0.0),
+        ];
+        let system = System {
+            size: 4,
+            cell: Cell::cubic(40.0),
+            species: vec![Species::new(1.0, 0.0); 4],
+            positions,
+            velocities: vec![Vector3::zeros(); 4],
+            topology: Topology::default(),
+        };
+        let slab = AdaptiveResolution::slab(Vector3::new(10.0, 0.0, 0.0), 0, 5.0, 5.0);
+        let weights = slab.weights(&system);
+        assert_eq!(weights[0], 1.0);
+        assert_relative_eq!(weights[1], 0.5, epsilon = 1e-5);
+        assert_relative_eq!(weights[2], 0.5, epsilon = 1e-5);
+        assert_eq!(weights[3], 0.0);
+
+        // the closest periodic image of the center lies across the boundary
+        let sphere = AdaptiveResolution::sphere(Vector3::new(38.0, 0.0, 0.0), 5.0, 2.0);
+        let weights = sphere.weights(&system);
+        assert_eq!(weights[2], 1.0);
+        assert_eq!(weights[0], 0.0);
+
+        assert_eq!(Resolution::Atomistic.weight(0.5, 0.5), 0.25);
+        assert_eq!(Resolution::CoarseGrained.weight(0.5, 0.5), 0.75);
+        assert_eq!(Resolution::CoarseGrained.weight(1.0, 1.0), 0.0);
+    }
+}
@@ -1,25 +1,74 @@
 //! Potentials which describe Coulombic electrostatic interactions.
 
 #[cfg(feature = "f64")]
-use libm::erfc as erfc;
+use libm::erfc;
 
 #[cfg(not(feature = "f64"))]
 use libm::erfcf as erfc;
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use std::sync::{Arc, Mutex};
+
+use nalgebra::{Matrix3, Vector3};
+
 use crate::internal::consts::COULOMB;
-use crate::internal::Float;
 use crate::internal::consts::FRAC_2_SQRT_PI;
-use crate::potentials::types::{DampedShiftedForce, StandardCoulombic};
+use crate::internal::Float;
+use crate::potentials::ewald;
+use crate::potentials::types::{
+    DampedShiftedForce, Ewald, ParticleMeshEwald, StandardCoulombic, Tabulated,
+};
 use crate::potentials::Potential;
-use crate::selection::{setup_pairs_with_charge, update_pairs_by_cutoff_radius, Selection};
+use crate::selection::{setup_pairs_with_charge, update_excluded_image_pairs, update_image_pairs, update_pairs_by_cutoff_radius, ImagePair, Selection};
 use crate::system::System;
 
+/// Energy, forces, and virial of the interactions which are not captured by the pairwise terms.
+#[derive(Clone, Debug)]
+pub struct LongRange {
+    /// Long range energy of the whole system.
+    pub energy: Float,
+    /// Long range force acting on each atom.
+    pub forces: Option<Vec<Vector3<Float>>>,
+    /// Long range virial tensor.
+    pub virial: Option<Matrix3<Float>>,
+}
+
 /// Shared behavior for Coulombic potentials.
 pub trait CoulombPotential: Potential {
     /// Returns the potential energy of an atom in a pair with charges `qi` and `qj` seperated by a distance `r`.
     fn energy(&self, qi: Float, qj: Float, r: Float) -> Float;
     /// Returns the magnitude of the force acting on an atom separated from another by a distance `r` with charges `qi` and `qj`.
     fn force(&self, qi: Float, qj: Float, r: Float) -> Float;
+    /// Returns the energy of the whole system which is not captured by the pairwise terms.
+    fn long_range_energy(&self, _: &System) -> Float {
+        0.0
+    }
+    /// Returns the force acting on each atom which is not captured by the pairwise terms.
+    fn long_range_forces(&self, _: &System) -> Option<Vec<Vector3<Float>>> {
+        None
+    }
+    /// Returns the virial tensor which is not captured by the pairwise terms.
+    fn long_range_virial(&self, _: &System) -> Option<Matrix3<Float>> {
+        None
+    }
+    /// Returns the long range energy, forces, and virial from a single evaluation.
+    fn long_range(&self, system: &System) -> LongRange {
+        LongRange {
+            energy: self.long_range_energy(system),
+            forces: self.long_range_forces(system),
+            virial: self.long_range_virial(system),
+        }
+    }
+    /// Returns the energy which removes the long range interaction of an excluded pair with charges `qi` and `qj` separated by a distance `r`.
+    fn excluded_energy(&self, _qi: Float, _qj: Float, _r: Float) -> Float {
+        0.0
+    }
+    /// Returns the magnitude of the force which removes the long range interaction of an excluded pair.
+    fn excluded_force(&self, _qi: Float, _qj: Float, _r: Float) -> Float {
+        0.0
+    }
 }
 
 impl CoulombPotential for DampedShiftedForce {
@@ -52,6 +101,84 @@ impl CoulombPotential for DampedShiftedForce {
     }
 }
 
+impl CoulombPotential for Ewald {
+    fn energy(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::real_space_energy(self.alpha, qi, qj, r)
+    }
+
+    fn force(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::real_space_force(self.alpha, qi, qj, r)
+    }
+
+    fn long_range_energy(&self, system: &System) -> Float {
+        self.long_range(system).energy
+    }
+
+    fn long_range_forces(&self, system: &System) -> Option<Vec<Vector3<Float>>> {
+        self.long_range(system).forces
+    }
+
+    fn long_range_virial(&self, system: &System) -> Option<Matrix3<Float>> {
+        self.long_range(system).virial
+    }
+
+    fn long_range(&self, system: &System) -> LongRange {
+        let reciprocal = ewald::ewald_reciprocal(self.alpha, self.kmax, system);
+        LongRange {
+            energy: reciprocal.energy + ewald::self_energy(self.alpha, system),
+            forces: Some(reciprocal.forces),
+            virial: Some(reciprocal.virial),
+        }
+    }
+
+    fn excluded_energy(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::excluded_energy(self.alpha, qi, qj, r)
+    }
+
+    fn excluded_force(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::excluded_force(self.alpha, qi, qj, r)
+    }
+}
+
+impl CoulombPotential for ParticleMeshEwald {
+    fn energy(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::real_space_energy(self.alpha, qi, qj, r)
+    }
+
+    fn force(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::real_space_force(self.alpha, qi, qj, r)
+    }
+
+    fn long_range_energy(&self, system: &System) -> Float {
+        self.long_range(system).energy
+    }
+
+    fn long_range_forces(&self, system: &System) -> Option<Vec<Vector3<Float>>> {
+        self.long_range(system).forces
+    }
+
+    fn long_range_virial(&self, system: &System) -> Option<Matrix3<Float>> {
+        self.long_range(system).virial
+    }
+
+    fn long_range(&self, system: &System) -> LongRange {
+        let reciprocal = ewald::pme_reciprocal(self.alpha, self.spacing, self.order, system);
+        LongRange {
+            energy: reciprocal.energy + ewald::self_energy(self.alpha, system),
+            forces: Some(reciprocal.forces),
+            virial: Some(reciprocal.virial),
+        }
+    }
+
+    fn excluded_energy(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::excluded_energy(self.alpha, qi, qj, r)
+    }
+
+    fn excluded_force(&self, qi: Float, qj: Float, r: Float) -> Float {
+        ewald::excluded_force(self.alpha, qi, qj, r)
+    }
+}
+
 impl CoulombPotential for StandardCoulombic {
     fn energy(&self, qi: Float, qj: Float, r: Float) -> Float {
         (COULOMB * qi * qj) / (self.dielectric * r)
@@ -62,17 +189,87 @@ impl CoulombPotential for StandardCoulombic {
     }
 }
 
+impl<T: CoulombPotential> CoulombPotential for Tabulated<T> {
+    fn energy(&self, qi: Float, qj: Float, r: Float) -> Float {
+        if r < self.inner || r > self.cutoff {
+            return self.potential.energy(qi, qj, r);
+        }
+        qi * qj * self.energy.eval(r)
+    }
+
+    fn force(&self, qi: Float, qj: Float, r: Float) -> Float {
+        if r < self.inner || r > self.cutoff {
+            return self.potential.force(qi, qj, r);
+        }
+        qi * qj * self.force.eval(r)
+    }
+
+    fn long_range_energy(&self, system: &System) -> Float {
+        self.potential.long_range_energy(system)
+    }
+
+    fn long_range_forces(&self, system: &System) -> Option<Vec<Vector3<Float>>> {
+        self.potential.long_range_forces(system)
+    }
+
+    fn long_range_virial(&self, system: &System) -> Option<Matrix3<Float>> {
+        self.potential.long_range_virial(system)
+    }
+
+    fn long_range(&self, system: &System) -> LongRange {
+        self.potential.long_range(system)
+    }
+
+    fn excluded_energy(&self, qi: Float, qj: Float, r: Float) -> Float {
+        self.potential.excluded_energy(qi, qj, r)
+    }
+
+    fn excluded_force(&self, qi: Float, qj: Float, r: Float) -> Float {
+        self.potential.excluded_force(qi, qj, r)
+    }
+}
+
 type CoulombSetupFn = fn(&System, ()) -> Vec<[usize; 2]>;
 
 type CoulombUpdateFn = fn(&System, &[[usize; 2]], Float) -> Vec<[usize; 2]>;
 
 type CoulombSelection = Selection<CoulombSetupFn, (), CoulombUpdateFn, Float, 2>;
 
+// Long range terms along with the configuration they were evaluated for.
+struct LongRangeCache {
+    positions: Vec<Vector3<Float>>,
+    matrix: Matrix3<Float>,
+    charges: Vec<Float>,
+    long_range: Arc<LongRange>,
+}
+
+impl LongRangeCache {
+    fn new(system: &System, long_range: Arc<LongRange>) -> LongRangeCache {
+        LongRangeCache {
+            positions: system.positions.clone(),
+            matrix: system.cell.matrix(),
+            charges: system.charges(),
+            long_range,
+        }
+    }
+
+    fn matches(&self, system: &System) -> bool {
+        self.charges.len() == system.size
+            && self.positions == system.positions
+            && self.matrix == system.cell.matrix()
+            && (0..system.size).all(|i| self.charges[i] == system.charge(i))
+    }
+}
+
 pub(crate) struct CoulombPotentialMeta {
     pub potential: Box<dyn CoulombPotential>,
     pub cutoff: Float,
     pub thickness: Float,
     pub selection: CoulombSelection,
+    pub images: Vec<ImagePair>,
+    selves: Vec<usize>,
+    excluded: Vec<[usize; 2]>,
+    cache: Mutex<Option<LongRangeCache>>,
 }
 
 impl CoulombPotentialMeta {
@@ -89,22 +286,282 @@ impl CoulombPotentialMeta {
             selection,
             cutoff,
             thickness,
+            images: Vec::new(),
+            selves: Vec::new(),
+            excluded: Vec::new(),
+            cache: Mutex::new(None),
         }
     }
 
-    pub fn setup(&mut self, system: &System) {
-        self.selection.setup(system, ())
+    pub fn setup(&mut self, system: &System, excluded: &[[usize; 2]]) {
+        self.selection.setup(system, ());
+        self.excluded = self.selection.exclude(excluded);
+        // charged atoms may interact with their own periodic images
+        self.selves = (0..system.size)
+            .filter(|&i| system.charge(i).abs() > Float::EPSILON)
+            .collect();
+    }
+
+    // Returns the current number of neighbor pairs and the bytes allocated for the neighbor list.
+    pub fn neighbor_list(&self) -> (usize, usize) {
+        let pairs = self.selection.indices().count() + self.images.len();
+        let bytes = self.selection.allocated_bytes() + self.images.capacity() * std::mem::size_of::<ImagePair>();
+        (pairs, bytes)
     }
 
     pub fn update(&mut self, system: &System) {
-        self.selection.update(system, self.cutoff + self.thickness)
+        let cutoff = self.cutoff + self.thickness;
+        if cutoff > system.cell.inscribed_radius() {
+            // the minimum image convention misses pairs in cells smaller than the cutoff
+            self.selection.clear();
+            self.images = update_image_pairs(system, self.selection.possible_indices(), &self.selves, cutoff);
+            self.images.extend(update_excluded_image_pairs(system, &self.excluded, cutoff));
+        } else {
+            self.images.clear();
+            self.selection.update(system, cutoff)
+        }
+    }
+
+    /// Returns the long range energy including the correction for every excluded pair.
+    pub fn long_range_energy(&self, system: &System) -> Float {
+        let correction: Float = self
+            .excluded_pairs(system)
+            .map(|(qi, qj, separation)| self.potential.excluded_energy(qi, qj, separation.norm()))
+            .sum();
+        self.long_range(system).energy + correction
+    }
+
+    /// Returns the long range forces including the correction for every excluded pair.
+    pub fn long_range_forces(&self, system: &System) -> Option<Vec<Vector3<Float>>> {
+        let long_range = self.long_range(system).forces.clone();
+        if self.excluded.is_empty() {
+            return long_range;
+        }
+        let mut forces = long_range.unwrap_or_else(|| vec![Vector3::zeros(); system.size]);
+        for (&[i, j], (qi, qj, separation)) in self.excluded.iter().zip(self.excluded_pairs(system)) {
+            let r = separation.norm();
+            let force = self.potential.excluded_force(qi, qj, r) * separation / r;
+            forces[i] += force;
+            forces[j] -= force;
+        }
+        Some(forces)
+    }
+
+    /// Returns the long range virial including the correction for every excluded pair.
+    pub fn long_range_virial(&self, system: &System) -> Option<Matrix3<Float>> {
+        let long_range = self.long_range(system).virial;
+        if self.excluded.is_empty() {
+            return long_range;
+        }
+        let correction = self
+            .excluded_pairs(system)
+            .fold(Matrix3::zeros(), |virial, (qi, qj, separation)| {
+                let r = separation.norm();
+                virial - self.potential.excluded_force(qi, qj, r) / r * separation * separation.transpose()
+            });
+        Some(long_range.unwrap_or_else(Matrix3::zeros) + correction)
+    }
+
+    // Returns the long range terms of the current configuration which are only evaluated once per configuration.
+    fn long_range(&self, system: &System) -> Arc<LongRange> {
+        let mut cache = self.cache.lock().unwrap();
+        match &*cache {
+            Some(cached) if cached.matches(system) => cached.long_range.clone(),
+            _ => {
+                let long_range = Arc::new(self.potential.long_range(system));
+                *cache = Some(LongRangeCache::new(system, long_range.clone()));
+                long_range
+            }
+        }
+    }
+
+    // Returns the charges and nearest image separation vector of each excluded pair.
+    fn excluded_pairs<'a>(&'a self, system: &'a System) -> impl Iterator<Item = (Float, Float, Vector3<Float>)> + 'a {
+        self.excluded.iter().map(move |&[i, j]| {
+            let mut separation = system.positions[j] - system.positions[i];
+            system.cell.vector_image(&mut separation);
+            (system.charge(i), system.charge(j), separation)
+        })
+    }
+
+    /// Returns the indices and separation vector of each pair in the neighbor list.
+    pub fn pairs<'a>(&'a self, system: &'a System) -> impl Iterator<Item = (usize, usize, Vector3<Float>)> + 'a {
+        let nearest = self.selection.indices().map(move |&[i, j]| {
+            let mut separation = system.positions[j] - system.positions[i];
+            system.cell.vector_image(&mut separation);
+            (i, j, separation)
+        });
+        let images = self
+            .images
+            .iter()
+            .map(move |image| (image.i, image.j, image.separation(system)));
+        nearest.chain(images)
+    }
+
+    /// Returns a parallel iterator over the indices and separation vector of each pair in the neighbor list.
+    #[cfg(feature = "rayon")]
+    pub fn par_pairs<'a>(&'a self, system: &'a System) -> impl ParallelIterator<Item = (usize, usize, Vector3<Float>)> + 'a {
+        let nearest = self.selection.par_indices().map(move |&[i, j]| {
+            let mut separation = system.positions[j] - system.positions[i];
+            system.cell.vector_image(&mut separation);
+            (i, j, separation)
+        });
+        let images = self
+            .images
+            .par_iter()
+            .map(move |image| (image.i, image.j, image.separation(system)));
+        nearest.chain(images)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{CoulombPotential, StandardCoulombic};
+    use super::{CoulombPotential, CoulombPotentialMeta, Ewald, ParticleMeshEwald, StandardCoulombic, Tabulated};
+    use std::sync::Arc;
+    use crate::internal::consts::COULOMB;
+    use crate::internal::Float;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
     use approx::*;
+    use nalgebra::Vector3;
+
+    // conventional rock salt cell with 4 formula units
+    fn sodium_chloride() -> System {
+        let a0 = 5.64;
+        let sodium = Species::new(Element::Na.mass(), 1.0);
+        let chlorine = Species::new(Element::Cl.mass(), -1.0);
+        let sites = [
+            [0.0, 0.0, 0.0],
+            [0.0, 0.5, 0.5],
+            [0.5, 0.0, 0.5],
+            [0.5, 0.5, 0.0],
+        ];
+        let mut species = Vec::new();
+        let mut positions = Vec::new();
+        for site in sites.iter() {
+            species.push(sodium);
+            positions.push(Vector3::new(site[0], site[1], site[2]) * a0);
+            species.push(chlorine);
+            positions.push(Vector3::new(site[0] + 0.5, site[1], site[2]) * a0);
+        }
+        System {
+            size: 8,
+            cell: Cell::cubic(a0),
+            species,
+            positions,
+            velocities: vec![Vector3::zeros(); 8],
+            topology: Topology::default(),
+        }
+    }
+
+    // pairwise real space energy under the minimum image convention
+    fn real_space_energy<T: CoulombPotential>(potential: &T, system: &System, cutoff: Float) -> Float {
+        let mut energy = 0.0;
+        for i in 0..system.size {
+            for j in (i + 1)..system.size {
+                let r = system.cell.distance(&system.positions[i], &system.positions[j]);
+                if r < cutoff {
+                    let qi = system.charge(i);
+                    let qj = system.charge(j);
+                    energy += potential.energy(qi, qj, r);
+                }
+            }
+        }
+        energy
+    }
+
+    #[test]
+    fn ewald_madelung() {
+        let system = sodium_chloride();
+        let ewald = Ewald::new(1.2, 10);
+        let energy = real_space_energy(&ewald, &system, 2.8) + ewald.long_range_energy(&system);
+        let madelung = -4.0 * COULOMB * 1.747565 / 2.82;
+        assert_relative_eq!(energy, madelung, max_relative = 1e-3);
+    }
+
+    #[test]
+    fn particle_mesh_ewald() {
+        let mut system = sodium_chloride();
+        system.positions[0] += Vector3::new(0.2, -0.1, 0.15);
+        system.positions[3] += Vector3::new(-0.1, 0.25, 0.05);
+        let ewald = Ewald::new(1.2, 10);
+        let pme = ParticleMeshEwald::new(1.2, 0.25, 6);
+
+        assert_relative_eq!(
+            ewald.long_range_energy(&system),
+            pme.long_range_energy(&system),
+            max_relative = 1e-3
+        );
+
+        let ewald_forces = ewald.long_range_forces(&system).unwrap();
+        let pme_forces = pme.long_range_forces(&system).unwrap();
+        for (a, b) in ewald_forces.iter().zip(pme_forces.iter()) {
+            assert_relative_eq!((a - b).norm(), 0.0, epsilon = 1e-1);
+        }
+        // the perturbed atoms feel a restoring force
+        assert!(ewald_forces[0].norm() > 1.0);
+    }
+
+    #[test]
+    fn ewald_virial() {
+        // the trace of the virial is the response of the energy to a uniform dilation
+        let ewald = Ewald::new(1.2, 10);
+        let dilated_energy = |strain: Float| {
+            let mut system = sodium_chloride();
+            system.positions[0] += Vector3::new(0.2, -0.1, 0.15);
+            system.cell = Cell::cubic(5.64 * (1.0 + strain));
+            for pos in system.positions.iter_mut() {
+                *pos *= 1.0 + strain;
+            }
+            ewald.long_range_energy(&system)
+        };
+        let mut system = sodium_chloride();
+        system.positions[0] += Vector3::new(0.2, -0.1, 0.15);
+        let virial = ewald.long_range_virial(&system).unwrap();
+        let strain = 1e-3;
+        let derivative = (dilated_energy(strain) - dilated_energy(-strain)) / (2.0 * strain);
+        assert_relative_eq!(virial.trace(), -derivative, max_relative = 1e-2);
+    }
+
+    #[test]
+    fn cached_long_range() {
+        let ewald = Ewald::new(1.2, 10);
+        let mut meta = CoulombPotentialMeta::new(ewald, 5.0, 1.0);
+        let mut system = sodium_chloride();
+        meta.setup(&system, &[]);
+        let first = meta.long_range(&system);
+        assert!(Arc::ptr_eq(&first, &meta.long_range(&system)));
+        assert_relative_eq!(meta.long_range_energy(&system), ewald.long_range_energy(&system));
+
+        // moving an atom invalidates the cached terms
+        system.positions[0] += Vector3::new(0.2, -0.1, 0.15);
+        let moved = meta.long_range(&system);
+        assert!(!Arc::ptr_eq(&first, &moved));
+        assert_relative_eq!(moved.energy, ewald.long_range_energy(&system));
+    }
+
+    #[test]
+    fn tabulated_ewald() {
+        let ewald = Ewald::new(1.2, 10);
+        let coarse = Tabulated::new(ewald, 1.0, 10.0, 1e-1);
+        let tabulated = Tabulated::new(ewald, 1.0, 10.0, 1e-3);
+        assert!(tabulated.samples() > coarse.samples());
+        for i in 0..=900 {
+            let r = 1.0 + i as Float / 100.0;
+            assert_relative_eq!(tabulated.energy(-1.0, 1.0, r), ewald.energy(-1.0, 1.0, r), epsilon = 1e-3);
+            assert_relative_eq!(tabulated.force(2.0, 1.0, r), ewald.force(2.0, 1.0, r), epsilon = 2e-3);
+        }
+        // separations outside of the table fall back to the exact potential
+        assert_eq!(tabulated.energy(1.0, 1.0, 0.5), ewald.energy(1.0, 1.0, 0.5));
+
+        let system = sodium_chloride();
+        let exact = real_space_energy(&ewald, &system, 2.8) + ewald.long_range_energy(&system);
+        let approx = real_space_energy(&tabulated, &system, 2.8) + tabulated.long_range_energy(&system);
+        assert_relative_eq!(exact, approx, max_relative = 1e-4);
+    }
 
     #[test]
     fn standard_coulombic() {
@@ -0,0 +1,308 @@
+//! Cutoff radii suggested from the structure of a configuration.
+
+use crate::internal::consts::PI;
+use crate::internal::Float;
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Cutoff radius suggested for the interactions between a pair of species.
+#[derive(Clone, Copy, Debug)]
+pub struct SuggestedCutoff {
+    /// Pair of species the cutoff applies to.
+    pub species: (Species, Species),
+    /// Suggested cutoff radius in angstroms.
+    pub cutoff: Float,
+    /// Number of coordination shells enclosed by the cutoff.
+    pub shells: usize,
+}
+
+/// Suggests cutoff radii from the radial distribution functions of a configuration.
+///
+/// A smoothed radial distribution function is computed for every pair of species present in
+/// the system and the cutoff is placed at the minimum which follows the requested number of
+/// coordination shells, so that whole shells of neighbors are either included or excluded.
+/// Peaks and minima are located with a hysteresis of `prominence` in the value of the radial
+/// distribution function, which ignores the small fluctuations of a single thermal snapshot.
+///
+/// If fewer shells than requested fit within the range of the analysis, the cutoff is placed
+/// after the last complete shell, or at the range itself if not even one shell was found, and
+/// the number of enclosed shells is reported with the suggestion.
+///
+/// # Examples
+///
+/// ```
+/// use nalgebra::Vector3;
+/// use velvet_core::prelude::*;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let system = System {
+///     size: 2,
+///     cell: Cell::cubic(20.0),
+///     species: vec![argon; 2],
+///     positions: vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(3.8, 0.0, 0.0)],
+///     velocities: vec![Vector3::zeros(); 2],
+///     topology: Topology::default(),
+/// };
+/// let suggestions = CutoffAnalysis::new(8.0).shells(1).suggest(&system);
+/// assert_eq!(suggestions.len(), 1);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CutoffAnalysis {
+    range: Float,
+    bin_width: Float,
+    smoothing: Float,
+    prominence: Float,
+    shells: usize,
+}
+
+impl CutoffAnalysis {
+    /// Returns a new [`CutoffAnalysis`].
+    ///
+    /// # Arguments
+    ///
+    /// * `range` - Largest pair distance considered, which should not exceed half the width of the cell.
+    pub fn new(range: Float) -> CutoffAnalysis {
+        CutoffAnalysis {
+            range,
+            bin_width: 0.02,
+            smoothing: 0.1,
+            prominence: 0.1,
+            shells: 2,
+        }
+    }
+
+    /// Sets the width of each histogram bin in angstroms.
+    pub fn bin_width(mut self, bin_width: Float) -> CutoffAnalysis {
+        self.bin_width = bin_width;
+        self
+    }
+
+    /// Sets the standard deviation of the Gaussian which broadens each pair distance in angstroms.
+    pub fn smoothing(mut self, smoothing: Float) -> CutoffAnalysis {
+        self.smoothing = smoothing;
+        self
+    }
+
+    /// Sets the change in the radial distribution function which separates a peak from a minimum.
+    pub fn prominence(mut self, prominence: Float) -> CutoffAnalysis {
+        self.prominence = prominence;
+        self
+    }
+
+    /// Sets the number of coordination shells enclosed by each cutoff.
+    pub fn shells(mut self, shells: usize) -> CutoffAnalysis {
+        self.shells = shells;
+        self
+    }
+
+    /// Returns a suggested cutoff for every pair of species in `system`.
+    ///
+    /// Pairs are ordered by the first appearance of each species in the system.
+    pub fn suggest(&self, system: &System) -> Vec<SuggestedCutoff> {
+        let mut unique: Vec<Species> = Vec::new();
+        for species in &system.species {
+            if !unique.contains(species) {
+                unique.push(*species);
+            }
+        }
+        let mut suggestions = Vec::new();
+        for (a, first) in unique.iter().enumerate() {
+            for second in unique.iter().skip(a) {
+                let rdf = self.rdf(system, (*first, *second));
+                let minima = self.minima(&rdf);
+                let shells = minima.len().min(self.shells);
+                let cutoff = match shells {
+                    0 => self.range,
+                    n => (minima[n - 1] as Float + 0.5) * self.bin_width,
+                };
+                suggestions.push(SuggestedCutoff {
+                    species: (*first, *second),
+                    cutoff,
+                    shells,
+                });
+            }
+        }
+        suggestions
+    }
+
+    // Returns the Gaussian smoothed radial distribution function between two species.
+    fn rdf(&self, system: &System, species: (Species, Species)) -> Vec<Float> {
+        let bins = (self.range / self.bin_width).ceil() as usize;
+        let spread = (4.0 * self.smoothing / self.bin_width).ceil() as isize;
+        let norm = 1.0 / (self.smoothing * (2.0 * PI).sqrt());
+        let mut histogram = vec![0.0; bins];
+        let matches = |i: usize, j: usize| {
+            (system.species[i] == species.0 && system.species[j] == species.1)
+                || (system.species[i] == species.1 && system.species[j] == species.0)
+        };
+        for i in 0..system.size {
+            for j in (i + 1)..system.size {
+                if !matches(i, j) {
+                    continue;
+                }
+                let r = system.cell.distance(&system.positions[i], &system.positions[j]);
+                let center = (r / self.bin_width) as isize;
+                for bin in (center - spread).max(0)..(center + spread + 1).min(bins as isize) {
+                    let x = (bin as Float + 0.5) * self.bin_width - r;
+                    let weight = norm * Float::exp(-0.5 * (x / self.smoothing).powi(2));
+                    histogram[bin as usize] += weight * self.bin_width;
+                }
+            }
+        }
+
+        // normalize by the number of pairs expected in each shell of an ideal gas
+        let count = |s: Species| system.species.iter().filter(|&&x| x == s).count() as Float;
+        let pairs = if species.0 == species.1 {
+            let n = count(species.0);
+            n * (n - 1.0) / 2.0
+        } else {
+            count(species.0) * count(species.1)
+        };
+        let density = pairs / system.cell.volume();
+        histogram
+            .iter()
+            .enumerate()
+            .map(|(bin, value)| {
+                let r = (bin as Float + 0.5) * self.bin_width;
+                let shell = 4.0 * PI * r * r * self.bin_width;
+                if density > 0.0 {
+                    value / (shell * density)
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    // Returns the bins of the minima which follow each peak of `rdf`.
+    fn minima(&self, rdf: &[Float]) -> Vec<usize> {
+        let mut minima = Vec::new();
+        let mut rising = true;
+        // extremum of the current search and the first and last bins within the flat region around it
+        let mut extremum = 0.0;
+        let (mut start, mut end) = (0, 0);
+        for (bin, &value) in rdf.iter().enumerate() {
+            if rising {
+                if value > extremum {
+                    extremum = value;
+                } else if value < extremum - self.prominence {
+                    // a peak was passed, search for the following minimum
+                    rising = false;
+                    extremum = value;
+                    start = bin;
+                    end = bin;
+                }
+            } else if value < extremum - Float::EPSILON {
+                extremum = value;
+                start = bin;
+                end = bin;
+            } else if value <= extremum + Float::EPSILON {
+                end = bin;
+            } else if value > extremum + self.prominence {
+                // a minimum was passed, search for the following peak
+                minima.push((start + end) / 2);
+                rising = true;
+                extremum = value;
+            }
+        }
+        minima
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CutoffAnalysis;
+    use crate::internal::Float;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // cubic crystal of `cells` conventional cells with the given basis of fractional sites
+    fn crystal(a: Float, cells: usize, basis: &[([Float; 3], Species)]) -> System {
+        let mut species = Vec::new();
+        let mut positions = Vec::new();
+        for i in 0..cells {
+            for j in 0..cells {
+                for k in 0..cells {
+                    for (site, s) in basis.iter() {
+                        let frac = Vector3::new(site[0] + i as Float, site[1] + j as Float, site[2] + k as Float);
+                        positions.push(frac * a);
+                        species.push(*s);
+                    }
+                }
+            }
+        }
+        System {
+            size: positions.len(),
+            cell: Cell::cubic(a * cells as Float),
+            species,
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn fcc_shells() {
+        let argon = Species::new(39.948, 0.0);
+        let basis = [
+            ([0.0, 0.0, 0.0], argon),
+            ([0.5, 0.5, 0.0], argon),
+            ([0.5, 0.0, 0.5], argon),
+            ([0.0, 0.5, 0.5], argon),
+        ];
+        let mut system = crystal(5.26, 3, &basis);
+
+        // shells at 3.72, 5.26, and 6.44 angstroms
+        let first = CutoffAnalysis::new(7.5).shells(1).suggest(&system);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].shells, 1);
+        assert!(first[0].cutoff > 4.2 && first[0].cutoff < 4.8);
+        let second = CutoffAnalysis::new(7.5).suggest(&system);
+        assert_eq!(second[0].shells, 2);
+        assert!(second[0].cutoff > 5.6 && second[0].cutoff < 6.1);
+
+        // thermal noise does not introduce spurious shells
+        let mut rng = StdRng::seed_from_u64(3);
+        for pos in system.positions.iter_mut() {
+            *pos += Vector3::new(rng.gen_range(-0.15, 0.15), rng.gen_range(-0.15, 0.15), rng.gen_range(-0.15, 0.15));
+        }
+        let noisy = CutoffAnalysis::new(7.5).suggest(&system);
+        assert_eq!(noisy[0].shells, 2);
+        assert!(noisy[0].cutoff > 5.5 && noisy[0].cutoff < 6.2);
+
+        // more shells than fit within the range
+        let short = CutoffAnalysis::new(5.0).shells(3).suggest(&system);
+        assert_eq!(short[0].shells, 1);
+        assert!(short[0].cutoff > 4.0 && short[0].cutoff < 5.0);
+    }
+
+    #[test]
+    fn rock_salt_pairs() {
+        let sodium = Species::new(22.99, 1.0);
+        let chlorine = Species::new(35.45, -1.0);
+        let mut basis = Vec::new();
+        for site in [[0.0, 0.0, 0.0], [0.0, 0.5, 0.5], [0.5, 0.0, 0.5], [0.5, 0.5, 0.0]].iter() {
+            basis.push((*site, sodium));
+            basis.push(([site[0] + 0.5, site[1], site[2]], chlorine));
+        }
+        let system = crystal(5.64, 2, &basis);
+        let suggestions = CutoffAnalysis::new(5.6).shells(1).suggest(&system);
+        assert_eq!(suggestions.len(), 3);
+        for suggestion in suggestions.iter() {
+            assert_eq!(suggestion.shells, 1);
+            let (a, b) = suggestion.species;
+            if a == b {
+                // like ions at 3.99 and 5.64 angstroms
+                assert!(suggestion.cutoff > 4.5 && suggestion.cutoff < 5.2);
+            } else {
+                // unlike ions at 2.82 and 4.88 angstroms
+                assert!(suggestion.cutoff > 3.5 && suggestion.cutoff < 4.2);
+            }
+        }
+    }
+}
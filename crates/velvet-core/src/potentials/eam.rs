@@ -0,0 +1,441 @@
+//! Embedded atom method many-body potential for metals.
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+use crate::potentials::tables::LookupTable;
+use crate::potentials::Potential;
+use crate::selection::{setup_pairs_among_species, update_image_pairs, update_pairs_by_cutoff_radius, ImagePair, Selection};
+use crate::system::species::Species;
+use crate::system::System;
+
+// Uniformly spaced samples of a function starting at zero along with their finite difference derivatives.
+#[derive(Clone, Debug)]
+struct Sampled {
+    end: Float,
+    values: LookupTable,
+    derivatives: LookupTable,
+}
+
+impl Sampled {
+    fn new(step: Float, values: Vec<Float>) -> Sampled {
+        assert!(values.len() >= 2, "A sampled function requires at least two samples");
+        let last = values.len() - 1;
+        let derivatives = (0..=last)
+            .map(|i| {
+                let (a, b) = (i.saturating_sub(1), (i + 1).min(last));
+                (values[b] - values[a]) / ((b - a) as Float * step)
+            })
+            .collect();
+        Sampled {
+            end: step * last as Float,
+            values: LookupTable::from_values(0.0, step, values),
+            derivatives: LookupTable::from_values(0.0, step, derivatives),
+        }
+    }
+
+    // Returns the value and derivative at `x`, continued linearly beyond either end of the samples.
+    fn eval(&self, x: Float) -> (Float, Float) {
+        let clamped = x.max(0.0).min(self.end);
+        let derivative = self.derivatives.eval(clamped);
+        (self.values.eval(clamped) + derivative * (x - clamped), derivative)
+    }
+
+    // Returns the value and derivative at the separation `r`, which vanish beyond the last sample.
+    fn radial(&self, r: Float) -> (Float, Float) {
+        if r > self.end {
+            (0.0, 0.0)
+        } else {
+            self.eval(r)
+        }
+    }
+}
+
+/// [Embedded atom method](https://lammps.sandia.gov/doc/pair_eam.html#description) potential for metals.
+///
+/// The energy of each atom is the embedding energy `F(rho)` of the electron density `rho`
+/// contributed by its neighbors plus half of a pair energy `phi(r)` with each neighbor. Every
+/// function is sampled at uniformly spaced points starting from zero and interpolated linearly.
+/// The embedding energy is continued linearly beyond its samples while the electron density and
+/// pair energy vanish beyond theirs. Any function which is not set evaluates to zero.
+///
+/// Energies are in kcal/mol and distances in angstroms. Tabulated `setfl` and `funcfl` files,
+/// whose energies are in eV, are read by the `velvet-external-data` crate.
+///
+/// # References
+///
+/// [1] Daw, Murray S., and Michael I. Baskes. "Embedded-atom method: Derivation and application to impurities, surfaces, and other defects in metals." Physical Review B 29.12 (1984): 6443.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let copper = Species::from_element(Element::Cu);
+/// let model = EmbeddedAtom::new(vec![copper], 4.5)
+///     .embedding(copper, 1.0, vec![0.0, -1.0, -1.41, -1.73, -2.0])
+///     .density(copper, 1.5, vec![1.0, 0.22, 0.05, 0.01])
+///     .pair((copper, copper), 1.5, vec![20.0, 2.7, 0.37, 0.05]);
+/// let potentials = PotentialsBuilder::new()
+///     .embedded_atom(model, 1.0)
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct EmbeddedAtom {
+    species: Vec<Species>,
+    cutoff: Float,
+    embedding: Vec<Option<Sampled>>,
+    density: Vec<Option<Sampled>>,
+    pair: Vec<Option<Sampled>>,
+}
+
+impl EmbeddedAtom {
+    /// Returns a new [`EmbeddedAtom`] potential between `species` without any functions set.
+    ///
+    /// # Arguments
+    ///
+    /// * `species` - Species described by the model.
+    /// * `cutoff` - Cutoff radius of the electron density and pair energy.
+    pub fn new(species: Vec<Species>, cutoff: Float) -> EmbeddedAtom {
+        let n = species.len();
+        EmbeddedAtom {
+            species,
+            cutoff,
+            embedding: vec![None; n],
+            density: vec![None; n],
+            pair: vec![None; n * n],
+        }
+    }
+
+    /// Sets the embedding energy of `species` sampled at electron densities spaced by `step`.
+    pub fn embedding(mut self, species: Species, step: Float, values: Vec<Float>) -> EmbeddedAtom {
+        let kind = self.kind_of(species);
+        self.embedding[kind] = Some(Sampled::new(step, values));
+        self
+    }
+
+    /// Sets the electron density contributed by an atom of `species` sampled at separations spaced by `step`.
+    pub fn density(mut self, species: Species, step: Float, values: Vec<Float>) -> EmbeddedAtom {
+        let kind = self.kind_of(species);
+        self.density[kind] = Some(Sampled::new(step, values));
+        self
+    }
+
+    /// Sets the pair energy between `species` sampled at separations spaced by `step`.
+    pub fn pair(mut self, species: (Species, Species), step: Float, values: Vec<Float>) -> EmbeddedAtom {
+        let (a, b) = (self.kind_of(species.0), self.kind_of(species.1));
+        let n = self.species.len();
+        let sampled = Sampled::new(step, values);
+        self.pair[a * n + b] = Some(sampled.clone());
+        self.pair[b * n + a] = Some(sampled);
+        self
+    }
+
+    /// Returns the species described by the model.
+    pub fn species(&self) -> &[Species] {
+        &self.species
+    }
+
+    /// Returns the cutoff radius of the electron density and pair energy.
+    pub fn cutoff(&self) -> Float {
+        self.cutoff
+    }
+
+    // Returns the index of `species` within the model.
+    fn kind_of(&self, species: Species) -> usize {
+        self.species
+            .iter()
+            .position(|&s| s == species)
+            .expect("The species is not described by the embedded atom model")
+    }
+
+    // Returns the embedding energy of an atom of the given kind and its derivative.
+    fn embedding_energy(&self, kind: usize, rho: Float) -> (Float, Float) {
+        match &self.embedding[kind] {
+            Some(sampled) => sampled.eval(rho),
+            None => (0.0, 0.0),
+        }
+    }
+
+    // Returns the electron density contributed by an atom of the given kind and its derivative.
+    fn electron_density(&self, kind: usize, r: Float) -> (Float, Float) {
+        match &self.density[kind] {
+            Some(sampled) => sampled.radial(r),
+            None => (0.0, 0.0),
+        }
+    }
+
+    // Returns the pair energy between atoms of the given kinds and its derivative.
+    fn pair_energy(&self, a: usize, b: usize, r: Float) -> (Float, Float) {
+        match &self.pair[a * self.species.len() + b] {
+            Some(sampled) => sampled.radial(r),
+            None => (0.0, 0.0),
+        }
+    }
+}
+
+impl Potential for EmbeddedAtom {
+    fn describe(&self) -> String {
+        format!("EmbeddedAtom {{ species: {}, cutoff: {:?} }}", self.species.len(), self.cutoff)
+    }
+}
+
+type EmbeddedAtomSetupFn = fn(&System, Vec<Species>) -> Vec<[usize; 2]>;
+
+type EmbeddedAtomUpdateFn = fn(&System, &[[usize; 2]], Float) -> Vec<[usize; 2]>;
+
+type EmbeddedAtomSelection = Selection<EmbeddedAtomSetupFn, Vec<Species>, EmbeddedAtomUpdateFn, Float, 2>;
+
+pub(crate) struct EmbeddedAtomMeta {
+    pub potential: EmbeddedAtom,
+    pub thickness: Float,
+    selection: EmbeddedAtomSelection,
+    images: Vec<ImagePair>,
+    selves: Vec<usize>,
+    kinds: Vec<Option<usize>>,
+}
+
+impl EmbeddedAtomMeta {
+    pub fn new(potential: EmbeddedAtom, thickness: Float) -> EmbeddedAtomMeta {
+        let selection = Selection::new(
+            setup_pairs_among_species as EmbeddedAtomSetupFn,
+            update_pairs_by_cutoff_radius as EmbeddedAtomUpdateFn,
+        );
+        EmbeddedAtomMeta {
+            potential,
+            thickness,
+            selection,
+            images: Vec::new(),
+            selves: Vec::new(),
+            kinds: Vec::new(),
+        }
+    }
+
+    pub fn setup(&mut self, system: &System) {
+        self.selection.setup(system, self.potential.species.clone());
+        self.kinds = system
+            .species
+            .iter()
+            .map(|species| self.potential.species.iter().position(|s| s == species))
+            .collect();
+        // atoms which may interact with their own periodic images
+        self.selves = (0..system.size).filter(|&i| self.kinds[i].is_some()).collect();
+    }
+
+    // Returns the current number of neighbor pairs and the bytes allocated for the neighbor list.
+    pub fn neighbor_list(&self) -> (usize, usize) {
+        let pairs = self.selection.indices().count() + self.images.len();
+        let bytes = self.selection.allocated_bytes() + self.images.capacity() * std::mem::size_of::<ImagePair>();
+        (pairs, bytes)
+    }
+
+    pub fn update(&mut self, system: &System) {
+        let cutoff = self.potential.cutoff + self.thickness;
+        if cutoff > system.cell.inscribed_radius() {
+            // the minimum image convention misses pairs in cells smaller than the cutoff
+            self.selection.clear();
+            self.images = update_image_pairs(system, self.selection.possible_indices(), &self.selves, cutoff);
+        } else {
+            self.images.clear();
+            self.selection.update(system, cutoff)
+        }
+    }
+
+    // Returns the indices, kinds, and separation vector of each pair in the neighbor list within the cutoff.
+    fn pairs<'a>(&'a self, system: &'a System) -> impl Iterator<Item = (usize, usize, usize, usize, Vector3<Float>)> + 'a {
+        let nearest = self.selection.indices().map(move |&[i, j]| {
+            let mut separation = system.positions[j] - system.positions[i];
+            system.cell.vector_image(&mut separation);
+            (i, j, separation)
+        });
+        let images = self
+            .images
+            .iter()
+            .map(move |image| (image.i, image.j, image.separation(system)));
+        nearest
+            .chain(images)
+            .filter(move |(_, _, separation)| separation.norm() < self.potential.cutoff)
+            .filter_map(move |(i, j, separation)| Some((i, j, self.kinds[i]?, self.kinds[j]?, separation)))
+    }
+
+    /// Returns the electron density at each atom.
+    pub fn densities(&self, system: &System) -> Vec<Float> {
+        let mut densities = vec![0.0; system.size];
+        for (i, j, a, b, separation) in self.pairs(system) {
+            let r = separation.norm();
+            densities[i] += self.potential.electron_density(b, r).0;
+            densities[j] += self.potential.electron_density(a, r).0;
+        }
+        densities
+    }
+
+    /// Returns the embedding energy of each atom plus half of the pair energy with each of its neighbors.
+    pub fn energies(&self, system: &System) -> Vec<Float> {
+        let mut energies: Vec<Float> = self
+            .densities(system)
+            .iter()
+            .zip(self.kinds.iter())
+            .map(|(&rho, kind)| match kind {
+                Some(kind) => self.potential.embedding_energy(*kind, rho).0,
+                None => 0.0,
+            })
+            .collect();
+        for (i, j, a, b, separation) in self.pairs(system) {
+            let energy = self.potential.pair_energy(a, b, separation.norm()).0 / 2.0;
+            energies[i] += energy;
+            energies[j] += energy;
+        }
+        energies
+    }
+
+    /// Returns the indices, separation vector, and derivative of the energy with respect to the separation of each pair.
+    ///
+    /// The embedding energy makes the force between a pair depend on the electron density at
+    /// both atoms, which requires a density pass over every pair before the forces are known.
+    pub fn pair_forces(&self, system: &System) -> Vec<(usize, usize, Vector3<Float>, Float)> {
+        let slopes: Vec<Float> = self
+            .densities(system)
+            .iter()
+            .zip(self.kinds.iter())
+            .map(|(&rho, kind)| match kind {
+                Some(kind) => self.potential.embedding_energy(*kind, rho).1,
+                None => 0.0,
+            })
+            .collect();
+        self.pairs(system)
+            .map(|(i, j, a, b, separation)| {
+                let r = separation.norm();
+                let force = slopes[i] * self.potential.electron_density(b, r).1
+                    + slopes[j] * self.potential.electron_density(a, r).1
+                    + self.potential.pair_energy(a, b, r).1;
+                (i, j, separation, force)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmbeddedAtom, EmbeddedAtomMeta};
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::forces::Forces;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    // Embedding energy -sqrt(rho), exponential density, and a repulsive pair energy.
+    fn model(species: Species) -> EmbeddedAtom {
+        let step = 0.001;
+        let embedding = (0..20000).map(|i| -Float::sqrt(i as Float * step)).collect();
+        let density = (0..5000).map(|i| Float::exp(-(i as Float * step))).collect();
+        let pair = (0..5000).map(|i| Float::exp(-2.0 * i as Float * step)).collect();
+        EmbeddedAtom::new(vec![species], 4.5)
+            .embedding(species, step, embedding)
+            .density(species, step, density)
+            .pair((species, species), step, pair)
+    }
+
+    fn dimer(species: Species, r: Float) -> System {
+        System {
+            size: 3,
+            cell: Cell::cubic(30.0),
+            species: vec![species, species, Species::new(1.0, 0.0)],
+            positions: vec![
+                Vector3::new(5.0, 5.0, 5.0),
+                Vector3::new(5.0 + r, 5.0, 5.0),
+                Vector3::new(5.0, 6.0, 5.0),
+            ],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn dimer_energy_and_force() {
+        let copper = Species::from_element(Element::Cu);
+        let system = dimer(copper, 2.0);
+        let mut meta = EmbeddedAtomMeta::new(model(copper), 1.0);
+        meta.setup(&system);
+        meta.update(&system);
+
+        // each atom embedded in the density of the other and the atom outside the model ignored
+        let rho = Float::exp(-2.0);
+        assert_eq!(meta.densities(&system)[2], 0.0);
+        assert_relative_eq!(meta.densities(&system)[0], rho, epsilon = 1e-4);
+        let energy: Float = meta.energies(&system).iter().sum();
+        assert_relative_eq!(energy, -2.0 * rho.sqrt() + Float::exp(-4.0), epsilon = 1e-3);
+
+        // derivative of the energy with respect to the separation
+        let forces = meta.pair_forces(&system);
+        assert_eq!(forces.len(), 1);
+        let exact = 2.0 * (-0.5 / rho.sqrt()) * -rho - 2.0 * Float::exp(-4.0);
+        assert_relative_eq!(forces[0].3, exact, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn periodic_images() {
+        // a simple cubic lattice with one atom per cell interacts only with its own images
+        let copper = Species::from_element(Element::Cu);
+        let system = System {
+            size: 1,
+            cell: Cell::cubic(2.5),
+            species: vec![copper],
+            positions: vec![Vector3::zeros()],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let mut meta = EmbeddedAtomMeta::new(model(copper), 0.0);
+        meta.setup(&system);
+        meta.update(&system);
+        let expected = 6.0 * Float::exp(-2.5)
+            + 12.0 * Float::exp(-2.5 * Float::sqrt(2.0))
+            + 8.0 * Float::exp(-2.5 * Float::sqrt(3.0));
+        assert_relative_eq!(meta.densities(&system)[0], expected, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn forces_match_energy_gradient() {
+        // perturbed face centered cubic lattice in a cell smaller than twice the cutoff
+        let copper = Species::from_element(Element::Cu);
+        let a = 3.6;
+        let basis = [[0.0, 0.0, 0.0], [0.5, 0.5, 0.0], [0.5, 0.0, 0.5], [0.0, 0.5, 0.5]];
+        let mut positions = Vec::new();
+        for (n, offset) in (0..8).flat_map(|n| basis.iter().map(move |b| (n, b))) {
+            let cell = Vector3::new((n % 2) as Float, ((n / 2) % 2) as Float, (n / 4) as Float);
+            let jitter = 0.05 * Vector3::new((n as Float).sin(), (n as Float).cos(), 0.5);
+            positions.push((cell + Vector3::new(offset[0], offset[1], offset[2])) * a + jitter);
+        }
+        let size = positions.len();
+        let mut system = System {
+            size,
+            cell: Cell::cubic(2.0 * a),
+            species: vec![copper; size],
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology::default(),
+        };
+        let mut potentials = PotentialsBuilder::new().embedded_atom(model(copper), 0.5).build();
+        potentials.setup(&system);
+
+        let forces = Forces.calculate(&system, &potentials);
+        let h = 0.01;
+        for (k, &force) in forces[0].iter().enumerate() {
+            system.positions[0][k] += h;
+            let forward = PotentialEnergy.calculate(&system, &potentials);
+            system.positions[0][k] -= 2.0 * h;
+            let backward = PotentialEnergy.calculate(&system, &potentials);
+            system.positions[0][k] += h;
+            let gradient = (forward - backward) / (2.0 * h);
+            assert_relative_eq!(force, -gradient, epsilon = 1e-2);
+        }
+        // forces sum to zero
+        assert!(forces.iter().sum::<Vector3<Float>>().norm() < 1e-3);
+    }
+}
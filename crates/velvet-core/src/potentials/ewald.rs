@@ -0,0 +1,401 @@
+//! Real and reciprocal space terms shared by the Ewald family of Coulombic potentials.
+
+#[cfg(feature = "f64")]
+use libm::{erf, erfc};
+
+#[cfg(not(feature = "f64"))]
+use libm::{erfcf as erfc, erff as erf};
+
+use nalgebra::{Complex, Matrix3, Vector3};
+
+use crate::internal::consts::{COULOMB, FRAC_2_SQRT_PI, PI};
+use crate::internal::Float;
+use crate::system::System;
+
+/// Returns the real space energy of a pair with charges `qi` and `qj` separated by a distance `r`.
+pub(crate) fn real_space_energy(alpha: Float, qi: Float, qj: Float, r: Float) -> Float {
+    COULOMB * qi * qj * erfc(alpha * r) / r
+}
+
+/// Returns the derivative of the real space energy of a pair with respect to their separation.
+pub(crate) fn real_space_force(alpha: Float, qi: Float, qj: Float, r: Float) -> Float {
+    let term_a = erfc(alpha * r) / r.powi(2);
+    let term_b = FRAC_2_SQRT_PI * alpha * Float::exp(-(alpha * r).powi(2)) / r;
+    -COULOMB * qi * qj * (term_a + term_b)
+}
+
+/// Returns the energy which removes the reciprocal space interaction of an excluded pair.
+pub(crate) fn excluded_energy(alpha: Float, qi: Float, qj: Float, r: Float) -> Float {
+    -COULOMB * qi * qj * erf(alpha * r) / r
+}
+
+/// Returns the derivative of the excluded pair energy with respect to the separation.
+pub(crate) fn excluded_force(alpha: Float, qi: Float, qj: Float, r: Float) -> Float {
+    let term_a = erf(alpha * r) / r.powi(2);
+    let term_b = FRAC_2_SQRT_PI * alpha * Float::exp(-(alpha * r).powi(2)) / r;
+    -COULOMB * qi * qj * (term_b - term_a)
+}
+
+/// Returns the self interaction energy of each charge with its own screening distribution.
+pub(crate) fn self_energy(alpha: Float, system: &System) -> Float {
+    let sum_q2: Float = system.charges().iter().map(|q| q.powi(2)).sum();
+    -COULOMB * alpha * (FRAC_2_SQRT_PI / 2.0) * sum_q2
+}
+
+/// Energy, forces, and virial contributed by the reciprocal space sum.
+pub(crate) struct Reciprocal {
+    pub energy: Float,
+    pub forces: Vec<Vector3<Float>>,
+    pub virial: Matrix3<Float>,
+}
+
+// Returns the virial contribution of a single reciprocal vector `m` with energy `energy`.
+fn reciprocal_virial(alpha: Float, energy: Float, m: &Vector3<Float>) -> Matrix3<Float> {
+    let m2 = m.norm_squared();
+    let factor = 2.0 * (1.0 + PI.powi(2) * m2 / alpha.powi(2)) / m2;
+    energy * (Matrix3::identity() - factor * m * m.transpose())
+}
+
+/// Returns the reciprocal space terms evaluated as a direct sum over reciprocal vectors.
+pub(crate) fn ewald_reciprocal(alpha: Float, kmax: usize, system: &System) -> Reciprocal {
+    let inv = system.cell.inverse_matrix();
+    let prefactor = COULOMB / (2.0 * PI * system.cell.volume());
+    let fractional: Vec<Vector3<Float>> = system
+        .positions
+        .iter()
+        .map(|pos| system.cell.fractional(pos))
+        .collect();
+    let charges = system.charges();
+
+    let kmax = kmax as i64;
+    let mut energy = 0.0;
+    let mut virial = Matrix3::zeros();
+    let mut forces = vec![Vector3::zeros(); system.size];
+    let mut phases = vec![0.0; system.size];
+    for n1 in -kmax..=kmax {
+        for n2 in -kmax..=kmax {
+            for n3 in -kmax..=kmax {
+                if n1 == 0 && n2 == 0 && n3 == 0 {
+                    continue;
+                }
+                let n = Vector3::new(n1 as Float, n2 as Float, n3 as Float);
+                let m = inv.transpose() * n;
+                let m2 = m.norm_squared();
+                let g = Float::exp(-PI.powi(2) * m2 / alpha.powi(2)) / m2;
+
+                // structure factor
+                let (mut s_re, mut s_im) = (0.0, 0.0);
+                for ((phase, frac), q) in phases.iter_mut().zip(fractional.iter()).zip(charges.iter()) {
+                    *phase = 2.0 * PI * n.dot(frac);
+                    s_re += q * phase.cos();
+                    s_im += q * phase.sin();
+                }
+                let energy_m = prefactor * g * (s_re.powi(2) + s_im.powi(2));
+                energy += energy_m;
+                virial += reciprocal_virial(alpha, energy_m, &m);
+
+                for ((force, phase), q) in forces.iter_mut().zip(phases.iter()).zip(charges.iter()) {
+                    let im = s_re * phase.sin() - s_im * phase.cos();
+                    *force += 4.0 * PI * prefactor * g * q * im * m;
+                }
+            }
+        }
+    }
+    Reciprocal {
+        energy,
+        forces,
+        virial,
+    }
+}
+
+/// Returns the reciprocal space terms evaluated on a mesh with smooth particle-mesh Ewald.
+///
+/// # References
+///
+/// [1] Essmann, Ulrich, et al. "A smooth particle mesh Ewald method." The Journal of chemical physics 103.19 (1995): 8577-8593.
+pub(crate) fn pme_reciprocal(
+    alpha: Float,
+    spacing: Float,
+    order: usize,
+    system: &System,
+) -> Reciprocal {
+    let cell = &system.cell;
+    let inv = cell.inverse_matrix();
+    let prefactor = COULOMB / (2.0 * PI * cell.volume());
+    let dims = [
+        mesh_size(cell.a(), spacing, order),
+        mesh_size(cell.b(), spacing, order),
+        mesh_size(cell.c(), spacing, order),
+    ];
+    let index = |k: [usize; 3]| (k[0] * dims[1] + k[1]) * dims[2] + k[2];
+    let charges = system.charges();
+
+    // spread the charges onto the mesh
+    let stencils: Vec<Stencil> = system
+        .positions
+        .iter()
+        .map(|pos| {
+            let frac = cell.fractional(pos);
+            let mut base = [0; 3];
+            let mut weights: [Vec<Float>; 3] = Default::default();
+            let mut derivatives: [Vec<Float>; 3] = Default::default();
+            for d in 0..3 {
+                let u = (frac[d] - frac[d].floor()) * dims[d] as Float;
+                let (w, dw) = bspline(u - u.floor(), order);
+                base[d] = u.floor() as usize;
+                weights[d] = w;
+                derivatives[d] = dw;
+            }
+            Stencil {
+                base,
+                weights,
+                derivatives,
+            }
+        })
+        .collect();
+
+    let mut mesh = vec![Complex::new(0.0, 0.0); dims[0] * dims[1] * dims[2]];
+    for (stencil, &q) in stencils.iter().zip(charges.iter()) {
+        let (base, weights) = (&stencil.base, &stencil.weights);
+        for j0 in 0..order {
+            for j1 in 0..order {
+                for j2 in 0..order {
+                    let k = grid_point(base, [j0, j1, j2], dims);
+                    mesh[index(k)].re += q * weights[0][j0] * weights[1][j1] * weights[2][j2];
+                }
+            }
+        }
+    }
+
+    // convolve with the reciprocal space influence function
+    fft3(&mut mesh, dims, false);
+    let moduli = [
+        bspline_moduli(dims[0], order),
+        bspline_moduli(dims[1], order),
+        bspline_moduli(dims[2], order),
+    ];
+    let mut energy = 0.0;
+    let mut virial = Matrix3::zeros();
+    for k0 in 0..dims[0] {
+        for k1 in 0..dims[1] {
+            for k2 in 0..dims[2] {
+                let idx = index([k0, k1, k2]);
+                if idx == 0 {
+                    mesh[idx] = Complex::new(0.0, 0.0);
+                    continue;
+                }
+                let n = Vector3::new(
+                    wrapped_frequency(k0, dims[0]),
+                    wrapped_frequency(k1, dims[1]),
+                    wrapped_frequency(k2, dims[2]),
+                );
+                let m = inv.transpose() * n;
+                let m2 = m.norm_squared();
+                let g = moduli[0][k0] * moduli[1][k1] * moduli[2][k2]
+                    * Float::exp(-PI.powi(2) * m2 / alpha.powi(2))
+                    / m2;
+                let energy_m = prefactor * g * mesh[idx].norm_sqr();
+                energy += energy_m;
+                virial += reciprocal_virial(alpha, energy_m, &m);
+                mesh[idx] *= 2.0 * prefactor * g;
+            }
+        }
+    }
+    fft3(&mut mesh, dims, true);
+
+    // gather the forces from the mesh potential
+    let forces = stencils
+        .iter()
+        .zip(charges.iter())
+        .map(|(stencil, &q)| {
+            let (base, weights, derivatives) =
+                (&stencil.base, &stencil.weights, &stencil.derivatives);
+            let mut grad: Vector3<Float> = Vector3::zeros();
+            for j0 in 0..order {
+                for j1 in 0..order {
+                    for j2 in 0..order {
+                        let phi = mesh[index(grid_point(base, [j0, j1, j2], dims))].re;
+                        grad[0] += phi * derivatives[0][j0] * weights[1][j1] * weights[2][j2];
+                        grad[1] += phi * weights[0][j0] * derivatives[1][j1] * weights[2][j2];
+                        grad[2] += phi * weights[0][j0] * weights[1][j1] * derivatives[2][j2];
+                    }
+                }
+            }
+            (0..3).fold(Vector3::zeros(), |acc, d| {
+                let row: Vector3<Float> = inv.row(d).transpose();
+                acc - q * grad[d] * dims[d] as Float * row
+            })
+        })
+        .collect();
+    Reciprocal {
+        energy,
+        forces,
+        virial,
+    }
+}
+
+// B-spline weights of a single atom on the mesh.
+struct Stencil {
+    // mesh point which receives the first weight along each dimension
+    base: [usize; 3],
+    weights: [Vec<Float>; 3],
+    derivatives: [Vec<Float>; 3],
+}
+
+// Returns the number of mesh points along a lattice vector of the given length.
+fn mesh_size(length: Float, spacing: Float, order: usize) -> usize {
+    let size = Float::ceil(length / spacing) as usize;
+    size.max(2 * order).next_power_of_two()
+}
+
+// Returns the mesh point which receives the `j`th spline weight of an atom.
+fn grid_point(base: &[usize; 3], j: [usize; 3], dims: [usize; 3]) -> [usize; 3] {
+    let mut k = [0; 3];
+    for d in 0..3 {
+        k[d] = (base[d] + dims[d] - j[d]) % dims[d];
+    }
+    k
+}
+
+// Maps a mesh index onto a signed frequency.
+fn wrapped_frequency(k: usize, size: usize) -> Float {
+    if k <= size / 2 {
+        k as Float
+    } else {
+        k as Float - size as Float
+    }
+}
+
+// Returns the cardinal B-spline values `M_n(w + j)` and their derivatives for `j` in `0..n`.
+fn bspline(w: Float, order: usize) -> (Vec<Float>, Vec<Float>) {
+    let mut values = vec![0.0; order];
+    values[0] = w;
+    values[1] = 1.0 - w;
+    let mut derivatives = vec![0.0; order];
+    for k in 3..=order {
+        if k == order {
+            derivatives[0] = values[0];
+            for j in 1..order {
+                derivatives[j] = values[j] - values[j - 1];
+            }
+        }
+        let kf = k as Float;
+        for j in (0..k).rev() {
+            let jf = j as Float;
+            let current = if j < k - 1 { values[j] } else { 0.0 };
+            let previous = if j > 0 { values[j - 1] } else { 0.0 };
+            values[j] = ((w + jf) * current + (kf - w - jf) * previous) / (kf - 1.0);
+        }
+    }
+    if order == 2 {
+        derivatives[0] = 1.0;
+        derivatives[1] = -1.0;
+    }
+    (values, derivatives)
+}
+
+// Returns the squared moduli of the B-spline Euler exponential factors along one mesh dimension.
+fn bspline_moduli(size: usize, order: usize) -> Vec<Float> {
+    let (values, _) = bspline(0.0, order);
+    let mut moduli: Vec<Float> = (0..size)
+        .map(|m| {
+            let denominator = (0..order - 1).fold(Complex::new(0.0, 0.0), |acc, k| {
+                let arg = 2.0 * PI * (m * k) as Float / size as Float;
+                acc + Complex::new(arg.cos(), arg.sin()) * values[k + 1]
+            });
+            denominator.norm_sqr()
+        })
+        .collect();
+    // odd orders vanish at the Nyquist frequency so interpolate from the neighbors
+    for m in 0..size {
+        if moduli[m] < 1e-7 {
+            moduli[m] = 0.5 * (moduli[(m + size - 1) % size] + moduli[(m + 1) % size]);
+        }
+    }
+    moduli.iter().map(|x| 1.0 / x).collect()
+}
+
+// Unnormalized in-place radix-2 FFT along each dimension of a row-major mesh.
+fn fft3(mesh: &mut [Complex<Float>], dims: [usize; 3], inverse: bool) {
+    let strides = [dims[1] * dims[2], dims[2], 1];
+    let mut line = Vec::new();
+    for d in 0..3 {
+        for start in 0..mesh.len() {
+            // only begin a line from points whose index along `d` is zero
+            if !(start / strides[d]).is_multiple_of(dims[d]) {
+                continue;
+            }
+            line.clear();
+            line.extend((0..dims[d]).map(|k| mesh[start + k * strides[d]]));
+            fft(&mut line, inverse);
+            for (k, value) in line.iter().enumerate() {
+                mesh[start + k * strides[d]] = *value;
+            }
+        }
+    }
+}
+
+// Unnormalized in-place iterative radix-2 Cooley-Tukey FFT.
+fn fft(data: &mut [Complex<Float>], inverse: bool) {
+    let n = data.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut len = 2;
+    while len <= n {
+        let angle = sign * 2.0 * PI / len as Float;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let arg = angle * k as Float;
+                let twiddle = Complex::new(arg.cos(), arg.sin());
+                let a = data[start + k];
+                let b = data[start + k + len / 2] * twiddle;
+                data[start + k] = a + b;
+                data[start + k + len / 2] = a - b;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bspline, fft};
+    use crate::internal::Float;
+    use approx::*;
+    use nalgebra::Complex;
+
+    #[test]
+    fn bspline_partition_of_unity() {
+        for order in 2..8 {
+            let (values, derivatives) = bspline(0.3, order);
+            assert_relative_eq!(values.iter().sum::<Float>(), 1.0, epsilon = 1e-5);
+            assert_relative_eq!(derivatives.iter().sum::<Float>(), 0.0, epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn fft_round_trip() {
+        let original: Vec<Complex<Float>> = (0..8)
+            .map(|x| Complex::new(x as Float, (x * x) as Float))
+            .collect();
+        let mut data = original.clone();
+        fft(&mut data, false);
+        assert_relative_eq!(data[0].re, 28.0, epsilon = 1e-4);
+        fft(&mut data, true);
+        for (a, b) in data.iter().zip(original.iter()) {
+            assert_relative_eq!(a.re / 8.0, b.re, epsilon = 1e-4);
+            assert_relative_eq!(a.im / 8.0, b.im, epsilon = 1e-4);
+        }
+    }
+}
@@ -0,0 +1,746 @@
+//! Potentials evaluated outside of Velvet, such as machine-learned interatomic potentials, and
+//! fields which act on each atom independently.
+
+use std::fmt;
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::internal::Float;
+use crate::potentials::Potential;
+use crate::selection::{setup_pairs_among_species, update_image_pairs, update_pairs_by_cutoff_radius, ImagePair, Selection};
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Every pair of atoms within the cutoff radius of an [`ExternalPotential`].
+///
+/// Each pair appears once as the indices of its atoms and the vector from the first atom to
+/// the second. In cells smaller than twice the cutoff a pair may appear once for each periodic
+/// image of the second atom in range, and an atom may be paired with images of itself.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Neighborhood {
+    /// Cutoff radius of the neighborhood.
+    pub cutoff: Float,
+    /// Indices and separation vector of each pair.
+    pub pairs: Vec<(usize, usize, Vector3<Float>)>,
+}
+
+/// Energy and forces returned by an [`ExternalPotential`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExternalEvaluation {
+    /// Potential energy of the whole system.
+    pub energy: Float,
+    /// Force acting on each atom.
+    pub forces: Vec<Vector3<Float>>,
+    /// Virial tensor with the same sign convention as the [`Virial`](crate::properties::pressure::Virial) property, if known.
+    ///
+    /// The pressure omits the contribution of the potential when it is not given.
+    pub virial: Option<Matrix3<Float>>,
+    /// Uncertainty of the force on each atom, such as the spread of an [`ExternalEnsemble`], if known.
+    pub uncertainty: Option<Vec<Float>>,
+}
+
+/// Shared behavior for potentials which are evaluated for the whole system at once.
+///
+/// External potentials receive the positions of every atom along with the neighborhood of pairs
+/// within their cutoff radius and return the energy and forces, which lets machine-learned
+/// interatomic potentials or bindings to other codes drive any simulation. The potential is
+/// evaluated whenever the energy or forces are calculated, so the evaluation should be
+/// deterministic for a given configuration. Inference runtimes such as ONNX or TorchScript are
+/// not dependencies of this crate and are bound by implementing this trait or wrapping a model
+/// in an [`ExternalCallback`].
+pub trait ExternalPotential: Potential {
+    /// Returns the cutoff radius of the neighborhood passed to `evaluate`.
+    fn cutoff(&self) -> Float;
+
+    /// Returns the energy and forces of the system.
+    fn evaluate(&self, system: &System, neighborhood: &Neighborhood) -> ExternalEvaluation;
+}
+
+/// [`ExternalPotential`] evaluated by a user-supplied function.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // harmonic springs between neighbors standing in for a machine-learned model
+/// let model = ExternalCallback::new(3.0, |system: &System, neighborhood: &Neighborhood| {
+///     let mut evaluation = ExternalEvaluation {
+///         forces: vec![nalgebra::Vector3::zeros(); system.size],
+///         ..ExternalEvaluation::default()
+///     };
+///     for (i, j, separation) in &neighborhood.pairs {
+///         evaluation.energy += 0.5 * separation.norm_squared();
+///         evaluation.forces[*i] += separation;
+///         evaluation.forces[*j] -= separation;
+///     }
+///     evaluation
+/// });
+/// let potentials = PotentialsBuilder::new()
+///     .external(model, 1.0)
+///     .build();
+/// ```
+pub struct ExternalCallback<F> {
+    cutoff: Float,
+    callback: F,
+}
+
+impl<F> ExternalCallback<F>
+where
+    F: Fn(&System, &Neighborhood) -> ExternalEvaluation + Send + Sync,
+{
+    /// Returns a new [`ExternalCallback`] potential.
+    ///
+    /// # Arguments
+    ///
+    /// * `cutoff` - Cutoff radius of the neighborhood passed to the callback.
+    /// * `callback` - Function which returns the energy and forces of the system.
+    pub fn new(cutoff: Float, callback: F) -> ExternalCallback<F> {
+        ExternalCallback { cutoff, callback }
+    }
+}
+
+impl<F> fmt::Debug for ExternalCallback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExternalCallback").field("cutoff", &self.cutoff).finish()
+    }
+}
+
+impl<F> Potential for ExternalCallback<F>
+where
+    F: Fn(&System, &Neighborhood) -> ExternalEvaluation + Send + Sync,
+{
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl<F> ExternalPotential for ExternalCallback<F>
+where
+    F: Fn(&System, &Neighborhood) -> ExternalEvaluation + Send + Sync,
+{
+    fn cutoff(&self) -> Float {
+        self.cutoff
+    }
+
+    fn evaluate(&self, system: &System, neighborhood: &Neighborhood) -> ExternalEvaluation {
+        (self.callback)(system, neighborhood)
+    }
+}
+
+/// Ensemble of [`ExternalPotential`]s whose mean drives the simulation and whose spread
+/// estimates the uncertainty of the forces.
+///
+/// Each member, such as a machine-learned model trained from different initial weights or on a
+/// different split of the data, is evaluated with the pairs within its own cutoff radius. The
+/// energy, forces, and virial are the means over the members, with the virial omitted unless
+/// every member gives one. The uncertainty of the force on each atom is the standard deviation
+/// `sqrt(<|F_k - <F>|^2>)` of the members' forces, which is large where the members disagree,
+/// typically in configurations unlike their training data. It is reported by the
+/// [`ForceUncertainty`](crate::properties::forces::ForceUncertainty) and
+/// [`MaxForceUncertainty`](crate::properties::forces::MaxForceUncertainty) properties and acted
+/// upon by an [`UncertaintyMonitor`](crate::hooks::UncertaintyMonitor). Every evaluation costs
+/// one evaluation of each member.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // two models which disagree on the stiffness of their springs
+/// let ensemble = [1.0, 1.1].iter().fold(ExternalEnsemble::new(), |ensemble, &k| {
+///     ensemble.member(ExternalCallback::new(3.0, move |system: &System, neighborhood: &Neighborhood| {
+///         let mut evaluation = ExternalEvaluation {
+///             forces: vec![nalgebra::Vector3::zeros(); system.size],
+///             ..ExternalEvaluation::default()
+///         };
+///         for (i, j, separation) in &neighborhood.pairs {
+///             evaluation.energy += 0.5 * separation.norm_squared() * k;
+///             evaluation.forces[*i] += separation * k;
+///             evaluation.forces[*j] -= separation * k;
+///         }
+///         evaluation
+///     }))
+/// });
+/// let potentials = PotentialsBuilder::new().external(ensemble, 1.0).build();
+/// ```
+#[derive(Default)]
+pub struct ExternalEnsemble {
+    members: Vec<Box<dyn ExternalPotential>>,
+}
+
+impl ExternalEnsemble {
+    /// Returns a new [`ExternalEnsemble`] without any members.
+    pub fn new() -> ExternalEnsemble {
+        ExternalEnsemble::default()
+    }
+
+    /// Adds `potential` to the members of the ensemble.
+    pub fn member<T: ExternalPotential + 'static>(mut self, potential: T) -> ExternalEnsemble {
+        self.members.push(Box::new(potential));
+        self
+    }
+
+    /// Returns the number of members in the ensemble.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns true if the ensemble has no members.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+impl fmt::Debug for ExternalEnsemble {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let members: Vec<String> = self.members.iter().map(|member| member.describe()).collect();
+        f.debug_struct("ExternalEnsemble").field("members", &members).finish()
+    }
+}
+
+impl Potential for ExternalEnsemble {
+    fn describe(&self) -> String {
+        format!("ExternalEnsemble of {} members", self.members.len())
+    }
+}
+
+impl ExternalPotential for ExternalEnsemble {
+    fn cutoff(&self) -> Float {
+        self.members.iter().map(|member| member.cutoff()).fold(0.0, Float::max)
+    }
+
+    fn evaluate(&self, system: &System, neighborhood: &Neighborhood) -> ExternalEvaluation {
+        assert!(!self.members.is_empty(), "Expected at least one member in the ensemble");
+        let evaluations: Vec<ExternalEvaluation> = self
+            .members
+            .iter()
+            .map(|member| {
+                let cutoff = member.cutoff();
+                let pairs = neighborhood
+                    .pairs
+                    .iter()
+                    .filter(|(_, _, separation)| separation.norm() < cutoff)
+                    .cloned()
+                    .collect();
+                let evaluation = member.evaluate(system, &Neighborhood { cutoff, pairs });
+                assert_eq!(
+                    evaluation.forces.len(),
+                    system.size,
+                    "Expected an external force for each of the {} atoms",
+                    system.size
+                );
+                evaluation
+            })
+            .collect();
+        let n = evaluations.len() as Float;
+        let energy = evaluations.iter().map(|evaluation| evaluation.energy).sum::<Float>() / n;
+        let forces: Vec<Vector3<Float>> = (0..system.size)
+            .map(|i| evaluations.iter().map(|evaluation| evaluation.forces[i]).sum::<Vector3<Float>>() / n)
+            .collect();
+        let virial = evaluations
+            .iter()
+            .map(|evaluation| evaluation.virial)
+            .sum::<Option<Matrix3<Float>>>()
+            .map(|virial| virial / n);
+        let uncertainty = forces
+            .iter()
+            .enumerate()
+            .map(|(i, mean)| {
+                let spread: Float = evaluations
+                    .iter()
+                    .map(|evaluation| (evaluation.forces[i] - mean).norm_squared())
+                    .sum();
+                Float::sqrt(spread / n)
+            })
+            .collect();
+        ExternalEvaluation {
+            energy,
+            forces,
+            virial,
+            uncertainty: Some(uncertainty),
+        }
+    }
+}
+
+/// Shared behavior for fields which act on each atom independently of the others.
+///
+/// Any number of fields can act alongside the other potentials. Their forces and energies are
+/// included in the [`Forces`](crate::properties::forces::Forces) and
+/// [`PotentialEnergy`](crate::properties::energy::PotentialEnergy) of the system, but not in its
+/// virial, since a uniform field exerts no internal stress. The energy of a uniform field depends
+/// on the absolute positions of the atoms, so it jumps whenever an atom is wrapped back into a
+/// periodic cell and only its differences between steps without wrapping are meaningful.
+pub trait ExternalField: Potential {
+    /// Returns the energy of atom `i` in the field.
+    fn energy(&self, system: &System, i: usize) -> Float;
+
+    /// Returns the force the field exerts on atom `i`.
+    fn force(&self, system: &System, i: usize) -> Vector3<Float>;
+}
+
+/// Conversion from volts per angstrom to kilocalories per mole per angstrom per elementary charge.
+const VOLTS_PER_ANGSTROM: Float = 23.060_55;
+
+/// Uniform electric field which acts on the charge of each atom.
+///
+/// Exerts the force `q E` on every atom with charge `q` and adds the energy `-q E . r`.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // 0.1 V/A along z
+/// let field = ElectricField::volts_per_angstrom(nalgebra::Vector3::new(0.0, 0.0, 0.1));
+/// let potentials = PotentialsBuilder::new().field(field).build();
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ElectricField {
+    field: Vector3<Float>,
+}
+
+impl ElectricField {
+    /// Returns a new [`ElectricField`] in kilocalories per mole per angstrom per elementary charge.
+    pub fn new(field: Vector3<Float>) -> ElectricField {
+        ElectricField { field }
+    }
+
+    /// Returns a new [`ElectricField`] from a field strength in volts per angstrom.
+    pub fn volts_per_angstrom(field: Vector3<Float>) -> ElectricField {
+        ElectricField::new(field * VOLTS_PER_ANGSTROM)
+    }
+
+    /// Returns the field in kilocalories per mole per angstrom per elementary charge.
+    pub fn field(&self) -> Vector3<Float> {
+        self.field
+    }
+}
+
+impl Potential for ElectricField {
+    fn describe(&self) -> String {
+        format!("ElectricField({}, {}, {})", self.field.x, self.field.y, self.field.z)
+    }
+}
+
+impl ExternalField for ElectricField {
+    fn energy(&self, system: &System, i: usize) -> Float {
+        -system.charge(i) * self.field.dot(&system.positions[i])
+    }
+
+    fn force(&self, system: &System, i: usize) -> Vector3<Float> {
+        system.charge(i) * self.field
+    }
+}
+
+/// Constant acceleration of every atom, such as gravity.
+///
+/// Exerts the force `m g` on every atom with mass `m` and adds the energy `-m g . r`, which
+/// drives gravity-driven flow or sedimentation.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let gravity = Gravity::new(nalgebra::Vector3::new(0.0, 0.0, -1e-6));
+/// let potentials = PotentialsBuilder::new().field(gravity).build();
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Gravity {
+    acceleration: Vector3<Float>,
+}
+
+impl Gravity {
+    /// Returns a new [`Gravity`] field with an acceleration in angstroms per square femtosecond.
+    pub fn new(acceleration: Vector3<Float>) -> Gravity {
+        Gravity { acceleration }
+    }
+
+    /// Returns the acceleration in angstroms per square femtosecond.
+    pub fn acceleration(&self) -> Vector3<Float> {
+        self.acceleration
+    }
+}
+
+impl Potential for Gravity {
+    fn describe(&self) -> String {
+        format!(
+            "Gravity({}, {}, {})",
+            self.acceleration.x, self.acceleration.y, self.acceleration.z
+        )
+    }
+}
+
+impl ExternalField for Gravity {
+    fn energy(&self, system: &System, i: usize) -> Float {
+        -system.species[i].mass() * self.acceleration.dot(&system.positions[i])
+    }
+
+    fn force(&self, system: &System, i: usize) -> Vector3<Float> {
+        system.species[i].mass() * self.acceleration
+    }
+}
+
+/// [`ExternalField`] whose force on each atom is given by a user-supplied function.
+///
+/// The function receives the system and the index of an atom and returns the force acting on
+/// it. Arbitrary forces have no energy in general, so the field adds nothing to the potential
+/// energy unless an energy function is also given with [`energy`](FieldCallback::energy).
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // pull the first atom along x
+/// let pull = FieldCallback::new(|_: &System, i: usize| {
+///     if i == 0 {
+///         nalgebra::Vector3::new(0.5, 0.0, 0.0)
+///     } else {
+///         nalgebra::Vector3::zeros()
+///     }
+/// });
+/// let potentials = PotentialsBuilder::new().field(pull).build();
+/// ```
+pub struct FieldCallback<F> {
+    force: F,
+    energy: Option<FieldEnergyFn>,
+}
+
+type FieldEnergyFn = Box<dyn Fn(&System, usize) -> Float + Send + Sync>;
+
+impl<F> FieldCallback<F>
+where
+    F: Fn(&System, usize) -> Vector3<Float> + Send + Sync,
+{
+    /// Returns a new [`FieldCallback`] which exerts the force returned by `force` on each atom.
+    pub fn new(force: F) -> FieldCallback<F> {
+        FieldCallback { force, energy: None }
+    }
+
+    /// Sets the function which returns the energy of each atom, which should be consistent with the forces.
+    pub fn energy<E>(mut self, energy: E) -> FieldCallback<F>
+    where
+        E: Fn(&System, usize) -> Float + Send + Sync + 'static,
+    {
+        self.energy = Some(Box::new(energy));
+        self
+    }
+}
+
+impl<F> fmt::Debug for FieldCallback<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FieldCallback")
+            .field("energy", &self.energy.is_some())
+            .finish()
+    }
+}
+
+impl<F> Potential for FieldCallback<F>
+where
+    F: Fn(&System, usize) -> Vector3<Float> + Send + Sync,
+{
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl<F> ExternalField for FieldCallback<F>
+where
+    F: Fn(&System, usize) -> Vector3<Float> + Send + Sync,
+{
+    fn energy(&self, system: &System, i: usize) -> Float {
+        match &self.energy {
+            Some(energy) => energy(system, i),
+            None => 0.0,
+        }
+    }
+
+    fn force(&self, system: &System, i: usize) -> Vector3<Float> {
+        (self.force)(system, i)
+    }
+}
+
+type ExternalSetupFn = fn(&System, Vec<Species>) -> Vec<[usize; 2]>;
+
+type ExternalUpdateFn = fn(&System, &[[usize; 2]], Float) -> Vec<[usize; 2]>;
+
+type ExternalSelection = Selection<ExternalSetupFn, Vec<Species>, ExternalUpdateFn, Float, 2>;
+
+pub(crate) struct ExternalPotentialMeta {
+    pub potential: Box<dyn ExternalPotential>,
+    pub thickness: Float,
+    selection: ExternalSelection,
+    images: Vec<ImagePair>,
+    selves: Vec<usize>,
+}
+
+impl ExternalPotentialMeta {
+    pub fn new<T>(potential: T, thickness: Float) -> ExternalPotentialMeta
+    where
+        T: ExternalPotential + 'static,
+    {
+        let selection = Selection::new(
+            setup_pairs_among_species as ExternalSetupFn,
+            update_pairs_by_cutoff_radius as ExternalUpdateFn,
+        );
+        ExternalPotentialMeta {
+            potential: Box::new(potential),
+            thickness,
+            selection,
+            images: Vec::new(),
+            selves: Vec::new(),
+        }
+    }
+
+    pub fn setup(&mut self, system: &System) {
+        let mut species: Vec<Species> = Vec::new();
+        for sp in &system.species {
+            if !species.contains(sp) {
+                species.push(*sp);
+            }
+        }
+        self.selection.setup(system, species);
+        self.selves = (0..system.size).collect();
+    }
+
+    // Returns the current number of neighbor pairs and the bytes allocated for the neighbor list.
+    pub fn neighbor_list(&self) -> (usize, usize) {
+        let pairs = self.selection.indices().count() + self.images.len();
+        let bytes = self.selection.allocated_bytes() + self.images.capacity() * std::mem::size_of::<ImagePair>();
+        (pairs, bytes)
+    }
+
+    pub fn update(&mut self, system: &System) {
+        let cutoff = self.potential.cutoff() + self.thickness;
+        if cutoff > system.cell.inscribed_radius() {
+            // the minimum image convention misses pairs in cells smaller than the cutoff
+            self.selection.clear();
+            self.images = update_image_pairs(system, self.selection.possible_indices(), &self.selves, cutoff);
+        } else {
+            self.images.clear();
+            self.selection.update(system, cutoff)
+        }
+    }
+
+    /// Returns the pairs within the cutoff radius of the potential.
+    pub fn neighborhood(&self, system: &System) -> Neighborhood {
+        let cutoff = self.potential.cutoff();
+        let nearest = self.selection.indices().map(|&[i, j]| {
+            let mut separation = system.positions[j] - system.positions[i];
+            system.cell.vector_image(&mut separation);
+            (i, j, separation)
+        });
+        let images = self
+            .images
+            .iter()
+            .map(|image| (image.i, image.j, image.separation(system)));
+        let pairs = nearest
+            .chain(images)
+            .filter(|(_, _, separation)| separation.norm() < cutoff)
+            .collect();
+        Neighborhood { cutoff, pairs }
+    }
+
+    /// Returns the energy and forces of the potential.
+    pub fn evaluate(&self, system: &System) -> ExternalEvaluation {
+        let evaluation = self.potential.evaluate(system, &self.neighborhood(system));
+        assert_eq!(
+            evaluation.forces.len(),
+            system.size,
+            "Expected an external force for each of the {} atoms",
+            system.size
+        );
+        evaluation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ElectricField, ExternalCallback, ExternalEnsemble, ExternalEvaluation, FieldCallback, Gravity, Neighborhood,
+    };
+    use crate::hooks::{Hook, UncertaintyMonitor};
+    use crate::internal::Float;
+    use crate::potentials::types::Harmonic;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::forces::{ForceUncertainty, Forces, MaxForceUncertainty};
+    use crate::properties::pressure::Virial;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::{Matrix3, Vector3};
+
+    // Harmonic springs between every pair of neighbors.
+    fn springs(system: &System, neighborhood: &Neighborhood) -> ExternalEvaluation {
+        let mut evaluation = ExternalEvaluation {
+            forces: vec![Vector3::zeros(); system.size],
+            virial: Some(Matrix3::zeros()),
+            ..ExternalEvaluation::default()
+        };
+        for (i, j, separation) in &neighborhood.pairs {
+            let r = separation.norm();
+            let force = 2.0 * (r - 1.0) * separation / r;
+            evaluation.energy += (r - 1.0).powi(2);
+            evaluation.forces[*i] += force;
+            evaluation.forces[*j] -= force;
+            evaluation.virial = evaluation.virial.map(|virial| virial - separation * force.transpose());
+        }
+        evaluation
+    }
+
+    #[test]
+    fn matches_pair_potential() {
+        let atom = Species::new(1.0, 0.0);
+        let system = System {
+            size: 3,
+            cell: Cell::cubic(5.0),
+            species: vec![atom; 3],
+            positions: vec![
+                Vector3::new(0.5, 0.5, 0.5),
+                Vector3::new(1.7, 0.5, 0.5),
+                Vector3::new(4.6, 1.0, 0.5),
+            ],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology::default(),
+        };
+        // the cutoff exceeds the inscribed radius so periodic images are included
+        let mut external = PotentialsBuilder::new()
+            .external(ExternalCallback::new(3.0, springs), 0.5)
+            .build();
+        let mut pair = PotentialsBuilder::new()
+            .pair(Harmonic::new(1.0, 1.0), (atom, atom), 3.0, 0.5)
+            .build();
+        external.setup(&system);
+        pair.setup(&system);
+
+        assert_relative_eq!(
+            PotentialEnergy.calculate(&system, &external),
+            PotentialEnergy.calculate(&system, &pair),
+            epsilon = 1e-3
+        );
+        let (a, b) = (Forces.calculate(&system, &external), Forces.calculate(&system, &pair));
+        for (a, b) in a.iter().zip(b.iter()) {
+            assert_relative_eq!(a, b, epsilon = 1e-3);
+        }
+        assert_relative_eq!(Virial.calculate(&system, &external), Virial.calculate(&system, &pair), epsilon = 1e-3);
+    }
+
+    #[test]
+    fn ensemble() {
+        let atom = Species::new(1.0, 0.0);
+        let mut system = System {
+            size: 3,
+            cell: Cell::cubic(20.0),
+            species: vec![atom; 3],
+            positions: vec![
+                Vector3::new(5.0, 5.0, 5.0),
+                Vector3::new(6.5, 5.0, 5.0),
+                Vector3::new(12.0, 5.0, 5.0),
+            ],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology::default(),
+        };
+        // the second model doubles the springs and only the last atom is out of range of both
+        let doubled = |system: &System, neighborhood: &Neighborhood| {
+            let mut evaluation = springs(system, neighborhood);
+            evaluation.energy *= 2.0;
+            evaluation.forces.iter_mut().for_each(|force| *force *= 2.0);
+            evaluation.virial = None;
+            evaluation
+        };
+        let ensemble = ExternalEnsemble::new()
+            .member(ExternalCallback::new(3.0, springs))
+            .member(ExternalCallback::new(2.0, doubled));
+        assert_eq!(ensemble.len(), 2);
+        let mut potentials = PotentialsBuilder::new().external(ensemble, 0.5).build();
+        potentials.setup(&system);
+
+        // the mean of the members drives the system
+        let force = 2.0 * 0.5;
+        assert_relative_eq!(PotentialEnergy.calculate(&system, &potentials), 1.5 * 0.25, epsilon = 1e-5);
+        assert_relative_eq!(Forces.calculate(&system, &potentials)[0].x, 1.5 * force, epsilon = 1e-5);
+        assert_eq!(Virial.calculate(&system, &potentials), Matrix3::zeros());
+
+        // and their spread is the uncertainty of each force
+        let uncertainty = ForceUncertainty.calculate(&system, &potentials);
+        assert_relative_eq!(uncertainty[0], 0.5 * force, epsilon = 1e-5);
+        assert_relative_eq!(uncertainty[2], 0.0);
+        assert_relative_eq!(MaxForceUncertainty.calculate(&system, &potentials), 0.5 * force, epsilon = 1e-5);
+
+        // which the monitor flags and stops on
+        let mut monitor = UncertaintyMonitor::new(0.1).stop_above(1.0);
+        let flagged = monitor.flagged();
+        monitor.setup(&system, &potentials);
+        monitor.post_step(1, &mut system, &potentials);
+        assert!(!monitor.stop());
+        // only the first model reaches the stretched pair
+        system.positions[1].x = 7.5;
+        potentials.setup(&system);
+        monitor.post_step(2, &mut system, &potentials);
+        assert!(monitor.stop());
+        let flagged = flagged.lock().unwrap();
+        assert_eq!(flagged.iter().map(|&(step, _)| step).collect::<Vec<_>>(), vec![1, 2]);
+        assert_relative_eq!(flagged[1].1, 1.5, epsilon = 1e-5);
+    }
+
+    #[test]
+    fn fields() {
+        let anion = Species::new(35.45, -1.0);
+        let cation = Species::new(22.99, 1.0);
+        let system = System {
+            size: 2,
+            cell: Cell::cubic(10.0),
+            species: vec![anion, cation],
+            positions: vec![Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0)],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        };
+        let field = Vector3::new(0.0, 0.0, 2.0);
+        let g = Vector3::new(0.0, -1e-3, 0.0);
+        let pull = FieldCallback::new(|_: &System, i: usize| Vector3::new(i as Float, 0.0, 0.0))
+            .energy(|system: &System, i: usize| -(i as Float) * system.positions[i].x);
+        let mut potentials = PotentialsBuilder::new()
+            .field(ElectricField::new(field))
+            .field(Gravity::new(g))
+            .field(pull)
+            .build();
+        potentials.setup(&system);
+
+        // the forces of every field add up on each atom
+        let forces = Forces.calculate(&system, &potentials);
+        assert_relative_eq!(forces[0], -field + 35.45 * g, epsilon = 1e-5);
+        assert_relative_eq!(forces[1], field + 22.99 * g + Vector3::new(1.0, 0.0, 0.0), epsilon = 1e-5);
+
+        // and so do their energies
+        let energy = (3.0 - 6.0) * 2.0 + (35.45 * 2.0 + 22.99 * 5.0) * 1e-3 - 4.0;
+        assert_relative_eq!(PotentialEnergy.calculate(&system, &potentials), energy, epsilon = 1e-4);
+        assert_eq!(Virial.calculate(&system, &potentials), Matrix3::zeros());
+
+        // volts per angstrom are converted to the internal units
+        let field = ElectricField::volts_per_angstrom(Vector3::new(1.0, 0.0, 0.0));
+        assert_relative_eq!(field.field().x, 23.06055, epsilon = 1e-4);
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected an external force for each of the 1 atoms")]
+    fn missing_forces() {
+        let system = System {
+            size: 1,
+            cell: Cell::cubic(10.0),
+            species: vec![Species::new(1.0, 0.0)],
+            positions: vec![Vector3::zeros()],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let callback = |_: &System, _: &Neighborhood| ExternalEvaluation::default();
+        let mut potentials = PotentialsBuilder::new().external(ExternalCallback::new(2.0, callback), 0.0).build();
+        potentials.setup(&system);
+        let _: Vec<Vector3<Float>> = Forces.calculate(&system, &potentials);
+    }
+}
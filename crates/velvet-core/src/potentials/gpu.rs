@@ -0,0 +1,283 @@
+//! Evaluation of pair potentials on a GPU through wgpu.
+//!
+//! Separation vectors and resolution weights are gathered from the neighbor lists on the CPU,
+//! the potential and its cutoff scheme are evaluated for every pair in a compute shader, and the
+//! per-pair forces and energies are read back and accumulated on the CPU. Shaders run in single
+//! precision regardless of the `f64` feature.
+
+use std::sync::mpsc;
+
+use nalgebra::Vector3;
+use wgpu::util::DeviceExt;
+
+use crate::internal::Float;
+use crate::potentials::pair::{CutoffScheme, PairForm, PairPotentialMeta};
+use crate::system::System;
+
+const LENNARD_JONES: u32 = 0;
+const BUCKINGHAM: u32 = 1;
+const MORSE: u32 = 2;
+
+const TRUNCATED: u32 = 0;
+const SHIFTED: u32 = 1;
+const SWITCHED: u32 = 2;
+
+const WORKGROUP_SIZE: u32 = 64;
+
+const SHADER: &str = r#"
+struct Parameters {
+    form: u32,
+    scheme: u32,
+    count: u32,
+    padding: u32,
+    // three coefficients of the functional form followed by the cutoff radius
+    coefficients: vec4<f32>,
+    // inner radius of the switching window and the energy at the cutoff radius
+    window: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> parameters: Parameters;
+// separation vector and resolution weight of each pair
+@group(0) @binding(1) var<storage, read> pairs: array<vec4<f32>>;
+// force on the first atom of each pair and the energy of the pair
+@group(0) @binding(2) var<storage, read_write> results: array<vec4<f32>>;
+
+fn energy(r: f32) -> f32 {
+    let c = parameters.coefficients;
+    if (parameters.form == 0u) {
+        let term = pow(c.y / r, 6.0);
+        return 4.0 * c.x * (term * term - term);
+    } else if (parameters.form == 1u) {
+        return c.x * exp(-r / c.y) - c.z / pow(r, 6.0);
+    }
+    return c.y * (exp(-2.0 * c.x * (r - c.z)) - 2.0 * exp(-c.x * (r - c.z)));
+}
+
+fn force(r: f32) -> f32 {
+    let c = parameters.coefficients;
+    if (parameters.form == 0u) {
+        let term = pow(c.y / r, 6.0);
+        return c.x * (24.0 * term - 48.0 * term * term) / r;
+    } else if (parameters.form == 1u) {
+        return 6.0 * c.z / pow(r, 7.0) - c.x * exp(-r / c.y) / c.y;
+    }
+    return 2.0 * c.x * c.y * (exp(-c.x * (r - c.z)) - exp(-2.0 * c.x * (r - c.z)));
+}
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    if (index >= parameters.count) {
+        return;
+    }
+    let pair = pairs[index];
+    let r = length(pair.xyz);
+    let cutoff = parameters.coefficients.w;
+    if (r >= cutoff) {
+        results[index] = vec4<f32>(0.0);
+        return;
+    }
+    var u = energy(r);
+    var f = force(r);
+    let inner = parameters.window.x;
+    if (parameters.scheme == 1u) {
+        u = u - parameters.window.y;
+    } else if (parameters.scheme == 2u && r > inner) {
+        let rc2 = cutoff * cutoff;
+        let rs2 = inner * inner;
+        let r2 = r * r;
+        let denominator = pow(rc2 - rs2, 3.0);
+        let value = (rc2 - r2) * (rc2 - r2) * (rc2 + 2.0 * r2 - 3.0 * rs2) / denominator;
+        let derivative = 12.0 * r * (rc2 - r2) * (rs2 - r2) / denominator;
+        f = f * value + u * derivative;
+        u = u * value;
+    }
+    results[index] = vec4<f32>(pair.w * f * pair.xyz / r, pair.w * u);
+}
+"#;
+
+// Returns the index of the functional form read by the shader and its coefficients.
+#[allow(clippy::unnecessary_cast)]
+fn coefficients(form: PairForm) -> (u32, [f32; 3]) {
+    match form {
+        PairForm::LennardJones(lj) => (LENNARD_JONES, [lj.epsilon as f32, lj.sigma as f32, 0.0]),
+        PairForm::Buckingham(buckingham) => (BUCKINGHAM, [buckingham.a as f32, buckingham.rho as f32, buckingham.c as f32]),
+        PairForm::Morse(morse) => (MORSE, [morse.a as f32, morse.d_e as f32, morse.r_e as f32]),
+    }
+}
+
+// Layout of the uniform buffer read by the shader.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Parameters {
+    form: u32,
+    scheme: u32,
+    count: u32,
+    padding: u32,
+    coefficients: [f32; 4],
+    window: [f32; 4],
+}
+
+/// Pair of atoms in a neighbor list evaluated on the GPU.
+pub(crate) struct PairResult {
+    pub i: usize,
+    pub j: usize,
+    pub separation: Vector3<Float>,
+    /// Weighted force acting on atom `i`.
+    pub force: Vector3<Float>,
+    /// Weighted energy of the pair.
+    pub energy: Float,
+}
+
+/// Device and compute pipeline used to evaluate pair potentials.
+pub(crate) struct GpuPairs {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+}
+
+impl GpuPairs {
+    /// Returns a backend on the default adapter, or `None` if no adapter supports compute shaders.
+    pub fn new() -> Option<GpuPairs> {
+        let instance = wgpu::Instance::default();
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions::default()))?;
+        let capabilities = adapter.get_downlevel_capabilities();
+        if !capabilities.flags.contains(wgpu::DownlevelFlags::COMPUTE_SHADERS) {
+            return None;
+        }
+        let descriptor = wgpu::DeviceDescriptor {
+            label: Some("velvet"),
+            required_features: wgpu::Features::empty(),
+            required_limits: wgpu::Limits::downlevel_defaults(),
+        };
+        let (device, queue) = pollster::block_on(adapter.request_device(&descriptor, None)).ok()?;
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("pair potentials"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("pair potentials"),
+            layout: None,
+            module: &module,
+            entry_point: "main",
+        });
+        Some(GpuPairs { device, queue, pipeline })
+    }
+
+    /// Returns the weighted force and energy of each pair in the neighbor list of `meta`.
+    ///
+    /// Returns `None` if the potential is not supported by the shader or the device fails, in
+    /// which case the pairs should be evaluated on the CPU.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn evaluate(&self, meta: &PairPotentialMeta, system: &System, weights: Option<&[Float]>) -> Option<Vec<PairResult>> {
+        let form = meta.form?;
+        let pairs: Vec<(usize, usize, Vector3<Float>)> = meta.pairs(system).collect();
+        let inputs: Vec<[f32; 4]> = pairs
+            .iter()
+            .map(|&(i, j, s)| [s.x as f32, s.y as f32, s.z as f32, meta.weight(weights, i, j) as f32])
+            .collect();
+        let (scheme, inner) = match meta.scheme {
+            CutoffScheme::Truncated => (TRUNCATED, 0.0),
+            CutoffScheme::Shifted => (SHIFTED, 0.0),
+            CutoffScheme::Switched(inner) => (SWITCHED, inner),
+        };
+        let (kind, [a, b, c]) = coefficients(form);
+        let mut parameters = Parameters {
+            form: kind,
+            scheme,
+            count: 0,
+            padding: 0,
+            coefficients: [a, b, c, meta.cutoff as f32],
+            window: [inner as f32, meta.potential.energy(meta.cutoff) as f32, 0.0, 0.0],
+        };
+        // each dispatch is limited in the number of workgroups along a dimension
+        let chunk = (self.device.limits().max_compute_workgroups_per_dimension * WORKGROUP_SIZE) as usize;
+        let mut outputs = Vec::with_capacity(inputs.len());
+        for inputs in inputs.chunks(chunk) {
+            parameters.count = inputs.len() as u32;
+            outputs.extend(self.dispatch(&parameters, inputs)?);
+        }
+        let results = pairs
+            .into_iter()
+            .zip(outputs)
+            .map(|((i, j, separation), [x, y, z, energy])| PairResult {
+                i,
+                j,
+                separation,
+                force: Vector3::new(x as Float, y as Float, z as Float),
+                energy: energy as Float,
+            })
+            .collect();
+        Some(results)
+    }
+
+    // Runs the shader over a batch of pairs and reads back the results.
+    fn dispatch(&self, parameters: &Parameters, inputs: &[[f32; 4]]) -> Option<Vec<[f32; 4]>> {
+        if inputs.is_empty() {
+            return Some(Vec::new());
+        }
+        let size = std::mem::size_of_val(inputs) as wgpu::BufferAddress;
+        let uniform = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("parameters"),
+            contents: bytemuck::bytes_of(parameters),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let pairs = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("pairs"),
+            contents: bytemuck::cast_slice(inputs),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let results = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("results"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("staging"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: pairs.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: results.as_entire_binding(),
+                },
+            ],
+        });
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: None,
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((inputs.len() as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&results, 0, &staging, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+        let slice = staging.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+        let outputs = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        Some(outputs)
+    }
+}
@@ -1,69 +1,891 @@
 //! Classical interatomic potentials.
 
+pub mod adaptive;
 pub mod coulomb;
+pub mod cutoffs;
+pub mod eam;
+pub mod external;
+mod ewald;
+#[cfg(feature = "gpu")]
+pub(crate) mod gpu;
 pub mod pair;
+pub mod restraints;
+#[cfg(feature = "simd")]
+pub(crate) mod simd;
+mod tables;
 pub mod types;
 
+use std::fmt::{self, Write as _};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::colvars::{Bias, BiasEvaluation};
 use crate::internal::Float;
+use crate::potentials::adaptive::{AdaptiveResolution, Resolution};
 use crate::potentials::coulomb::{CoulombPotential, CoulombPotentialMeta};
-use crate::potentials::pair::{PairPotential, PairPotentialMeta};
+use crate::potentials::eam::{EmbeddedAtom, EmbeddedAtomMeta};
+use crate::potentials::external::{ExternalField, ExternalPotential, ExternalPotentialMeta};
+#[cfg(feature = "gpu")]
+use crate::potentials::gpu::GpuPairs;
+use crate::potentials::pair::{CutoffScheme, PairPotential, PairPotentialMeta};
 use crate::system::species::Species;
 use crate::system::System;
 
 /// Base trait for all potentials.
-pub trait Potential: Send + Sync {}
+pub trait Potential: Send + Sync {
+    /// Returns a short description of the potential and its parameters for run summaries.
+    ///
+    /// Defaults to the name of the type without its module path.
+    fn describe(&self) -> String {
+        let name = std::any::type_name::<Self>();
+        let name = &name[..name.find('<').unwrap_or(name.len())];
+        name.rsplit("::").next().unwrap_or(name).to_string()
+    }
+}
+
+/// Displacement based neighbor list rebuild criterion with an adaptive skin thickness.
+///
+/// Rather than rebuilding the neighbor lists every fixed number of iterations, the lists
+/// are rebuilt only once the largest displacement of any atom since the last rebuild could
+/// have moved a pair from outside the skin to inside the cutoff radius. On each rebuild the
+/// skin thickness is chosen from the largest per-step displacement observed since the
+/// previous rebuild such that, at that velocity, roughly `target_interval` iterations pass
+/// between rebuilds.
+#[derive(Clone, Debug)]
+pub struct AdaptiveSkin {
+    target_interval: usize,
+    min_thickness: Float,
+    max_thickness: Float,
+    thickness: Float,
+    max_step: Float,
+    rebuilds: usize,
+    reference: Vec<Vector3<Float>>,
+    previous: Vec<Vector3<Float>>,
+}
+
+impl AdaptiveSkin {
+    /// Returns a new [`AdaptiveSkin`] rebuild criterion.
+    ///
+    /// # Arguments
+    ///
+    /// * `target_interval` - Desired number of iterations between neighbor list rebuilds.
+    /// * `min_thickness` - Lower bound on the skin thickness.
+    /// * `max_thickness` - Upper bound on the skin thickness.
+    pub fn new(target_interval: usize, min_thickness: Float, max_thickness: Float) -> AdaptiveSkin {
+        AdaptiveSkin {
+            target_interval,
+            min_thickness,
+            max_thickness,
+            thickness: max_thickness,
+            max_step: 0.0,
+            rebuilds: 0,
+            reference: Vec::new(),
+            previous: Vec::new(),
+        }
+    }
+
+    /// Returns the current skin thickness.
+    pub fn thickness(&self) -> Float {
+        self.thickness
+    }
+
+    /// Returns the number of times the neighbor lists have been rebuilt.
+    pub fn rebuilds(&self) -> usize {
+        self.rebuilds
+    }
+
+    // Records the current positions and returns true if the neighbor lists must be rebuilt.
+    fn observe(&mut self, system: &System) -> bool {
+        let mut max_displacement: Float = 0.0;
+        for ((pos, reference), previous) in system
+            .positions
+            .iter()
+            .zip(self.reference.iter())
+            .zip(self.previous.iter_mut())
+        {
+            let mut step = pos - *previous;
+            system.cell.vector_image(&mut step);
+            self.max_step = self.max_step.max(step.norm());
+            let mut displacement = pos - reference;
+            system.cell.vector_image(&mut displacement);
+            max_displacement = max_displacement.max(displacement.norm());
+            *previous = *pos;
+        }
+        // two atoms moving toward each other close the gap by up to twice the maximum displacement
+        2.0 * max_displacement > self.thickness
+    }
+
+    // Resets the reference positions and returns the skin thickness to use until the next rebuild.
+    fn rebuild(&mut self, system: &System) -> Float {
+        if self.rebuilds > 0 {
+            let thickness = 2.0 * self.max_step * self.target_interval as Float;
+            self.thickness = thickness.max(self.min_thickness).min(self.max_thickness);
+        }
+        self.max_step = 0.0;
+        self.rebuilds += 1;
+        self.reference = system.positions.clone();
+        self.previous = system.positions.clone();
+        self.thickness
+    }
+}
+
+/// Upper bound on the magnitude of the force acting on each atom.
+///
+/// Forces larger than the threshold are rescaled to the threshold while keeping their
+/// direction, which allows badly packed initial configurations to be relaxed with ordinary
+/// dynamics rather than a separate minimizer. The atoms capped during the most recent force
+/// evaluation are logged and may be written alongside other outputs with the
+/// [`CappedAtoms`](crate::properties::forces::CappedAtoms) property. The virial is not capped.
+#[derive(Debug)]
+pub struct ForceCap {
+    max_force: Float,
+    capped: Mutex<Vec<usize>>,
+    total: AtomicUsize,
+}
+
+impl ForceCap {
+    /// Returns a new [`ForceCap`].
+    ///
+    /// # Arguments
+    ///
+    /// * `max_force` - Largest allowed magnitude of the force on a single atom.
+    pub fn new(max_force: Float) -> ForceCap {
+        ForceCap {
+            max_force,
+            capped: Mutex::new(Vec::new()),
+            total: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the largest allowed magnitude of the force on a single atom.
+    pub fn max_force(&self) -> Float {
+        self.max_force
+    }
+
+    /// Returns the indices of the atoms capped during the most recent force evaluation.
+    pub fn capped_atoms(&self) -> Vec<usize> {
+        self.capped.lock().unwrap().clone()
+    }
+
+    /// Returns the total number of times any atom has been capped.
+    pub fn total_capped(&self) -> usize {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    // Rescales forces which exceed the threshold and logs the affected atoms.
+    pub(crate) fn apply(&self, forces: &mut [Vector3<Float>]) {
+        let mut capped = self.capped.lock().unwrap();
+        capped.clear();
+        for (i, force) in forces.iter_mut().enumerate() {
+            let magnitude = force.norm();
+            if magnitude > self.max_force {
+                *force *= self.max_force / magnitude;
+                capped.push(i);
+            }
+        }
+        self.total.fetch_add(capped.len(), Ordering::Relaxed);
+    }
+}
+
+/// Description of one potential in a [`Potentials`] collection.
+///
+/// Returned by [`Potentials::summaries`] so that the configuration can be checked
+/// programmatically before a run, and rendered as a table by [`Potentials::summary`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct PotentialSummary {
+    /// Kind of interaction, one of `pair`, `coulomb`, `embedded atom`, or `external`.
+    pub kind: String,
+    /// Description of the potential and its parameters.
+    pub potential: String,
+    /// Atoms the potential acts between.
+    pub species: String,
+    /// Cutoff radius.
+    pub cutoff: Float,
+    /// Thickness of the neighbor list skin beyond the cutoff radius.
+    pub thickness: Float,
+    /// Cutoff scheme, tail correction, and resolution of pair potentials.
+    pub restrictions: String,
+}
+
+/// Coverage of the species pairs in a system by the nonbonded potentials.
+///
+/// Atoms of a species pair without any potential silently ignore each other, which usually
+/// means that a species was mistyped when the potentials were built. Returned by
+/// [`Potentials::coverage`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PairCoverage {
+    /// Species pairs present in the system which no pair, Coulomb, embedded atom, or external potential acts between.
+    pub uncovered: Vec<(Species, Species)>,
+    /// Species pairs of pair potentials which match no pair of atoms in the system.
+    pub unused: Vec<(Species, Species)>,
+}
+
+impl PairCoverage {
+    /// Returns true if every species pair interacts and every pair potential acts on some pair of atoms.
+    pub fn is_complete(&self) -> bool {
+        self.uncovered.is_empty() && self.unused.is_empty()
+    }
+}
+
+impl fmt::Display for PairCoverage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (a, b) in &self.uncovered {
+            writeln!(f, "warning: no potential acts between {}-{} pairs", species_label(*a), species_label(*b))?;
+        }
+        for (a, b) in &self.unused {
+            writeln!(f, "warning: the {}-{} pair potential matches no pairs", species_label(*a), species_label(*b))?;
+        }
+        Ok(())
+    }
+}
 
+// Labels a species by its chemical symbol or, for custom species, by its mass.
+pub(crate) fn species_label(species: Species) -> String {
+    match species.element() {
+        Some(element) => element.symbol().to_string(),
+        None => format!("custom({})", species.mass()),
+    }
+}
+
+// Summed evaluation of the biases and the configuration it belongs to.
+struct BiasCache {
+    positions: Vec<Vector3<Float>>,
+    matrix: Matrix3<Float>,
+    evaluation: Arc<BiasEvaluation>,
+}
+
+/// Collection of all potentials applied to a system.
 pub struct Potentials {
     pub(crate) coulomb_meta: Option<CoulombPotentialMeta>,
     pub(crate) pair_metas: Vec<PairPotentialMeta>,
+    pub(crate) embedded_atom_meta: Option<EmbeddedAtomMeta>,
+    pub(crate) external_meta: Option<ExternalPotentialMeta>,
     pub(crate) update_frequency: usize,
+    pub(crate) adaptive_skin: Option<AdaptiveSkin>,
+    pub(crate) force_cap: Option<ForceCap>,
+    pub(crate) biases: Vec<Box<dyn Bias>>,
+    pub(crate) fields: Vec<Box<dyn ExternalField>>,
+    adaptive_resolution: Option<AdaptiveResolution>,
+    exclusions: usize,
+    #[cfg(feature = "gpu")]
+    gpu: bool,
+    #[cfg(feature = "gpu")]
+    gpu_pairs: Option<GpuPairs>,
+    stale: bool,
+    bias_cache: Mutex<Option<BiasCache>>,
+    constraint_virial: Mutex<Matrix3<Float>>,
 }
 
 impl Potentials {
+    /// Prepares each potential to run and builds the initial neighbor lists.
     pub fn setup(&mut self, system: &System) {
+        *self.constraint_virial.lock().unwrap() = Matrix3::zeros();
+        let excluded = system.topology.excluded_pairs(self.exclusions);
         // setup coulomb potential if it exists
-        match &mut self.coulomb_meta {
-            Some(meta) => meta.setup(system),
-            None => {}
+        if let Some(meta) = &mut self.coulomb_meta {
+            meta.setup(system, &excluded)
         }
         // setup each pair potential
         self.pair_metas
             .iter_mut()
-            .for_each(|meta| meta.setup(system))
+            .for_each(|meta| meta.setup(system, &excluded));
+        // setup embedded atom potential if it exists
+        if let Some(meta) = &mut self.embedded_atom_meta {
+            meta.setup(system)
+        }
+        // setup external potential if it exists
+        if let Some(meta) = &mut self.external_meta {
+            meta.setup(system)
+        }
+        // select the GPU backend for pair potentials if one was requested and an adapter exists
+        #[cfg(feature = "gpu")]
+        if self.gpu && self.gpu_pairs.is_none() {
+            self.gpu_pairs = GpuPairs::new();
+        }
+        self.rebuild(system);
+        self.stale = false;
+    }
+
+    /// Returns true if pair potentials are evaluated on a GPU.
+    ///
+    /// The backend is selected by [`setup`](Potentials::setup) and requires the `gpu` feature,
+    /// a request through [`PotentialsBuilder::gpu`], and an adapter which supports compute shaders.
+    #[cfg(feature = "gpu")]
+    pub fn uses_gpu(&self) -> bool {
+        self.gpu_pairs.is_some()
     }
 
+    #[cfg(feature = "gpu")]
+    pub(crate) fn gpu_pairs(&self) -> Option<&GpuPairs> {
+        self.gpu_pairs.as_ref()
+    }
+
+    /// Returns the current number of neighbor pairs and the bytes allocated for the neighbor
+    /// lists of every potential.
+    pub fn neighbor_lists(&self) -> (usize, usize) {
+        let coulomb = self.coulomb_meta.iter().map(|meta| meta.neighbor_list());
+        let pairs = self.pair_metas.iter().map(|meta| meta.neighbor_list());
+        let embedded = self.embedded_atom_meta.iter().map(|meta| meta.neighbor_list());
+        let external = self.external_meta.iter().map(|meta| meta.neighbor_list());
+        coulomb
+            .chain(pairs)
+            .chain(embedded)
+            .chain(external)
+            .fold((0, 0), |(pairs, bytes), (p, b)| (pairs + p, bytes + b))
+    }
+
+    /// Updates the neighbor lists of each potential if a rebuild is required.
     pub fn update(&mut self, system: &System, iteration: usize) {
-        // only update if the update frequency is reached
-        if iteration % self.update_frequency != 0 {
+        // potentials which changed since the last update need a fresh setup
+        self.biases.iter_mut().for_each(|bias| bias.update(system));
+        self.invalidate_biases();
+        if self.stale {
+            self.setup(system);
             return;
         }
+        let should_rebuild = match &mut self.adaptive_skin {
+            Some(skin) => skin.observe(system),
+            None => iteration.is_multiple_of(self.update_frequency),
+        };
+        if should_rebuild {
+            self.rebuild(system)
+        }
+    }
+
+    /// Replaces the Coulombic potential, or adds one if none exists.
+    ///
+    /// The neighbor lists are rebuilt on the next call to `setup` or `update`.
+    pub fn set_coulomb<T>(&mut self, potential: T, cutoff: Float, thickness: Float)
+    where
+        T: CoulombPotential + 'static,
+    {
+        self.coulomb_meta = Some(CoulombPotentialMeta::new(potential, cutoff, thickness));
+        self.stale = true;
+    }
+
+    /// Removes the Coulombic potential if one exists.
+    pub fn remove_coulomb(&mut self) {
+        self.coulomb_meta = None;
+        self.stale = true;
+    }
+
+    /// Replaces the atomistic pair potential between `species`, or adds one if none exists.
+    ///
+    /// The neighbor lists are rebuilt on the next call to `setup` or `update`.
+    pub fn set_pair<T>(
+        &mut self,
+        potential: T,
+        species: (Species, Species),
+        cutoff: Float,
+        thickness: Float,
+    ) where
+        T: PairPotential + 'static,
+    {
+        self.remove_pair_with_resolution(species, Resolution::Atomistic);
+        self.pair_metas.push(PairPotentialMeta::new(
+            potential,
+            species,
+            cutoff,
+            thickness,
+        ));
+    }
+
+    /// Replaces the coarse-grained pair potential between `species`, or adds one if none exists.
+    ///
+    /// The neighbor lists are rebuilt on the next call to `setup` or `update`.
+    pub fn set_coarse_pair<T>(
+        &mut self,
+        potential: T,
+        species: (Species, Species),
+        cutoff: Float,
+        thickness: Float,
+    ) where
+        T: PairPotential + 'static,
+    {
+        self.remove_pair_with_resolution(species, Resolution::CoarseGrained);
+        let mut meta = PairPotentialMeta::new(potential, species, cutoff, thickness);
+        meta.resolution = Resolution::CoarseGrained;
+        self.pair_metas.push(meta);
+    }
+
+    /// Replaces the embedded atom potential, or adds one if none exists.
+    ///
+    /// The neighbor lists are rebuilt on the next call to `setup` or `update`.
+    pub fn set_embedded_atom(&mut self, potential: EmbeddedAtom, thickness: Float) {
+        self.embedded_atom_meta = Some(EmbeddedAtomMeta::new(potential, thickness));
+        self.stale = true;
+    }
+
+    /// Removes the embedded atom potential if one exists.
+    pub fn remove_embedded_atom(&mut self) {
+        self.embedded_atom_meta = None;
+        self.stale = true;
+    }
+
+    /// Replaces the external potential, or adds one if none exists.
+    ///
+    /// The neighbor lists are rebuilt on the next call to `setup` or `update`.
+    pub fn set_external<T>(&mut self, potential: T, thickness: Float)
+    where
+        T: ExternalPotential + 'static,
+    {
+        self.external_meta = Some(ExternalPotentialMeta::new(potential, thickness));
+        self.stale = true;
+    }
+
+    /// Removes the external potential if one exists.
+    pub fn remove_external(&mut self) {
+        self.external_meta = None;
+        self.stale = true;
+    }
+
+    /// Enables or disables the long range tail correction of the pair potential between `species`.
+    pub fn set_tail_correction(&mut self, species: (Species, Species), enabled: bool) {
+        let (a, b) = species;
+        self.pair_metas
+            .iter_mut()
+            .filter(|meta| meta.species == (a, b) || meta.species == (b, a))
+            .for_each(|meta| meta.tail = enabled);
+    }
+
+    /// Sets the treatment at the cutoff radius of the pair potential between `species`.
+    pub fn set_cutoff_scheme(&mut self, species: (Species, Species), scheme: CutoffScheme) {
+        let (a, b) = species;
+        self.pair_metas
+            .iter_mut()
+            .filter(|meta| meta.species == (a, b) || meta.species == (b, a))
+            .for_each(|meta| meta.scheme = scheme);
+    }
+
+    /// Removes the atomistic and coarse-grained pair potentials between `species` if they exist.
+    pub fn remove_pair(&mut self, species: (Species, Species)) {
+        let (a, b) = species;
+        self.pair_metas
+            .retain(|meta| meta.species != (a, b) && meta.species != (b, a));
+        self.stale = true;
+    }
+
+    fn remove_pair_with_resolution(&mut self, species: (Species, Species), resolution: Resolution) {
+        let (a, b) = species;
+        self.pair_metas.retain(|meta| {
+            meta.resolution != resolution || (meta.species != (a, b) && meta.species != (b, a))
+        });
+        self.stale = true;
+    }
+
+    /// Sets the region treated atomistically with coarse-grained pair potentials elsewhere, replacing any existing region.
+    pub fn set_adaptive_resolution(&mut self, region: AdaptiveResolution) {
+        self.adaptive_resolution = Some(region);
+    }
+
+    /// Removes the adaptive resolution region if one exists, which makes every atom atomistic.
+    pub fn remove_adaptive_resolution(&mut self) {
+        self.adaptive_resolution = None;
+    }
+
+    /// Returns the adaptive resolution region if one is in use.
+    pub fn adaptive_resolution(&self) -> Option<&AdaptiveResolution> {
+        self.adaptive_resolution.as_ref()
+    }
+
+    /// Returns the resolution weight of each atom if an adaptive resolution region is in use.
+    pub(crate) fn resolution_weights(&self, system: &System) -> Option<Vec<Float>> {
+        self.adaptive_resolution
+            .as_ref()
+            .map(|region| region.weights(system))
+    }
+
+    /// Excludes nonbonded interactions between atoms separated by at most `separation` bonds.
+    ///
+    /// The neighbor lists are rebuilt on the next call to `setup` or `update`.
+    pub fn set_exclusions(&mut self, separation: usize) {
+        self.exclusions = separation;
+        self.stale = true;
+    }
+
+    /// Returns the largest number of bonds separating atoms whose nonbonded interactions are excluded.
+    pub fn exclusions(&self) -> usize {
+        self.exclusions
+    }
+
+    /// Returns the adaptive skin rebuild criterion if one is in use.
+    pub fn adaptive_skin(&self) -> Option<&AdaptiveSkin> {
+        self.adaptive_skin.as_ref()
+    }
+
+    /// Caps the magnitude of the force on each atom, or replaces the existing cap.
+    pub fn set_force_cap(&mut self, cap: ForceCap) {
+        self.force_cap = Some(cap);
+    }
+
+    /// Removes the force cap if one exists.
+    pub fn remove_force_cap(&mut self) {
+        self.force_cap = None;
+    }
+
+    /// Returns the force cap if one is in use.
+    pub fn force_cap(&self) -> Option<&ForceCap> {
+        self.force_cap.as_ref()
+    }
+
+    /// Adds a bias which acts on a collective variable.
+    pub fn add_bias<T>(&mut self, bias: T)
+    where
+        T: Bias + 'static,
+    {
+        self.biases.push(Box::new(bias));
+        self.invalidate_biases();
+    }
+
+    /// Removes every bias.
+    pub fn remove_biases(&mut self) {
+        self.biases.clear();
+        self.invalidate_biases();
+    }
+
+    // Returns the summed evaluation of every bias, which is only computed once per configuration.
+    pub(crate) fn bias_evaluation(&self, system: &System) -> Arc<BiasEvaluation> {
+        let mut cache = self.bias_cache.lock().unwrap();
+        match &*cache {
+            Some(cached) if cached.positions == system.positions && cached.matrix == system.cell.matrix() => {
+                cached.evaluation.clone()
+            }
+            _ => {
+                let evaluation = self.biases.iter().fold(BiasEvaluation::zeros(system.size), |mut sum, bias| {
+                    let evaluation = bias.evaluate(system);
+                    sum.energy += evaluation.energy;
+                    sum.forces
+                        .iter_mut()
+                        .zip(evaluation.forces.iter())
+                        .for_each(|(force, bias_force)| *force += bias_force);
+                    sum.virial += evaluation.virial;
+                    sum
+                });
+                let evaluation = Arc::new(evaluation);
+                *cache = Some(BiasCache {
+                    positions: system.positions.clone(),
+                    matrix: system.cell.matrix(),
+                    evaluation: evaluation.clone(),
+                });
+                evaluation
+            }
+        }
+    }
+
+    // Discards the cached bias evaluation after the biases change without the atoms moving.
+    pub(crate) fn invalidate_biases(&self) {
+        *self.bias_cache.lock().unwrap() = None;
+    }
+
+    // Records the virial of the constraint forces of the last integration step.
+    pub(crate) fn record_constraint_virial(&self, virial: Matrix3<Float>) {
+        *self.constraint_virial.lock().unwrap() = virial;
+    }
+
+    // Returns the virial of the constraint forces of the last integration step since setup.
+    pub(crate) fn constraint_virial(&self) -> Matrix3<Float> {
+        *self.constraint_virial.lock().unwrap()
+    }
+
+    /// Adds a field which acts on each atom independently.
+    pub fn add_field<T>(&mut self, field: T)
+    where
+        T: ExternalField + 'static,
+    {
+        self.fields.push(Box::new(field));
+    }
+
+    /// Removes every field.
+    pub fn remove_fields(&mut self) {
+        self.fields.clear();
+    }
+
+    /// Returns a description of each configured potential.
+    pub fn summaries(&self) -> Vec<PotentialSummary> {
+        let mut summaries = Vec::new();
+        for meta in &self.pair_metas {
+            let mut restrictions = vec![format!("{:?}", meta.scheme).to_lowercase()];
+            if meta.tail {
+                restrictions.push("tail correction".to_string());
+            }
+            if self.adaptive_resolution.is_some() {
+                restrictions.push(format!("{:?}", meta.resolution).to_lowercase());
+            }
+            summaries.push(PotentialSummary {
+                kind: "pair".to_string(),
+                potential: meta.potential.describe(),
+                species: format!("{}-{}", species_label(meta.species.0), species_label(meta.species.1)),
+                cutoff: meta.cutoff,
+                thickness: meta.thickness,
+                restrictions: restrictions.join(", "),
+            });
+        }
+        if let Some(meta) = &self.coulomb_meta {
+            summaries.push(PotentialSummary {
+                kind: "coulomb".to_string(),
+                potential: meta.potential.describe(),
+                species: "charged".to_string(),
+                cutoff: meta.cutoff,
+                thickness: meta.thickness,
+                restrictions: String::new(),
+            });
+        }
+        if let Some(meta) = &self.embedded_atom_meta {
+            let species: Vec<String> = meta.potential.species().iter().map(|s| species_label(*s)).collect();
+            summaries.push(PotentialSummary {
+                kind: "embedded atom".to_string(),
+                potential: meta.potential.describe(),
+                species: species.join(", "),
+                cutoff: meta.potential.cutoff(),
+                thickness: meta.thickness,
+                restrictions: String::new(),
+            });
+        }
+        if let Some(meta) = &self.external_meta {
+            summaries.push(PotentialSummary {
+                kind: "external".to_string(),
+                potential: meta.potential.describe(),
+                species: "all".to_string(),
+                cutoff: meta.potential.cutoff(),
+                thickness: meta.thickness,
+                restrictions: String::new(),
+            });
+        }
+        summaries
+    }
+
+    /// Returns the species pairs of `system` which no potential acts between and the pair
+    /// potentials which act on no pair of its atoms.
+    ///
+    /// The `velvet run` command prints any warnings along with the [`summary`](Potentials::summary)
+    /// before a run starts.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    /// use nalgebra::Vector3;
+    ///
+    /// let argon = Species::from_element(Element::Ar);
+    /// let xenon = Species::from_element(Element::Xe);
+    /// let system = System {
+    ///     size: 2,
+    ///     cell: Cell::cubic(10.0),
+    ///     species: vec![argon, xenon],
+    ///     positions: vec![Vector3::zeros(), Vector3::new(4.0, 0.0, 0.0)],
+    ///     velocities: vec![Vector3::zeros(); 2],
+    ///     topology: Topology::default(),
+    /// };
+    /// // the argon-xenon interaction was forgotten
+    /// let potentials = PotentialsBuilder::new()
+    ///     .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+    ///     .build();
+    ///
+    /// let coverage = potentials.coverage(&system);
+    /// assert_eq!(coverage.uncovered, vec![(argon, xenon)]);
+    /// assert_eq!(coverage.unused, vec![(argon, argon)]);
+    /// ```
+    pub fn coverage(&self, system: &System) -> PairCoverage {
+        let mut species: Vec<Species> = Vec::new();
+        let mut counts: Vec<usize> = Vec::new();
+        let mut charged: Vec<bool> = Vec::new();
+        for (i, s) in system.species.iter().enumerate() {
+            let index = match species.iter().position(|x| x == s) {
+                Some(index) => index,
+                None => {
+                    species.push(*s);
+                    counts.push(0);
+                    charged.push(false);
+                    species.len() - 1
+                }
+            };
+            counts[index] += 1;
+            charged[index] |= system.charge(i).abs() > Float::EPSILON;
+        }
+        let count = |s: Species| species.iter().position(|x| *x == s).map_or(0, |index| counts[index]);
+        let matches = |a: Species, b: Species, pair: (Species, Species)| pair == (a, b) || pair == (b, a);
+
+        let mut coverage = PairCoverage::default();
+        for (i, &a) in species.iter().enumerate() {
+            for (j, &b) in species.iter().enumerate().skip(i) {
+                // a single atom of a species has no partner of its own species
+                if i == j && counts[i] < 2 {
+                    continue;
+                }
+                let pair = self.pair_metas.iter().any(|meta| matches(a, b, meta.species));
+                let coulomb = self.coulomb_meta.is_some() && (charged[i] || charged[j]);
+                let embedded = self
+                    .embedded_atom_meta
+                    .as_ref()
+                    .is_some_and(|meta| meta.potential.species().contains(&a) && meta.potential.species().contains(&b));
+                if !(pair || coulomb || embedded || self.external_meta.is_some()) {
+                    coverage.uncovered.push((a, b));
+                }
+            }
+        }
+        for meta in &self.pair_metas {
+            let (a, b) = meta.species;
+            let present = if a == b { count(a) >= 2 } else { count(a) >= 1 && count(b) >= 1 };
+            if !present && !coverage.unused.iter().any(|pair| matches(a, b, *pair)) {
+                coverage.unused.push(meta.species);
+            }
+        }
+        coverage
+    }
+
+    /// Returns a human readable table of every configured potential followed by the settings
+    /// shared between them.
+    ///
+    /// The `velvet run` command prints the summary before a run starts, so mistakes such as a
+    /// missing species pair or an unintended cutoff are visible before the run gets underway.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    ///
+    /// let argon = Species::from_element(Element::Ar);
+    /// let potentials = PotentialsBuilder::new()
+    ///     .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+    ///     .build();
+    /// assert!(potentials.summary().contains("Ar-Ar"));
+    /// ```
+    pub fn summary(&self) -> String {
+        let header = ["kind", "potential", "species", "cutoff", "skin", "restrictions"];
+        let rows: Vec<[String; 6]> = self
+            .summaries()
+            .into_iter()
+            .map(|s| {
+                [
+                    s.kind,
+                    s.potential,
+                    s.species,
+                    s.cutoff.to_string(),
+                    s.thickness.to_string(),
+                    s.restrictions,
+                ]
+            })
+            .collect();
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row.iter()) {
+                *width = (*width).max(cell.len());
+            }
+        }
+        let mut table = String::new();
+        let mut write_row = |cells: &[&str]| {
+            let padded: Vec<String> = cells
+                .iter()
+                .zip(widths.iter())
+                .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+                .collect();
+            writeln!(table, "{}", padded.join("  ").trim_end()).unwrap();
+        };
+        write_row(&header);
+        for row in &rows {
+            write_row(&row.iter().map(String::as_str).collect::<Vec<&str>>());
+        }
+        if rows.is_empty() {
+            writeln!(table, "no potentials").unwrap();
+        }
+
+        match &self.adaptive_skin {
+            Some(skin) => writeln!(table, "neighbor lists: adaptive skin with current thickness {}", skin.thickness()),
+            None => writeln!(table, "neighbor lists: rebuilt every {} steps", self.update_frequency),
+        }
+        .unwrap();
+        writeln!(table, "exclusions: pairs separated by at most {} bonds", self.exclusions).unwrap();
+        if let Some(region) = &self.adaptive_resolution {
+            writeln!(table, "adaptive resolution: {}", region).unwrap();
+        }
+        if let Some(cap) = &self.force_cap {
+            writeln!(table, "force cap: {}", cap.max_force()).unwrap();
+        }
+        if !self.biases.is_empty() {
+            writeln!(table, "biases: {}", self.biases.len()).unwrap();
+        }
+        for field in &self.fields {
+            writeln!(table, "field: {}", field.describe()).unwrap();
+        }
+        table
+    }
+
+    fn rebuild(&mut self, system: &System) {
+        // choose a new skin thickness if the adaptive criterion is in use
+        if let Some(skin) = &mut self.adaptive_skin {
+            let thickness = skin.rebuild(system);
+            if let Some(meta) = &mut self.coulomb_meta {
+                meta.thickness = thickness
+            }
+            self.pair_metas
+                .iter_mut()
+                .for_each(|meta| meta.thickness = thickness);
+            if let Some(meta) = &mut self.embedded_atom_meta {
+                meta.thickness = thickness
+            }
+            if let Some(meta) = &mut self.external_meta {
+                meta.thickness = thickness
+            }
+        }
         // update coulomb potential if it exists
-        match &mut self.coulomb_meta {
-            Some(meta) => meta.update(system),
-            None => {}
+        if let Some(meta) = &mut self.coulomb_meta {
+            meta.update(system)
         }
         // update each pair potential
         self.pair_metas
             .iter_mut()
-            .for_each(|meta| meta.update(system))
+            .for_each(|meta| meta.update(system));
+        // update embedded atom potential if it exists
+        if let Some(meta) = &mut self.embedded_atom_meta {
+            meta.update(system)
+        }
+        // update external potential if it exists
+        if let Some(meta) = &mut self.external_meta {
+            meta.update(system)
+        }
     }
 }
 
+/// Constructor for the [`Potentials`] type.
 pub struct PotentialsBuilder {
     coulomb_meta: Option<CoulombPotentialMeta>,
     pair_metas: Vec<PairPotentialMeta>,
+    embedded_atom_meta: Option<EmbeddedAtomMeta>,
+    external_meta: Option<ExternalPotentialMeta>,
     update_frequency: usize,
+    adaptive_skin: Option<AdaptiveSkin>,
+    force_cap: Option<ForceCap>,
+    biases: Vec<Box<dyn Bias>>,
+    fields: Vec<Box<dyn ExternalField>>,
+    adaptive_resolution: Option<AdaptiveResolution>,
+    exclusions: usize,
+    #[cfg(feature = "gpu")]
+    gpu: bool,
 }
 
 impl PotentialsBuilder {
+    /// Returns a new `PotentialsBuilder`.
     pub fn new() -> PotentialsBuilder {
         PotentialsBuilder {
             coulomb_meta: None,
             pair_metas: Vec::new(),
+            embedded_atom_meta: None,
+            external_meta: None,
             update_frequency: 1,
+            adaptive_skin: None,
+            force_cap: None,
+            biases: Vec::new(),
+            fields: Vec::new(),
+            adaptive_resolution: None,
+            exclusions: 0,
+            #[cfg(feature = "gpu")]
+            gpu: false,
         }
     }
 
+    /// Sets the Coulombic potential applied to all charged atoms.
     pub fn coulomb<T>(mut self, potential: T, cutoff: Float, thickness: Float) -> PotentialsBuilder
     where
         T: CoulombPotential + 'static,
@@ -72,6 +894,7 @@ impl PotentialsBuilder {
         self
     }
 
+    /// Adds a pair potential which applies between atoms of the given species.
     pub fn pair<T>(
         mut self,
         potential: T,
@@ -91,16 +914,693 @@ impl PotentialsBuilder {
         self
     }
 
+    /// Adds a coarse-grained pair potential which applies between atoms of the given species.
+    ///
+    /// Coarse-grained pair potentials act outside the atomistic region of an
+    /// [`AdaptiveResolution`] scheme, and have no effect without one.
+    pub fn coarse_pair<T>(
+        mut self,
+        potential: T,
+        species: (Species, Species),
+        cutoff: Float,
+        thickness: Float,
+    ) -> PotentialsBuilder
+    where
+        T: PairPotential + 'static,
+    {
+        let mut meta = PairPotentialMeta::new(potential, species, cutoff, thickness);
+        meta.resolution = Resolution::CoarseGrained;
+        self.pair_metas.push(meta);
+        self
+    }
+
+    /// Sets the embedded atom potential applied between the species it describes.
+    ///
+    /// The embedded atom potential acts alongside any pair potentials, which should not also
+    /// describe the same species.
+    pub fn embedded_atom(mut self, potential: EmbeddedAtom, thickness: Float) -> PotentialsBuilder {
+        self.embedded_atom_meta = Some(EmbeddedAtomMeta::new(potential, thickness));
+        self
+    }
+
+    /// Sets the external potential evaluated for the whole system at once.
+    ///
+    /// The external potential acts alongside every other potential, so a machine-learned model
+    /// which describes all interactions should be used on its own.
+    pub fn external<T>(mut self, potential: T, thickness: Float) -> PotentialsBuilder
+    where
+        T: ExternalPotential + 'static,
+    {
+        self.external_meta = Some(ExternalPotentialMeta::new(potential, thickness));
+        self
+    }
+
+    /// Applies long range tail corrections to the most recently added pair potential.
+    ///
+    /// Truncating a pair potential at its cutoff omits the interactions of every farther pair,
+    /// which systematically offsets the energy and pressure. The tail correction adds these
+    /// contributions analytically assuming a uniform pair distribution beyond the cutoff, which
+    /// suits homogeneous fluids but not interfaces or isolated clusters.
+    pub fn tail_correction(mut self) -> PotentialsBuilder {
+        if let Some(meta) = self.pair_metas.last_mut() {
+            meta.tail = true;
+        }
+        self
+    }
+
+    /// Sets the treatment at the cutoff radius of the most recently added pair potential.
+    ///
+    /// Pair potentials are truncated by default, which makes the energy jump whenever a pair
+    /// crosses the cutoff and causes energy drift in microcanonical simulations.
+    pub fn cutoff_scheme(mut self, scheme: CutoffScheme) -> PotentialsBuilder {
+        if let Some(meta) = self.pair_metas.last_mut() {
+            meta.scheme = scheme;
+        }
+        self
+    }
+
+    /// Sets the number of iterations between neighbor list rebuilds.
     pub fn update_frequency(mut self, freq: usize) -> PotentialsBuilder {
         self.update_frequency = freq;
         self
     }
 
+    /// Rebuilds the neighbor lists based on atomic displacements rather than a fixed frequency.
+    ///
+    /// The skin thickness chosen by the criterion overrides the thickness of each potential.
+    pub fn adaptive_skin(mut self, skin: AdaptiveSkin) -> PotentialsBuilder {
+        self.adaptive_skin = Some(skin);
+        self
+    }
+
+    /// Caps the magnitude of the force on each atom.
+    pub fn force_cap(mut self, cap: ForceCap) -> PotentialsBuilder {
+        self.force_cap = Some(cap);
+        self
+    }
+
+    /// Adds a bias which acts on a collective variable.
+    pub fn bias<T>(mut self, bias: T) -> PotentialsBuilder
+    where
+        T: Bias + 'static,
+    {
+        self.biases.push(Box::new(bias));
+        self
+    }
+
+    /// Adds a field which acts on each atom independently, such as a uniform electric field or gravity.
+    pub fn field<T>(mut self, field: T) -> PotentialsBuilder
+    where
+        T: ExternalField + 'static,
+    {
+        self.fields.push(Box::new(field));
+        self
+    }
+
+    /// Treats a region of the system atomistically with coarse-grained pair potentials elsewhere.
+    pub fn adaptive_resolution(mut self, region: AdaptiveResolution) -> PotentialsBuilder {
+        self.adaptive_resolution = Some(region);
+        self
+    }
+
+    /// Excludes nonbonded interactions between atoms separated by at most `separation` bonds.
+    ///
+    /// Excluded pairs are found from the bonds in the topology of the system. A separation of 1
+    /// excludes bonded pairs, 2 also excludes the ends of each angle, and 3 the ends of each
+    /// dihedral. Ewald summation still includes every excluded pair in its reciprocal space sum,
+    /// so the corresponding correction is subtracted from the long range terms. Defaults to 0,
+    /// which excludes nothing.
+    pub fn exclusions(mut self, separation: usize) -> PotentialsBuilder {
+        self.exclusions = separation;
+        self
+    }
+
+    /// Evaluates the Lennard-Jones, Buckingham and Morse pair potentials on a GPU.
+    ///
+    /// The adapter is acquired when the potentials are set up. Other pair potentials, and every
+    /// pair potential when no adapter supports compute shaders, are evaluated on the CPU. The
+    /// shader runs in single precision.
+    #[cfg(feature = "gpu")]
+    pub fn gpu(mut self) -> PotentialsBuilder {
+        self.gpu = true;
+        self
+    }
+
+    /// Returns an initialized [`Potentials`].
     pub fn build(self) -> Potentials {
         Potentials {
             coulomb_meta: self.coulomb_meta,
             pair_metas: self.pair_metas,
+            embedded_atom_meta: self.embedded_atom_meta,
+            external_meta: self.external_meta,
             update_frequency: self.update_frequency,
+            adaptive_skin: self.adaptive_skin,
+            force_cap: self.force_cap,
+            biases: self.biases,
+            fields: self.fields,
+            adaptive_resolution: self.adaptive_resolution,
+            exclusions: self.exclusions,
+            #[cfg(feature = "gpu")]
+            gpu: self.gpu,
+            #[cfg(feature = "gpu")]
+            gpu_pairs: None,
+            stale: true,
+            bias_cache: Mutex::new(None),
+            constraint_virial: Mutex::new(Matrix3::zeros()),
+        }
+    }
+}
+
+impl Default for PotentialsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AdaptiveSkin, ForceCap, Potentials, PotentialsBuilder};
+    use crate::potentials::adaptive::AdaptiveResolution;
+    use crate::internal::consts::{COULOMB, PI, PRESSURE};
+    use crate::internal::Float;
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::forces::{CappedAtoms, Forces};
+    use crate::properties::pressure::{Pressure, StressTensor};
+    use crate::properties::Property;
+    use crate::potentials::pair::{CutoffScheme, PairPotential};
+    use crate::potentials::types::{Ewald, LennardJones, StandardCoulombic, TabulatedPair};
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::{Matrix3, Vector3};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn argon_dimer() -> System {
+        let argon = Species::from_element(Element::Ar);
+        System {
+            size: 2,
+            cell: Cell::cubic(20.0),
+            species: vec![argon, argon],
+            positions: vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new(9.0, 5.0, 5.0)],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn adaptive_skin() {
+        let mut system = argon_dimer();
+        let argon = system.species[0];
+        let mut potentials = PotentialsBuilder::new()
+            .adaptive_skin(AdaptiveSkin::new(4, 0.1, 1.0))
+            .pair(LennardJones::new(1.0, 3.4), (argon, argon), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+        assert_eq!(potentials.adaptive_skin().unwrap().rebuilds(), 1);
+
+        // small displacements do not trigger a rebuild
+        for i in 0..5 {
+            system.positions[0][0] += 0.05;
+            potentials.update(&system, i);
+        }
+        assert_eq!(potentials.adaptive_skin().unwrap().rebuilds(), 1);
+
+        // exceeding half the skin triggers a rebuild with a skin sized from the observed steps
+        for i in 5..12 {
+            system.positions[0][0] += 0.05;
+            potentials.update(&system, i);
+        }
+        let skin = potentials.adaptive_skin().unwrap();
+        assert_eq!(skin.rebuilds(), 2);
+        assert!((skin.thickness() - 0.4).abs() < 1e-4);
+        assert_eq!(potentials.pair_metas[0].selection.indices().count(), 1);
+    }
+
+    #[test]
+    fn swap_potentials() {
+        let system = argon_dimer();
+        let argon = system.species[0];
+        let mut potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(1.0, 3.4), (argon, argon), 3.0, 0.5)
+            .build();
+        potentials.setup(&system);
+        assert_eq!(potentials.pair_metas[0].selection.indices().count(), 0);
+
+        // replacing the pair potential invalidates the neighbor lists
+        potentials.set_pair(LennardJones::new(0.5, 3.4), (argon, argon), 8.5, 1.0);
+        assert_eq!(potentials.pair_metas.len(), 1);
+        potentials.update(&system, 1);
+        assert_eq!(potentials.pair_metas[0].selection.indices().count(), 1);
+
+        potentials.set_coulomb(StandardCoulombic::new(1.0), 8.5, 1.0);
+        potentials.remove_pair((argon, argon));
+        potentials.update(&system, 2);
+        assert!(potentials.pair_metas.is_empty());
+        assert!(potentials.coulomb_meta.is_some());
+    }
+
+    #[test]
+    fn force_cap() {
+        let mut system = argon_dimer();
+        let argon = system.species[0];
+        // overlapping atoms produce an enormous repulsion
+        system.positions[1] = Vector3::new(6.0, 5.0, 5.0);
+        let mut potentials = PotentialsBuilder::new()
+            .force_cap(ForceCap::new(10.0))
+            .pair(LennardJones::new(1.0, 3.4), (argon, argon), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+        let forces = Forces.calculate(&system, &potentials);
+        assert!((forces[0].norm() - 10.0).abs() < 1e-4);
+        assert!((forces[1].norm() - 10.0).abs() < 1e-4);
+        assert!(forces[0][0] < 0.0);
+        assert_eq!(CappedAtoms.calculate(&system, &potentials), vec![0, 1]);
+
+        // forces below the threshold are untouched
+        system.positions[1] = Vector3::new(9.0, 5.0, 5.0);
+        let forces = Forces.calculate(&system, &potentials);
+        assert!(forces[0].norm() < 10.0);
+        assert!(CappedAtoms.calculate(&system, &potentials).is_empty());
+        assert_eq!(potentials.force_cap().unwrap().total_capped(), 2);
+
+        potentials.remove_force_cap();
+        system.positions[1] = Vector3::new(6.0, 5.0, 5.0);
+        assert!(Forces.calculate(&system, &potentials)[0].norm() > 10.0);
+    }
+
+    // copies of `cell` repeated `n` times along each lattice vector
+    fn replicate(cell: &System, n: usize) -> System {
+        let mut positions = Vec::new();
+        let mut species = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    let shift = cell.cell.matrix() * Vector3::new(i as Float, j as Float, k as Float);
+                    positions.extend(cell.positions.iter().map(|pos| pos + shift));
+                    species.extend(cell.species.iter().copied());
+                }
+            }
+        }
+        System {
+            size: positions.len(),
+            cell: Cell::from_matrix(cell.cell.matrix() * n as Float),
+            species,
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
         }
     }
+
+    #[test]
+    fn small_cells() {
+        // distorted conventional fcc argon cell much smaller than the cutoff
+        let argon = Species::from_element(Element::Ar);
+        let a = 5.26;
+        let small = System {
+            size: 4,
+            cell: Cell::cubic(a),
+            species: vec![argon; 4],
+            positions: vec![
+                Vector3::new(0.1, 0.0, -0.05),
+                Vector3::new(0.5 * a, 0.5 * a - 0.1, 0.0),
+                Vector3::new(0.5 * a + 0.15, 0.0, 0.5 * a),
+                Vector3::new(0.0, 0.5 * a, 0.5 * a + 0.1),
+            ],
+            velocities: vec![Vector3::zeros(); 4],
+            topology: Topology::default(),
+        };
+        let large = replicate(&small, 4);
+        let build = || {
+            PotentialsBuilder::new()
+                .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+                .build()
+        };
+        let mut small_potentials = build();
+        small_potentials.setup(&small);
+        let mut large_potentials = build();
+        large_potentials.setup(&large);
+        assert!(!small_potentials.pair_metas[0].images.is_empty());
+        assert!(large_potentials.pair_metas[0].images.is_empty());
+
+        // every replica of an atom feels the same environment
+        let energy = PotentialEnergy.calculate(&small, &small_potentials);
+        let expected = PotentialEnergy.calculate(&large, &large_potentials) / 64.0;
+        assert!((energy - expected).abs() < 1e-4 * expected.abs());
+        let forces = Forces.calculate(&small, &small_potentials);
+        let expected = Forces.calculate(&large, &large_potentials);
+        for (force, expected) in forces.iter().zip(expected.iter()) {
+            assert!((force - expected).norm() < 1e-3 * expected.norm().max(1.0));
+        }
+        let stress = StressTensor.calculate(&small, &small_potentials);
+        let expected = StressTensor.calculate(&large, &large_potentials);
+        assert!((stress - expected).norm() < 1e-3 * expected.norm());
+
+        // a single atom in the rhombohedral primitive cell interacts only with its own images
+        let primitive = System {
+            size: 1,
+            cell: Cell::from_vectors(
+                Vector3::new(0.0, 0.5 * a, 0.5 * a),
+                Vector3::new(0.5 * a, 0.0, 0.5 * a),
+                Vector3::new(0.5 * a, 0.5 * a, 0.0),
+            ),
+            species: vec![argon],
+            positions: vec![Vector3::new(1.0, 2.0, 3.0)],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let mut potentials = build();
+        potentials.setup(&primitive);
+        let conventional = replicate(
+            &System {
+                size: 4,
+                positions: vec![
+                    Vector3::zeros(),
+                    Vector3::new(0.5 * a, 0.5 * a, 0.0),
+                    Vector3::new(0.5 * a, 0.0, 0.5 * a),
+                    Vector3::new(0.0, 0.5 * a, 0.5 * a),
+                ],
+                ..small.clone()
+            },
+            4,
+        );
+        let mut large_potentials = build();
+        large_potentials.setup(&conventional);
+        let energy = PotentialEnergy.calculate(&primitive, &potentials);
+        let expected = PotentialEnergy.calculate(&conventional, &large_potentials) / 256.0;
+        assert!((energy - expected).abs() < 1e-4 * expected.abs());
+        assert!(Forces.calculate(&primitive, &potentials)[0].norm() < 1e-4);
+    }
+
+    #[test]
+    fn small_cell_ewald() {
+        // conventional rock salt cell with a real space cutoff of twice its width
+        let a0: Float = 5.64;
+        let sodium = Species::new(Element::Na.mass(), 1.0);
+        let chlorine = Species::new(Element::Cl.mass(), -1.0);
+        let mut species = Vec::new();
+        let mut positions = Vec::new();
+        for site in [[0.0, 0.0, 0.0], [0.0, 0.5, 0.5], [0.5, 0.0, 0.5], [0.5, 0.5, 0.0]].iter() {
+            species.push(sodium);
+            positions.push(Vector3::new(site[0], site[1], site[2]) * a0);
+            species.push(chlorine);
+            positions.push(Vector3::new(site[0] + 0.5, site[1], site[2]) * a0);
+        }
+        let system = System {
+            size: 8,
+            cell: Cell::cubic(a0),
+            species,
+            positions,
+            velocities: vec![Vector3::zeros(); 8],
+            topology: Topology::default(),
+        };
+        let mut potentials = PotentialsBuilder::new()
+            .coulomb(Ewald::new(0.5, 10), 11.0, 0.5)
+            .build();
+        potentials.setup(&system);
+        let energy = PotentialEnergy.calculate(&system, &potentials);
+        let madelung = -4.0 * COULOMB * 1.747565 / 2.82;
+        assert!((energy - madelung).abs() < 1e-3 * madelung.abs());
+    }
+
+    #[test]
+    fn exclusions() {
+        // randomly placed and oriented polar dimers bonded internally
+        let mut rng = StdRng::seed_from_u64(13);
+        let cation = Species::new(1.0, 0.5);
+        let anion = Species::new(16.0, -0.5);
+        let mut species = Vec::new();
+        let mut positions = Vec::new();
+        let mut bonds = Vec::new();
+        for n in 0..6 {
+            let center = Vector3::new(rng.gen_range(0.0, 12.0), rng.gen_range(0.0, 12.0), rng.gen_range(0.0, 12.0));
+            let axis: Vector3<Float> = Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)).normalize();
+            species.extend([cation, anion].iter());
+            positions.push(center - 0.5 * axis);
+            positions.push(center + 0.5 * axis);
+            bonds.push([2 * n, 2 * n + 1]);
+        }
+        let system = System {
+            size: 12,
+            cell: Cell::cubic(12.0),
+            species,
+            positions,
+            velocities: vec![Vector3::zeros(); 12],
+            topology: Topology {
+                bonds,
+                ..Topology::default()
+            },
+        };
+
+        // excluding the bonded pairs removes exactly their bare interactions
+        let lj = LennardJones::new(0.1, 0.8);
+        let bare_energy = lj.energy(1.0) + COULOMB * 0.5 * -0.5;
+        let bare_force = lj.force(1.0) - COULOMB * 0.5 * -0.5;
+        for &cutoff in [5.5, 8.0].iter() {
+            let build = |exclusions: usize| {
+                let mut potentials = PotentialsBuilder::new()
+                    .pair(lj, (cation, cation), cutoff, 0.5)
+                    .pair(lj, (cation, anion), cutoff, 0.5)
+                    .pair(lj, (anion, anion), cutoff, 0.5)
+                    .coulomb(Ewald::new(0.35, 8), cutoff, 0.5)
+                    .exclusions(exclusions)
+                    .build();
+                potentials.setup(&system);
+                potentials
+            };
+            let (full, excluded) = (build(0), build(1));
+            assert_eq!(excluded.exclusions(), 1);
+            let energy = PotentialEnergy.calculate(&system, &full) - 6.0 * bare_energy;
+            assert!((PotentialEnergy.calculate(&system, &excluded) - energy).abs() < 1e-2);
+
+            let full_forces = Forces.calculate(&system, &full);
+            let forces = Forces.calculate(&system, &excluded);
+            let mut virial = Matrix3::zeros();
+            for n in 0..6 {
+                let (i, j) = (2 * n, 2 * n + 1);
+                let dir = system.positions[j] - system.positions[i];
+                assert!((forces[i] - (full_forces[i] - bare_force * dir)).norm() < 1e-2);
+                assert!((forces[j] - (full_forces[j] + bare_force * dir)).norm() < 1e-2);
+                virial += bare_force * dir * dir.transpose();
+            }
+            let stress = StressTensor.calculate(&system, &full) + virial * PRESSURE / system.cell.volume();
+            let difference = StressTensor.calculate(&system, &excluded) - stress;
+            assert!(difference.norm() < 1e-4 * stress.norm(), "{}", difference);
+        }
+    }
+
+    #[test]
+    fn tail_correction() {
+        // disordered argon packing without overlaps whose pair distribution is nearly uniform at long range
+        let mut rng = StdRng::seed_from_u64(19);
+        let argon = Species::from_element(Element::Ar);
+        let cell = Cell::cubic(21.0);
+        let mut positions: Vec<Vector3<Float>> = Vec::new();
+        while positions.len() < 180 {
+            let candidate = Vector3::new(rng.gen_range(0.0, 21.0), rng.gen_range(0.0, 21.0), rng.gen_range(0.0, 21.0));
+            if positions.iter().all(|pos| cell.distance(pos, &candidate) > 3.0) {
+                positions.push(candidate);
+            }
+        }
+        let system = System {
+            size: positions.len(),
+            cell,
+            species: vec![argon; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        };
+        let lj = LennardJones::new(0.238, 3.4);
+        let evaluate = |cutoff: Float, tail: bool| {
+            let mut builder = PotentialsBuilder::new().pair(lj, (argon, argon), cutoff, 0.5);
+            if tail {
+                builder = builder.tail_correction();
+            }
+            let mut potentials = builder.build();
+            potentials.setup(&system);
+            (
+                PotentialEnergy.calculate(&system, &potentials),
+                Pressure.calculate(&system, &potentials),
+            )
+        };
+        let (energy, pressure) = evaluate(5.5, false);
+        let (tail_energy, tail_pressure) = evaluate(5.5, true);
+        let (reference_energy, reference_pressure) = evaluate(10.0, true);
+
+        // the correction matches the analytic expression and makes the result nearly independent of the cutoff
+        let density = system.size as Float / system.cell.volume();
+        let expected = 2.0 * PI * density * system.size as Float * lj.tail_integral(5.5);
+        assert!((tail_energy - energy - expected).abs() < 1e-3 * expected.abs());
+        assert!(tail_energy < energy && tail_pressure < pressure);
+        assert!((tail_energy - reference_energy).abs() < 0.05 * (energy - reference_energy).abs());
+        assert!((tail_pressure - reference_pressure).abs() < 0.05 * (pressure - reference_pressure).abs());
+    }
+
+    #[test]
+    fn cutoff_schemes() {
+        let argon = Species::from_element(Element::Ar);
+        let lj = LennardJones::new(0.238, 3.4);
+        // energy of the argon dimer at a separation on either side of the cutoff
+        let jump = |scheme: CutoffScheme| {
+            let mut potentials = PotentialsBuilder::new()
+                .pair(lj, (argon, argon), 8.5, 1.0)
+                .cutoff_scheme(scheme)
+                .build();
+            let mut system = argon_dimer();
+            let energy = |system: &mut System, potentials: &mut Potentials, r: Float| {
+                system.positions[1] = system.positions[0] + Vector3::new(r, 0.0, 0.0);
+                potentials.setup(system);
+                PotentialEnergy.calculate(system, potentials)
+            };
+            energy(&mut system, &mut potentials, 8.4999) - energy(&mut system, &mut potentials, 8.5001)
+        };
+        assert!(jump(CutoffScheme::Truncated).abs() > 1e-3);
+        assert!(jump(CutoffScheme::Shifted).abs() < 1e-5);
+        assert!(jump(CutoffScheme::Switched(7.5)).abs() < 1e-6);
+
+        // schemes can be changed after the potentials are built
+        let mut potentials = PotentialsBuilder::new().pair(lj, (argon, argon), 8.5, 1.0).build();
+        let mut system = argon_dimer();
+        system.positions[1] = system.positions[0] + Vector3::new(8.0, 0.0, 0.0);
+        potentials.setup(&system);
+        let truncated = PotentialEnergy.calculate(&system, &potentials);
+        potentials.set_cutoff_scheme((argon, argon), CutoffScheme::Shifted);
+        let shifted = PotentialEnergy.calculate(&system, &potentials);
+        assert!((truncated - shifted - lj.energy(8.5)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn adaptive_resolution() {
+        let argon = Species::from_element(Element::Ar);
+        let atomistic = LennardJones::new(0.238, 3.4);
+        let coarse = LennardJones::new(0.1, 3.8);
+        let mut system = argon_dimer();
+        // the first atom is atomistic and the second halfway across the hybrid region
+        let region = AdaptiveResolution::slab(Vector3::new(5.0, 0.0, 0.0), 0, 1.0, 6.0);
+        let mut potentials = PotentialsBuilder::new()
+            .pair(atomistic, (argon, argon), 8.5, 1.0)
+            .coarse_pair(coarse, (argon, argon), 8.5, 1.0)
+            .adaptive_resolution(region)
+            .build();
+        potentials.setup(&system);
+        let energy = PotentialEnergy.calculate(&system, &potentials);
+        let forces = Forces.calculate(&system, &potentials);
+        assert!((energy - 0.5 * (atomistic.energy(4.0) + coarse.energy(4.0))).abs() < 1e-5);
+        assert!((forces[0].x - 0.5 * (atomistic.force(4.0) + coarse.force(4.0))).abs() < 1e-5);
+        assert!((forces[0] + forces[1]).norm() < 1e-6);
+
+        // both atoms in the coarse-grained region
+        potentials.set_adaptive_resolution(AdaptiveResolution::slab(Vector3::new(15.0, 0.0, 0.0), 0, 1.0, 1.0));
+        assert!((PotentialEnergy.calculate(&system, &potentials) - coarse.energy(4.0)).abs() < 1e-6);
+
+        // every atom is atomistic without a region
+        potentials.remove_adaptive_resolution();
+        assert!(potentials.adaptive_resolution().is_none());
+        assert!((PotentialEnergy.calculate(&system, &potentials) - atomistic.energy(4.0)).abs() < 1e-6);
+
+        // atomistic and coarse-grained potentials are replaced independently
+        let softer = LennardJones::new(0.2, 3.4);
+        potentials.set_pair(softer, (argon, argon), 8.5, 1.0);
+        potentials.set_coarse_pair(coarse, (argon, argon), 8.5, 1.0);
+        assert_eq!(potentials.pair_metas.len(), 2);
+        potentials.setup(&system);
+        assert!((PotentialEnergy.calculate(&system, &potentials) - softer.energy(4.0)).abs() < 1e-6);
+        potentials.remove_pair((argon, argon));
+        assert!(potentials.pair_metas.is_empty());
+
+        // the pair is moved so both atoms sit in the atomistic region
+        system.positions[1] = Vector3::new(6.0, 8.0, 5.0);
+        let mut potentials = PotentialsBuilder::new()
+            .pair(atomistic, (argon, argon), 8.5, 1.0)
+            .coarse_pair(coarse, (argon, argon), 8.5, 1.0)
+            .adaptive_resolution(region)
+            .build();
+        potentials.setup(&system);
+        let r = system.cell.distance(&system.positions[0], &system.positions[1]);
+        assert!((PotentialEnergy.calculate(&system, &potentials) - atomistic.energy(r)).abs() < 1e-6);
+    }
+
+    #[test]
+    fn summary() {
+        let argon = Species::from_element(Element::Ar);
+        let bead = Species::new(72.0, 0.0);
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+            .cutoff_scheme(CutoffScheme::Shifted)
+            .tail_correction()
+            .coarse_pair(TabulatedPair::from_energies(3.0, 0.5, vec![1.0, 0.5, 0.0]), (bead, argon), 4.0, 1.0)
+            .coulomb(StandardCoulombic::new(1.0), 10.0, 1.0)
+            .adaptive_resolution(AdaptiveResolution::sphere(Vector3::zeros(), 5.0, 2.0))
+            .exclusions(3)
+            .build();
+
+        let summaries = potentials.summaries();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].potential, "LennardJones { epsilon: 0.238, sigma: 3.4 }");
+        assert_eq!(summaries[0].species, "Ar-Ar");
+        assert_eq!(summaries[0].restrictions, "shifted, tail correction, atomistic");
+        assert_eq!(summaries[1].potential, "TabulatedPair { start: 3.0, end: 4.0, samples: 3 }");
+        assert_eq!(summaries[1].species, "custom(72)-Ar");
+        assert_eq!(summaries[1].restrictions, "truncated, coarsegrained");
+        assert_eq!(summaries[2].kind, "coulomb");
+        assert_eq!(summaries[2].cutoff, 10.0);
+
+        let table = potentials.summary();
+        let lines: Vec<&str> = table.lines().collect();
+        assert!(lines[0].starts_with("kind     potential"));
+        assert!(lines[1].starts_with("pair     LennardJones"));
+        assert!(lines[3].starts_with("coulomb  StandardCoulombic { dielectric: 1.0 }"));
+        // columns line up across rows
+        assert_eq!(lines[1].find("Ar-Ar"), lines[3].find("charged"));
+        assert!(table.contains("exclusions: pairs separated by at most 3 bonds"));
+        assert!(table.contains("adaptive resolution: sphere centered at [0, 0, 0] with atomistic radius 5 and hybrid width 2"));
+    }
+
+    #[test]
+    fn coverage() {
+        let sodium = Species::new(22.99, 1.0);
+        let chlorine = Species::new(35.45, -1.0);
+        let argon = Species::from_element(Element::Ar);
+        let xenon = Species::from_element(Element::Xe);
+        let system = System {
+            size: 4,
+            cell: Cell::cubic(20.0),
+            species: vec![sodium, chlorine, argon, argon],
+            positions: vec![
+                Vector3::new(1.0, 1.0, 1.0),
+                Vector3::new(4.0, 1.0, 1.0),
+                Vector3::new(1.0, 6.0, 1.0),
+                Vector3::new(1.0, 10.0, 1.0),
+            ],
+            velocities: vec![Vector3::zeros(); 4],
+            topology: Topology::default(),
+        };
+
+        // xenon typed in place of argon
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (xenon, argon), 8.5, 1.0)
+            .pair(LennardJones::new(0.1, 3.0), (sodium, sodium), 8.5, 1.0)
+            .build();
+        let coverage = potentials.coverage(&system);
+        assert!(!coverage.is_complete());
+        // a lone sodium atom has no sodium partner
+        assert_eq!(coverage.unused, vec![(xenon, argon), (sodium, sodium)]);
+        assert_eq!(coverage.uncovered.len(), 4);
+        assert!(coverage.uncovered.contains(&(argon, argon)));
+        assert!(!coverage.uncovered.iter().any(|(a, b)| *a == xenon || *b == xenon));
+        let warnings = coverage.to_string();
+        assert!(warnings.contains("no potential acts between Ar-Ar pairs"));
+        assert!(warnings.contains("the Xe-Ar pair potential matches no pairs"));
+
+        // the coulomb potential covers every pair with a charged atom
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+            .coulomb(StandardCoulombic::new(1.0), 10.0, 1.0)
+            .build();
+        let coverage = potentials.coverage(&system);
+        assert!(coverage.is_complete());
+        assert_eq!(coverage.to_string(), "");
+    }
 }
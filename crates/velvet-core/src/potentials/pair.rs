@@ -1,95 +1,308 @@
 //! Potentials which describe pairwise nonbonded interactions..
 
+#[cfg(any(feature = "gpu", feature = "simd"))]
+use std::any::Any;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::internal::consts::{COULOMB, PI};
 use crate::internal::Float;
-use crate::potentials::types::{Buckingham, Harmonic, LennardJones, Mie, Morse};
+use crate::potentials::adaptive::Resolution;
+use crate::potentials::types::{Buckingham, Harmonic, LennardJones, Mie, Morse, TabulatedPair, Zbl, ZblSpline};
 use crate::potentials::Potential;
-use crate::selection::{setup_pairs_by_species, update_pairs_by_cutoff_radius, Selection};
+use crate::precision::Real;
+use crate::selection::{setup_pairs_by_species, update_excluded_image_pairs, update_image_pairs, update_pairs_by_cutoff_radius, ImagePair, Selection};
 use crate::system::species::Species;
 use crate::system::System;
 
 /// Shared behavior for pair potentials.
-pub trait PairPotential: Potential {
+///
+/// The analytic forms are implemented in every [`Real`] precision, so a potential with
+/// parameters in the default precision can be evaluated at separations of another.
+/// Tabulated forms are only implemented in the default precision.
+pub trait PairPotential<R: Real = Float>: Potential {
     /// Returns the potential energy of an atom in a pair separated by a distance `r`.
-    fn energy(&self, r: Float) -> Float;
+    fn energy(&self, r: R) -> R;
     /// Returns the magnitude of the force acting on an atom separated from another by a distance `r`.
-    fn force(&self, r: Float) -> Float;
+    fn force(&self, r: R) -> R;
+    /// Returns the integral of `r^2 u(r)` from `cutoff` to infinity used by long range tail corrections.
+    ///
+    /// Potentials without a finite tail return zero.
+    fn tail_integral(&self, _cutoff: R) -> R {
+        R::zero()
+    }
 }
 
-impl PairPotential for Buckingham {
+// Returns the integral of `r^2 exp(-k (r - cutoff))` from `cutoff` to infinity.
+fn exponential_tail<R: Real>(k: R, cutoff: R) -> R {
+    let two = R::from_double(2.0);
+    cutoff.powi(2) / k + two * cutoff / k.powi(2) + two / k.powi(3)
+}
+
+impl<R: Real> PairPotential<R> for Buckingham {
     #[inline]
-    fn energy(&self, r: Float) -> Float {
-        self.a * Float::exp(-r / self.rho) - (self.c / r.powi(6))
+    fn energy(&self, r: R) -> R {
+        let (a, rho, c) = (R::from_float(self.a), R::from_float(self.rho), R::from_float(self.c));
+        a * (-r / rho).exp() - (c / r.powi(6))
     }
 
     #[inline]
-    fn force(&self, r: Float) -> Float {
-        let term_a = (6.0 * self.c) / r.powi(7);
-        let term_b = (self.a * Float::exp(-r / self.rho)) / self.rho;
+    fn force(&self, r: R) -> R {
+        let (a, rho, c) = (R::from_float(self.a), R::from_float(self.rho), R::from_float(self.c));
+        let term_a = (R::from_double(6.0) * c) / r.powi(7);
+        let term_b = (a * (-r / rho).exp()) / rho;
         term_a - term_b
     }
+
+    fn tail_integral(&self, cutoff: R) -> R {
+        let (a, rho, c) = (R::from_float(self.a), R::from_float(self.rho), R::from_float(self.c));
+        let repulsion = a * (-cutoff / rho).exp() * exponential_tail(R::one() / rho, cutoff);
+        repulsion - c / (R::from_double(3.0) * cutoff.powi(3))
+    }
 }
 
-impl PairPotential for Harmonic {
+impl<R: Real> PairPotential<R> for Harmonic {
     #[inline]
-    fn energy(&self, r: Float) -> Float {
-        let dr = r - self.x0;
-        self.k * dr * dr
+    fn energy(&self, r: R) -> R {
+        let dr = r - R::from_float(self.x0);
+        R::from_float(self.k) * dr * dr
     }
 
     #[inline]
-    fn force(&self, r: Float) -> Float {
-        2.0 * self.k * (r - self.x0)
+    fn force(&self, r: R) -> R {
+        R::from_double(2.0) * R::from_float(self.k) * (r - R::from_float(self.x0))
+    }
+}
+
+impl<R: Real> PairPotential<R> for LennardJones {
+    #[inline]
+    fn energy(&self, r: R) -> R {
+        let (epsilon, sigma) = (R::from_float(self.epsilon), R::from_float(self.sigma));
+        let term = (sigma / r).powi(6);
+        R::from_double(4.0) * epsilon * (term * term - term)
+    }
+
+    #[inline]
+    fn force(&self, r: R) -> R {
+        let (epsilon, sigma) = (R::from_float(self.epsilon), R::from_float(self.sigma));
+        let term_a = (R::from_double(24.0) * sigma.powi(6)) / r.powi(7);
+        let term_b = (R::from_double(48.0) * sigma.powi(12)) / r.powi(13);
+        epsilon * (term_a - term_b)
+    }
+
+    fn tail_integral(&self, cutoff: R) -> R {
+        let (epsilon, sigma) = (R::from_float(self.epsilon), R::from_float(self.sigma));
+        let term = (sigma / cutoff).powi(3);
+        R::from_double(4.0) * epsilon * sigma.powi(3) * (term.powi(3) / R::from_double(9.0) - term / R::from_double(3.0))
     }
 }
 
-impl PairPotential for LennardJones {
+impl Mie {
+    // Returns the parameters in another precision and the prefactor which sets the well depth to epsilon.
+    fn coefficients<R: Real>(&self) -> (R, R, R, R, R) {
+        let (epsilon, sigma) = (R::from_float(self.epsilon), R::from_float(self.sigma));
+        let (gamma_r, gamma_a) = (R::from_float(self.gamma_r), R::from_float(self.gamma_a));
+        let c = (gamma_r / (gamma_r - gamma_a)) * (gamma_r / gamma_a).powf(gamma_a / (gamma_r - gamma_a));
+        (epsilon, sigma, gamma_r, gamma_a, c)
+    }
+}
+
+impl<R: Real> PairPotential<R> for Mie {
+    #[inline]
+    fn energy(&self, r: R) -> R {
+        let (epsilon, sigma, gamma_r, gamma_a, c) = self.coefficients::<R>();
+        let term_a = (sigma / r).powf(gamma_r);
+        let term_b = (sigma / r).powf(gamma_a);
+        c * epsilon * (term_a - term_b)
+    }
+
+    #[inline]
+    fn force(&self, r: R) -> R {
+        let (epsilon, sigma, gamma_r, gamma_a, c) = self.coefficients::<R>();
+        let term_a = (c * gamma_a * epsilon * (sigma / r).powf(gamma_a)) / r;
+        let term_b = (c * gamma_r * epsilon * (sigma / r).powf(gamma_r)) / r;
+        term_a - term_b
+    }
+
+    fn tail_integral(&self, cutoff: R) -> R {
+        let (epsilon, sigma, gamma_r, gamma_a, c) = self.coefficients::<R>();
+        let three = R::from_double(3.0);
+        let term_a = (sigma / cutoff).powf(gamma_r - three) / (gamma_r - three);
+        let term_b = (sigma / cutoff).powf(gamma_a - three) / (gamma_a - three);
+        c * epsilon * sigma.powi(3) * (term_a - term_b)
+    }
+}
+
+impl<R: Real> PairPotential<R> for Morse {
+    #[inline]
+    fn energy(&self, r: R) -> R {
+        let (a, d_e, r_e) = (R::from_float(self.a), R::from_float(self.d_e), R::from_float(self.r_e));
+        let two = R::from_double(2.0);
+        let term_a = (-two * a * (r - r_e)).exp();
+        let term_b = two * (-a * (r - r_e)).exp();
+        d_e * (term_a - term_b)
+    }
+
+    #[inline]
+    fn force(&self, r: R) -> R {
+        let (a, d_e, r_e) = (R::from_float(self.a), R::from_float(self.d_e), R::from_float(self.r_e));
+        let two = R::from_double(2.0);
+        let term_a = (-a * (r - r_e)).exp();
+        let term_b = (-two * a * (r - r_e)).exp();
+        two * a * d_e * (term_a - term_b)
+    }
+
+    fn tail_integral(&self, cutoff: R) -> R {
+        let (a, d_e, r_e) = (R::from_float(self.a), R::from_float(self.d_e), R::from_float(self.r_e));
+        let two = R::from_double(2.0);
+        let term_a = (-two * a * (cutoff - r_e)).exp() * exponential_tail(two * a, cutoff);
+        let term_b = two * (-a * (cutoff - r_e)).exp() * exponential_tail(a, cutoff);
+        d_e * (term_a - term_b)
+    }
+}
+
+impl PairPotential for TabulatedPair {
     #[inline]
     fn energy(&self, r: Float) -> Float {
-        let term = (self.sigma / r).powi(6);
-        4.0 * self.epsilon * (term * term - term)
+        if r < self.start {
+            self.energy.eval(self.start) + self.force.eval(self.start) * (r - self.start)
+        } else if r > self.end {
+            0.0
+        } else {
+            self.energy.eval(r)
+        }
     }
 
     #[inline]
     fn force(&self, r: Float) -> Float {
-        let term_a = (24.0 * self.sigma.powi(6)) / r.powi(7);
-        let term_b = (48.0 * self.sigma.powi(12)) / r.powi(13);
-        self.epsilon * (term_a - term_b)
+        if r < self.start {
+            self.force.eval(self.start)
+        } else if r > self.end {
+            0.0
+        } else {
+            self.force.eval(r)
+        }
     }
 }
 
-impl PairPotential for Mie {
+/// Coefficients and exponents of the universal ZBL screening function.
+const ZBL_SCREENING: [(Float, Float); 4] = [
+    (0.18175, 3.19980),
+    (0.50986, 0.94229),
+    (0.28022, 0.40290),
+    (0.02817, 0.20162),
+];
+
+impl Zbl {
+    // Returns the screening length of the pair.
+    fn screening_length(&self) -> Float {
+        0.46850 / (self.z_i.powf(0.23) + self.z_j.powf(0.23))
+    }
+}
+
+impl PairPotential for Zbl {
     #[inline]
     fn energy(&self, r: Float) -> Float {
-        let term_a = (self.sigma / r).powf(self.gamma_r);
-        let term_b = (self.sigma / r).powf(self.gamma_a);
-        let c = (self.gamma_r / (self.gamma_r - self.gamma_a))
-            * (self.gamma_r / self.gamma_a).powf(self.gamma_a / (self.gamma_r - self.gamma_a));
-        c * self.epsilon * (term_a - term_b)
+        let x = r / self.screening_length();
+        let phi: Float = ZBL_SCREENING.iter().map(|(c, d)| c * Float::exp(-d * x)).sum();
+        COULOMB * self.z_i * self.z_j * phi / r
     }
 
     #[inline]
     fn force(&self, r: Float) -> Float {
-        let c = (self.gamma_r / (self.gamma_r - self.gamma_a))
-            * (self.gamma_r / self.gamma_a).powf(self.gamma_a / (self.gamma_r - self.gamma_a));
-        let term_a = (c * self.gamma_a * self.epsilon * (self.sigma / r).powf(self.gamma_a)) / r;
-        let term_b = (c * self.gamma_r * self.epsilon * (self.sigma / r).powf(self.gamma_r)) / r;
-        term_a - term_b
+        let a = self.screening_length();
+        let x = r / a;
+        let phi: Float = ZBL_SCREENING.iter().map(|(c, d)| c * Float::exp(-d * x)).sum();
+        let dphi: Float = ZBL_SCREENING.iter().map(|(c, d)| -c * d * Float::exp(-d * x)).sum();
+        COULOMB * self.z_i * self.z_j * (dphi / (a * r) - phi / r.powi(2))
+    }
+}
+
+impl<T> ZblSpline<T> {
+    // Returns the weight of the ZBL potential and its derivative with respect to the separation.
+    fn weight(&self, r: Float) -> (Float, Float) {
+        if r <= self.inner {
+            return (1.0, 0.0);
+        }
+        if r >= self.outer {
+            return (0.0, 0.0);
+        }
+        let width = self.outer - self.inner;
+        let t = (r - self.inner) / width;
+        (1.0 - t * t * (3.0 - 2.0 * t), -6.0 * t * (1.0 - t) / width)
     }
 }
 
-impl PairPotential for Morse {
+impl<T: PairPotential> PairPotential for ZblSpline<T> {
     #[inline]
     fn energy(&self, r: Float) -> Float {
-        let term_a = Float::exp(-2.0 * self.a * (r - self.r_e));
-        let term_b = 2.0 * Float::exp(-self.a * (r - self.r_e));
-        self.d_e * (term_a - term_b)
+        let (w, _) = self.weight(r);
+        if w == 0.0 {
+            self.potential.energy(r)
+        } else if w == 1.0 {
+            self.zbl.energy(r)
+        } else {
+            w * self.zbl.energy(r) + (1.0 - w) * self.potential.energy(r)
+        }
     }
 
     #[inline]
     fn force(&self, r: Float) -> Float {
-        let term_a = Float::exp(-self.a * (r - self.r_e));
-        let term_b = Float::exp(-2.0 * self.a * (r - self.r_e));
-        2.0 * self.a * self.d_e * (term_a - term_b)
+        let (w, dw) = self.weight(r);
+        if w == 0.0 {
+            self.potential.force(r)
+        } else if w == 1.0 {
+            self.zbl.force(r)
+        } else {
+            let (zbl, pair) = (self.zbl.energy(r), self.potential.energy(r));
+            w * self.zbl.force(r) + (1.0 - w) * self.potential.force(r) + dw * (zbl - pair)
+        }
+    }
+
+    fn tail_integral(&self, cutoff: Float) -> Float {
+        self.potential.tail_integral(cutoff)
+    }
+}
+
+/// Treatment of a pair potential at its cutoff radius.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum CutoffScheme {
+    /// Energy and force drop abruptly to zero at the cutoff.
+    #[default]
+    Truncated,
+    /// Energy is shifted to reach zero at the cutoff while the force is left unchanged.
+    Shifted,
+    /// Energy is multiplied by a switching function between the inner radius and the cutoff so
+    /// that both the energy and the force go continuously to zero.
+    ///
+    /// Uses the CHARMM switching function, whose derivative vanishes at both ends of the window.
+    Switched(Float),
+}
+
+/// Pair potential with a closed form evaluated by the GPU and SIMD kernels.
+#[cfg(any(feature = "gpu", feature = "simd"))]
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum PairForm {
+    LennardJones(LennardJones),
+    Buckingham(Buckingham),
+    Morse(Morse),
+}
+
+#[cfg(any(feature = "gpu", feature = "simd"))]
+impl PairForm {
+    /// Returns the form of `potential`, or `None` if the kernels do not implement it.
+    pub fn of<T: Any>(potential: &T) -> Option<PairForm> {
+        let potential = potential as &dyn Any;
+        if let Some(&lj) = potential.downcast_ref::<LennardJones>() {
+            Some(PairForm::LennardJones(lj))
+        } else if let Some(&buckingham) = potential.downcast_ref::<Buckingham>() {
+            Some(PairForm::Buckingham(buckingham))
+        } else {
+            potential.downcast_ref::<Morse>().map(|&morse| PairForm::Morse(morse))
+        }
     }
 }
 
@@ -105,6 +318,14 @@ pub(crate) struct PairPotentialMeta {
     pub cutoff: Float,
     pub thickness: Float,
     pub selection: PairSelection,
+    pub images: Vec<ImagePair>,
+    pub tail: bool,
+    pub scheme: CutoffScheme,
+    pub resolution: Resolution,
+    #[cfg(any(feature = "gpu", feature = "simd"))]
+    pub form: Option<PairForm>,
+    selves: Vec<usize>,
+    excluded: Vec<[usize; 2]>,
 }
 
 impl PairPotentialMeta {
@@ -122,26 +343,173 @@ impl PairPotentialMeta {
             update_pairs_by_cutoff_radius as PairUpdateFn,
         );
         PairPotentialMeta {
+            #[cfg(any(feature = "gpu", feature = "simd"))]
+            form: PairForm::of(&potential),
             potential: Box::new(potential),
             species,
             cutoff,
             thickness,
             selection,
+            images: Vec::new(),
+            tail: false,
+            scheme: CutoffScheme::default(),
+            resolution: Resolution::default(),
+            selves: Vec::new(),
+            excluded: Vec::new(),
         }
     }
 
-    pub fn setup(&mut self, system: &System) {
-        self.selection.setup(system, self.species)
+    pub fn setup(&mut self, system: &System, excluded: &[[usize; 2]]) {
+        self.selection.setup(system, self.species);
+        self.excluded = self.selection.exclude(excluded);
+        // atoms which may interact with their own periodic images
+        let (a, b) = self.species;
+        self.selves = if a == b {
+            (0..system.size).filter(|&i| system.species[i] == a).collect()
+        } else {
+            Vec::new()
+        };
+    }
+
+    // Returns the current number of neighbor pairs and the bytes allocated for the neighbor list.
+    pub fn neighbor_list(&self) -> (usize, usize) {
+        let pairs = self.selection.indices().count() + self.images.len();
+        let bytes = self.selection.allocated_bytes() + self.images.capacity() * std::mem::size_of::<ImagePair>();
+        (pairs, bytes)
     }
 
     pub fn update(&mut self, system: &System) {
-        self.selection.update(system, self.cutoff + self.thickness)
+        let cutoff = self.cutoff + self.thickness;
+        if cutoff > system.cell.inscribed_radius() {
+            // the minimum image convention misses pairs in cells smaller than the cutoff
+            self.selection.clear();
+            self.images = update_image_pairs(system, self.selection.possible_indices(), &self.selves, cutoff);
+            self.images.extend(update_excluded_image_pairs(system, &self.excluded, cutoff));
+        } else {
+            self.images.clear();
+            self.selection.update(system, cutoff)
+        }
+    }
+
+    /// Returns the energy of a pair within the cutoff separated by a distance `r` under the cutoff scheme.
+    pub fn energy(&self, r: Float) -> Float {
+        match self.scheme {
+            CutoffScheme::Truncated => self.potential.energy(r),
+            CutoffScheme::Shifted => self.potential.energy(r) - self.potential.energy(self.cutoff),
+            CutoffScheme::Switched(inner) => self.potential.energy(r) * self.switch(inner, r).0,
+        }
+    }
+
+    /// Returns the magnitude of the force on a pair within the cutoff under the cutoff scheme.
+    pub fn force(&self, r: Float) -> Float {
+        match self.scheme {
+            CutoffScheme::Truncated | CutoffScheme::Shifted => self.potential.force(r),
+            CutoffScheme::Switched(inner) => {
+                let (value, derivative) = self.switch(inner, r);
+                self.potential.force(r) * value + self.potential.energy(r) * derivative
+            }
+        }
+    }
+
+    // Returns the switching function and its derivative with respect to the separation.
+    fn switch(&self, inner: Float, r: Float) -> (Float, Float) {
+        if r <= inner {
+            return (1.0, 0.0);
+        }
+        let (rc2, rs2, r2) = (self.cutoff.powi(2), inner.powi(2), r.powi(2));
+        let denominator = (rc2 - rs2).powi(3);
+        let value = (rc2 - r2).powi(2) * (rc2 + 2.0 * r2 - 3.0 * rs2) / denominator;
+        let derivative = 12.0 * r * (rc2 - r2) * (rs2 - r2) / denominator;
+        (value, derivative)
+    }
+
+    /// Returns the long range tail correction to the energy, or zero if it is disabled.
+    ///
+    /// The pair distribution is assumed to be uniform beyond the cutoff, and under adaptive
+    /// resolution the correction is weighted by the mean resolution weight of the atoms.
+    pub fn tail_energy(&self, system: &System, weights: Option<&[Float]>) -> Float {
+        if !self.tail {
+            return 0.0;
+        }
+        self.tail_prefactor(system, weights) * self.potential.tail_integral(self.cutoff)
+    }
+
+    /// Returns the long range tail correction to the virial tensor, or zeros if it is disabled.
+    pub fn tail_virial(&self, system: &System, weights: Option<&[Float]>) -> Matrix3<Float> {
+        if !self.tail {
+            return Matrix3::zeros();
+        }
+        // integral of r^3 u'(r) beyond the cutoff by parts
+        let integral = -self.cutoff.powi(3) * self.potential.energy(self.cutoff) - 3.0 * self.potential.tail_integral(self.cutoff);
+        Matrix3::identity() * (-self.tail_prefactor(system, weights) * integral / 3.0)
+    }
+
+    /// Returns the weight of the pair of atoms `i` and `j` given the resolution weight of each atom.
+    ///
+    /// Every atom is atomistic when there are no resolution weights.
+    pub fn weight(&self, weights: Option<&[Float]>, i: usize, j: usize) -> Float {
+        match weights {
+            Some(weights) => self.resolution.weight(weights[i], weights[j]),
+            None => self.resolution.weight(1.0, 1.0),
+        }
+    }
+
+    // Returns the number of pairs per unit volume in a spherical shell of unit radial width divided by r^2,
+    // weighted by the mean resolution weight of the atoms.
+    fn tail_prefactor(&self, system: &System, weights: Option<&[Float]>) -> Float {
+        let mean = match weights {
+            Some(weights) if !weights.is_empty() => weights.iter().sum::<Float>() / weights.len() as Float,
+            _ => 1.0,
+        };
+        let (a, b) = self.species;
+        let count = |species: Species| system.species.iter().filter(|&&s| s == species).count() as Float;
+        let pairs = if a == b {
+            count(a).powi(2) / 2.0
+        } else {
+            count(a) * count(b)
+        };
+        4.0 * PI * pairs * self.resolution.weight(mean, mean) / system.cell.volume()
+    }
+
+    /// Returns the indices and separation vector of each pair in the neighbor list.
+    pub fn pairs<'a>(&'a self, system: &'a System) -> impl Iterator<Item = (usize, usize, Vector3<Float>)> + 'a {
+        let nearest = self.selection.indices().map(move |&[i, j]| {
+            let mut separation = system.positions[j] - system.positions[i];
+            system.cell.vector_image(&mut separation);
+            (i, j, separation)
+        });
+        let images = self
+            .images
+            .iter()
+            .map(move |image| (image.i, image.j, image.separation(system)));
+        nearest.chain(images)
+    }
+
+    /// Returns a parallel iterator over the indices and separation vector of each pair in the neighbor list.
+    #[cfg(feature = "rayon")]
+    pub fn par_pairs<'a>(&'a self, system: &'a System) -> impl ParallelIterator<Item = (usize, usize, Vector3<Float>)> + 'a {
+        let nearest = self.selection.par_indices().map(move |&[i, j]| {
+            let mut separation = system.positions[j] - system.positions[i];
+            system.cell.vector_image(&mut separation);
+            (i, j, separation)
+        });
+        let images = self
+            .images
+            .par_iter()
+            .map(move |image| (image.i, image.j, image.separation(system)));
+        nearest.chain(images)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Buckingham, Harmonic, LennardJones, Mie, Morse, PairPotential};
+    use super::{
+        Buckingham, CutoffScheme, Harmonic, LennardJones, Mie, Morse, PairPotential, PairPotentialMeta, TabulatedPair, Zbl,
+        ZblSpline,
+    };
+    use crate::system::elements::Element;
+    use crate::internal::Float;
+    use crate::system::species::Species;
     use approx::*;
 
     #[test]
@@ -174,6 +542,23 @@ mod tests {
         assert_relative_eq!(r2_force, buckingham.force(r2), epsilon = 1e-5);
     }
 
+    #[test]
+    fn precision() {
+        // the same parameters evaluated at single and double precision separations
+        let lennard_jones = LennardJones::new(0.238, 3.4);
+        let mie = Mie::new(0.238, 3.4, 14.0, 6.0);
+        let morse = Morse::new(1.3, 0.2, 4.1);
+        for &r in [3.2f64, 3.8, 5.0].iter() {
+            let s = r as f32;
+            assert_relative_eq!(f64::from(lennard_jones.energy(s)), lennard_jones.energy(r), max_relative = 1e-5);
+            assert_relative_eq!(f64::from(lennard_jones.force(s)), lennard_jones.force(r), max_relative = 1e-5);
+            assert_relative_eq!(f64::from(mie.energy(s)), mie.energy(r), max_relative = 1e-5);
+            assert_relative_eq!(f64::from(morse.force(s)), morse.force(r), max_relative = 1e-5);
+        }
+        let tail: f64 = lennard_jones.tail_integral(8.5);
+        assert_relative_eq!(f64::from(PairPotential::<f32>::tail_integral(&lennard_jones, 8.5)), tail, max_relative = 1e-5);
+    }
+
     #[test]
     fn harmonic() {
         // initialize the potantial
@@ -290,4 +675,114 @@ mod tests {
         assert_relative_eq!(r2_energy, morse.energy(r2), epsilon = 1e-5);
         assert_relative_eq!(r2_force, morse.force(r2), epsilon = 1e-5);
     }
+
+    #[test]
+    fn tabulated_pair() {
+        let lj = LennardJones::new(0.238, 3.4);
+        let energies: Vec<Float> = (0..=600).map(|i| lj.energy(3.0 + 0.01 * i as Float)).collect();
+        let forces: Vec<Float> = (0..=600).map(|i| lj.force(3.0 + 0.01 * i as Float)).collect();
+        let table = TabulatedPair::from_energies(3.0, 0.01, energies.clone());
+        assert_eq!(table.samples(), 601);
+        assert_relative_eq!(table.distances()[600], 9.0, epsilon = 1e-4);
+        for &r in [3.3, 3.815, 5.0, 8.72].iter() {
+            assert_relative_eq!(table.energy(r), lj.energy(r), epsilon = 2e-3);
+            assert_relative_eq!(table.force(r), lj.force(r), epsilon = 2e-2);
+        }
+        // linear continuation below the table and nothing beyond it
+        assert_relative_eq!(table.energy(2.9), energies[0] - 0.1 * table.force(3.0), epsilon = 1e-4);
+        assert_eq!(table.energy(9.5), 0.0);
+        assert_eq!(table.force(9.5), 0.0);
+
+        // integrating the forces recovers the energy up to its value at the last sample
+        let table = TabulatedPair::from_forces(3.0, 0.01, forces);
+        assert_relative_eq!(table.energy(4.0), lj.energy(4.0) - lj.energy(9.0), epsilon = 1e-3);
+        assert_eq!(table.energies()[600], 0.0);
+    }
+
+    #[test]
+    fn zbl() {
+        let zbl = Zbl::from_elements(Element::W, Element::W);
+        assert_eq!(zbl.z_i, 74.0);
+        // the screening function tends to one as the nuclei meet
+        let r = 1e-4;
+        assert_relative_eq!(zbl.energy(r) * r, 332.0636 * 74.0 * 74.0, max_relative = 1e-2);
+        // the force is the derivative of the energy
+        for &r in [0.2, 0.8, 1.5].iter() {
+            let h = 1e-3;
+            let numeric = (zbl.energy(r + h) - zbl.energy(r - h)) / (2.0 * h);
+            assert_relative_eq!(zbl.force(r), numeric, max_relative = 1e-3);
+        }
+        assert!(zbl.force(1.0) < 0.0);
+    }
+
+    #[test]
+    fn zbl_spline() {
+        let lj = LennardJones::new(0.238, 3.4);
+        let zbl = Zbl::from_elements(Element::Ar, Element::Ar);
+        let spline = ZblSpline::new(lj, zbl, 1.0, 2.5);
+        assert_eq!(spline.energy(0.5), zbl.energy(0.5));
+        assert_eq!(spline.energy(3.0), lj.energy(3.0));
+        assert_eq!(spline.force(3.0), lj.force(3.0));
+        // the energy and force are continuous through the spline
+        for &r in [1.0, 1.7, 2.5].iter() {
+            // small steps since the curvature jumps at the inner radius against the steep Lennard-Jones wall
+            let h = 1e-5;
+            assert_relative_eq!(spline.energy(r - h), spline.energy(r + h), max_relative = 1e-2);
+            let numeric = (spline.energy(r + h) - spline.energy(r - h)) / (2.0 * h);
+            assert_relative_eq!(spline.force(r), numeric, max_relative = 1e-2);
+        }
+        assert_eq!(spline.tail_integral(8.5), lj.tail_integral(8.5));
+    }
+
+    #[test]
+    fn tail_integrals() {
+        // midpoint rule from the cutoff out to a distance where every tail has vanished
+        fn numeric<T: PairPotential>(potential: &T, cutoff: Float) -> Float {
+            let width = 1e-3;
+            (0..200_000)
+                .map(|n| {
+                    let r = cutoff + (n as Float + 0.5) * width;
+                    r * r * potential.energy(r) * width
+                })
+                .sum()
+        }
+        let lj = LennardJones::new(0.238, 3.4);
+        assert_relative_eq!(lj.tail_integral(8.5), numeric(&lj, 8.5), max_relative = 1e-3);
+        let mie = Mie::new(0.238, 3.4, 6.0, 14.0);
+        assert_relative_eq!(mie.tail_integral(8.5), numeric(&mie, 8.5), max_relative = 1e-3);
+        let buckingham = Buckingham::new(10_000.0, 0.3, 30.0);
+        assert_relative_eq!(buckingham.tail_integral(4.0), numeric(&buckingham, 4.0), max_relative = 1e-3);
+        let morse = Morse::new(1.5, 4.0, 2.0);
+        assert_relative_eq!(morse.tail_integral(3.0), numeric(&morse, 3.0), max_relative = 1e-3);
+        // potentials which do not decay have no tail
+        assert_eq!(Harmonic::new(1.0, 2.0).tail_integral(5.0), 0.0);
+    }
+
+    #[test]
+    fn cutoff_schemes() {
+        let argon = Species::new(39.948, 0.0);
+        let lj = LennardJones::new(0.238, 3.4);
+        let mut meta = PairPotentialMeta::new(lj, (argon, argon), 8.5, 0.5);
+        assert_eq!(meta.scheme, CutoffScheme::Truncated);
+        assert_eq!(meta.energy(5.0), lj.energy(5.0));
+        assert!(meta.energy(8.499).abs() > 1e-3);
+
+        // shifting leaves the force unchanged
+        meta.scheme = CutoffScheme::Shifted;
+        assert_relative_eq!(meta.energy(8.5), 0.0);
+        assert_relative_eq!(meta.energy(5.0), lj.energy(5.0) - lj.energy(8.5));
+        assert_eq!(meta.force(5.0), lj.force(5.0));
+
+        // switching is exact inside the window and smooth across it
+        meta.scheme = CutoffScheme::Switched(7.0);
+        assert_eq!(meta.energy(6.0), lj.energy(6.0));
+        assert_eq!(meta.force(6.0), lj.force(6.0));
+        assert_relative_eq!(meta.energy(8.5), 0.0);
+        assert_relative_eq!(meta.force(8.5), 0.0);
+        for &r in [7.0, 7.3, 7.8, 8.2, 8.49].iter() {
+            let h = 1e-3;
+            let derivative = (meta.energy(r + h) - meta.energy(r - h)) / (2.0 * h);
+            assert_relative_eq!(meta.force(r), derivative, epsilon = 1e-5);
+        }
+    }
 }
@@ -0,0 +1,347 @@
+//! Restraints which tether atoms in place and walls which confine them.
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+use crate::potentials::external::ExternalField;
+use crate::potentials::Potential;
+use crate::system::System;
+
+// Returns true if `atoms`, which is sorted or `None` for every atom, includes atom `i`.
+fn acts_on(atoms: &Option<Vec<usize>>, i: usize) -> bool {
+    match atoms {
+        Some(atoms) => atoms.binary_search(&i).is_ok(),
+        None => true,
+    }
+}
+
+// Returns the sorted and deduplicated indices of `atoms`.
+fn sorted(mut atoms: Vec<usize>) -> Vec<usize> {
+    atoms.sort_unstable();
+    atoms.dedup();
+    atoms
+}
+
+/// Harmonic restraint which tethers atoms to reference positions.
+///
+/// Each restrained atom feels the energy `0.5 * k * d^2` where `d` is the minimum image
+/// distance from its reference position, so a restrained atom which crosses the periodic
+/// boundary is still pulled back toward its reference. Atoms without a reference are free.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let system = System {
+///     size: 2,
+///     cell: Cell::cubic(10.0),
+///     species: vec![argon; 2],
+///     positions: vec![Vector3::new(1.0, 1.0, 1.0), Vector3::new(4.0, 4.0, 4.0)],
+///     velocities: vec![Vector3::zeros(); 2],
+///     topology: Topology::default(),
+/// };
+/// // hold the first atom where it is
+/// let restraint = PositionRestraint::new(10.0).tether(&system, &[0]);
+/// let potentials = PotentialsBuilder::new().field(restraint).build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct PositionRestraint {
+    k: Float,
+    references: Vec<(usize, Vector3<Float>)>,
+}
+
+impl PositionRestraint {
+    /// Returns a new [`PositionRestraint`] of no atoms.
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - Spring constant in kcal/mol/angstrom^2.
+    pub fn new(k: Float) -> PositionRestraint {
+        PositionRestraint {
+            k,
+            references: Vec::new(),
+        }
+    }
+
+    /// Restrains atom `i` to `position`.
+    pub fn reference(mut self, i: usize, position: Vector3<Float>) -> PositionRestraint {
+        match self.references.binary_search_by_key(&i, |(j, _)| *j) {
+            Ok(k) => self.references[k].1 = position,
+            Err(k) => self.references.insert(k, (i, position)),
+        }
+        self
+    }
+
+    /// Restrains the atoms with the given indices to their current positions in `system`.
+    pub fn tether(self, system: &System, atoms: &[usize]) -> PositionRestraint {
+        atoms
+            .iter()
+            .fold(self, |restraint, &i| restraint.reference(i, system.positions[i]))
+    }
+
+    // Returns the minimum image displacement of atom `i` from its reference.
+    fn displacement(&self, system: &System, i: usize) -> Option<Vector3<Float>> {
+        let k = self.references.binary_search_by_key(&i, |(j, _)| *j).ok()?;
+        let mut displacement = system.positions[i] - self.references[k].1;
+        system.cell.vector_image(&mut displacement);
+        Some(displacement)
+    }
+}
+
+impl Potential for PositionRestraint {
+    fn describe(&self) -> String {
+        format!("PositionRestraint(k={}, atoms={})", self.k, self.references.len())
+    }
+}
+
+impl ExternalField for PositionRestraint {
+    fn energy(&self, system: &System, i: usize) -> Float {
+        match self.displacement(system, i) {
+            Some(d) => 0.5 * self.k * d.norm_squared(),
+            None => 0.0,
+        }
+    }
+
+    fn force(&self, system: &System, i: usize) -> Vector3<Float> {
+        match self.displacement(system, i) {
+            Some(d) => -self.k * d,
+            None => Vector3::zeros(),
+        }
+    }
+}
+
+// Returns the energy and force magnitude of a harmonic repulsion which begins `cutoff` away from a wall.
+fn repulsion(k: Float, cutoff: Float, distance: Float) -> (Float, Float) {
+    if distance < cutoff {
+        let overlap = cutoff - distance;
+        (0.5 * k * overlap * overlap, k * overlap)
+    } else {
+        (0.0, 0.0)
+    }
+}
+
+/// Flat wall which confines atoms to one side of a plane.
+///
+/// Atoms within `cutoff` of the plane on the side its normal points to, or on the wrong side
+/// of it, feel the energy `0.5 * k * (cutoff - d)^2` where `d` is their signed distance from the
+/// plane. The wall uses the absolute positions of the atoms, so it should be placed where the
+/// confined atoms do not reach the periodic boundary, such as in a slab with vacuum along the
+/// normal.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// // keep every atom above z = 2 and below z = 28
+/// let floor = PlanarWall::new(Vector3::new(0.0, 0.0, 2.0), Vector3::z(), 20.0, 1.0);
+/// let ceiling = PlanarWall::new(Vector3::new(0.0, 0.0, 28.0), -Vector3::z(), 20.0, 1.0);
+/// let potentials = PotentialsBuilder::new().field(floor).field(ceiling).build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct PlanarWall {
+    point: Vector3<Float>,
+    normal: Vector3<Float>,
+    k: Float,
+    cutoff: Float,
+    atoms: Option<Vec<usize>>,
+}
+
+impl PlanarWall {
+    /// Returns a new [`PlanarWall`] which acts on every atom.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - Any point on the plane.
+    /// * `normal` - Normal of the plane pointing toward the confined side.
+    /// * `k` - Spring constant in kcal/mol/angstrom^2.
+    /// * `cutoff` - Distance from the plane at which the repulsion begins.
+    pub fn new(point: Vector3<Float>, normal: Vector3<Float>, k: Float, cutoff: Float) -> PlanarWall {
+        PlanarWall {
+            point,
+            normal: normal.normalize(),
+            k,
+            cutoff,
+            atoms: None,
+        }
+    }
+
+    /// Restricts the wall to the atoms with the given indices.
+    pub fn atoms(mut self, atoms: Vec<usize>) -> PlanarWall {
+        self.atoms = Some(sorted(atoms));
+        self
+    }
+
+    fn repulsion(&self, system: &System, i: usize) -> (Float, Float) {
+        if !acts_on(&self.atoms, i) {
+            return (0.0, 0.0);
+        }
+        let distance = (system.positions[i] - self.point).dot(&self.normal);
+        repulsion(self.k, self.cutoff, distance)
+    }
+}
+
+impl Potential for PlanarWall {}
+
+impl ExternalField for PlanarWall {
+    fn energy(&self, system: &System, i: usize) -> Float {
+        self.repulsion(system, i).0
+    }
+
+    fn force(&self, system: &System, i: usize) -> Vector3<Float> {
+        self.repulsion(system, i).1 * self.normal
+    }
+}
+
+/// Spherical wall which confines atoms inside a sphere.
+///
+/// Atoms within `cutoff` of the surface of the sphere, or outside of it, feel the energy
+/// `0.5 * k * (cutoff - d)^2` where `d` is their distance inside the surface. This holds a
+/// droplet or cluster together without a container of explicit atoms.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let droplet = SphericalWall::new(Vector3::new(15.0, 15.0, 15.0), 12.0, 20.0, 1.0);
+/// let potentials = PotentialsBuilder::new().field(droplet).build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SphericalWall {
+    center: Vector3<Float>,
+    radius: Float,
+    k: Float,
+    cutoff: Float,
+    atoms: Option<Vec<usize>>,
+}
+
+impl SphericalWall {
+    /// Returns a new [`SphericalWall`] which acts on every atom.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - Center of the sphere.
+    /// * `radius` - Radius of the sphere.
+    /// * `k` - Spring constant in kcal/mol/angstrom^2.
+    /// * `cutoff` - Distance inside the surface at which the repulsion begins.
+    pub fn new(center: Vector3<Float>, radius: Float, k: Float, cutoff: Float) -> SphericalWall {
+        SphericalWall {
+            center,
+            radius,
+            k,
+            cutoff,
+            atoms: None,
+        }
+    }
+
+    /// Restricts the wall to the atoms with the given indices.
+    pub fn atoms(mut self, atoms: Vec<usize>) -> SphericalWall {
+        self.atoms = Some(sorted(atoms));
+        self
+    }
+
+    // Returns the energy, the magnitude of the force, and the outward unit vector of atom `i`.
+    fn repulsion(&self, system: &System, i: usize) -> (Float, Float, Vector3<Float>) {
+        if !acts_on(&self.atoms, i) {
+            return (0.0, 0.0, Vector3::zeros());
+        }
+        let offset = system.positions[i] - self.center;
+        let r = offset.norm();
+        let outward = if r > 0.0 { offset / r } else { Vector3::zeros() };
+        let (energy, force) = repulsion(self.k, self.cutoff, self.radius - r);
+        (energy, force, outward)
+    }
+}
+
+impl Potential for SphericalWall {}
+
+impl ExternalField for SphericalWall {
+    fn energy(&self, system: &System, i: usize) -> Float {
+        self.repulsion(system, i).0
+    }
+
+    fn force(&self, system: &System, i: usize) -> Vector3<Float> {
+        let (_, force, outward) = self.repulsion(system, i);
+        -force * outward
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlanarWall, PositionRestraint, SphericalWall};
+    use crate::internal::Float;
+    use crate::potentials::external::ExternalField;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    fn atoms(positions: Vec<Vector3<Float>>) -> System {
+        System {
+            size: positions.len(),
+            cell: Cell::cubic(20.0),
+            species: vec![Species::new(1.0, 0.0); positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    // the force of a field is the negative gradient of its energy
+    fn assert_gradient<F: ExternalField>(field: &F, system: &System, i: usize) {
+        let h = 1e-3;
+        let force = field.force(system, i);
+        for k in 0..3 {
+            let mut plus = system.clone();
+            plus.positions[i][k] += h;
+            let mut minus = system.clone();
+            minus.positions[i][k] -= h;
+            let slope = (field.energy(&plus, i) - field.energy(&minus, i)) / (2.0 * h);
+            assert_relative_eq!(force[k], -slope, epsilon = 1e-2);
+        }
+    }
+
+    #[test]
+    fn position_restraint() {
+        let mut system = atoms(vec![Vector3::new(0.5, 5.0, 5.0), Vector3::new(8.0, 8.0, 8.0)]);
+        let restraint = PositionRestraint::new(4.0).tether(&system, &[0]);
+        // the restrained atom crosses the boundary and the other is free
+        system.positions[0].x = 19.5;
+        system.positions[1].x = 9.0;
+        assert_relative_eq!(restraint.energy(&system, 0), 2.0, epsilon = 1e-4);
+        assert_relative_eq!(restraint.force(&system, 0), Vector3::new(4.0, 0.0, 0.0), epsilon = 1e-4);
+        assert_eq!(restraint.energy(&system, 1), 0.0);
+        assert_eq!(restraint.force(&system, 1), Vector3::zeros());
+        assert_gradient(&restraint, &system, 0);
+    }
+
+    #[test]
+    fn walls() {
+        let system = atoms(vec![
+            Vector3::new(5.0, 5.0, 1.5),
+            Vector3::new(5.0, 5.0, 0.5),
+            Vector3::new(5.0, 5.0, 10.0),
+        ]);
+        let floor = PlanarWall::new(Vector3::new(0.0, 0.0, 1.0), Vector3::z(), 10.0, 1.0);
+        // only the atom beyond the wall and the atom within the cutoff are pushed
+        assert_relative_eq!(floor.energy(&system, 0), 1.25, epsilon = 1e-4);
+        assert_relative_eq!(floor.force(&system, 1), Vector3::new(0.0, 0.0, 15.0), epsilon = 1e-4);
+        assert_eq!(floor.energy(&system, 2), 0.0);
+        assert_gradient(&floor, &system, 0);
+        let floor = floor.atoms(vec![1]);
+        assert_eq!(floor.energy(&system, 0), 0.0);
+
+        let sphere = SphericalWall::new(Vector3::new(5.0, 5.0, 5.0), 4.0, 10.0, 1.0);
+        assert_relative_eq!(sphere.energy(&system, 0), 1.25, epsilon = 1e-4);
+        assert_relative_eq!(sphere.force(&system, 2), Vector3::new(0.0, 0.0, -20.0), epsilon = 1e-4);
+        assert_gradient(&sphere, &system, 1);
+    }
+}
@@ -0,0 +1,282 @@
+//! Explicitly vectorized evaluation of pair potentials.
+//!
+//! Positions are copied into the structure-of-arrays layout of [`Soa3`] and the pairs of a
+//! neighbor list are evaluated a SIMD register at a time with [`wide`]. Only the minimum image
+//! neighbor lists of orthorhombic cells are vectorized, the periodic images of small cells and
+//! triclinic cells are left to the scalar kernels.
+
+use nalgebra::Matrix3;
+#[cfg(not(feature = "f64"))]
+use wide::f32x8 as Lanes;
+#[cfg(feature = "f64")]
+use wide::f64x4 as Lanes;
+use wide::{CmpGt, CmpLt};
+
+use crate::internal::Float;
+use crate::potentials::pair::{CutoffScheme, PairForm, PairPotentialMeta};
+use crate::system::soa::Soa3;
+use crate::system::System;
+
+// Number of pairs evaluated at once.
+const WIDTH: usize = std::mem::size_of::<Lanes>() / std::mem::size_of::<Float>();
+
+/// Forces, virial, and energy summed over the pairs of a neighbor list.
+pub(crate) struct PairSums {
+    /// Force acting on each atom, left empty unless forces were requested.
+    pub forces: Soa3,
+    pub virial: Matrix3<Float>,
+    pub energy: Float,
+}
+
+/// Returns the weighted sums over the pairs of `meta`, or `None` if its potential or neighbor
+/// list is not vectorized.
+pub(crate) fn evaluate(meta: &PairPotentialMeta, system: &System, weights: Option<&[Float]>, forces: bool) -> Option<PairSums> {
+    let form = meta.form?;
+    if !meta.images.is_empty() || !system.cell.is_orthorhombic() {
+        return None;
+    }
+    let positions = Soa3::from(&system.positions[..]);
+    let lengths = system.cell.matrix().diagonal();
+    let lengths = [Lanes::splat(lengths.x), Lanes::splat(lengths.y), Lanes::splat(lengths.z)];
+    let cutoff = Lanes::splat(meta.cutoff * meta.cutoff);
+
+    let mut sums = PairSums {
+        forces: if forces { Soa3::zeros(system.size) } else { Soa3::default() },
+        virial: Matrix3::zeros(),
+        energy: 0.0,
+    };
+    let (mut energy, mut virial) = (Lanes::ZERO, [Lanes::ZERO; 6]);
+    let mut indices = meta.selection.indices();
+    loop {
+        let mut batch = [[0; 2]; WIDTH];
+        let mut count = 0;
+        for (slot, &pair) in batch.iter_mut().zip(&mut indices) {
+            *slot = pair;
+            count += 1;
+        }
+        if count == 0 {
+            break;
+        }
+        // unused lanes are placed at the cutoff radius so that they drop out
+        let (mut dx, mut dy, mut dz, mut w) = ([meta.cutoff; WIDTH], [0.0; WIDTH], [0.0; WIDTH], [0.0; WIDTH]);
+        for (lane, &[i, j]) in batch[..count].iter().enumerate() {
+            dx[lane] = positions.x[j] - positions.x[i];
+            dy[lane] = positions.y[j] - positions.y[i];
+            dz[lane] = positions.z[j] - positions.z[i];
+            w[lane] = meta.weight(weights, i, j);
+        }
+        let separation = [Lanes::from(dx), Lanes::from(dy), Lanes::from(dz)];
+        let [dx, dy, dz] = minimum_image(separation, &lengths);
+        let r2 = dx * dx + dy * dy + dz * dz;
+        let within = r2.cmp_lt(cutoff);
+        // keep the lanes beyond the cutoff finite before they are discarded
+        let r2 = within.blend(r2, cutoff);
+        let r = r2.sqrt();
+        let (u, f) = scheme(meta, form_lanes(form, r, r2), r, r2);
+        let w = Lanes::from(w);
+        energy += within.blend(w * u, Lanes::ZERO);
+        // force on the first atom of each pair per unit separation
+        let k = within.blend(w * f / r, Lanes::ZERO);
+        let (fx, fy, fz) = (k * dx, k * dy, k * dz);
+        virial[0] -= dx * fx;
+        virial[1] -= dy * fy;
+        virial[2] -= dz * fz;
+        virial[3] -= dx * fy;
+        virial[4] -= dx * fz;
+        virial[5] -= dy * fz;
+        if forces {
+            let (fx, fy, fz) = (fx.to_array(), fy.to_array(), fz.to_array());
+            for (lane, &[i, j]) in batch[..count].iter().enumerate() {
+                sums.forces.x[i] += fx[lane];
+                sums.forces.y[i] += fy[lane];
+                sums.forces.z[i] += fz[lane];
+                sums.forces.x[j] -= fx[lane];
+                sums.forces.y[j] -= fy[lane];
+                sums.forces.z[j] -= fz[lane];
+            }
+        }
+    }
+    let [xx, yy, zz, xy, xz, yz] = virial.map(|lanes| lanes.reduce_add());
+    sums.virial = Matrix3::new(xx, xy, xz, xy, yy, yz, xz, yz, zz);
+    sums.energy = energy.reduce_add();
+    Some(sums)
+}
+
+// Applies the orthorhombic minimum image convention to each component of the separations.
+fn minimum_image(separation: [Lanes; 3], lengths: &[Lanes; 3]) -> [Lanes; 3] {
+    let [x, y, z] = separation;
+    [
+        x - lengths[0] * (x / lengths[0]).round(),
+        y - lengths[1] * (y / lengths[1]).round(),
+        z - lengths[2] * (z / lengths[2]).round(),
+    ]
+}
+
+// Returns the energy and the magnitude of the force of each pair separated by `r`.
+fn form_lanes(form: PairForm, r: Lanes, r2: Lanes) -> (Lanes, Lanes) {
+    match form {
+        PairForm::LennardJones(lj) => {
+            // same order of operations as the scalar kernel
+            let q = Lanes::splat(lj.sigma) / r;
+            let q2 = q * q;
+            let term = q2 * q2 * q2;
+            let epsilon = Lanes::splat(lj.epsilon);
+            let energy = Lanes::splat(4.0) * epsilon * (term * term - term);
+            let force = epsilon * (Lanes::splat(24.0) * term - Lanes::splat(48.0) * term * term) / r;
+            (energy, force)
+        }
+        PairForm::Buckingham(buckingham) => {
+            let repulsion = Lanes::splat(buckingham.a) * (-r / Lanes::splat(buckingham.rho)).exp();
+            let dispersion = Lanes::splat(buckingham.c) / (r2 * r2 * r2);
+            let energy = repulsion - dispersion;
+            let force = Lanes::splat(6.0) * dispersion / r - repulsion / Lanes::splat(buckingham.rho);
+            (energy, force)
+        }
+        PairForm::Morse(morse) => {
+            let a = Lanes::splat(morse.a);
+            let term = (-a * (r - Lanes::splat(morse.r_e))).exp();
+            let d_e = Lanes::splat(morse.d_e);
+            let energy = d_e * (term * term - Lanes::splat(2.0) * term);
+            let force = Lanes::splat(2.0) * a * d_e * (term - term * term);
+            (energy, force)
+        }
+    }
+}
+
+// Applies the cutoff scheme of `meta` to the energy and force of each pair.
+fn scheme(meta: &PairPotentialMeta, (energy, force): (Lanes, Lanes), r: Lanes, r2: Lanes) -> (Lanes, Lanes) {
+    match meta.scheme {
+        CutoffScheme::Truncated => (energy, force),
+        CutoffScheme::Shifted => (energy - Lanes::splat(meta.potential.energy(meta.cutoff)), force),
+        CutoffScheme::Switched(inner) => {
+            let (rc2, rs2) = (meta.cutoff * meta.cutoff, inner * inner);
+            let denominator = Lanes::splat((rc2 - rs2).powi(3));
+            let (rc2, rs2) = (Lanes::splat(rc2), Lanes::splat(rs2));
+            let value = (rc2 - r2) * (rc2 - r2) * (rc2 + Lanes::splat(2.0) * r2 - Lanes::splat(3.0) * rs2) / denominator;
+            let derivative = Lanes::splat(12.0) * r * (rc2 - r2) * (rs2 - r2) / denominator;
+            // the switching function is one inside the inner radius
+            let switched = r.cmp_gt(Lanes::splat(inner));
+            let value = switched.blend(value, Lanes::ONE);
+            let derivative = switched.blend(derivative, Lanes::ZERO);
+            (energy * value, force * value + energy * derivative)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::evaluate;
+    use crate::internal::Float;
+    use crate::potentials::pair::{CutoffScheme, PairPotentialMeta};
+    use crate::potentials::types::{Buckingham, LennardJones, Mie, Morse};
+    use crate::potentials::PotentialsBuilder;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::{Matrix3, Vector3};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    // Argon on a jittered simple cubic lattice in a 20 angstrom cell with a number of atoms
+    // which is not a multiple of the SIMD width.
+    fn gas(cell: Cell) -> System {
+        let mut rng = StdRng::seed_from_u64(7);
+        let positions: Vec<Vector3<Float>> = (0..101)
+            .map(|n| {
+                let site = Vector3::new((n % 5) as Float, (n / 5 % 5) as Float, (n / 25) as Float) * 4.0;
+                site + Vector3::new(rng.gen_range(-0.5, 0.5), rng.gen_range(-0.5, 0.5), rng.gen_range(-0.5, 0.5))
+            })
+            .collect();
+        System {
+            size: positions.len(),
+            cell,
+            species: vec![Species::from_element(Element::Ar); positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    // Returns the forces, virial, and energy of the pairs of `meta` from the scalar kernels.
+    fn scalar(meta: &PairPotentialMeta, system: &System) -> (Vec<Vector3<Float>>, Matrix3<Float>, Float) {
+        let (mut forces, mut virial, mut energy) = (vec![Vector3::zeros(); system.size], Matrix3::zeros(), 0.0);
+        for (i, j, separation) in meta.pairs(system) {
+            let r = separation.norm();
+            if r < meta.cutoff {
+                let force = meta.force(r) * separation / r;
+                forces[i] += force;
+                forces[j] -= force;
+                virial -= separation * force.transpose();
+                energy += meta.energy(r);
+            }
+        }
+        (forces, virial, energy)
+    }
+
+    fn assert_matches_scalar(builder: PotentialsBuilder) {
+        let system = gas(Cell::cubic(20.0));
+        let mut potentials = builder.build();
+        potentials.setup(&system);
+        let meta = &potentials.pair_metas[0];
+        let (forces, virial, energy) = scalar(meta, &system);
+        let sums = evaluate(meta, &system, None, true).unwrap();
+        assert_relative_eq!(sums.energy, energy, max_relative = 1e-4);
+        assert_relative_eq!(sums.virial, virial, epsilon = 1e-4 * virial.norm());
+        let scale = forces.iter().map(|f| f.norm()).fold(0.0, Float::max);
+        for (i, force) in forces.iter().enumerate() {
+            assert_relative_eq!(sums.forces.get(i), force, epsilon = 1e-4 * scale);
+        }
+        // only the energy is summed when forces are not requested
+        let sums = evaluate(meta, &system, None, false).unwrap();
+        assert!(sums.forces.is_empty());
+        assert_relative_eq!(sums.energy, energy, max_relative = 1e-4);
+    }
+
+    fn argon() -> (Species, Species) {
+        (Species::from_element(Element::Ar), Species::from_element(Element::Ar))
+    }
+
+    #[test]
+    fn lennard_jones() {
+        assert_matches_scalar(PotentialsBuilder::new().pair(LennardJones::new(0.238, 3.4), argon(), 8.5, 1.0));
+        assert_matches_scalar(
+            PotentialsBuilder::new()
+                .pair(LennardJones::new(0.238, 3.4), argon(), 8.5, 1.0)
+                .cutoff_scheme(CutoffScheme::Switched(7.0)),
+        );
+    }
+
+    #[test]
+    fn buckingham() {
+        assert_matches_scalar(PotentialsBuilder::new().pair(Buckingham::new(1000.0, 0.3, 50.0), argon(), 8.5, 1.0));
+    }
+
+    #[test]
+    fn morse() {
+        assert_matches_scalar(
+            PotentialsBuilder::new()
+                .pair(Morse::new(1.3, 0.2, 3.8), argon(), 8.5, 1.0)
+                .cutoff_scheme(CutoffScheme::Shifted),
+        );
+    }
+
+    #[test]
+    fn scalar_fallback() {
+        let system = gas(Cell::cubic(20.0));
+        let mut potentials = PotentialsBuilder::new()
+            .pair(Mie::new(0.238, 3.4, 6.0, 12.0), argon(), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+        assert!(evaluate(&potentials.pair_metas[0], &system, None, true).is_none());
+
+        let system = gas(Cell::triclinic(20.0, 20.0, 20.0, 90.0, 90.0, 100.0));
+        let mut potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), argon(), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+        assert!(evaluate(&potentials.pair_metas[0], &system, None, true).is_none());
+    }
+}
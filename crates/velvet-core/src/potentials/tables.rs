@@ -0,0 +1,90 @@
+//! Lookup tables which replace expensive function evaluations with interpolation.
+
+use crate::internal::Float;
+
+/// Largest number of intervals a lookup table will be refined to.
+const MAX_INTERVALS: usize = 1 << 20;
+
+/// Uniformly spaced samples of a function with linear interpolation between them.
+#[derive(Clone, Debug)]
+pub(crate) struct LookupTable {
+    start: Float,
+    inv_step: Float,
+    values: Vec<Float>,
+}
+
+impl LookupTable {
+    /// Returns a table of `f` over `start..end` refined until the interpolation error is below `accuracy`.
+    ///
+    /// The error is checked at the midpoint of every interval where linear interpolation
+    /// deviates most from a smooth function.
+    pub fn new<F: Fn(Float) -> Float>(f: F, start: Float, end: Float, accuracy: Float) -> LookupTable {
+        let mut intervals = 64;
+        loop {
+            let step = (end - start) / intervals as Float;
+            let values: Vec<Float> = (0..=intervals)
+                .map(|i| f(start + i as Float * step))
+                .collect();
+            let converged = values.windows(2).enumerate().all(|(i, pair)| {
+                let midpoint = start + (i as Float + 0.5) * step;
+                (0.5 * (pair[0] + pair[1]) - f(midpoint)).abs() <= accuracy
+            });
+            if converged || intervals >= MAX_INTERVALS {
+                return LookupTable {
+                    start,
+                    inv_step: 1.0 / step,
+                    values,
+                };
+            }
+            intervals *= 2;
+        }
+    }
+
+    /// Returns a table of uniformly spaced samples starting at `start` with spacing `step`.
+    pub fn from_values(start: Float, step: Float, values: Vec<Float>) -> LookupTable {
+        assert!(values.len() >= 2, "A lookup table requires at least two samples");
+        LookupTable {
+            start,
+            inv_step: 1.0 / step,
+            values,
+        }
+    }
+
+    /// Returns the sampled values of the function.
+    pub fn values(&self) -> &[Float] {
+        &self.values
+    }
+
+    /// Returns the number of samples in the table.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the interpolated value of the function at `x` which must lie within the table.
+    #[inline]
+    pub fn eval(&self, x: Float) -> Float {
+        let position = (x - self.start) * self.inv_step;
+        let index = (position as usize).min(self.values.len() - 2);
+        let fraction = position - index as Float;
+        let (a, b) = (self.values[index], self.values[index + 1]);
+        a + fraction * (b - a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LookupTable;
+    use crate::internal::Float;
+
+    #[test]
+    fn refines_to_accuracy() {
+        let f = |x: Float| Float::exp(-x * x);
+        let coarse = LookupTable::new(f, 0.0, 4.0, 1e-2);
+        let fine = LookupTable::new(f, 0.0, 4.0, 1e-5);
+        assert!(fine.len() > coarse.len());
+        for i in 0..=1000 {
+            let x = 4.0 * i as Float / 1000.0;
+            assert!((fine.eval(x) - f(x)).abs() < 1e-5);
+        }
+    }
+}
@@ -1,7 +1,10 @@
 //! Interatomic potential functions.
 
 use crate::internal::Float;
+use crate::potentials::coulomb::CoulombPotential;
+use crate::potentials::tables::LookupTable;
 use crate::potentials::Potential;
+use crate::system::elements::Element;
 
 /// [Buckingham](https://lammps.sandia.gov/doc/pair_buck.html#description) potential.
 #[derive(Clone, Copy, Debug)]
@@ -21,7 +24,11 @@ impl Buckingham {
     }
 }
 
-impl Potential for Buckingham {}
+impl Potential for Buckingham {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
 /// [Damped Shifted Force](https://lammps.sandia.gov/doc/pair_coul.html#description) potential.
 #[derive(Clone, Copy, Debug)]
@@ -39,9 +46,36 @@ impl DampedShiftedForce {
     }
 }
 
-impl Potential for DampedShiftedForce {}
+impl Potential for DampedShiftedForce {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
+/// [Ewald](https://lammps.sandia.gov/doc/kspace_style.html#description) summation of Coulombic interactions.
+///
+/// The reciprocal space sum is evaluated directly over every reciprocal lattice vector
+/// within `kmax` multiples of the reciprocal cell vectors.
+#[derive(Clone, Copy, Debug)]
+pub struct Ewald {
+    /// Splitting parameter between real and reciprocal space (inverse distance units).
+    pub alpha: Float,
+    /// Largest multiple of each reciprocal cell vector included in the reciprocal sum.
+    pub kmax: usize,
+}
 
+impl Ewald {
+    /// Returns a new [`Ewald`] potential.
+    pub fn new(alpha: Float, kmax: usize) -> Ewald {
+        Ewald { alpha, kmax }
+    }
+}
+
+impl Potential for Ewald {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
 /// [Harmonic](https://lammps.sandia.gov/doc/bond_harmonic.html#description) oscillator potential.
 #[derive(Clone, Copy, Debug)]
@@ -59,7 +93,11 @@ impl Harmonic {
     }
 }
 
-impl Potential for Harmonic {}
+impl Potential for Harmonic {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
 /// [Lennard-Jones](https://lammps.sandia.gov/doc/pair_lj.html#description) 12/6 potential.
 #[derive(Clone, Copy, Debug)]
@@ -77,7 +115,49 @@ impl LennardJones {
     }
 }
 
-impl Potential for LennardJones {}
+impl Potential for LennardJones {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// Smooth [particle-mesh Ewald](https://lammps.sandia.gov/doc/kspace_style.html#description) summation of Coulombic interactions.
+///
+/// The reciprocal space sum is evaluated with fast Fourier transforms of the charges
+/// interpolated onto a mesh with cardinal B-splines.
+#[derive(Clone, Copy, Debug)]
+pub struct ParticleMeshEwald {
+    /// Splitting parameter between real and reciprocal space (inverse distance units).
+    pub alpha: Float,
+    /// Maximum distance between mesh points.
+    pub spacing: Float,
+    /// Order of the B-spline charge interpolation.
+    pub order: usize,
+}
+
+impl ParticleMeshEwald {
+    /// Returns a new [`ParticleMeshEwald`] potential.
+    ///
+    /// # Arguments
+    ///
+    /// * `alpha` - Splitting parameter between real and reciprocal space.
+    /// * `spacing` - Maximum distance between mesh points.
+    /// * `order` - Order of the B-spline charge interpolation (at least 2).
+    pub fn new(alpha: Float, spacing: Float, order: usize) -> ParticleMeshEwald {
+        assert!(order >= 2, "interpolation order must be at least 2");
+        ParticleMeshEwald {
+            alpha,
+            spacing,
+            order,
+        }
+    }
+}
+
+impl Potential for ParticleMeshEwald {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
 /// [Mie](https://lammps.sandia.gov/doc/pair_mie.html#description) potential.
 #[derive(Clone, Copy, Debug)]
@@ -104,7 +184,11 @@ impl Mie {
     }
 }
 
-impl Potential for Mie {}
+impl Potential for Mie {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
 /// [Morse](https://lammps.sandia.gov/doc/pair_morse.html#description) potential.
 #[derive(Clone, Copy, Debug)]
@@ -124,7 +208,11 @@ impl Morse {
     }
 }
 
-impl Potential for Morse {}
+impl Potential for Morse {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
 
 /// Standard [Coulombic](https://lammps.sandia.gov/doc/pair_coul.html#description) potential.
 #[derive(Clone, Copy, Debug)]
@@ -140,4 +228,231 @@ impl StandardCoulombic {
     }
 }
 
-impl Potential for StandardCoulombic {}
+impl Potential for StandardCoulombic {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// Coulombic potential whose pairwise terms are interpolated from lookup tables.
+///
+/// Every pairwise Coulombic term is the product of the charges and a function of the separation,
+/// so that function is tabulated once for unit charges between `inner` and `cutoff`. This replaces
+/// the `erfc` and exponential evaluations of the [`Ewald`] family real space sums with a linear
+/// interpolation. Separations outside of the table and any long range terms are evaluated by the
+/// wrapped potential.
+#[derive(Clone, Debug)]
+pub struct Tabulated<T> {
+    /// Potential evaluated by the tables.
+    pub potential: T,
+    pub(crate) inner: Float,
+    pub(crate) cutoff: Float,
+    pub(crate) energy: LookupTable,
+    pub(crate) force: LookupTable,
+}
+
+impl<T: CoulombPotential> Tabulated<T> {
+    /// Returns a new [`Tabulated`] potential.
+    ///
+    /// # Arguments
+    ///
+    /// * `potential` - Potential to be tabulated.
+    /// * `inner` - Smallest separation covered by the tables.
+    /// * `cutoff` - Largest separation covered by the tables.
+    /// * `accuracy` - Largest acceptable error of the interpolated energy and force between unit charges.
+    pub fn new(potential: T, inner: Float, cutoff: Float, accuracy: Float) -> Tabulated<T> {
+        let energy = LookupTable::new(|r| potential.energy(1.0, 1.0, r), inner, cutoff, accuracy);
+        let force = LookupTable::new(|r| potential.force(1.0, 1.0, r), inner, cutoff, accuracy);
+        Tabulated {
+            potential,
+            inner,
+            cutoff,
+            energy,
+            force,
+        }
+    }
+
+    /// Returns the number of samples in each table.
+    pub fn samples(&self) -> usize {
+        self.energy.len()
+    }
+}
+
+impl<T: CoulombPotential> Potential for Tabulated<T> {
+    fn describe(&self) -> String {
+        format!(
+            "Tabulated {{ potential: {}, inner: {:?}, cutoff: {:?}, samples: {} }}",
+            self.potential.describe(),
+            self.inner,
+            self.cutoff,
+            self.samples()
+        )
+    }
+}
+
+/// Pair potential interpolated from uniformly spaced samples of its energy and force.
+///
+/// Tables typically come from coarse-graining procedures such as force matching or Boltzmann
+/// inversion. The energy and force are linearly interpolated between the samples. Below the
+/// first sample the energy continues linearly with the force of the first sample, and beyond the
+/// last sample both are zero, so tables should decay to zero at their last sample.
+#[derive(Clone, Debug)]
+pub struct TabulatedPair {
+    pub(crate) start: Float,
+    pub(crate) end: Float,
+    pub(crate) energy: LookupTable,
+    pub(crate) force: LookupTable,
+}
+
+impl TabulatedPair {
+    /// Returns a new [`TabulatedPair`] potential.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - Separation of the first sample.
+    /// * `step` - Spacing between consecutive samples.
+    /// * `energies` - Energy of a pair at each sample.
+    /// * `forces` - Derivative of the energy with respect to the separation at each sample.
+    pub fn new(start: Float, step: Float, energies: Vec<Float>, forces: Vec<Float>) -> TabulatedPair {
+        assert_eq!(energies.len(), forces.len(), "Expected an equal number of energy and force samples");
+        let end = start + step * (energies.len() - 1) as Float;
+        TabulatedPair {
+            start,
+            end,
+            energy: LookupTable::from_values(start, step, energies),
+            force: LookupTable::from_values(start, step, forces),
+        }
+    }
+
+    /// Returns a [`TabulatedPair`] potential whose forces are finite differences of the sampled energies.
+    pub fn from_energies(start: Float, step: Float, energies: Vec<Float>) -> TabulatedPair {
+        let last = energies.len() - 1;
+        let forces = (0..=last)
+            .map(|i| {
+                let (a, b) = (i.saturating_sub(1), (i + 1).min(last));
+                (energies[b] - energies[a]) / ((b - a) as Float * step)
+            })
+            .collect();
+        TabulatedPair::new(start, step, energies, forces)
+    }
+
+    /// Returns a [`TabulatedPair`] potential whose energies integrate the sampled forces inward from zero at the last sample.
+    pub fn from_forces(start: Float, step: Float, forces: Vec<Float>) -> TabulatedPair {
+        let mut energies = vec![0.0; forces.len()];
+        for i in (0..forces.len() - 1).rev() {
+            // trapezoidal rule for the energy difference across each interval
+            energies[i] = energies[i + 1] - 0.5 * step * (forces[i] + forces[i + 1]);
+        }
+        TabulatedPair::new(start, step, energies, forces)
+    }
+
+    /// Returns the separation of each sample.
+    pub fn distances(&self) -> Vec<Float> {
+        let samples = self.samples();
+        let step = (self.end - self.start) / (samples - 1) as Float;
+        (0..samples).map(|i| self.start + i as Float * step).collect()
+    }
+
+    /// Returns the energy at each sample.
+    pub fn energies(&self) -> &[Float] {
+        self.energy.values()
+    }
+
+    /// Returns the force at each sample.
+    pub fn forces(&self) -> &[Float] {
+        self.force.values()
+    }
+
+    /// Returns the number of samples in the table.
+    pub fn samples(&self) -> usize {
+        self.energy.len()
+    }
+}
+
+impl Potential for TabulatedPair {
+    fn describe(&self) -> String {
+        format!("TabulatedPair {{ start: {:?}, end: {:?}, samples: {} }}", self.start, self.end, self.samples())
+    }
+}
+
+/// Universal [ZBL](https://lammps.sandia.gov/doc/pair_zbl.html#description) screened nuclear repulsion.
+///
+/// The Coulomb repulsion of two bare nuclei screened by the universal function of Ziegler,
+/// Biersack, and Littmark, which describes the interaction of atoms at the very short
+/// separations reached in high energy collisions.
+#[derive(Clone, Copy, Debug)]
+pub struct Zbl {
+    /// Atomic number of the first element.
+    pub z_i: Float,
+    /// Atomic number of the second element.
+    pub z_j: Float,
+}
+
+impl Zbl {
+    /// Returns a new [`Zbl`] potential.
+    pub fn new(z_i: Float, z_j: Float) -> Zbl {
+        Zbl { z_i, z_j }
+    }
+
+    /// Returns a new [`Zbl`] potential between the nuclei of two elements.
+    pub fn from_elements(a: Element, b: Element) -> Zbl {
+        Zbl::new(a.number() as Float, b.number() as Float)
+    }
+}
+
+impl Potential for Zbl {
+    fn describe(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+/// Pair potential joined to the [`Zbl`] repulsion at short range.
+///
+/// Below `inner` the pair interacts through the ZBL potential alone and beyond `outer` through
+/// the wrapped potential alone. Between the two the energies are blended with a smoothstep
+/// weight whose derivative vanishes at both ends, so the energy and the force are continuous
+/// everywhere. Equilibrium potentials are rarely fit to close approaches and many become
+/// attractive or collapse there, which the ZBL core prevents in collision cascades.
+#[derive(Clone, Copy, Debug)]
+pub struct ZblSpline<T> {
+    /// Potential used beyond the spline.
+    pub potential: T,
+    /// Repulsion used below the spline.
+    pub zbl: Zbl,
+    /// Separation below which only the ZBL potential acts.
+    pub inner: Float,
+    /// Separation beyond which only the wrapped potential acts.
+    pub outer: Float,
+}
+
+impl<T> ZblSpline<T> {
+    /// Returns a new [`ZblSpline`] potential.
+    ///
+    /// # Arguments
+    ///
+    /// * `potential` - Potential used beyond the spline.
+    /// * `zbl` - Repulsion used below the spline.
+    /// * `inner` - Start of the spline.
+    /// * `outer` - End of the spline.
+    pub fn new(potential: T, zbl: Zbl, inner: Float, outer: Float) -> ZblSpline<T> {
+        assert!(inner < outer, "The spline must start before it ends.");
+        ZblSpline {
+            potential,
+            zbl,
+            inner,
+            outer,
+        }
+    }
+}
+
+impl<T: Potential> Potential for ZblSpline<T> {
+    fn describe(&self) -> String {
+        format!(
+            "ZblSpline {{ potential: {}, zbl: {:?}, inner: {:?}, outer: {:?} }}",
+            self.potential.describe(),
+            self.zbl,
+            self.inner,
+            self.outer
+        )
+    }
+}
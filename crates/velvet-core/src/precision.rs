@@ -0,0 +1,120 @@
+//! Floating point precision of the core types.
+//!
+//! [`Cell`](crate::system::cell::Cell), [`System`](crate::system::System), the analytic
+//! [pair potentials](crate::potentials::pair::PairPotential), and the kinetic
+//! [properties](crate::properties::IntrinsicProperty) are generic over a [`Real`] type. Each
+//! defaults to the precision selected by the `f64` feature, so a single build can still hold
+//! a system in either precision and convert between them with [`System::cast`](crate::system::System::cast).
+
+use std::fmt::Debug;
+
+use nalgebra::RealField;
+
+use crate::internal::Float;
+
+/// Floating point type of positions, velocities, lattice vectors, and the quantities derived from them.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::potentials::pair::PairPotential;
+/// use velvet_core::prelude::*;
+///
+/// // forces evaluated in single precision and accumulated in double precision
+/// let lj = LennardJones::new(0.238, 3.4);
+/// let total: f64 = [3.6f32, 3.8, 4.0].iter().map(|&r| lj.force(r).to_double()).sum();
+/// let exact: f64 = [3.6f64, 3.8, 4.0].iter().map(|&r| lj.force(r)).sum();
+/// assert!((total - exact).abs() < 1e-4);
+/// ```
+pub trait Real: RealField + Copy + Debug + Default + Send + Sync + 'static {
+    /// Converts from double precision, rounding to the nearest value of `Self`.
+    fn from_double(x: f64) -> Self;
+
+    /// Converts to double precision.
+    fn to_double(self) -> f64;
+
+    /// Converts from the default precision.
+    // the default precision is double precision under the `f64` feature
+    #[allow(clippy::unnecessary_cast)]
+    fn from_float(x: Float) -> Self {
+        Self::from_double(x as f64)
+    }
+
+    /// Converts to the default precision, rounding if it is single precision.
+    #[allow(clippy::unnecessary_cast)]
+    fn to_float(self) -> Float {
+        self.to_double() as Float
+    }
+
+    /// Converts to another precision.
+    fn cast<S: Real>(self) -> S {
+        S::from_double(self.to_double())
+    }
+}
+
+impl Real for f32 {
+    fn from_double(x: f64) -> f32 {
+        x as f32
+    }
+
+    fn to_double(self) -> f64 {
+        f64::from(self)
+    }
+}
+
+impl Real for f64 {
+    fn from_double(x: f64) -> f64 {
+        x
+    }
+
+    fn to_double(self) -> f64 {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Real;
+    use crate::properties::energy::KineticEnergy;
+    use crate::properties::pressure::KineticTensor;
+    use crate::properties::temperature::Temperature;
+    use crate::properties::IntrinsicProperty;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn conversions() {
+        assert_eq!(f32::from_double(0.1), 0.1f32);
+        assert_eq!(0.1f32.to_double(), f64::from(0.1f32));
+        assert_eq!(0.5f64.cast::<f32>(), 0.5f32);
+        assert_eq!(f64::from_float(2.0), 2.0);
+        assert_eq!(1.5f32.to_float(), 1.5);
+    }
+
+    #[test]
+    fn kinetic_properties() {
+        let system: System = System {
+            size: 2,
+            cell: Cell::cubic(10.0),
+            species: vec![Species::from_element(Element::Ar), Species::from_element(Element::Xe)],
+            positions: vec![Vector3::zeros(), Vector3::new(1.0, 2.0, 3.0)],
+            velocities: vec![Vector3::new(0.01, -0.02, 0.03), Vector3::new(-0.004, 0.005, 0.006)],
+            topology: Topology::default(),
+        };
+        let single: System<f32> = system.cast();
+        let double: System<f64> = system.cast();
+        assert_eq!(double.cast::<f32>().velocities, single.velocities);
+
+        let kinetic = KineticEnergy.calculate_intrinsic(&double);
+        assert_relative_eq!(KineticEnergy.calculate_intrinsic(&single).to_double(), kinetic, max_relative = 1e-6);
+        assert_relative_eq!(KineticEnergy.calculate_intrinsic(&system).to_double(), kinetic, max_relative = 1e-6);
+        assert_relative_eq!(KineticTensor.calculate_intrinsic(&double).trace(), 2.0 * kinetic, max_relative = 1e-12);
+        let temperature = Temperature.calculate_intrinsic(&double);
+        assert_relative_eq!(Temperature.calculate_intrinsic(&single).to_double(), temperature, max_relative = 1e-6);
+    }
+}
@@ -1,21 +1,64 @@
 //! Algorithms to control the progress of a simulation.
 
+use nalgebra::Vector3;
+
+use crate::barostats::Barostat;
+use crate::checkpoint::PropagatorState;
+use crate::errors::VelvetError;
 use crate::integrators::Integrator;
 use crate::potentials::Potentials;
 use crate::system::System;
 use crate::thermostats::Thermostat;
 
+/// Shared behavior for algorithms which advance the state of a system.
 pub trait Propagator: Send + Sync {
-    fn setup(&mut self, _: &mut System, _: &Potentials) {}
+    /// Prepares the propagator to run, or returns an error if it cannot act on the system.
+    fn setup(&mut self, _: &mut System, _: &Potentials) -> Result<(), VelvetError> {
+        Ok(())
+    }
+    /// Advances the system by one step.
     fn propagate(&mut self, _: &mut System, _: &Potentials) {}
+    /// Returns `true` once the propagator has finished, which ends the current run early.
+    fn converged(&self) -> bool {
+        false
+    }
+    /// Returns and clears the error which cut the last step short, which ends the current run
+    /// with that error.
+    fn take_error(&mut self) -> Option<VelvetError> {
+        None
+    }
+    /// Returns the internal state of the propagator to store in a checkpoint.
+    fn state(&self) -> PropagatorState {
+        PropagatorState::default()
+    }
+    /// Restores internal state previously returned by [`state`](Propagator::state) once the
+    /// propagator is set up, or returns an error if the state belongs to a different propagator.
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 0, 0)
+    }
 }
 
+/// Molecular dynamics propagation with an integrator, a thermostat, and an optional barostat.
+///
+/// The distances held fixed by the integrator are recorded as the constraints of the system's
+/// topology during setup, before the thermostat and barostat are set up, so the temperature
+/// excludes the degrees of freedom they remove. A step whose integration fails skips the
+/// thermostat and barostat and reports the error through
+/// [`take_error`](Propagator::take_error).
 pub struct MolecularDynamics {
     integrator: Box<dyn Integrator>,
     thermostat: Box<dyn Thermostat>,
+    barostat: Option<Box<dyn Barostat>>,
+    error: Option<VelvetError>,
 }
 
 impl MolecularDynamics {
+    /// Returns a new [`MolecularDynamics`] propagator.
+    ///
+    /// # Arguments
+    ///
+    /// * `integrator` - Algorithm which integrates the equations of motion.
+    /// * `thermostat` - Algorithm which controls the temperature of the system.
     pub fn new<I, T>(integrator: I, thermostat: T) -> MolecularDynamics
     where
         I: Integrator + 'static,
@@ -24,19 +67,203 @@ impl MolecularDynamics {
         MolecularDynamics {
             integrator: Box::new(integrator),
             thermostat: Box::new(thermostat),
+            barostat: None,
+            error: None,
         }
     }
+
+    /// Adds a barostat to control the pressure of the system.
+    pub fn with_barostat<B>(mut self, barostat: B) -> MolecularDynamics
+    where
+        B: Barostat + 'static,
+    {
+        self.barostat = Some(Box::new(barostat));
+        self
+    }
 }
 
 impl Propagator for MolecularDynamics {
-    fn setup(&mut self, system: &mut System, potentials: &Potentials) {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
         self.integrator.setup(system, potentials);
-        self.thermostat.setup(system);
+        system.topology.constraints = self.integrator.constrained_pairs();
+        self.thermostat.setup(system)?;
+        if let Some(barostat) = &mut self.barostat {
+            barostat.setup(system, potentials);
+        }
+        Ok(())
     }
 
     fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
         self.thermostat.pre_integrate(system);
-        self.integrator.integrate(system, potentials);
+        if let Some(barostat) = &mut self.barostat {
+            barostat.pre_integrate(system, potentials);
+        }
+        if let Err(err) = self.integrator.integrate(system, potentials) {
+            self.error = Some(err);
+            return;
+        }
         self.thermostat.post_integrate(system);
+        if let Some(barostat) = &mut self.barostat {
+            barostat.post_integrate(system, potentials);
+        }
+    }
+
+    fn take_error(&mut self) -> Option<VelvetError> {
+        self.error.take()
+    }
+
+    // the integrator, thermostat, and barostat states are stored as parts
+    fn state(&self) -> PropagatorState {
+        let barostat = match &self.barostat {
+            Some(barostat) => barostat.state(),
+            None => PropagatorState::default(),
+        };
+        PropagatorState {
+            parts: vec![self.integrator.state(), self.thermostat.state(), barostat],
+            ..PropagatorState::default()
+        }
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 0, 3)?;
+        self.integrator.restore(&state.parts[0])?;
+        self.thermostat.restore(&state.parts[1])?;
+        match &mut self.barostat {
+            Some(barostat) => barostat.restore(&state.parts[2]),
+            None => state.parts[2].expect(0, 0, 0),
+        }
+    }
+}
+
+/// Propagator which holds the atoms of a group fixed in place.
+///
+/// The atoms of the named [`Group`](crate::system::topology::Group) are recorded as the frozen
+/// atoms of the system's topology when the propagator is set up, which fails if the system has
+/// no group of that name. Integrators and minimizers take the forces on frozen atoms to be zero
+/// and their velocities are held at zero, so they never move during a step while they still
+/// exert forces on the rest of the system. Fixing the bottom layers of a surface slab this way
+/// mimics the bulk beneath it.
+///
+/// Each frozen atom removes three [degrees of freedom](System::degrees_of_freedom), so the
+/// [`Temperature`](crate::properties::temperature::Temperature) property and the thermostats
+/// measure the mobile atoms alone. Frozen atoms follow changes of the cell made by a barostat.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+/// let slab = Frozen::new(md, "fixed");
+/// ```
+pub struct Frozen<P: Propagator> {
+    propagator: P,
+    group: String,
+}
+
+impl<P: Propagator> Frozen<P> {
+    /// Returns `propagator` with the atoms of the group named `group` held fixed.
+    pub fn new(propagator: P, group: &str) -> Frozen<P> {
+        Frozen {
+            propagator,
+            group: group.to_string(),
+        }
+    }
+
+    /// Returns the wrapped propagator.
+    pub fn propagator(&self) -> &P {
+        &self.propagator
+    }
+}
+
+impl<P: Propagator> Propagator for Frozen<P> {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        system.topology.frozen = match system.topology.group(&self.group) {
+            Some(atoms) => atoms.to_vec(),
+            None => return Err(VelvetError::UnknownGroup(self.group.clone())),
+        };
+        for &i in &system.topology.frozen {
+            system.velocities[i] = Vector3::zeros();
+        }
+        self.propagator.setup(system, potentials)
+    }
+
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        self.propagator.propagate(system, potentials);
+        // thermostats which resample velocities act on every atom
+        for &i in &system.topology.frozen {
+            system.velocities[i] = Vector3::zeros();
+        }
+    }
+
+    fn converged(&self) -> bool {
+        self.propagator.converged()
+    }
+
+    fn take_error(&mut self) -> Option<VelvetError> {
+        self.propagator.take_error()
+    }
+
+    fn state(&self) -> PropagatorState {
+        self.propagator.state()
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.propagator.restore(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Frozen, MolecularDynamics, Propagator};
+    use crate::errors::VelvetError;
+    use crate::integrators::VelocityVerlet;
+    use crate::internal::Float;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::PotentialsBuilder;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::thermostats::NullThermostat;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn frozen_group() {
+        // a row of argon atoms compressed together with the first two held fixed
+        let argon = Species::new(39.948, 0.0);
+        let mut system = System {
+            size: 4,
+            cell: Cell::cubic(20.0),
+            species: vec![argon; 4],
+            positions: (0..4).map(|i| Vector3::new(5.0 + 3.2 * i as Float, 5.0, 5.0)).collect(),
+            velocities: vec![Vector3::new(0.001, 0.0, 0.0); 4],
+            topology: Topology::default(),
+        };
+        system.topology.add_group("fixed", vec![0, 1]);
+        let initial = system.positions.clone();
+        let mut potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (argon, argon), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+        let mut frozen = Frozen::new(MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat), "fixed");
+        frozen.setup(&mut system, &potentials).unwrap();
+        for _ in 0..10 {
+            frozen.propagate(&mut system, &potentials);
+        }
+
+        // the fixed atoms stay put while the last atom is pushed away
+        assert_eq!(&system.positions[..2], &initial[..2]);
+        assert!(system.velocities[..2].iter().all(|velocity| *velocity == Vector3::zeros()));
+        assert!(system.velocities[3].x > 0.001);
+        assert!(system.positions[3].x > initial[3].x + 0.01);
+        assert_eq!(system.topology.frozen, vec![0, 1]);
+        assert_eq!(system.degrees_of_freedom(), 6);
+
+        let mut missing = Frozen::new(MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat), "walls");
+        assert!(matches!(
+            missing.setup(&mut system, &potentials),
+            Err(VelvetError::UnknownGroup(name)) if name == "walls"
+        ));
     }
 }
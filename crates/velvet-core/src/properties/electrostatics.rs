@@ -0,0 +1,331 @@
+//! Electrostatic potential and field of the charge distribution on a grid.
+
+use std::io::{self, Write};
+
+#[cfg(feature = "f64")]
+use libm::erfc;
+
+#[cfg(not(feature = "f64"))]
+use libm::erfcf as erfc;
+
+use nalgebra::Vector3;
+
+use crate::internal::consts::{COULOMB, FRAC_2_SQRT_PI, PI};
+use crate::internal::Float;
+use crate::properties::IntrinsicProperty;
+use crate::system::System;
+
+/// Length of one bohr in angstroms.
+const BOHR: Float = 0.529177;
+
+/// Energy of one hartree in kcal/mol.
+const HARTREE: Float = 627.5095;
+
+/// Electrostatic potential and field on a regular grid spanning the cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PotentialMap {
+    /// Number of grid points along each cell vector.
+    pub shape: [usize; 3],
+    /// Potential at each grid point in kcal/mol/e, with the last index varying fastest.
+    pub potential: Vec<Float>,
+    /// Electric field at each grid point in kcal/mol/e/angstrom.
+    pub field: Vec<Vector3<Float>>,
+}
+
+impl PotentialMap {
+    /// Returns the index into the maps of the grid point at fractional coordinates `(i, j, k) / shape`.
+    pub fn index(&self, i: usize, j: usize, k: usize) -> usize {
+        (i * self.shape[1] + j) * self.shape[2] + k
+    }
+
+    /// Returns the potential averaged over each plane of grid points normal to cell vector `axis`.
+    ///
+    /// The planar average across an interface gives the potential drop of an electric double layer.
+    pub fn planar_average(&self, axis: usize) -> Vec<Float> {
+        let mut sums = vec![0.0; self.shape[axis]];
+        for i in 0..self.shape[0] {
+            for j in 0..self.shape[1] {
+                for k in 0..self.shape[2] {
+                    sums[[i, j, k][axis]] += self.potential[self.index(i, j, k)];
+                }
+            }
+        }
+        let plane = (self.potential.len() / self.shape[axis]) as Float;
+        sums.iter().map(|sum| sum / plane).collect()
+    }
+
+    /// Writes the atoms of `system` and the potential in the Gaussian cube format.
+    ///
+    /// Distances are written in bohr and the potential in hartree per elementary charge, the
+    /// atomic units expected by VMD, OVITO, and other readers of cube files. Atoms of custom
+    /// species are written with an atomic number of zero.
+    pub fn write_cube(&self, system: &System, writer: &mut dyn Write) -> io::Result<()> {
+        writeln!(writer, "Velvet electrostatic potential")?;
+        writeln!(writer, "hartree/e on a {}x{}x{} grid", self.shape[0], self.shape[1], self.shape[2])?;
+        writeln!(writer, "{:5} {:12.6} {:12.6} {:12.6}", system.size, 0.0, 0.0, 0.0)?;
+        let matrix = system.cell.matrix();
+        for axis in 0..3 {
+            let voxel = matrix.column(axis) / (self.shape[axis] as Float * BOHR);
+            writeln!(
+                writer,
+                "{:5} {:12.6} {:12.6} {:12.6}",
+                self.shape[axis], voxel[0], voxel[1], voxel[2]
+            )?;
+        }
+        for (i, (species, pos)) in system.species.iter().zip(system.positions.iter()).enumerate() {
+            let number = species.element().map_or(0, |element| element.number());
+            let pos = pos / BOHR;
+            writeln!(
+                writer,
+                "{:5} {:12.6} {:12.6} {:12.6} {:12.6}",
+                number,
+                system.charge(i),
+                pos[0],
+                pos[1],
+                pos[2]
+            )?;
+        }
+        for row in self.potential.chunks(self.shape[2]) {
+            for line in row.chunks(6) {
+                let values: Vec<String> = line.iter().map(|v| format!("{:13.5E}", v / HARTREE)).collect();
+                writeln!(writer, "{}", values.join(""))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Electrostatic potential and field of the atomic charges on a grid, evaluated by Ewald summation.
+///
+/// The grid spans the cell with `shape` points along each cell vector at fractional coordinates
+/// `i / n`. The potential of the periodic charges is split into a screened real space sum over
+/// the minimum images within the cutoff, which must not exceed the inscribed radius of the cell,
+/// and a smooth reciprocal space sum, with the same conventions as the [`Ewald`] potential. Grid
+/// points which coincide with an atom are singular.
+///
+/// The potential is in kcal/mol/e, which converts to volts by multiplying by 0.0433641, and is
+/// defined up to a constant. The planar average across an electrode or an interface gives the
+/// potential profile of electric double layers.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// # let system = System {
+/// #     size: 0,
+/// #     cell: Cell::cubic(10.0),
+/// #     species: Vec::new(),
+/// #     positions: Vec::new(),
+/// #     velocities: Vec::new(),
+/// #     topology: Topology::default(),
+/// # };
+///
+/// let map = ElectrostaticMap::new([20, 20, 40], 0.3, 5.0, 6).calculate_intrinsic(&system);
+/// let profile = map.planar_average(2);
+/// let mut cube = Vec::new();
+/// map.write_cube(&system, &mut cube).unwrap();
+/// ```
+///
+/// [`Ewald`]: crate::potentials::types::Ewald
+#[derive(Clone, Copy, Debug)]
+pub struct ElectrostaticMap {
+    shape: [usize; 3],
+    alpha: Float,
+    cutoff: Float,
+    kmax: usize,
+}
+
+impl ElectrostaticMap {
+    /// Returns a new [`ElectrostaticMap`].
+    ///
+    /// # Arguments
+    ///
+    /// * `shape` - Number of grid points along each cell vector.
+    /// * `alpha` - Splitting parameter between real and reciprocal space.
+    /// * `cutoff` - Cutoff radius of the real space sum.
+    /// * `kmax` - Largest multiple of each reciprocal cell vector in the reciprocal sum.
+    pub fn new(shape: [usize; 3], alpha: Float, cutoff: Float, kmax: usize) -> ElectrostaticMap {
+        assert!(shape.iter().all(|&n| n > 0), "The grid must have at least one point along each axis.");
+        ElectrostaticMap {
+            shape,
+            alpha,
+            cutoff,
+            kmax,
+        }
+    }
+}
+
+// Reciprocal vector with its Gaussian weight and the structure factor of the charges.
+struct Wave {
+    n: Vector3<Float>,
+    m: Vector3<Float>,
+    weight: Float,
+    cos: Float,
+    sin: Float,
+}
+
+impl IntrinsicProperty for ElectrostaticMap {
+    type Res = PotentialMap;
+
+    fn calculate_intrinsic(&self, system: &System) -> Self::Res {
+        let alpha = self.alpha;
+        let inv = system.cell.inverse_matrix();
+        let prefactor = COULOMB / (PI * system.cell.volume());
+        let fractional: Vec<Vector3<Float>> = system.positions.iter().map(|pos| system.cell.fractional(pos)).collect();
+        let charges = system.charges();
+
+        let kmax = self.kmax as i64;
+        let mut waves = Vec::new();
+        for n1 in -kmax..=kmax {
+            for n2 in -kmax..=kmax {
+                for n3 in -kmax..=kmax {
+                    if n1 == 0 && n2 == 0 && n3 == 0 {
+                        continue;
+                    }
+                    let n = Vector3::new(n1 as Float, n2 as Float, n3 as Float);
+                    let m = inv.transpose() * n;
+                    let m2 = m.norm_squared();
+                    let (mut cos, mut sin) = (0.0, 0.0);
+                    for (frac, q) in fractional.iter().zip(charges.iter()) {
+                        let phase = 2.0 * PI * n.dot(frac);
+                        cos += q * phase.cos();
+                        sin += q * phase.sin();
+                    }
+                    waves.push(Wave {
+                        n,
+                        m,
+                        weight: Float::exp(-PI.powi(2) * m2 / alpha.powi(2)) / m2,
+                        cos,
+                        sin,
+                    });
+                }
+            }
+        }
+
+        let [nx, ny, nz] = self.shape;
+        let mut potential = Vec::with_capacity(nx * ny * nz);
+        let mut field = Vec::with_capacity(nx * ny * nz);
+        for i in 0..nx {
+            for j in 0..ny {
+                for k in 0..nz {
+                    let frac = Vector3::new(i as Float / nx as Float, j as Float / ny as Float, k as Float / nz as Float);
+                    let point = system.cell.cartesian(&frac);
+                    let (mut phi, mut e) = (0.0, Vector3::zeros());
+
+                    // screened charges within the cutoff
+                    for (pos, charge) in system.positions.iter().zip(charges.iter()) {
+                        let mut separation = point - pos;
+                        system.cell.vector_image(&mut separation);
+                        let r = separation.norm();
+                        if r < self.cutoff {
+                            let q = COULOMB * charge;
+                            let screened = erfc(alpha * r) / r;
+                            phi += q * screened;
+                            let gaussian = FRAC_2_SQRT_PI * alpha * Float::exp(-(alpha * r).powi(2));
+                            e += q * (screened + gaussian) / r.powi(2) * separation;
+                        }
+                    }
+
+                    // smooth compensating charge distributions
+                    for wave in &waves {
+                        let phase = 2.0 * PI * wave.n.dot(&frac);
+                        let (s, c) = phase.sin_cos();
+                        phi += prefactor * wave.weight * (c * wave.cos + s * wave.sin);
+                        e += prefactor * wave.weight * 2.0 * PI * (s * wave.cos - c * wave.sin) * wave.m;
+                    }
+                    potential.push(phi);
+                    field.push(e);
+                }
+            }
+        }
+        PotentialMap {
+            shape: self.shape,
+            potential,
+            field,
+        }
+    }
+
+    fn name(&self) -> String {
+        "electrostatic_map".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElectrostaticMap;
+    use crate::internal::consts::COULOMB;
+    use crate::internal::Float;
+    use crate::properties::IntrinsicProperty;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // ion pair off the grid points of a cubic cell
+    fn ion_pair() -> System {
+        let sodium = Species::from_id(Element::Na.number() as u128, Element::Na.mass(), 1.0);
+        let chlorine = Species::from_id(Element::Cl.number() as u128, Element::Cl.mass(), -1.0);
+        System {
+            size: 2,
+            cell: Cell::cubic(12.0),
+            species: vec![sodium, chlorine],
+            positions: vec![Vector3::new(3.1, 3.3, 3.2), Vector3::new(8.9, 8.7, 8.6)],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn splitting_independence() {
+        let system = ion_pair();
+        let a = ElectrostaticMap::new([4, 4, 4], 0.35, 6.0, 7).calculate_intrinsic(&system);
+        let b = ElectrostaticMap::new([4, 4, 4], 0.5, 6.0, 10).calculate_intrinsic(&system);
+        for (x, y) in a.potential.iter().zip(b.potential.iter()) {
+            assert!((x - y).abs() < 1e-2 * COULOMB / 12.0, "{} {}", x, y);
+        }
+        for (x, y) in a.field.iter().zip(b.field.iter()) {
+            assert!((x - y).norm() < 1e-2 * COULOMB / 12.0, "{} {}", x, y);
+        }
+        // the potential is dominated by the nearby ion
+        let near = a.potential[a.index(1, 1, 1)];
+        let r = (Vector3::new(3.0, 3.0, 3.0) - system.positions[0]).norm();
+        assert!((near - COULOMB / r).abs() < 0.1 * COULOMB / r, "{}", near);
+    }
+
+    #[test]
+    fn field_gradient() {
+        let system = ion_pair();
+        // the field is the negative gradient of the potential along each cell vector
+        let map = ElectrostaticMap::new([48, 4, 4], 0.4, 6.0, 8).calculate_intrinsic(&system);
+        let h = 12.0 / 48.0;
+        for i in [10, 30].iter() {
+            let derivative = (map.potential[map.index(i + 1, 2, 2)] - map.potential[map.index(i - 1, 2, 2)]) / (2.0 * h);
+            let field = map.field[map.index(*i, 2, 2)].x;
+            assert!((field + derivative).abs() < 0.02 * derivative.abs().max(1.0), "{} {}", field, derivative);
+        }
+        let profile = map.planar_average(0);
+        assert_eq!(profile.len(), 48);
+    }
+
+    #[test]
+    fn cube_file() {
+        let system = ion_pair();
+        let map = ElectrostaticMap::new([3, 2, 8], 0.4, 6.0, 5).calculate_intrinsic(&system);
+        let mut buffer = Vec::new();
+        map.write_cube(&system, &mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[2].split_whitespace().next(), Some("2"));
+        let voxel: Vec<Float> = lines[3].split_whitespace().map(|v| v.parse().unwrap()).collect();
+        assert_eq!(voxel[0], 3.0);
+        assert!((voxel[1] - 4.0 / 0.529177).abs() < 1e-4);
+        assert_eq!(lines[6].split_whitespace().next(), Some("11"));
+        assert_eq!(lines[7].split_whitespace().next(), Some("17"));
+        // two lines of six and two values for each of the six rows of eight points
+        assert_eq!(lines.len(), 8 + 12);
+        let values: usize = lines[8..].iter().map(|line| line.split_whitespace().count()).sum();
+        assert_eq!(values, 48);
+    }
+}
@@ -3,10 +3,17 @@
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
 
+use std::sync::{Arc, Mutex};
+
+use nalgebra::Vector3;
+
 use crate::internal::Float;
 use crate::potentials::Potentials;
 use crate::potentials::coulomb::CoulombPotentialMeta;
 use crate::potentials::pair::PairPotentialMeta;
+use crate::precision::Real;
+#[cfg(feature = "simd")]
+use crate::potentials::simd;
 use crate::properties::{IntrinsicProperty, Property};
 use crate::system::System;
 
@@ -15,12 +22,10 @@ use crate::system::System;
 pub struct CoulombicEnergy;
 
 impl CoulombicEnergy {
-    fn calculate_inner(&self, meta: &CoulombPotentialMeta, system: &System, i: usize, j: usize) -> Float {
-        let pos_i = system.positions[i];
-        let qi = system.species[i].charge();
-        let pos_j = system.positions[j];
-        let qj = system.species[j].charge();
-        let r = system.cell.distance(&pos_i, &pos_j);
+    fn calculate_inner(&self, meta: &CoulombPotentialMeta, system: &System, (i, j, separation): (usize, usize, Vector3<Float>)) -> Float {
+        let qi = system.charge(i);
+        let qj = system.charge(j);
+        let r = separation.norm();
         if r < meta.cutoff {
             meta.potential.energy(qi, qj, r)
         } else {
@@ -36,12 +41,13 @@ impl Property for CoulombicEnergy {
     fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
         match &potentials.coulomb_meta {
             None => 0.0,
-            Some(meta) => meta
-                .selection
-                .indices()
-                .map(|&[i, j]| {
-                    self.calculate_inner(meta, system, i, j)
-                }).sum()
+            Some(meta) => {
+                let pairwise: Float = meta
+                    .pairs(system)
+                    .map(|pair| self.calculate_inner(meta, system, pair))
+                    .sum();
+                pairwise + meta.long_range_energy(system)
+            }
         }
     }
 
@@ -49,12 +55,13 @@ impl Property for CoulombicEnergy {
     fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
         match &potentials.coulomb_meta {
             None => 0.0,
-            Some(meta) => meta
-                .selection
-                .par_indices()
-                .map(|&[i, j]| {
-                    self.calculate_inner(meta, system, i, j)
-                }).sum()
+            Some(meta) => {
+                let pairwise: Float = meta
+                    .par_pairs(system)
+                    .map(|pair| self.calculate_inner(meta, system, pair))
+                    .sum();
+                pairwise + meta.long_range_energy(system)
+            }
         }
     }
 
@@ -68,51 +75,133 @@ impl Property for CoulombicEnergy {
 pub struct PairEnergy;
 
 impl PairEnergy {
-    fn calculate_inner(&self, meta: &PairPotentialMeta, system: &System, i: usize, j: usize) -> Float {
-        let pos_i = system.positions[i];
-        let pos_j = system.positions[j];
-        let r = system.cell.distance(&pos_i, &pos_j);
+    fn calculate_inner(&self, meta: &PairPotentialMeta, weights: Option<&[Float]>, (i, j, separation): (usize, usize, Vector3<Float>)) -> Float {
+        let r = separation.norm();
         if r < meta.cutoff {
-            meta.potential.energy(r)
+            meta.weight(weights, i, j) * meta.energy(r)
         } else {
             0.0
         }
     }
+
+    #[cfg(not(feature = "rayon"))]
+    fn calculate_pairs(&self, meta: &PairPotentialMeta, system: &System, weights: Option<&[Float]>) -> Float {
+        meta.pairs(system).map(|pair| self.calculate_inner(meta, weights, pair)).sum()
+    }
+
+    #[cfg(feature = "rayon")]
+    fn calculate_pairs(&self, meta: &PairPotentialMeta, system: &System, weights: Option<&[Float]>) -> Float {
+        meta.par_pairs(system).map(|pair| self.calculate_inner(meta, weights, pair)).sum()
+    }
+
+    // Evaluates a pair potential on the GPU or with SIMD kernels when they support it.
+    #[cfg_attr(not(feature = "gpu"), allow(unused_variables))]
+    fn calculate_meta(&self, meta: &PairPotentialMeta, system: &System, potentials: &Potentials, weights: Option<&[Float]>) -> Float {
+        #[cfg(feature = "gpu")]
+        if let Some(results) = potentials.gpu_pairs().and_then(|gpu| gpu.evaluate(meta, system, weights)) {
+            return results.iter().map(|pair| pair.energy).sum();
+        }
+        #[cfg(feature = "simd")]
+        if let Some(sums) = simd::evaluate(meta, system, weights, false) {
+            return sums.energy;
+        }
+        self.calculate_pairs(meta, system, weights)
+    }
 }
 
 impl Property for PairEnergy {
     type Res = Float;
 
-    #[cfg(not(feature = "rayon"))]
     fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let weights = potentials.resolution_weights(system);
+        let weights = weights.as_deref();
         potentials
             .pair_metas
             .iter()
-            .map(|meta| -> Float {
-                meta.selection
-                    .indices()
-                    .map(|&[i, j]| -> Float {
-                        self.calculate_inner(meta, system, i, j)
-                    }).sum()
-            }).sum()
+            .map(|meta| self.calculate_meta(meta, system, potentials, weights) + meta.tail_energy(system, weights))
+            .sum()
     }
 
-    #[cfg(feature = "rayon")]
+    fn name(&self) -> String {
+        "pair_energy".to_string()
+    }
+}
+
+/// Potential energy due to the embedded atom potential.
+#[derive(Clone, Copy, Debug)]
+pub struct EmbeddedAtomEnergy;
+
+impl Property for EmbeddedAtomEnergy {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        match &potentials.embedded_atom_meta {
+            None => 0.0,
+            Some(meta) => meta.energies(system).iter().sum(),
+        }
+    }
+
+    fn name(&self) -> String {
+        "embedded_atom_energy".to_string()
+    }
+}
+
+/// Potential energy due to the external potential.
+#[derive(Clone, Copy, Debug)]
+pub struct ExternalPotentialEnergy;
+
+impl Property for ExternalPotentialEnergy {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        match &potentials.external_meta {
+            None => 0.0,
+            Some(meta) => meta.evaluate(system).energy,
+        }
+    }
+
+    fn name(&self) -> String {
+        "external_potential_energy".to_string()
+    }
+}
+
+/// Potential energy of the system due to biases on collective variables.
+#[derive(Clone, Copy, Debug)]
+pub struct BiasEnergy;
+
+impl Property for BiasEnergy {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        if potentials.biases.is_empty() {
+            0.0
+        } else {
+            potentials.bias_evaluation(system).energy
+        }
+    }
+
+    fn name(&self) -> String {
+        "bias_energy".to_string()
+    }
+}
+
+/// Potential energy of the system due to fields which act on each atom independently.
+#[derive(Clone, Copy, Debug)]
+pub struct FieldEnergy;
+
+impl Property for FieldEnergy {
+    type Res = Float;
+
     fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
         potentials
-            .pair_metas
+            .fields
             .iter()
-            .map(|meta| -> Float {
-                meta.selection
-                    .par_indices()
-                    .map(|&[i, j]| -> Float {
-                        self.calculate_inner(meta, system, i, j)
-                    }).sum()
-            }).sum()
+            .map(|field| (0..system.size).map(|i| field.energy(system, i)).sum::<Float>())
+            .sum()
     }
 
     fn name(&self) -> String {
-        "pair_energy".to_string()
+        "field_energy".to_string()
     }
 }
 
@@ -126,7 +215,11 @@ impl Property for PotentialEnergy {
     fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
         let coulomb_energy = CoulombicEnergy.calculate(system, potentials);
         let pair_energy = PairEnergy.calculate(system, potentials);
-        coulomb_energy + pair_energy
+        let embedded_atom_energy = EmbeddedAtomEnergy.calculate(system, potentials);
+        let external_energy = ExternalPotentialEnergy.calculate(system, potentials);
+        let bias_energy = BiasEnergy.calculate(system, potentials);
+        let field_energy = FieldEnergy.calculate(system, potentials);
+        coulomb_energy + pair_energy + embedded_atom_energy + external_energy + bias_energy + field_energy
     }
 
     fn name(&self) -> String {
@@ -138,19 +231,18 @@ impl Property for PotentialEnergy {
 #[derive(Clone, Copy, Debug)]
 pub struct KineticEnergy;
 
-impl IntrinsicProperty for KineticEnergy {
-    type Res = Float;
+impl<R: Real> IntrinsicProperty<R> for KineticEnergy {
+    type Res = R;
 
-    fn calculate_intrinsic(&self, system: &System) -> <Self as IntrinsicProperty>::Res {
-        let kinetic_energy: Float = system
+    fn calculate_intrinsic(&self, system: &System<R>) -> R {
+        let half = R::from_double(0.5);
+        system
             .species
             .iter()
             .zip(system.velocities.iter())
-            .map(|(species, vel)| {
-                0.5 * species.mass() * vel.norm_squared()
+            .fold(R::zero(), |kinetic_energy, (species, vel)| {
+                kinetic_energy + half * R::from_float(species.mass()) * vel.norm_squared()
             })
-            .sum();
-        kinetic_energy
     }
 
     fn name(&self) -> String {
@@ -175,3 +267,30 @@ impl Property for TotalEnergy {
         "total_energy".to_string()
     }
 }
+
+/// Energy of an extended system which is conserved by its equations of motion.
+///
+/// Sum of the total energy of the atoms and the energy stored in the variables of a
+/// thermostat, as returned by [`NoseHoover::conserved_energy`](crate::thermostats::NoseHoover::conserved_energy).
+#[derive(Clone, Debug)]
+pub struct ConservedEnergy {
+    reservoir: Arc<Mutex<Float>>,
+}
+
+impl ConservedEnergy {
+    pub(crate) fn new(reservoir: Arc<Mutex<Float>>) -> ConservedEnergy {
+        ConservedEnergy { reservoir }
+    }
+}
+
+impl Property for ConservedEnergy {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        TotalEnergy.calculate(system, potentials) + *self.reservoir.lock().unwrap()
+    }
+
+    fn name(&self) -> String {
+        "conserved_energy".to_string()
+    }
+}
@@ -3,34 +3,63 @@
 #[cfg(feature = "rayon")]
 use rayon::prelude::*;
 
-use nalgebra::Vector3;
+use nalgebra::{Matrix3, Vector3};
 
 use crate::internal::Float;
 use crate::potentials::Potentials;
 use crate::potentials::coulomb::CoulombPotentialMeta;
 use crate::potentials::pair::PairPotentialMeta;
-use crate::properties::Property;
+#[cfg(feature = "simd")]
+use crate::potentials::simd;
+use crate::properties::{PerAtomProperty, Property};
 use crate::system::System;
 
+/// Forces acting on each atom paired with the virial tensor accumulated alongside them.
+type ForcesAndVirial = (Vec<Vector3<Float>>, Matrix3<Float>);
+
 /// Force acting on each atom in the system due to Coulombic potentials.
 #[derive(Clone, Copy, Debug)]
 pub struct CoulombicForces;
 
 impl CoulombicForces {
-    fn calculate_inner(&self, mut accumulator: Vec<Vector3<Float>>, meta: &CoulombPotentialMeta, system: &System, i: usize, j: usize) -> Vec<Vector3<Float>> {
-        let pos_i = system.positions[i];
-        let qi = system.species[i].charge();
-        let pos_j = system.positions[j];
-        let qj = system.species[j].charge();
-        let r = system.cell.distance(&pos_i, &pos_j);
+    fn calculate_inner(&self, mut accumulator: ForcesAndVirial, meta: &CoulombPotentialMeta, system: &System, (i, j, separation): (usize, usize, Vector3<Float>)) -> ForcesAndVirial {
+        let qi = system.charge(i);
+        let qj = system.charge(j);
+        let r = separation.norm();
         if r < meta.cutoff {
-            let dir = system.cell.direction(&pos_i, &pos_j);
+            let dir = separation / r;
             let force = meta.potential.force(qi, qj, r) * dir;
-            accumulator[i] += force;
-            accumulator[j] -= force;
+            accumulator.0[i] += force;
+            accumulator.0[j] -= force;
+            accumulator.1 -= r * dir * force.transpose();
         }
         accumulator
     }
+
+    /// Returns the Coulombic forces along with the virial tensor of the Coulombic potential.
+    pub(crate) fn calculate_with_virial(&self, system: &System, potentials: &Potentials) -> ForcesAndVirial {
+        match &potentials.coulomb_meta {
+            None => (vec![Vector3::zeros(); system.size], Matrix3::zeros()),
+            Some(meta) => {
+                let (pairwise, virial) = meta.pairs(system).fold(
+                    (vec![Vector3::zeros(); system.size], Matrix3::zeros()),
+                    |accumulator, pair| self.calculate_inner(accumulator, meta, system, pair),
+                );
+                let forces = match meta.long_range_forces(system) {
+                    None => pairwise,
+                    Some(long_range) => pairwise
+                        .iter()
+                        .zip(long_range.iter())
+                        .map(|(a, b)| a + b)
+                        .collect(),
+                };
+                match meta.long_range_virial(system) {
+                    None => (forces, virial),
+                    Some(long_range) => (forces, virial + long_range),
+                }
+            }
+        }
+    }
 }
 
 impl Property for CoulombicForces {
@@ -39,12 +68,20 @@ impl Property for CoulombicForces {
     fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
         match &potentials.coulomb_meta {
             None => vec![Vector3::zeros(); system.size],
-            Some(meta) => meta.selection.indices().fold(
-                vec![Vector3::zeros(); system.size],
-                |accumulator, &[i, j]| {
-                    self.calculate_inner(accumulator, meta, system, i, j)
+            Some(meta) => {
+                let (pairwise, _) = meta.pairs(system).fold(
+                    (vec![Vector3::zeros(); system.size], Matrix3::zeros()),
+                    |accumulator, pair| self.calculate_inner(accumulator, meta, system, pair),
+                );
+                match meta.long_range_forces(system) {
+                    None => pairwise,
+                    Some(long_range) => pairwise
+                        .iter()
+                        .zip(long_range.iter())
+                        .map(|(a, b)| a + b)
+                        .collect(),
                 }
-            )
+            }
         }
     }
 
@@ -59,62 +96,176 @@ pub struct PairForces;
 
 impl PairForces {
     #[cfg(not(feature = "rayon"))]
-    fn calculate_inner(&self, meta: &PairPotentialMeta, system: &System) -> Vec<Vector3<Float>> {
-        meta.selection.indices().fold(vec![Vector3::zeros(); system.size], |mut accumulator, &[i, j]| {
-            let pos_i = system.positions[i];
-            let pos_j = system.positions[j];
-            let r = system.cell.distance(&pos_i, &pos_j);
+    fn calculate_inner(&self, meta: &PairPotentialMeta, system: &System, weights: Option<&[Float]>) -> ForcesAndVirial {
+        meta.pairs(system).fold((vec![Vector3::zeros(); system.size], Matrix3::zeros()), |mut accumulator, (i, j, separation)| {
+            let r = separation.norm();
             if r < meta.cutoff {
-                let dir = system.cell.direction(&pos_i, &pos_j);
-                let force = meta.potential.force(r) * dir;
-                accumulator[i] += force;
-                accumulator[j] -= force;
+                let dir = separation / r;
+                let force = meta.weight(weights, i, j) * meta.force(r) * dir;
+                accumulator.0[i] += force;
+                accumulator.0[j] -= force;
+                accumulator.1 -= r * dir * force.transpose();
             }
             accumulator
         })
     }
 
     #[cfg(feature = "rayon")]
-    fn calculate_inner(&self, meta: &PairPotentialMeta, system: &System) -> Vec<Vector3<Float>>{
-        meta.selection.par_indices().fold(|| vec![Vector3::zeros(); system.size], |mut accumulator, &[i, j]| {
-            let pos_i = system.positions[i];
-            let pos_j = system.positions[j];
-            let r = system.cell.distance(&pos_i, &pos_j);
+    fn calculate_inner(&self, meta: &PairPotentialMeta, system: &System, weights: Option<&[Float]>) -> ForcesAndVirial {
+        meta.par_pairs(system).fold(|| (vec![Vector3::zeros(); system.size], Matrix3::zeros()), |mut accumulator, (i, j, separation)| {
+            let r = separation.norm();
             if r < meta.cutoff {
-                let dir = system.cell.direction(&pos_i, &pos_j);
-                let force = meta.potential.force(r) * dir;
-                accumulator[i] += force;
-                accumulator[j] -= force;
+                let dir = separation / r;
+                let force = meta.weight(weights, i, j) * meta.force(r) * dir;
+                accumulator.0[i] += force;
+                accumulator.0[j] -= force;
+                accumulator.1 -= r * dir * force.transpose();
             }
             accumulator
         })
-        .reduce(|| vec![Vector3::zeros(); system.size], |a, b| {
-            a.iter().zip(b.iter()).map(|(_a, _b)| _a + _b).collect()
+        .reduce(|| (vec![Vector3::zeros(); system.size], Matrix3::zeros()), |a, b| {
+            (a.0.iter().zip(b.0.iter()).map(|(_a, _b)| _a + _b).collect(), a.1 + b.1)
         })
     }
-}
 
-impl Property for PairForces {
-    type Res = Vec<Vector3<Float>>;
+    // Evaluates a pair potential on the GPU or with SIMD kernels when they support it.
+    #[cfg_attr(not(feature = "gpu"), allow(unused_variables))]
+    fn calculate_meta(&self, meta: &PairPotentialMeta, system: &System, potentials: &Potentials, weights: Option<&[Float]>) -> ForcesAndVirial {
+        #[cfg(feature = "gpu")]
+        if let Some(results) = potentials.gpu_pairs().and_then(|gpu| gpu.evaluate(meta, system, weights)) {
+            return results.into_iter().fold(
+                (vec![Vector3::zeros(); system.size], Matrix3::zeros()),
+                |mut accumulator, pair| {
+                    accumulator.0[pair.i] += pair.force;
+                    accumulator.0[pair.j] -= pair.force;
+                    accumulator.1 -= pair.separation * pair.force.transpose();
+                    accumulator
+                },
+            );
+        }
+        #[cfg(feature = "simd")]
+        if let Some(sums) = simd::evaluate(meta, system, weights, true) {
+            return (sums.forces.to_vectors(), sums.virial);
+        }
+        self.calculate_inner(meta, system, weights)
+    }
 
-    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+    /// Returns the pairwise forces along with the virial tensor accumulated over every pair potential.
+    pub(crate) fn calculate_with_virial(&self, system: &System, potentials: &Potentials) -> ForcesAndVirial {
+        let weights = potentials.resolution_weights(system);
+        let weights = weights.as_deref();
         potentials.pair_metas.iter().fold(
-            vec![Vector3::zeros(); system.size],
+            (vec![Vector3::zeros(); system.size], Matrix3::zeros()),
             |accumulator, meta| {
-                accumulator
+                let (forces, virial) = self.calculate_meta(meta, system, potentials, weights);
+                let forces = accumulator
+                    .0
                     .iter()
-                    .zip(self.calculate_inner(meta, system).iter())
+                    .zip(forces.iter())
                     .map(|(a, b)| a + b)
-                    .collect()
+                    .collect();
+                (forces, accumulator.1 + virial + meta.tail_virial(system, weights))
             },
         )
     }
+}
+
+impl Property for PairForces {
+    type Res = Vec<Vector3<Float>>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let (forces, _) = self.calculate_with_virial(system, potentials);
+        forces
+    }
 
     fn name(&self) -> String {
         "pair_forces".to_string()
     }
 }
 
+/// Force acting on each atom in the system due to the embedded atom potential.
+#[derive(Clone, Copy, Debug)]
+pub struct EmbeddedAtomForces;
+
+impl EmbeddedAtomForces {
+    /// Returns the embedded atom forces along with the virial tensor of the embedded atom potential.
+    pub(crate) fn calculate_with_virial(&self, system: &System, potentials: &Potentials) -> ForcesAndVirial {
+        let mut accumulator = (vec![Vector3::zeros(); system.size], Matrix3::zeros());
+        if let Some(meta) = &potentials.embedded_atom_meta {
+            for (i, j, separation, force) in meta.pair_forces(system) {
+                let r = separation.norm();
+                let dir = separation / r;
+                let force = force * dir;
+                accumulator.0[i] += force;
+                accumulator.0[j] -= force;
+                accumulator.1 -= r * dir * force.transpose();
+            }
+        }
+        accumulator
+    }
+}
+
+impl Property for EmbeddedAtomForces {
+    type Res = Vec<Vector3<Float>>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let (forces, _) = self.calculate_with_virial(system, potentials);
+        forces
+    }
+
+    fn name(&self) -> String {
+        "embedded_atom_forces".to_string()
+    }
+}
+
+/// Force acting on each atom in the system due to the external potential.
+#[derive(Clone, Copy, Debug)]
+pub struct ExternalPotentialForces;
+
+impl ExternalPotentialForces {
+    /// Returns the external forces along with the virial tensor of the external potential, or zeros if it is unknown.
+    pub(crate) fn calculate_with_virial(&self, system: &System, potentials: &Potentials) -> ForcesAndVirial {
+        match &potentials.external_meta {
+            None => (vec![Vector3::zeros(); system.size], Matrix3::zeros()),
+            Some(meta) => {
+                let evaluation = meta.evaluate(system);
+                (evaluation.forces, evaluation.virial.unwrap_or_else(Matrix3::zeros))
+            }
+        }
+    }
+}
+
+impl Property for ExternalPotentialForces {
+    type Res = Vec<Vector3<Float>>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let (forces, _) = self.calculate_with_virial(system, potentials);
+        forces
+    }
+
+    fn name(&self) -> String {
+        "external_potential_forces".to_string()
+    }
+}
+
+/// Forces due to fields which act on each atom independently.
+#[derive(Clone, Copy, Debug)]
+pub struct FieldForces;
+
+impl Property for FieldForces {
+    type Res = Vec<Vector3<Float>>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        (0..system.size)
+            .map(|i| potentials.fields.iter().map(|field| field.force(system, i)).sum())
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "field_forces".to_string()
+    }
+}
+
 /// Force acting on each atom in the system.
 #[derive(Clone, Copy, Debug)]
 pub struct Forces;
@@ -125,14 +276,137 @@ impl Property for Forces {
     fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
         let coulomb_forces = CoulombicForces.calculate(system, potentials);
         let pair_forces = PairForces.calculate(system, potentials);
-        coulomb_forces
+        let mut forces: Vec<Vector3<Float>> = coulomb_forces
             .iter()
             .zip(pair_forces.iter())
             .map(|(coul, pair)| coul + pair)
-            .collect()
+            .collect();
+        if potentials.embedded_atom_meta.is_some() {
+            let embedded_atom_forces = EmbeddedAtomForces.calculate(system, potentials);
+            forces
+                .iter_mut()
+                .zip(embedded_atom_forces.iter())
+                .for_each(|(force, eam_force)| *force += eam_force);
+        }
+        if potentials.external_meta.is_some() {
+            let external_forces = ExternalPotentialForces.calculate(system, potentials);
+            forces
+                .iter_mut()
+                .zip(external_forces.iter())
+                .for_each(|(force, external_force)| *force += external_force);
+        }
+        if !potentials.biases.is_empty() {
+            let bias = potentials.bias_evaluation(system);
+            forces
+                .iter_mut()
+                .zip(bias.forces.iter())
+                .for_each(|(force, bias_force)| *force += bias_force);
+        }
+        if !potentials.fields.is_empty() {
+            let field_forces = FieldForces.calculate(system, potentials);
+            forces
+                .iter_mut()
+                .zip(field_forces.iter())
+                .for_each(|(force, field_force)| *force += field_force);
+        }
+        if let Some(cap) = potentials.force_cap() {
+            cap.apply(&mut forces);
+        }
+        forces
     }
 
     fn name(&self) -> String {
         "forces".to_string()
     }
 }
+
+impl PerAtomProperty for Forces {
+    fn components(&self) -> usize {
+        3
+    }
+
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate(system, potentials)
+            .iter()
+            .map(|force| force.as_slice().to_vec())
+            .collect()
+    }
+}
+
+/// Uncertainty of the force on each atom estimated by the external potential, such as the spread
+/// of the forces of an [`ExternalEnsemble`](crate::potentials::external::ExternalEnsemble).
+///
+/// Zero for every atom unless the external potential estimates its uncertainty. Each calculation
+/// evaluates the external potential again.
+#[derive(Clone, Copy, Debug)]
+pub struct ForceUncertainty;
+
+impl Property for ForceUncertainty {
+    type Res = Vec<Float>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        potentials
+            .external_meta
+            .as_ref()
+            .and_then(|meta| meta.evaluate(system).uncertainty)
+            .unwrap_or_else(|| vec![0.0; system.size])
+    }
+
+    fn name(&self) -> String {
+        "force_uncertainty".to_string()
+    }
+}
+
+impl PerAtomProperty for ForceUncertainty {
+    fn components(&self) -> usize {
+        1
+    }
+
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate(system, potentials)
+            .into_iter()
+            .map(|uncertainty| vec![uncertainty])
+            .collect()
+    }
+}
+
+/// Largest [`ForceUncertainty`] of any atom, the usual measure of whether a configuration lies
+/// outside the training data of a machine-learned potential.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxForceUncertainty;
+
+impl Property for MaxForceUncertainty {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        ForceUncertainty
+            .calculate(system, potentials)
+            .into_iter()
+            .fold(0.0, Float::max)
+    }
+
+    fn name(&self) -> String {
+        "max_force_uncertainty".to_string()
+    }
+}
+
+/// Indices of the atoms whose forces were capped during the most recent force evaluation.
+///
+/// Empty unless the potentials include a [`ForceCap`](crate::potentials::ForceCap).
+#[derive(Clone, Copy, Debug)]
+pub struct CappedAtoms;
+
+impl Property for CappedAtoms {
+    type Res = Vec<usize>;
+
+    fn calculate(&self, _: &System, potentials: &Potentials) -> Self::Res {
+        match potentials.force_cap() {
+            None => Vec::new(),
+            Some(cap) => cap.capped_atoms(),
+        }
+    }
+
+    fn name(&self) -> String {
+        "capped_atoms".to_string()
+    }
+}
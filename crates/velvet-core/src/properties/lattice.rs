@@ -0,0 +1,177 @@
+//! Lattice vectors and parameters of the simulation cell.
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+use crate::properties::IntrinsicProperty;
+use crate::system::System;
+
+/// Lattice vectors of a cell together with the parameters derived from them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatticeParameters {
+    /// The 'a', 'b', and 'c' lattice vectors in angstroms.
+    pub vectors: [Vector3<Float>; 3],
+    /// Lengths of the 'a', 'b', and 'c' vectors in angstroms.
+    pub lengths: Vector3<Float>,
+    /// Angles alpha, beta, and gamma between the vectors in degrees.
+    pub angles: Vector3<Float>,
+    /// Volume of the cell in cubic angstroms.
+    pub volume: Float,
+}
+
+/// Lattice vectors, lengths, angles, and volume of the simulation cell.
+///
+/// Recording the cell at a regular interval gives the time series needed to follow the box
+/// fluctuations of NPT runs and the changes in shape across phase transitions. The individual
+/// values can be tabulated with [`CellParameter`] columns, such as those added by
+/// [`ThermoOutput::cell`](crate::outputs::thermo::ThermoOutput::cell).
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(100)
+///     .output(CellParameters)
+///     .build();
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct CellParameters;
+
+impl IntrinsicProperty for CellParameters {
+    type Res = LatticeParameters;
+
+    fn calculate_intrinsic(&self, system: &System) -> <Self as IntrinsicProperty>::Res {
+        let cell = &system.cell;
+        LatticeParameters {
+            vectors: [cell.a_vector(), cell.b_vector(), cell.c_vector()],
+            lengths: Vector3::new(cell.a(), cell.b(), cell.c()),
+            angles: Vector3::new(cell.alpha(), cell.beta(), cell.gamma()),
+            volume: cell.volume(),
+        }
+    }
+
+    fn name(&self) -> String {
+        "cell_parameters".to_string()
+    }
+}
+
+/// Single scalar parameter of the simulation cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CellParameter {
+    /// Length of the 'a' vector.
+    A,
+    /// Length of the 'b' vector.
+    B,
+    /// Length of the 'c' vector.
+    C,
+    /// Angle between 'b' and 'c' in degrees.
+    Alpha,
+    /// Angle between 'a' and 'c' in degrees.
+    Beta,
+    /// Angle between 'a' and 'b' in degrees.
+    Gamma,
+    /// Volume of the cell.
+    Volume,
+    /// Cartesian component `axis` (0 to 2) of lattice vector `vector` (0 to 2 for 'a', 'b', and 'c').
+    Component(usize, usize),
+}
+
+impl CellParameter {
+    /// Returns the parameters of a full record of the cell: the nine components of the lattice
+    /// vectors followed by the lengths, the angles, and the volume.
+    pub fn all() -> Vec<CellParameter> {
+        let mut parameters: Vec<CellParameter> = (0..3)
+            .flat_map(|vector| (0..3).map(move |axis| CellParameter::Component(vector, axis)))
+            .collect();
+        parameters.extend_from_slice(&[
+            CellParameter::A,
+            CellParameter::B,
+            CellParameter::C,
+            CellParameter::Alpha,
+            CellParameter::Beta,
+            CellParameter::Gamma,
+            CellParameter::Volume,
+        ]);
+        parameters
+    }
+}
+
+impl IntrinsicProperty for CellParameter {
+    type Res = Float;
+
+    fn calculate_intrinsic(&self, system: &System) -> <Self as IntrinsicProperty>::Res {
+        let cell = &system.cell;
+        match *self {
+            CellParameter::A => cell.a(),
+            CellParameter::B => cell.b(),
+            CellParameter::C => cell.c(),
+            CellParameter::Alpha => cell.alpha(),
+            CellParameter::Beta => cell.beta(),
+            CellParameter::Gamma => cell.gamma(),
+            CellParameter::Volume => cell.volume(),
+            CellParameter::Component(vector, axis) => cell.matrix()[(axis, vector)],
+        }
+    }
+
+    fn name(&self) -> String {
+        match *self {
+            CellParameter::A => "a".to_string(),
+            CellParameter::B => "b".to_string(),
+            CellParameter::C => "c".to_string(),
+            CellParameter::Alpha => "alpha".to_string(),
+            CellParameter::Beta => "beta".to_string(),
+            CellParameter::Gamma => "gamma".to_string(),
+            CellParameter::Volume => "volume".to_string(),
+            CellParameter::Component(vector, axis) => format!("{}_{}", ["a", "b", "c"][vector], ["x", "y", "z"][axis]),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CellParameter, CellParameters};
+    use crate::properties::IntrinsicProperty;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn parameters() {
+        let system = System {
+            size: 1,
+            cell: Cell::triclinic(4.0, 5.0, 6.0, 90.0, 100.0, 120.0),
+            species: vec![Species::new(1.0, 0.0)],
+            positions: vec![Vector3::zeros()],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let parameters = CellParameters.calculate_intrinsic(&system);
+        assert_relative_eq!(parameters.lengths, Vector3::new(4.0, 5.0, 6.0), epsilon = 1e-4);
+        assert_relative_eq!(parameters.angles, Vector3::new(90.0, 100.0, 120.0), epsilon = 1e-3);
+        assert_relative_eq!(
+            parameters.vectors[0]
+                .cross(&parameters.vectors[1])
+                .dot(&parameters.vectors[2]),
+            parameters.volume,
+            epsilon = 1e-3
+        );
+
+        let columns = CellParameter::all();
+        assert_eq!(columns.len(), 16);
+        assert_eq!(columns[5].name(), "b_z");
+        assert_eq!(columns[15].name(), "volume");
+        assert_relative_eq!(CellParameter::Gamma.calculate_intrinsic(&system), 120.0, epsilon = 1e-3);
+        for (vector, expected) in parameters.vectors.iter().enumerate() {
+            for axis in 0..3 {
+                let component = CellParameter::Component(vector, axis).calculate_intrinsic(&system);
+                assert_relative_eq!(component, expected[axis]);
+            }
+        }
+    }
+}
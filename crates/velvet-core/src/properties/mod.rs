@@ -1,10 +1,22 @@
 //! Physical properties of the simulated system.
 
+pub mod electrostatics;
 pub mod energy;
 pub mod forces;
+pub mod lattice;
+pub mod msd;
+pub mod per_atom;
+pub mod pressure;
+pub mod rdf;
+pub mod rmsd;
+pub mod shape;
 pub mod temperature;
+pub mod vacf;
+pub mod velocities;
 
+use crate::internal::Float;
 use crate::potentials::Potentials;
+use crate::precision::Real;
 use crate::system::System;
 
 /// Calculates a system-wide property.
@@ -19,13 +31,33 @@ pub trait Property {
     fn name(&self) -> String;
 }
 
+/// Calculates a property with a fixed number of scalar components for each atom.
+///
+/// Per-atom properties can be written as extra columns of an extended
+/// [`XyzTrajectory`](crate::outputs::trajectory::XyzTrajectory).
+pub trait PerAtomProperty: Property {
+    /// Returns the number of scalar components of the property for each atom.
+    fn components(&self) -> usize;
+
+    /// Returns the scalar components of the property for each atom.
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>>;
+
+    /// Returns true if every component is a whole number such as a count or a structure label.
+    fn integral(&self) -> bool {
+        false
+    }
+}
+
 /// Calculates a system-wide property without using the applied potentials.
-pub trait IntrinsicProperty {
+///
+/// Properties of the velocities alone, such as the [`KineticEnergy`](energy::KineticEnergy), are
+/// implemented for systems of every [`Real`] precision.
+pub trait IntrinsicProperty<R: Real = Float> {
     /// The property's return type.
     type Res: std::fmt::Debug;
 
     /// Returns a physical property of the system without accessing the associated potentials.
-    fn calculate_intrinsic(&self, system: &System) -> Self::Res;
+    fn calculate_intrinsic(&self, system: &System<R>) -> Self::Res;
 
     /// Returns the name of the property used in output headers.
     fn name(&self) -> String;
@@ -35,7 +67,7 @@ impl<T: IntrinsicProperty> Property for T {
     type Res = T::Res;
 
     fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
-        <T as IntrinsicProperty>::calculate_intrinsic(&self, system)
+        <T as IntrinsicProperty>::calculate_intrinsic(self, system)
     }
 
     fn name(&self) -> String {
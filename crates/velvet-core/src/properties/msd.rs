@@ -0,0 +1,287 @@
+//! Mean squared displacement and self-diffusion coefficients accumulated over a simulation.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::Property;
+use crate::system::species::Species;
+use crate::system::System;
+
+// Trajectory information recorded since the first frame.
+#[derive(Debug, Default)]
+struct Tracker {
+    species: Vec<Species>,
+    reference: Vec<Vector3<Float>>,
+    previous: Vec<Vector3<Float>>,
+    images: Vec<Vector3<Float>>,
+    history: Vec<Vec<Float>>,
+}
+
+/// Mean squared displacement of each species from its positions in the first frame.
+///
+/// Positions are unwrapped across periodic boundaries by tracking the image of the cell each
+/// atom occupies, so wrapped and unwrapped trajectories give the same result as long as no atom
+/// moves more than half the width of the cell between two frames. Each calculation records a
+/// frame and returns the current mean squared displacement of each species in square angstroms,
+/// with species ordered by their first appearance in the system.
+///
+/// The self-diffusion coefficient of each species follows from the Einstein relation as one
+/// sixth of the slope of a least squares fit to the mean squared displacement over time.
+/// Clones share the same recorded frames, so a clone kept aside before the property is added to
+/// an output group can be analyzed once the simulation ends.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // frames are recorded every 100 steps of 1 fs
+/// let msd = Msd::new(100.0);
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(100)
+///     .output(msd.clone())
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Msd {
+    interval: Float,
+    skip: Float,
+    tracker: Arc<Mutex<Tracker>>,
+}
+
+impl Msd {
+    /// Returns a new [`Msd`].
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - Time between consecutive frames in femtoseconds.
+    pub fn new(interval: Float) -> Msd {
+        Msd {
+            interval,
+            skip: 0.1,
+            tracker: Arc::new(Mutex::new(Tracker::default())),
+        }
+    }
+
+    /// Sets the fraction of the earliest frames excluded from the diffusion fit.
+    ///
+    /// Excluding the ballistic regime at short times improves the estimate. Defaults to 0.1.
+    pub fn skip(mut self, fraction: Float) -> Msd {
+        self.skip = fraction;
+        self
+    }
+
+    /// Returns each species in the order of the reported values.
+    pub fn species(&self) -> Vec<Species> {
+        self.tracker.lock().unwrap().species.clone()
+    }
+
+    /// Returns the number of frames recorded so far.
+    pub fn frames(&self) -> usize {
+        self.tracker.lock().unwrap().history.len()
+    }
+
+    /// Returns the time of each recorded frame in femtoseconds.
+    pub fn times(&self) -> Vec<Float> {
+        (0..self.frames()).map(|n| n as Float * self.interval).collect()
+    }
+
+    /// Returns the mean squared displacement of each species in each recorded frame.
+    pub fn history(&self) -> Vec<Vec<Float>> {
+        self.tracker.lock().unwrap().history.clone()
+    }
+
+    /// Returns the self-diffusion coefficient of each species in square angstroms per femtosecond.
+    ///
+    /// Multiply by 0.1 to convert to square centimeters per second. Returns zero for every species
+    /// until at least two frames remain after skipping the earliest frames.
+    pub fn diffusion(&self) -> Vec<Float> {
+        let tracker = self.tracker.lock().unwrap();
+        let first = (self.skip * tracker.history.len() as Float).floor() as usize;
+        let frames = &tracker.history[first.min(tracker.history.len())..];
+        (0..tracker.species.len())
+            .map(|s| {
+                if frames.len() < 2 {
+                    return 0.0;
+                }
+                // least squares slope of the displacement over time
+                let n = frames.len() as Float;
+                let times: Vec<Float> = (first..first + frames.len()).map(|i| i as Float * self.interval).collect();
+                let mean_t = times.iter().sum::<Float>() / n;
+                let mean_msd = frames.iter().map(|frame| frame[s]).sum::<Float>() / n;
+                let (cov, var) = times
+                    .iter()
+                    .zip(frames.iter())
+                    .fold((0.0, 0.0), |(cov, var), (t, frame)| {
+                        (cov + (t - mean_t) * (frame[s] - mean_msd), var + (t - mean_t).powi(2))
+                    });
+                cov / var / 6.0
+            })
+            .collect()
+    }
+
+    /// Discards every recorded frame so the next frame becomes the new reference.
+    pub fn reset(&self) {
+        *self.tracker.lock().unwrap() = Tracker::default();
+    }
+
+    /// Writes the mean squared displacement of each species as columns after the time.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let species = self.species();
+        let labels: Vec<String> = (1..=species.len()).map(|s| format!("msd_{}", s)).collect();
+        writeln!(writer, "# time {}", labels.join(" "))?;
+        for (time, frame) in self.times().iter().zip(self.history().iter()) {
+            let values: Vec<String> = frame.iter().map(|msd| msd.to_string()).collect();
+            writeln!(writer, "{} {}", time, values.join(" "))?;
+        }
+        writer.flush()
+    }
+}
+
+impl Property for Msd {
+    type Res = Vec<Float>;
+
+    fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
+        let mut tracker = self.tracker.lock().unwrap();
+        if tracker.history.is_empty() {
+            tracker.species.clear();
+            for species in &system.species {
+                if !tracker.species.contains(species) {
+                    tracker.species.push(*species);
+                }
+            }
+            tracker.reference = system.positions.clone();
+            tracker.previous = system.positions.clone();
+            tracker.images = vec![Vector3::zeros(); system.size];
+        }
+
+        // count the boundaries crossed since the previous frame
+        let inverse = system.cell.inverse_matrix();
+        let matrix = system.cell.matrix();
+        let mut sums = vec![0.0; tracker.species.len()];
+        let mut counts = vec![0usize; tracker.species.len()];
+        for i in 0..system.size {
+            let jump = inverse * (system.positions[i] - tracker.previous[i]);
+            tracker.images[i] -= jump.map(Float::round);
+            tracker.previous[i] = system.positions[i];
+            let unwrapped = system.positions[i] + matrix * tracker.images[i];
+            let s = tracker.species.iter().position(|s| *s == system.species[i]).unwrap();
+            sums[s] += (unwrapped - tracker.reference[i]).norm_squared();
+            counts[s] += 1;
+        }
+        let msd: Vec<Float> = sums
+            .iter()
+            .zip(counts.iter())
+            .map(|(sum, count)| sum / *count as Float)
+            .collect();
+        tracker.history.push(msd.clone());
+        msd
+    }
+
+    fn name(&self) -> String {
+        "msd".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Msd;
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn unwraps_boundaries() {
+        let atom = Species::new(1.0, 0.0);
+        let cell = Cell::triclinic(4.0, 5.0, 6.0, 80.0, 100.0, 110.0);
+        let mut system = System {
+            size: 1,
+            cell,
+            species: vec![atom],
+            positions: vec![Vector3::new(1.0, 1.0, 1.0)],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        let msd = Msd::new(2.0);
+        let step = Vector3::new(0.7, -0.4, 0.9);
+        let mut unwrapped = system.positions[0];
+        for n in 0..50 {
+            if n > 0 {
+                unwrapped += step;
+                // store the wrapped position as a simulation with wrapped coordinates would
+                let mut wrapped = unwrapped;
+                system.cell.wrap_vector(&mut wrapped);
+                system.positions[0] = wrapped;
+            }
+            let value = msd.calculate(&system, &potentials);
+            let expected = (n as Float * step).norm_squared();
+            assert!((value[0] - expected).abs() < 1e-3 * expected.max(1.0));
+        }
+        assert_eq!(msd.frames(), 50);
+        assert_eq!(msd.times()[49], 98.0);
+    }
+
+    #[test]
+    fn random_walk_diffusion() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let slow = Species::new(1.0, 0.0);
+        let fast = Species::new(2.0, 0.0);
+        let size = 200;
+        let species: Vec<Species> = (0..size).map(|i| if i % 2 == 0 { slow } else { fast }).collect();
+        let mut system = System {
+            size,
+            cell: Cell::cubic(10.0),
+            species,
+            positions: (0..size)
+                .map(|_| Vector3::new(rng.gen_range(0.0, 10.0), rng.gen_range(0.0, 10.0), rng.gen_range(0.0, 10.0)))
+                .collect(),
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        // frames every 10 fs with uniform steps of variance width^2 / 3 along each axis
+        let msd = Msd::new(10.0).skip(0.0);
+        let shared = msd.clone();
+        for _ in 0..400 {
+            msd.calculate(&system, &potentials);
+            for i in 0..size {
+                let width: Float = if i % 2 == 0 { 0.3 } else { 0.6 };
+                let step = Vector3::new(
+                    rng.gen_range(-width, width),
+                    rng.gen_range(-width, width),
+                    rng.gen_range(-width, width),
+                );
+                system.positions[i] += step;
+                system.cell.wrap_vector(&mut system.positions[i]);
+            }
+        }
+
+        // D = 3 var / (6 interval) for a random walk
+        let diffusion = shared.diffusion();
+        assert_eq!(shared.species(), vec![slow, fast]);
+        let expected = |width: Float| width * width / 3.0 / 2.0 / 10.0;
+        assert!((diffusion[0] - expected(0.3)).abs() < 0.15 * expected(0.3), "{:?}", diffusion);
+        assert!((diffusion[1] - expected(0.6)).abs() < 0.15 * expected(0.6), "{:?}", diffusion);
+
+        let mut buffer = Vec::new();
+        shared.write(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().count(), 401);
+        assert_eq!(text.lines().nth(1).unwrap(), "0 0 0");
+        shared.reset();
+        assert_eq!(msd.frames(), 0);
+    }
+}
@@ -0,0 +1,570 @@
+//! Properties resolved for each individual atom.
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::internal::consts::PRESSURE;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::energy::BiasEnergy;
+use crate::properties::{PerAtomProperty, Property};
+use crate::system::System;
+
+/// Potential energy of each atom in the system.
+///
+/// The energy of every interacting pair is split evenly between its two atoms, and each atom
+/// also takes its own embedding energy under an embedded atom potential and its energy in any
+/// field. Long range
+/// Coulombic energy, pair tail corrections, the energy of an external potential, and the energy
+/// of biases on collective variables have no unique per-atom decomposition and are divided evenly among all atoms, so the per-atom
+/// energies always sum to the [`PotentialEnergy`](crate::properties::energy::PotentialEnergy)
+/// of the system.
+#[derive(Clone, Copy, Debug)]
+pub struct PerAtomPotentialEnergy;
+
+impl Property for PerAtomPotentialEnergy {
+    type Res = Vec<Float>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let mut energies = vec![0.0; system.size];
+        let mut shared = BiasEnergy.calculate(system, potentials);
+        let weights = potentials.resolution_weights(system);
+        let weights = weights.as_deref();
+        for meta in &potentials.pair_metas {
+            for (i, j, separation) in meta.pairs(system) {
+                let r = separation.norm();
+                if r < meta.cutoff {
+                    let energy = meta.weight(weights, i, j) * meta.energy(r) / 2.0;
+                    energies[i] += energy;
+                    energies[j] += energy;
+                }
+            }
+            shared += meta.tail_energy(system, weights);
+        }
+        if let Some(meta) = &potentials.coulomb_meta {
+            for (i, j, separation) in meta.pairs(system) {
+                let r = separation.norm();
+                if r < meta.cutoff {
+                    let (qi, qj) = (system.charge(i), system.charge(j));
+                    let energy = meta.potential.energy(qi, qj, r) / 2.0;
+                    energies[i] += energy;
+                    energies[j] += energy;
+                }
+            }
+            shared += meta.long_range_energy(system);
+        }
+        if let Some(meta) = &potentials.external_meta {
+            shared += meta.evaluate(system).energy;
+        }
+        if let Some(meta) = &potentials.embedded_atom_meta {
+            energies
+                .iter_mut()
+                .zip(meta.energies(system))
+                .for_each(|(energy, embedded)| *energy += embedded);
+        }
+        for field in &potentials.fields {
+            energies
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, energy)| *energy += field.energy(system, i));
+        }
+        let shared = shared / system.size as Float;
+        energies.iter_mut().for_each(|energy| *energy += shared);
+        energies
+    }
+
+    fn name(&self) -> String {
+        "per_atom_potential_energy".to_string()
+    }
+}
+
+impl PerAtomProperty for PerAtomPotentialEnergy {
+    fn components(&self) -> usize {
+        1
+    }
+
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate(system, potentials)
+            .into_iter()
+            .map(|energy| vec![energy])
+            .collect()
+    }
+}
+
+/// Virial stress tensor of each atom in atmosphere-cubic angstroms.
+///
+/// Each atom contributes its kinetic energy tensor and half of the virial of every pair it
+/// belongs to, with the same sign convention as the
+/// [`StressTensor`](crate::properties::pressure::StressTensor). Any long range Coulombic virial
+/// and pair tail correction, along with any virial of an external potential or of the biases on
+/// collective variables, is divided evenly among all atoms. The per-atom stresses are not divided by a volume, since an
+/// atomic volume is not well defined, but their sum divided by the volume of the cell is the
+/// stress tensor of the system.
+#[derive(Clone, Copy, Debug)]
+pub struct PerAtomStress;
+
+impl Property for PerAtomStress {
+    type Res = Vec<Matrix3<Float>>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let mut stresses: Vec<Matrix3<Float>> = system
+            .species
+            .iter()
+            .zip(system.velocities.iter())
+            .map(|(species, vel)| species.mass() * vel * vel.transpose())
+            .collect();
+        // half of the virial of a pair with a central force of magnitude `force`
+        let mut accumulate = |i: usize, j: usize, force: Float, separation: Vector3<Float>| {
+            let virial = -0.5 * force / separation.norm() * separation * separation.transpose();
+            stresses[i] += virial;
+            stresses[j] += virial;
+        };
+        let mut shared = Matrix3::zeros();
+        let weights = potentials.resolution_weights(system);
+        let weights = weights.as_deref();
+        for meta in &potentials.pair_metas {
+            for (i, j, separation) in meta.pairs(system) {
+                let r = separation.norm();
+                if r < meta.cutoff {
+                    accumulate(i, j, meta.weight(weights, i, j) * meta.force(r), separation);
+                }
+            }
+            shared += meta.tail_virial(system, weights);
+        }
+        if let Some(meta) = &potentials.coulomb_meta {
+            for (i, j, separation) in meta.pairs(system) {
+                let r = separation.norm();
+                if r < meta.cutoff {
+                    let (qi, qj) = (system.charge(i), system.charge(j));
+                    accumulate(i, j, meta.potential.force(qi, qj, r), separation);
+                }
+            }
+            if let Some(virial) = meta.long_range_virial(system) {
+                shared += virial;
+            }
+        }
+        if let Some(virial) = potentials.external_meta.as_ref().and_then(|meta| meta.evaluate(system).virial) {
+            shared += virial;
+        }
+        if !potentials.biases.is_empty() {
+            shared += potentials.bias_evaluation(system).virial;
+        }
+        if let Some(meta) = &potentials.embedded_atom_meta {
+            for (i, j, separation, force) in meta.pair_forces(system) {
+                accumulate(i, j, force, separation);
+            }
+        }
+        let shared = shared / system.size as Float;
+        stresses
+            .into_iter()
+            .map(|stress| (stress + shared) * PRESSURE)
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "per_atom_stress".to_string()
+    }
+}
+
+impl PerAtomProperty for PerAtomStress {
+    fn components(&self) -> usize {
+        9
+    }
+
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate(system, potentials)
+            .iter()
+            .map(|stress| stress.transpose().as_slice().to_vec())
+            .collect()
+    }
+}
+
+/// Von Mises equivalent of the [`PerAtomStress`] of each atom in atmosphere-cubic angstroms.
+///
+/// The von Mises stress is invariant to the orientation of the stress tensor and vanishes under
+/// hydrostatic pressure, which makes it a common scalar for coloring shear localization,
+/// dislocation cores, and grain boundaries in visualization software.
+#[derive(Clone, Copy, Debug)]
+pub struct VonMisesStress;
+
+impl Property for VonMisesStress {
+    type Res = Vec<Float>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        PerAtomStress
+            .calculate(system, potentials)
+            .iter()
+            .map(von_mises)
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "von_mises_stress".to_string()
+    }
+}
+
+impl PerAtomProperty for VonMisesStress {
+    fn components(&self) -> usize {
+        1
+    }
+
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate(system, potentials)
+            .into_iter()
+            .map(|stress| vec![stress])
+            .collect()
+    }
+}
+
+fn von_mises(s: &Matrix3<Float>) -> Float {
+    let normal = (s[(0, 0)] - s[(1, 1)]).powi(2) + (s[(1, 1)] - s[(2, 2)]).powi(2) + (s[(2, 2)] - s[(0, 0)]).powi(2);
+    let shear = s[(0, 1)].powi(2) + s[(1, 2)].powi(2) + s[(2, 0)].powi(2);
+    Float::sqrt(0.5 * normal + 3.0 * shear)
+}
+
+// Returns the indices of the atoms within `cutoff` of each atom under the minimum image convention.
+fn neighbor_lists(system: &System, cutoff: Float) -> Vec<Vec<usize>> {
+    let mut neighbors = vec![Vec::new(); system.size];
+    for i in 0..system.size {
+        for j in (i + 1)..system.size {
+            if system.cell.distance(&system.positions[i], &system.positions[j]) < cutoff {
+                neighbors[i].push(j);
+                neighbors[j].push(i);
+            }
+        }
+    }
+    neighbors
+}
+
+/// Number of atoms within a cutoff radius of each atom.
+///
+/// Atoms are counted under the minimum image convention regardless of the applied potentials.
+#[derive(Clone, Copy, Debug)]
+pub struct Coordination {
+    cutoff: Float,
+}
+
+impl Coordination {
+    /// Returns a new [`Coordination`] property which counts neighbors closer than `cutoff`.
+    pub fn new(cutoff: Float) -> Coordination {
+        Coordination { cutoff }
+    }
+}
+
+impl Property for Coordination {
+    type Res = Vec<usize>;
+
+    fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
+        neighbor_lists(system, self.cutoff).iter().map(|n| n.len()).collect()
+    }
+
+    fn name(&self) -> String {
+        "coordination".to_string()
+    }
+}
+
+impl PerAtomProperty for Coordination {
+    fn components(&self) -> usize {
+        1
+    }
+
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate(system, potentials)
+            .into_iter()
+            .map(|count| vec![count as Float])
+            .collect()
+    }
+
+    fn integral(&self) -> bool {
+        true
+    }
+}
+
+/// Local crystal structure identified by [`CommonNeighborAnalysis`].
+///
+/// The discriminants match the structure type labels of OVITO.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Structure {
+    /// Any other or disordered environment.
+    Other = 0,
+    /// Face centered cubic.
+    Fcc = 1,
+    /// Hexagonal close packed.
+    Hcp = 2,
+    /// Body centered cubic.
+    Bcc = 3,
+    /// Icosahedral.
+    Icosahedral = 4,
+}
+
+/// Local crystal structure of each atom from conventional common neighbor analysis.
+///
+/// Each bond between an atom and a neighbor within the cutoff is classified by the number of
+/// neighbors the pair shares, the number of bonds among those common neighbors, and the length
+/// of the longest chain those bonds form. The signatures of every bond of an atom identify
+/// its [`Structure`]. The cutoff should lie between the first and second neighbor shells of close
+/// packed crystals, about `0.854 a` for a lattice constant `a`, and between the second and third
+/// shells of body centered cubic crystals, about `1.207 a`.
+///
+/// # References
+///
+/// [1] Honeycutt, J. Dana, and Hans C. Andersen. "Molecular dynamics study of melting and freezing of small Lennard-Jones clusters." Journal of Physical Chemistry 91.19 (1987): 4950-4963.
+///
+/// [2] Stukowski, Alexander. "Structure identification methods for atomistic simulations of crystalline materials." Modelling and Simulation in Materials Science and Engineering 20.4 (2012): 045021.
+#[derive(Clone, Copy, Debug)]
+pub struct CommonNeighborAnalysis {
+    cutoff: Float,
+}
+
+impl CommonNeighborAnalysis {
+    /// Returns a new [`CommonNeighborAnalysis`] with bonds between atoms closer than `cutoff`.
+    pub fn new(cutoff: Float) -> CommonNeighborAnalysis {
+        CommonNeighborAnalysis { cutoff }
+    }
+}
+
+impl Property for CommonNeighborAnalysis {
+    type Res = Vec<Structure>;
+
+    fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
+        let neighbors = neighbor_lists(system, self.cutoff);
+        neighbors
+            .iter()
+            .enumerate()
+            .map(|(i, bonded)| {
+                let signatures: Vec<(usize, usize, usize)> =
+                    bonded.iter().map(|&j| signature(&neighbors, i, j)).collect();
+                let count = |target: (usize, usize, usize)| signatures.iter().filter(|&&s| s == target).count();
+                match bonded.len() {
+                    12 if count((4, 2, 1)) == 12 => Structure::Fcc,
+                    12 if count((4, 2, 1)) == 6 && count((4, 2, 2)) == 6 => Structure::Hcp,
+                    12 if count((5, 5, 5)) == 12 => Structure::Icosahedral,
+                    14 if count((6, 6, 6)) == 8 && count((4, 4, 4)) == 6 => Structure::Bcc,
+                    _ => Structure::Other,
+                }
+            })
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "structure_type".to_string()
+    }
+}
+
+impl PerAtomProperty for CommonNeighborAnalysis {
+    fn components(&self) -> usize {
+        1
+    }
+
+    fn calculate_per_atom(&self, system: &System, potentials: &Potentials) -> Vec<Vec<Float>> {
+        self.calculate(system, potentials)
+            .into_iter()
+            .map(|structure| vec![structure as usize as Float])
+            .collect()
+    }
+
+    fn integral(&self) -> bool {
+        true
+    }
+}
+
+// Returns the common neighbor, bond, and longest chain counts of the bond between `i` and `j`.
+fn signature(neighbors: &[Vec<usize>], i: usize, j: usize) -> (usize, usize, usize) {
+    let common: Vec<usize> = neighbors[i].iter().copied().filter(|k| neighbors[j].contains(k)).collect();
+    let mut bonds: Vec<[usize; 2]> = Vec::new();
+    for (a, &k) in common.iter().enumerate() {
+        for &l in &common[a + 1..] {
+            if neighbors[k].contains(&l) {
+                bonds.push([k, l]);
+            }
+        }
+    }
+    // the longest chain is the largest number of bonds in one connected cluster
+    let mut longest = 0;
+    let mut remaining = bonds.clone();
+    while let Some(seed) = remaining.pop() {
+        let mut cluster = vec![seed[0], seed[1]];
+        let mut size = 1;
+        let mut grown = true;
+        while grown {
+            grown = false;
+            let mut k = 0;
+            while k < remaining.len() {
+                let [a, b] = remaining[k];
+                if cluster.contains(&a) || cluster.contains(&b) {
+                    cluster.push(a);
+                    cluster.push(b);
+                    remaining.swap_remove(k);
+                    size += 1;
+                    grown = true;
+                } else {
+                    k += 1;
+                }
+            }
+        }
+        longest = longest.max(size);
+    }
+    (common.len(), bonds.len(), longest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        von_mises, CommonNeighborAnalysis, Coordination, PerAtomPotentialEnergy, PerAtomStress, Structure,
+    };
+    use crate::internal::Float;
+    use crate::potentials::types::{Ewald, LennardJones};
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::pressure::StressTensor;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::{Matrix3, Vector3};
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    // disordered ionic fluid on a perturbed simple cubic grid
+    fn ionic_fluid() -> System {
+        let mut rng = StdRng::seed_from_u64(7);
+        let cation = Species::new(22.99, 0.5);
+        let anion = Species::new(35.45, -0.5);
+        let mut species = Vec::new();
+        let mut positions = Vec::new();
+        let mut velocities = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..2 {
+                    let site = Vector3::new(i as Float, j as Float, k as Float) * 3.5;
+                    let shift = Vector3::new(rng.gen_range(-0.3, 0.3), rng.gen_range(-0.3, 0.3), rng.gen_range(-0.3, 0.3));
+                    positions.push(site + shift);
+                    species.push(if (i + j + k) % 2 == 0 { cation } else { anion });
+                    velocities.push(Vector3::new(rng.gen_range(-0.01, 0.01), rng.gen_range(-0.01, 0.01), 0.0));
+                }
+            }
+        }
+        System {
+            size: positions.len(),
+            cell: Cell::triclinic(10.5, 10.5, 7.0, 90.0, 90.0, 90.0),
+            species,
+            positions,
+            velocities,
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn sums_to_system_properties() {
+        let system = ionic_fluid();
+        let (cation, anion) = (system.species[0], system.species[1]);
+        let lj = LennardJones::new(0.1, 3.0);
+        let mut potentials = PotentialsBuilder::new()
+            .pair(lj, (cation, cation), 3.4, 0.0)
+            .pair(lj, (cation, anion), 3.4, 0.0)
+            .pair(lj, (anion, anion), 3.4, 0.0)
+            .coulomb(Ewald::new(0.8, 6), 3.4, 0.0)
+            .build();
+        potentials.setup(&system);
+
+        let energies = PerAtomPotentialEnergy.calculate(&system, &potentials);
+        let total: Float = energies.iter().sum();
+        let expected = PotentialEnergy.calculate(&system, &potentials);
+        assert_relative_eq!(total, expected, epsilon = 1e-3, max_relative = 1e-4);
+
+        let stresses = PerAtomStress.calculate(&system, &potentials);
+        let total = stresses.iter().fold(Matrix3::zeros(), |a, b| a + b) / system.cell.volume();
+        let expected = StressTensor.calculate(&system, &potentials);
+        assert_relative_eq!(total, expected, epsilon = 1e-1, max_relative = 1e-3);
+    }
+
+    #[test]
+    fn symmetric_dimer() {
+        let argon = Species::new(39.948, 0.0);
+        let system = System {
+            size: 3,
+            cell: Cell::cubic(30.0),
+            species: vec![argon; 3],
+            positions: vec![
+                Vector3::new(5.0, 5.0, 5.0),
+                Vector3::new(8.5, 5.0, 5.0),
+                Vector3::new(20.0, 20.0, 20.0),
+            ],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology::default(),
+        };
+        let lj = LennardJones::new(0.238, 3.4);
+        let mut potentials = PotentialsBuilder::new()
+            .pair(lj, (argon, argon), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+
+        // the isolated atom has no energy or stress while the dimer shares its pair equally
+        let energies = PerAtomPotentialEnergy.calculate(&system, &potentials);
+        assert_relative_eq!(energies[0], energies[1]);
+        assert_relative_eq!(energies[0] + energies[1], PotentialEnergy.calculate(&system, &potentials));
+        assert_eq!(energies[2], 0.0);
+        let stresses = PerAtomStress.calculate(&system, &potentials);
+        assert_relative_eq!(stresses[0], stresses[1]);
+        assert!(stresses[0][(0, 0)] != 0.0);
+        assert_relative_eq!(stresses[0][(1, 1)], 0.0);
+        assert_eq!(stresses[2], Matrix3::zeros());
+    }
+
+    // conventional cubic cell with the given fractional basis repeated n times along each axis
+    fn crystal(basis: &[[Float; 3]], a: Float, n: usize) -> System {
+        let argon = Species::new(39.948, 0.0);
+        let mut positions = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    for b in basis.iter() {
+                        positions.push(Vector3::new(b[0] + i as Float, b[1] + j as Float, b[2] + k as Float) * a);
+                    }
+                }
+            }
+        }
+        System {
+            size: positions.len(),
+            cell: Cell::cubic(n as Float * a),
+            species: vec![argon; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn structure_identification() {
+        let potentials = PotentialsBuilder::new().build();
+        let fcc = crystal(&[[0.0, 0.0, 0.0], [0.5, 0.5, 0.0], [0.5, 0.0, 0.5], [0.0, 0.5, 0.5]], 5.26, 3);
+        let coordination = Coordination::new(0.854 * 5.26).calculate(&fcc, &potentials);
+        assert!(coordination.iter().all(|&n| n == 12));
+        let structures = CommonNeighborAnalysis::new(0.854 * 5.26).calculate(&fcc, &potentials);
+        assert!(structures.iter().all(|&s| s == Structure::Fcc));
+
+        let bcc = crystal(&[[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]], 3.3, 4);
+        let structures = CommonNeighborAnalysis::new(1.207 * 3.3).calculate(&bcc, &potentials);
+        assert!(structures.iter().all(|&s| s == Structure::Bcc));
+
+        // a simple cubic crystal is none of the recognized structures
+        let sc = crystal(&[[0.0, 0.0, 0.0]], 3.0, 4);
+        let structures = CommonNeighborAnalysis::new(3.5).calculate(&sc, &potentials);
+        assert!(structures.iter().all(|&s| s == Structure::Other));
+    }
+
+    #[test]
+    fn von_mises_invariants() {
+        // uniaxial stress
+        let uniaxial = Matrix3::from_diagonal(&Vector3::new(5.0, 0.0, 0.0));
+        assert_relative_eq!(von_mises(&uniaxial), 5.0, epsilon = 1e-5);
+        // pure shear
+        let mut shear = Matrix3::zeros();
+        shear[(0, 1)] = 2.0;
+        shear[(1, 0)] = 2.0;
+        assert_relative_eq!(von_mises(&shear), 2.0 * Float::sqrt(3.0), epsilon = 1e-5);
+        // hydrostatic pressure
+        assert_relative_eq!(von_mises(&(Matrix3::identity() * 7.0)), 0.0, epsilon = 1e-5);
+    }
+}
@@ -0,0 +1,167 @@
+//! Virial based pressure of the system.
+
+use nalgebra::Matrix3;
+
+use crate::internal::consts::PRESSURE;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::precision::Real;
+use crate::properties::forces::{CoulombicForces, EmbeddedAtomForces, ExternalPotentialForces, PairForces};
+use crate::properties::{IntrinsicProperty, Property};
+use crate::system::System;
+
+/// Virial tensor of the interatomic forces.
+///
+/// Accumulated as the sum of the outer product of each pair separation and the force between
+/// them over every potential, plus any long range contribution of the Coulombic potential, the
+/// virial reported by an external potential, the virial of the biases on collective variables,
+/// and the virial of the constraint forces of the last integration step.
+#[derive(Clone, Copy, Debug)]
+pub struct Virial;
+
+impl Property for Virial {
+    type Res = Matrix3<Float>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let (_, coulomb_virial) = CoulombicForces.calculate_with_virial(system, potentials);
+        let (_, pair_virial) = PairForces.calculate_with_virial(system, potentials);
+        let (_, embedded_atom_virial) = EmbeddedAtomForces.calculate_with_virial(system, potentials);
+        let (_, external_virial) = ExternalPotentialForces.calculate_with_virial(system, potentials);
+        let bias_virial = if potentials.biases.is_empty() {
+            Matrix3::zeros()
+        } else {
+            potentials.bias_evaluation(system).virial
+        };
+        let constraint_virial = potentials.constraint_virial();
+        coulomb_virial + pair_virial + embedded_atom_virial + external_virial + bias_virial + constraint_virial
+    }
+
+    fn name(&self) -> String {
+        "virial".to_string()
+    }
+}
+
+/// Kinetic energy tensor of the whole system.
+#[derive(Clone, Copy, Debug)]
+pub struct KineticTensor;
+
+impl<R: Real> IntrinsicProperty<R> for KineticTensor {
+    type Res = Matrix3<R>;
+
+    fn calculate_intrinsic(&self, system: &System<R>) -> Matrix3<R> {
+        system
+            .species
+            .iter()
+            .zip(system.velocities.iter())
+            .fold(Matrix3::zeros(), |accumulator, (species, vel)| {
+                accumulator + vel * R::from_float(species.mass()) * vel.transpose()
+            })
+    }
+
+    fn name(&self) -> String {
+        "kinetic_tensor".to_string()
+    }
+}
+
+/// Pressure tensor of the system in atmospheres.
+///
+/// Follows the LAMMPS sign convention in which positive diagonal components indicate
+/// compression, such that the [`Pressure`] is one third of the trace.
+#[derive(Clone, Copy, Debug)]
+pub struct StressTensor;
+
+impl Property for StressTensor {
+    type Res = Matrix3<Float>;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        let kinetic = KineticTensor.calculate_intrinsic(system);
+        let virial = Virial.calculate(system, potentials);
+        (kinetic + virial) * (PRESSURE / system.cell.volume())
+    }
+
+    fn name(&self) -> String {
+        "stress_tensor".to_string()
+    }
+}
+
+/// Instantaneous pressure of the system in atmospheres.
+#[derive(Clone, Copy, Debug)]
+pub struct Pressure;
+
+impl Property for Pressure {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, potentials: &Potentials) -> Self::Res {
+        StressTensor.calculate(system, potentials).trace() / 3.0
+    }
+
+    fn name(&self) -> String {
+        "pressure".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pressure, StressTensor};
+    use crate::internal::consts::{BOLTZMANN, PRESSURE};
+    use crate::internal::Float;
+    use crate::potentials::pair::PairPotential;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::temperature::Temperature;
+    use crate::properties::{IntrinsicProperty, Property};
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    fn argon_dimer(r: Float) -> System {
+        let argon = Species::from_element(Element::Ar);
+        System {
+            size: 2,
+            cell: Cell::cubic(20.0),
+            species: vec![argon, argon],
+            positions: vec![
+                Vector3::new(5.0, 5.0, 5.0),
+                Vector3::new(5.0 + r, 5.0, 5.0),
+            ],
+            velocities: vec![Vector3::new(1e-3, -2e-3, 0.0), Vector3::new(-1e-3, 2e-3, 0.0)],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn ideal_gas() {
+        let system = argon_dimer(4.0);
+        let potentials = PotentialsBuilder::new().build();
+        let pressure = Pressure.calculate(&system, &potentials);
+        let temperature = Temperature.calculate_intrinsic(&system);
+        let volume = system.cell.volume();
+        let expected = 2.0 * BOLTZMANN * temperature / volume * PRESSURE;
+        assert_relative_eq!(pressure, expected, max_relative = 1e-4);
+    }
+
+    #[test]
+    fn pair_virial() {
+        let mut system = argon_dimer(3.5);
+        system.velocities = vec![Vector3::zeros(); 2];
+        let argon = system.species[0];
+        let lj = LennardJones::new(4.184, 3.4);
+        let mut potentials = PotentialsBuilder::new()
+            .pair(lj, (argon, argon), 8.5, 1.0)
+            .build();
+        potentials.setup(&system);
+        let stress = StressTensor.calculate(&system, &potentials);
+        let expected = -3.5 * lj.force(3.5) / system.cell.volume() * PRESSURE;
+        assert_relative_eq!(stress[(0, 0)], expected, max_relative = 1e-4);
+        assert_relative_eq!(stress[(1, 1)], 0.0);
+        assert_relative_eq!(
+            Pressure.calculate(&system, &potentials),
+            expected / 3.0,
+            max_relative = 1e-4
+        );
+    }
+}
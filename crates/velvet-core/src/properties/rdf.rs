@@ -0,0 +1,304 @@
+//! Radial distribution function accumulated over a simulation.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::internal::consts::PI;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::Property;
+use crate::system::species::Species;
+use crate::system::System;
+
+// Normalized histograms summed over every accumulated frame.
+#[derive(Debug, Default)]
+struct Accumulator {
+    sum: Vec<Float>,
+    frames: usize,
+}
+
+/// Radial distribution function averaged over every frame in which it is calculated.
+///
+/// Each calculation bins the minimum image distance of every pair of atoms, or of every pair
+/// between two selected species, and normalizes the histogram by the number of pairs an ideal
+/// gas at the density of the current cell would place in each spherical shell, so frames from
+/// a fluctuating cell are averaged correctly. The returned value is the average over every frame
+/// accumulated so far, including the current one.
+///
+/// Clones share the same accumulated frames, so a clone kept aside before the property is added
+/// to an output group can be written out with [`write`](Rdf::write) once the simulation ends.
+/// The cutoff must not exceed half the smallest width of the cell, beyond which distances under
+/// the minimum image convention no longer account for every periodic image.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let rdf = Rdf::new(8.0, 0.05).species(argon, argon);
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(100)
+///     .output(rdf.clone())
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Rdf {
+    cutoff: Float,
+    bin_width: Float,
+    species: Option<(Species, Species)>,
+    accumulator: Arc<Mutex<Accumulator>>,
+}
+
+impl Rdf {
+    /// Returns a new [`Rdf`] between every pair of atoms.
+    ///
+    /// # Arguments
+    ///
+    /// * `cutoff` - Largest pair distance in angstroms.
+    /// * `bin_width` - Width of each histogram bin in angstroms.
+    pub fn new(cutoff: Float, bin_width: Float) -> Rdf {
+        Rdf {
+            cutoff,
+            bin_width,
+            species: None,
+            accumulator: Arc::new(Mutex::new(Accumulator::default())),
+        }
+    }
+
+    /// Restricts the radial distribution function to pairs between two species.
+    pub fn species(mut self, a: Species, b: Species) -> Rdf {
+        self.species = Some((a, b));
+        self
+    }
+
+    /// Returns the number of bins in the histogram.
+    pub fn bins(&self) -> usize {
+        (self.cutoff / self.bin_width).ceil() as usize
+    }
+
+    /// Returns the distance at the center of each bin in angstroms.
+    pub fn distances(&self) -> Vec<Float> {
+        (0..self.bins())
+            .map(|bin| (bin as Float + 0.5) * self.bin_width)
+            .collect()
+    }
+
+    /// Returns the number of frames accumulated so far.
+    pub fn frames(&self) -> usize {
+        self.accumulator.lock().unwrap().frames
+    }
+
+    /// Returns the radial distribution function averaged over the accumulated frames.
+    pub fn average(&self) -> Vec<Float> {
+        let accumulator = self.accumulator.lock().unwrap();
+        if accumulator.frames == 0 {
+            return vec![0.0; self.bins()];
+        }
+        let frames = accumulator.frames as Float;
+        accumulator.sum.iter().map(|g| g / frames).collect()
+    }
+
+    /// Discards every accumulated frame.
+    pub fn reset(&self) {
+        *self.accumulator.lock().unwrap() = Accumulator::default();
+    }
+
+    /// Writes the averaged radial distribution function as columns of distance and value.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "# r g(r)")?;
+        for (r, g) in self.distances().iter().zip(self.average().iter()) {
+            writeln!(writer, "{} {}", r, g)?;
+        }
+        writer.flush()
+    }
+
+    // Returns `true` if the pair of atoms `i` and `j` is included.
+    fn selected(&self, system: &System, i: usize, j: usize) -> bool {
+        match self.species {
+            None => true,
+            Some((a, b)) => {
+                let (si, sj) = (system.species[i], system.species[j]);
+                (si == a && sj == b) || (si == b && sj == a)
+            }
+        }
+    }
+
+    // Returns the radial distribution function of a single configuration.
+    fn histogram(&self, system: &System) -> Vec<Float> {
+        let bins = self.bins();
+        let mut histogram = vec![0.0; bins];
+        let mut pairs = 0.0;
+        for i in 0..system.size {
+            for j in (i + 1)..system.size {
+                if !self.selected(system, i, j) {
+                    continue;
+                }
+                pairs += 1.0;
+                let r = system.cell.distance(&system.positions[i], &system.positions[j]);
+                let bin = (r / self.bin_width) as usize;
+                if r < self.cutoff && bin < bins {
+                    histogram[bin] += 1.0;
+                }
+            }
+        }
+        if pairs == 0.0 {
+            return histogram;
+        }
+
+        // ideal gas pairs within each spherical shell at the density of the current cell
+        let density = pairs / system.cell.volume();
+        histogram
+            .iter()
+            .enumerate()
+            .map(|(bin, count)| {
+                let inner = bin as Float * self.bin_width;
+                let outer = inner + self.bin_width;
+                let shell = 4.0 / 3.0 * PI * (outer.powi(3) - inner.powi(3));
+                count / (shell * density)
+            })
+            .collect()
+    }
+}
+
+impl Property for Rdf {
+    type Res = Vec<Float>;
+
+    fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
+        let histogram = self.histogram(system);
+        {
+            let mut accumulator = self.accumulator.lock().unwrap();
+            if accumulator.sum.is_empty() {
+                accumulator.sum = vec![0.0; histogram.len()];
+            }
+            accumulator
+                .sum
+                .iter_mut()
+                .zip(histogram.iter())
+                .for_each(|(sum, g)| *sum += g);
+            accumulator.frames += 1;
+        }
+        self.average()
+    }
+
+    fn name(&self) -> String {
+        "rdf".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rdf;
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn random_gas(rng: &mut StdRng, species: &[Species], cell: Cell) -> System {
+        let positions: Vec<Vector3<Float>> = (0..species.len())
+            .map(|_| cell.cartesian(&Vector3::new(rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0), rng.gen_range(0.0, 1.0))))
+            .collect();
+        System {
+            size: species.len(),
+            cell,
+            species: species.to_vec(),
+            velocities: vec![Vector3::zeros(); species.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn ideal_gas() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let a = Species::new(1.0, 0.0);
+        let b = Species::new(2.0, 0.0);
+        let mut species = vec![a; 60];
+        species.extend(vec![b; 40]);
+        let potentials = PotentialsBuilder::new().build();
+        let total = Rdf::new(5.0, 0.5);
+        let cross = Rdf::new(5.0, 0.5).species(b, a);
+        let like = Rdf::new(5.0, 0.5).species(b, b);
+        for frame in 0..200 {
+            // alternate between cells of different volume
+            let cell = if frame % 2 == 0 {
+                Cell::cubic(12.0)
+            } else {
+                Cell::triclinic(13.0, 12.0, 14.0, 90.0, 90.0, 90.0)
+            };
+            let system = random_gas(&mut rng, &species, cell);
+            total.calculate(&system, &potentials);
+            cross.calculate(&system, &potentials);
+            like.calculate(&system, &potentials);
+        }
+
+        // uncorrelated positions have no structure at any distance
+        for rdf in [total, cross, like].iter() {
+            assert_eq!(rdf.frames(), 200);
+            let average = rdf.average();
+            assert_eq!(average.len(), 10);
+            let outer: Float = average[4..].iter().sum::<Float>() / 6.0;
+            assert!((outer - 1.0).abs() < 0.05, "{:?}", average);
+            assert!(average[4..].iter().all(|g| (g - 1.0).abs() < 0.15), "{:?}", average);
+        }
+    }
+
+    #[test]
+    fn fcc_shells() {
+        let argon = Species::new(39.948, 0.0);
+        let a: Float = 5.26;
+        let basis = [[0.0, 0.0, 0.0], [0.5, 0.5, 0.0], [0.5, 0.0, 0.5], [0.0, 0.5, 0.5]];
+        let mut positions = Vec::new();
+        for i in 0..3 {
+            for j in 0..3 {
+                for k in 0..3 {
+                    for site in basis.iter() {
+                        positions.push(Vector3::new(site[0] + i as Float, site[1] + j as Float, site[2] + k as Float) * a);
+                    }
+                }
+            }
+        }
+        let system = System {
+            size: positions.len(),
+            cell: Cell::cubic(3.0 * a),
+            species: vec![argon; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+        let rdf = Rdf::new(6.0, 0.1);
+        let shared = rdf.clone();
+        let g = rdf.calculate(&system, &potentials);
+        assert_eq!(shared.frames(), 1);
+
+        // 12 nearest neighbors at a / sqrt(2) and 6 second neighbors at a
+        let distances = rdf.distances();
+        let density = system.size as Float / system.cell.volume();
+        let coordination = |low: Float, high: Float| -> Float {
+            distances
+                .iter()
+                .zip(g.iter())
+                .filter(|(r, _)| **r > low && **r < high)
+                .map(|(r, g)| 4.0 * crate::internal::consts::PI * r * r * 0.1 * density * g)
+                .sum()
+        };
+        assert!(g.iter().zip(distances.iter()).filter(|(_, r)| **r < 3.6).all(|(g, _)| *g == 0.0));
+        assert!((coordination(3.6, 3.9) - 12.0).abs() < 0.5);
+        assert!((coordination(5.1, 5.4) - 6.0).abs() < 0.5);
+
+        let mut buffer = Vec::new();
+        shared.write(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().count(), 61);
+        shared.reset();
+        assert_eq!(rdf.frames(), 0);
+    }
+}
@@ -0,0 +1,432 @@
+//! Optimal superposition of structures and their root mean squared deviation.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::Property;
+use crate::system::System;
+
+/// Rigid body motion which best superposes one set of positions onto another.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Superposition {
+    /// Proper rotation applied about the center of the mobile positions.
+    pub rotation: Matrix3<Float>,
+    /// Weighted center of the mobile positions.
+    pub mobile_center: Vector3<Float>,
+    /// Weighted center of the reference positions.
+    pub reference_center: Vector3<Float>,
+    /// Weighted root mean squared deviation after superposition.
+    pub rmsd: Float,
+}
+
+impl Superposition {
+    /// Returns `position` moved by the rigid body motion onto the reference.
+    pub fn apply(&self, position: &Vector3<Float>) -> Vector3<Float> {
+        self.rotation * (position - self.mobile_center) + self.reference_center
+    }
+}
+
+// Returns the weighted center of `positions`.
+fn center(positions: &[Vector3<Float>], weights: &[Float]) -> Vector3<Float> {
+    let total: Float = weights.iter().sum();
+    positions
+        .iter()
+        .zip(weights.iter())
+        .map(|(position, weight)| position * *weight)
+        .sum::<Vector3<Float>>()
+        / total
+}
+
+/// Returns the rotation and translation which minimize the weighted deviation of `mobile` from `reference`.
+///
+/// Uses the Kabsch algorithm, which finds the optimal rotation from the singular value
+/// decomposition of the weighted covariance between the centered positions and corrects it to
+/// exclude reflections. Every position has equal weight unless `weights` are given.
+///
+/// # References
+///
+/// [1] Kabsch, Wolfgang. "A solution for the best rotation to relate two sets of vectors." Acta Crystallographica Section A 32.5 (1976): 922-923.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let reference = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 2.0, 0.0)];
+/// // rotated by 90 degrees about the z axis and translated
+/// let mobile = vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new(5.0, 6.0, 5.0), Vector3::new(3.0, 5.0, 5.0)];
+/// let superposition = kabsch(&reference, &mobile, None);
+/// assert!(superposition.rmsd < 1e-3);
+/// assert!((superposition.apply(&mobile[1]) - reference[1]).norm() < 1e-3);
+/// ```
+pub fn kabsch(reference: &[Vector3<Float>], mobile: &[Vector3<Float>], weights: Option<&[Float]>) -> Superposition {
+    assert_eq!(reference.len(), mobile.len(), "Expected an equal number of reference and mobile positions");
+    let uniform = vec![1.0; reference.len()];
+    let weights = weights.unwrap_or(&uniform);
+    let reference_center = center(reference, weights);
+    let mobile_center = center(mobile, weights);
+
+    // covariance of the centered positions
+    let covariance = mobile
+        .iter()
+        .zip(reference.iter())
+        .zip(weights.iter())
+        .map(|((p, q), w)| (p - mobile_center) * (q - reference_center).transpose() * *w)
+        .sum::<Matrix3<Float>>();
+    let svd = covariance.svd(true, true);
+    let (u, v) = (svd.u.unwrap(), svd.v_t.unwrap().transpose());
+    // flip the axis of the smallest singular value if the rotation would be improper
+    let sign = (v * u.transpose()).determinant().signum();
+    let rotation = v * Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, sign)) * u.transpose();
+
+    let mut superposition = Superposition {
+        rotation,
+        mobile_center,
+        reference_center,
+        rmsd: 0.0,
+    };
+    let aligned: Vec<Vector3<Float>> = mobile.iter().map(|p| superposition.apply(p)).collect();
+    superposition.rmsd = rmsd(reference, &aligned, Some(weights));
+    superposition
+}
+
+/// Returns the weighted root mean squared deviation between two sets of positions without superposing them.
+pub fn rmsd(a: &[Vector3<Float>], b: &[Vector3<Float>], weights: Option<&[Float]>) -> Float {
+    assert_eq!(a.len(), b.len(), "Expected an equal number of positions");
+    let uniform = vec![1.0; a.len()];
+    let weights = weights.unwrap_or(&uniform);
+    let total: Float = weights.iter().sum();
+    let sum: Float = a
+        .iter()
+        .zip(b.iter())
+        .zip(weights.iter())
+        .map(|((p, q), w)| w * (p - q).norm_squared())
+        .sum();
+    Float::sqrt(sum / total)
+}
+
+// Trajectory information recorded since the first frame.
+#[derive(Debug, Default)]
+struct Tracker {
+    reference: Vec<Vector3<Float>>,
+    previous: Vec<Vector3<Float>>,
+    images: Vec<Vector3<Float>>,
+    history: Vec<Float>,
+}
+
+/// Root mean squared deviation of a selection of atoms from its structure in a reference frame.
+///
+/// Each frame is first superposed onto the reference by the rigid body motion which best fits
+/// the fitting selection, so overall translation and rotation of the structure do not contribute
+/// to the deviation of the measured selection. The fitting selection defaults to the measured
+/// selection, and both default to every atom. The first recorded frame is the reference unless
+/// one is given.
+///
+/// Positions are unwrapped across periodic boundaries. In the first frame each atom takes the
+/// periodic image nearest to its reference position if a reference is given, or otherwise nearest
+/// to the first fitted atom, and no atom may move more than half the width of the cell between
+/// two frames.
+/// Clones share the same recorded frames, so a clone kept aside before the property is added to
+/// an output group can be analyzed once the simulation ends.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // deviation of a ligand after fitting the protein backbone
+/// let rmsd = Rmsd::new()
+///     .atoms(vec![100, 101, 102, 103])
+///     .fit(vec![0, 4, 8, 12, 16, 20])
+///     .mass_weighted();
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(100)
+///     .output(rmsd.clone())
+///     .build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Rmsd {
+    atoms: Option<Vec<usize>>,
+    fit: Option<Vec<usize>>,
+    reference: Option<Vec<Vector3<Float>>>,
+    mass_weighted: bool,
+    tracker: Arc<Mutex<Tracker>>,
+}
+
+impl Rmsd {
+    /// Returns a new [`Rmsd`] of every atom fit on every atom.
+    pub fn new() -> Rmsd {
+        Rmsd::default()
+    }
+
+    /// Sets the indices of the atoms whose deviation is measured.
+    pub fn atoms(mut self, atoms: Vec<usize>) -> Rmsd {
+        self.atoms = Some(atoms);
+        self
+    }
+
+    /// Sets the indices of the atoms superposed onto the reference before measuring.
+    pub fn fit(mut self, atoms: Vec<usize>) -> Rmsd {
+        self.fit = Some(atoms);
+        self
+    }
+
+    /// Sets the position of every atom in the reference structure instead of using the first frame.
+    pub fn reference(mut self, positions: Vec<Vector3<Float>>) -> Rmsd {
+        self.reference = Some(positions);
+        self
+    }
+
+    /// Weights each atom by its mass in both the superposition and the deviation.
+    pub fn mass_weighted(mut self) -> Rmsd {
+        self.mass_weighted = true;
+        self
+    }
+
+    /// Returns the number of frames recorded so far.
+    pub fn frames(&self) -> usize {
+        self.tracker.lock().unwrap().history.len()
+    }
+
+    /// Returns the root mean squared deviation in each recorded frame.
+    pub fn history(&self) -> Vec<Float> {
+        self.tracker.lock().unwrap().history.clone()
+    }
+
+    /// Discards every recorded frame so the next frame becomes the new reference unless one was given.
+    pub fn reset(&self) {
+        *self.tracker.lock().unwrap() = Tracker::default();
+    }
+
+    /// Writes the root mean squared deviation in each recorded frame after the frame index.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "# frame rmsd")?;
+        for (frame, value) in self.history().iter().enumerate() {
+            writeln!(writer, "{} {}", frame, value)?;
+        }
+        writer.flush()
+    }
+
+    /// Returns the position of every atom superposed onto the reference by the fit to the current frame.
+    ///
+    /// Records nothing, so it may be called alongside the property to write an aligned trajectory.
+    /// Returns the current positions unchanged before the first frame is recorded.
+    pub fn align(&self, system: &System) -> Vec<Vector3<Float>> {
+        let tracker = self.tracker.lock().unwrap();
+        if tracker.reference.is_empty() {
+            return system.positions.clone();
+        }
+        let (unwrapped, _) = self.unwrap(&tracker, system);
+        let superposition = self.superpose(&tracker.reference, &unwrapped, system);
+        unwrapped.iter().map(|position| superposition.apply(position)).collect()
+    }
+
+    // Returns the indices of the measured atoms.
+    fn measured(&self, system: &System) -> Vec<usize> {
+        match &self.atoms {
+            Some(atoms) => atoms.clone(),
+            None => (0..system.size).collect(),
+        }
+    }
+
+    // Returns the indices of the atoms superposed onto the reference.
+    fn fitted(&self, system: &System) -> Vec<usize> {
+        match &self.fit {
+            Some(atoms) => atoms.clone(),
+            None => self.measured(system),
+        }
+    }
+
+    fn weights(&self, atoms: &[usize], system: &System) -> Vec<Float> {
+        atoms
+            .iter()
+            .map(|&i| if self.mass_weighted { system.species[i].mass() } else { 1.0 })
+            .collect()
+    }
+
+    // Returns the unwrapped positions and the periodic image of each atom given the boundaries crossed since the previous frame.
+    fn unwrap(&self, tracker: &Tracker, system: &System) -> (Vec<Vector3<Float>>, Vec<Vector3<Float>>) {
+        let inverse = system.cell.inverse_matrix();
+        let matrix = system.cell.matrix();
+        let images: Vec<Vector3<Float>> = system
+            .positions
+            .iter()
+            .zip(tracker.previous.iter())
+            .zip(tracker.images.iter())
+            .map(|((position, previous), image)| image - (inverse * (position - previous)).map(Float::round))
+            .collect();
+        let unwrapped = system
+            .positions
+            .iter()
+            .zip(images.iter())
+            .map(|(position, image)| position + matrix * image)
+            .collect();
+        (unwrapped, images)
+    }
+
+    // Returns the superposition of the fitted atoms in `positions` onto `reference`.
+    fn superpose(&self, reference: &[Vector3<Float>], positions: &[Vector3<Float>], system: &System) -> Superposition {
+        let fitted = self.fitted(system);
+        let weights = self.weights(&fitted, system);
+        let reference: Vec<Vector3<Float>> = fitted.iter().map(|&i| reference[i]).collect();
+        let mobile: Vec<Vector3<Float>> = fitted.iter().map(|&i| positions[i]).collect();
+        kabsch(&reference, &mobile, Some(&weights))
+    }
+
+    // Returns the image of each atom which places it nearest to its target position.
+    fn nearest_images(system: &System, targets: &[Vector3<Float>]) -> Vec<Vector3<Float>> {
+        let inverse = system.cell.inverse_matrix();
+        system
+            .positions
+            .iter()
+            .zip(targets.iter())
+            .map(|(position, target)| -(inverse * (position - target)).map(Float::round))
+            .collect()
+    }
+}
+
+impl Property for Rmsd {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
+        let mut tracker = self.tracker.lock().unwrap();
+        if tracker.history.is_empty() {
+            // make the selections whole around the reference or the first fitted atom
+            let targets = match &self.reference {
+                Some(reference) => reference.clone(),
+                None => {
+                    let anchor = self.fitted(system).first().map_or(Vector3::zeros(), |&i| system.positions[i]);
+                    vec![anchor; system.size]
+                }
+            };
+            tracker.images = Rmsd::nearest_images(system, &targets);
+            tracker.previous = system.positions.clone();
+            let (unwrapped, _) = self.unwrap(&tracker, system);
+            tracker.reference = self.reference.clone().unwrap_or(unwrapped);
+        }
+
+        let (unwrapped, images) = self.unwrap(&tracker, system);
+        tracker.images = images;
+        tracker.previous = system.positions.clone();
+        let superposition = self.superpose(&tracker.reference, &unwrapped, system);
+        let measured = self.measured(system);
+        let reference: Vec<Vector3<Float>> = measured.iter().map(|&i| tracker.reference[i]).collect();
+        let aligned: Vec<Vector3<Float>> = measured.iter().map(|&i| superposition.apply(&unwrapped[i])).collect();
+        let value = rmsd(&reference, &aligned, Some(&self.weights(&measured, system)));
+        tracker.history.push(value);
+        value
+    }
+
+    fn name(&self) -> String {
+        "rmsd".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{kabsch, rmsd, Rmsd};
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::{Rotation3, Vector3};
+
+    fn structure() -> Vec<Vector3<Float>> {
+        vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.5, 0.0, 0.0),
+            Vector3::new(2.0, 1.4, 0.0),
+            Vector3::new(3.5, 1.5, 0.3),
+            Vector3::new(4.0, 2.9, 1.0),
+        ]
+    }
+
+    #[test]
+    fn superposition() {
+        let reference = structure();
+        let rotation = Rotation3::from_axis_angle(&Vector3::y_axis(), 1.2) * Rotation3::from_axis_angle(&Vector3::x_axis(), -0.7);
+        let shift = Vector3::new(3.0, -2.0, 7.0);
+        let mobile: Vec<Vector3<Float>> = reference.iter().map(|p| rotation * p + shift).collect();
+        let superposition = kabsch(&reference, &mobile, None);
+        assert!(superposition.rmsd < 1e-3);
+        assert_relative_eq!(superposition.rotation, rotation.inverse().into_inner(), epsilon = 1e-4);
+        assert!(rmsd(&reference, &mobile, None) > 1.0);
+
+        // a mirror image cannot be superposed by a proper rotation
+        let mirror: Vec<Vector3<Float>> = reference.iter().map(|p| Vector3::new(p.x, p.y, -p.z)).collect();
+        let superposition = kabsch(&reference, &mirror, None);
+        assert_relative_eq!(superposition.rotation.determinant(), 1.0, epsilon = 1e-4);
+        assert!(superposition.rmsd > 0.1);
+
+        // heavily weighted positions dominate the fit
+        let mut displaced = reference.clone();
+        displaced[4].z += 1.0;
+        let weights = [100.0, 100.0, 100.0, 100.0, 1.0];
+        let superposition = kabsch(&reference, &displaced, Some(&weights));
+        assert!((superposition.apply(&displaced[0]) - reference[0]).norm() < 1e-2);
+    }
+
+    #[test]
+    fn drifting_structure() {
+        // a rigid structure spanning the periodic boundary which drifts and tumbles
+        let cell = Cell::cubic(8.0);
+        let reference: Vec<Vector3<Float>> = structure().iter().map(|p| p + Vector3::new(6.0, 6.5, 7.5)).collect();
+        let mut system = System {
+            size: 5,
+            cell,
+            species: vec![Species::new(12.0, 0.0); 5],
+            positions: reference.clone(),
+            velocities: vec![Vector3::zeros(); 5],
+            topology: Topology::default(),
+        };
+        let wrap = |system: &mut System| {
+            for position in system.positions.iter_mut() {
+                system.cell.wrap_vector(position);
+            }
+        };
+        wrap(&mut system);
+        let potentials = PotentialsBuilder::new().build();
+        let rigid = Rmsd::new();
+        let flexible = Rmsd::new().atoms(vec![4]).fit(vec![0, 1, 2, 3]);
+        let shared = rigid.clone();
+        for n in 0..20 {
+            let rotation = Rotation3::from_axis_angle(&Vector3::z_axis(), 0.1 * n as Float);
+            let shift = Vector3::new(0.3, -0.2, 0.4) * n as Float;
+            system.positions = reference.iter().map(|p| rotation * p + shift).collect();
+            // the last atom of the flexible selection moves relative to the others
+            system.positions[4] += rotation * Vector3::new(0.0, 0.0, 0.05 * n as Float);
+            wrap(&mut system);
+            rigid.calculate(&system, &potentials);
+            let value = flexible.calculate(&system, &potentials);
+            assert_relative_eq!(value, 0.05 * n as Float, epsilon = 1e-3);
+        }
+        assert_eq!(shared.frames(), 20);
+        // fitting every atom spreads the displacement of one atom over the others
+        let bound = 0.05 * 19.0 / Float::sqrt(5.0);
+        let history = shared.history();
+        assert!(history[19] > 0.1 && history[19] < bound, "{:?}", history);
+        assert!(history.windows(2).all(|pair| pair[1] > pair[0]));
+
+        let aligned = flexible.align(&system);
+        for i in 0..4 {
+            assert!((aligned[i] - reference[i]).norm() < 1e-3);
+        }
+
+        let mut buffer = Vec::new();
+        shared.write(&mut buffer).unwrap();
+        let text = String::from_utf8(buffer).unwrap();
+        assert_eq!(text.lines().count(), 21);
+        shared.reset();
+        assert_eq!(rigid.frames(), 0);
+    }
+}
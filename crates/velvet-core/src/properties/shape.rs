@@ -0,0 +1,243 @@
+//! Shape of molecules from the eigenvalues of their gyration tensors.
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::internal::Float;
+use crate::properties::IntrinsicProperty;
+use crate::system::System;
+
+/// Size and shape of a single molecule.
+///
+/// The principal moments are the eigenvalues `l1 <= l2 <= l3` of the gyration tensor, the
+/// average outer product of the displacement of each atom from the center of the molecule.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MoleculeShape {
+    /// Principal moments of the gyration tensor in ascending order in square angstroms.
+    pub moments: Vector3<Float>,
+    /// Radius of gyration in angstroms.
+    pub radius_of_gyration: Float,
+    /// Asphericity `l3 - (l1 + l2) / 2`, zero for shapes with tetrahedral or higher symmetry.
+    pub asphericity: Float,
+    /// Acylindricity `l2 - l1`, zero for shapes with cylindrical symmetry.
+    pub acylindricity: Float,
+    /// Relative shape anisotropy between zero for spherical shapes and one for linear shapes.
+    pub anisotropy: Float,
+}
+
+impl MoleculeShape {
+    /// Returns the shape descriptors of a gyration tensor.
+    pub fn from_gyration_tensor(tensor: &Matrix3<Float>) -> MoleculeShape {
+        let mut moments: Vec<Float> = tensor.symmetric_eigenvalues().iter().map(|l| l.max(0.0)).collect();
+        moments.sort_by(|a, b| a.total_cmp(b));
+        let (l1, l2, l3) = (moments[0], moments[1], moments[2]);
+        let trace = l1 + l2 + l3;
+        let anisotropy = if trace > 0.0 {
+            1.5 * (l1 * l1 + l2 * l2 + l3 * l3) / (trace * trace) - 0.5
+        } else {
+            0.0
+        };
+        MoleculeShape {
+            moments: Vector3::new(l1, l2, l3),
+            radius_of_gyration: trace.sqrt(),
+            asphericity: l3 - 0.5 * (l1 + l2),
+            acylindricity: l2 - l1,
+            anisotropy,
+        }
+    }
+}
+
+/// Gyration tensor shape descriptors of each molecule in the system.
+///
+/// Molecules default to the groups of bonded atoms in the topology of the system. Each
+/// molecule is made whole across periodic boundaries by following its bonds, so molecules
+/// may span more than half of the cell. Atoms of an explicitly given molecule which are not
+/// connected by bonds take the periodic image nearest to its first atom.
+///
+/// # References
+///
+/// [1] Theodorou, Doros N., and Ulrich W. Suter. "Shape of unperturbed linear polymers: polypropylene." Macromolecules 18.6 (1985): 1206-1214.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(1000)
+///     .output(ShapeDescriptors::new().mass_weighted())
+///     .build();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ShapeDescriptors {
+    molecules: Option<Vec<Vec<usize>>>,
+    mass_weighted: bool,
+}
+
+impl ShapeDescriptors {
+    /// Returns new [`ShapeDescriptors`] of the bonded molecules of the system.
+    pub fn new() -> ShapeDescriptors {
+        ShapeDescriptors::default()
+    }
+
+    /// Sets the indices of the atoms of each molecule instead of using the bonded molecules.
+    pub fn molecules(mut self, molecules: Vec<Vec<usize>>) -> ShapeDescriptors {
+        self.molecules = Some(molecules);
+        self
+    }
+
+    /// Weights each atom by its mass in the center and gyration tensor of its molecule.
+    pub fn mass_weighted(mut self) -> ShapeDescriptors {
+        self.mass_weighted = true;
+        self
+    }
+
+    // Returns the positions of the atoms of a molecule made whole by following its bonds.
+    fn unwrap(system: &System, atoms: &[usize], neighbors: &[Vec<usize>]) -> Vec<Vector3<Float>> {
+        let mut positions: Vec<Option<Vector3<Float>>> = vec![None; atoms.len()];
+        let local = |atom: usize| atoms.iter().position(|&a| a == atom);
+        for start in 0..atoms.len() {
+            if positions[start].is_some() {
+                continue;
+            }
+            // atoms not bonded to the rest of the molecule are placed near its first atom
+            let mut origin = system.positions[atoms[start]];
+            if let Some(first) = positions[0] {
+                let mut displacement = origin - first;
+                system.cell.vector_image(&mut displacement);
+                origin = first + displacement;
+            }
+            positions[start] = Some(origin);
+            let mut stack = vec![start];
+            while let Some(k) = stack.pop() {
+                let position = positions[k].unwrap();
+                for &neighbor in &neighbors[atoms[k]] {
+                    if let Some(n) = local(neighbor) {
+                        if positions[n].is_none() {
+                            let mut bond = system.positions[neighbor] - system.positions[atoms[k]];
+                            system.cell.vector_image(&mut bond);
+                            positions[n] = Some(position + bond);
+                            stack.push(n);
+                        }
+                    }
+                }
+            }
+        }
+        positions.into_iter().map(Option::unwrap).collect()
+    }
+}
+
+impl IntrinsicProperty for ShapeDescriptors {
+    type Res = Vec<MoleculeShape>;
+
+    fn calculate_intrinsic(&self, system: &System) -> <Self as IntrinsicProperty>::Res {
+        let molecules = match &self.molecules {
+            Some(molecules) => molecules.clone(),
+            None => system.topology.molecules(),
+        };
+        let mut neighbors = vec![Vec::new(); system.size];
+        for &[i, j] in &system.topology.bonds {
+            neighbors[i].push(j);
+            neighbors[j].push(i);
+        }
+        molecules
+            .iter()
+            .filter(|atoms| !atoms.is_empty())
+            .map(|atoms| {
+                let positions = ShapeDescriptors::unwrap(system, atoms, &neighbors);
+                let weights: Vec<Float> = atoms
+                    .iter()
+                    .map(|&i| if self.mass_weighted { system.species[i].mass() } else { 1.0 })
+                    .collect();
+                let total: Float = weights.iter().sum();
+                let center = positions
+                    .iter()
+                    .zip(weights.iter())
+                    .map(|(position, weight)| position * *weight)
+                    .sum::<Vector3<Float>>()
+                    / total;
+                let tensor = positions
+                    .iter()
+                    .zip(weights.iter())
+                    .map(|(position, weight)| (position - center) * (position - center).transpose() * *weight)
+                    .sum::<Matrix3<Float>>()
+                    / total;
+                MoleculeShape::from_gyration_tensor(&tensor)
+            })
+            .collect()
+    }
+
+    fn name(&self) -> String {
+        "shape_descriptors".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MoleculeShape, ShapeDescriptors};
+    use crate::internal::Float;
+    use crate::properties::IntrinsicProperty;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::{Matrix3, Vector3};
+
+    #[test]
+    fn limiting_shapes() {
+        let sphere = MoleculeShape::from_gyration_tensor(&Matrix3::from_diagonal_element(2.0));
+        assert_relative_eq!(sphere.asphericity, 0.0);
+        assert_relative_eq!(sphere.anisotropy, 0.0, epsilon = 1e-6);
+        assert_relative_eq!(sphere.radius_of_gyration, Float::sqrt(6.0), epsilon = 1e-6);
+
+        let rod = MoleculeShape::from_gyration_tensor(&Matrix3::from_diagonal(&Vector3::new(0.0, 4.0, 0.0)));
+        assert_relative_eq!(rod.anisotropy, 1.0, epsilon = 1e-6);
+        assert_relative_eq!(rod.asphericity, 4.0, epsilon = 1e-6);
+        assert_relative_eq!(rod.acylindricity, 0.0, epsilon = 1e-6);
+
+        // a flat disc is cylindrically symmetric about its normal
+        let disc = MoleculeShape::from_gyration_tensor(&Matrix3::from_diagonal(&Vector3::new(1.0, 1.0, 0.0)));
+        assert_relative_eq!(disc.acylindricity, 1.0, epsilon = 1e-6);
+        assert_relative_eq!(disc.anisotropy, 0.25, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn wrapped_chain() {
+        // straight chain of ten atoms spanning most of the cell across the boundary
+        let size = 11;
+        let atom = Species::new(1.0, 0.0);
+        let mut positions: Vec<Vector3<Float>> = (0..10)
+            .map(|i| Vector3::new(8.0 + 1.5 * i as Float, 3.0, 3.0))
+            .collect();
+        positions.push(Vector3::new(1.0, 1.0, 1.0));
+        let mut system = System {
+            size,
+            cell: Cell::cubic(20.0),
+            species: vec![atom; size],
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology {
+                bonds: (0..9).map(|i| [i + 1, i]).collect(),
+                ..Topology::default()
+            },
+        };
+        for position in system.positions.iter_mut() {
+            system.cell.wrap_vector(position);
+        }
+        let shapes = ShapeDescriptors::new().calculate_intrinsic(&system);
+        assert_eq!(shapes.len(), 1);
+        // radius of gyration of evenly spaced points along a line
+        let expected = 1.5 * Float::sqrt((100.0 - 1.0) / 12.0);
+        assert_relative_eq!(shapes[0].radius_of_gyration, expected, epsilon = 1e-3);
+        assert_relative_eq!(shapes[0].anisotropy, 1.0, epsilon = 1e-4);
+
+        // explicit molecules include the unbonded atom as its own molecule
+        let shapes = ShapeDescriptors::new()
+            .molecules(vec![(0..10).collect(), vec![10]])
+            .mass_weighted()
+            .calculate_intrinsic(&system);
+        assert_relative_eq!(shapes[0].radius_of_gyration, expected, epsilon = 1e-3);
+        assert_eq!(shapes[1].radius_of_gyration, 0.0);
+    }
+}
@@ -1,24 +1,25 @@
 //! Instantaneous temperature of the system.
 
 use crate::internal::consts::BOLTZMANN;
-use crate::internal::Float;
+use crate::precision::Real;
 use crate::properties::energy::KineticEnergy;
 use crate::properties::IntrinsicProperty;
 use crate::system::System;
 
 /// Instantaneous temperature of the system.
+///
+/// The kinetic energy is shared among the [degrees of freedom](System::degrees_of_freedom) of the
+/// atoms, which excludes those removed by constrained distances.
 #[derive(Clone, Copy, Debug)]
 pub struct Temperature;
 
-impl IntrinsicProperty for Temperature {
-    type Res = Float;
+impl<R: Real> IntrinsicProperty<R> for Temperature {
+    type Res = R;
 
-    fn calculate_intrinsic(&self, system: &System) -> <Self as IntrinsicProperty>::Res {
+    fn calculate_intrinsic(&self, system: &System<R>) -> R {
         let kinetic = KineticEnergy.calculate_intrinsic(system);
-        // NOTE: Calculating DOF this way is a potentially nasty bug if future
-        // support is added for degrees of freedom beyond just 3D particles.
-        let dof = (system.size * 3) as Float;
-        2.0 * kinetic / (dof * BOLTZMANN)
+        let dof = R::from_double(system.degrees_of_freedom() as f64);
+        R::from_double(2.0) * kinetic / (dof * R::from_float(BOLTZMANN))
     }
 
     fn name(&self) -> String {
@@ -0,0 +1,312 @@
+//! Velocity autocorrelation function and vibrational density of states.
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+use nalgebra::Vector3;
+
+use crate::correlation::MultiTauCorrelator;
+use crate::internal::consts::PI;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::Property;
+use crate::system::System;
+
+// Velocities fed to the correlator since the first calculation.
+#[derive(Debug, Default)]
+struct Samples {
+    calls: usize,
+    first: Option<Vec<Vector3<Float>>>,
+    correlator: MultiTauCorrelator,
+}
+
+/// Velocity autocorrelation function of the atoms and its vibrational density of states.
+///
+/// Every `stride` calculations the velocities are fed to a [`MultiTauCorrelator`], which
+/// averages the autocorrelation over every time origin with lags spaced more widely at longer
+/// times, so long runs need little memory. The Fourier transform of the normalized
+/// autocorrelation gives the vibrational density of states. Each calculation returns the
+/// correlation of the current velocities with those of the first calculation.
+///
+/// Clones share the same correlator, so a clone kept aside before the property is added to an
+/// output group can be analyzed once the simulation ends.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // calculated every 2 steps of 1 fs with the velocities sampled every other calculation
+/// let vacf = VelocityAutocorrelation::new(2.0).stride(2);
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(2)
+///     .output(vacf.clone())
+///     .build();
+/// ```
+#[derive(Clone, Debug)]
+pub struct VelocityAutocorrelation {
+    interval: Float,
+    stride: usize,
+    samples: Arc<Mutex<Samples>>,
+}
+
+impl VelocityAutocorrelation {
+    /// Returns a new [`VelocityAutocorrelation`] with the default [`MultiTauCorrelator`].
+    ///
+    /// # Arguments
+    ///
+    /// * `interval` - Time between consecutive calculations in femtoseconds.
+    pub fn new(interval: Float) -> VelocityAutocorrelation {
+        VelocityAutocorrelation {
+            interval,
+            stride: 1,
+            samples: Arc::new(Mutex::new(Samples::default())),
+        }
+    }
+
+    /// Sets the number of calculations between samples of the velocities. Defaults to 1.
+    pub fn stride(mut self, stride: usize) -> VelocityAutocorrelation {
+        self.stride = stride.max(1);
+        self
+    }
+
+    /// Sets the correlator which accumulates the velocities.
+    ///
+    /// A correlator with a single level gives evenly spaced lags at the cost of a shorter window.
+    pub fn correlator(mut self, correlator: MultiTauCorrelator) -> VelocityAutocorrelation {
+        self.samples = Arc::new(Mutex::new(Samples {
+            correlator,
+            ..Samples::default()
+        }));
+        self
+    }
+
+    /// Returns the number of velocity samples so far.
+    pub fn frames(&self) -> usize {
+        self.samples.lock().unwrap().correlator.samples()
+    }
+
+    /// Returns the time between velocity samples in femtoseconds.
+    pub fn timestep(&self) -> Float {
+        self.interval * self.stride as Float
+    }
+
+    /// Returns the number of lags in the autocorrelation function.
+    pub fn lags(&self) -> usize {
+        self.samples.lock().unwrap().correlator.lags().len()
+    }
+
+    /// Returns the time of each lag in femtoseconds.
+    pub fn times(&self) -> Vec<Float> {
+        self.samples.lock().unwrap().correlator.times(self.timestep())
+    }
+
+    /// Returns the velocity autocorrelation function normalized to one at zero lag.
+    pub fn correlation(&self) -> Vec<Float> {
+        self.samples.lock().unwrap().correlator.normalized()
+    }
+
+    // Number of sampling intervals spanned by the lags, including the one at zero lag.
+    fn span(&self) -> usize {
+        let lags = self.samples.lock().unwrap().correlator.lags();
+        lags.last().map_or(0, |lag| lag + 1)
+    }
+
+    /// Returns the frequency of each point of the vibrational density of states in terahertz.
+    pub fn frequencies(&self) -> Vec<Float> {
+        let span = self.span();
+        // resolution of the transform over the full lag window
+        let resolution = 1000.0 / (2.0 * span as Float * self.timestep());
+        (0..span).map(|k| k as Float * resolution).collect()
+    }
+
+    /// Returns the vibrational density of states normalized to unit area over frequency.
+    ///
+    /// The density of states is the cosine transform of the autocorrelation function tapered by
+    /// a Hann window, which suppresses the ringing caused by truncating the lags. The unevenly
+    /// spaced lags of the correlator are integrated with the trapezoidal rule.
+    pub fn density_of_states(&self) -> Vec<Float> {
+        let correlation = self.correlation();
+        if correlation.is_empty() {
+            return Vec::new();
+        }
+        let times = self.times();
+        let dt = self.timestep();
+        let window = self.span() as Float * dt;
+        // trapezoidal weight of each lag in a transform over positive and negative lags
+        let weights: Vec<Float> = (0..times.len())
+            .map(|i| {
+                let before = if i == 0 { times[0] } else { times[i - 1] };
+                let after = times.get(i + 1).copied().unwrap_or(times[i] + dt);
+                after - before
+            })
+            .collect();
+        let frequencies = self.frequencies();
+        let density: Vec<Float> = frequencies
+            .iter()
+            .map(|nu| {
+                let omega = 2.0 * PI * nu / 1000.0;
+                correlation
+                    .iter()
+                    .zip(times.iter())
+                    .zip(weights.iter())
+                    .map(|((c, t), weight)| {
+                        let hann = 0.5 * (1.0 + Float::cos(PI * t / window));
+                        weight * hann * c * Float::cos(omega * t)
+                    })
+                    .sum::<Float>()
+            })
+            .collect();
+        let area = density.iter().sum::<Float>() * frequencies.get(1).copied().unwrap_or(1.0);
+        if area > 0.0 {
+            density.iter().map(|d| d / area).collect()
+        } else {
+            density
+        }
+    }
+
+    /// Discards every velocity sample, keeping the configuration of the correlator.
+    pub fn reset(&self) {
+        let mut samples = self.samples.lock().unwrap();
+        samples.calls = 0;
+        samples.first = None;
+        samples.correlator.reset();
+    }
+
+    /// Writes the autocorrelation function as columns of time and value.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "# time vacf")?;
+        for (time, c) in self.times().iter().zip(self.correlation().iter()) {
+            writeln!(writer, "{} {}", time, c)?;
+        }
+        writer.flush()
+    }
+
+    /// Writes the vibrational density of states as columns of frequency and value.
+    pub fn write_density_of_states<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writeln!(writer, "# frequency vdos")?;
+        for (nu, d) in self.frequencies().iter().zip(self.density_of_states().iter()) {
+            writeln!(writer, "{} {}", nu, d)?;
+        }
+        writer.flush()
+    }
+}
+
+impl Property for VelocityAutocorrelation {
+    type Res = Float;
+
+    fn calculate(&self, system: &System, _: &Potentials) -> Self::Res {
+        let mut samples = self.samples.lock().unwrap();
+        if samples.calls.is_multiple_of(self.stride) {
+            samples.correlator.push(&system.velocities);
+        }
+        samples.calls += 1;
+        let first = samples.first.get_or_insert_with(|| system.velocities.clone());
+        let norm: Float = first.iter().map(|v| v.norm_squared()).sum();
+        if norm > 0.0 {
+            let overlap: Float = first
+                .iter()
+                .zip(system.velocities.iter())
+                .map(|(a, b)| a.dot(b))
+                .sum();
+            overlap / norm
+        } else {
+            0.0
+        }
+    }
+
+    fn name(&self) -> String {
+        "vacf".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VelocityAutocorrelation;
+    use crate::correlation::MultiTauCorrelator;
+    use crate::internal::consts::PI;
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn harmonic_oscillators() {
+        // independent oscillators with a period of 100 fs and random phases and directions
+        let mut rng = StdRng::seed_from_u64(17);
+        let size = 100;
+        let omega = 2.0 * PI / 100.0;
+        let phases: Vec<Float> = (0..size).map(|_| rng.gen_range(0.0, 2.0 * PI)).collect();
+        let axes: Vec<Vector3<Float>> = (0..size)
+            .map(|_| Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)).normalize())
+            .collect();
+        let mut system = System {
+            size,
+            cell: Cell::cubic(10.0),
+            species: vec![Species::new(1.0, 0.0); size],
+            positions: vec![Vector3::zeros(); size],
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new().build();
+
+        // calculated every 2 fs with samples every 4 fs
+        let vacf = VelocityAutocorrelation::new(2.0).stride(2);
+        let shared = vacf.clone();
+        // a single level of the correlator gives evenly spaced lags
+        let even = VelocityAutocorrelation::new(2.0)
+            .stride(2)
+            .correlator(MultiTauCorrelator::new(100, 2).levels(1));
+        for n in 0..400 {
+            let t = n as Float * 2.0;
+            for i in 0..size {
+                system.velocities[i] = Float::cos(omega * t + phases[i]) * axes[i];
+            }
+            let value = vacf.calculate(&system, &potentials);
+            assert!(value.abs() <= 1.0 + 1e-4);
+            even.calculate(&system, &potentials);
+        }
+        assert_eq!(shared.frames(), 200);
+        assert_eq!(shared.timestep(), 4.0);
+        // 16 evenly spaced lags followed by 8 at each coarser level with any samples
+        assert_eq!(shared.lags(), 44);
+        assert_eq!(shared.times()[43], 704.0);
+
+        // the correlation follows the cosine of the oscillation
+        let correlation = shared.correlation();
+        let period = shared.times().iter().position(|&t| t == 96.0).unwrap();
+        assert!((correlation[0] - 1.0).abs() < 1e-4);
+        assert!(correlation[12] < -0.9, "{:?}", correlation);
+        assert!(correlation[period] > 0.9, "{:?}", correlation);
+        assert_eq!(even.lags(), 100);
+        assert!(even.correlation()[25] > 0.9);
+
+        // a single peak at 10 THz with unit area
+        let frequencies = shared.frequencies();
+        let density = shared.density_of_states();
+        let peak = (0..density.len())
+            .max_by(|&a, &b| density[a].total_cmp(&density[b]))
+            .unwrap();
+        assert!((frequencies[peak] - 10.0).abs() < 1.3, "{}", frequencies[peak]);
+        let area: Float = density.iter().sum::<Float>() * frequencies[1];
+        assert!((area - 1.0).abs() < 1e-3);
+        assert!(density[0].abs() < 0.05 * density[peak]);
+
+        let mut buffer = Vec::new();
+        shared.write(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap().lines().count(), 45);
+        let mut buffer = Vec::new();
+        shared.write_density_of_states(&mut buffer).unwrap();
+        assert_eq!(String::from_utf8(buffer).unwrap().lines().count(), 178);
+        shared.reset();
+        assert_eq!(vacf.frames(), 0);
+        assert!(vacf.density_of_states().is_empty());
+    }
+}
@@ -0,0 +1,250 @@
+//! Diagnostics of the distribution of atomic velocities.
+
+#[cfg(feature = "f64")]
+use libm::erf;
+
+#[cfg(not(feature = "f64"))]
+use libm::erff as erf;
+
+use nalgebra::Vector3;
+
+use crate::internal::consts::{BOLTZMANN, FRAC_1_SQRT_2};
+use crate::internal::Float;
+use crate::properties::temperature::Temperature;
+use crate::properties::IntrinsicProperty;
+use crate::system::System;
+
+/// Moments of the velocity distribution compared against Maxwell-Boltzmann statistics.
+///
+/// Each velocity component is scaled by `sqrt(m / kT)` at the reference temperature, so at
+/// equilibrium the scaled components follow a standard normal distribution and the kinetic energy
+/// of each atom in units of `kT` follows a gamma distribution with shape 3/2.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct VelocityStatistics {
+    /// Reference temperature in Kelvin.
+    pub temperature: Float,
+    /// Mean of the scaled velocity component along each axis, zero at equilibrium.
+    pub mean: Vector3<Float>,
+    /// Variance of the scaled velocity component along each axis, one at equilibrium.
+    pub variance: Vector3<Float>,
+    /// Skewness of every scaled velocity component, zero at equilibrium.
+    pub skewness: Float,
+    /// Excess kurtosis of every scaled velocity component, zero at equilibrium.
+    pub kurtosis: Float,
+    /// Mean kinetic energy of an atom in units of `kT`, 3/2 at equilibrium.
+    pub kinetic_mean: Float,
+    /// Variance of the kinetic energy of an atom in units of `(kT)^2`, 3/2 at equilibrium.
+    pub kinetic_variance: Float,
+    /// Kolmogorov-Smirnov distance between the scaled velocity components and a standard normal distribution.
+    pub ks_distance: Float,
+}
+
+/// Diagnostics which reveal departures of the velocities from a Maxwell-Boltzmann distribution.
+///
+/// Adding the diagnostics to an output group logs the [`VelocityStatistics`] periodically, so
+/// pathologies of a thermostat such as unequal temperatures along each axis, a drifting center of
+/// mass, or a distorted distribution of kinetic energies are caught while the simulation runs.
+/// Velocities are compared against the instantaneous temperature unless a reference temperature
+/// is set, in which case deviations of the overall temperature are also reported.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let group = RawOutputGroupBuilder::new()
+///     .destination(Vec::new())
+///     .interval(1000)
+///     .output(VelocityDiagnostics::new().temperature(300.0))
+///     .build();
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VelocityDiagnostics {
+    temperature: Option<Float>,
+}
+
+impl VelocityDiagnostics {
+    /// Returns a new [`VelocityDiagnostics`] which compares against the instantaneous temperature.
+    pub fn new() -> VelocityDiagnostics {
+        VelocityDiagnostics { temperature: None }
+    }
+
+    /// Sets the reference temperature of the Maxwell-Boltzmann distribution in Kelvin.
+    pub fn temperature(mut self, temperature: Float) -> VelocityDiagnostics {
+        self.temperature = Some(temperature);
+        self
+    }
+}
+
+impl IntrinsicProperty for VelocityDiagnostics {
+    type Res = VelocityStatistics;
+
+    fn calculate_intrinsic(&self, system: &System) -> <Self as IntrinsicProperty>::Res {
+        let temperature = self
+            .temperature
+            .unwrap_or_else(|| Temperature.calculate_intrinsic(system));
+        if system.size == 0 || temperature <= 0.0 {
+            return VelocityStatistics {
+                temperature,
+                ..VelocityStatistics::default()
+            };
+        }
+        let kt = BOLTZMANN * temperature;
+        let scaled: Vec<Vector3<Float>> = system
+            .species
+            .iter()
+            .zip(system.velocities.iter())
+            .map(|(species, vel)| vel * Float::sqrt(species.mass() / kt))
+            .collect();
+        let n = system.size as Float;
+
+        // moments of the components along each axis and of every component together
+        let mean = scaled.iter().sum::<Vector3<Float>>() / n;
+        let variance = scaled
+            .iter()
+            .map(|u| (u - mean).component_mul(&(u - mean)))
+            .sum::<Vector3<Float>>()
+            / n;
+        let central: Vec<Float> = scaled
+            .iter()
+            .flat_map(|u| (u - mean).iter().copied().collect::<Vec<Float>>())
+            .collect();
+        let moment = |k: i32| central.iter().map(|x| x.powi(k)).sum::<Float>() / central.len() as Float;
+        let (m2, m3, m4) = (moment(2), moment(3), moment(4));
+        let (skewness, kurtosis) = if m2 > 0.0 {
+            (m3 / m2.powf(1.5), m4 / m2.powi(2) - 3.0)
+        } else {
+            (0.0, 0.0)
+        };
+
+        // kinetic energy of each atom in units of kT
+        let kinetic: Vec<Float> = scaled.iter().map(|u| 0.5 * u.norm_squared()).collect();
+        let kinetic_mean = kinetic.iter().sum::<Float>() / n;
+        let kinetic_variance = kinetic.iter().map(|e| (e - kinetic_mean).powi(2)).sum::<Float>() / n;
+
+        // largest distance between the empirical and standard normal cumulative distributions
+        let mut components: Vec<Float> = scaled
+            .iter()
+            .flat_map(|u| u.iter().copied().collect::<Vec<Float>>())
+            .collect();
+        components.sort_by(|a, b| a.total_cmp(b));
+        let count = components.len() as Float;
+        let ks_distance = components
+            .iter()
+            .enumerate()
+            .map(|(i, &x)| {
+                let cdf = 0.5 * (1.0 + erf(x * FRAC_1_SQRT_2));
+                Float::max(cdf - i as Float / count, (i + 1) as Float / count - cdf)
+            })
+            .fold(0.0, Float::max);
+
+        VelocityStatistics {
+            temperature,
+            mean,
+            variance,
+            skewness,
+            kurtosis,
+            kinetic_mean,
+            kinetic_variance,
+            ks_distance,
+        }
+    }
+
+    fn name(&self) -> String {
+        "velocity_statistics".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VelocityDiagnostics;
+    use crate::internal::Float;
+    use crate::properties::IntrinsicProperty;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::velocity_distributions::{Boltzmann, VelocityDistribution};
+    use nalgebra::Vector3;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn gas(size: usize) -> System {
+        let light = Species::new(4.0, 0.0);
+        let heavy = Species::new(40.0, 0.0);
+        System {
+            size,
+            cell: Cell::cubic(50.0),
+            species: (0..size).map(|i| if i % 2 == 0 { light } else { heavy }).collect(),
+            positions: vec![Vector3::zeros(); size],
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn maxwell_boltzmann() {
+        let mut system = gas(3000);
+        Boltzmann::new(300.0).apply(&mut system);
+        let stats = VelocityDiagnostics::new().calculate_intrinsic(&system);
+        assert!((stats.temperature - 300.0).abs() < 1e-2);
+        assert!(stats.mean.norm() < 0.1);
+        assert!(stats.variance.iter().all(|v| (v - 1.0).abs() < 0.1), "{:?}", stats);
+        assert!(stats.skewness.abs() < 0.1 && stats.kurtosis.abs() < 0.2, "{:?}", stats);
+        assert!((stats.kinetic_mean - 1.5).abs() < 1e-2);
+        assert!((stats.kinetic_variance - 1.5).abs() < 0.2, "{:?}", stats);
+        assert!(stats.ks_distance < 0.03, "{:?}", stats);
+
+        // velocities at half of the reference temperature
+        let cold = VelocityDiagnostics::new()
+            .temperature(600.0)
+            .calculate_intrinsic(&system);
+        assert!((cold.kinetic_mean - 0.75).abs() < 1e-2);
+        assert!(cold.ks_distance > 0.05);
+    }
+
+    #[test]
+    fn distorted_distributions() {
+        let mut rng = StdRng::seed_from_u64(23);
+        let mut system = gas(3000);
+        // isotropic directions with the same kinetic energy for every atom
+        for (species, vel) in system.species.iter().zip(system.velocities.iter_mut()) {
+            let direction: Vector3<Float> = loop {
+                let candidate = Vector3::new(
+                    rng.gen_range(-1.0, 1.0),
+                    rng.gen_range(-1.0, 1.0),
+                    rng.gen_range(-1.0, 1.0),
+                );
+                if candidate.norm() > 0.1 && candidate.norm() < 1.0 {
+                    break candidate.normalize();
+                }
+            };
+            *vel = direction * Float::sqrt(0.01 / species.mass());
+        }
+        let stats = VelocityDiagnostics::new().calculate_intrinsic(&system);
+        // components of a fixed speed are uniformly distributed with an excess kurtosis of -6/5
+        assert!((stats.kurtosis + 1.2).abs() < 0.1, "{:?}", stats);
+        assert!(stats.kinetic_variance < 1e-3);
+        assert!(stats.ks_distance > 0.03);
+
+        // all kinetic energy along a single axis
+        for (i, vel) in system.velocities.iter_mut().enumerate() {
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            *vel = Vector3::new(sign * vel.norm(), 0.0, 0.0);
+        }
+        let stats = VelocityDiagnostics::new().calculate_intrinsic(&system);
+        assert!((stats.variance.x - 3.0).abs() < 1e-3 && stats.variance.y == 0.0 && stats.variance.z == 0.0);
+
+        // every atom drifting in the same direction
+        for vel in system.velocities.iter_mut() {
+            vel.x = vel.x.abs();
+        }
+        let stats = VelocityDiagnostics::new().calculate_intrinsic(&system);
+        assert!((stats.mean.x - Float::sqrt(3.0)).abs() < 1e-3);
+
+        // motionless atoms have no distribution to compare
+        let stats = VelocityDiagnostics::new().calculate_intrinsic(&gas(10));
+        assert_eq!(stats.temperature, 0.0);
+        assert_eq!(stats.ks_distance, 0.0);
+    }
+}
@@ -0,0 +1,90 @@
+//! Random number generation shared by every stochastic algorithm.
+
+use std::cell::RefCell;
+
+use rand::rngs::StdRng;
+use rand::{Error, RngCore, SeedableRng};
+
+thread_local! {
+    static GENERATOR: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Reseeds the random number generator of the current thread.
+///
+/// Velocity distributions, stochastic integrators and thermostats, and Monte Carlo moves all
+/// draw from this generator, so two runs of the same input which start from the same seed
+/// produce bit-identical trajectories. The generator is local to each thread and algorithms
+/// which spread their work across threads, such as ensembles of replicas, are only
+/// reproducible when run on a single thread. A [`Simulation`](crate::simulation::Simulation)
+/// reseeds the generator at the start of every run when its
+/// [`Configuration`](crate::config::Configuration) has a seed.
+///
+/// # Examples
+///
+/// ```
+/// use rand::Rng;
+/// use velvet_core::prelude::*;
+///
+/// seed_rng(42);
+/// let first: u64 = shared_rng().gen();
+/// seed_rng(42);
+/// assert_eq!(first, shared_rng().gen::<u64>());
+/// ```
+pub fn seed_rng(seed: u64) {
+    GENERATOR.with(|generator| *generator.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Returns a handle to the random number generator of the current thread.
+///
+/// The generator is seeded from system entropy until [`seed_rng`] is called.
+pub fn shared_rng() -> SharedRng {
+    SharedRng { _private: () }
+}
+
+/// Handle to the random number generator of the current thread returned by [`shared_rng`].
+#[derive(Clone, Copy, Debug)]
+pub struct SharedRng {
+    _private: (),
+}
+
+impl RngCore for SharedRng {
+    fn next_u32(&mut self) -> u32 {
+        GENERATOR.with(|generator| generator.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        GENERATOR.with(|generator| generator.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        GENERATOR.with(|generator| generator.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        GENERATOR.with(|generator| generator.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{seed_rng, shared_rng};
+    use rand::Rng;
+
+    #[test]
+    fn reseeding_repeats_the_stream() {
+        seed_rng(7);
+        let first: Vec<f64> = (0..8).map(|_| shared_rng().gen()).collect();
+        seed_rng(7);
+        let second: Vec<f64> = (0..8).map(|_| shared_rng().gen()).collect();
+        assert_eq!(first, second);
+
+        seed_rng(8);
+        let third: Vec<f64> = (0..8).map(|_| shared_rng().gen()).collect();
+        assert_ne!(first, third);
+
+        // other threads keep their own generators
+        let other = std::thread::spawn(|| shared_rng().gen::<f64>()).join().unwrap();
+        seed_rng(7);
+        assert_ne!(shared_rng().gen::<f64>(), other);
+    }
+}
@@ -0,0 +1,414 @@
+//! Integration of molecules as rigid bodies.
+
+use nalgebra::{Matrix3, Rotation3, Unit, UnitQuaternion, Vector3};
+
+use crate::checkpoint::PropagatorState;
+use crate::errors::VelvetError;
+use crate::integrators::Integrator;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::properties::forces::Forces;
+use crate::properties::Property;
+use crate::system::System;
+
+/// Molecule which moves as a rigid body.
+///
+/// The state of the body is its center of mass and linear velocity, and the quaternion which
+/// rotates its principal axes onto the cartesian axes together with its angular momentum in
+/// the principal frame. Atom positions are fixed in the principal frame, so atoms may be
+/// massless as long as the body has mass, like the virtual charge site of TIP4P water.
+#[derive(Clone, Debug)]
+pub struct RigidBody {
+    atoms: Vec<usize>,
+    mass: Float,
+    moments: Vector3<Float>,
+    body_positions: Vec<Vector3<Float>>,
+    center: Vector3<Float>,
+    velocity: Vector3<Float>,
+    orientation: UnitQuaternion<Float>,
+    angular_momentum: Vector3<Float>,
+}
+
+impl RigidBody {
+    /// Returns a new [`RigidBody`] from the current positions and velocities of `atoms`.
+    ///
+    /// Atoms are unwrapped relative to the first atom with the minimum image convention. The
+    /// linear and angular momenta of the body are those of its atoms, and velocities of the atoms
+    /// along the internal coordinates of the body are discarded once the atoms are first moved.
+    pub fn from_atoms(system: &System, atoms: Vec<usize>) -> RigidBody {
+        let origin = system.positions[atoms[0]];
+        let relative: Vec<Vector3<Float>> = atoms
+            .iter()
+            .map(|&i| {
+                let mut r = system.positions[i] - origin;
+                system.cell.vector_image(&mut r);
+                r
+            })
+            .collect();
+        let masses: Vec<Float> = atoms.iter().map(|&i| system.species[i].mass()).collect();
+        let mass: Float = masses.iter().sum();
+        let offset = relative.iter().zip(masses.iter()).map(|(r, m)| r * *m).sum::<Vector3<Float>>() / mass;
+        let arms: Vec<Vector3<Float>> = relative.iter().map(|r| r - offset).collect();
+        let center = origin + offset;
+
+        let mut inertia = Matrix3::zeros();
+        for (r, m) in arms.iter().zip(masses.iter()) {
+            inertia += (Matrix3::identity() * r.norm_squared() - r * r.transpose()) * *m;
+        }
+        let eigen = inertia.symmetric_eigen();
+        let mut axes = eigen.eigenvectors;
+        // principal axes must form a proper rotation
+        if axes.determinant() < 0.0 {
+            axes.set_column(2, &(-axes.column(2)));
+        }
+        let orientation = UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(axes));
+        let inverse = orientation.inverse();
+
+        let velocity = atoms
+            .iter()
+            .zip(masses.iter())
+            .map(|(&i, m)| system.velocities[i] * *m)
+            .sum::<Vector3<Float>>()
+            / mass;
+        let angular_momentum: Vector3<Float> = atoms
+            .iter()
+            .zip(arms.iter().zip(masses.iter()))
+            .map(|(&i, (r, m))| r.cross(&(system.velocities[i] - velocity)) * *m)
+            .sum();
+
+        RigidBody {
+            body_positions: arms.iter().map(|r| inverse * r).collect(),
+            atoms,
+            mass,
+            moments: eigen.eigenvalues,
+            center,
+            velocity,
+            orientation,
+            angular_momentum: inverse * angular_momentum,
+        }
+    }
+
+    /// Returns the indices of the atoms in the body.
+    pub fn atoms(&self) -> &[usize] {
+        &self.atoms
+    }
+
+    /// Returns the total mass of the body.
+    pub fn mass(&self) -> Float {
+        self.mass
+    }
+
+    /// Returns the principal moments of inertia of the body.
+    pub fn moments(&self) -> Vector3<Float> {
+        self.moments
+    }
+
+    /// Returns the center of mass of the body.
+    pub fn center_of_mass(&self) -> Vector3<Float> {
+        self.center
+    }
+
+    /// Returns the rotation from the principal frame of the body to the cartesian frame.
+    pub fn orientation(&self) -> UnitQuaternion<Float> {
+        self.orientation
+    }
+
+    /// Returns the angular velocity of the body in the cartesian frame.
+    pub fn angular_velocity(&self) -> Vector3<Float> {
+        self.orientation * self.principal_angular_velocity()
+    }
+
+    /// Returns the total force and torque about the center of mass exerted by `forces` on the atoms.
+    pub fn force_and_torque(&self, forces: &[Vector3<Float>]) -> (Vector3<Float>, Vector3<Float>) {
+        self.atoms
+            .iter()
+            .zip(self.body_positions.iter())
+            .fold((Vector3::zeros(), Vector3::zeros()), |(force, torque), (&i, b)| {
+                (force + forces[i], torque + (self.orientation * b).cross(&forces[i]))
+            })
+    }
+
+    // Rotational motion about axes without a moment of inertia, as for linear molecules, is ignored.
+    fn principal_angular_velocity(&self) -> Vector3<Float> {
+        let threshold = 1e-6 * self.moments.max();
+        self.angular_momentum
+            .zip_map(&self.moments, |l, moment| if moment > threshold { l / moment } else { 0.0 })
+    }
+
+    fn kick(&mut self, forces: &[Vector3<Float>], dt: Float) {
+        let (force, torque) = self.force_and_torque(forces);
+        self.velocity += force / self.mass * dt;
+        self.angular_momentum += self.orientation.inverse() * torque * dt;
+    }
+
+    // Free rotation split into exact rotations about single principal axes in the symmetric
+    // sequence of Dullweber, Leimkuhler, and McLachlan.
+    fn drift(&mut self, dt: Float) {
+        self.center += self.velocity * dt;
+        for &(axis, fraction) in &[(0, 0.5), (1, 0.5), (2, 1.0), (1, 0.5), (0, 0.5)] {
+            let omega = self.principal_angular_velocity()[axis];
+            if omega == 0.0 {
+                continue;
+            }
+            let rotation =
+                UnitQuaternion::from_axis_angle(&Unit::new_unchecked(Vector3::ith(axis, 1.0)), omega * fraction * dt);
+            self.orientation *= rotation;
+            self.angular_momentum = rotation.inverse() * self.angular_momentum;
+        }
+        self.orientation.renormalize();
+    }
+
+    // Moves the atoms of the body to match its state.
+    fn place(&self, system: &mut System) {
+        let omega = self.angular_velocity();
+        for (&i, b) in self.atoms.iter().zip(self.body_positions.iter()) {
+            let arm = self.orientation * b;
+            system.positions[i] = self.center + arm;
+            system.velocities[i] = self.velocity + omega.cross(&arm);
+        }
+    }
+}
+
+/// Velocity Verlet integration with molecules of selected types treated as rigid bodies.
+///
+/// Residues whose names are given are integrated as rigid bodies, with the translation of each
+/// body driven by the total force on its atoms and the rotation by the torque about its center
+/// of mass, while all other atoms follow velocity Verlet. Rotations use the symplectic splitting
+/// of the free rigid body into rotations about its principal axes so energy is conserved over
+/// long runs with the timesteps of rigid water models.
+///
+/// Atoms of rigid bodies are placed from the state of their body after each step without
+/// wrapping into the cell. The [`Temperature`](crate::properties::temperature::Temperature)
+/// property assumes three degrees of freedom per atom and so does not account for the degrees
+/// of freedom removed by the rigid bodies.
+///
+/// # References
+///
+/// [1] Dullweber, Andreas, Benedict Leimkuhler, and Robert McLachlan. "Symplectic splitting methods for rigid body molecular dynamics." The Journal of chemical physics 107.15 (1997): 5840-5851.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let integrator = RigidBodies::new(2.0, &["SOL"]);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RigidBodies {
+    timestep: Float,
+    residues: Vec<String>,
+    bodies: Vec<RigidBody>,
+    free: Vec<usize>,
+    forces: Vec<Vector3<Float>>,
+}
+
+impl RigidBodies {
+    /// Returns a new [`RigidBodies`] algorithm.
+    ///
+    /// # Arguments
+    ///
+    /// * `timestep` - Timestep duration.
+    /// * `residues` - Names of the residues which are integrated as rigid bodies.
+    pub fn new(timestep: Float, residues: &[&str]) -> RigidBodies {
+        RigidBodies {
+            timestep,
+            residues: residues.iter().map(|name| name.to_string()).collect(),
+            bodies: Vec::new(),
+            free: Vec::new(),
+            forces: Vec::new(),
+        }
+    }
+
+    /// Returns the rigid bodies found during setup.
+    pub fn bodies(&self) -> &[RigidBody] {
+        &self.bodies
+    }
+
+    fn kick(&mut self, system: &mut System) {
+        let dt = 0.5 * self.timestep;
+        for &i in &self.free {
+            system.velocities[i] += self.forces[i] / system.species[i].mass() * dt;
+        }
+        for body in self.bodies.iter_mut() {
+            body.kick(&self.forces, dt);
+        }
+    }
+}
+
+impl Integrator for RigidBodies {
+    fn setup(&mut self, system: &System, potentials: &Potentials) {
+        self.bodies = system
+            .topology
+            .residues
+            .iter()
+            .filter(|residue| self.residues.contains(&residue.name))
+            .map(|residue| RigidBody::from_atoms(system, residue.atoms.clone()))
+            .collect();
+        let mut rigid = vec![false; system.size];
+        for &i in self.bodies.iter().flat_map(|body| body.atoms.iter()) {
+            rigid[i] = true;
+        }
+        self.free = (0..system.size).filter(|&i| !rigid[i]).collect();
+        self.forces = Forces.calculate(system, potentials);
+    }
+
+    fn integrate(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        let dt = self.timestep;
+        self.kick(system);
+        for &i in &self.free {
+            let velocity = system.velocities[i];
+            system.positions[i] += velocity * dt;
+        }
+        for body in self.bodies.iter_mut() {
+            body.drift(dt);
+            body.place(system);
+        }
+
+        self.forces = Forces.calculate(system, potentials);
+        self.kick(system);
+        for body in &self.bodies {
+            body.place(system);
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> PropagatorState {
+        PropagatorState::from_vectors(&self.forces)
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.forces = state.vectors(self.forces.len())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RigidBodies, RigidBody};
+    use crate::integrators::Integrator;
+    use crate::internal::Float;
+    use crate::potentials::pair::CutoffScheme;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::properties::energy::{KineticEnergy, PotentialEnergy};
+    use crate::properties::{IntrinsicProperty, Property};
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::{Residue, Topology};
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    // Three water molecules and an argon atom with random velocities.
+    fn waters() -> System {
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        let argon = Species::from_element(Element::Ar);
+        let origins = [[5.0, 5.0, 5.0], [8.1, 5.4, 5.2], [6.2, 8.3, 4.7]];
+        let mut positions = Vec::new();
+        let mut species = Vec::new();
+        let mut residues = Vec::new();
+        for (k, o) in origins.iter().enumerate() {
+            let origin = Vector3::new(o[0], o[1], o[2]);
+            positions.extend(vec![
+                origin,
+                origin + Vector3::new(0.9572, 0.0, 0.0),
+                origin + Vector3::new(-0.2400, 0.9266, 0.0),
+            ]);
+            species.extend(vec![oxygen, hydrogen, hydrogen]);
+            residues.push(Residue {
+                name: "SOL".to_string(),
+                atoms: vec![3 * k, 3 * k + 1, 3 * k + 2],
+            });
+        }
+        positions.push(Vector3::new(9.0, 9.0, 8.5));
+        species.push(argon);
+        let velocities = (0..10)
+            .map(|i| {
+                let x = i as Float;
+                Vector3::new(0.01 * (1.3 * x).sin(), 0.01 * (2.1 * x).cos(), 0.01 * (0.7 * x + 0.5).sin())
+            })
+            .collect();
+        System {
+            size: 10,
+            cell: Cell::cubic(15.0),
+            species,
+            positions,
+            velocities,
+            topology: Topology {
+                residues,
+                ..Topology::default()
+            },
+        }
+    }
+
+    fn potentials(system: &System) -> Potentials {
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        let argon = Species::from_element(Element::Ar);
+        let mut potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(0.1553, 3.166), (oxygen, oxygen), 7.0, 1.0)
+            .cutoff_scheme(CutoffScheme::Shifted)
+            .pair(LennardJones::new(0.05, 2.5), (hydrogen, argon), 7.0, 1.0)
+            .cutoff_scheme(CutoffScheme::Shifted)
+            .pair(LennardJones::new(0.2, 3.3), (oxygen, argon), 7.0, 1.0)
+            .cutoff_scheme(CutoffScheme::Shifted)
+            .build();
+        potentials.setup(system);
+        potentials
+    }
+
+    fn energy(system: &System, potentials: &Potentials) -> Float {
+        KineticEnergy.calculate_intrinsic(system) + PotentialEnergy.calculate(system, potentials)
+    }
+
+    #[test]
+    fn free_rotation() {
+        let mut system = waters();
+        let mut integrator = RigidBodies::new(2.0, &["SOL"]);
+        let potentials = PotentialsBuilder::new().build();
+        integrator.setup(&system, &potentials);
+        for body in integrator.bodies() {
+            body.place(&mut system);
+        }
+        let body = &integrator.bodies()[0];
+        assert_relative_eq!(body.mass(), 18.015, epsilon = 1e-2);
+        let momentum = body.orientation() * body.angular_momentum;
+        let kinetic = KineticEnergy.calculate_intrinsic(&system);
+        for _ in 0..500 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+        }
+        let body = &integrator.bodies()[0];
+        assert_relative_eq!(body.orientation() * body.angular_momentum, momentum, epsilon = 1e-4);
+        assert_relative_eq!(KineticEnergy.calculate_intrinsic(&system), kinetic, epsilon = 1e-4);
+        assert_relative_eq!(system.cell.distance(&system.positions[0], &system.positions[1]), 0.9572, epsilon = 1e-4);
+        assert_relative_eq!(system.cell.distance(&system.positions[1], &system.positions[2]), 1.5139, epsilon = 1e-3);
+    }
+
+    #[test]
+    fn conserves_energy() {
+        let mut system = waters();
+        let potentials = potentials(&system);
+        let mut integrator = RigidBodies::new(0.25, &["SOL"]);
+        integrator.setup(&system, &potentials);
+        // velocities along the internal coordinates of the molecules are removed during setup
+        for body in integrator.bodies() {
+            body.place(&mut system);
+        }
+        let initial = energy(&system, &potentials);
+        for _ in 0..4000 {
+            integrator.integrate(&mut system, &potentials).unwrap();
+        }
+        assert_relative_eq!(energy(&system, &potentials), initial, epsilon = 2e-3);
+    }
+
+    #[test]
+    fn linear_molecule() {
+        let mut system = waters();
+        system.positions[2] = system.positions[0] - Vector3::new(1.1, 0.0, 0.0);
+        let body = RigidBody::from_atoms(&system, vec![0, 1, 2]);
+        assert_relative_eq!(body.moments().min(), 0.0, epsilon = 1e-4);
+        assert_relative_eq!(body.angular_velocity()[0], 0.0);
+    }
+}
@@ -5,6 +5,8 @@ use rayon::prelude::*;
 
 use std::marker::PhantomData;
 
+use nalgebra::Vector3;
+
 use crate::internal::Float;
 use crate::system::species::Species;
 use crate::system::System;
@@ -46,11 +48,26 @@ where
         self.current_indices = (self.update_func)(system, &self.possible_indices, args)
     }
 
+    /// Clears the current selection until the next call to `update`.
+    pub fn clear(&mut self) {
+        self.current_indices.clear()
+    }
+
+    /// Returns the set of possible indices found by the last call to `setup`.
+    pub fn possible_indices(&self) -> &[[usize; N]] {
+        &self.possible_indices
+    }
+
     /// Returns an iterator over the selection's current indices.
     pub fn indices(&self) -> impl Iterator<Item = &[usize; N]> {
         self.current_indices.iter()
     }
 
+    /// Returns the number of bytes allocated for the possible and current indices.
+    pub fn allocated_bytes(&self) -> usize {
+        (self.possible_indices.capacity() + self.current_indices.capacity()) * std::mem::size_of::<[usize; N]>()
+    }
+
     /// Returns a parallel iterator over the selection's current indices.
     #[cfg(feature = "rayon")]
     pub fn par_indices(&self) -> impl ParallelIterator<Item = &[usize; N]> {
@@ -58,6 +75,21 @@ where
     }
 }
 
+impl<SFn, SArgs, UFn, UArgs> Selection<SFn, SArgs, UFn, UArgs, 2> {
+    /// Removes the pairs found in the sorted list `excluded` from the possible indices until the
+    /// next call to `setup`, returning the removed pairs in their original order.
+    pub fn exclude(&mut self, excluded: &[[usize; 2]]) -> Vec<[usize; 2]> {
+        if excluded.is_empty() {
+            return Vec::new();
+        }
+        let (removed, kept) = std::mem::take(&mut self.possible_indices)
+            .into_iter()
+            .partition(|&[i, j]| excluded.binary_search(&[i.min(j), i.max(j)]).is_ok());
+        self.possible_indices = kept;
+        removed
+    }
+}
+
 // This function should not be used in the public API but must be exported for integration testing purposes.
 #[doc(hidden)]
 pub fn setup_pairs_by_species(
@@ -80,15 +112,33 @@ pub fn setup_pairs_by_species(
     possible_indices
 }
 
+// This function should not be used in the public API but must be exported for integration testing purposes.
+#[doc(hidden)]
+pub fn setup_pairs_among_species(system: &System, species: Vec<Species>) -> Vec<[usize; 2]> {
+    let mut possible_indices: Vec<[usize; 2]> = Vec::with_capacity(system.size.pow(2));
+    for i in 0..system.size {
+        if !species.contains(&system.species[i]) {
+            continue;
+        }
+        for j in (i + 1)..system.size {
+            if species.contains(&system.species[j]) {
+                possible_indices.push([i, j]);
+            }
+        }
+    }
+    possible_indices.shrink_to_fit();
+    possible_indices
+}
+
 // This function should not be used in the public API but must be exported for integration testing purposes.
 #[doc(hidden)]
 pub fn setup_pairs_with_charge(system: &System, _: ()) -> Vec<[usize; 2]> {
     let mut possible_indices: Vec<[usize; 2]> = Vec::with_capacity(system.size.pow(2));
     for i in 0..system.size {
-        let species_i = system.species[i];
+        let charge_i = system.charge(i);
         for j in (i + 1)..system.size {
-            let species_j = system.species[j];
-            if species_i.charge().abs() > Float::EPSILON || species_j.charge().abs() > Float::EPSILON {
+            let charge_j = system.charge(j);
+            if charge_i.abs() > Float::EPSILON || charge_j.abs() > Float::EPSILON {
                 possible_indices.push([i, j]);
             }
         }
@@ -115,3 +165,101 @@ pub fn update_pairs_by_cutoff_radius(
         .copied()
         .collect()
 }
+
+/// Pair of atoms interacting through a specific periodic image.
+///
+/// Used instead of the minimum image convention when the cutoff exceeds the inscribed radius of
+/// the cell, in which case an atom may interact with several images of another atom or with
+/// images of itself.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ImagePair {
+    /// Index of the first atom.
+    pub i: usize,
+    /// Index of the second atom, which may equal the first.
+    pub j: usize,
+    /// Lattice translation of the second atom in units of the lattice vectors.
+    pub shift: Vector3<Float>,
+}
+
+impl ImagePair {
+    /// Returns the vector from the first atom to the image of the second atom.
+    pub fn separation(&self, system: &System) -> Vector3<Float> {
+        system.positions[self.j] - system.positions[self.i] + system.cell.matrix() * self.shift
+    }
+}
+
+// This function should not be used in the public API but must be exported for integration testing purposes.
+#[doc(hidden)]
+pub fn update_image_pairs(
+    system: &System,
+    indices: &[[usize; 2]],
+    selves: &[usize],
+    cutoff: Float,
+) -> Vec<ImagePair> {
+    let inverse = system.cell.inverse_matrix();
+    // number of lattice spacings spanned by the cutoff along each lattice vector
+    let reach: Vec<Float> = (0..3).map(|k| cutoff * inverse.row(k).norm()).collect();
+    let mut pairs = Vec::new();
+    for &[i, j] in indices {
+        let separation = system.positions[j] - system.positions[i];
+        let fractional = inverse * separation;
+        let range = |k: usize| {
+            let low = Float::ceil(-fractional[k] - reach[k]) as i32;
+            let high = Float::floor(-fractional[k] + reach[k]) as i32;
+            low..=high
+        };
+        for a in range(0) {
+            for b in range(1) {
+                for c in range(2) {
+                    let shift = Vector3::new(a as Float, b as Float, c as Float);
+                    let image = ImagePair { i, j, shift };
+                    if image.separation(system).norm() < cutoff {
+                        pairs.push(image);
+                    }
+                }
+            }
+        }
+    }
+    // each atom interacts with one image of every pair of opposite images of itself
+    let (ra, rb, rc) = (reach[0] as i32, reach[1] as i32, reach[2] as i32);
+    for &i in selves {
+        for a in 0..=ra {
+            for b in -rb..=rb {
+                for c in -rc..=rc {
+                    if (a, b, c) <= (0, 0, 0) {
+                        continue;
+                    }
+                    let shift = Vector3::new(a as Float, b as Float, c as Float);
+                    let image = ImagePair { i, j: i, shift };
+                    if image.separation(system).norm() < cutoff {
+                        pairs.push(image);
+                    }
+                }
+            }
+        }
+    }
+    pairs
+}
+
+// This function should not be used in the public API but must be exported for integration testing purposes.
+#[doc(hidden)]
+pub fn update_excluded_image_pairs(system: &System, excluded: &[[usize; 2]], cutoff: Float) -> Vec<ImagePair> {
+    // only the nearest image of an excluded pair is excluded
+    let mut pairs = Vec::new();
+    for &pair in excluded {
+        let mut images = update_image_pairs(system, &[pair], &[], cutoff);
+        let nearest = images
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                let (a, b) = (a.separation(system).norm(), b.separation(system).norm());
+                a.total_cmp(&b)
+            })
+            .map(|(index, _)| index);
+        if let Some(index) = nearest {
+            images.swap_remove(index);
+        }
+        pairs.extend(images);
+    }
+    pairs
+}
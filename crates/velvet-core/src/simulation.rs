@@ -3,21 +3,63 @@
 #[cfg(feature = "quiet")]
 use indicatif::ProgressDrawTarget;
 use indicatif::{ProgressBar, ProgressStyle};
+use std::fmt;
+use std::mem;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use crate::checkpoint::{Checkpoint, PropagatorState};
 use crate::config::Configuration;
+use crate::convergence::ConvergenceMonitor;
+use crate::coupling::{ExchangeBuffers, ExternalForces};
+use crate::errors::VelvetError;
+use crate::hooks::Hook;
+use crate::internal::Float;
 use crate::potentials::Potentials;
 use crate::propagators::Propagator;
+use crate::properties::energy::{KineticEnergy, PotentialEnergy};
+use crate::properties::forces::Forces;
+use crate::properties::pressure::Pressure;
+use crate::properties::temperature::Temperature;
+use crate::properties::Property;
+use crate::random::seed_rng;
+use crate::system::species::Species;
 use crate::system::System;
+use crate::trajectory::Trajectory;
+use nalgebra::Vector3;
 
 /// High level abstraction for an atomistic simulation.
+///
+/// Consecutive calls to [`run`](Simulation::run) continue from the current state of the
+/// system and may be used as the stages of a protocol. The potentials can be swapped or
+/// reparameterized between stages through [`potentials_mut`](Simulation::potentials_mut).
+///
+/// The simulation can be saved to a [`Checkpoint`] with
+/// [`save_checkpoint`](Simulation::save_checkpoint), either explicitly or periodically through
+/// the [`Configuration`], and resumed with [`from_checkpoint`](Simulation::from_checkpoint).
+///
+/// Embedding applications can poll the progress of a run through a [`StateHandle`] returned by
+/// [`state_handle`](Simulation::state_handle), which holds the current step along with the most
+/// recent values of the properties added with [`watch`](Simulation::watch).
+///
+/// Snapshots of the system can be kept in memory for analysis after a run by setting a
+/// [`Trajectory`] with [`record_trajectory`](Simulation::record_trajectory).
 pub struct Simulation {
     system: System,
     potentials: Potentials,
     propagator: Box<dyn Propagator>,
     config: Configuration,
+    step: usize,
+    restored: Option<PropagatorState>,
+    hooks: Vec<Box<dyn Hook>>,
+    watched: Vec<Box<dyn Property<Res = Float> + Send + Sync>>,
+    watch_interval: usize,
+    state: StateHandle,
+    trajectory: Option<Trajectory>,
 }
 
-impl<'a> Simulation {
+impl Simulation {
     /// Returns a new [`Simulation`].
     pub fn new<P>(
         system: System,
@@ -33,16 +75,287 @@ impl<'a> Simulation {
             potentials,
             propagator: Box::new(propagator),
             config,
+            step: 0,
+            restored: None,
+            hooks: Vec::new(),
+            watched: Vec::new(),
+            watch_interval: 1,
+            state: StateHandle::default(),
+            trajectory: None,
         }
     }
 
+    /// Returns a [`Simulation`] which resumes from the checkpoint at `path`.
+    ///
+    /// The potentials and propagator must be constructed the same way as in the interrupted
+    /// simulation. The system and step counter are read from the checkpoint and the internal
+    /// state of the propagator is restored at the start of the next call to [`run`](Simulation::run),
+    /// which returns an error if the state does not belong to the propagator. The potentials start
+    /// from their initial state, so any internal state of their biases is not resumed.
+    pub fn from_checkpoint<P, T>(
+        path: P,
+        potentials: Potentials,
+        propagator: T,
+        config: Configuration,
+    ) -> Result<Simulation, VelvetError>
+    where
+        P: AsRef<Path>,
+        T: Propagator + 'static,
+    {
+        let checkpoint = Checkpoint::read_file(path)?;
+        let mut simulation = Simulation::new(checkpoint.system, potentials, propagator, config);
+        simulation.step = checkpoint.step;
+        simulation.restored = Some(checkpoint.state);
+        Ok(simulation)
+    }
+
+    /// Writes the system, step counter, and propagator state to a checkpoint at `path`.
+    pub fn save_checkpoint<P: AsRef<Path>>(&self, path: P) -> Result<(), VelvetError> {
+        let checkpoint = Checkpoint {
+            system: self.system.clone(),
+            step: self.step,
+            state: self.propagator.state(),
+        };
+        checkpoint.write_file(path)
+    }
+
+    /// Adds a [`Hook`] which runs before and after every step of subsequent runs.
+    pub fn add_hook<H: Hook + 'static>(&mut self, hook: H) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Returns the total number of steps completed by every call to [`run`](Simulation::run).
+    pub fn step(&self) -> usize {
+        self.step
+    }
+
+    /// Adds a scalar property whose most recent value is kept in the [`SimulationState`].
+    ///
+    /// Watched properties are evaluated at the start of each run, every
+    /// [`watch_interval`](Simulation::set_watch_interval) steps, and on the last step of a run.
+    pub fn watch<T>(&mut self, property: T)
+    where
+        T: Property<Res = Float> + Send + Sync + 'static,
+    {
+        self.watched.push(Box::new(property));
+    }
+
+    /// Sets the number of steps between evaluations of the watched properties.
+    pub fn set_watch_interval(&mut self, interval: usize) {
+        self.watch_interval = interval.max(1);
+    }
+
+    /// Records frames of the system into `trajectory` during subsequent runs.
+    ///
+    /// Replaces and returns the trajectory which was being recorded, if any.
+    pub fn record_trajectory(&mut self, trajectory: Trajectory) -> Option<Trajectory> {
+        self.trajectory.replace(trajectory)
+    }
+
+    /// Returns the trajectory being recorded.
+    pub fn trajectory(&self) -> Option<&Trajectory> {
+        self.trajectory.as_ref()
+    }
+
+    /// Stops recording and returns the recorded trajectory.
+    pub fn take_trajectory(&mut self) -> Option<Trajectory> {
+        self.trajectory.take()
+    }
+
+    /// Returns a copy of the current [`SimulationState`].
+    pub fn state(&self) -> SimulationState {
+        self.state.get()
+    }
+
+    /// Returns a handle to the state of the simulation which other threads can poll during a run.
+    pub fn state_handle(&self) -> StateHandle {
+        self.state.clone()
+    }
+
     /// Runs the full iteration loop of the simulation.
-    pub fn run(&mut self, steps: usize) {
+    ///
+    /// The run ends after `steps` steps, as soon as the propagator reports it has converged,
+    /// once the properties of the [`ConvergenceMonitor`](crate::convergence::ConvergenceMonitor)
+    /// in the configuration have converged, or as soon as a [`Hook`] requests it to stop.
+    ///
+    /// Returns an error without running any steps if the system fails
+    /// [`validate`](System::validate), or as soon as a periodic checkpoint cannot be written.
+    pub fn run(&mut self, steps: usize) -> Result<(), VelvetError> {
+        let pb = self.start(steps)?;
+        for i in 0..steps {
+            let last = self.advance(i, steps)?;
+            pb.inc(1);
+            if last {
+                break;
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    /// Checks the simulation without running any dynamics.
+    ///
+    /// The potentials and propagator are set up, the neighbor lists are built, and the forces
+    /// are evaluated once, which is enough to find most mistakes in a configuration before a long
+    /// job is submitted. The propagator is set up on a copy of the system so the simulation is
+    /// left unchanged and a following call to [`run`](Simulation::run) starts from the same state.
+    /// Errors from setting up the propagator are returned.
+    pub fn dry_run(&mut self) -> Result<DryRun, VelvetError> {
+        let clock = Instant::now();
+        self.potentials.setup(&self.system);
+        let potentials_setup = clock.elapsed();
+
+        let mut system = self.system.clone();
+        let clock = Instant::now();
+        self.propagator.setup(&mut system, &self.potentials)?;
+        let propagator_setup = clock.elapsed();
+
+        let clock = Instant::now();
+        let forces = Forces.calculate(&self.system, &self.potentials);
+        let force_evaluation = clock.elapsed();
+
+        let (neighbor_pairs, neighbor_bytes) = self.potentials.neighbor_lists();
+        // positions, velocities, species, and the forces of a step
+        let atom_bytes = 3 * mem::size_of::<Vector3<Float>>() + mem::size_of::<Species>();
+        let potential_energy = PotentialEnergy.calculate(&self.system, &self.potentials);
+        let max_force = forces.iter().map(|force| force.norm()).fold(0.0, Float::max);
+
+        let mut warnings: Vec<String> = self
+            .potentials
+            .coverage(&self.system)
+            .to_string()
+            .lines()
+            .map(|line| line.trim_start_matches("warning: ").to_string())
+            .collect();
+        let nonfinite = forces.iter().filter(|force| !force.iter().all(|x| x.is_finite())).count();
+        if nonfinite > 0 {
+            warnings.push(format!("{} atoms have non-finite forces", nonfinite));
+        }
+        if !potential_energy.is_finite() {
+            warnings.push("the potential energy is not finite".to_string());
+        }
+
+        Ok(DryRun {
+            atoms: self.system.size,
+            potentials_setup,
+            propagator_setup,
+            force_evaluation,
+            neighbor_pairs,
+            system_bytes: self.system.size * atom_bytes,
+            neighbor_bytes,
+            potential_energy,
+            kinetic_energy: KineticEnergy.calculate(&self.system, &self.potentials),
+            temperature: Temperature.calculate(&self.system, &self.potentials),
+            pressure: Pressure.calculate(&self.system, &self.potentials),
+            max_force,
+            warnings,
+        })
+    }
+
+    /// Runs the simulation coupled to an external solver which exchanges data every `interval` steps.
+    ///
+    /// The simulation yields to `exchange` before the first step, after every block of `interval`
+    /// steps, and once the run ends. Each call receives the current state of the atoms in the
+    /// [`ExchangeBuffers`] along with the wall clock time spent on the previous block, which allows
+    /// the coupled solver to balance its own time budget. The external forces written to the buffers
+    /// act on the atoms over the following block of steps.
+    ///
+    /// # Arguments
+    ///
+    /// * `steps` - Largest number of steps to run, as for [`run`](Simulation::run).
+    /// * `interval` - Number of steps between exchanges.
+    /// * `exchange` - Callback of the coupled solver.
+    pub fn run_coupled<F>(&mut self, steps: usize, interval: usize, mut exchange: F) -> Result<(), VelvetError>
+    where
+        F: FnMut(&mut ExchangeBuffers),
+    {
+        self.system.validate()?;
+        let interval = interval.max(1);
+        let external = ExternalForces::default();
+        self.potentials.add_bias(external.clone());
+        let result = self.couple(steps, interval, &external, &mut exchange);
+        // the external forces were the last bias added
+        self.potentials.biases.pop();
+        self.potentials.invalidate_biases();
+        result
+    }
+
+    // Runs the steps of a coupled run with the external forces already added as a bias.
+    fn couple<F>(&mut self, steps: usize, interval: usize, external: &ExternalForces, exchange: &mut F) -> Result<(), VelvetError>
+    where
+        F: FnMut(&mut ExchangeBuffers),
+    {
+        let mut buffers = ExchangeBuffers::default();
+        // the initial external forces are known before the propagator is set up
+        self.potentials.setup(&self.system);
+        self.exchange(external, &mut buffers, exchange);
+
+        let pb = self.start(steps)?;
+        let mut clock = Instant::now();
+        for i in 0..steps {
+            let last = self.advance(i, steps)?;
+            buffers.steps += 1;
+            pb.inc(1);
+            if last || buffers.steps == interval {
+                buffers.elapsed = clock.elapsed();
+                self.exchange(external, &mut buffers, exchange);
+                buffers.steps = 0;
+                clock = Instant::now();
+            }
+            if last {
+                break;
+            }
+        }
+        pb.finish();
+        Ok(())
+    }
+
+    // Fills the exchange buffers, yields to the coupled solver, and applies its external forces.
+    fn exchange<F>(&self, external: &ExternalForces, buffers: &mut ExchangeBuffers, exchange: &mut F)
+    where
+        F: FnMut(&mut ExchangeBuffers),
+    {
+        let current = external.forces(&self.system);
+        buffers.step = self.step;
+        buffers.positions = self.system.positions.clone();
+        buffers.velocities = self.system.velocities.clone();
+        buffers.forces = Forces
+            .calculate(&self.system, &self.potentials)
+            .iter()
+            .zip(current.iter())
+            .map(|(force, external)| force - external)
+            .collect();
+        buffers.external = current;
+        exchange(buffers);
+        external.set(&self.system, &buffers.external);
+        self.potentials.invalidate_biases();
+    }
+
+    // Prepares the potentials and propagator and returns the progress bar of a run.
+    fn start(&mut self, steps: usize) -> Result<ProgressBar, VelvetError> {
+        self.system.validate()?;
+        if let Some(seed) = self.config.seed() {
+            seed_rng(seed.wrapping_add(self.step as u64));
+        }
+
         // setup potentials
         self.potentials.setup(&self.system);
 
         // setup propagation
-        self.propagator.setup(&mut self.system, &self.potentials);
+        self.propagator.setup(&mut self.system, &self.potentials)?;
+        if let Some(state) = self.restored.take() {
+            self.propagator.restore(&state)?;
+        }
+        for hook in self.hooks.iter_mut() {
+            hook.setup(&self.system, &self.potentials);
+            if let Some(err) = hook.take_error() {
+                return Err(err);
+            }
+        }
+        self.record(steps > 0);
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.record(self.step, &self.system);
+        }
 
         // setup progress bar
         let pb = ProgressBar::new(steps as u64);
@@ -54,42 +367,129 @@ impl<'a> Simulation {
         #[cfg(feature = "quiet")]
         pb.set_draw_target(ProgressDrawTarget::hidden());
 
-        // start iteration loop
-        for i in 0..steps {
-            // do one propagation step
-            self.propagator
-                .propagate(&mut self.system, &self.potentials);
-
-            // update the potentials
-            self.potentials.update(&self.system, i);
-
-            // raw outputs
-            for group in self.config.raw_output_groups() {
-                let should_output = i % group.interval == 0 || i == steps - 1;
-                let destination = group.destination.as_mut();
-                for output in group.outputs.iter() {
-                    if should_output {
-                        output.output_raw(&self.system, &self.potentials, destination)
-                    }
+        Ok(pb)
+    }
+
+    // Advances the simulation by step `i` of a run and returns whether it was the last step.
+    //
+    // Neighbor list updates and output intervals follow the total step count, so a simulation
+    // resumed from a checkpoint or run in stages keeps the schedule of a single run.
+    fn advance(&mut self, i: usize, steps: usize) -> Result<bool, VelvetError> {
+        let step = self.step;
+        for hook in self.hooks.iter_mut() {
+            hook.pre_step(self.step, &mut self.system, &self.potentials);
+            if let Some(err) = hook.take_error() {
+                return Err(err);
+            }
+        }
+
+        // do one propagation step
+        self.propagator
+            .propagate(&mut self.system, &self.potentials);
+        if let Some(err) = self.propagator.take_error() {
+            return Err(err);
+        }
+
+        // update the potentials
+        self.potentials.update(&self.system, step);
+        self.step += 1;
+
+        for hook in self.hooks.iter_mut() {
+            hook.post_step(self.step, &mut self.system, &self.potentials);
+            if let Some(err) = hook.take_error() {
+                return Err(err);
+            }
+        }
+        let stopped = self.hooks.iter().any(|hook| hook.stop());
+        if let Some(trajectory) = &mut self.trajectory {
+            trajectory.record_if_due(self.step, &self.system);
+        }
+
+        // convergence monitoring
+        if let Some(monitor) = self.config.convergence_mut() {
+            if step.is_multiple_of(monitor.interval()) {
+                monitor.observe(&self.system, &self.potentials);
+            }
+        }
+        let converged = self.config.convergence().is_some_and(|monitor| monitor.converged());
+        let last = i == steps - 1 || self.propagator.converged() || converged || stopped;
+        if last || self.step.is_multiple_of(self.watch_interval) {
+            self.record(!last);
+        } else {
+            self.state.0.lock().unwrap().step = self.step;
+        }
+
+        // raw outputs
+        for group in self.config.raw_output_groups() {
+            let should_output = step.is_multiple_of(group.interval) || last;
+            let destination = group.destination.as_mut();
+            for output in group.outputs.iter() {
+                if should_output {
+                    output.output_raw(&self.system, &self.potentials, destination)
                 }
             }
+        }
 
-            // HDF5 outputs
-            #[cfg(feature = "hdf5-output")]
-            {
-                for group in self.config.hdf5_output_groups() {
-                    let should_output = i % group.interval == 0 || i == steps - 1;
-                    let g = group.file_handle.create_group(&format!("{}", i)).unwrap();
-                    for output in group.outputs.iter() {
-                        if should_output {
-                            output.output_hdf5(&self.system, &self.potentials, &g)
-                        }
-                    }
+        // HDF5 outputs
+        #[cfg(feature = "hdf5-output")]
+        {
+            for group in self.config.hdf5_output_groups() {
+                if step.is_multiple_of(group.interval()) || last {
+                    group.write_frame(self.step, &self.system, &self.potentials)?;
                 }
             }
-            pb.inc(1);
         }
-        pb.finish();
+        // periodic checkpoints
+        if let Some((path, interval)) = self.config.checkpoint() {
+            if self.step.is_multiple_of(interval) {
+                self.save_checkpoint(path)?;
+            }
+        }
+        Ok(last)
+    }
+
+    // Evaluates the watched properties and publishes them to the state handle.
+    fn record(&self, running: bool) {
+        let values = self
+            .watched
+            .iter()
+            .map(|property| (property.name(), property.calculate(&self.system, &self.potentials)))
+            .collect();
+        *self.state.0.lock().unwrap() = SimulationState {
+            step: self.step,
+            running,
+            values,
+        };
+    }
+
+    /// Returns a reference to the simulated system.
+    pub fn system(&self) -> &System {
+        &self.system
+    }
+
+    /// Returns a reference to the potentials applied to the system.
+    pub fn potentials(&self) -> &Potentials {
+        &self.potentials
+    }
+
+    /// Returns the convergence monitor of the current configuration if one is set.
+    pub fn convergence(&self) -> Option<&ConvergenceMonitor> {
+        self.config.convergence()
+    }
+
+    /// Replaces the configuration used by subsequent calls to [`run`](Simulation::run) and returns the previous one.
+    ///
+    /// This allows each stage of a protocol to use its own outputs and intervals, e.g. no
+    /// outputs during equilibration followed by dense outputs during production.
+    pub fn set_configuration(&mut self, config: Configuration) -> Configuration {
+        std::mem::replace(&mut self.config, config)
+    }
+
+    /// Returns a mutable reference to the potentials applied to the system.
+    ///
+    /// Changes take effect at the start of the next call to [`run`](Simulation::run).
+    pub fn potentials_mut(&mut self) -> &mut Potentials {
+        &mut self.potentials
     }
 
     /// Consume the simulation and return its [`System`] and [`Potentials`].
@@ -97,3 +497,492 @@ impl<'a> Simulation {
         (self.system, self.potentials)
     }
 }
+
+/// Progress of a [`Simulation`] and the most recent values of its watched properties.
+#[derive(Clone, Debug, Default)]
+pub struct SimulationState {
+    /// Total number of steps completed.
+    pub step: usize,
+    /// Whether a run is in progress.
+    pub running: bool,
+    /// Name and most recent value of each watched property.
+    pub values: Vec<(String, Float)>,
+}
+
+impl SimulationState {
+    /// Returns the most recent value of the watched property named `name`.
+    pub fn value(&self, name: &str) -> Option<Float> {
+        self.values
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Shared handle to the [`SimulationState`] of a running simulation.
+///
+/// The handle can be cloned and sent to other threads, such as the event loop of a GUI, which
+/// poll it while the simulation runs.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let system = System {
+///     size: 1,
+///     cell: Cell::cubic(10.0),
+///     species: vec![argon],
+///     positions: vec![nalgebra::Vector3::zeros()],
+///     velocities: vec![nalgebra::Vector3::new(0.01, 0.0, 0.0)],
+///     topology: Topology::default(),
+/// };
+///
+/// // run the simulation on a worker thread which hands back its state handle
+/// let (sender, receiver) = std::sync::mpsc::channel();
+/// let worker = std::thread::spawn(move || {
+///     let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+///     let config = ConfigurationBuilder::new().build();
+///     let mut simulation = Simulation::new(system, PotentialsBuilder::new().build(), md, config);
+///     simulation.watch(KineticEnergy);
+///     sender.send(simulation.state_handle()).unwrap();
+///     simulation.run(100).unwrap();
+/// });
+///
+/// // poll the handle while the simulation runs
+/// let handle = receiver.recv().unwrap();
+/// let _ = handle.get().value("kinetic_energy");
+/// worker.join().unwrap();
+/// assert_eq!(handle.get().step, 100);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct StateHandle(Arc<Mutex<SimulationState>>);
+
+impl StateHandle {
+    /// Returns a copy of the current state.
+    pub fn get(&self) -> SimulationState {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+/// Report of a [`Simulation::dry_run`].
+#[derive(Clone, Debug)]
+pub struct DryRun {
+    /// Number of atoms in the system.
+    pub atoms: usize,
+    /// Wall clock time spent setting up the potentials and building the neighbor lists.
+    pub potentials_setup: Duration,
+    /// Wall clock time spent setting up the propagator.
+    pub propagator_setup: Duration,
+    /// Wall clock time of a single force evaluation, which dominates the cost of a step.
+    pub force_evaluation: Duration,
+    /// Number of pairs in the neighbor lists of every potential.
+    pub neighbor_pairs: usize,
+    /// Estimated bytes held by the atoms of the system during a run.
+    pub system_bytes: usize,
+    /// Bytes allocated for the neighbor lists of every potential.
+    pub neighbor_bytes: usize,
+    /// Initial potential energy of the system.
+    pub potential_energy: Float,
+    /// Initial kinetic energy of the system.
+    pub kinetic_energy: Float,
+    /// Initial temperature of the system.
+    pub temperature: Float,
+    /// Initial pressure of the system.
+    pub pressure: Float,
+    /// Largest magnitude of the initial force on any atom.
+    pub max_force: Float,
+    /// Problems found with the configuration, such as species pairs which do not interact.
+    pub warnings: Vec<String>,
+}
+
+impl fmt::Display for DryRun {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+        writeln!(f, "atoms:              {}", self.atoms)?;
+        writeln!(f, "potentials setup:   {:?}", self.potentials_setup)?;
+        writeln!(f, "propagator setup:   {:?}", self.propagator_setup)?;
+        writeln!(f, "force evaluation:   {:?}", self.force_evaluation)?;
+        writeln!(f, "neighbor pairs:     {}", self.neighbor_pairs)?;
+        writeln!(f, "system memory:      {:.3} MiB", mib(self.system_bytes))?;
+        writeln!(f, "neighbor memory:    {:.3} MiB", mib(self.neighbor_bytes))?;
+        writeln!(f, "potential energy:   {}", self.potential_energy)?;
+        writeln!(f, "kinetic energy:     {}", self.kinetic_energy)?;
+        writeln!(f, "temperature:        {}", self.temperature)?;
+        writeln!(f, "pressure:           {}", self.pressure)?;
+        writeln!(f, "largest force:      {}", self.max_force)?;
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use nalgebra::Vector3;
+
+    use super::Simulation;
+    use crate::config::ConfigurationBuilder;
+    use crate::convergence::ConvergenceMonitor;
+    use crate::errors::VelvetError;
+    use crate::hooks::Hook;
+    use crate::integrators::{Langevin, VelocityVerlet};
+    use crate::internal::Float;
+    use crate::outputs::raw::RawOutputGroupBuilder;
+    use crate::potentials::types::LennardJones;
+    use crate::potentials::{Potentials, PotentialsBuilder};
+    use crate::propagators::MolecularDynamics;
+    use crate::properties::energy::PotentialEnergy;
+    use crate::properties::Property;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::barostats::ParrinelloRahman;
+    use crate::config::Configuration;
+    use crate::thermostats::{Andersen, NoseHoover, NullThermostat};
+    use crate::trajectory::Trajectory;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn argon_simulation() -> Simulation {
+        let argon = Species::from_element(Element::Ar);
+        let system = System {
+            size: 2,
+            cell: Cell::cubic(20.0),
+            species: vec![argon, argon],
+            positions: vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new(9.0, 5.0, 5.0)],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        };
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(4.184, 3.4), (argon, argon), 8.5, 1.0)
+            .build();
+        let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+        Simulation::new(system, potentials, md, ConfigurationBuilder::new().build())
+    }
+
+    #[test]
+    fn per_stage_configuration() {
+        let mut sim = argon_simulation();
+
+        // equilibration stage without outputs
+        sim.run(10).unwrap();
+
+        // production stage with dense outputs
+        let buffer = SharedBuffer::default();
+        let group = RawOutputGroupBuilder::new()
+            .destination(buffer.clone())
+            .interval(2)
+            .output(PotentialEnergy)
+            .build();
+        sim.set_configuration(ConfigurationBuilder::new().raw_output_group(group).build());
+        sim.run(10).unwrap();
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(text.lines().count(), 6);
+    }
+
+    #[test]
+    fn recorded_trajectory() {
+        let mut sim = argon_simulation();
+        sim.record_trajectory(Trajectory::new(4).velocities(true));
+        sim.run(10).unwrap();
+        sim.run(6).unwrap();
+
+        let trajectory = sim.take_trajectory().unwrap();
+        assert!(sim.trajectory().is_none());
+        // the start of the second run is recorded once more
+        assert_eq!(trajectory.steps(), vec![0, 4, 8, 10, 12, 16]);
+        let last = trajectory.last().unwrap();
+        assert_eq!(last.positions.as_ref().unwrap(), &sim.system().positions);
+        assert_eq!(last.velocities.as_ref().unwrap(), &sim.system().velocities);
+    }
+
+    #[test]
+    fn seeded_runs() {
+        let trajectory = |seed: u64| {
+            let argon = Species::from_element(Element::Ar);
+            let system = System {
+                size: 2,
+                cell: Cell::cubic(20.0),
+                species: vec![argon, argon],
+                positions: vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new(9.0, 5.0, 5.0)],
+                velocities: vec![Vector3::zeros(); 2],
+                topology: Topology::default(),
+            };
+            let potentials = PotentialsBuilder::new()
+                .pair(LennardJones::new(4.184, 3.4), (argon, argon), 8.5, 1.0)
+                .build();
+            // both the integrator and the thermostat draw random numbers on every step
+            let md = MolecularDynamics::new(Langevin::new(1.0, 300.0, 0.01), Andersen::new(300.0, 0.1, 1.0));
+            let config = ConfigurationBuilder::new().seed(seed).build();
+            let mut sim = Simulation::new(system, potentials, md, config);
+            sim.run(10).unwrap();
+            let first = sim.system().positions.clone();
+            sim.run(10).unwrap();
+            (first, sim.system().positions.clone())
+        };
+
+        let (first, second) = trajectory(3);
+        assert_eq!(trajectory(3), (first.clone(), second.clone()));
+        assert_ne!(trajectory(4).0, first);
+    }
+
+    #[test]
+    fn coupled_run() {
+        let mut sim = argon_simulation();
+        let mut exchanges = Vec::new();
+        sim.run_coupled(10, 4, |buffers| {
+            if buffers.step == 0 {
+                assert!(buffers.forces[0].x > 0.0);
+                assert_eq!(buffers.external, vec![Vector3::zeros(); 2]);
+                // the same external force on both atoms
+                buffers.external = vec![Vector3::new(0.01, 0.0, 0.0); 2];
+            } else {
+                assert!(buffers.elapsed > Duration::default());
+                assert_eq!(buffers.external[0], Vector3::new(0.01, 0.0, 0.0));
+            }
+            // internal forces between the atoms cancel
+            let total: Vector3<Float> = buffers.forces.iter().sum();
+            assert!(total.norm() < 1e-5);
+            exchanges.push((buffers.step, buffers.steps));
+        })
+        .unwrap();
+        assert_eq!(exchanges, vec![(0, 0), (4, 4), (8, 4), (10, 2)]);
+        assert_eq!(sim.step(), 10);
+        assert!(sim.potentials().biases.is_empty());
+
+        // the external forces change the total momentum over ten steps of 1 fs
+        let momentum: Vector3<Float> = sim
+            .system()
+            .species
+            .iter()
+            .zip(sim.system().velocities.iter())
+            .map(|(species, vel)| species.mass() * vel)
+            .sum();
+        assert!((momentum - Vector3::new(0.2, 0.0, 0.0)).norm() < 1e-4, "{}", momentum);
+    }
+
+    fn npt_propagator() -> MolecularDynamics {
+        MolecularDynamics::new(VelocityVerlet::new(1.0), NoseHoover::new(100.0, 0.1, 1.0))
+            .with_barostat(ParrinelloRahman::new(1.0, 4.5e-5, 100.0, 1.0))
+    }
+
+    fn npt_simulation(config: Configuration) -> Simulation {
+        let sim = argon_simulation();
+        let (mut system, potentials) = sim.consume();
+        system.velocities = vec![Vector3::new(0.01, 0.002, 0.0), Vector3::new(-0.01, 0.0, 0.003)];
+        Simulation::new(system, potentials, npt_propagator(), config)
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let path = std::env::temp_dir().join(format!("velvet-resume-{}.chk", std::process::id()));
+
+        // uninterrupted reference run
+        let mut reference = npt_simulation(ConfigurationBuilder::new().build());
+        reference.run(20).unwrap();
+
+        // interrupted run which checkpoints periodically
+        let config = ConfigurationBuilder::new().checkpoint(&path, 5).build();
+        let mut interrupted = npt_simulation(config);
+        interrupted.run(12).unwrap();
+        assert_eq!(interrupted.step(), 12);
+
+        // resuming from the last checkpoint repeats the steps since it was written
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(4.184, 3.4), (reference.system().species[0], reference.system().species[0]), 8.5, 1.0)
+            .build();
+        let mut resumed = Simulation::from_checkpoint(
+            &path,
+            potentials,
+            npt_propagator(),
+            ConfigurationBuilder::new().build(),
+        )
+        .unwrap();
+        assert_eq!(resumed.step(), 10);
+        resumed.run(10).unwrap();
+
+        // the state of the propagator can not be restored into one without a barostat
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(4.184, 3.4), (reference.system().species[0], reference.system().species[0]), 8.5, 1.0)
+            .build();
+        let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NoseHoover::new(100.0, 0.1, 1.0));
+        let mut mismatched = Simulation::from_checkpoint(&path, potentials, md, ConfigurationBuilder::new().build()).unwrap();
+        assert!(matches!(mismatched.run(1), Err(VelvetError::Parse { .. })));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(resumed.step(), 20);
+        assert_eq!(resumed.system().cell.matrix(), reference.system().cell.matrix());
+        assert_eq!(resumed.system().positions, reference.system().positions);
+        assert_eq!(resumed.system().velocities, reference.system().velocities);
+    }
+
+    #[test]
+    fn converged_run() {
+        let (mut system, potentials) = argon_simulation().consume();
+        // the potential energy of two atoms at rest in the minimum is constant
+        system.positions[1].x = system.positions[0].x + Float::powf(2.0, 1.0 / 6.0) * 3.4;
+        let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+        let mut sim = Simulation::new(system, potentials, md, ConfigurationBuilder::new().build());
+        let monitor = ConvergenceMonitor::new(2).property(PotentialEnergy, 1e-3).min_samples(5);
+        sim.set_configuration(ConfigurationBuilder::new().convergence(monitor).build());
+        sim.run(100).unwrap();
+        assert!(sim.step() < 100);
+        let estimate = &sim.convergence().unwrap().estimates()[0];
+        assert!(estimate.converged());
+        assert_eq!(estimate.samples, 5);
+    }
+
+    // records the steps it sees and ends the run after `limit` steps
+    struct Recorder {
+        steps: Arc<Mutex<Vec<(usize, usize)>>>,
+        limit: usize,
+        done: bool,
+    }
+
+    impl Hook for Recorder {
+        fn pre_step(&mut self, step: usize, system: &mut System, _: &Potentials) {
+            // a small impulse on the first atom before every step
+            system.velocities[0].y += 0.001;
+            self.steps.lock().unwrap().push((step, 0));
+        }
+
+        fn post_step(&mut self, step: usize, _: &mut System, _: &Potentials) {
+            self.steps.lock().unwrap().push((step, 1));
+            self.done = step >= self.limit;
+        }
+
+        fn stop(&self) -> bool {
+            self.done
+        }
+    }
+
+    #[test]
+    fn hooks() {
+        let mut sim = argon_simulation();
+        let steps = Arc::new(Mutex::new(Vec::new()));
+        sim.add_hook(Recorder {
+            steps: steps.clone(),
+            limit: 3,
+            done: false,
+        });
+        sim.run(10).unwrap();
+        assert_eq!(sim.step(), 3);
+        assert_eq!(
+            *steps.lock().unwrap(),
+            vec![(0, 0), (1, 1), (1, 0), (2, 1), (2, 0), (3, 1)]
+        );
+        assert!(sim.system().velocities[0].y > 0.0);
+    }
+
+    #[test]
+    fn watched_state() {
+        let mut sim = argon_simulation();
+        sim.watch(PotentialEnergy);
+        sim.set_watch_interval(4);
+        let handle = sim.state_handle();
+        assert!(!handle.get().running);
+
+        // the hook sees the state published on the previous multiple of the interval
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let observer = handle.clone();
+        sim.add_hook(Observer(move || {
+            let state = observer.get();
+            log.lock().unwrap().push((state.step, state.running));
+        }));
+        sim.run(6).unwrap();
+        let expected: Vec<(usize, bool)> = (0..6).map(|step| (step, true)).collect();
+        assert_eq!(*seen.lock().unwrap(), expected);
+
+        let state = sim.state();
+        assert_eq!(state.step, 6);
+        assert!(!state.running);
+        let energy = PotentialEnergy.calculate(sim.system(), sim.potentials());
+        assert_eq!(state.value("potential_energy"), Some(energy));
+        assert_eq!(state.value("temperature"), None);
+    }
+
+    // calls a function after every step
+    struct Observer<F>(F);
+
+    impl<F: FnMut()> Hook for Observer<F> {
+        fn post_step(&mut self, _: usize, _: &mut System, _: &Potentials) {
+            (self.0)()
+        }
+    }
+
+    #[test]
+    fn invalid_topology() {
+        let (mut system, potentials) = argon_simulation().consume();
+        system.topology.bonds.push([0, 2]);
+        let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+        let mut sim = Simulation::new(system, potentials, md, ConfigurationBuilder::new().build());
+        let err = sim.run(10).unwrap_err();
+        assert!(matches!(err, VelvetError::IndexOutOfRange { entry: "bond", index: 2, size: 2 }));
+        assert_eq!(sim.step(), 0);
+        assert!(sim.run_coupled(10, 5, |_| {}).is_err());
+        assert!(sim.potentials().biases.is_empty());
+    }
+
+    #[test]
+    fn dry_run() {
+        let mut sim = argon_simulation();
+        let positions = sim.system().positions.clone();
+        let report = sim.dry_run().unwrap();
+        assert_eq!(report.atoms, 2);
+        assert_eq!(report.neighbor_pairs, 1);
+        assert!(report.neighbor_bytes > 0);
+        assert!(report.system_bytes > 0);
+        assert!(report.potential_energy < 0.0);
+        assert_eq!(report.kinetic_energy, 0.0);
+        assert!(report.max_force > 0.0);
+        assert!(report.warnings.is_empty());
+        assert!(report.to_string().contains("neighbor pairs:     1"));
+
+        // no dynamics were run
+        assert_eq!(sim.step(), 0);
+        assert_eq!(sim.system().positions, positions);
+        sim.run(1).unwrap();
+        assert_eq!(sim.step(), 1);
+    }
+
+    #[test]
+    fn dry_run_warnings() {
+        let sim = argon_simulation();
+        let (system, _) = sim.consume();
+        let xenon = Species::from_element(Element::Xe);
+        let potentials = PotentialsBuilder::new()
+            .pair(LennardJones::new(4.184, 3.4), (xenon, xenon), 8.5, 1.0)
+            .build();
+        let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+        let mut sim = Simulation::new(system, potentials, md, ConfigurationBuilder::new().build());
+        let report = sim.dry_run().unwrap();
+        assert_eq!(report.neighbor_pairs, 0);
+        assert_eq!(report.potential_energy, 0.0);
+        assert_eq!(
+            report.warnings,
+            vec!["no potential acts between Ar-Ar pairs", "the Xe-Xe pair potential matches no pairs"]
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! Electronic stopping of energetic atoms in radiation damage simulations.
+
+use crate::checkpoint::PropagatorState;
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Propagator which removes energy from fast atoms through inelastic collisions with electrons.
+///
+/// After each step of the wrapped propagator every atom of a configured species whose kinetic
+/// energy exceeds the threshold of its species feels a friction force `-m gamma v`, which is
+/// applied exactly by scaling its velocity by `exp(-gamma dt)`. Atoms below the threshold and
+/// atoms of other species are untouched, so the thermal motion of the lattice is preserved while
+/// the primary knock-on atom and the fast recoils of a collision cascade lose energy to the
+/// electronic subsystem. The energy removed over the whole simulation is reported by
+/// [`energy_lost`](ElectronicStopping::energy_lost).
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // tungsten atoms above 10 kcal/mol lose energy to the electrons
+/// let tungsten = Species::from_element(Element::W);
+/// let md = MolecularDynamics::new(VelocityVerlet::new(0.1), NullThermostat);
+/// let cascade = ElectronicStopping::new(md, 0.1).species(tungsten, 0.002, 10.0);
+/// ```
+pub struct ElectronicStopping<P: Propagator> {
+    propagator: P,
+    timestep: Float,
+    species: Vec<(Species, Float, Float)>,
+    lost: Float,
+}
+
+impl<P: Propagator> ElectronicStopping<P> {
+    /// Returns a new [`ElectronicStopping`] propagator wrapping `propagator`.
+    ///
+    /// # Arguments
+    ///
+    /// * `propagator` - Propagator which advances the system.
+    /// * `timestep` - Timestep duration of the wrapped propagator.
+    pub fn new(propagator: P, timestep: Float) -> ElectronicStopping<P> {
+        ElectronicStopping {
+            propagator,
+            timestep,
+            species: Vec::new(),
+            lost: 0.0,
+        }
+    }
+
+    /// Applies electronic stopping to atoms of `species`.
+    ///
+    /// # Arguments
+    ///
+    /// * `species` - Species of the stopped atoms.
+    /// * `friction` - Electronic friction coefficient in inverse femtoseconds.
+    /// * `threshold` - Kinetic energy above which an atom is stopped.
+    pub fn species(mut self, species: Species, friction: Float, threshold: Float) -> ElectronicStopping<P> {
+        self.species.retain(|(s, _, _)| *s != species);
+        self.species.push((species, friction, threshold));
+        self
+    }
+
+    /// Returns the wrapped propagator.
+    pub fn propagator(&self) -> &P {
+        &self.propagator
+    }
+
+    /// Returns the total kinetic energy removed by electronic stopping.
+    pub fn energy_lost(&self) -> Float {
+        self.lost
+    }
+
+    fn stop(&mut self, system: &mut System) {
+        for i in 0..system.size {
+            let species = system.species[i];
+            let (friction, threshold) = match self.species.iter().find(|(s, _, _)| *s == species) {
+                Some(&(_, friction, threshold)) => (friction, threshold),
+                None => continue,
+            };
+            let mass = species.mass();
+            let kinetic_energy = 0.5 * mass * system.velocities[i].norm_squared();
+            if kinetic_energy <= threshold {
+                continue;
+            }
+            let scale = Float::exp(-friction * self.timestep);
+            system.velocities[i] *= scale;
+            self.lost += kinetic_energy * (1.0 - scale * scale);
+        }
+    }
+}
+
+impl<P: Propagator> Propagator for ElectronicStopping<P> {
+    fn setup(&mut self, system: &mut System, potentials: &Potentials) -> Result<(), VelvetError> {
+        self.propagator.setup(system, potentials)
+    }
+
+    fn propagate(&mut self, system: &mut System, potentials: &Potentials) {
+        self.propagator.propagate(system, potentials);
+        self.stop(system);
+    }
+
+    fn converged(&self) -> bool {
+        self.propagator.converged()
+    }
+
+    fn take_error(&mut self) -> Option<VelvetError> {
+        self.propagator.take_error()
+    }
+
+    // the energy lost is stored beside the state of the wrapped propagator
+    fn state(&self) -> PropagatorState {
+        PropagatorState {
+            floats: vec![self.lost],
+            parts: vec![self.propagator.state()],
+            ..PropagatorState::default()
+        }
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 1, 1)?;
+        self.propagator.restore(&state.parts[0])?;
+        self.lost = state.floats[0];
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ElectronicStopping;
+    use crate::integrators::VelocityVerlet;
+    use crate::internal::Float;
+    use crate::potentials::PotentialsBuilder;
+    use crate::propagators::{MolecularDynamics, Propagator};
+    use crate::properties::energy::KineticEnergy;
+    use crate::properties::IntrinsicProperty;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use crate::thermostats::NullThermostat;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    // free atoms of two species with one fast recoil of each
+    fn recoils() -> (System, Species) {
+        let heavy = Species::new(183.84, 0.0);
+        let light = Species::new(12.011, 0.0);
+        let system = System {
+            size: 4,
+            cell: Cell::cubic(20.0),
+            species: vec![heavy, heavy, light, light],
+            positions: vec![
+                Vector3::new(1.0, 1.0, 1.0),
+                Vector3::new(5.0, 5.0, 5.0),
+                Vector3::new(9.0, 9.0, 9.0),
+                Vector3::new(13.0, 13.0, 13.0),
+            ],
+            velocities: vec![
+                Vector3::new(0.1, 0.0, 0.0),
+                Vector3::new(0.001, 0.0, 0.0),
+                Vector3::new(0.0, 0.1, 0.0),
+                Vector3::new(0.0, 0.001, 0.0),
+            ],
+            topology: Topology::default(),
+        };
+        (system, heavy)
+    }
+
+    #[test]
+    fn stop_fast_atoms() {
+        let (mut system, heavy) = recoils();
+        let initial = system.velocities.clone();
+        let mut potentials = PotentialsBuilder::new().build();
+        potentials.setup(&system);
+        let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+        let mut stopping = ElectronicStopping::new(md, 1.0).species(heavy, 0.01, 0.1);
+        stopping.setup(&mut system, &potentials).unwrap();
+        let before = KineticEnergy.calculate_intrinsic(&system);
+        stopping.propagate(&mut system, &potentials);
+
+        // only the fast atom of the stopped species slows down
+        let scale = Float::exp(-0.01);
+        assert_relative_eq!(system.velocities[0].x, 0.1 * scale, epsilon = 1e-6);
+        assert_eq!(system.velocities[1], initial[1]);
+        assert_eq!(system.velocities[2], initial[2]);
+        assert_eq!(system.velocities[3], initial[3]);
+
+        // the energy lost is the kinetic energy removed from the system
+        let after = KineticEnergy.calculate_intrinsic(&system);
+        assert_relative_eq!(stopping.energy_lost(), before - after, epsilon = 1e-4);
+
+        // and is stored with the checkpoint state
+        let state = stopping.state();
+        let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+        let mut restored = ElectronicStopping::new(md, 1.0).species(heavy, 0.01, 0.1);
+        restored.setup(&mut system, &potentials).unwrap();
+        restored.restore(&state).unwrap();
+        assert_eq!(restored.energy_lost(), stopping.energy_lost());
+        assert!(restored.restore(&state.parts[0]).is_err());
+    }
+}
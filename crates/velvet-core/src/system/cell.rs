@@ -3,15 +3,35 @@
 use nalgebra::{Matrix3, Vector3};
 
 use crate::internal::Float;
+use crate::precision::Real;
 
 /// Bounding box of the simulation environment.
+///
+/// Cells may be fully triclinic with any right- or left-handed set of lattice vectors.
+/// Orthorhombic cells are detected at construction and use a specialized minimum image
+/// convention which avoids the general matrix transformations.
+///
+/// In skewed cells the nearest image of a vector is not always found by rounding its
+/// fractional coordinates, so vectors longer than the radius of the sphere inscribed in the
+/// cell are compared against their images in the neighboring cells. This is exact for cells
+/// whose lattice vectors are reasonably reduced, such as Niggli reduced cells.
 #[derive(Clone, Debug)]
-pub struct Cell {
-    matrix: Matrix3<Float>,
-    inv_matrix: Matrix3<Float>,
+pub struct Cell<R: Real = Float> {
+    matrix: Matrix3<R>,
+    inv_matrix: Matrix3<R>,
+    orthorhombic: Option<Orthorhombic<R>>,
+    // squared radius of the sphere inscribed in the cell
+    inscribed: R,
 }
 
-impl Cell {
+// Edge lengths of an orthorhombic cell and their reciprocals.
+#[derive(Clone, Copy, Debug)]
+struct Orthorhombic<R: Real> {
+    lengths: Vector3<R>,
+    inv_lengths: Vector3<R>,
+}
+
+impl<R: Real> Cell<R> {
     /// Constructs a [`Cell`] from triclinic lattice parameters.
     ///
     /// # Examples
@@ -26,16 +46,14 @@ impl Cell {
     /// assert_eq!(cell.c(), 3.0);
     /// ```
     pub fn triclinic(
-        a: Float,
-        b: Float,
-        c: Float,
-        alpha: Float,
-        beta: Float,
-        gamma: Float,
-    ) -> Cell {
-        let matrix = cell_matrix(a, b, c, alpha, beta, gamma);
-        let inv_matrix = matrix.try_inverse().unwrap();
-        Cell { matrix, inv_matrix }
+        a: R,
+        b: R,
+        c: R,
+        alpha: R,
+        beta: R,
+        gamma: R,
+    ) -> Cell<R> {
+        Cell::from_matrix(cell_matrix(a, b, c, alpha, beta, gamma))
     }
 
     /// Constructs a [`Cell`] from cubic lattice parameters.
@@ -51,56 +69,141 @@ impl Cell {
     /// assert_eq!(cell.b(), a0);
     /// assert_eq!(cell.c(), a0);
     /// ```
-    pub fn cubic(a: Float) -> Cell {
-        let matrix = cell_matrix(a, a, a, 90.0, 90.0, 90.0);
-        let inv_matrix = matrix.try_inverse().unwrap();
-        Cell { matrix, inv_matrix }
+    pub fn cubic(a: R) -> Cell<R> {
+        let right = R::from_double(90.0);
+        Cell::from_matrix(cell_matrix(a, a, a, right, right, right))
+    }
+
+    /// Constructs a [`Cell`] from its 'a', 'b', and 'c' lattice vectors.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    /// use nalgebra::Vector3;
+    /// use approx::*;
+    ///
+    /// // rhombohedral primitive cell of a face centered cubic lattice
+    /// let cell = Cell::from_vectors(
+    ///     Vector3::new(0.0, 2.0, 2.0),
+    ///     Vector3::new(2.0, 0.0, 2.0),
+    ///     Vector3::new(2.0, 2.0, 0.0),
+    /// );
+    /// assert_relative_eq!(cell.alpha(), 60.0, epsilon = 1e-4);
+    /// assert_relative_eq!(cell.volume(), 16.0, epsilon = 1e-4);
+    /// ```
+    pub fn from_vectors(a: Vector3<R>, b: Vector3<R>, c: Vector3<R>) -> Cell<R> {
+        Cell::from_matrix(Matrix3::from_columns(&[a, b, c]))
     }
 
-    /// Constructs a [`Cell`] from a 3x3 matrix.
-    pub fn from_matrix(matrix: Matrix3<Float>) -> Cell {
-        let inv_matrix = matrix.try_inverse().unwrap();
-        Cell { matrix, inv_matrix }
+    /// Constructs a [`Cell`] from a 3x3 matrix whose columns are the lattice vectors.
+    ///
+    /// The matrix is stored as given. Only a matrix whose off-diagonal elements are exactly zero
+    /// uses the faster orthorhombic minimum image convention.
+    pub fn from_matrix(matrix: Matrix3<R>) -> Cell<R> {
+        let orthorhombic = (0..3)
+            .flat_map(|i| (0..3).map(move |j| (i, j)))
+            .filter(|(i, j)| i != j)
+            .all(|(i, j)| matrix[(i, j)] == R::zero());
+        let orthorhombic = if orthorhombic {
+            Some(Orthorhombic {
+                lengths: matrix.diagonal(),
+                inv_lengths: matrix.diagonal().map(|x| R::one() / x),
+            })
+        } else {
+            None
+        };
+        let inv_matrix = matrix
+            .try_inverse()
+            .expect("The lattice vectors of a cell must be linearly independent.");
+        // the rows of the inverse are normal to the faces with lengths of the reciprocal spacings
+        let spacing = (1..3)
+            .map(|i| R::one() / inv_matrix.row(i).norm())
+            .fold(R::one() / inv_matrix.row(0).norm(), R::min);
+        Cell {
+            matrix,
+            inv_matrix,
+            orthorhombic,
+            inscribed: (R::from_double(0.5) * spacing).powi(2),
+        }
+    }
+
+    /// Returns the cell with its lattice vectors converted to another precision.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    ///
+    /// let cell: Cell<f64> = Cell::cubic(4.0f32).cast();
+    /// assert_eq!(cell.a(), 4.0);
+    /// ```
+    pub fn cast<S: Real>(&self) -> Cell<S> {
+        Cell::from_matrix(self.matrix.map(R::cast))
+    }
+
+    /// Returns true if the lattice vectors are mutually orthogonal and aligned with the cartesian axes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    ///
+    /// assert!(Cell::triclinic(3.0, 4.0, 5.0, 90.0, 90.0, 90.0).is_orthorhombic());
+    /// assert!(!Cell::triclinic(3.0, 4.0, 5.0, 90.0, 90.0, 120.0).is_orthorhombic());
+    /// ```
+    pub fn is_orthorhombic(&self) -> bool {
+        self.orthorhombic.is_some()
+    }
+
+    /// Returns the matrix whose columns are the 'a', 'b', and 'c' vectors.
+    pub fn matrix(&self) -> Matrix3<R> {
+        self.matrix
+    }
+
+    /// Returns the inverse of the cell matrix whose rows are the reciprocal lattice vectors (without a factor of 2π).
+    pub fn inverse_matrix(&self) -> Matrix3<R> {
+        self.inv_matrix
     }
 
     /// Returns the magnitude of the 'a' vector.
-    pub fn a(&self) -> Float {
+    pub fn a(&self) -> R {
         self.a_vector().norm()
     }
 
     /// Returns the magnitude of the 'b' vector.
-    pub fn b(&self) -> Float {
+    pub fn b(&self) -> R {
         self.b_vector().norm()
     }
 
     /// Returns the magnitude of the 'c' vector.
-    pub fn c(&self) -> Float {
+    pub fn c(&self) -> R {
         self.c_vector().norm()
     }
 
     /// Return the angle between 'b' and 'c' in degrees.
-    pub fn alpha(&self) -> Float {
+    pub fn alpha(&self) -> R {
         let b = self.b_vector();
         let c = self.c_vector();
-        b.angle(&c).to_degrees()
+        degrees(b.angle(&c))
     }
 
     /// Returns the angle between 'a' and 'c' in degrees.
-    pub fn beta(&self) -> Float {
+    pub fn beta(&self) -> R {
         let a = self.a_vector();
         let c = self.c_vector();
-        a.angle(&c).to_degrees()
+        degrees(a.angle(&c))
     }
 
     /// Returns the angle between 'a' and 'b' in degrees.
-    pub fn gamma(&self) -> Float {
+    pub fn gamma(&self) -> R {
         let a = self.a_vector();
         let b = self.b_vector();
-        a.angle(&b).to_degrees()
+        degrees(a.angle(&b))
     }
 
     /// Returns the 'a' vector.
-    pub fn a_vector(&self) -> Vector3<Float> {
+    pub fn a_vector(&self) -> Vector3<R> {
         Vector3::new(
             self.matrix[(0, 0)],
             self.matrix[(1, 0)],
@@ -109,7 +212,7 @@ impl Cell {
     }
 
     /// Returns the 'b' vector.
-    pub fn b_vector(&self) -> Vector3<Float> {
+    pub fn b_vector(&self) -> Vector3<R> {
         Vector3::new(
             self.matrix[(0, 1)],
             self.matrix[(1, 1)],
@@ -118,7 +221,7 @@ impl Cell {
     }
 
     /// Returns the 'c' vector.
-    pub fn c_vector(&self) -> Vector3<Float> {
+    pub fn c_vector(&self) -> Vector3<R> {
         Vector3::new(
             self.matrix[(0, 2)],
             self.matrix[(1, 2)],
@@ -142,7 +245,7 @@ impl Cell {
     /// assert_relative_eq!(frac[1], 0.5);
     /// assert_relative_eq!(frac[2], 0.5);
     /// ```
-    pub fn fractional(&self, cartesian: &Vector3<Float>) -> Vector3<Float> {
+    pub fn fractional(&self, cartesian: &Vector3<R>) -> Vector3<R> {
         self.inv_matrix * cartesian
     }
 
@@ -162,7 +265,7 @@ impl Cell {
     /// assert_relative_eq!(cart[1], 2.0);
     /// assert_relative_eq!(cart[2], 2.0);
     /// ```
-    pub fn cartesian(&self, fractional: &Vector3<Float>) -> Vector3<Float> {
+    pub fn cartesian(&self, fractional: &Vector3<R>) -> Vector3<R> {
         self.matrix * fractional
     }
 
@@ -182,11 +285,16 @@ impl Cell {
     /// assert_relative_eq!(vec[1], 1.0, epsilon=1e-6);
     /// assert_relative_eq!(vec[2], 1.0, epsilon=1e-6);
     /// ```
-    pub fn wrap_vector(&self, vector: &mut Vector3<Float>) {
+    pub fn wrap_vector(&self, vector: &mut Vector3<R>) {
+        if let Some(ortho) = &self.orthorhombic {
+            for k in 0..3 {
+                let shift = ortho.lengths[k] * (vector[k] * ortho.inv_lengths[k]).floor();
+                vector[k] -= shift;
+            }
+            return;
+        }
         let mut fractional = self.fractional(vector);
-        fractional[0] -= Float::floor(fractional[0]);
-        fractional[1] -= Float::floor(fractional[1]);
-        fractional[2] -= Float::floor(fractional[2]);
+        fractional -= fractional.map(R::floor);
         *vector = self.cartesian(&fractional);
     }
 
@@ -206,12 +314,33 @@ impl Cell {
     /// assert_relative_eq!(vec[1], -1.0, epsilon=1e-6);
     /// assert_relative_eq!(vec[2], 1.0, epsilon=1e-6);
     /// ```
-    pub fn vector_image(&self, vector: &mut Vector3<Float>) {
+    pub fn vector_image(&self, vector: &mut Vector3<R>) {
+        if let Some(ortho) = &self.orthorhombic {
+            for k in 0..3 {
+                let shift = ortho.lengths[k] * (vector[k] * ortho.inv_lengths[k]).round();
+                vector[k] -= shift;
+            }
+            return;
+        }
         let mut fractional = self.fractional(vector);
-        fractional[0] -= Float::round(fractional[0]);
-        fractional[1] -= Float::round(fractional[1]);
-        fractional[2] -= Float::round(fractional[2]);
+        fractional -= fractional.map(R::round);
         *vector = self.cartesian(&fractional);
+        if vector.norm_squared() <= self.inscribed {
+            return;
+        }
+        // search the neighboring images of skewed cells
+        let reduced = *vector;
+        for i in -1..=1 {
+            for j in -1..=1 {
+                for k in -1..=1 {
+                    let shift = Vector3::new(i, j, k).map(|n: i32| R::from_double(f64::from(n)));
+                    let image = reduced + self.matrix * shift;
+                    if image.norm_squared() < vector.norm_squared() {
+                        *vector = image;
+                    }
+                }
+            }
+        }
     }
 
     /// Returns the unit vector path between `v1` and `v2` obeying periodic boundary conditions.
@@ -231,7 +360,7 @@ impl Cell {
     /// assert_relative_eq!(dir[1], 0.0, epsilon=1e-6);
     /// assert_relative_eq!(dir[2], 0.0, epsilon=1e-6);
     /// ```
-    pub fn direction(&self, v1: &Vector3<Float>, v2: &Vector3<Float>) -> Vector3<Float> {
+    pub fn direction(&self, v1: &Vector3<R>, v2: &Vector3<R>) -> Vector3<R> {
         let mut d = v2 - v1;
         self.vector_image(&mut d);
         d.normalize()
@@ -252,7 +381,7 @@ impl Cell {
     /// let dist = cell.distance(&v1, &v2);
     /// assert_relative_eq!(dist, 1.5, epsilon=1e-6);
     /// ```
-    pub fn distance(&self, v1: &Vector3<Float>, v2: &Vector3<Float>) -> Float {
+    pub fn distance(&self, v1: &Vector3<R>, v2: &Vector3<R>) -> R {
         let mut d = v2 - v1;
         self.vector_image(&mut d);
         d.norm()
@@ -274,12 +403,12 @@ impl Cell {
     /// let angle = cell.angle(&v1, &v2, &v3);
     /// assert_relative_eq!(angle, 3.14159, epsilon=1e-5);
     /// ```
-    pub fn angle(&self, v1: &Vector3<Float>, v2: &Vector3<Float>, v3: &Vector3<Float>) -> Float {
+    pub fn angle(&self, v1: &Vector3<R>, v2: &Vector3<R>, v3: &Vector3<R>) -> R {
         let mut v12 = v1 - v2;
         self.vector_image(&mut v12);
         let mut v32 = v3 - v2;
         self.vector_image(&mut v32);
-        Float::acos(v12.dot(&v32) / (v12.norm() * v32.norm()))
+        (v12.dot(&v32) / (v12.norm() * v32.norm())).acos()
     }
 
     /// Returns the dihedral angle between `v1`, `v2`, `v3`, and `v4`.
@@ -301,11 +430,11 @@ impl Cell {
     /// ```
     pub fn dihedral(
         &self,
-        v1: &Vector3<Float>,
-        v2: &Vector3<Float>,
-        v3: &Vector3<Float>,
-        v4: &Vector3<Float>,
-    ) -> Float {
+        v1: &Vector3<R>,
+        v2: &Vector3<R>,
+        v3: &Vector3<R>,
+        v4: &Vector3<R>,
+    ) -> R {
         let mut v21 = v2 - v1;
         self.vector_image(&mut v21);
         let mut v32 = v3 - v2;
@@ -315,10 +444,10 @@ impl Cell {
 
         let u = v21.cross(&v32);
         let v = v32.cross(&v43);
-        Float::atan2(v32.norm() * v.dot(&v21), u.dot(&v))
+        (v32.norm() * v.dot(&v21)).atan2(u.dot(&v))
     }
 
-    /// Returns the total volume of the cell.
+    /// Returns the total volume of the cell, which is positive regardless of handedness.
     ///
     /// # Examples
     ///
@@ -329,40 +458,76 @@ impl Cell {
     /// let cell = Cell::cubic(4.0);
     /// assert_relative_eq!(cell.volume(), 64.0);
     /// ```
-    pub fn volume(&self) -> Float {
-        (self.a_vector().cross(&self.b_vector())).dot(&self.c_vector())
+    pub fn volume(&self) -> R {
+        self.matrix.determinant().abs()
+    }
+
+    /// Returns the radius of the largest sphere which fits inside the cell.
+    ///
+    /// This is half the smallest distance between opposite faces of the cell and is the largest
+    /// cutoff for which the minimum image convention finds every interacting pair of atoms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    /// use approx::*;
+    ///
+    /// let cell = Cell::triclinic(4.0, 6.0, 8.0, 90.0, 90.0, 90.0);
+    /// assert_relative_eq!(cell.inscribed_radius(), 2.0);
+    /// ```
+    pub fn inscribed_radius(&self) -> R {
+        self.inscribed.sqrt()
+    }
+}
+
+// Converts an angle in radians to degrees with the same factor as `to_degrees`.
+fn degrees<R: Real>(angle: R) -> R {
+    angle * R::from_double(180.0 / std::f64::consts::PI)
+}
+
+// Returns the sine and cosine of an angle in degrees which are exact for right angles,
+// so cells with right angles between their lattice vectors are exactly orthogonal.
+fn sin_cos<R: Real>(angle: R) -> (R, R) {
+    if angle == R::from_double(90.0) {
+        (R::one(), R::zero())
+    } else {
+        (angle * (R::pi() / R::from_double(180.0))).sin_cos()
     }
 }
 
-fn cell_matrix(
-    a: Float,
-    b: Float,
-    c: Float,
-    alpha: Float,
-    beta: Float,
-    gamma: Float,
-) -> Matrix3<Float> {
-    let cos_alpha = alpha.to_radians().cos();
-    let cos_beta = beta.to_radians().cos();
-    let (sin_gamma, cos_gamma) = gamma.to_radians().sin_cos();
+fn cell_matrix<R: Real>(
+    a: R,
+    b: R,
+    c: R,
+    alpha: R,
+    beta: R,
+    gamma: R,
+) -> Matrix3<R> {
+    let (_, cos_alpha) = sin_cos(alpha);
+    let (_, cos_beta) = sin_cos(beta);
+    let (sin_gamma, cos_gamma) = sin_cos(gamma);
 
     let b_x = b * cos_gamma;
     let b_y = b * sin_gamma;
 
     let c_x = c * cos_beta;
     let c_y = c * (cos_alpha - cos_beta * cos_gamma) / sin_gamma;
-    let c_z = Float::sqrt(c * c - c_y * c_y - c_x * c_x);
+    let c_z = (c * c - c_y * c_y - c_x * c_x).sqrt();
 
-    Matrix3::new(a, b_x, c_x, 0.0, b_y, c_y, 0.0, 0.0, c_z)
+    Matrix3::new(a, b_x, c_x, R::zero(), b_y, c_y, R::zero(), R::zero(), c_z)
 }
 
 #[cfg(test)]
+#[allow(clippy::op_ref)]
 mod tests {
     use super::Cell;
     use crate::internal::consts::PI;
     use crate::internal::Float;
     use approx::*;
-    use nalgebra::Vector3;
+    use nalgebra::{Matrix3, Rotation3, Vector3};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
 
     #[test]
     fn triclinic() {
@@ -379,6 +544,21 @@ mod tests {
         assert_relative_eq!(cell.gamma(), 110.0);
     }
 
+    #[test]
+    fn precision() {
+        let single = Cell::triclinic(3.0f32, 4.0, 5.0, 80.0, 90.0, 110.0);
+        let double = Cell::triclinic(3.0f64, 4.0, 5.0, 80.0, 90.0, 110.0);
+        assert_relative_eq!(single.cast::<f64>().matrix(), double.matrix(), max_relative = 1e-6);
+        assert_relative_eq!(double.cast::<f32>().volume(), single.volume(), max_relative = 1e-6);
+        assert!(Cell::cubic(4.0f64).cast::<f32>().is_orthorhombic());
+
+        let mut v = Vector3::new(2.9, -3.1, 4.4);
+        double.vector_image(&mut v);
+        let mut u = Vector3::new(2.9f32, -3.1, 4.4);
+        single.vector_image(&mut u);
+        assert_relative_eq!(u.map(f64::from), v, epsilon = 1e-5);
+    }
+
     #[test]
     fn cubic() {
         let a0 = 4.0;
@@ -405,6 +585,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn orthorhombic_fast_path() {
+        let ortho = Cell::triclinic(3.0, 4.0, 5.0, 90.0, 90.0, 90.0);
+        assert!(ortho.is_orthorhombic());
+        assert!(Cell::cubic(4.0).is_orthorhombic());
+        assert!(!Cell::triclinic(3.0, 4.0, 5.0, 90.0, 90.0, 91.0).is_orthorhombic());
+
+        // nearly diagonal matrices are kept as given and use the general path
+        let mut matrix = Matrix3::from_diagonal(&Vector3::new(3.0, 4.0, 5.0));
+        matrix[(0, 1)] = 1e-7;
+        let skewed = Cell::from_matrix(matrix);
+        assert!(!skewed.is_orthorhombic());
+        assert_eq!(skewed.matrix(), matrix);
+
+        // a cell with the same matrix that is forced down the general path
+        let general = Cell {
+            orthorhombic: None,
+            ..ortho.clone()
+        };
+        let tests = vec![
+            Vector3::new(1.0, 1.5, 6.0),
+            Vector3::new(-7.3, 2.1, -0.4),
+            Vector3::new(14.9, -9.2, 12.6),
+        ];
+        for test in &tests {
+            let (mut fast, mut slow) = (*test, *test);
+            ortho.vector_image(&mut fast);
+            general.vector_image(&mut slow);
+            assert_relative_eq!((fast - slow).norm(), 0.0, epsilon = 1e-5);
+            let (mut fast, mut slow) = (*test, *test);
+            ortho.wrap_vector(&mut fast);
+            general.wrap_vector(&mut slow);
+            assert_relative_eq!((fast - slow).norm(), 0.0, epsilon = 1e-5);
+        }
+    }
+
     #[test]
     fn wrap_vector() {
         let cell = Cell::triclinic(3.0, 4.0, 5.0, 90.0, 90.0, 90.0);
@@ -480,4 +696,134 @@ mod tests {
         let volume = 60.0;
         assert_relative_eq!(cell.volume(), volume, epsilon = 1e-5);
     }
+
+    // random triclinic cells with angles far enough from degenerate
+    fn random_cells(rng: &mut StdRng, count: usize) -> Vec<Cell> {
+        (0..count)
+            .map(|_| {
+                Cell::triclinic(
+                    rng.gen_range(3.0, 10.0),
+                    rng.gen_range(3.0, 10.0),
+                    rng.gen_range(3.0, 10.0),
+                    rng.gen_range(60.0, 120.0),
+                    rng.gen_range(60.0, 120.0),
+                    rng.gen_range(60.0, 120.0),
+                )
+            })
+            .filter(|cell| cell.volume() > 0.3 * cell.a() * cell.b() * cell.c())
+            .collect()
+    }
+
+    fn random_vector(rng: &mut StdRng, scale: Float) -> Vector3<Float> {
+        Vector3::new(
+            rng.gen_range(-scale, scale),
+            rng.gen_range(-scale, scale),
+            rng.gen_range(-scale, scale),
+        )
+    }
+
+    // shortest image found by searching a wide block of cells around the rounded image
+    fn brute_force_image(cell: &Cell, vector: &Vector3<Float>) -> Vector3<Float> {
+        let frac = cell.fractional(vector);
+        let rounded = cell.cartesian(&(frac - frac.map(Float::round)));
+        let mut best = rounded;
+        for i in -3..=3 {
+            for j in -3..=3 {
+                for k in -3..=3 {
+                    let image = rounded + cell.cartesian(&Vector3::new(i as Float, j as Float, k as Float));
+                    if image.norm() < best.norm() {
+                        best = image;
+                    }
+                }
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn triclinic_minimum_image() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for cell in random_cells(&mut rng, 50) {
+            for _ in 0..20 {
+                let v1 = random_vector(&mut rng, 15.0);
+                let v2 = random_vector(&mut rng, 15.0);
+                let expected = brute_force_image(&cell, &(v2 - v1));
+
+                // the image differs from the vector by a lattice translation
+                let mut image = v2 - v1;
+                cell.vector_image(&mut image);
+                let shift = cell.fractional(&(image - (v2 - v1)));
+                assert_relative_eq!((shift - shift.map(Float::round)).norm(), 0.0, epsilon = 1e-3);
+
+                let distance = cell.distance(&v1, &v2);
+                assert_relative_eq!(distance, expected.norm(), epsilon = 1e-3);
+                assert_relative_eq!(distance, cell.distance(&v2, &v1), epsilon = 1e-3);
+                let direction = cell.direction(&v1, &v2);
+                assert_relative_eq!(direction.norm(), 1.0, epsilon = 1e-4);
+                assert_relative_eq!((direction * distance - image).norm(), 0.0, epsilon = 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn triclinic_conversions() {
+        let mut rng = StdRng::seed_from_u64(11);
+        for cell in random_cells(&mut rng, 50) {
+            let cart = random_vector(&mut rng, 20.0);
+            let res = cell.cartesian(&cell.fractional(&cart));
+            assert_relative_eq!((cart - res).norm(), 0.0, epsilon = 1e-3);
+
+            // wrapped positions lie inside the cell and are equivalent to the original
+            let mut wrapped = cart;
+            cell.wrap_vector(&mut wrapped);
+            let frac = cell.fractional(&wrapped);
+            assert!(frac.iter().all(|x| *x >= -1e-4 && *x < 1.0 + 1e-4));
+            assert_relative_eq!(cell.distance(&cart, &wrapped), 0.0, epsilon = 1e-3);
+
+            // volume from the lattice parameters
+            let (ca, cb, cg) = (
+                cell.alpha().to_radians().cos(),
+                cell.beta().to_radians().cos(),
+                cell.gamma().to_radians().cos(),
+            );
+            let expected = cell.a() * cell.b() * cell.c()
+                * Float::sqrt(1.0 - ca * ca - cb * cb - cg * cg + 2.0 * ca * cb * cg);
+            assert_relative_eq!(cell.volume(), expected, max_relative = 1e-3);
+        }
+    }
+
+    #[test]
+    fn from_vectors() {
+        let mut rng = StdRng::seed_from_u64(13);
+        for cell in random_cells(&mut rng, 20) {
+            // an arbitrarily oriented copy of the cell has the same geometry
+            let rotation = Rotation3::from_euler_angles(
+                rng.gen_range(0.0, PI),
+                rng.gen_range(0.0, PI),
+                rng.gen_range(0.0, PI),
+            );
+            let rotated = Cell::from_vectors(
+                rotation * cell.a_vector(),
+                rotation * cell.b_vector(),
+                rotation * cell.c_vector(),
+            );
+            assert!(!rotated.is_orthorhombic());
+            assert_relative_eq!(rotated.a(), cell.a(), epsilon = 1e-3);
+            assert_relative_eq!(rotated.alpha(), cell.alpha(), epsilon = 1e-2);
+            assert_relative_eq!(rotated.gamma(), cell.gamma(), epsilon = 1e-2);
+            assert_relative_eq!(rotated.volume(), cell.volume(), max_relative = 1e-3);
+
+            let v1 = random_vector(&mut rng, 10.0);
+            let v2 = random_vector(&mut rng, 10.0);
+            assert_relative_eq!(
+                rotated.distance(&(rotation * v1), &(rotation * v2)),
+                cell.distance(&v1, &v2),
+                epsilon = 1e-3
+            );
+        }
+
+        // left handed lattice vectors still have a positive volume
+        let left = Cell::from_matrix(Matrix3::new(3.0, 0.0, 0.0, 0.0, 4.0, 0.0, 0.0, 0.0, -5.0));
+        assert_relative_eq!(left.volume(), 60.0, epsilon = 1e-4);
+    }
 }
@@ -0,0 +1,228 @@
+//! Assignment of partial charges to the atoms of a system.
+
+use crate::internal::Float;
+use crate::system::elements::Element;
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Number of charge equalization iterations of the Gasteiger scheme.
+const GASTEIGER_ITERATIONS: usize = 6;
+
+/// Electronegativity of the hydrogen cation used in place of `a + b + c` for hydrogen.
+const HYDROGEN_CATION: Float = 20.02;
+
+/// Sets the charge of each atom in the system.
+///
+/// The charges are stored as the partial charges of the system's
+/// [`Topology`](crate::system::topology::Topology) and the species of the atoms are left as they
+/// are, so atoms of one element share a species whatever their charge.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let oxygen = Species::from_element(Element::O);
+/// let hydrogen = Species::from_element(Element::H);
+/// let mut system = System {
+///     size: 3,
+///     cell: Cell::cubic(10.0),
+///     species: vec![oxygen, hydrogen, hydrogen],
+///     positions: vec![Vector3::zeros(); 3],
+///     velocities: vec![Vector3::zeros(); 3],
+///     topology: Topology::default(),
+/// };
+///
+/// assign_charges(&mut system, &[-0.834, 0.417, 0.417]);
+/// assert_eq!(system.species[0], oxygen);
+/// assert_eq!(system.charge(0), -0.834);
+/// ```
+pub fn assign_charges(system: &mut System, charges: &[Float]) {
+    assert_eq!(charges.len(), system.size, "Expected a charge for each of the {} atoms", system.size);
+    system.topology.charges = charges.to_vec();
+}
+
+/// Returns the charge of each atom looked up from the charge of its species in `table`.
+///
+/// Atoms of species missing from the table keep their current charge.
+pub fn charges_by_species(system: &System, table: &[(Species, Float)]) -> Vec<Float> {
+    system
+        .species
+        .iter()
+        .enumerate()
+        .map(|(i, species)| {
+            table
+                .iter()
+                .find(|(s, _)| s == species)
+                .map(|(_, charge)| *charge)
+                .unwrap_or_else(|| system.charge(i))
+        })
+        .collect()
+}
+
+// Returns the Gasteiger-Marsili electronegativity coefficients of an element with `neighbors` bonds.
+fn gasteiger_parameters(element: Element, neighbors: usize) -> Option<[Float; 3]> {
+    let parameters = match (element, neighbors) {
+        (Element::H, _) => [7.17, 6.24, -0.56],
+        (Element::C, 0..=2) => [10.39, 9.45, 0.73],
+        (Element::C, 3) => [8.79, 9.32, 1.51],
+        (Element::C, _) => [7.98, 9.18, 1.88],
+        (Element::N, 0..=1) => [15.68, 11.70, -0.27],
+        (Element::N, 2) => [12.87, 11.15, 0.85],
+        (Element::N, _) => [11.54, 10.82, 1.36],
+        (Element::O, 0..=1) => [17.07, 13.79, 0.47],
+        (Element::O, _) => [14.18, 12.92, 1.39],
+        (Element::F, _) => [14.66, 13.85, 2.31],
+        (Element::Cl, _) => [11.00, 9.69, 1.35],
+        (Element::Br, _) => [10.08, 8.47, 1.16],
+        (Element::I, _) => [9.90, 7.96, 0.96],
+        (Element::S, 0..=1) => [10.88, 9.49, 1.33],
+        (Element::S, _) => [10.14, 9.13, 1.38],
+        (Element::P, _) => [8.90, 8.24, 0.96],
+        _ => return None,
+    };
+    Some(parameters)
+}
+
+/// Returns partial charges of a neutral system from the partial equalization of orbital
+/// electronegativity along its bonds.
+///
+/// The electronegativity of each atom is the quadratic `a + b q + c q^2` in its charge, with
+/// the coefficients of its element and a hybridization guessed from its number of bonds in the
+/// [`Topology`](crate::system::topology::Topology). Over six iterations of geometrically
+/// decreasing weight charge flows along each bond toward the more electronegative atom, so
+/// the total charge of the system remains zero. Parameters are available for H, C, N, O, F,
+/// S, P, Cl, Br, and I.
+///
+/// # References
+///
+/// [1] Gasteiger, Johann, and Mario Marsili. "Iterative partial equalization of orbital electronegativity—a rapid access to atomic charges." Tetrahedron 36.22 (1980): 3219-3228.
+///
+/// # Panics
+///
+/// Panics if an atom's species was not constructed from an element with known parameters.
+pub fn gasteiger_charges(system: &System) -> Vec<Float> {
+    let bonds = &system.topology.bonds;
+    let mut neighbors = vec![0; system.size];
+    for [i, j] in bonds {
+        neighbors[*i] += 1;
+        neighbors[*j] += 1;
+    }
+    let parameters: Vec<[Float; 3]> = system
+        .species
+        .iter()
+        .zip(neighbors.iter())
+        .map(|(species, &count)| {
+            species
+                .element()
+                .and_then(|element| gasteiger_parameters(element, count))
+                .unwrap_or_else(|| panic!("No Gasteiger parameters for species {:?}", species))
+        })
+        .collect();
+    // electronegativity of the cation used to scale the charge transferred from each atom
+    let cations: Vec<Float> = system
+        .species
+        .iter()
+        .zip(parameters.iter())
+        .map(|(species, [a, b, c])| {
+            if species.element() == Some(Element::H) {
+                HYDROGEN_CATION
+            } else {
+                a + b + c
+            }
+        })
+        .collect();
+
+    let mut charges: Vec<Float> = vec![0.0; system.size];
+    let mut damping = 1.0;
+    for _ in 0..GASTEIGER_ITERATIONS {
+        damping *= 0.5;
+        let electronegativities: Vec<Float> = charges
+            .iter()
+            .zip(parameters.iter())
+            .map(|(q, [a, b, c])| a + b * q + c * q * q)
+            .collect();
+        let mut transfers = vec![0.0; system.size];
+        for [i, j] in bonds {
+            let (i, j) = (*i, *j);
+            let difference = electronegativities[j] - electronegativities[i];
+            // charge flows from the less electronegative atom
+            let donor = if difference > 0.0 { i } else { j };
+            let transfer = damping * difference / cations[donor];
+            transfers[i] += transfer;
+            transfers[j] -= transfer;
+        }
+        for (charge, transfer) in charges.iter_mut().zip(transfers.iter()) {
+            *charge += transfer;
+        }
+    }
+    charges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assign_charges, charges_by_species, gasteiger_charges};
+    use crate::internal::Float;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    fn methanol() -> System {
+        let carbon = Species::from_element(Element::C);
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        System {
+            size: 6,
+            cell: Cell::cubic(10.0),
+            species: vec![carbon, oxygen, hydrogen, hydrogen, hydrogen, hydrogen],
+            positions: vec![Vector3::zeros(); 6],
+            velocities: vec![Vector3::zeros(); 6],
+            topology: Topology {
+                bonds: vec![[0, 1], [0, 2], [0, 3], [0, 4], [1, 5]],
+                ..Topology::default()
+            },
+        }
+    }
+
+    #[test]
+    fn per_atom_charges() {
+        let mut system = methanol();
+        assign_charges(&mut system, &[0.1, -0.6, 0.0, 0.0, 0.0, 0.5]);
+        // the hydroxyl and methyl hydrogens differ in charge but remain one species
+        assert_eq!(system.species[2], system.species[5]);
+        assert_eq!(system.species[5], Species::from_element(Element::H));
+        assert_relative_eq!(system.charge(5), 0.5);
+        assert_relative_eq!(system.charge(2), 0.0);
+        assert_relative_eq!(system.charge(1), -0.6);
+
+        let table = [(system.species[1], -0.7)];
+        let charges = charges_by_species(&system, &table);
+        assert_relative_eq!(charges[1], -0.7);
+        assert_relative_eq!(charges[0], 0.1);
+    }
+
+    #[test]
+    fn gasteiger() {
+        let charges = gasteiger_charges(&methanol());
+        let total: Float = charges.iter().sum();
+        assert_relative_eq!(total, 0.0, epsilon = 1e-6);
+        // oxygen draws charge from its neighbors and the hydroxyl hydrogen is the most positive
+        assert!(charges[1] < -0.3);
+        assert!(charges[5] > charges[2]);
+        assert!(charges[5] > 0.15);
+        assert_relative_eq!(charges[2], charges[4], epsilon = 1e-6);
+    }
+
+    #[test]
+    #[should_panic(expected = "No Gasteiger parameters")]
+    fn missing_parameters() {
+        let mut system = methanol();
+        system.species[0] = Species::new(12.0, 0.0);
+        gasteiger_charges(&system);
+    }
+}
@@ -0,0 +1,269 @@
+//! Removal of atoms and molecules left in unphysical arrangements by system preparation.
+
+use crate::internal::Float;
+use crate::system::topology::Residue;
+use crate::system::System;
+
+/// Removes the atoms with indices in `atoms` from the system.
+///
+/// The remaining atoms keep their order and the topology is reindexed to match. Bonds, angles,
+/// dihedrals, and constraints involving a removed atom are dropped, as are residues left without
+/// any atoms. Groups keep their names even when all of their atoms are removed. Partial charges are kept for
+/// the remaining atoms.
+pub fn remove_atoms(system: &mut System, atoms: &[usize]) {
+    let mut index: Vec<Option<usize>> = vec![Some(0); system.size];
+    for &atom in atoms {
+        index[atom] = None;
+    }
+    let mut count = 0;
+    for entry in index.iter_mut().filter(|entry| entry.is_some()) {
+        *entry = Some(count);
+        count += 1;
+    }
+    let keep = |i: &usize| index[*i].is_some();
+
+    retain_indexed(&mut system.species, &index);
+    retain_indexed(&mut system.positions, &index);
+    retain_indexed(&mut system.velocities, &index);
+    if !system.topology.charges.is_empty() {
+        retain_indexed(&mut system.topology.charges, &index);
+    }
+    system.size = count;
+
+    let topology = &mut system.topology;
+    topology.bonds = topology
+        .bonds
+        .iter()
+        .filter(|bond| bond.iter().all(keep))
+        .map(|[i, j]| [index[*i].unwrap(), index[*j].unwrap()])
+        .collect();
+    topology.angles = topology
+        .angles
+        .iter()
+        .filter(|angle| angle.iter().all(keep))
+        .map(|[i, j, k]| [index[*i].unwrap(), index[*j].unwrap(), index[*k].unwrap()])
+        .collect();
+    topology.dihedrals = topology
+        .dihedrals
+        .iter()
+        .filter(|dihedral| dihedral.iter().all(keep))
+        .map(|[i, j, k, l]| [index[*i].unwrap(), index[*j].unwrap(), index[*k].unwrap(), index[*l].unwrap()])
+        .collect();
+    topology.constraints = topology
+        .constraints
+        .iter()
+        .filter(|pair| pair.iter().all(keep))
+        .map(|[i, j]| [index[*i].unwrap(), index[*j].unwrap()])
+        .collect();
+    topology.residues = topology
+        .residues
+        .iter()
+        .map(|residue| Residue {
+            name: residue.name.clone(),
+            atoms: residue.atoms.iter().filter_map(|i| index[*i]).collect(),
+        })
+        .filter(|residue| !residue.atoms.is_empty())
+        .collect();
+    for group in &mut topology.groups {
+        group.atoms = group.atoms.iter().filter_map(|i| index[*i]).collect();
+    }
+}
+
+// Retains the values whose entry in `index` is not `None`.
+fn retain_indexed<T>(values: &mut Vec<T>, index: &[Option<usize>]) {
+    let mut k = 0;
+    values.retain(|_| {
+        k += 1;
+        index[k - 1].is_some()
+    });
+}
+
+/// Removes solvent residues which overlap with the rest of the system and returns the number removed.
+///
+/// Solvent residues are those named in `solvent`. After solvating or packing a solute, any
+/// solvent residue with an atom closer than `distance` to a solute atom, using the minimum
+/// image convention, is deleted in its entirety so no partial molecules are left behind.
+/// Solvent residues are not tested against each other.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let mut system = System {
+///     size: 3,
+///     cell: Cell::cubic(10.0),
+///     species: vec![argon; 3],
+///     positions: vec![
+///         Vector3::new(5.0, 5.0, 5.0),
+///         Vector3::new(6.0, 5.0, 5.0),
+///         Vector3::new(9.0, 5.0, 5.0),
+///     ],
+///     velocities: vec![Vector3::zeros(); 3],
+///     topology: Topology {
+///         residues: vec![
+///             Residue { name: "SOL".to_string(), atoms: vec![1] },
+///             Residue { name: "SOL".to_string(), atoms: vec![2] },
+///         ],
+///         ..Topology::default()
+///     },
+/// };
+///
+/// assert_eq!(remove_overlapping_solvent(&mut system, &["SOL"], 2.0), 1);
+/// assert_eq!(system.size, 2);
+/// ```
+pub fn remove_overlapping_solvent(system: &mut System, solvent: &[&str], distance: Float) -> usize {
+    let residues: Vec<&Residue> = system
+        .topology
+        .residues
+        .iter()
+        .filter(|residue| solvent.contains(&residue.name.as_str()))
+        .collect();
+    let mut is_solvent = vec![false; system.size];
+    for &i in residues.iter().flat_map(|residue| residue.atoms.iter()) {
+        is_solvent[i] = true;
+    }
+    let solute: Vec<usize> = (0..system.size).filter(|&i| !is_solvent[i]).collect();
+    let overlapping: Vec<&Residue> = residues
+        .into_iter()
+        .filter(|residue| {
+            residue.atoms.iter().any(|&i| {
+                solute
+                    .iter()
+                    .any(|&j| system.cell.distance(&system.positions[i], &system.positions[j]) < distance)
+            })
+        })
+        .collect();
+    let count = overlapping.len();
+    let atoms: Vec<usize> = overlapping.iter().flat_map(|residue| residue.atoms.clone()).collect();
+    remove_atoms(system, &atoms);
+    count
+}
+
+/// Removes molecules which cross the boundary of the cell and returns the number removed.
+///
+/// Structures which were cut from a larger configuration without wrapping contain molecules
+/// which protrude from the cell and overlap with periodic images of molecules on the opposite
+/// side, while wrapping each atom separately splits molecules across the boundary. A molecule
+/// crosses the boundary if any of its atoms lies outside the cell or any of its bonds is shorter
+/// through a periodic boundary than directly. Molecules are the groups of bonded atoms in the
+/// topology, so unbonded atoms are only removed if they lie outside the cell.
+pub fn remove_boundary_molecules(system: &mut System) -> usize {
+    let mut molecules = system.topology.molecules();
+    let mut bonded = vec![false; system.size];
+    for &i in molecules.iter().flatten() {
+        bonded[i] = true;
+    }
+    molecules.extend((0..system.size).filter(|&i| !bonded[i]).map(|i| vec![i]));
+
+    let outside: Vec<bool> = system
+        .positions
+        .iter()
+        .map(|position| {
+            let fractional = system.cell.fractional(position);
+            fractional.iter().any(|&x| !(0.0..1.0).contains(&x))
+        })
+        .collect();
+    let mut split = vec![false; system.size];
+    for &[i, j] in &system.topology.bonds {
+        let direct = system.positions[j] - system.positions[i];
+        let mut image = direct;
+        system.cell.vector_image(&mut image);
+        // a small tolerance avoids flagging bonds of exactly half the cell length
+        if image.norm() < direct.norm() - 1e-4 {
+            split[i] = true;
+            split[j] = true;
+        }
+    }
+
+    let crossing: Vec<Vec<usize>> = molecules
+        .into_iter()
+        .filter(|atoms| atoms.iter().any(|&i| outside[i] || split[i]))
+        .collect();
+    let count = crossing.len();
+    let atoms: Vec<usize> = crossing.into_iter().flatten().collect();
+    remove_atoms(system, &atoms);
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remove_atoms, remove_boundary_molecules, remove_overlapping_solvent};
+    use crate::internal::Float;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::{Residue, Topology};
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // Diatomic solute near the boundary of the cell surrounded by diatomic solvent molecules.
+    fn solvated() -> System {
+        let carbon = Species::from_element(Element::C);
+        let oxygen = Species::from_element(Element::O);
+        let centers: [[Float; 3]; 4] = [[0.5, 5.0, 5.0], [2.0, 5.0, 5.0], [0.5, 8.0, 5.0], [9.5, 5.0, 5.0]];
+        let positions: Vec<Vector3<Float>> = centers
+            .iter()
+            .flat_map(|c| vec![Vector3::new(c[0], c[1], c[2]), Vector3::new(c[0], c[1], c[2] + 1.1)])
+            .collect();
+        let residue = |name: &str, k: usize| Residue {
+            name: name.to_string(),
+            atoms: vec![2 * k, 2 * k + 1],
+        };
+        System {
+            size: 8,
+            cell: Cell::cubic(10.0),
+            species: vec![carbon, oxygen, carbon, oxygen, carbon, oxygen, carbon, oxygen],
+            positions,
+            velocities: vec![Vector3::zeros(); 8],
+            topology: Topology {
+                bonds: vec![[0, 1], [2, 3], [4, 5], [6, 7]],
+                residues: vec![residue("LIG", 0), residue("SOL", 1), residue("SOL", 2), residue("SOL", 3)],
+                ..Topology::default()
+            },
+        }
+    }
+
+    #[test]
+    fn remove() {
+        let mut system = solvated();
+        system.topology.angles = vec![[0, 1, 2], [4, 5, 6]];
+        remove_atoms(&mut system, &[3, 1]);
+        assert_eq!(system.size, 6);
+        assert_eq!(system.positions[1], Vector3::new(2.0, 5.0, 5.0));
+        assert_eq!(system.topology.bonds, vec![[2, 3], [4, 5]]);
+        assert_eq!(system.topology.angles, vec![[2, 3, 4]]);
+        assert_eq!(system.topology.residues[1].atoms, vec![1]);
+    }
+
+    #[test]
+    fn overlapping_solvent() {
+        let mut system = solvated();
+        // the neighboring molecule and the molecule across the boundary overlap the solute
+        assert_eq!(remove_overlapping_solvent(&mut system, &["SOL"], 2.0), 2);
+        assert_eq!(system.size, 4);
+        let names: Vec<&str> = system.topology.residues.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["LIG", "SOL"]);
+        assert_eq!(system.positions[2], Vector3::new(0.5, 8.0, 5.0));
+        assert_eq!(system.topology.bonds, vec![[0, 1], [2, 3]]);
+    }
+
+    #[test]
+    fn boundary_molecules() {
+        let mut system = solvated();
+        // one molecule protrudes from the cell and another is split by wrapping
+        system.positions[5][1] = 10.5;
+        system.positions[6][0] = 9.8;
+        system.positions[7][0] = 0.3;
+        system.size += 1;
+        system.species.push(Species::from_element(Element::Ar));
+        system.positions.push(Vector3::new(1.0, 1.0, 1.0));
+        system.velocities.push(Vector3::zeros());
+        assert_eq!(remove_boundary_molecules(&mut system), 2);
+        assert_eq!(system.size, 5);
+        assert_eq!(system.topology.bonds, vec![[0, 1], [2, 3]]);
+        assert_eq!(system.positions[4], Vector3::new(1.0, 1.0, 1.0));
+    }
+}
@@ -0,0 +1,284 @@
+//! Starting configurations with coexisting solid and liquid phases.
+
+use nalgebra::{Matrix3, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::internal::Float;
+use crate::random::shared_rng;
+use crate::system::cell::Cell;
+use crate::system::topology::{Residue, Topology};
+use crate::system::System;
+
+/// Maximum number of random insertion attempts per liquid atom.
+const MAX_ATTEMPTS: usize = 1000;
+
+/// Constructor for a two-phase [`System`] used in direct coexistence simulations.
+///
+/// A crystal unit cell is replicated into a slab and the cell is elongated along its `c`
+/// vector to make room for a liquid region. Liquid atoms are inserted at random positions,
+/// at least `min_distance` from any other atom, until the region reaches the requested number
+/// density. Species are assigned to the liquid atoms by cycling through the species of the
+/// unit cell so both phases share the same composition.
+///
+/// The crystal and liquid atoms are recorded as the `crystal` and `liquid` residues of the
+/// system's [`Topology`]. Velocities are zero and any topology of the unit cell other than its
+/// partial charges is discarded, with each liquid atom taking the charge of the unit cell atom
+/// whose species it was given.
+/// Positions are drawn from the shared generator of the [`random`](crate::random) module unless
+/// the builder has its own seed.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let unit = System {
+///     size: 4,
+///     cell: Cell::cubic(5.26),
+///     species: vec![argon; 4],
+///     positions: vec![
+///         Vector3::new(0.0, 0.0, 0.0),
+///         Vector3::new(2.63, 2.63, 0.0),
+///         Vector3::new(2.63, 0.0, 2.63),
+///         Vector3::new(0.0, 2.63, 2.63),
+///     ],
+///     velocities: vec![Vector3::zeros(); 4],
+///     topology: Topology::default(),
+/// };
+///
+/// let system = CoexistenceBuilder::new(unit)
+///     .repeats(2, 2, 2)
+///     .liquid(10.52, 0.02)
+///     .build();
+///
+/// assert_eq!(system.topology.residues[0].atoms.len(), 32);
+/// assert!((system.cell.c() - 21.04).abs() < 1e-3);
+/// ```
+#[derive(Clone, Debug)]
+pub struct CoexistenceBuilder {
+    unit: System,
+    repeats: [usize; 3],
+    liquid_length: Float,
+    liquid_density: Float,
+    min_distance: Float,
+    seed: Option<u64>,
+}
+
+impl CoexistenceBuilder {
+    /// Returns a new `CoexistenceBuilder`.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - Periodic unit cell of the crystal phase.
+    pub fn new(unit: System) -> CoexistenceBuilder {
+        CoexistenceBuilder {
+            unit,
+            repeats: [1, 1, 1],
+            liquid_length: 0.0,
+            liquid_density: 0.0,
+            min_distance: 2.0,
+            seed: None,
+        }
+    }
+
+    /// Sets the number of unit cells in the crystal slab along each lattice vector.
+    pub fn repeats(mut self, na: usize, nb: usize, nc: usize) -> CoexistenceBuilder {
+        self.repeats = [na, nb, nc];
+        self
+    }
+
+    /// Sets the liquid region's length along the `c` vector and its number density in atoms per cubic angstrom.
+    pub fn liquid(mut self, length: Float, density: Float) -> CoexistenceBuilder {
+        self.liquid_length = length;
+        self.liquid_density = density;
+        self
+    }
+
+    /// Sets the smallest allowed distance between an inserted liquid atom and any other atom.
+    pub fn min_distance(mut self, distance: Float) -> CoexistenceBuilder {
+        self.min_distance = distance;
+        self
+    }
+
+    /// Draws the liquid positions from a generator seeded with `seed`.
+    pub fn seed(mut self, seed: u64) -> CoexistenceBuilder {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Returns the two-phase [`System`].
+    pub fn build(self) -> System {
+        let [na, nb, nc] = self.repeats;
+        let unit = &self.unit;
+
+        // replicate the unit cell into the crystal slab
+        let mut atoms = Vec::with_capacity(unit.size * na * nb * nc);
+        let mut positions = Vec::with_capacity(unit.size * na * nb * nc);
+        for i in 0..na {
+            for j in 0..nb {
+                for k in 0..nc {
+                    let shift = Vector3::new(i as Float, j as Float, k as Float);
+                    for (n, pos) in unit.positions.iter().enumerate() {
+                        atoms.push(n);
+                        positions.push(unit.cell.cartesian(&(unit.cell.fractional(pos) + shift)));
+                    }
+                }
+            }
+        }
+        let crystal = positions.len();
+
+        // elongate the cell along c to hold the liquid
+        let slab = unit.cell.c() * nc as Float;
+        let length = slab + self.liquid_length;
+        let matrix = unit.cell.matrix();
+        let scale = Matrix3::from_diagonal(&Vector3::new(
+            na as Float,
+            nb as Float,
+            length / unit.cell.c(),
+        ));
+        let cell = Cell::from_matrix(matrix * scale);
+
+        // insert liquid atoms uniformly in the fractional region beyond the slab
+        let volume = cell.volume() * self.liquid_length / length;
+        let count = (self.liquid_density * volume).round() as usize;
+        let start = slab / length;
+        match self.seed {
+            Some(seed) => self.insert_liquid(&cell, start, count, &mut positions, &mut StdRng::seed_from_u64(seed)),
+            None => self.insert_liquid(&cell, start, count, &mut positions, &mut shared_rng()),
+        }
+        atoms.extend((0..count).map(|n| n % unit.size));
+
+        let size = positions.len();
+        let charges = if unit.topology.charges.is_empty() {
+            Vec::new()
+        } else {
+            atoms.iter().map(|&n| unit.topology.charges[n]).collect()
+        };
+        System {
+            size,
+            cell,
+            species: atoms.iter().map(|&n| unit.species[n]).collect(),
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology {
+                residues: vec![
+                    Residue {
+                        name: "crystal".to_string(),
+                        atoms: (0..crystal).collect(),
+                    },
+                    Residue {
+                        name: "liquid".to_string(),
+                        atoms: (crystal..size).collect(),
+                    },
+                ],
+                charges,
+                ..Topology::default()
+            },
+        }
+    }
+
+    // Inserts `count` liquid positions in the fractional region of `cell` beyond `start` along c.
+    fn insert_liquid<R: Rng>(
+        &self,
+        cell: &Cell,
+        start: Float,
+        count: usize,
+        positions: &mut Vec<Vector3<Float>>,
+        rng: &mut R,
+    ) {
+        for n in 0..count {
+            let position = (0..MAX_ATTEMPTS)
+                .map(|_| {
+                    let frac = Vector3::new(
+                        rng.gen::<Float>(),
+                        rng.gen::<Float>(),
+                        start + (1.0 - start) * rng.gen::<Float>(),
+                    );
+                    cell.cartesian(&frac)
+                })
+                .find(|candidate| {
+                    positions
+                        .iter()
+                        .all(|pos| cell.distance(pos, candidate) >= self.min_distance)
+                })
+                .unwrap_or_else(|| {
+                    panic!(
+                        "Unable to insert liquid atom {} of {} with a minimum distance of {}.",
+                        n + 1,
+                        count,
+                        self.min_distance
+                    )
+                });
+            positions.push(position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoexistenceBuilder;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // primitive rock salt cell with two species
+    fn rock_salt() -> System {
+        let na = Species::new(22.99, 1.0);
+        let cl = Species::new(35.45, -1.0);
+        let cell = Cell::triclinic(4.0, 4.0, 4.0, 60.0, 60.0, 60.0);
+        System {
+            size: 2,
+            positions: vec![Vector3::zeros(), cell.cartesian(&Vector3::new(0.5, 0.5, 0.5))],
+            cell,
+            species: vec![na, cl],
+            velocities: vec![Vector3::zeros(); 2],
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn two_phases() {
+        let unit = rock_salt();
+        let system = CoexistenceBuilder::new(unit.clone())
+            .repeats(3, 3, 4)
+            .liquid(16.0, 0.03)
+            .min_distance(1.5)
+            .build();
+
+        // the cell keeps its shape in the a-b plane and is elongated along c
+        assert!((system.cell.a() - 12.0).abs() < 1e-3);
+        assert!((system.cell.c() - 32.0).abs() < 1e-3);
+        assert!((system.cell.alpha() - 60.0).abs() < 1e-3);
+
+        let crystal = &system.topology.residues[0].atoms;
+        let liquid = &system.topology.residues[1].atoms;
+        assert_eq!(crystal.len(), 72);
+        let expected = 0.03 * system.cell.volume() * 0.5;
+        assert_eq!(liquid.len(), expected.round() as usize);
+        assert_eq!(system.size, crystal.len() + liquid.len());
+
+        // liquid atoms occupy the elongated region with an equal composition of both species
+        for &i in liquid {
+            let frac = system.cell.fractional(&system.positions[i]);
+            assert!(frac[2] >= 0.5 - 1e-4);
+            for j in 0..i {
+                assert!(system.cell.distance(&system.positions[i], &system.positions[j]) >= 1.5 - 1e-4);
+            }
+        }
+        let na = liquid.iter().filter(|&&i| system.species[i] == unit.species[0]).count();
+        assert!((na as isize - (liquid.len() - na) as isize).abs() <= 1);
+    }
+
+    #[test]
+    fn seeded() {
+        let builder = CoexistenceBuilder::new(rock_salt()).repeats(2, 2, 2).liquid(8.0, 0.03).min_distance(1.5);
+        let first = builder.clone().seed(5).build();
+        assert_eq!(first.positions, builder.clone().seed(5).build().positions);
+        assert_ne!(first.positions, builder.seed(6).build().positions);
+    }
+}
@@ -0,0 +1,398 @@
+//! Point defects, dislocations, and grain boundaries in crystalline systems.
+
+use nalgebra::{Matrix3, Rotation3, Vector2, Vector3};
+
+use crate::internal::consts::PI;
+use crate::internal::Float;
+use crate::system::cell::Cell;
+use crate::system::cleanup::remove_atoms;
+use crate::system::species::Species;
+use crate::system::topology::{Residue, Topology};
+use crate::system::System;
+
+/// Removes the atom nearest to `position` and returns its former index.
+///
+/// The remaining atoms keep their order and the topology is reindexed as in [`remove_atoms`].
+pub fn vacancy(system: &mut System, position: &Vector3<Float>) -> usize {
+    let atom = (0..system.size)
+        .min_by(|&i, &j| {
+            let a = system.cell.distance(&system.positions[i], position);
+            let b = system.cell.distance(&system.positions[j], position);
+            a.total_cmp(&b)
+        })
+        .expect("A vacancy requires at least one atom.");
+    remove_atoms(system, &[atom]);
+    atom
+}
+
+/// Adds an atom of `species` at rest at `position` and returns its index.
+///
+/// If the system has partial charges the new atom carries the charge of its species.
+pub fn interstitial(system: &mut System, species: Species, position: Vector3<Float>) -> usize {
+    let mut position = position;
+    system.cell.wrap_vector(&mut position);
+    if !system.topology.charges.is_empty() {
+        system.topology.charges.push(species.charge());
+    }
+    system.species.push(species);
+    system.positions.push(position);
+    system.velocities.push(Vector3::zeros());
+    system.size += 1;
+    system.size - 1
+}
+
+/// Returns the formation energy of a defect from the energies of relaxed defective and perfect systems.
+///
+/// The perfect system's energy is scaled to the number of atoms in the defective system, which
+/// gives the formation energy with respect to the bulk crystal as the reservoir of atoms.
+pub fn formation_energy(defect_energy: Float, defect_size: usize, perfect_energy: Float, perfect_size: usize) -> Float {
+    defect_energy - defect_size as Float / perfect_size as Float * perfect_energy
+}
+
+/// Pair of straight dislocations with opposite Burgers vectors along the `z` axis.
+///
+/// The displacement field of each dislocation is the isotropic elastic solution for a mixed
+/// dislocation, with the screw component of the Burgers vector along `z` and the edge component
+/// in the `x-y` plane. The slip plane cut runs between the two cores, so the dipole leaves the
+/// crystal perfect far away from the pair and only a small mismatch remains at the boundaries of
+/// a periodic cell which is large compared to the separation of the cores. The cell should have
+/// its `c` vector along `z`.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::{Vector2, Vector3};
+///
+/// // screw dislocation dipole with a Burgers vector of 2.5 angstroms
+/// let dipole = DislocationDipole::new(
+///     Vector3::new(0.0, 0.0, 2.5),
+///     Vector2::new(10.0, 20.0),
+///     Vector2::new(30.0, 20.0),
+/// );
+/// // atoms just above and below the cut between the cores are displaced by the Burgers vector
+/// let above = dipole.displacement(&Vector3::new(20.0, 20.01, 0.0));
+/// let below = dipole.displacement(&Vector3::new(20.0, 19.99, 0.0));
+/// assert!(((above - below).z.abs() - 2.5).abs() < 1e-2);
+/// ```
+///
+/// # References
+///
+/// [1] Hirth, John Price, and Jens Lothe. "Theory of dislocations." Krieger (1982).
+#[derive(Clone, Copy, Debug)]
+pub struct DislocationDipole {
+    burgers: Vector3<Float>,
+    cores: [Vector2<Float>; 2],
+    poisson: Float,
+}
+
+impl DislocationDipole {
+    /// Returns a new [`DislocationDipole`].
+    ///
+    /// # Arguments
+    ///
+    /// * `burgers` - Burgers vector of the first dislocation in angstroms.
+    /// * `first` - Position of the first core in the `x-y` plane.
+    /// * `second` - Position of the second core, which has the opposite Burgers vector.
+    pub fn new(burgers: Vector3<Float>, first: Vector2<Float>, second: Vector2<Float>) -> DislocationDipole {
+        DislocationDipole {
+            burgers,
+            cores: [first, second],
+            poisson: 0.3,
+        }
+    }
+
+    /// Sets the Poisson ratio of the crystal used by the edge component. Defaults to 0.3.
+    pub fn poisson_ratio(mut self, ratio: Float) -> DislocationDipole {
+        self.poisson = ratio;
+        self
+    }
+
+    /// Returns the displacement of an atom at `position` in the perfect crystal.
+    pub fn displacement(&self, position: &Vector3<Float>) -> Vector3<Float> {
+        let nu = self.poisson;
+        let edge = Vector2::new(self.burgers.x, self.burgers.y);
+        let b = edge.norm();
+        let phi = Float::atan2(edge.y, edge.x);
+        // the branch cuts of both cores point along the line from the first to the second core
+        let d = self.cores[1] - self.cores[0];
+        let psi = Float::atan2(-d.y, -d.x);
+
+        let mut total = Vector3::zeros();
+        for (core, &sign) in self.cores.iter().zip([1.0, -1.0].iter()) {
+            let r = Vector2::new(position.x, position.y) - core;
+            let r2 = r.norm_squared();
+            if r2 < 1e-12 {
+                continue;
+            }
+            let theta = {
+                let (s, c) = (psi.sin(), psi.cos());
+                Float::atan2(-s * r.x + c * r.y, c * r.x + s * r.y)
+            };
+            // edge component in the frame of its Burgers vector
+            let (s, c) = (phi.sin(), phi.cos());
+            let (x, y) = (c * r.x + s * r.y, -s * r.x + c * r.y);
+            let ux = b / (2.0 * PI) * (theta + x * y / (2.0 * (1.0 - nu) * r2));
+            let uy = -b / (2.0 * PI)
+                * ((1.0 - 2.0 * nu) / (4.0 * (1.0 - nu)) * r2.ln() + (x * x - y * y) / (4.0 * (1.0 - nu) * r2));
+            let uz = self.burgers.z / (2.0 * PI) * theta;
+            total += sign * Vector3::new(c * ux - s * uy, s * ux + c * uy, uz);
+        }
+        total
+    }
+
+    /// Displaces every atom of `system` by the field of the dipole and wraps it into the cell.
+    pub fn apply(&self, system: &mut System) {
+        for position in system.positions.iter_mut() {
+            *position += self.displacement(position);
+            system.cell.wrap_vector(position);
+        }
+    }
+}
+
+/// Constructor for a periodic bicrystal with two symmetric tilt grain boundaries.
+///
+/// Two grains of the crystal are rotated by plus and minus half of the misorientation angle about
+/// the `z` axis and fill the lower and upper halves of an orthorhombic cell along `y`. Periodic
+/// boundaries make the cell hold two grain boundaries, in its middle and at its edges. Atoms
+/// closer than `min_distance` to an atom of the other grain are removed from the upper grain.
+///
+/// The cell is only free of spurious boundaries if its length along `x` is a period of both
+/// rotated grains and its length along `z` is a period of the crystal, e.g. `sqrt(10) a` for the
+/// Σ5 (310) boundary of a cubic crystal with a misorientation of 36.87 degrees.
+///
+/// The grains are recorded as the `grain_a` and `grain_b` residues of the system's
+/// [`Topology`]. Velocities are zero and any topology of the unit cell other than its partial
+/// charges is discarded.
+#[derive(Clone, Debug)]
+pub struct Bicrystal {
+    unit: System,
+    angle: Float,
+    lengths: Vector3<Float>,
+    min_distance: Float,
+}
+
+impl Bicrystal {
+    /// Returns a new `Bicrystal` builder.
+    ///
+    /// # Arguments
+    ///
+    /// * `unit` - Periodic unit cell of the crystal.
+    /// * `angle` - Misorientation angle between the grains in degrees.
+    pub fn new(unit: System, angle: Float) -> Bicrystal {
+        let lengths = Vector3::new(unit.cell.a(), 2.0 * unit.cell.b(), unit.cell.c());
+        Bicrystal {
+            unit,
+            angle,
+            lengths,
+            min_distance: 1.0,
+        }
+    }
+
+    /// Sets the lengths of the orthorhombic cell along `x`, `y`, and `z`.
+    pub fn lengths(mut self, x: Float, y: Float, z: Float) -> Bicrystal {
+        self.lengths = Vector3::new(x, y, z);
+        self
+    }
+
+    /// Sets the smallest allowed distance between atoms of different grains.
+    pub fn min_distance(mut self, distance: Float) -> Bicrystal {
+        self.min_distance = distance;
+        self
+    }
+
+    /// Returns the bicrystal [`System`].
+    pub fn build(self) -> System {
+        let cell = Cell::from_matrix(Matrix3::from_diagonal(&self.lengths));
+        let half = self.angle.to_radians() / 2.0;
+        let mut grain_a = self.grain(-half, 0.0, 0.5);
+        let grain_b = self.grain(half, 0.5, 1.0);
+
+        // drop atoms of the second grain which overlap the first across either boundary
+        let boundary = grain_a.len();
+        let kept: Vec<(usize, Vector3<Float>)> = grain_b
+            .into_iter()
+            .filter(|(_, pos)| {
+                grain_a
+                    .iter()
+                    .all(|(_, other)| cell.distance(pos, other) >= self.min_distance)
+            })
+            .collect();
+        grain_a.extend(kept);
+
+        let size = grain_a.len();
+        let (atoms, positions): (Vec<usize>, _) = grain_a.into_iter().unzip();
+        let charges = if self.unit.topology.charges.is_empty() {
+            Vec::new()
+        } else {
+            atoms.iter().map(|&i| self.unit.topology.charges[i]).collect()
+        };
+        System {
+            size,
+            cell,
+            species: atoms.iter().map(|&i| self.unit.species[i]).collect(),
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology {
+                residues: vec![
+                    Residue {
+                        name: "grain_a".to_string(),
+                        atoms: (0..boundary).collect(),
+                    },
+                    Residue {
+                        name: "grain_b".to_string(),
+                        atoms: (boundary..size).collect(),
+                    },
+                ],
+                charges,
+                ..Topology::default()
+            },
+        }
+    }
+
+    // Returns the unit cell index and position of the atoms of the crystal rotated by `angle` about z which fall between the
+    // fractions `low` and `high` of the cell along y.
+    fn grain(&self, angle: Float, low: Float, high: Float) -> Vec<(usize, Vector3<Float>)> {
+        let unit = &self.unit;
+        let rotation = Rotation3::from_axis_angle(&Vector3::z_axis(), angle);
+        let inverse = unit.cell.inverse_matrix();
+        // lattice translations needed to cover the cell from the origin of the rotated crystal
+        let reach: Vec<i64> = (0..3)
+            .map(|k| (self.lengths.norm() * inverse.row(k).norm()).ceil() as i64 + 1)
+            .collect();
+        let (y_low, y_high) = (low * self.lengths.y, high * self.lengths.y);
+        let mut atoms = Vec::new();
+        for i in -reach[0]..=reach[0] {
+            for j in -reach[1]..=reach[1] {
+                for k in -reach[2]..=reach[2] {
+                    let shift = Vector3::new(i as Float, j as Float, k as Float);
+                    for (n, pos) in unit.positions.iter().enumerate() {
+                        let p = rotation * unit.cell.cartesian(&(unit.cell.fractional(pos) + shift));
+                        // half open bounds keep periodic copies of an atom out of the grain
+                        let inside = (0..3).all(|d| p[d] >= -1e-4 && p[d] < self.lengths[d] - 1e-4);
+                        if inside && p.y >= y_low - 1e-4 && p.y < y_high - 1e-4 {
+                            atoms.push((n, p));
+                        }
+                    }
+                }
+            }
+        }
+        atoms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{formation_energy, interstitial, vacancy, Bicrystal, DislocationDipole};
+    use crate::internal::Float;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::{Vector2, Vector3};
+
+    // simple cubic crystal with a lattice constant of 2.5 angstroms
+    fn simple_cubic(n: usize) -> System {
+        let atom = Species::new(39.948, 0.0);
+        let mut positions = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                for k in 0..n {
+                    positions.push(Vector3::new(i as Float, j as Float, k as Float) * 2.5);
+                }
+            }
+        }
+        System {
+            size: positions.len(),
+            cell: Cell::cubic(2.5 * n as Float),
+            species: vec![atom; positions.len()],
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        }
+    }
+
+    #[test]
+    fn point_defects() {
+        let mut system = simple_cubic(3);
+        let removed = vacancy(&mut system, &Vector3::new(2.4, 2.6, 0.1));
+        assert_eq!(removed, 12);
+        assert_eq!(system.size, 26);
+        assert_eq!(system.positions.len(), 26);
+
+        let species = system.species[0];
+        let added = interstitial(&mut system, species, Vector3::new(1.25, 1.25, 8.75));
+        assert_eq!(added, 26);
+        assert_eq!(system.size, 27);
+        // the position is wrapped into the cell
+        assert!((system.positions[26] - Vector3::new(1.25, 1.25, 1.25)).norm() < 1e-5);
+
+        assert!((formation_energy(-26.0, 26, -27.0, 27) - 0.0).abs() < 1e-5);
+        assert!((formation_energy(-25.0, 26, -27.0, 27) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn dislocation_dipole() {
+        let dipole = DislocationDipole::new(
+            Vector3::new(2.5, 0.0, 0.0),
+            Vector2::new(30.0, 20.0),
+            Vector2::new(10.0, 20.0),
+        );
+        // the edge dipole opens a slip of one Burgers vector between the cores
+        let above = dipole.displacement(&Vector3::new(20.0, 20.01, 0.0));
+        let below = dipole.displacement(&Vector3::new(20.0, 19.99, 0.0));
+        assert!(((above - below).x.abs() - 2.5).abs() < 1e-2, "{}", above - below);
+        assert!((above - below).y.abs() < 1e-2);
+        // and is continuous beyond them
+        let above = dipole.displacement(&Vector3::new(40.0, 20.01, 0.0));
+        let below = dipole.displacement(&Vector3::new(40.0, 19.99, 0.0));
+        assert!((above - below).norm() < 1e-2);
+        // the fields of the cores cancel far away
+        assert!(dipole.displacement(&Vector3::new(2000.0, 1500.0, 0.0)).norm() < 1e-2);
+
+        let mut system = simple_cubic(16);
+        let reference = system.positions.clone();
+        dipole.apply(&mut system);
+        for (pos, start) in system.positions.iter().zip(reference.iter()) {
+            let mut expected = start + dipole.displacement(start);
+            system.cell.wrap_vector(&mut expected);
+            assert!(system.cell.distance(pos, &expected) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn sigma5_bicrystal() {
+        let unit = System {
+            size: 1,
+            cell: Cell::cubic(2.5),
+            species: vec![Species::new(39.948, 0.0)],
+            positions: vec![Vector3::zeros()],
+            velocities: vec![Vector3::zeros()],
+            topology: Topology::default(),
+        };
+        let period = Float::sqrt(10.0) * 2.5;
+        let system = Bicrystal::new(unit, 2.0 * Float::atan(1.0 / 3.0).to_degrees())
+            .lengths(period, 4.0 * period, 2.5)
+            .min_distance(1.0)
+            .build();
+
+        // every atom is inside the cell and none overlap
+        for pos in system.positions.iter() {
+            let frac = system.cell.fractional(pos);
+            assert!(frac.iter().all(|&f| (-1e-4..1.0).contains(&f)), "{}", frac);
+        }
+        for i in 0..system.size {
+            for j in 0..i {
+                assert!(system.cell.distance(&system.positions[i], &system.positions[j]) >= 1.0);
+            }
+        }
+        // both grains have the density of the crystal away from the boundaries
+        let expected = system.cell.volume() / 2.5f32.powi(3) as Float;
+        assert!((system.size as Float - expected).abs() < 0.1 * expected, "{} {}", system.size, expected);
+        assert_eq!(system.topology.residues.len(), 2);
+        assert_eq!(system.topology.residues[0].name, "grain_a");
+        let a = system.topology.residues[0].atoms.len();
+        let b = system.topology.residues[1].atoms.len();
+        assert!(a > 0 && b > 0 && a + b == system.size);
+    }
+}
@@ -0,0 +1,358 @@
+//! Comparison of two snapshots of a system.
+
+use std::fmt;
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+use crate::potentials::species_label;
+use crate::system::species::Species;
+use crate::system::topology::Topology;
+use crate::system::System;
+
+/// Number of entries of one kind of connectivity found in only one of two snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConnectivityDiff {
+    /// Entries found only in the second snapshot.
+    pub added: usize,
+    /// Entries found only in the first snapshot.
+    pub removed: usize,
+}
+
+impl ConnectivityDiff {
+    // Compares entries which are equivalent when read in either direction.
+    fn new<T: Ord + Clone>(before: &[T], after: &[T], canonical: impl Fn(&T) -> T) -> ConnectivityDiff {
+        let mut before: Vec<T> = before.iter().map(&canonical).collect();
+        let mut after: Vec<T> = after.iter().map(&canonical).collect();
+        before.sort();
+        after.sort();
+        ConnectivityDiff {
+            added: after.iter().filter(|entry| before.binary_search(entry).is_err()).count(),
+            removed: before.iter().filter(|entry| after.binary_search(entry).is_err()).count(),
+        }
+    }
+
+    /// Returns true if both snapshots contain the same entries.
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0
+    }
+}
+
+/// Differences between two snapshots of a system.
+///
+/// Atoms are matched by their index, so only the atoms present in both snapshots are compared
+/// and a change in size is reported on its own. Displacements follow the minimum image
+/// convention of the second cell, which ignores atoms wrapped back into the cell between the
+/// snapshots. This is handy to check that a restart reproduces the system it was written from
+/// or to see how far a minimization moved the atoms.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let before = System {
+///     size: 2,
+///     cell: Cell::cubic(10.0),
+///     species: vec![argon; 2],
+///     positions: vec![Vector3::new(1.0, 1.0, 1.0), Vector3::new(4.0, 4.0, 4.0)],
+///     velocities: vec![Vector3::zeros(); 2],
+///     topology: Topology::default(),
+/// };
+/// let mut after = before.clone();
+/// after.positions[1].x += 0.5;
+///
+/// let diff = SystemDiff::new(&before, &after);
+/// assert_eq!(diff.max_displacement_atom, Some(1));
+/// assert!(!diff.is_identical());
+/// println!("{}", diff);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SystemDiff {
+    /// Number of atoms in each snapshot.
+    pub sizes: (usize, usize),
+    /// Largest displacement of any atom.
+    pub max_displacement: Float,
+    /// Index of the atom with the largest displacement, if any atoms were compared.
+    pub max_displacement_atom: Option<usize>,
+    /// Mean displacement of the atoms.
+    pub mean_displacement: Float,
+    /// Root mean square displacement of the atoms.
+    pub rms_displacement: Float,
+    /// Largest change in the velocity of any atom.
+    pub max_velocity_change: Float,
+    /// Largest change in the charge of any atom.
+    pub max_charge_change: Float,
+    /// Largest change in any component of the cell matrix.
+    pub max_cell_change: Float,
+    /// Change in the lengths of the cell vectors.
+    pub length_changes: Vector3<Float>,
+    /// Change in the angles between the cell vectors in degrees.
+    pub angle_changes: Vector3<Float>,
+    /// Relative change in the volume of the cell.
+    pub volume_strain: Float,
+    /// Label and count in each snapshot of every species whose count differs.
+    pub composition: Vec<(String, usize, usize)>,
+    /// Number of compared atoms whose species differs.
+    pub species_changes: usize,
+    /// Bonds found in only one snapshot.
+    pub bonds: ConnectivityDiff,
+    /// Angles found in only one snapshot.
+    pub angles: ConnectivityDiff,
+    /// Dihedrals found in only one snapshot.
+    pub dihedrals: ConnectivityDiff,
+    /// Residues found in only one snapshot.
+    pub residues: ConnectivityDiff,
+}
+
+impl SystemDiff {
+    /// Returns the differences from `before` to `after`.
+    pub fn new(before: &System, after: &System) -> SystemDiff {
+        let compared = before.size.min(after.size);
+        let displacements: Vec<Float> = (0..compared)
+            .map(|i| {
+                let mut displacement = after.positions[i] - before.positions[i];
+                after.cell.vector_image(&mut displacement);
+                displacement.norm()
+            })
+            .collect();
+        let (max_displacement_atom, max_displacement) = displacements
+            .iter()
+            .enumerate()
+            .fold((None, 0.0), |(index, max), (i, &d)| match index {
+                Some(_) if d <= max => (index, max),
+                _ => (Some(i), d),
+            });
+        let (mean_displacement, rms_displacement) = if compared == 0 {
+            (0.0, 0.0)
+        } else {
+            let n = compared as Float;
+            let mean = displacements.iter().sum::<Float>() / n;
+            let rms = Float::sqrt(displacements.iter().map(|d| d * d).sum::<Float>() / n);
+            (mean, rms)
+        };
+        let max_velocity_change = (0..compared)
+            .map(|i| (after.velocities[i] - before.velocities[i]).norm())
+            .fold(0.0, Float::max);
+        let max_charge_change = (0..compared)
+            .map(|i| (after.charge(i) - before.charge(i)).abs())
+            .fold(0.0, Float::max);
+
+        let (a, b) = (&before.cell, &after.cell);
+        let max_cell_change = (b.matrix() - a.matrix()).iter().map(|x| x.abs()).fold(0.0, Float::max);
+        let length_changes = Vector3::new(b.a() - a.a(), b.b() - a.b(), b.c() - a.c());
+        let angle_changes = Vector3::new(b.alpha() - a.alpha(), b.beta() - a.beta(), b.gamma() - a.gamma());
+        let volume_strain = b.volume() / a.volume() - 1.0;
+
+        SystemDiff {
+            sizes: (before.size, after.size),
+            max_displacement,
+            max_displacement_atom,
+            mean_displacement,
+            rms_displacement,
+            max_velocity_change,
+            max_charge_change,
+            max_cell_change,
+            length_changes,
+            angle_changes,
+            volume_strain,
+            composition: composition(before, after),
+            species_changes: (0..compared)
+                .filter(|&i| before.species[i] != after.species[i])
+                .count(),
+            bonds: ConnectivityDiff::new(&before.topology.bonds, &after.topology.bonds, |&[i, j]| {
+                [i.min(j), i.max(j)]
+            }),
+            angles: ConnectivityDiff::new(&before.topology.angles, &after.topology.angles, |&[i, j, k]| {
+                [i.min(k), j, i.max(k)]
+            }),
+            dihedrals: ConnectivityDiff::new(&before.topology.dihedrals, &after.topology.dihedrals, |&d| {
+                let reversed = [d[3], d[2], d[1], d[0]];
+                d.min(reversed)
+            }),
+            residues: residues(&before.topology, &after.topology),
+        }
+    }
+
+    /// Returns true if the snapshots match exactly.
+    pub fn is_identical(&self) -> bool {
+        self.sizes.0 == self.sizes.1
+            && self.max_displacement == 0.0
+            && self.max_velocity_change == 0.0
+            && self.max_charge_change == 0.0
+            && self.max_cell_change == 0.0
+            && self.composition.is_empty()
+            && self.species_changes == 0
+            && self.bonds.is_empty()
+            && self.angles.is_empty()
+            && self.dihedrals.is_empty()
+            && self.residues.is_empty()
+    }
+}
+
+impl fmt::Display for SystemDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "atoms:              {} -> {}", self.sizes.0, self.sizes.1)?;
+        match self.max_displacement_atom {
+            Some(atom) => writeln!(f, "max displacement:   {} (atom {})", self.max_displacement, atom)?,
+            None => writeln!(f, "max displacement:   {}", self.max_displacement)?,
+        }
+        writeln!(f, "mean displacement:  {}", self.mean_displacement)?;
+        writeln!(f, "rms displacement:   {}", self.rms_displacement)?;
+        writeln!(f, "velocity change:    {}", self.max_velocity_change)?;
+        writeln!(f, "charge change:      {}", self.max_charge_change)?;
+        writeln!(f, "max cell change:    {}", self.max_cell_change)?;
+        writeln!(
+            f,
+            "length changes:     {} {} {}",
+            self.length_changes.x, self.length_changes.y, self.length_changes.z
+        )?;
+        writeln!(
+            f,
+            "angle changes:      {} {} {}",
+            self.angle_changes.x, self.angle_changes.y, self.angle_changes.z
+        )?;
+        writeln!(f, "volume strain:      {}", self.volume_strain)?;
+        for (label, before, after) in &self.composition {
+            writeln!(f, "composition:        {} {} -> {}", label, before, after)?;
+        }
+        writeln!(f, "species changes:    {}", self.species_changes)?;
+        let connectivity = [
+            ("bonds", self.bonds),
+            ("angles", self.angles),
+            ("dihedrals", self.dihedrals),
+            ("residues", self.residues),
+        ];
+        for (name, diff) in connectivity.iter() {
+            writeln!(f, "{:<20}+{} -{}", format!("{}:", name), diff.added, diff.removed)?;
+        }
+        Ok(())
+    }
+}
+
+// Returns the label and counts of every species whose count differs between the snapshots.
+fn composition(before: &System, after: &System) -> Vec<(String, usize, usize)> {
+    let mut species: Vec<Species> = Vec::new();
+    for sp in before.species.iter().chain(after.species.iter()) {
+        if !species.contains(sp) {
+            species.push(*sp);
+        }
+    }
+    species
+        .into_iter()
+        .map(|sp| {
+            let count = |system: &System| system.species.iter().filter(|&&s| s == sp).count();
+            (species_label(sp), count(before), count(after))
+        })
+        .filter(|(_, before, after)| before != after)
+        .collect()
+}
+
+// Residues match when both their name and atoms are equal.
+fn residues(before: &Topology, after: &Topology) -> ConnectivityDiff {
+    let key = |topology: &Topology| -> Vec<(String, Vec<usize>)> {
+        topology
+            .residues
+            .iter()
+            .map(|residue| {
+                let mut atoms = residue.atoms.clone();
+                atoms.sort_unstable();
+                (residue.name.clone(), atoms)
+            })
+            .collect()
+    };
+    ConnectivityDiff::new(&key(before), &key(after), Clone::clone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SystemDiff;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::{Residue, Topology};
+    use crate::system::System;
+    use approx::*;
+    use nalgebra::Vector3;
+
+    fn water() -> System {
+        let oxygen = Species::new(15.999, -0.834);
+        let hydrogen = Species::new(1.008, 0.417);
+        System {
+            size: 3,
+            cell: Cell::cubic(10.0),
+            species: vec![oxygen, hydrogen, hydrogen],
+            positions: vec![
+                Vector3::new(0.2, 5.0, 5.0),
+                Vector3::new(1.0, 5.5, 5.0),
+                Vector3::new(9.6, 5.5, 5.0),
+            ],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology {
+                bonds: vec![[0, 1], [0, 2]],
+                angles: vec![[1, 0, 2]],
+                dihedrals: Vec::new(),
+                residues: vec![Residue {
+                    name: "SOL".to_string(),
+                    atoms: vec![0, 1, 2],
+                }],
+                groups: Vec::new(),
+                charges: vec![-0.834, 0.417, 0.417],
+                constraints: Vec::new(),
+                frozen: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn identical() {
+        let system = water();
+        let mut copy = system.clone();
+        // wrapping an atom and reversing connectivity changes nothing
+        copy.positions[2].x -= 10.0;
+        copy.topology.bonds = vec![[2, 0], [1, 0]];
+        copy.topology.angles = vec![[2, 0, 1]];
+        let diff = SystemDiff::new(&system, &copy);
+        assert_relative_eq!(diff.max_displacement, 0.0, epsilon = 1e-5);
+        assert!(diff.bonds.is_empty());
+        assert!(diff.angles.is_empty());
+        assert!(SystemDiff::new(&system, &system).is_identical());
+    }
+
+    #[test]
+    fn differences() {
+        let before = water();
+        let mut after = before.clone();
+        after.cell = Cell::cubic(11.0);
+        after.positions[0].x -= 0.4;
+        after.positions[1].y += 0.2;
+        after.velocities[1].z = 0.01;
+        after.topology.charges[0] = -0.8;
+        let argon = Species::new(39.948, 0.0);
+        after.size = 4;
+        after.species.push(argon);
+        after.positions.push(Vector3::zeros());
+        after.velocities.push(Vector3::zeros());
+        after.topology.bonds.pop();
+        after.topology.residues[0].name = "HOH".to_string();
+
+        let diff = SystemDiff::new(&before, &after);
+        assert_eq!(diff.sizes, (3, 4));
+        assert_eq!(diff.max_displacement_atom, Some(0));
+        assert_relative_eq!(diff.max_displacement, 0.4, epsilon = 1e-5);
+        assert_relative_eq!(diff.mean_displacement, 0.2, epsilon = 1e-5);
+        assert_relative_eq!(diff.max_velocity_change, 0.01, epsilon = 1e-6);
+        assert_relative_eq!(diff.max_charge_change, 0.034, epsilon = 1e-6);
+        assert_relative_eq!(diff.length_changes, Vector3::new(1.0, 1.0, 1.0), epsilon = 1e-5);
+        assert_relative_eq!(diff.volume_strain, 1.331 - 1.0, epsilon = 1e-4);
+        assert_eq!(diff.composition.len(), 1);
+        assert_eq!((diff.composition[0].1, diff.composition[0].2), (0, 1));
+        assert_eq!(diff.species_changes, 0);
+        assert_eq!((diff.bonds.added, diff.bonds.removed), (0, 1));
+        assert_eq!((diff.residues.added, diff.residues.removed), (1, 1));
+        assert!(diff.angles.is_empty());
+        assert!(!diff.is_identical());
+        assert!(diff.to_string().contains("bonds:"));
+    }
+}
@@ -1,9 +1,11 @@
 //! Elemental properties.
 
+use strum::IntoEnumIterator;
+
 use crate::internal::Float;
 
 /// Every element on the periodic table.
-#[derive(Clone, Copy, Debug, PartialEq, EnumString, Hash, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, EnumIter, EnumString, IntoStaticStr, Hash, Eq)]
 pub enum Element {
     /// Hydrogen
     H,
@@ -192,6 +194,25 @@ pub enum Element {
 }
 
 impl Element {
+    /// Returns the element with atomic number `number` if one exists.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    ///
+    /// assert_eq!(Element::from_number(18), Some(Element::Ar));
+    /// assert_eq!(Element::from_number(0), None);
+    /// ```
+    pub fn from_number(number: u8) -> Option<Element> {
+        Element::iter().find(|element| element.number() == number)
+    }
+
+    /// Returns the chemical symbol of the element.
+    pub fn symbol(&self) -> &'static str {
+        self.into()
+    }
+
     /// Returns the atomic mass of the element in amu.
     pub const fn mass(&self) -> Float {
         match self {
@@ -485,6 +506,108 @@ impl Element {
             Element::U => 92,
         }
     }
+
+    /// Returns the single bond covalent radius of the element in angstroms.
+    ///
+    /// # References
+    ///
+    /// [1] Cordero, Beatriz, et al. "Covalent radii revisited." Dalton Transactions 21 (2008): 2832-2838.
+    pub const fn covalent_radius(&self) -> Float {
+        match self {
+            Element::H => 0.31,
+            Element::He => 0.28,
+            Element::Li => 1.28,
+            Element::Be => 0.96,
+            Element::B => 0.84,
+            Element::C => 0.76,
+            Element::N => 0.71,
+            Element::O => 0.66,
+            Element::F => 0.57,
+            Element::Ne => 0.58,
+            Element::Na => 1.66,
+            Element::Mg => 1.41,
+            Element::Al => 1.21,
+            Element::Si => 1.11,
+            Element::P => 1.07,
+            Element::S => 1.05,
+            Element::Cl => 1.02,
+            Element::Ar => 1.06,
+            Element::K => 2.03,
+            Element::Ca => 1.76,
+            Element::Sc => 1.70,
+            Element::Ti => 1.60,
+            Element::V => 1.53,
+            Element::Cr => 1.39,
+            Element::Mn => 1.39,
+            Element::Fe => 1.32,
+            Element::Co => 1.26,
+            Element::Ni => 1.24,
+            Element::Cu => 1.32,
+            Element::Zn => 1.22,
+            Element::Ga => 1.22,
+            Element::Ge => 1.20,
+            Element::As => 1.19,
+            Element::Se => 1.20,
+            Element::Br => 1.20,
+            Element::Kr => 1.16,
+            Element::Rb => 2.20,
+            Element::Sr => 1.95,
+            Element::Y => 1.90,
+            Element::Zr => 1.75,
+            Element::Nb => 1.64,
+            Element::Mo => 1.54,
+            Element::Tc => 1.47,
+            Element::Ru => 1.46,
+            Element::Rh => 1.42,
+            Element::Pd => 1.39,
+            Element::Ag => 1.45,
+            Element::Cd => 1.44,
+            Element::In => 1.42,
+            Element::Sn => 1.39,
+            Element::Sb => 1.39,
+            Element::Te => 1.38,
+            Element::I => 1.39,
+            Element::Xe => 1.40,
+            Element::Cs => 2.44,
+            Element::Ba => 2.15,
+            Element::La => 2.07,
+            Element::Ce => 2.04,
+            Element::Pr => 2.03,
+            Element::Nd => 2.01,
+            Element::Pm => 1.99,
+            Element::Sm => 1.98,
+            Element::Eu => 1.98,
+            Element::Gd => 1.96,
+            Element::Tb => 1.94,
+            Element::Dy => 1.92,
+            Element::Ho => 1.92,
+            Element::Er => 1.89,
+            Element::Tm => 1.90,
+            Element::Yb => 1.87,
+            Element::Lu => 1.87,
+            Element::Hf => 1.75,
+            Element::Ta => 1.70,
+            Element::W => 1.62,
+            Element::Re => 1.51,
+            Element::Os => 1.44,
+            Element::Ir => 1.41,
+            Element::Pt => 1.36,
+            Element::Au => 1.36,
+            Element::Hg => 1.32,
+            Element::Tl => 1.45,
+            Element::Pb => 1.46,
+            Element::Bi => 1.48,
+            Element::Po => 1.40,
+            Element::At => 1.50,
+            Element::Rn => 1.50,
+            Element::Fr => 2.60,
+            Element::Ra => 2.21,
+            Element::Ac => 2.15,
+            Element::Th => 2.06,
+            Element::Pa => 2.00,
+            Element::U => 1.96,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -498,6 +621,21 @@ mod tests {
         assert_eq!(Element::H, hydrogen)
     }
 
+    #[test]
+    fn symbol_round_trip() {
+        for number in 1..=92 {
+            let element = Element::from_number(number).unwrap();
+            assert_eq!(Element::from_str(element.symbol()).unwrap(), element);
+        }
+    }
+
+    #[test]
+    fn covalent_radius() {
+        // the C-C single bond length is twice the covalent radius of carbon
+        assert!((2.0 * Element::C.covalent_radius() - 1.52).abs() < 1e-6);
+        assert!(Element::Cs.covalent_radius() > Element::Na.covalent_radius());
+    }
+
     #[test]
     #[should_panic]
     fn from_str_invalid() {
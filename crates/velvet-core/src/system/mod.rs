@@ -1,26 +1,245 @@
 //! Data structures to hold physical information about the simulation environment.
 
 pub mod cell;
+pub mod charges;
+pub mod cleanup;
+pub mod coexistence;
+pub mod defects;
+pub mod diff;
 pub mod elements;
+pub mod select;
+pub mod soa;
 pub mod species;
+pub mod topology;
 
 use nalgebra::Vector3;
 
+use crate::errors::VelvetError;
 use crate::internal::Float;
+use crate::precision::Real;
 use crate::system::cell::Cell;
 use crate::system::species::Species;
+use crate::system::topology::Topology;
 
 /// Collection of atomic properties and bonding information.
+///
+/// Positions, velocities, and the cell are stored in the [`Real`] precision `R`. Species and
+/// topology parameters such as masses and charges are always stored in the default precision.
 #[derive(Clone, Debug)]
-pub struct System {
+pub struct System<R: Real = Float> {
     /// Number of atoms in the system.
     pub size: usize,
     /// Simulation cell with periodic boundary conditions.
-    pub cell: Cell,
+    pub cell: Cell<R>,
     /// Chemical species of each atom in the system.
     pub species: Vec<Species>,
     /// Position of each atom in the system.
-    pub positions: Vec<Vector3<Float>>,
+    pub positions: Vec<Vector3<R>>,
     /// Velocity of each atom in the system.
-    pub velocities: Vec<Vector3<Float>>,
+    pub velocities: Vec<Vector3<R>>,
+    /// Bonded connectivity between atoms.
+    pub topology: Topology,
+}
+
+impl<R: Real> System<R> {
+    /// Returns the system with its positions, velocities, and cell converted to another precision.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use velvet_core::prelude::*;
+    /// use nalgebra::Vector3;
+    ///
+    /// let system = System {
+    ///     size: 1,
+    ///     cell: Cell::cubic(10.0),
+    ///     species: vec![Species::from_element(Element::Ar)],
+    ///     positions: vec![Vector3::new(1.0f32, 2.0, 3.0)],
+    ///     velocities: vec![Vector3::zeros()],
+    ///     topology: Topology::default(),
+    /// };
+    /// let double: System<f64> = system.cast();
+    /// assert_eq!(double.positions[0], Vector3::new(1.0, 2.0, 3.0));
+    /// ```
+    pub fn cast<S: Real>(&self) -> System<S> {
+        let convert = |vectors: &[Vector3<R>]| vectors.iter().map(|v| v.map(R::cast)).collect();
+        System {
+            size: self.size,
+            cell: self.cell.cast(),
+            species: self.species.clone(),
+            positions: convert(&self.positions),
+            velocities: convert(&self.velocities),
+            topology: self.topology.clone(),
+        }
+    }
+
+    /// Returns the charge of atom `index`.
+    ///
+    /// This is the atom's partial charge in the topology if any are set and the charge of its
+    /// species otherwise.
+    pub fn charge(&self, index: usize) -> Float {
+        match self.topology.charges.get(index) {
+            Some(&charge) => charge,
+            None => self.species[index].charge(),
+        }
+    }
+
+    /// Returns the charge of each atom as given by [`System::charge`].
+    pub fn charges(&self) -> Vec<Float> {
+        (0..self.size).map(|i| self.charge(i)).collect()
+    }
+
+    /// Sets the partial charge of atom `index`.
+    ///
+    /// If the topology holds no partial charges yet every other atom is given the charge of its species.
+    pub fn set_charge(&mut self, index: usize, charge: Float) {
+        if self.topology.charges.is_empty() {
+            self.topology.charges = self.charges();
+        }
+        self.topology.charges[index] = charge;
+    }
+
+    /// Returns the number of degrees of freedom of the atoms, which is three per mobile atom less
+    /// one for each constrained distance in the topology.
+    pub fn degrees_of_freedom(&self) -> usize {
+        (3 * self.size).saturating_sub(self.topology.constraints.len() + 3 * self.topology.frozen.len())
+    }
+
+    /// Returns a mask which is `true` for each atom held fixed by the topology.
+    pub fn frozen_mask(&self) -> Vec<bool> {
+        let mut mask = vec![false; self.size];
+        for &i in &self.topology.frozen {
+            mask[i] = true;
+        }
+        mask
+    }
+
+    /// Checks that the per-atom data matches the number of atoms and that every bond, angle,
+    /// dihedral, residue, group, constraint, and frozen atom references atoms of the system.
+    pub fn validate(&self) -> Result<(), VelvetError> {
+        let lengths = [
+            ("species", self.species.len()),
+            ("positions", self.positions.len()),
+            ("velocities", self.velocities.len()),
+        ];
+        for &(field, found) in lengths.iter() {
+            if found != self.size {
+                return Err(VelvetError::LengthMismatch {
+                    field,
+                    expected: self.size,
+                    found,
+                });
+            }
+        }
+        let topology = &self.topology;
+        if !topology.charges.is_empty() && topology.charges.len() != self.size {
+            return Err(VelvetError::LengthMismatch {
+                field: "charges",
+                expected: self.size,
+                found: topology.charges.len(),
+            });
+        }
+        let entries = topology
+            .bonds
+            .iter()
+            .flat_map(|bond| bond.iter().map(|&i| ("bond", i)))
+            .chain(topology.angles.iter().flat_map(|angle| angle.iter().map(|&i| ("angle", i))))
+            .chain(topology.dihedrals.iter().flat_map(|dihedral| dihedral.iter().map(|&i| ("dihedral", i))))
+            .chain(topology.residues.iter().flat_map(|residue| residue.atoms.iter().map(|&i| ("residue", i))))
+            .chain(topology.groups.iter().flat_map(|group| group.atoms.iter().map(|&i| ("group", i))))
+            .chain(topology.constraints.iter().flat_map(|pair| pair.iter().map(|&i| ("constraint", i))))
+            .chain(topology.frozen.iter().map(|&i| ("frozen atom", i)));
+        for (entry, index) in entries {
+            if index >= self.size {
+                return Err(VelvetError::IndexOutOfRange {
+                    entry,
+                    index,
+                    size: self.size,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::System;
+    use crate::errors::VelvetError;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn validate() {
+        let mut system = System {
+            size: 3,
+            cell: Cell::cubic(10.0),
+            species: vec![Species::new(1.0, 0.0); 3],
+            positions: vec![Vector3::zeros(); 3],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology::default(),
+        };
+        system.topology.bonds = vec![[0, 1], [1, 2]];
+        system.topology.angles = vec![[0, 1, 2]];
+        assert!(system.validate().is_ok());
+
+        system.topology.dihedrals = vec![[0, 1, 2, 3]];
+        match system.validate() {
+            Err(VelvetError::IndexOutOfRange { entry, index, size }) => {
+                assert_eq!((entry, index, size), ("dihedral", 3, 3));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        system.topology.dihedrals.clear();
+        system.topology.constraints = vec![[0, 1], [1, 4]];
+        assert!(matches!(
+            system.validate(),
+            Err(VelvetError::IndexOutOfRange { entry: "constraint", index: 4, .. })
+        ));
+
+        system.topology.constraints.pop();
+        assert_eq!(system.degrees_of_freedom(), 8);
+        system.topology.frozen = vec![2, 3];
+        assert!(matches!(
+            system.validate(),
+            Err(VelvetError::IndexOutOfRange { entry: "frozen atom", index: 3, .. })
+        ));
+
+        system.topology.frozen.pop();
+        assert_eq!(system.degrees_of_freedom(), 5);
+        assert_eq!(system.frozen_mask(), vec![false, false, true]);
+        system.topology.frozen.clear();
+        system.topology.charges = vec![0.5, -0.5];
+        assert!(matches!(
+            system.validate(),
+            Err(VelvetError::LengthMismatch { field: "charges", .. })
+        ));
+
+        system.topology.charges.clear();
+        system.velocities.pop();
+        assert!(matches!(
+            system.validate(),
+            Err(VelvetError::LengthMismatch { found: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn charges() {
+        let mut system = System {
+            size: 3,
+            cell: Cell::cubic(10.0),
+            species: vec![Species::new(1.0, 0.5); 3],
+            positions: vec![Vector3::zeros(); 3],
+            velocities: vec![Vector3::zeros(); 3],
+            topology: Topology::default(),
+        };
+        assert_eq!(system.charges(), vec![0.5; 3]);
+        // setting one partial charge keeps the species charge of the others
+        system.set_charge(1, -1.0);
+        assert_eq!(system.charges(), vec![0.5, -1.0, 0.5]);
+        assert_eq!(system.species[1].charge(), 0.5);
+    }
 }
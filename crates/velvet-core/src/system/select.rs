@@ -0,0 +1,437 @@
+//! Selection of atoms by their properties, connectivity, and location.
+
+use std::ops::Not;
+use std::str::FromStr;
+
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::system::elements::Element;
+use crate::system::species::Species;
+use crate::system::System;
+
+/// Set of atoms chosen by their properties, connectivity, or location.
+///
+/// A selection is resolved against a system with [`indices`](AtomSelection::indices), which
+/// returns indices usable wherever atoms are chosen by index, such as the atoms of a
+/// [`PositionRestraint`](crate::potentials::restraints::PositionRestraint) or wall, the atoms of
+/// an [`Rmsd`](crate::properties::rmsd::Rmsd), or the atoms written by a
+/// [`FrameSelection`](crate::outputs::trajectory::FrameSelection). Selections are built from
+/// their variants and combined with [`and`](AtomSelection::and), [`or`](AtomSelection::or), and
+/// the `!` operator, or parsed from a small language with
+/// [`parse`](AtomSelection::parse).
+///
+/// # Syntax
+///
+/// * `all` - Every atom.
+/// * `index 0 4 10..20` - Atoms with the given indices.
+/// * `element O H` - Atoms of the given elements.
+/// * `residue SOL LIG` - Atoms of residues with the given names.
+/// * `group fixed` - Atoms of the [`Group`](crate::system::topology::Group)s with the given names.
+/// * `molecule 0..=9` - Atoms of the molecules formed by bonded atoms, counted from zero as
+///   returned by [`Topology::molecules`](crate::system::topology::Topology::molecules).
+/// * `z 10.0..20.0` - Atoms whose `x`, `y`, or `z` coordinate lies in the half-open range.
+/// * `not`, `and`, `or`, and parentheses combine selections, in order of decreasing precedence.
+///
+/// Ranges of indices `a..b` exclude their end and `a..=b` includes it.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let oxygen = Species::from_element(Element::O);
+/// let hydrogen = Species::from_element(Element::H);
+/// let system = System {
+///     size: 3,
+///     cell: Cell::cubic(10.0),
+///     species: vec![oxygen, hydrogen, hydrogen],
+///     positions: vec![
+///         Vector3::new(5.0, 5.0, 5.0),
+///         Vector3::new(5.8, 5.6, 5.0),
+///         Vector3::new(4.2, 5.6, 5.0),
+///     ],
+///     velocities: vec![Vector3::zeros(); 3],
+///     topology: Topology::default(),
+/// };
+///
+/// let selection = AtomSelection::parse("element H and x 5.0..10.0").unwrap();
+/// assert_eq!(selection.indices(&system), vec![1]);
+///
+/// // restrain the oxygen atoms
+/// let oxygens = AtomSelection::Elements(vec![Element::O]).indices(&system);
+/// let restraint = PositionRestraint::new(10.0).tether(&system, &oxygens);
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub enum AtomSelection {
+    /// Every atom.
+    All,
+    /// Atoms with the given indices.
+    Indices(Vec<usize>),
+    /// Atoms of the given elements.
+    Elements(Vec<Element>),
+    /// Atoms of the given species.
+    Species(Vec<Species>),
+    /// Atoms of residues with the given names.
+    Residues(Vec<String>),
+    /// Atoms of groups with the given names.
+    Groups(Vec<String>),
+    /// Atoms of the molecules with the given indices.
+    Molecules(Vec<usize>),
+    /// Atoms whose coordinate along a Cartesian axis lies in the half-open range from `low` to `high`.
+    Region {
+        /// Cartesian axis, 0 for `x`, 1 for `y`, and 2 for `z`.
+        axis: usize,
+        /// Lower bound of the range.
+        low: Float,
+        /// Upper bound of the range.
+        high: Float,
+    },
+    /// Atoms in both selections.
+    And(Box<AtomSelection>, Box<AtomSelection>),
+    /// Atoms in either selection.
+    Or(Box<AtomSelection>, Box<AtomSelection>),
+    /// Atoms not in the selection.
+    Not(Box<AtomSelection>),
+}
+
+impl AtomSelection {
+    /// Parses a selection from its string syntax.
+    pub fn parse(text: &str) -> Result<AtomSelection, VelvetError> {
+        let spaced = text.replace('(', " ( ").replace(')', " ) ");
+        let tokens: Vec<&str> = spaced.split_whitespace().collect();
+        let mut parser = Parser { tokens, position: 0 };
+        let selection = parser.or()?;
+        match parser.peek() {
+            None => Ok(selection),
+            Some(token) => Err(parser.error(format!("unexpected `{}`", token))),
+        }
+    }
+
+    /// Returns the atoms in both this selection and `other`.
+    pub fn and(self, other: AtomSelection) -> AtomSelection {
+        AtomSelection::And(Box::new(self), Box::new(other))
+    }
+
+    /// Returns the atoms in either this selection or `other`.
+    pub fn or(self, other: AtomSelection) -> AtomSelection {
+        AtomSelection::Or(Box::new(self), Box::new(other))
+    }
+
+    /// Returns the sorted indices of the selected atoms of `system`.
+    pub fn indices(&self, system: &System) -> Vec<usize> {
+        self.mask(system)
+            .iter()
+            .enumerate()
+            .filter(|(_, &selected)| selected)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // Returns whether each atom of `system` is selected.
+    fn mask(&self, system: &System) -> Vec<bool> {
+        let mut mask = vec![false; system.size];
+        match self {
+            AtomSelection::All => mask.iter_mut().for_each(|selected| *selected = true),
+            AtomSelection::Indices(indices) => {
+                for &i in indices.iter().filter(|&&i| i < system.size) {
+                    mask[i] = true;
+                }
+            }
+            AtomSelection::Elements(elements) => {
+                for (selected, species) in mask.iter_mut().zip(system.species.iter()) {
+                    *selected = species.element().is_some_and(|element| elements.contains(&element));
+                }
+            }
+            AtomSelection::Species(species) => {
+                for (selected, sp) in mask.iter_mut().zip(system.species.iter()) {
+                    *selected = species.contains(sp);
+                }
+            }
+            AtomSelection::Residues(names) => {
+                let residues = system.topology.residues.iter();
+                for residue in residues.filter(|residue| names.contains(&residue.name)) {
+                    for &i in residue.atoms.iter().filter(|&&i| i < system.size) {
+                        mask[i] = true;
+                    }
+                }
+            }
+            AtomSelection::Groups(names) => {
+                let groups = system.topology.groups.iter();
+                for group in groups.filter(|group| names.contains(&group.name)) {
+                    for &i in group.atoms.iter().filter(|&&i| i < system.size) {
+                        mask[i] = true;
+                    }
+                }
+            }
+            AtomSelection::Molecules(indices) => {
+                let molecules = system.topology.molecules();
+                for molecule in indices.iter().filter_map(|&m| molecules.get(m)) {
+                    for &i in molecule.iter().filter(|&&i| i < system.size) {
+                        mask[i] = true;
+                    }
+                }
+            }
+            AtomSelection::Region { axis, low, high } => {
+                for (selected, position) in mask.iter_mut().zip(system.positions.iter()) {
+                    *selected = position[*axis] >= *low && position[*axis] < *high;
+                }
+            }
+            AtomSelection::And(a, b) => {
+                mask = a.mask(system);
+                mask.iter_mut().zip(b.mask(system)).for_each(|(x, y)| *x &= y);
+            }
+            AtomSelection::Or(a, b) => {
+                mask = a.mask(system);
+                mask.iter_mut().zip(b.mask(system)).for_each(|(x, y)| *x |= y);
+            }
+            AtomSelection::Not(a) => {
+                mask = a.mask(system);
+                mask.iter_mut().for_each(|selected| *selected = !*selected);
+            }
+        }
+        mask
+    }
+}
+
+impl Not for AtomSelection {
+    type Output = AtomSelection;
+
+    fn not(self) -> AtomSelection {
+        AtomSelection::Not(Box::new(self))
+    }
+}
+
+impl FromStr for AtomSelection {
+    type Err = VelvetError;
+
+    fn from_str(text: &str) -> Result<AtomSelection, VelvetError> {
+        AtomSelection::parse(text)
+    }
+}
+
+// Recursive descent parser of the selection syntax.
+struct Parser<'a> {
+    tokens: Vec<&'a str>,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, message: String) -> VelvetError {
+        VelvetError::parse("selection", format!("{} at token {}", message, self.position + 1))
+    }
+
+    fn or(&mut self) -> Result<AtomSelection, VelvetError> {
+        let mut selection = self.and()?;
+        while self.peek() == Some("or") {
+            self.next();
+            selection = selection.or(self.and()?);
+        }
+        Ok(selection)
+    }
+
+    fn and(&mut self) -> Result<AtomSelection, VelvetError> {
+        let mut selection = self.unary()?;
+        while self.peek() == Some("and") {
+            self.next();
+            selection = selection.and(self.unary()?);
+        }
+        Ok(selection)
+    }
+
+    fn unary(&mut self) -> Result<AtomSelection, VelvetError> {
+        match self.next() {
+            Some("not") => Ok(self.unary()?.not()),
+            Some("(") => {
+                let selection = self.or()?;
+                match self.next() {
+                    Some(")") => Ok(selection),
+                    _ => Err(self.error("expected `)`".to_string())),
+                }
+            }
+            Some("all") => Ok(AtomSelection::All),
+            Some("index") => Ok(AtomSelection::Indices(self.indices()?)),
+            Some("molecule") => Ok(AtomSelection::Molecules(self.indices()?)),
+            Some("element") => {
+                let elements = self
+                    .arguments()
+                    .iter()
+                    .map(|symbol| {
+                        Element::from_str(symbol).map_err(|_| self.error(format!("unknown element `{}`", symbol)))
+                    })
+                    .collect::<Result<Vec<Element>, VelvetError>>()?;
+                self.nonempty(AtomSelection::Elements(elements), "element")
+            }
+            Some("residue") => {
+                let names = self.arguments().iter().map(|name| name.to_string()).collect();
+                self.nonempty(AtomSelection::Residues(names), "residue")
+            }
+            Some("group") => {
+                let names = self.arguments().iter().map(|name| name.to_string()).collect();
+                self.nonempty(AtomSelection::Groups(names), "group")
+            }
+            Some(axis @ "x") | Some(axis @ "y") | Some(axis @ "z") => {
+                let axis = ["x", "y", "z"].iter().position(|&a| a == axis).unwrap();
+                let range = self.next().unwrap_or("");
+                let bounds: Vec<Result<Float, _>> = range.splitn(2, "..").map(str::parse).collect();
+                match bounds.as_slice() {
+                    [Ok(low), Ok(high)] => Ok(AtomSelection::Region {
+                        axis,
+                        low: *low,
+                        high: *high,
+                    }),
+                    _ => Err(self.error(format!("expected a range of coordinates, found `{}`", range))),
+                }
+            }
+            Some(token) => Err(self.error(format!("unexpected `{}`", token))),
+            None => Err(self.error("unexpected end of selection".to_string())),
+        }
+    }
+
+    // Consumes the arguments of a keyword up to the next operator or parenthesis.
+    fn arguments(&mut self) -> Vec<&'a str> {
+        let mut arguments = Vec::new();
+        while let Some(token) = self.peek() {
+            if ["and", "or", "not", "(", ")"].contains(&token) {
+                break;
+            }
+            arguments.push(token);
+            self.position += 1;
+        }
+        arguments
+    }
+
+    fn nonempty(&self, selection: AtomSelection, keyword: &str) -> Result<AtomSelection, VelvetError> {
+        let empty = match &selection {
+            AtomSelection::Indices(values) | AtomSelection::Molecules(values) => values.is_empty(),
+            AtomSelection::Elements(values) => values.is_empty(),
+            AtomSelection::Residues(values) | AtomSelection::Groups(values) => values.is_empty(),
+            _ => false,
+        };
+        if empty {
+            Err(self.error(format!("expected arguments after `{}`", keyword)))
+        } else {
+            Ok(selection)
+        }
+    }
+
+    fn indices(&mut self) -> Result<Vec<usize>, VelvetError> {
+        let keyword = self.tokens[self.position - 1];
+        let mut indices = Vec::new();
+        for argument in self.arguments() {
+            let invalid = || self.error(format!("invalid index `{}`", argument));
+            let parse = |value: &str| value.parse::<usize>().map_err(|_| invalid());
+            if let Some((first, last)) = argument.split_once("..=") {
+                indices.extend(parse(first)?..=parse(last)?);
+            } else if let Some((first, end)) = argument.split_once("..") {
+                indices.extend(parse(first)?..parse(end)?);
+            } else {
+                indices.push(parse(argument)?);
+            }
+        }
+        match self.nonempty(AtomSelection::Indices(indices), keyword)? {
+            AtomSelection::Indices(indices) => Ok(indices),
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AtomSelection;
+    use crate::internal::Float;
+    use crate::system::cell::Cell;
+    use crate::system::elements::Element;
+    use crate::system::species::Species;
+    use crate::system::topology::{Group, Residue, Topology};
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    // two water molecules and a sodium ion along x
+    fn solution() -> System {
+        let oxygen = Species::from_element(Element::O);
+        let hydrogen = Species::from_element(Element::H);
+        let sodium = Species::from_element(Element::Na);
+        System {
+            size: 7,
+            cell: Cell::cubic(20.0),
+            species: vec![oxygen, hydrogen, hydrogen, oxygen, hydrogen, hydrogen, sodium],
+            positions: (0..7).map(|i| Vector3::new(i as Float, 0.0, 0.0)).collect(),
+            velocities: vec![Vector3::zeros(); 7],
+            topology: Topology {
+                bonds: vec![[0, 1], [0, 2], [3, 4], [3, 5]],
+                residues: vec![
+                    Residue {
+                        name: "SOL".to_string(),
+                        atoms: vec![0, 1, 2, 3, 4, 5],
+                    },
+                    Residue {
+                        name: "NA".to_string(),
+                        atoms: vec![6],
+                    },
+                ],
+                groups: vec![Group {
+                    name: "ions".to_string(),
+                    atoms: vec![6],
+                }],
+                ..Topology::default()
+            },
+        }
+    }
+
+    #[test]
+    fn keywords() {
+        let system = solution();
+        let select = |text: &str| AtomSelection::parse(text).unwrap().indices(&system);
+        assert_eq!(select("all").len(), 7);
+        assert_eq!(select("index 1 5..=6"), vec![1, 5, 6]);
+        assert_eq!(select("element O Na"), vec![0, 3, 6]);
+        assert_eq!(select("residue NA"), vec![6]);
+        assert_eq!(select("group ions"), vec![6]);
+        assert_eq!(select("molecule 1"), vec![3, 4, 5]);
+        assert_eq!(select("x 2.0..4.5"), vec![2, 3, 4]);
+    }
+
+    #[test]
+    fn operators() {
+        let system = solution();
+        let select = |text: &str| AtomSelection::parse(text).unwrap().indices(&system);
+        assert_eq!(select("element H and molecule 0..1"), vec![1, 2]);
+        assert_eq!(select("not residue SOL or index 0"), vec![0, 6]);
+        assert_eq!(select("not (residue SOL or index 0)"), vec![6]);
+        assert_eq!(select("element O and not (x 0.0..1.0 or molecule 5)"), vec![3]);
+
+        let built = AtomSelection::Elements(vec![Element::H]).and(AtomSelection::Molecules(vec![0]));
+        assert_eq!(built.indices(&system), vec![1, 2]);
+        assert_eq!("element H and molecule 0".parse::<AtomSelection>().unwrap(), built);
+    }
+
+    #[test]
+    fn errors() {
+        for text in [
+            "",
+            "element",
+            "element Xx",
+            "index a",
+            "residue SOL and",
+            "group",
+            "(all",
+            "all all",
+            "z 1.0",
+        ]
+        .iter()
+        {
+            assert!(AtomSelection::parse(text).is_err(), "`{}` should not parse", text);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Structure-of-arrays storage of per-atom vectors.
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+
+/// Per-atom vectors stored as separate arrays of their x, y, and z components.
+///
+/// [`System`](crate::system::System) stores each position as a `Vector3`, which interleaves the
+/// components in memory. Kernels which load one component of several atoms into the lanes of a
+/// SIMD register read contiguous memory from this layout instead.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Soa3 {
+    /// Components along the x axis.
+    pub x: Vec<Float>,
+    /// Components along the y axis.
+    pub y: Vec<Float>,
+    /// Components along the z axis.
+    pub z: Vec<Float>,
+}
+
+impl Soa3 {
+    /// Returns `len` zero vectors.
+    pub fn zeros(len: usize) -> Soa3 {
+        Soa3 {
+            x: vec![0.0; len],
+            y: vec![0.0; len],
+            z: vec![0.0; len],
+        }
+    }
+
+    /// Returns the number of vectors.
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Returns true if there are no vectors.
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Returns the vector at `index`.
+    pub fn get(&self, index: usize) -> Vector3<Float> {
+        Vector3::new(self.x[index], self.y[index], self.z[index])
+    }
+
+    /// Adds `vector` to the vector at `index`.
+    pub fn add(&mut self, index: usize, vector: &Vector3<Float>) {
+        self.x[index] += vector.x;
+        self.y[index] += vector.y;
+        self.z[index] += vector.z;
+    }
+
+    /// Returns the vectors in the array-of-structures layout used by [`System`](crate::system::System).
+    pub fn to_vectors(&self) -> Vec<Vector3<Float>> {
+        (0..self.len()).map(|i| self.get(i)).collect()
+    }
+}
+
+impl From<&[Vector3<Float>]> for Soa3 {
+    fn from(vectors: &[Vector3<Float>]) -> Soa3 {
+        Soa3 {
+            x: vectors.iter().map(|v| v.x).collect(),
+            y: vectors.iter().map(|v| v.y).collect(),
+            z: vectors.iter().map(|v| v.z).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Soa3;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn round_trip() {
+        let vectors = vec![Vector3::new(1.0, 2.0, 3.0), Vector3::new(-4.0, 5.0, -6.0)];
+        let mut soa = Soa3::from(&vectors[..]);
+        assert_eq!(soa.len(), 2);
+        assert_eq!(soa.y, vec![2.0, 5.0]);
+        assert_eq!(soa.get(1), vectors[1]);
+        assert_eq!(soa.to_vectors(), vectors);
+        soa.add(0, &Vector3::new(1.0, 1.0, 1.0));
+        assert_eq!(soa.get(0), Vector3::new(2.0, 3.0, 4.0));
+        assert!(Soa3::zeros(0).is_empty());
+    }
+}
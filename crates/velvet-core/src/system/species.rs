@@ -44,6 +44,22 @@ impl Species {
         }
     }
 
+    /// Restores a [`Species`] from a previously issued unique ID.
+    ///
+    /// Species with equal IDs are considered equivalent so this should only be used
+    /// to reconstruct species which were serialized from an existing system.
+    pub fn from_id(id: u128, mass: Float, charge: Float) -> Species {
+        Species { id, mass, charge }
+    }
+
+    /// Returns the [`Element`] the species was constructed from, if any.
+    pub fn element(&self) -> Option<Element> {
+        if self.id > u8::MAX as u128 {
+            return None;
+        }
+        Element::from_number(self.id as u8)
+    }
+
     /// Returns the species' unique ID.
     pub fn id(&self) -> u128 {
         self.id
@@ -84,6 +100,15 @@ mod tests {
         assert_eq!(species.mass(), element.mass());
         assert_eq!(species.charge(), element.charge());
         assert_eq!(species.id(), element.number() as u128);
+        assert_eq!(species.element(), Some(element));
+        assert_eq!(Species::new(1.0, 0.0).element(), None);
+    }
+
+    #[test]
+    fn from_id() {
+        let species = Species::new(1.0, -1.0);
+        let restored = Species::from_id(species.id(), species.mass(), species.charge());
+        assert_eq!(species, restored);
     }
 
     #[test]
@@ -0,0 +1,295 @@
+//! Bonded connectivity between atoms.
+
+use crate::internal::Float;
+
+/// Named group of atoms such as a residue or molecule.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Residue {
+    /// Name of the residue.
+    pub name: String,
+    /// Indices of the atoms which belong to the residue.
+    pub atoms: Vec<usize>,
+}
+
+/// Named set of atoms chosen for special treatment, such as the fixed bottom layers of a slab.
+///
+/// Unlike residues, groups carry no chemical meaning and may overlap, so an atom can belong to
+/// any number of groups.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Group {
+    /// Name of the group.
+    pub name: String,
+    /// Indices of the atoms which belong to the group.
+    pub atoms: Vec<usize>,
+}
+
+/// Bonds, angles, dihedrals, residues, groups, partial charges, constrained distances, and frozen
+/// atoms identified by their index in the system.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Topology {
+    /// Pairs of bonded atoms.
+    pub bonds: Vec<[usize; 2]>,
+    /// Triplets of atoms forming an angle about the central atom.
+    pub angles: Vec<[usize; 3]>,
+    /// Quadruplets of atoms forming a dihedral about the central bond.
+    pub dihedrals: Vec<[usize; 4]>,
+    /// Residue or molecule assignment of the atoms.
+    pub residues: Vec<Residue>,
+    /// Named groups of atoms.
+    pub groups: Vec<Group>,
+    /// Partial charge of each atom, which takes the place of the charge of its species.
+    ///
+    /// Empty when every atom carries the charge of its species.
+    pub charges: Vec<Float>,
+    /// Pairs of atoms whose distance is held fixed during integration, each of which removes one
+    /// degree of freedom from the system.
+    ///
+    /// Recorded by [`MolecularDynamics`](crate::propagators::MolecularDynamics) from the
+    /// constraints of its integrator when it is set up, so they are not stored with the system.
+    pub constraints: Vec<[usize; 2]>,
+    /// Atoms held fixed in place during integration, each of which removes three degrees of
+    /// freedom from the system.
+    ///
+    /// Recorded by [`Frozen`](crate::propagators::Frozen) from its group when it is set up, so
+    /// they are not stored with the system.
+    pub frozen: Vec<usize>,
+}
+
+impl Topology {
+    /// Returns true if the topology contains no connectivity, residues, groups, partial charges,
+    /// constraints, or frozen atoms.
+    pub fn is_empty(&self) -> bool {
+        self.bonds.is_empty()
+            && self.angles.is_empty()
+            && self.dihedrals.is_empty()
+            && self.residues.is_empty()
+            && self.groups.is_empty()
+            && self.charges.is_empty()
+            && self.constraints.is_empty()
+            && self.frozen.is_empty()
+    }
+
+    /// Returns the atoms of the group named `name` if one exists.
+    pub fn group(&self, name: &str) -> Option<&[usize]> {
+        self.groups
+            .iter()
+            .find(|group| group.name == name)
+            .map(|group| group.atoms.as_slice())
+    }
+
+    /// Adds a group named `name` containing `atoms`, replacing any existing group of that name.
+    pub fn add_group(&mut self, name: &str, atoms: Vec<usize>) {
+        self.groups.retain(|group| group.name != name);
+        self.groups.push(Group {
+            name: name.to_string(),
+            atoms,
+        });
+    }
+
+    /// Returns the topology of the system made of `atoms`, with atom `atoms[k]` at index `k`.
+    ///
+    /// Bonds, angles, dihedrals, and constraints are kept when all of their atoms are selected,
+    /// while residues, groups, partial charges, and frozen atoms are restricted to the selection.
+    pub fn select(&self, atoms: &[usize]) -> Topology {
+        let size = atoms.iter().map(|&i| i + 1).max().unwrap_or(0);
+        let mut index = vec![None; size];
+        for (local, &i) in atoms.iter().enumerate() {
+            index[i] = Some(local);
+        }
+        let local = |i: usize| index.get(i).copied().flatten();
+        let term = |term: &[usize]| -> Option<Vec<usize>> { term.iter().map(|&i| local(i)).collect() };
+        Topology {
+            bonds: self.bonds.iter().filter_map(|bond| term(bond)).map(|t| [t[0], t[1]]).collect(),
+            angles: self
+                .angles
+                .iter()
+                .filter_map(|angle| term(angle))
+                .map(|t| [t[0], t[1], t[2]])
+                .collect(),
+            dihedrals: self
+                .dihedrals
+                .iter()
+                .filter_map(|dihedral| term(dihedral))
+                .map(|t| [t[0], t[1], t[2], t[3]])
+                .collect(),
+            residues: self
+                .residues
+                .iter()
+                .map(|residue| Residue {
+                    name: residue.name.clone(),
+                    atoms: residue.atoms.iter().filter_map(|&i| local(i)).collect(),
+                })
+                .filter(|residue| !residue.atoms.is_empty())
+                .collect(),
+            groups: self
+                .groups
+                .iter()
+                .map(|group| Group {
+                    name: group.name.clone(),
+                    atoms: group.atoms.iter().filter_map(|&i| local(i)).collect(),
+                })
+                .collect(),
+            charges: if self.charges.is_empty() {
+                Vec::new()
+            } else {
+                atoms.iter().map(|&i| self.charges[i]).collect()
+            },
+            constraints: self
+                .constraints
+                .iter()
+                .filter_map(|pair| term(pair))
+                .map(|t| [t[0], t[1]])
+                .collect(),
+            frozen: self.frozen.iter().filter_map(|&i| local(i)).collect(),
+        }
+    }
+
+    /// Returns the index of the residue containing atom `index` if one exists.
+    pub fn residue_of(&self, index: usize) -> Option<usize> {
+        self.residues
+            .iter()
+            .position(|residue| residue.atoms.contains(&index))
+    }
+
+    /// Returns each pair of distinct atoms connected by a path of at most `separation` bonds.
+    ///
+    /// A separation of 1 gives the bonded pairs, 2 adds the ends of each angle, and 3 adds the
+    /// ends of each dihedral. Pairs are returned in ascending order with the smaller index first.
+    pub fn excluded_pairs(&self, separation: usize) -> Vec<[usize; 2]> {
+        let size = self.bonds.iter().flatten().map(|&i| i + 1).max().unwrap_or(0);
+        let mut neighbors = vec![Vec::new(); size];
+        for &[i, j] in &self.bonds {
+            neighbors[i].push(j);
+            neighbors[j].push(i);
+        }
+        let mut pairs = Vec::new();
+        for start in 0..size {
+            // breadth first search out to the requested number of bonds
+            let mut visited = vec![start];
+            let mut frontier = vec![start];
+            for _ in 0..separation {
+                let mut next = Vec::new();
+                for &atom in &frontier {
+                    for &neighbor in &neighbors[atom] {
+                        if !visited.contains(&neighbor) {
+                            visited.push(neighbor);
+                            next.push(neighbor);
+                        }
+                    }
+                }
+                frontier = next;
+            }
+            pairs.extend(visited.into_iter().filter(|&atom| atom > start).map(|atom| [start, atom]));
+        }
+        pairs.sort_unstable();
+        pairs
+    }
+
+    /// Returns the atoms of each molecule formed by bonded atoms.
+    ///
+    /// Molecules are ordered by their smallest atom index and list their atoms in ascending order.
+    /// Atoms without bonds do not belong to any molecule.
+    pub fn molecules(&self) -> Vec<Vec<usize>> {
+        let size = self.bonds.iter().flatten().map(|&i| i + 1).max().unwrap_or(0);
+        // union find with the smallest index of each molecule as its root
+        let mut roots: Vec<usize> = (0..size).collect();
+        fn find(roots: &mut [usize], mut i: usize) -> usize {
+            while roots[i] != i {
+                roots[i] = roots[roots[i]];
+                i = roots[i];
+            }
+            i
+        }
+        for &[i, j] in &self.bonds {
+            let (a, b) = (find(&mut roots, i), find(&mut roots, j));
+            roots[a.max(b)] = a.min(b);
+        }
+        let mut bonded = vec![false; size];
+        self.bonds.iter().flatten().for_each(|&i| bonded[i] = true);
+        let mut molecules: Vec<Vec<usize>> = Vec::new();
+        let mut index: Vec<Option<usize>> = vec![None; size];
+        for atom in (0..size).filter(|&atom| bonded[atom]) {
+            let root = find(&mut roots, atom);
+            match index[root] {
+                Some(m) => molecules[m].push(atom),
+                None => {
+                    index[root] = Some(molecules.len());
+                    molecules.push(vec![atom]);
+                }
+            }
+        }
+        molecules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Topology;
+
+    #[test]
+    fn excluded_pairs() {
+        // butane-like chain with a branch on the second atom
+        let topology = Topology {
+            bonds: vec![[0, 1], [2, 1], [2, 3], [1, 4]],
+            ..Topology::default()
+        };
+        assert!(topology.excluded_pairs(0).is_empty());
+        assert_eq!(topology.excluded_pairs(1), vec![[0, 1], [1, 2], [1, 4], [2, 3]]);
+        assert_eq!(
+            topology.excluded_pairs(2),
+            vec![[0, 1], [0, 2], [0, 4], [1, 2], [1, 3], [1, 4], [2, 3], [2, 4]]
+        );
+        assert_eq!(topology.excluded_pairs(3).len(), 10);
+        // rings do not report a pair twice
+        let ring = Topology {
+            bonds: vec![[0, 1], [1, 2], [2, 0]],
+            ..Topology::default()
+        };
+        assert_eq!(ring.excluded_pairs(3), vec![[0, 1], [0, 2], [1, 2]]);
+    }
+
+    #[test]
+    fn select() {
+        let topology = Topology {
+            bonds: vec![[0, 1], [1, 2], [2, 3]],
+            angles: vec![[0, 1, 2], [1, 2, 3]],
+            charges: vec![0.1, 0.2, 0.3, 0.4],
+            constraints: vec![[1, 2], [0, 3]],
+            frozen: vec![0, 2],
+            ..Topology::default()
+        };
+        // the selection reorders the atoms and drops the terms reaching atom 0
+        let selected = topology.select(&[3, 2, 1]);
+        assert_eq!(selected.bonds, vec![[2, 1], [1, 0]]);
+        assert_eq!(selected.angles, vec![[2, 1, 0]]);
+        assert_eq!(selected.charges, vec![0.4, 0.3, 0.2]);
+        assert_eq!(selected.constraints, vec![[2, 1]]);
+        assert_eq!(selected.frozen, vec![1]);
+        assert!(topology.select(&[]).is_empty());
+    }
+
+    #[test]
+    fn molecules() {
+        // two chains bonded out of order around an unbonded atom
+        let topology = Topology {
+            bonds: vec![[5, 6], [0, 4], [3, 1], [4, 3], [6, 2]],
+            ..Topology::default()
+        };
+        assert_eq!(topology.molecules(), vec![vec![0, 1, 3, 4], vec![2, 5, 6]]);
+        assert!(Topology::default().molecules().is_empty());
+    }
+
+    #[test]
+    fn groups() {
+        let mut topology = Topology::default();
+        topology.add_group("fixed", vec![0, 1]);
+        topology.add_group("mobile", vec![2, 3]);
+        assert!(!topology.is_empty());
+        assert_eq!(topology.group("fixed"), Some(&[0, 1][..]));
+        assert_eq!(topology.group("surface"), None);
+        // adding a group of the same name replaces it
+        topology.add_group("fixed", vec![0]);
+        assert_eq!(topology.groups.len(), 2);
+        assert_eq!(topology.group("fixed"), Some(&[0][..]));
+    }
+}
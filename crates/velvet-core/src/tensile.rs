@@ -0,0 +1,217 @@
+//! Virtual mechanical tests which record the stress-strain response of a system.
+
+use nalgebra::{Matrix3, Vector3};
+
+use crate::barostats::deform;
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::properties::energy::PotentialEnergy;
+use crate::properties::pressure::StressTensor;
+use crate::properties::Property;
+use crate::system::System;
+
+/// Point on the stress-strain curve of a [`TensileTest`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StressStrain {
+    /// Engineering strain along the loading axis.
+    pub strain: Float,
+    /// Tensile stress along the loading axis in atmospheres, positive under tension.
+    pub stress: Float,
+    /// Averaged stress tensor in atmospheres with the sign convention of [`StressTensor`].
+    pub tensor: Matrix3<Float>,
+    /// Averaged potential energy in kcal/mol.
+    pub energy: Float,
+}
+
+/// Strain-controlled uniaxial tensile test.
+///
+/// The cell and the atomic positions are stretched along one cartesian axis in increments of
+/// engineering strain. After each increment the system is relaxed or equilibrated for a fixed
+/// number of steps by a fresh propagator, and the stress tensor and potential energy are averaged
+/// over the final steps to give a point of the stress-strain curve. The lateral dimensions of the
+/// cell are held fixed unless the propagator includes a barostat. Negative increments give a
+/// compression test.
+///
+/// # Examples
+///
+/// ```no_run
+/// use velvet_core::prelude::*;
+/// # fn system() -> System { unimplemented!() }
+/// # fn potentials() -> Potentials { unimplemented!() }
+///
+/// let mut system = system();
+/// let mut potentials = potentials();
+/// // quasi-static test with 0.5% strain increments along x
+/// let mut test = TensileTest::new(0, 0.005).steps(500);
+/// let curve = test.run(&mut system, &mut potentials, || Fire::new(0.5, 0.1), 20)?;
+/// println!("elastic modulus: {} atm", elastic_modulus(&curve, 0.02));
+/// # Ok::<(), VelvetError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct TensileTest {
+    axis: usize,
+    increment: Float,
+    steps: usize,
+    average: usize,
+    length: Option<Float>,
+    curve: Vec<StressStrain>,
+}
+
+impl TensileTest {
+    /// Returns a new [`TensileTest`] driver.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - Cartesian axis of the load, 0 for `x`, 1 for `y`, and 2 for `z`.
+    /// * `increment` - Engineering strain applied in each increment.
+    pub fn new(axis: usize, increment: Float) -> TensileTest {
+        assert!(axis < 3, "The loading axis must be 0, 1, or 2.");
+        TensileTest {
+            axis,
+            increment,
+            steps: 1000,
+            average: 1,
+            length: None,
+            curve: Vec::new(),
+        }
+    }
+
+    /// Sets the number of propagator steps run after each increment. Defaults to 1000.
+    pub fn steps(mut self, steps: usize) -> TensileTest {
+        self.steps = steps;
+        self
+    }
+
+    /// Sets the number of final steps of each increment the stress is averaged over. Defaults to 1.
+    pub fn average(mut self, steps: usize) -> TensileTest {
+        self.average = steps.max(1);
+        self
+    }
+
+    /// Returns the engineering strain applied so far.
+    pub fn strain(&self) -> Float {
+        self.curve.last().map_or(0.0, |point| point.strain)
+    }
+
+    /// Returns the stress-strain curve recorded so far.
+    pub fn curve(&self) -> &[StressStrain] {
+        &self.curve
+    }
+
+    /// Returns the point of the curve with the largest tensile stress.
+    pub fn ultimate(&self) -> Option<StressStrain> {
+        self.curve
+            .iter()
+            .copied()
+            .max_by(|a, b| a.stress.total_cmp(&b.stress))
+    }
+
+    /// Applies `increments` strain increments and returns the stress-strain curve.
+    ///
+    /// The unstrained system is relaxed and measured first, so the curve holds `increments + 1`
+    /// points on the first call. Later calls continue from the strain reached so far and add a
+    /// point for each increment. On return `system` holds the final strained configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - Configuration under test.
+    /// * `potentials` - Potentials of the system.
+    /// * `propagator` - Constructor for the minimizer or dynamics run after each increment.
+    /// * `increments` - Number of strain increments to apply.
+    pub fn run<G, P>(
+        &mut self,
+        system: &mut System,
+        potentials: &mut Potentials,
+        propagator: G,
+        increments: usize,
+    ) -> Result<Vec<StressStrain>, VelvetError>
+    where
+        G: Fn() -> P,
+        P: Propagator,
+    {
+        if self.length.is_none() {
+            self.length = Some(self.length_of(system));
+            let point = self.measure(system, potentials, propagator(), 0.0)?;
+            self.curve.push(point);
+        }
+        let reference = self.length.unwrap();
+        for _ in 0..increments {
+            let strain = self.strain() + self.increment;
+            let target = reference * (1.0 + strain);
+            let mut scale = Vector3::repeat(1.0);
+            scale[self.axis] = target / self.length_of(system);
+            deform(system, &Matrix3::from_diagonal(&scale));
+            let point = self.measure(system, potentials, propagator(), strain)?;
+            self.curve.push(point);
+        }
+        Ok(self.curve.clone())
+    }
+
+    // Returns the extent of the cell along the loading axis.
+    fn length_of(&self, system: &System) -> Float {
+        system.cell.matrix().row(self.axis).iter().map(|x| x.abs()).sum()
+    }
+
+    // Runs the propagator on the strained system and averages the stress over the final steps.
+    fn measure<P: Propagator>(
+        &self,
+        system: &mut System,
+        potentials: &mut Potentials,
+        mut propagator: P,
+        strain: Float,
+    ) -> Result<StressStrain, VelvetError> {
+        potentials.setup(system);
+        propagator.setup(system, potentials)?;
+        let mut tensor = Matrix3::zeros();
+        let mut energy = 0.0;
+        let mut samples = 0;
+        for i in 0..self.steps {
+            if propagator.converged() {
+                break;
+            }
+            propagator.propagate(system, potentials);
+            potentials.update(system, i);
+            if i + self.average >= self.steps {
+                tensor += StressTensor.calculate(system, potentials);
+                energy += PotentialEnergy.calculate(system, potentials);
+                samples += 1;
+            }
+        }
+        // a minimizer which converged early is measured in its final configuration
+        if samples == 0 {
+            tensor = StressTensor.calculate(system, potentials);
+            energy = PotentialEnergy.calculate(system, potentials);
+            samples = 1;
+        }
+        let tensor = tensor / samples as Float;
+        Ok(StressStrain {
+            strain,
+            stress: -tensor[(self.axis, self.axis)],
+            tensor,
+            energy: energy / samples as Float,
+        })
+    }
+}
+
+/// Returns the slope of a least squares line through the points of a stress-strain curve with
+/// strains up to `max_strain`, an estimate of the elastic modulus in atmospheres.
+///
+/// Under the fixed lateral dimensions of a [`TensileTest`] without a barostat this is the `C11`
+/// type elastic constant rather than the Young's modulus, which requires free lateral contraction.
+pub fn elastic_modulus(curve: &[StressStrain], max_strain: Float) -> Float {
+    let points: Vec<&StressStrain> = curve.iter().filter(|p| p.strain.abs() <= max_strain).collect();
+    let n = points.len() as Float;
+    if points.len() < 2 {
+        return Float::NAN;
+    }
+    let mean_strain = points.iter().map(|p| p.strain).sum::<Float>() / n;
+    let mean_stress = points.iter().map(|p| p.stress).sum::<Float>() / n;
+    let covariance: Float = points
+        .iter()
+        .map(|p| (p.strain - mean_strain) * (p.stress - mean_stress))
+        .sum();
+    let variance: Float = points.iter().map(|p| (p.strain - mean_strain).powi(2)).sum();
+    covariance / variance
+}
@@ -0,0 +1,250 @@
+//! Thermal expansion of a system from isothermal-isobaric runs at a series of temperatures.
+
+use crate::convergence::statistical_inefficiency;
+use crate::errors::VelvetError;
+use crate::internal::Float;
+use crate::potentials::Potentials;
+use crate::propagators::Propagator;
+use crate::system::System;
+
+/// Equilibrium volume of the system at one temperature.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VolumeEstimate {
+    /// Temperature of the run in Kelvin.
+    pub temperature: Float,
+    /// Mean volume of the cell in cubic angstroms.
+    pub volume: Float,
+    /// Standard error of the mean volume corrected for correlation between samples.
+    pub standard_error: Float,
+}
+
+/// Volume-temperature curve and volumetric thermal expansion coefficient of a system.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpansionCurve {
+    /// Equilibrium volume at each temperature in the order they were run.
+    pub points: Vec<VolumeEstimate>,
+    /// Volumetric thermal expansion coefficient in inverse Kelvin at the mean temperature.
+    pub coefficient: Float,
+    /// Standard error of the thermal expansion coefficient.
+    pub standard_error: Float,
+}
+
+/// Workflow which measures the volumetric thermal expansion coefficient of a system.
+///
+/// The system is equilibrated and sampled in turn at each temperature by an isothermal-isobaric
+/// propagator, such as [`MolecularDynamics`](crate::propagators::MolecularDynamics) with a
+/// thermostat and barostat or [`MonteCarlo`](crate::monte_carlo::MonteCarlo) with volume moves,
+/// which the workflow constructs for each temperature. Each run starts from the final
+/// configuration of the previous one. The volume is sampled every `interval` steps and the error
+/// of its mean accounts for the correlation between samples.
+///
+/// The coefficient `(1 / V) dV/dT` is the slope of a line through the volumes weighted by their
+/// errors, divided by the volume on the line at the mean temperature.
+///
+/// # Examples
+///
+/// ```no_run
+/// use velvet_core::prelude::*;
+/// # fn system() -> System { unimplemented!() }
+/// # fn potentials() -> Potentials { unimplemented!() }
+///
+/// let mut system = system();
+/// let mut potentials = potentials();
+/// let npt = |temperature| {
+///     MolecularDynamics::new(VelocityVerlet::new(1.0), Bussi::new(temperature, 100.0))
+///         .with_barostat(BerendsenBarostat::new(1.0, 4.5e-5, 1000.0))
+/// };
+/// let curve = ThermalExpansion::new(vec![50.0, 60.0, 70.0, 80.0])
+///     .equilibration(5000)
+///     .run(&mut system, &mut potentials, npt, 20000)?;
+/// println!("alpha: {} +/- {} 1/K", curve.coefficient, curve.standard_error);
+/// # Ok::<(), VelvetError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ThermalExpansion {
+    temperatures: Vec<Float>,
+    equilibration: usize,
+    interval: usize,
+}
+
+impl ThermalExpansion {
+    /// Returns a new [`ThermalExpansion`] workflow over the given temperatures in Kelvin.
+    pub fn new(temperatures: Vec<Float>) -> ThermalExpansion {
+        ThermalExpansion {
+            temperatures,
+            equilibration: 1000,
+            interval: 10,
+        }
+    }
+
+    /// Sets the number of steps run at each temperature before sampling begins. Defaults to 1000.
+    pub fn equilibration(mut self, steps: usize) -> ThermalExpansion {
+        self.equilibration = steps;
+        self
+    }
+
+    /// Sets the number of steps between samples of the volume. Defaults to 10.
+    pub fn interval(mut self, steps: usize) -> ThermalExpansion {
+        self.interval = steps.max(1);
+        self
+    }
+
+    /// Runs every temperature for `steps` production steps and returns the expansion curve.
+    ///
+    /// # Arguments
+    ///
+    /// * `system` - Starting configuration, which holds the final configuration on return.
+    /// * `potentials` - Potentials of the system.
+    /// * `propagator` - Constructor for the isothermal-isobaric propagator at a temperature.
+    /// * `steps` - Number of production steps run at each temperature.
+    pub fn run<G, P>(
+        &self,
+        system: &mut System,
+        potentials: &mut Potentials,
+        propagator: G,
+        steps: usize,
+    ) -> Result<ExpansionCurve, VelvetError>
+    where
+        G: Fn(Float) -> P,
+        P: Propagator,
+    {
+        let points: Vec<VolumeEstimate> = self
+            .temperatures
+            .iter()
+            .map(|&temperature| {
+                let mut propagator = propagator(temperature);
+                potentials.setup(system);
+                propagator.setup(system, potentials)?;
+                let mut samples = Vec::with_capacity(steps / self.interval);
+                for i in 0..self.equilibration + steps {
+                    propagator.propagate(system, potentials);
+                    potentials.update(system, i);
+                    if i >= self.equilibration && (i - self.equilibration + 1).is_multiple_of(self.interval) {
+                        samples.push(system.cell.volume());
+                    }
+                }
+                Ok(volume_estimate(temperature, &samples))
+            })
+            .collect::<Result<_, VelvetError>>()?;
+        let (coefficient, standard_error) = expansion_coefficient(&points);
+        Ok(ExpansionCurve {
+            points,
+            coefficient,
+            standard_error,
+        })
+    }
+}
+
+fn volume_estimate(temperature: Float, samples: &[Float]) -> VolumeEstimate {
+    let n = samples.len();
+    let volume = samples.iter().sum::<Float>() / n.max(1) as Float;
+    let standard_error = if n < 2 {
+        Float::INFINITY
+    } else {
+        let variance = samples.iter().map(|v| (v - volume).powi(2)).sum::<Float>() / (n - 1) as Float;
+        Float::sqrt(variance * statistical_inefficiency(samples, volume) / n as Float)
+    };
+    VolumeEstimate {
+        temperature,
+        volume,
+        standard_error,
+    }
+}
+
+// Returns the thermal expansion coefficient and its standard error from a weighted linear fit
+// of the volume against temperature, or an unweighted fit if any volume has no usable error.
+fn expansion_coefficient(points: &[VolumeEstimate]) -> (Float, Float) {
+    if points.len() < 2 {
+        return (Float::NAN, Float::NAN);
+    }
+    let weighted = points.iter().all(|p| p.standard_error.is_finite() && p.standard_error > 0.0);
+    let weights: Vec<Float> = points
+        .iter()
+        .map(|p| if weighted { p.standard_error.powi(-2) } else { 1.0 })
+        .collect();
+    let total: Float = weights.iter().sum();
+    let mean_t = points.iter().zip(weights.iter()).map(|(p, w)| w * p.temperature).sum::<Float>() / total;
+    let mean_v = points.iter().zip(weights.iter()).map(|(p, w)| w * p.volume).sum::<Float>() / total;
+    let sxx: Float = points
+        .iter()
+        .zip(weights.iter())
+        .map(|(p, w)| w * (p.temperature - mean_t).powi(2))
+        .sum();
+    let sxy: Float = points
+        .iter()
+        .zip(weights.iter())
+        .map(|(p, w)| w * (p.temperature - mean_t) * (p.volume - mean_v))
+        .sum();
+    let slope = sxy / sxx;
+    let slope_error = if weighted {
+        Float::sqrt(1.0 / sxx)
+    } else if points.len() > 2 {
+        let residuals: Float = points
+            .iter()
+            .map(|p| (p.volume - mean_v - slope * (p.temperature - mean_t)).powi(2))
+            .sum();
+        Float::sqrt(residuals / (points.len() - 2) as Float / sxx)
+    } else {
+        Float::INFINITY
+    };
+    // the fitted line passes through the weighted mean volume at the mean temperature
+    (slope / mean_v, slope_error / mean_v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expansion_coefficient, ThermalExpansion, VolumeEstimate};
+    use crate::internal::Float;
+    use crate::monte_carlo::MonteCarlo;
+    use crate::potentials::PotentialsBuilder;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn linear_fit() {
+        let points: Vec<VolumeEstimate> = [100.0, 200.0, 300.0]
+            .iter()
+            .map(|&t| VolumeEstimate {
+                temperature: t,
+                volume: 1000.0 + 0.5 * t,
+                standard_error: 1.0,
+            })
+            .collect();
+        let (alpha, error) = expansion_coefficient(&points);
+        assert!((alpha - 0.5 / 1100.0).abs() < 1e-7, "{}", alpha);
+        assert!((error - Float::sqrt(1.0 / 20000.0) / 1100.0).abs() < 1e-7, "{}", error);
+        assert!(expansion_coefficient(&points[..1]).0.is_nan());
+    }
+
+    #[test]
+    fn ideal_gas() {
+        let atom = Species::new(39.948, 0.0);
+        let mut system = System {
+            size: 10,
+            cell: Cell::cubic(10.0),
+            species: vec![atom; 10],
+            velocities: vec![Vector3::zeros(); 10],
+            positions: (0..10).map(|i| Vector3::new(i as Float, 0.5 * i as Float, 0.0)).collect(),
+            topology: Topology::default(),
+        };
+        let mut potentials = PotentialsBuilder::new().build();
+        let npt = |temperature| MonteCarlo::new(temperature, 0.5).with_volume_moves(450.0, 0.3);
+        let curve = ThermalExpansion::new(vec![200.0, 300.0, 400.0])
+            .equilibration(2000)
+            .interval(5)
+            .run(&mut system, &mut potentials, npt, 20000)
+            .unwrap();
+
+        assert_eq!(curve.points.len(), 3);
+        assert_eq!(curve.points[1].temperature, 300.0);
+        assert!(curve.points.windows(2).all(|w| w[1].volume > w[0].volume));
+        assert!(curve.points.iter().all(|p| p.standard_error > 0.0));
+        // the volume of an ideal gas is proportional to temperature, so alpha is 1 / T
+        let expected = 1.0 / 300.0;
+        assert!((curve.coefficient - expected).abs() < 0.25 * expected, "{}", curve.coefficient);
+        assert!(curve.standard_error > 0.0 && curve.standard_error < 0.25 * expected);
+    }
+}
@@ -1,20 +1,68 @@
 //! Algorithms which control the temperature of a system.
 
+use std::sync::{Arc, Mutex};
+
 use nalgebra::Vector3;
+use rand::Rng;
+use rand_distr::{ChiSquared, Distribution, StandardNormal};
 
+use crate::checkpoint::PropagatorState;
+use crate::errors::VelvetError;
+use crate::internal::consts::BOLTZMANN;
 use crate::internal::Float;
+use crate::properties::energy::{ConservedEnergy, KineticEnergy};
 use crate::properties::temperature::Temperature;
 use crate::properties::IntrinsicProperty;
+use crate::random::shared_rng;
 use crate::system::System;
 
 /// Shared behavior for algorithms which control the temperature of a system.
 pub trait Thermostat: Send + Sync {
-    /// Prepares the thermostat to run.
-    fn setup(&mut self, _: &System) {}
+    /// Prepares the thermostat to run, or returns an error if it cannot act on the system.
+    fn setup(&mut self, _: &System) -> Result<(), VelvetError> {
+        Ok(())
+    }
     /// Fires before the integration step.
     fn pre_integrate(&mut self, _: &mut System) {}
     /// Fires after the integration step.
     fn post_integrate(&mut self, _: &mut System) {}
+    /// Changes the target temperature, e.g. as a [`Scheduled`] thermostat follows its schedule.
+    fn set_target(&mut self, _: Float) {}
+    /// Returns the internal state of the thermostat to store in a checkpoint.
+    fn state(&self) -> PropagatorState {
+        PropagatorState::default()
+    }
+    /// Restores internal state previously returned by [`state`](Thermostat::state), or returns an
+    /// error if the state belongs to a different thermostat.
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 0, 0)
+    }
+}
+
+impl<T: Thermostat + ?Sized> Thermostat for Box<T> {
+    fn setup(&mut self, system: &System) -> Result<(), VelvetError> {
+        (**self).setup(system)
+    }
+
+    fn pre_integrate(&mut self, system: &mut System) {
+        (**self).pre_integrate(system)
+    }
+
+    fn post_integrate(&mut self, system: &mut System) {
+        (**self).post_integrate(system)
+    }
+
+    fn set_target(&mut self, target: Float) {
+        (**self).set_target(target)
+    }
+
+    fn state(&self) -> PropagatorState {
+        (**self).state()
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        (**self).restore(state)
+    }
 }
 
 /// Mock thermostat algorithm which applies no temperature controls.
@@ -58,10 +106,22 @@ impl Thermostat for Berendsen {
             .map(|&v| v * factor)
             .collect::<Vec<Vector3<Float>>>();
     }
+
+    fn set_target(&mut self, target: Float) {
+        self.target = target
+    }
 }
 
 /// Nose-Hoover style thermostat.
 ///
+/// The friction coefficient is advanced by half steps before and after each integration step
+/// and the velocities are scaled by half of the friction on either side of the integrator.
+/// The energy of the extended system, which combines the kinetic and potential energy of the
+/// atoms with the energy of the thermostat's variables, is conserved and is available as the
+/// [`ConservedEnergy`] property returned by [`conserved_energy`](NoseHoover::conserved_energy).
+/// A drift in the conserved energy indicates that the timestep is too large, the same way the
+/// total energy does in the NVE ensemble.
+///
 /// # References
 ///
 /// [1] Evans, Denis J., and Brad Lee Holian. "The nose–hoover thermostat." The Journal of chemical physics 83.8 (1985): 4069-4074.
@@ -73,8 +133,8 @@ pub struct NoseHoover {
     freq: Float,
     timestep: Float,
     psi: Float,
-    factor: Float,
-    temperature: Float,
+    eta: Float,
+    reservoir: Arc<Mutex<Float>>,
 }
 
 impl NoseHoover {
@@ -91,33 +151,844 @@ impl NoseHoover {
             freq,
             timestep,
             psi: 0 as Float,
-            factor: 0 as Float,
-            temperature: 0 as Float,
+            eta: 0 as Float,
+            reservoir: Arc::new(Mutex::new(0 as Float)),
         }
     }
+
+    /// Returns the energy of the extended system which the thermostat conserves.
+    ///
+    /// The property follows this thermostat through every subsequent run, so it can be added to
+    /// an output group before the thermostat is moved into a propagator.
+    pub fn conserved_energy(&self) -> ConservedEnergy {
+        ConservedEnergy::new(self.reservoir.clone())
+    }
+
+    // Advances the friction coefficient by half a timestep.
+    fn half_step(&mut self, system: &System) {
+        let temperature = Temperature.calculate_intrinsic(system);
+        let psidot = self.freq.powi(2) * ((temperature / self.target) - 1.0);
+        self.psi += psidot * (self.timestep / 2.0);
+    }
+
+    // Scales the velocities by half a timestep of friction.
+    fn scale(&mut self, system: &mut System) {
+        let factor = Float::exp(-self.psi * (self.timestep / 2.0));
+        self.eta += self.psi * (self.timestep / 2.0);
+        system.velocities = system
+            .velocities
+            .iter()
+            .map(|&v| v * factor)
+            .collect::<Vec<Vector3<Float>>>();
+    }
+
+    // Stores the energy of the thermostat variables, psi^2 Q / 2 + g kT eta, where the
+    // thermostat mass Q is g kT / freq^2 for g degrees of freedom.
+    fn update_reservoir(&self, system: &System) {
+        let gkt = system.degrees_of_freedom() as Float * BOLTZMANN * self.target;
+        let energy = gkt * (0.5 * (self.psi / self.freq).powi(2) + self.eta);
+        *self.reservoir.lock().unwrap() = energy;
+    }
 }
 
 impl Thermostat for NoseHoover {
-    fn setup(&mut self, system: &System) {
-        self.temperature = Temperature.calculate_intrinsic(system);
+    fn setup(&mut self, system: &System) -> Result<(), VelvetError> {
+        self.update_reservoir(system);
+        Ok(())
     }
 
     fn pre_integrate(&mut self, system: &mut System) {
-        let dt = self.timestep;
-        let psidot = self.freq.powi(2) * ((self.temperature / self.target) - 1.0);
-        self.psi += psidot * (dt / 2.0);
-        self.factor = Float::exp(-self.psi * (dt / 2.0));
+        self.half_step(system);
+        self.scale(system);
+    }
+
+    fn post_integrate(&mut self, system: &mut System) {
+        self.scale(system);
+        self.half_step(system);
+        self.update_reservoir(system);
+    }
+
+    fn state(&self) -> PropagatorState {
+        PropagatorState::from_floats(vec![self.psi, self.eta])
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 2, 0)?;
+        self.psi = state.floats[0];
+        self.eta = state.floats[1];
+        Ok(())
+    }
+
+    fn set_target(&mut self, target: Float) {
+        self.target = target
+    }
+}
+
+/// Nose-Hoover chain thermostat.
+///
+/// A single Nose-Hoover thermostat is not ergodic for small or stiff systems such as harmonic
+/// solids, where its friction coefficient oscillates without sampling the canonical ensemble.
+/// Each thermostat variable of a chain is itself coupled to the next one, which restores
+/// canonical sampling. The thermostat variables are propagated over half timesteps before and
+/// after each integration step, split into substeps and integrated with the Suzuki-Yoshida
+/// scheme of the given order. The energy of the extended system is conserved and is available
+/// as the [`ConservedEnergy`] property returned by
+/// [`conserved_energy`](NoseHooverChain::conserved_energy).
+///
+/// The first thermostat has a mass of `g kT / freq^2` for the `g` degrees of freedom of the
+/// atoms and every other thermostat has a mass of `kT / freq^2`.
+///
+/// # References
+///
+/// [1] Martyna, Glenn J., Michael L. Klein, and Mark Tuckerman. "Nosé–Hoover chains: The canonical ensemble via continuous dynamics." The Journal of chemical physics 97.4 (1992): 2635-2643.
+///
+/// [2] Martyna, Glenn J., et al. "Explicit reversible integrators for extended systems dynamics." Molecular Physics 87.5 (1996): 1117-1157.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// let thermostat = NoseHooverChain::new(300.0, 0.01, 1.0)
+///     .chain_length(5)?
+///     .substeps(2)
+///     .suzuki_yoshida(5)?;
+/// let md = MolecularDynamics::new(VelocityVerlet::new(1.0), thermostat);
+/// # Ok::<(), VelvetError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct NoseHooverChain {
+    target: Float,
+    freq: Float,
+    timestep: Float,
+    substeps: usize,
+    weights: Vec<Float>,
+    positions: Vec<Float>,
+    velocities: Vec<Float>,
+    reservoir: Arc<Mutex<Float>>,
+}
+
+impl NoseHooverChain {
+    /// Returns a new Nose-Hoover chain thermostat of three thermostats with third order
+    /// Suzuki-Yoshida integration and a single substep.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Target temperature.
+    /// * `freq` - Damping frequency.
+    /// * `timestep` - Timestep of the integrator.
+    pub fn new(target: Float, freq: Float, timestep: Float) -> NoseHooverChain {
+        NoseHooverChain {
+            target,
+            freq,
+            timestep,
+            substeps: 1,
+            weights: suzuki_yoshida_weights(3).unwrap(),
+            positions: vec![0.0; 3],
+            velocities: vec![0.0; 3],
+            reservoir: Arc::new(Mutex::new(0 as Float)),
+        }
+    }
+
+    /// Sets the number of thermostats in the chain, or returns an error if `length` is zero.
+    pub fn chain_length(mut self, length: usize) -> Result<NoseHooverChain, VelvetError> {
+        if length == 0 {
+            return Err(VelvetError::invalid(
+                "chain length",
+                "a Nose-Hoover chain needs at least one thermostat",
+            ));
+        }
+        self.positions = vec![0.0; length];
+        self.velocities = vec![0.0; length];
+        Ok(self)
+    }
+
+    /// Sets the number of substeps each half timestep of the thermostat variables is split into.
+    pub fn substeps(mut self, substeps: usize) -> NoseHooverChain {
+        self.substeps = substeps.max(1);
+        self
+    }
+
+    /// Sets the order of the Suzuki-Yoshida integration of each substep, or returns an error if
+    /// `order` is not 1, 3, 5, or 7.
+    pub fn suzuki_yoshida(mut self, order: usize) -> Result<NoseHooverChain, VelvetError> {
+        self.weights = suzuki_yoshida_weights(order)?;
+        Ok(self)
+    }
+
+    /// Returns the energy of the extended system which the thermostat conserves.
+    ///
+    /// The property follows this thermostat through every subsequent run, so it can be added to
+    /// an output group before the thermostat is moved into a propagator.
+    pub fn conserved_energy(&self) -> ConservedEnergy {
+        ConservedEnergy::new(self.reservoir.clone())
+    }
+
+    // Returns the mass of the `k`th thermostat for `dof` degrees of freedom.
+    fn mass(&self, k: usize, dof: Float) -> Float {
+        let kt = BOLTZMANN * self.target;
+        let scale = if k == 0 { dof } else { 1.0 };
+        scale * kt / self.freq.powi(2)
+    }
+
+    // Propagates the chain over half a timestep and scales the velocities of the atoms to match.
+    fn half_step(&mut self, system: &mut System) {
+        let dof = system.degrees_of_freedom() as Float;
+        let kt = BOLTZMANN * self.target;
+        let masses: Vec<Float> = (0..self.velocities.len()).map(|k| self.mass(k, dof)).collect();
+        let last = self.velocities.len() - 1;
+        // twice the kinetic energy of the atoms
+        let mut kinetic = 2.0 * KineticEnergy.calculate_intrinsic(system);
+        let force = |k: usize, kinetic: Float, velocities: &[Float]| {
+            if k == 0 {
+                (kinetic - dof * kt) / masses[0]
+            } else {
+                (masses[k - 1] * velocities[k - 1].powi(2) - kt) / masses[k]
+            }
+        };
+
+        let mut factor = 1.0;
+        for _ in 0..self.substeps {
+            for &weight in &self.weights {
+                let dt = weight * self.timestep / self.substeps as Float;
+                let v = &mut self.velocities;
+                v[last] += force(last, kinetic, v) * dt / 4.0;
+                for k in (0..last).rev() {
+                    let damping = Float::exp(-v[k + 1] * dt / 8.0);
+                    v[k] = v[k] * damping * damping + force(k, kinetic, v) * damping * dt / 4.0;
+                }
+                let scale = Float::exp(-v[0] * dt / 2.0);
+                factor *= scale;
+                kinetic *= scale * scale;
+                for (position, velocity) in self.positions.iter_mut().zip(v.iter()) {
+                    *position += velocity * dt / 2.0;
+                }
+                for k in 0..last {
+                    let damping = Float::exp(-v[k + 1] * dt / 8.0);
+                    v[k] = v[k] * damping * damping + force(k, kinetic, v) * damping * dt / 4.0;
+                }
+                v[last] += force(last, kinetic, v) * dt / 4.0;
+            }
+        }
         system.velocities = system
             .velocities
             .iter()
-            .map(|&v| v * self.factor)
+            .map(|&v| v * factor)
             .collect::<Vec<Vector3<Float>>>();
     }
 
+    // Stores the kinetic energy of the thermostats plus their potential energy,
+    // g kT eta_1 + kT (eta_2 + ... + eta_M).
+    fn update_reservoir(&self, system: &System) {
+        let dof = system.degrees_of_freedom() as Float;
+        let kt = BOLTZMANN * self.target;
+        let energy: Float = (0..self.velocities.len())
+            .map(|k| {
+                let scale = if k == 0 { dof } else { 1.0 };
+                0.5 * self.mass(k, dof) * self.velocities[k].powi(2) + scale * kt * self.positions[k]
+            })
+            .sum();
+        *self.reservoir.lock().unwrap() = energy;
+    }
+}
+
+// Returns the weights of the Suzuki-Yoshida integration scheme of the given order.
+fn suzuki_yoshida_weights(order: usize) -> Result<Vec<Float>, VelvetError> {
+    let weights = match order {
+        1 => vec![1.0],
+        3 => {
+            let w = 1.0 / (2.0 - Float::cbrt(2.0));
+            vec![w, 1.0 - 2.0 * w, w]
+        }
+        5 => {
+            let w = 1.0 / (4.0 - Float::cbrt(4.0));
+            vec![w, w, 1.0 - 4.0 * w, w, w]
+        }
+        7 => {
+            let (w1, w2, w3) = (0.784513610477560, 0.235573213359357, -1.17767998417887);
+            let w4 = 1.0 - 2.0 * (w1 + w2 + w3);
+            vec![w1, w2, w3, w4, w3, w2, w1]
+        }
+        _ => {
+            return Err(VelvetError::invalid(
+                "Suzuki-Yoshida order",
+                format!("expected 1, 3, 5, or 7, found {}", order),
+            ))
+        }
+    };
+    Ok(weights)
+}
+
+impl Thermostat for NoseHooverChain {
+    fn setup(&mut self, system: &System) -> Result<(), VelvetError> {
+        self.update_reservoir(system);
+        Ok(())
+    }
+
+    fn pre_integrate(&mut self, system: &mut System) {
+        self.half_step(system);
+    }
+
+    fn post_integrate(&mut self, system: &mut System) {
+        self.half_step(system);
+        self.update_reservoir(system);
+    }
+
+    fn state(&self) -> PropagatorState {
+        PropagatorState::from_floats(self.positions.iter().chain(self.velocities.iter()).copied().collect())
+    }
+
+    // the chain length is set when the thermostat is constructed
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        let length = self.positions.len();
+        state.expect(0, 2 * length, 0)?;
+        self.positions = state.floats[..length].to_vec();
+        self.velocities = state.floats[length..].to_vec();
+        Ok(())
+    }
+
+    fn set_target(&mut self, target: Float) {
+        self.target = target
+    }
+}
+
+/// Andersen collision thermostat.
+///
+/// After each integration step every atom collides with the heat bath with probability
+/// `freq * timestep`, drawing a new velocity from the Maxwell-Boltzmann distribution at the
+/// target temperature. The thermostat samples the canonical ensemble exactly but the collisions
+/// randomize the velocities, so dynamical properties such as diffusion coefficients are
+/// suppressed at high collision frequencies.
+///
+/// # References
+///
+/// [1] Andersen, Hans C. "Molecular dynamics simulations at constant pressure and/or temperature." The Journal of chemical physics 72.4 (1980): 2384-2393.
+#[derive(Clone, Debug)]
+pub struct Andersen {
+    target: Float,
+    freq: Float,
+    timestep: Float,
+}
+
+impl Andersen {
+    /// Returns a new Andersen style thermostat.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Target temperature.
+    /// * `freq` - Collision frequency of each atom.
+    /// * `timestep` - Timestep of the integrator.
+    pub fn new(target: Float, freq: Float, timestep: Float) -> Andersen {
+        Andersen { target, freq, timestep }
+    }
+}
+
+impl Thermostat for Andersen {
     fn post_integrate(&mut self, system: &mut System) {
-        let dt = self.timestep;
-        self.temperature = Temperature.calculate_intrinsic(system);
-        let psidot = self.freq.powi(2) * ((self.temperature / self.target) - 1.0);
-        self.psi += psidot * (dt / 2.0);
+        let mut rng = shared_rng();
+        let probability = self.freq * self.timestep;
+        for (velocity, species) in system.velocities.iter_mut().zip(system.species.iter()) {
+            if rng.gen::<Float>() < probability {
+                let sigma = Float::sqrt(BOLTZMANN * self.target / species.mass());
+                let mut sample = || sigma * rng.sample::<Float, _>(StandardNormal);
+                *velocity = Vector3::new(sample(), sample(), sample());
+            }
+        }
+    }
+
+    fn set_target(&mut self, target: Float) {
+        self.target = target
+    }
+}
+
+/// Canonical sampling through velocity rescaling (CSVR) thermostat of Bussi, Donadio, and Parrinello.
+///
+/// After each integration step the velocities are scaled so the kinetic energy follows a
+/// stochastic version of the [`Berendsen`] relaxation towards the target temperature, with noise
+/// chosen so the canonical distribution of the kinetic energy is sampled exactly. The energy
+/// removed from the system by the scaling is accumulated into a reservoir, and the sum of the
+/// total energy and the reservoir is available as the [`ConservedEnergy`] property returned by
+/// [`conserved_energy`](Bussi::conserved_energy).
+///
+/// # References
+///
+/// [1] Bussi, Giovanni, Davide Donadio, and Michele Parrinello. "Canonical sampling through velocity rescaling." The Journal of chemical physics 126.1 (2007): 014101.
+#[derive(Clone, Debug)]
+pub struct Bussi {
+    target: Float,
+    tau: Float,
+    reservoir: Arc<Mutex<Float>>,
+}
+
+impl Bussi {
+    /// Returns a new Bussi style thermostat.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - Target temperature.
+    /// * `tau` - Timestep of the thermostat expressed as a multiple of the integrator's timestep.
+    pub fn new(target: Float, tau: Float) -> Bussi {
+        Bussi {
+            target,
+            tau,
+            reservoir: Arc::new(Mutex::new(0 as Float)),
+        }
+    }
+
+    /// Returns the energy of the system plus the energy the thermostat has removed from it.
+    ///
+    /// The property follows this thermostat through every subsequent run, so it can be added to
+    /// an output group before the thermostat is moved into a propagator.
+    pub fn conserved_energy(&self) -> ConservedEnergy {
+        ConservedEnergy::new(self.reservoir.clone())
+    }
+}
+
+impl Thermostat for Bussi {
+    fn post_integrate(&mut self, system: &mut System) {
+        let dof = system.degrees_of_freedom() as Float;
+        let kinetic = 0.5 * dof * BOLTZMANN * Temperature.calculate_intrinsic(system);
+        // a system at rest has no velocities to rescale
+        if kinetic == 0.0 {
+            return;
+        }
+        let target = 0.5 * dof * BOLTZMANN * self.target;
+        let c = Float::exp(-1.0 / self.tau);
+
+        let mut rng = shared_rng();
+        let r1: Float = rng.sample(StandardNormal);
+        // the remaining degrees of freedom contribute a chi-squared distributed sum of squares
+        let rest: Float = if dof > 1.0 {
+            ChiSquared::new(dof - 1.0).unwrap().sample(&mut rng)
+        } else {
+            0.0
+        };
+        let new_kinetic = kinetic
+            + (1.0 - c) * (target * (r1 * r1 + rest) / dof - kinetic)
+            + 2.0 * r1 * Float::sqrt(c * (1.0 - c) * kinetic * target / dof);
+
+        let factor = Float::sqrt(new_kinetic.max(0.0) / kinetic);
+        system.velocities = system
+            .velocities
+            .iter()
+            .map(|&v| v * factor)
+            .collect::<Vec<Vector3<Float>>>();
+        *self.reservoir.lock().unwrap() -= new_kinetic - kinetic;
+    }
+
+    fn state(&self) -> PropagatorState {
+        PropagatorState::from_floats(vec![*self.reservoir.lock().unwrap()])
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(0, 1, 0)?;
+        *self.reservoir.lock().unwrap() = state.floats[0];
+        Ok(())
+    }
+
+    fn set_target(&mut self, target: Float) {
+        self.target = target
+    }
+}
+
+/// Target temperature as a function of the step of a run.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemperatureSchedule {
+    /// Linear ramp from `start` to `end` over `steps` steps, then held at `end`.
+    Linear {
+        /// Temperature at the first step.
+        start: Float,
+        /// Temperature at the end of the ramp.
+        end: Float,
+        /// Length of the ramp in steps.
+        steps: usize,
+    },
+    /// Exponential approach from `start` to `end` which closes the remaining gap by a factor of
+    /// `decay` on every step, as in a simulated annealing cooling schedule.
+    Exponential {
+        /// Temperature at the first step.
+        start: Float,
+        /// Temperature approached as the run goes on.
+        end: Float,
+        /// Fraction of the remaining gap left after each step.
+        decay: Float,
+    },
+    /// Linear interpolation between `(step, temperature)` points sorted by step, held at the
+    /// first and last temperatures outside of the points.
+    Piecewise(Vec<(usize, Float)>),
+}
+
+impl TemperatureSchedule {
+    /// Returns a linear ramp from `start` to `end` over `steps` steps.
+    pub fn linear(start: Float, end: Float, steps: usize) -> TemperatureSchedule {
+        TemperatureSchedule::Linear { start, end, steps }
+    }
+
+    /// Returns an exponential approach from `start` to `end` by a factor of `decay` per step.
+    pub fn exponential(start: Float, end: Float, decay: Float) -> TemperatureSchedule {
+        TemperatureSchedule::Exponential { start, end, decay }
+    }
+
+    /// Returns a piecewise linear schedule through `(step, temperature)` points.
+    ///
+    /// Repeating a temperature at two steps holds it between them, so heating, holding, and
+    /// cooling stages form a single schedule. An empty list of points is an error.
+    pub fn piecewise(mut points: Vec<(usize, Float)>) -> Result<TemperatureSchedule, VelvetError> {
+        if points.is_empty() {
+            return Err(VelvetError::invalid("schedule", "a piecewise schedule needs at least one point"));
+        }
+        points.sort_by_key(|&(step, _)| step);
+        Ok(TemperatureSchedule::Piecewise(points))
+    }
+
+    /// Returns the target temperature at `step`.
+    pub fn temperature(&self, step: usize) -> Float {
+        match self {
+            TemperatureSchedule::Linear { start, end, steps } => {
+                if step >= *steps {
+                    *end
+                } else {
+                    start + (end - start) * step as Float / *steps as Float
+                }
+            }
+            TemperatureSchedule::Exponential { start, end, decay } => {
+                end + (start - end) * decay.powi(step.min(i32::MAX as usize) as i32)
+            }
+            TemperatureSchedule::Piecewise(points) => {
+                let next = points.iter().position(|&(s, _)| s > step);
+                match next {
+                    Some(0) => points[0].1,
+                    Some(k) => {
+                        let (s0, t0) = points[k - 1];
+                        let (s1, t1) = points[k];
+                        t0 + (t1 - t0) * (step - s0) as Float / (s1 - s0) as Float
+                    }
+                    None => points[points.len() - 1].1,
+                }
+            }
+        }
+    }
+}
+
+/// Thermostat whose target temperature follows a [`TemperatureSchedule`].
+///
+/// The target of the wrapped thermostat is set from the schedule before every step, so heating
+/// ramps and simulated annealing run as a single stage without replacing the thermostat. The
+/// step count continues across consecutive runs of a [`Simulation`](crate::simulation::Simulation)
+/// and is stored in checkpoints. The conserved energy of an extended system thermostat is not
+/// conserved while its target changes.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // heat to 300 K over 1000 steps, hold for 1000 steps, and cool back down to 10 K
+/// let schedule = TemperatureSchedule::piecewise(vec![(0, 10.0), (1000, 300.0), (2000, 300.0), (3000, 10.0)])?;
+/// let thermostat = Scheduled::new(Berendsen::new(10.0, 2.0), schedule);
+/// let md = MolecularDynamics::new(VelocityVerlet::new(1.0), thermostat);
+/// # Ok::<(), VelvetError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct Scheduled<T> {
+    thermostat: T,
+    schedule: TemperatureSchedule,
+    step: usize,
+}
+
+impl<T: Thermostat> Scheduled<T> {
+    /// Returns `thermostat` with its target temperature set by `schedule`.
+    pub fn new(thermostat: T, schedule: TemperatureSchedule) -> Scheduled<T> {
+        Scheduled {
+            thermostat,
+            schedule,
+            step: 0,
+        }
+    }
+
+    /// Returns the current target temperature of the schedule.
+    pub fn target(&self) -> Float {
+        self.schedule.temperature(self.step)
+    }
+
+    /// Returns a reference to the wrapped thermostat.
+    pub fn thermostat(&self) -> &T {
+        &self.thermostat
+    }
+}
+
+impl<T: Thermostat> Thermostat for Scheduled<T> {
+    fn setup(&mut self, system: &System) -> Result<(), VelvetError> {
+        self.thermostat.set_target(self.target());
+        self.thermostat.setup(system)
+    }
+
+    fn pre_integrate(&mut self, system: &mut System) {
+        self.thermostat.set_target(self.target());
+        self.thermostat.pre_integrate(system)
+    }
+
+    fn post_integrate(&mut self, system: &mut System) {
+        self.thermostat.post_integrate(system);
+        self.step += 1;
+    }
+
+    // the step of the schedule is stored beside the state of the wrapped thermostat
+    fn state(&self) -> PropagatorState {
+        PropagatorState {
+            integers: vec![self.step as u64],
+            parts: vec![self.thermostat.state()],
+            ..PropagatorState::default()
+        }
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        state.expect(1, 0, 1)?;
+        self.thermostat.restore(&state.parts[0])?;
+        self.step = state.integers[0] as usize;
+        self.thermostat.set_target(self.target());
+        Ok(())
+    }
+}
+
+/// Thermostat which controls the temperature of only the atoms in a group.
+///
+/// The wrapped thermostat sees a system made of the atoms of the named
+/// [`Group`](crate::system::topology::Group), so it measures and controls the temperature of
+/// those atoms alone, and only their velocities are changed. The constraints and frozen atoms
+/// among the atoms of the group are carried into that system, so the degrees of freedom of the
+/// group are counted correctly. A surface slab with fixed bottom layers is thermostatted through
+/// its mobile atoms, and a region around an impact or a reaction can be left to evolve in the
+/// microcanonical ensemble while a surrounding shell removes the excess heat. The group is looked
+/// up when the thermostat is set up, which fails if the system has no group of that name.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // thermostat the atoms above the frozen bottom layers of a slab
+/// let thermostat = Grouped::new(Berendsen::new(300.0, 10.0), "mobile");
+/// let md = Frozen::new(MolecularDynamics::new(VelocityVerlet::new(1.0), thermostat), "fixed");
+/// ```
+#[derive(Clone, Debug)]
+pub struct Grouped<T> {
+    thermostat: T,
+    group: String,
+    atoms: Vec<usize>,
+}
+
+impl<T: Thermostat> Grouped<T> {
+    /// Returns `thermostat` restricted to the atoms of the group named `group`.
+    pub fn new(thermostat: T, group: &str) -> Grouped<T> {
+        Grouped {
+            thermostat,
+            group: group.to_string(),
+            atoms: Vec::new(),
+        }
+    }
+
+    /// Returns a reference to the wrapped thermostat.
+    pub fn thermostat(&self) -> &T {
+        &self.thermostat
+    }
+
+    // Returns a system made of the atoms of the group and the part of the topology among them.
+    fn subsystem(&self, system: &System) -> System {
+        System {
+            size: self.atoms.len(),
+            cell: system.cell.clone(),
+            species: self.atoms.iter().map(|&i| system.species[i]).collect(),
+            positions: self.atoms.iter().map(|&i| system.positions[i]).collect(),
+            velocities: self.atoms.iter().map(|&i| system.velocities[i]).collect(),
+            topology: system.topology.select(&self.atoms),
+        }
+    }
+
+    // Applies `f` to the atoms of the group and copies their new velocities back to `system`.
+    fn apply<F: FnOnce(&mut T, &mut System)>(&mut self, system: &mut System, f: F) {
+        let mut subsystem = self.subsystem(system);
+        f(&mut self.thermostat, &mut subsystem);
+        for (&i, velocity) in self.atoms.iter().zip(subsystem.velocities) {
+            system.velocities[i] = velocity;
+        }
+    }
+}
+
+impl<T: Thermostat> Thermostat for Grouped<T> {
+    fn setup(&mut self, system: &System) -> Result<(), VelvetError> {
+        self.atoms = match system.topology.group(&self.group) {
+            Some(atoms) => atoms.to_vec(),
+            None => return Err(VelvetError::UnknownGroup(self.group.clone())),
+        };
+        let subsystem = self.subsystem(system);
+        self.thermostat.setup(&subsystem)
+    }
+
+    fn pre_integrate(&mut self, system: &mut System) {
+        self.apply(system, |thermostat, subsystem| thermostat.pre_integrate(subsystem))
+    }
+
+    fn post_integrate(&mut self, system: &mut System) {
+        self.apply(system, |thermostat, subsystem| thermostat.post_integrate(subsystem))
+    }
+
+    fn set_target(&mut self, target: Float) {
+        self.thermostat.set_target(target)
+    }
+
+    fn state(&self) -> PropagatorState {
+        self.thermostat.state()
+    }
+
+    fn restore(&mut self, state: &PropagatorState) -> Result<(), VelvetError> {
+        self.thermostat.restore(state)
+    }
+}
+
+/// Velocity rescaling which holds slabs of the cell at the temperatures of a profile.
+///
+/// The cell is divided into equal slabs along one of its vectors and after every step the
+/// thermal velocities of the atoms in each slab are rescaled toward the target of that slab.
+/// Thermal velocities are measured relative to the center of mass velocity of the slab, so a
+/// streaming flow is left untouched. Rather than a hot and a cold thermostat at two slabs of a
+/// nonequilibrium simulation with the gradient left to develop between them, the whole
+/// temperature profile is imposed and the heat flux needed to sustain it is measured, e.g. from
+/// the energy added to each slab.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // 20 slabs along z from 250 K at the faces of the cell to 350 K at its center
+/// let thermostat = ProfileRescaling::linear(2, 20, 250.0, 350.0, 10.0)?;
+/// let md = MolecularDynamics::new(VelocityVerlet::new(1.0), thermostat);
+/// # Ok::<(), VelvetError>(())
+/// ```
+#[derive(Clone, Debug)]
+pub struct ProfileRescaling {
+    axis: usize,
+    targets: Vec<Float>,
+    tau: Float,
+}
+
+impl ProfileRescaling {
+    /// Returns a new [`ProfileRescaling`] thermostat.
+    ///
+    /// # Arguments
+    ///
+    /// * `axis` - Cell vector normal to the slabs, 0 for `a`, 1 for `b`, and 2 for `c`.
+    /// * `targets` - Target temperature of each slab in order along the cell vector.
+    /// * `tau` - Timestep of the thermostat expressed as a multiple of the integrator's timestep, 1 rescales exactly.
+    ///
+    /// Returns an error if `axis` is not 0, 1, or 2 or if there are no targets.
+    pub fn new(axis: usize, targets: Vec<Float>, tau: Float) -> Result<ProfileRescaling, VelvetError> {
+        if axis > 2 {
+            return Err(VelvetError::invalid(
+                "profile axis",
+                format!("expected 0, 1, or 2, found {}", axis),
+            ));
+        }
+        if targets.is_empty() {
+            return Err(VelvetError::invalid("profile", "the profile must have at least one slab"));
+        }
+        Ok(ProfileRescaling { axis, targets, tau })
+    }
+
+    /// Returns a [`ProfileRescaling`] thermostat with a linear gradient which is periodic across the cell.
+    ///
+    /// The target rises linearly from `cold` at the faces of the cell to `hot` at its center, which gives
+    /// two opposite gradients compatible with the periodic boundary. Returns an error if `axis` is
+    /// not 0, 1, or 2.
+    pub fn linear(
+        axis: usize,
+        slabs: usize,
+        cold: Float,
+        hot: Float,
+        tau: Float,
+    ) -> Result<ProfileRescaling, VelvetError> {
+        let slabs = slabs.max(1);
+        let targets = (0..slabs)
+            .map(|k| {
+                let s = (k as Float + 0.5) / slabs as Float;
+                cold + (hot - cold) * (1.0 - (2.0 * s - 1.0).abs())
+            })
+            .collect();
+        ProfileRescaling::new(axis, targets, tau)
+    }
+
+    /// Returns the target temperature of each slab.
+    pub fn targets(&self) -> &[Float] {
+        &self.targets
+    }
+
+    // Returns the indices of the atoms in each slab.
+    fn slabs(&self, system: &System) -> Vec<Vec<usize>> {
+        let n = self.targets.len();
+        let mut slabs = vec![Vec::new(); n];
+        for (i, position) in system.positions.iter().enumerate() {
+            let s = system.cell.fractional(position)[self.axis];
+            let s = s - s.floor();
+            slabs[((s * n as Float) as usize).min(n - 1)].push(i);
+        }
+        slabs
+    }
+}
+
+impl Thermostat for ProfileRescaling {
+    fn post_integrate(&mut self, system: &mut System) {
+        for (atoms, target) in self.slabs(system).iter().zip(self.targets.iter()) {
+            let mass: Float = atoms.iter().map(|&i| system.species[i].mass()).sum();
+            if atoms.len() < 2 || mass <= 0.0 {
+                continue;
+            }
+            let momentum: Vector3<Float> = atoms
+                .iter()
+                .map(|&i| system.species[i].mass() * system.velocities[i])
+                .sum();
+            let streaming = momentum / mass;
+            let kinetic: Float = atoms
+                .iter()
+                .map(|&i| 0.5 * system.species[i].mass() * (system.velocities[i] - streaming).norm_squared())
+                .sum();
+            // the center of mass motion of the slab is not thermal
+            let dof = (3 * (atoms.len() - 1)) as Float;
+            let temperature = 2.0 * kinetic / (dof * BOLTZMANN);
+            if temperature <= 0.0 {
+                continue;
+            }
+            let factor = Float::sqrt(1.0 + (target / temperature - 1.0) / self.tau);
+            for &i in atoms {
+                system.velocities[i] = streaming + (system.velocities[i] - streaming) * factor;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TemperatureSchedule;
+
+    #[test]
+    fn schedules() {
+        let linear = TemperatureSchedule::linear(100.0, 300.0, 200);
+        assert_eq!(linear.temperature(0), 100.0);
+        assert_eq!(linear.temperature(50), 150.0);
+        assert_eq!(linear.temperature(500), 300.0);
+
+        let exponential = TemperatureSchedule::exponential(300.0, 10.0, 0.5);
+        assert_eq!(exponential.temperature(0), 300.0);
+        assert_eq!(exponential.temperature(1), 155.0);
+        assert!((exponential.temperature(100) - 10.0).abs() < 1e-3);
+
+        let piecewise =
+            TemperatureSchedule::piecewise(vec![(200, 300.0), (100, 300.0), (0, 10.0), (300, 50.0)]).unwrap();
+        assert_eq!(piecewise.temperature(0), 10.0);
+        assert_eq!(piecewise.temperature(50), 155.0);
+        assert_eq!(piecewise.temperature(150), 300.0);
+        assert_eq!(piecewise.temperature(250), 175.0);
+        assert_eq!(piecewise.temperature(1000), 50.0);
+        assert!(TemperatureSchedule::piecewise(Vec::new()).is_err());
     }
 }
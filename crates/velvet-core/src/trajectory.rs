@@ -0,0 +1,289 @@
+//! In-memory trajectories recorded during a simulation.
+
+use std::ops::Index;
+use std::slice::{Iter, SliceIndex};
+
+use nalgebra::Vector3;
+
+use crate::internal::Float;
+use crate::outputs::trajectory::FrameSelection;
+use crate::system::cell::Cell;
+use crate::system::System;
+
+/// Snapshot of the system at one step of a [`Trajectory`].
+#[derive(Clone, Debug)]
+pub struct TrajectoryFrame {
+    /// Number of steps completed by the simulation when the frame was recorded.
+    pub step: usize,
+    /// Positions of the recorded atoms, if positions are recorded.
+    pub positions: Option<Vec<Vector3<Float>>>,
+    /// Velocities of the recorded atoms, if velocities are recorded.
+    pub velocities: Option<Vec<Vector3<Float>>>,
+    /// Simulation cell, if the cell is recorded.
+    pub cell: Option<Cell>,
+}
+
+/// Snapshots of a system kept in memory for analysis after a run.
+///
+/// A trajectory given to [`Simulation::record_trajectory`](crate::simulation::Simulation::record_trajectory)
+/// records a frame at the start of each run and whenever the step counter of the simulation is a
+/// multiple of the interval. Positions and the cell are recorded by default and velocities can
+/// be added. A [`FrameSelection`] limits the frames to some of the atoms, whose indices are
+/// resolved on the first frame and returned by [`atoms`](Trajectory::atoms).
+///
+/// Frames can be iterated over, indexed, and sliced like a vector.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+/// use nalgebra::Vector3;
+///
+/// let argon = Species::from_element(Element::Ar);
+/// let system = System {
+///     size: 2,
+///     cell: Cell::cubic(20.0),
+///     species: vec![argon; 2],
+///     positions: vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new(9.0, 5.0, 5.0)],
+///     velocities: vec![Vector3::zeros(); 2],
+///     topology: Topology::default(),
+/// };
+/// let potentials = PotentialsBuilder::new()
+///     .pair(LennardJones::new(4.184, 3.4), (argon, argon), 8.5, 1.0)
+///     .build();
+/// let md = MolecularDynamics::new(VelocityVerlet::new(1.0), NullThermostat);
+/// let mut simulation = Simulation::new(system, potentials, md, ConfigurationBuilder::new().build());
+///
+/// simulation.record_trajectory(Trajectory::new(10).velocities(true));
+/// simulation.run(100).unwrap();
+///
+/// let trajectory = simulation.trajectory().unwrap();
+/// assert_eq!(trajectory.len(), 11);
+/// let separations: Vec<f64> = trajectory[5..]
+///     .iter()
+///     .map(|frame| {
+///         let positions = frame.positions.as_ref().unwrap();
+///         (positions[1] - positions[0]).norm() as f64
+///     })
+///     .collect();
+/// assert_eq!(separations.len(), 6);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Trajectory {
+    interval: usize,
+    positions: bool,
+    velocities: bool,
+    cell: bool,
+    selection: Option<FrameSelection>,
+    atoms: Option<Vec<usize>>,
+    frames: Vec<TrajectoryFrame>,
+}
+
+impl Trajectory {
+    /// Returns an empty [`Trajectory`] of the positions and cell every `interval` steps.
+    pub fn new(interval: usize) -> Trajectory {
+        Trajectory {
+            interval: interval.max(1),
+            positions: true,
+            velocities: false,
+            cell: true,
+            selection: None,
+            atoms: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Sets whether the positions are recorded.
+    pub fn positions(mut self, positions: bool) -> Trajectory {
+        self.positions = positions;
+        self
+    }
+
+    /// Sets whether the velocities are recorded.
+    pub fn velocities(mut self, velocities: bool) -> Trajectory {
+        self.velocities = velocities;
+        self
+    }
+
+    /// Sets whether the cell is recorded.
+    pub fn cell(mut self, cell: bool) -> Trajectory {
+        self.cell = cell;
+        self
+    }
+
+    /// Records only the atoms in `selection`.
+    pub fn selection(mut self, selection: FrameSelection) -> Trajectory {
+        self.selection = Some(selection);
+        self
+    }
+
+    /// Returns the number of steps between frames.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Returns the indices of the recorded atoms, or `None` if every atom is recorded.
+    pub fn atoms(&self) -> Option<&[usize]> {
+        self.atoms.as_deref()
+    }
+
+    /// Records a frame of `system` at `step` unless the last frame was recorded at the same step.
+    pub fn record(&mut self, step: usize, system: &System) {
+        if self.frames.last().is_some_and(|frame| frame.step == step) {
+            return;
+        }
+        if self.atoms.is_none() {
+            self.atoms = self.selection.as_ref().map(|selection| selection.indices(system));
+        }
+        let pick = |values: &[Vector3<Float>]| match &self.atoms {
+            Some(atoms) => atoms.iter().map(|&i| values[i]).collect(),
+            None => values.to_vec(),
+        };
+        let frame = TrajectoryFrame {
+            step,
+            positions: self.positions.then(|| pick(&system.positions)),
+            velocities: self.velocities.then(|| pick(&system.velocities)),
+            cell: self.cell.then(|| system.cell.clone()),
+        };
+        self.frames.push(frame);
+    }
+
+    /// Records a frame of `system` if `step` is a multiple of the interval.
+    pub fn record_if_due(&mut self, step: usize, system: &System) {
+        if step.is_multiple_of(self.interval) {
+            self.record(step, system);
+        }
+    }
+
+    /// Returns the number of frames.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns true if no frames have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Returns the frame at `index`, or `None` if it is out of range.
+    pub fn get(&self, index: usize) -> Option<&TrajectoryFrame> {
+        self.frames.get(index)
+    }
+
+    /// Returns the most recent frame.
+    pub fn last(&self) -> Option<&TrajectoryFrame> {
+        self.frames.last()
+    }
+
+    /// Returns every frame in the order they were recorded.
+    pub fn frames(&self) -> &[TrajectoryFrame] {
+        &self.frames
+    }
+
+    /// Returns an iterator over the frames.
+    pub fn iter(&self) -> Iter<'_, TrajectoryFrame> {
+        self.frames.iter()
+    }
+
+    /// Returns an iterator over every `n`th frame starting with the first.
+    pub fn stride(&self, n: usize) -> impl Iterator<Item = &TrajectoryFrame> {
+        self.frames.iter().step_by(n.max(1))
+    }
+
+    /// Returns the frames recorded between steps `start` and `end`, inclusive.
+    pub fn between_steps(&self, start: usize, end: usize) -> &[TrajectoryFrame] {
+        let first = self.frames.partition_point(|frame| frame.step < start);
+        let last = self.frames.partition_point(|frame| frame.step <= end);
+        &self.frames[first..last.max(first)]
+    }
+
+    /// Returns the step of each frame.
+    pub fn steps(&self) -> Vec<usize> {
+        self.frames.iter().map(|frame| frame.step).collect()
+    }
+
+    /// Removes every frame, keeping the configuration.
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+
+    /// Consumes the trajectory and returns its frames.
+    pub fn into_frames(self) -> Vec<TrajectoryFrame> {
+        self.frames
+    }
+}
+
+impl<I: SliceIndex<[TrajectoryFrame]>> Index<I> for Trajectory {
+    type Output = I::Output;
+
+    fn index(&self, index: I) -> &Self::Output {
+        &self.frames[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a Trajectory {
+    type Item = &'a TrajectoryFrame;
+    type IntoIter = Iter<'a, TrajectoryFrame>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.frames.iter()
+    }
+}
+
+impl IntoIterator for Trajectory {
+    type Item = TrajectoryFrame;
+    type IntoIter = std::vec::IntoIter<TrajectoryFrame>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.frames.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Trajectory;
+    use crate::internal::Float;
+    use crate::outputs::trajectory::FrameSelection;
+    use crate::system::cell::Cell;
+    use crate::system::species::Species;
+    use crate::system::topology::Topology;
+    use crate::system::System;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn record_and_slice() {
+        let mut system = System {
+            size: 3,
+            cell: Cell::cubic(10.0),
+            species: vec![Species::new(1.0, 0.0); 3],
+            positions: vec![Vector3::zeros(); 3],
+            velocities: vec![Vector3::new(1.0, 0.0, 0.0); 3],
+            topology: Topology::default(),
+        };
+        let mut trajectory = Trajectory::new(5)
+            .velocities(true)
+            .cell(false)
+            .selection(FrameSelection::new().atoms(vec![2, 0]));
+        for step in 0..=20 {
+            system.positions[2].x = step as Float * 0.1;
+            trajectory.record_if_due(step, &system);
+        }
+        // repeated steps are recorded once
+        trajectory.record(20, &system);
+
+        assert_eq!(trajectory.len(), 5);
+        assert_eq!(trajectory.steps(), vec![0, 5, 10, 15, 20]);
+        assert_eq!(trajectory.atoms(), Some(&[0, 2][..]));
+        let frame = &trajectory[2];
+        assert!(frame.cell.is_none());
+        assert_eq!(frame.velocities.as_ref().unwrap().len(), 2);
+        assert!((frame.positions.as_ref().unwrap()[1].x - 1.0).abs() < 1e-6);
+
+        assert_eq!(trajectory[1..3].len(), 2);
+        assert_eq!(trajectory.between_steps(4, 15).len(), 3);
+        assert!(trajectory.between_steps(21, 30).is_empty());
+        let strided: Vec<usize> = trajectory.stride(2).map(|frame| frame.step).collect();
+        assert_eq!(strided, vec![0, 10, 20]);
+        assert_eq!((&trajectory).into_iter().count(), 5);
+    }
+}
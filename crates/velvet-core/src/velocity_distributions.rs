@@ -1,12 +1,15 @@
 //! Algorithms which initialize the temperature of a sytem from a velocity distribution.
 
 use nalgebra::Vector3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rand_distr::{Distribution, Normal};
 
 use crate::internal::consts::BOLTZMANN;
 use crate::internal::Float;
 use crate::properties::temperature::Temperature;
 use crate::properties::IntrinsicProperty;
+use crate::random::shared_rng;
 use crate::system::System;
 
 /// Shared behavior for algorithms which initialize the temperature of a system from a velocity distribution.
@@ -17,6 +20,20 @@ pub trait VelocityDistribution: Send + Sync {
 
 /// Maxwell-Boltzmann style velocity distribution.
 ///
+/// By default the net momentum of the sampled velocities is removed so the system does not drift,
+/// and the velocities are then rescaled to exactly the target temperature. Velocities are drawn
+/// from a thread local generator unless a seed is set, in which case every application of the
+/// distribution produces the same velocities.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_core::prelude::*;
+///
+/// // reproducible velocities which keep the sampled temperature
+/// let distribution = Boltzmann::new(300.0).seed(42).rescale(false);
+/// ```
+///
 /// # References
 ///
 /// [1] Hernandez, Hugo. "Standard Maxwell-Boltzmann distribution: definition and properties." ForsChem Research Reports 2 (2017): 2017-2.
@@ -24,6 +41,9 @@ pub trait VelocityDistribution: Send + Sync {
 pub struct Boltzmann {
     target: Float,
     distr: Normal<Float>,
+    seed: Option<u64>,
+    remove_momentum: bool,
+    rescale: bool,
 }
 
 impl Boltzmann {
@@ -34,30 +54,83 @@ impl Boltzmann {
     /// * `target` - Target temperature.
     pub fn new(target: Float) -> Boltzmann {
         let distr = Normal::new(0.0, Float::sqrt(BOLTZMANN * target)).unwrap();
-        Boltzmann { target, distr }
+        Boltzmann {
+            target,
+            distr,
+            seed: None,
+            remove_momentum: true,
+            rescale: true,
+        }
     }
-}
 
-impl VelocityDistribution for Boltzmann {
-    fn apply(&self, system: &mut System) {
+    /// Draws the velocities from a generator seeded with `seed`.
+    pub fn seed(mut self, seed: u64) -> Boltzmann {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Enables or disables the removal of the net momentum of the system.
+    pub fn remove_momentum(mut self, remove_momentum: bool) -> Boltzmann {
+        self.remove_momentum = remove_momentum;
+        self
+    }
+
+    /// Enables or disables the rescaling of the velocities to exactly the target temperature.
+    pub fn rescale(mut self, rescale: bool) -> Boltzmann {
+        self.rescale = rescale;
+        self
+    }
+
+    fn sample<R: Rng>(&self, system: &mut System, rng: &mut R) {
         system.velocities = system
             .species
             .iter()
             .map(|species| {
                 let inv_mass = 1.0 / species.mass();
-                let x = inv_mass.sqrt() * self.distr.sample(&mut rand::thread_rng());
-                let y = inv_mass.sqrt() * self.distr.sample(&mut rand::thread_rng());
-                let z = inv_mass.sqrt() * self.distr.sample(&mut rand::thread_rng());
+                let x = inv_mass.sqrt() * self.distr.sample(rng);
+                let y = inv_mass.sqrt() * self.distr.sample(rng);
+                let z = inv_mass.sqrt() * self.distr.sample(rng);
                 Vector3::new(x, y, z)
             })
             .collect::<Vec<Vector3<Float>>>();
-        scale(system, self.target);
     }
 }
 
+impl VelocityDistribution for Boltzmann {
+    fn apply(&self, system: &mut System) {
+        match self.seed {
+            Some(seed) => self.sample(system, &mut StdRng::seed_from_u64(seed)),
+            None => self.sample(system, &mut shared_rng()),
+        }
+        if self.remove_momentum {
+            remove_momentum(system);
+        }
+        if self.rescale {
+            scale(system, self.target);
+        }
+    }
+}
+
+/// Remove the net momentum of the system by shifting all velocities.
+fn remove_momentum(system: &mut System) {
+    let mass: Float = system.species.iter().map(|species| species.mass()).sum();
+    let momentum: Vector3<Float> = system
+        .species
+        .iter()
+        .zip(system.velocities.iter())
+        .map(|(species, velocity)| species.mass() * *velocity)
+        .sum();
+    let drift = momentum / mass;
+    system.velocities.iter_mut().for_each(|velocity| *velocity -= drift);
+}
+
 /// Scale all velocities in system to the target value.
 fn scale(system: &mut System, target: Float) {
     let temperature = Temperature.calculate_intrinsic(system);
+    // a single atom has no kinetic energy left once its momentum is removed
+    if temperature == 0.0 {
+        return;
+    }
     let factor = Float::sqrt(target / temperature);
     system.velocities = system.velocities.iter().map(|&x| x * factor).collect();
 }
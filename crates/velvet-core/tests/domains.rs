@@ -0,0 +1,130 @@
+use nalgebra::Vector3;
+use std::cell::RefCell;
+
+use velvet_core::domains::{Communicator, DomainDecomposition, SerialCommunicator};
+use velvet_core::potentials::Potentials;
+use velvet_core::properties::energy::PotentialEnergy;
+use velvet_core::properties::forces::Forces;
+use velvet_core::properties::Property;
+use velvet_core::system::System;
+use velvet_test_utils as test_utils;
+use velvet_test_utils::Float;
+
+// Communicator of one rank which records its partial sums instead of exchanging them.
+struct Recording {
+    rank: usize,
+    size: usize,
+    partial: RefCell<Vec<Float>>,
+}
+
+impl Communicator for Recording {
+    fn rank(&self) -> usize {
+        self.rank
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn sum(&self, values: &mut [Float]) {
+        *self.partial.borrow_mut() = values.to_vec();
+    }
+}
+
+// 4x4x4 fcc argon crystal with randomly displaced atoms.
+fn argon_crystal() -> (System, Potentials) {
+    (test_utils::argon_crystal(4, 0.2), test_utils::argon_crystal_potentials(8.5))
+}
+
+#[test]
+fn decompose() {
+    let (system, _) = argon_crystal();
+    let mut decomposition = DomainDecomposition::new([2, 1, 3], 4.0);
+    decomposition.decompose(&system);
+    assert_eq!(decomposition.domains().len(), 6);
+    // every atom is owned by exactly one domain
+    let mut owned: Vec<usize> = decomposition.domains().iter().flat_map(|d| d.owned.clone()).collect();
+    owned.sort_unstable();
+    assert_eq!(owned, (0..system.size).collect::<Vec<usize>>());
+    // ghosts are never owned by their own domain
+    for domain in decomposition.domains() {
+        assert!(!domain.ghosts.is_empty());
+        assert!(domain.ghosts.iter().all(|g| !domain.owned.contains(g)));
+    }
+    assert_eq!(decomposition.assigned(1, 4), vec![1, 5]);
+}
+
+#[test]
+fn distributed_forces() {
+    let (system, mut potentials) = argon_crystal();
+    potentials.setup(&system);
+    let reference = Forces.calculate(&system, &potentials);
+    let energy = PotentialEnergy.calculate(&system, &potentials);
+
+    let mut decomposition = DomainDecomposition::new([2, 2, 2], 9.5);
+    decomposition.decompose(&system);
+    let forces = decomposition.forces(&system, &mut potentials, &SerialCommunicator);
+    for (f, r) in forces.iter().zip(reference.iter()) {
+        assert!((f - r).norm() < 1e-3 * r.norm().max(1.0), "{} {}", f, r);
+    }
+    let distributed = decomposition.potential_energy(&system, &mut potentials, &SerialCommunicator);
+    assert!((distributed - energy).abs() < 1e-3 * energy.abs(), "{} {}", distributed, energy);
+
+    // the partial sums of three ranks add up to the full forces
+    let mut total = vec![0.0; 3 * system.size];
+    for rank in 0..3 {
+        let communicator = Recording {
+            rank,
+            size: 3,
+            partial: RefCell::new(Vec::new()),
+        };
+        decomposition.forces(&system, &mut potentials, &communicator);
+        for (t, p) in total.iter_mut().zip(communicator.partial.borrow().iter()) {
+            *t += p;
+        }
+    }
+    for (i, r) in reference.iter().enumerate() {
+        let f = Vector3::new(total[3 * i], total[3 * i + 1], total[3 * i + 2]);
+        assert!((f - r).norm() < 1e-3 * r.norm().max(1.0));
+    }
+}
+
+#[test]
+fn local_topology() {
+    let (mut system, _) = argon_crystal();
+    system.topology.bonds = vec![[0, 1]];
+    system.topology.charges = (0..system.size).map(|i| if i % 2 == 0 { 0.5 } else { -0.5 }).collect();
+    let mut decomposition = DomainDecomposition::new([2, 2, 2], 4.0);
+    decomposition.decompose(&system);
+    for domain in decomposition.domains() {
+        let local = decomposition.local_system(&system, domain);
+        let atoms: Vec<usize> = domain.owned.iter().chain(domain.ghosts.iter()).copied().collect();
+        for (l, &i) in atoms.iter().enumerate() {
+            assert_eq!(local.charge(l), system.charge(i));
+        }
+        // the bond is kept by every domain which holds both of its atoms
+        let both = atoms.contains(&0) && atoms.contains(&1);
+        assert_eq!(local.topology.bonds.len(), both as usize);
+        if both {
+            let [i, j] = local.topology.bonds[0];
+            assert_eq!([atoms[i], atoms[j]], [0, 1]);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "beyond the halo")]
+fn bond_beyond_halo() {
+    let (mut system, _) = argon_crystal();
+    let mut decomposition = DomainDecomposition::new([2, 2, 2], 2.0);
+    decomposition.decompose(&system);
+    let owner = decomposition.domains().iter().find(|d| d.owned.contains(&0)).unwrap();
+
+    // bond the first atom to the atom at the center of the opposite domain
+    let opposite = |k: usize| (owner.index[k] as Float + 0.5) / 2.0 + 0.5;
+    let center = system.cell.cartesian(&Vector3::new(opposite(0), opposite(1), opposite(2)));
+    let distance = |i: usize| system.cell.distance(&center, &system.positions[i]);
+    let far = (0..system.size).min_by(|&i, &j| distance(i).total_cmp(&distance(j))).unwrap();
+    system.topology.bonds = vec![[0, far]];
+    decomposition.local_system(&system, owner);
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "gpu")]
+
+use velvet_core::potentials::pair::CutoffScheme;
+use velvet_core::potentials::types::{LennardJones, Mie, Morse};
+use velvet_core::potentials::{Potentials, PotentialsBuilder};
+use velvet_core::properties::energy::PairEnergy;
+use velvet_core::properties::forces::PairForces;
+use velvet_core::properties::pressure::Virial;
+use velvet_core::properties::Property;
+use velvet_core::system::elements::Element;
+use velvet_core::system::species::Species;
+use velvet_test_utils as test_utils;
+use velvet_test_utils::Float;
+
+// Asserts that pair forces, energies and virials agree to single precision on the GPU and CPU.
+fn assert_matches_cpu(builder: fn() -> PotentialsBuilder) {
+    let system = test_utils::argon_crystal(4, 0.3);
+    let mut cpu = builder().build();
+    cpu.setup(&system);
+    let mut potentials: Potentials = builder().gpu().build();
+    potentials.setup(&system);
+    if !potentials.uses_gpu() {
+        // no adapter supports compute shaders
+        return;
+    }
+
+    let (expected, actual) = (PairEnergy.calculate(&system, &cpu), PairEnergy.calculate(&system, &potentials));
+    assert!((expected - actual).abs() < 1e-4 * expected.abs().max(1.0));
+    let expected = PairForces.calculate(&system, &cpu);
+    let actual = PairForces.calculate(&system, &potentials);
+    let scale = expected.iter().map(|f| f.norm()).fold(1.0 as Float, Float::max);
+    for (e, a) in expected.iter().zip(actual.iter()) {
+        assert!((e - a).norm() < 1e-4 * scale);
+    }
+    let (expected, actual) = (Virial.calculate(&system, &cpu), Virial.calculate(&system, &potentials));
+    assert!((expected - actual).norm() < 1e-4 * expected.norm().max(1.0));
+}
+
+fn argon() -> Species {
+    Species::from_element(Element::Ar)
+}
+
+#[test]
+fn lennard_jones() {
+    assert_matches_cpu(|| PotentialsBuilder::new().pair(LennardJones::new(0.238, 3.4), (argon(), argon()), 7.0, 1.0));
+}
+
+#[test]
+fn lennard_jones_switched() {
+    assert_matches_cpu(|| {
+        PotentialsBuilder::new()
+            .pair(LennardJones::new(0.238, 3.4), (argon(), argon()), 7.0, 1.0)
+            .cutoff_scheme(CutoffScheme::Switched(6.0))
+    });
+}
+
+#[test]
+fn morse_shifted() {
+    assert_matches_cpu(|| {
+        PotentialsBuilder::new()
+            .pair(Morse::new(1.3, 0.01, 3.8), (argon(), argon()), 7.0, 1.0)
+            .cutoff_scheme(CutoffScheme::Shifted)
+    });
+}
+
+#[test]
+fn unsupported_falls_back() {
+    // evaluated on the CPU alongside a GPU backend
+    assert_matches_cpu(|| PotentialsBuilder::new().pair(Mie::new(0.238, 3.4, 14.0, 6.0), (argon(), argon()), 7.0, 1.0));
+}
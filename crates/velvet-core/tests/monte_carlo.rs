@@ -0,0 +1,91 @@
+use velvet_core::monte_carlo::MonteCarlo;
+use velvet_core::potentials::Potentials;
+use velvet_core::propagators::Propagator;
+use velvet_core::properties::energy::PotentialEnergy;
+use velvet_core::properties::Property;
+use velvet_core::random::seed_rng;
+use velvet_core::system::System;
+use velvet_test_utils as test_utils;
+use velvet_test_utils::Float;
+
+// Boltzmann constant in kcal/mol-K.
+const BOLTZMANN: Float = 0.001985875;
+
+// 2x2x2 fcc argon crystal.
+fn argon_crystal() -> (System, Potentials) {
+    let system = test_utils::argon_crystal(2, 0.0);
+    let mut potentials = test_utils::argon_crystal_potentials(4.5);
+    potentials.setup(&system);
+    (system, potentials)
+}
+
+#[test]
+fn acceptance_tuning() {
+    seed_rng(42);
+    let (mut system, mut potentials) = argon_crystal();
+    let mut mc = MonteCarlo::new(50.0, 0.01).target_acceptance(0.4).tuning_interval(5);
+    let moves = mc.moves();
+    mc.setup(&mut system, &potentials).unwrap();
+    for i in 0..200 {
+        mc.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+    }
+    let statistics = moves.calculate(&system, &potentials);
+    assert_eq!(statistics.attempted, 200 * system.size);
+    // the tiny initial displacement grows until fewer moves are accepted
+    assert!(statistics.max_displacement > 0.05);
+    let before = mc.statistics();
+    for i in 0..100 {
+        mc.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+    }
+    let after = mc.statistics();
+    let acceptance = (after.accepted - before.accepted) as Float / (after.attempted - before.attempted) as Float;
+    assert!((acceptance - 0.4).abs() < 0.1, "acceptance {}", acceptance);
+}
+
+#[test]
+fn metropolis_sampling() {
+    seed_rng(42);
+    let (mut system, mut potentials) = argon_crystal();
+    let minimum = PotentialEnergy.calculate(&system, &potentials);
+
+    // a cold crystal stays close to its minimum
+    let mut mc = MonteCarlo::new(5.0, 0.05);
+    mc.setup(&mut system, &potentials).unwrap();
+    let mut cold = 0.0;
+    for i in 0..200 {
+        mc.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+        if i >= 100 {
+            cold += PotentialEnergy.calculate(&system, &potentials) / 100.0;
+        }
+    }
+
+    // equipartition gives 3/2 kT of potential energy per atom in a harmonic crystal
+    let mut mc = MonteCarlo::new(20.0, 0.05);
+    let mut warm = 0.0;
+    for i in 0..200 {
+        mc.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+        if i >= 100 {
+            warm += PotentialEnergy.calculate(&system, &potentials) / 100.0;
+        }
+    }
+    assert!(cold > minimum && warm > cold, "{} {} {}", minimum, cold, warm);
+    let expected = 1.5 * 32.0 * BOLTZMANN * (20.0 - 5.0);
+    assert!(((warm - cold) - expected).abs() < 0.5 * expected, "{} {}", warm - cold, expected);
+}
+
+#[test]
+fn restore_statistics() {
+    seed_rng(42);
+    let (mut system, potentials) = argon_crystal();
+    let mut mc = MonteCarlo::new(50.0, 0.1).tuning_interval(1);
+    mc.propagate(&mut system, &potentials);
+    let state = mc.state();
+    let mut restored = MonteCarlo::new(50.0, 0.2);
+    restored.restore(&state).unwrap();
+    assert_eq!(restored.statistics(), mc.statistics());
+    assert_eq!(restored.statistics().attempted, 32);
+}
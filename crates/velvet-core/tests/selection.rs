@@ -1,8 +1,13 @@
+use nalgebra::Vector3;
 use velvet_core::selection::{
-    setup_pairs_by_species, setup_pairs_with_charge, update_pairs_by_cutoff_radius, Selection,
+    setup_pairs_by_species, setup_pairs_with_charge, update_image_pairs,
+    update_pairs_by_cutoff_radius, Selection,
 };
+use velvet_core::system::cell::Cell;
 use velvet_core::system::elements::Element;
 use velvet_core::system::species::Species;
+use velvet_core::system::topology::Topology;
+use velvet_core::system::System;
 use velvet_test_utils as test_utils;
 
 #[test]
@@ -37,3 +42,29 @@ fn setup_pairs_with_charge_update_pairs_by_cutoff_radius() {
     selection.update(&system, cutoff);
     assert_ne!(selection.indices().count(), 0);
 }
+
+#[test]
+fn update_image_pairs_small_cell() {
+    let argon = Species::from_element(Element::Ar);
+    let system = System {
+        size: 2,
+        cell: Cell::cubic(3.0),
+        species: vec![argon; 2],
+        positions: vec![Vector3::new(0.5, 0.5, 0.5), Vector3::new(2.0, 0.5, 0.5)],
+        velocities: vec![Vector3::zeros(); 2],
+        topology: Topology::default(),
+    };
+    let mut selection = Selection::new(setup_pairs_by_species, update_pairs_by_cutoff_radius);
+    selection.setup(&system, (argon, argon));
+    let images = update_image_pairs(&system, selection.possible_indices(), &[0, 1], 3.2);
+
+    // both images of the other atom along x at 1.5 angstroms and four more at 3.35 are excluded
+    let others: Vec<_> = images.iter().filter(|image| image.i != image.j).collect();
+    assert_eq!(others.len(), 2);
+    assert!(others.iter().all(|image| (image.separation(&system).norm() - 1.5).abs() < 1e-5));
+
+    // each atom sees one of each pair of opposite images of itself at 3 angstroms
+    let selves: Vec<_> = images.iter().filter(|image| image.i == image.j).collect();
+    assert_eq!(selves.len(), 6);
+    assert!(selves.iter().all(|image| (image.separation(&system).norm() - 3.0).abs() < 1e-5));
+}
@@ -0,0 +1,46 @@
+use velvet_core::minimizers::Fire;
+use velvet_core::potentials::Potentials;
+use velvet_core::system::System;
+use velvet_core::tensile::{elastic_modulus, TensileTest};
+use velvet_test_utils as test_utils;
+
+// 4x4x4 fcc argon crystal.
+fn argon_crystal() -> (System, Potentials) {
+    (test_utils::argon_crystal(4, 0.0), test_utils::argon_crystal_potentials(8.5))
+}
+
+#[test]
+fn quasi_static_tension() {
+    let (mut system, mut potentials) = argon_crystal();
+    let length = system.cell.a();
+    let mut test = TensileTest::new(0, 0.01).steps(50);
+    let curve = test.run(&mut system, &mut potentials, || Fire::new(0.5, 0.05), 4).unwrap();
+
+    assert_eq!(curve.len(), 5);
+    assert!((test.strain() - 0.04).abs() < 1e-5);
+    assert!((system.cell.a() - 1.04 * length).abs() < 1e-3);
+    assert!((system.cell.b() - length).abs() < 1e-4);
+    // the stress rises steadily and the energy with it in the elastic regime
+    for pair in curve.windows(2) {
+        assert!(pair[1].stress > pair[0].stress);
+        assert!(pair[1].energy > pair[0].energy);
+    }
+    let modulus = elastic_modulus(&curve, 0.02);
+    assert!(modulus > 0.0);
+    let ultimate = test.ultimate().unwrap();
+    assert_eq!(ultimate.strain, curve[4].strain);
+
+    // continuing the test adds a point per increment
+    let curve = test.run(&mut system, &mut potentials, || Fire::new(0.5, 0.05), 1).unwrap();
+    assert_eq!(curve.len(), 6);
+    assert!((curve[5].strain - 0.05).abs() < 1e-5);
+}
+
+#[test]
+fn compression() {
+    let (mut system, mut potentials) = argon_crystal();
+    let mut test = TensileTest::new(2, -0.01).steps(10);
+    let curve = test.run(&mut system, &mut potentials, || Fire::new(0.5, 0.05), 2).unwrap();
+    assert!(curve[2].stress < curve[0].stress);
+    assert!(elastic_modulus(&curve, 0.05) > 0.0);
+}
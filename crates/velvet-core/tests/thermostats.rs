@@ -0,0 +1,272 @@
+use nalgebra::Vector3;
+
+use velvet_core::checkpoint::PropagatorState;
+use velvet_core::integrators::VelocityVerlet;
+use velvet_core::potentials::Potentials;
+use velvet_core::propagators::{MolecularDynamics, Propagator};
+use velvet_core::properties::energy::TotalEnergy;
+use velvet_core::properties::temperature::Temperature;
+use velvet_core::properties::{IntrinsicProperty, Property};
+use velvet_core::random::seed_rng;
+use velvet_core::system::System;
+use velvet_core::thermostats::{
+    Andersen, Berendsen, Bussi, Grouped, NoseHoover, NoseHooverChain, ProfileRescaling, Scheduled, TemperatureSchedule,
+    Thermostat,
+};
+use velvet_test_utils as test_utils;
+use velvet_test_utils::Float;
+
+// Boltzmann constant in kcal/mol-K.
+const BOLTZMANN: Float = 0.001985875;
+
+// 2x2x2 fcc argon crystal at 20 K.
+fn argon_crystal() -> (System, Potentials) {
+    let system = test_utils::argon_crystal(2, 0.0);
+    let mut potentials = test_utils::argon_crystal_potentials(4.5);
+    potentials.setup(&system);
+    (system, potentials)
+}
+
+#[test]
+fn conserved_energy() {
+    let (mut system, mut potentials) = argon_crystal();
+
+    // the thermostat heats the crystal far above its starting temperature
+    let thermostat = NoseHoover::new(100.0, 1.0, 0.01);
+    let conserved = thermostat.conserved_energy();
+    let mut md = MolecularDynamics::new(VelocityVerlet::new(0.01), thermostat);
+    md.setup(&mut system, &potentials).unwrap();
+    let initial = conserved.calculate(&system, &potentials);
+    let total = TotalEnergy.calculate(&system, &potentials);
+    let mut drift: Float = 0.0;
+    for i in 0..2000 {
+        md.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+        drift = drift.max((conserved.calculate(&system, &potentials) - initial).abs());
+    }
+    let heat = TotalEnergy.calculate(&system, &potentials) - total;
+    assert!(heat > 1.0);
+    assert!(drift < 0.05 * heat, "drift {} with {} of heat", drift, heat);
+}
+
+#[test]
+fn bussi_conserved_energy() {
+    seed_rng(42);
+    let (mut system, mut potentials) = argon_crystal();
+    let thermostat = Bussi::new(100.0, 20.0);
+    let conserved = thermostat.conserved_energy();
+    let mut md = MolecularDynamics::new(VelocityVerlet::new(0.01), thermostat);
+    md.setup(&mut system, &potentials).unwrap();
+    let initial = conserved.calculate(&system, &potentials);
+    let total = TotalEnergy.calculate(&system, &potentials);
+    let mut temperature = 0.0;
+    for i in 0..2000 {
+        md.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+        if i >= 1000 {
+            temperature += Temperature.calculate_intrinsic(&system) / 1000.0;
+        }
+    }
+    let heat = TotalEnergy.calculate(&system, &potentials) - total;
+    assert!(heat > 1.0);
+    let drift = (conserved.calculate(&system, &potentials) - initial).abs();
+    assert!(drift < 0.1 * heat, "drift {} with {} of heat", drift, heat);
+    assert!((temperature - 100.0).abs() < 25.0, "average temperature {}", temperature);
+}
+
+#[test]
+fn bussi_at_rest() {
+    let (mut system, _) = argon_crystal();
+    system.velocities.iter_mut().for_each(|velocity| *velocity = Vector3::zeros());
+    let mut thermostat = Bussi::new(100.0, 20.0);
+    thermostat.setup(&system).unwrap();
+    thermostat.post_integrate(&mut system);
+
+    // the velocities stay at rest instead of turning into NaN
+    assert!(system.velocities.iter().all(|velocity| velocity.norm() == 0.0));
+    assert_eq!(thermostat.state().floats, vec![0.0]);
+}
+
+#[test]
+fn andersen_temperature() {
+    seed_rng(42);
+    let (mut system, mut potentials) = argon_crystal();
+    let mut md = MolecularDynamics::new(VelocityVerlet::new(1.0), Andersen::new(100.0, 0.01, 1.0));
+    md.setup(&mut system, &potentials).unwrap();
+    let mut temperature = 0.0;
+    for i in 0..2000 {
+        md.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+        if i >= 1000 {
+            temperature += Temperature.calculate_intrinsic(&system) / 1000.0;
+        }
+    }
+    assert!((temperature - 100.0).abs() < 25.0, "average temperature {}", temperature);
+}
+
+#[test]
+fn chain_conserved_energy() {
+    let (mut system, mut potentials) = argon_crystal();
+    let thermostat = NoseHooverChain::new(100.0, 0.05, 0.5)
+        .chain_length(4)
+        .unwrap()
+        .substeps(2)
+        .suzuki_yoshida(5)
+        .unwrap();
+    let conserved = thermostat.conserved_energy();
+    let mut md = MolecularDynamics::new(VelocityVerlet::new(0.5), thermostat);
+    md.setup(&mut system, &potentials).unwrap();
+    let initial = conserved.calculate(&system, &potentials);
+    let total = TotalEnergy.calculate(&system, &potentials);
+    let mut drift: Float = 0.0;
+    let mut temperature = 0.0;
+    for i in 0..4000 {
+        md.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+        drift = drift.max((conserved.calculate(&system, &potentials) - initial).abs());
+        if i >= 2000 {
+            temperature += Temperature.calculate_intrinsic(&system) / 2000.0;
+        }
+    }
+    let heat = TotalEnergy.calculate(&system, &potentials) - total;
+    assert!(heat > 1.0);
+    assert!(drift < 0.1 * heat, "drift {} with {} of heat", drift, heat);
+    assert!((temperature - 100.0).abs() < 25.0, "average temperature {}", temperature);
+}
+
+#[test]
+fn chain_state() {
+    let (mut system, _) = argon_crystal();
+    let mut thermostat = NoseHooverChain::new(100.0, 0.05, 0.5).chain_length(2).unwrap();
+    thermostat.setup(&system).unwrap();
+    thermostat.pre_integrate(&mut system);
+    let state = thermostat.state();
+    assert_eq!(state.floats.len(), 4);
+    let mut restored = NoseHooverChain::new(100.0, 0.05, 0.5).chain_length(2).unwrap();
+    restored.restore(&state).unwrap();
+    assert_eq!(restored.state(), state);
+    // the first thermostat accelerates to heat the system
+    assert!(state.floats[2] < 0.0);
+    // the state of a chain of another length is rejected
+    let mut longer = NoseHooverChain::new(100.0, 0.05, 0.5).chain_length(3).unwrap();
+    assert!(longer.restore(&state).is_err());
+}
+
+#[test]
+fn temperature_profile() {
+    seed_rng(42);
+    let linear = ProfileRescaling::linear(2, 4, 100.0, 300.0, 1.0).unwrap();
+    assert_eq!(linear.targets(), &[150.0, 250.0, 250.0, 150.0]);
+
+    let (mut system, _) = argon_crystal();
+    // keep the atomic planes away from the slab boundaries and add a streaming flow along x
+    let shift = system.cell.c() / 8.0;
+    system.positions.iter_mut().for_each(|position| position.z += shift);
+    system.velocities.iter_mut().for_each(|velocity| velocity.x += 0.002);
+    let mut thermostat = ProfileRescaling::new(2, vec![10.0, 40.0], 1.0).unwrap();
+    thermostat.post_integrate(&mut system);
+
+    // each half of the cell is rescaled to its own target while the flow is preserved
+    let half = system.cell.c() / 2.0;
+    for &(lower, target) in [(true, 10.0), (false, 40.0)].iter() {
+        let atoms: Vec<usize> = (0..system.size)
+            .filter(|&i| (system.positions[i].z < half) == lower)
+            .collect();
+        let n = atoms.len() as Float;
+        let streaming = atoms.iter().map(|&i| system.velocities[i]).sum::<Vector3<Float>>() / n;
+        assert!((streaming.x - 0.002).abs() < 1e-3);
+        let mass = system.species[0].mass();
+        let kinetic: Float = atoms
+            .iter()
+            .map(|&i| 0.5 * mass * (system.velocities[i] - streaming).norm_squared())
+            .sum();
+        let temperature = 2.0 * kinetic / (3.0 * (n - 1.0) * BOLTZMANN);
+        assert!((temperature - target).abs() < 1e-2 * target);
+    }
+}
+
+#[test]
+fn grouped() {
+    let (mut system, _) = argon_crystal();
+    let half = system.size / 2;
+    system.topology.add_group("hot", (0..half).collect());
+    let initial = system.velocities.clone();
+    let mut thermostat = Grouped::new(Berendsen::new(80.0, 1.0), "hot");
+    thermostat.setup(&system).unwrap();
+    thermostat.post_integrate(&mut system);
+
+    // only the atoms of the group are rescaled, to the target temperature
+    assert_eq!(&system.velocities[half..], &initial[half..]);
+    let mass = system.species[0].mass();
+    let kinetic: Float = system.velocities[..half]
+        .iter()
+        .map(|velocity| 0.5 * mass * velocity.norm_squared())
+        .sum();
+    let temperature = 2.0 * kinetic / (3.0 * half as Float * BOLTZMANN);
+    assert!((temperature - 80.0).abs() < 1e-2);
+}
+
+#[test]
+fn grouped_frozen() {
+    let (mut system, _) = argon_crystal();
+    let half = system.size / 2;
+    system.topology.add_group("slab", (0..system.size).collect());
+    system.topology.frozen = (0..half).collect();
+    for velocity in system.velocities[..half].iter_mut() {
+        *velocity = Vector3::zeros();
+    }
+    let mut thermostat = Grouped::new(Berendsen::new(80.0, 1.0), "slab");
+    thermostat.setup(&system).unwrap();
+    thermostat.post_integrate(&mut system);
+
+    // the frozen atoms of the group carry no degrees of freedom
+    assert!(system.velocities[..half].iter().all(|velocity| *velocity == Vector3::zeros()));
+    let mass = system.species[0].mass();
+    let kinetic: Float = system.velocities[half..]
+        .iter()
+        .map(|velocity| 0.5 * mass * velocity.norm_squared())
+        .sum();
+    let temperature = 2.0 * kinetic / (3.0 * (system.size - half) as Float * BOLTZMANN);
+    assert!((temperature - 80.0).abs() < 1e-2);
+
+    let mut missing = Grouped::new(Berendsen::new(80.0, 1.0), "shell");
+    assert!(missing.setup(&system).is_err());
+}
+
+#[test]
+fn heating_ramp() {
+    seed_rng(42);
+    let (mut system, mut potentials) = argon_crystal();
+    let schedule = TemperatureSchedule::linear(20.0, 200.0, 1000);
+    let mut md = MolecularDynamics::new(VelocityVerlet::new(1.0), Scheduled::new(Berendsen::new(20.0, 10.0), schedule));
+    md.setup(&mut system, &potentials).unwrap();
+    let mut temperature = 0.0;
+    for i in 0..2000 {
+        md.propagate(&mut system, &potentials);
+        potentials.update(&system, i);
+        if i >= 1500 {
+            temperature += Temperature.calculate_intrinsic(&system) / 500.0;
+        }
+    }
+    assert!((temperature - 200.0).abs() < 25.0, "average temperature {}", temperature);
+}
+
+#[test]
+fn scheduled_state() {
+    let (mut system, _) = argon_crystal();
+    let schedule = TemperatureSchedule::linear(100.0, 200.0, 10);
+    let mut thermostat = Scheduled::new(NoseHoover::new(100.0, 0.05, 1.0), schedule.clone());
+    thermostat.setup(&system).unwrap();
+    for _ in 0..4 {
+        thermostat.pre_integrate(&mut system);
+        thermostat.post_integrate(&mut system);
+    }
+    assert_eq!(thermostat.target(), 140.0);
+    let state = thermostat.state();
+    assert_eq!(state.integers, vec![4]);
+    let mut restored = Scheduled::new(NoseHoover::new(100.0, 0.05, 1.0), schedule);
+    assert!(restored.restore(&PropagatorState::default()).is_err());
+    restored.restore(&state).unwrap();
+    assert_eq!(restored.target(), 140.0);
+    assert_eq!(restored.state(), state);
+}
@@ -1,4 +1,5 @@
 use approx::*;
+use nalgebra::Vector3;
 
 use velvet_core::properties::temperature::Temperature;
 use velvet_core::properties::IntrinsicProperty;
@@ -17,3 +18,36 @@ fn boltzmann() {
         epsilon = 1e-3
     );
 }
+
+#[test]
+fn boltzmann_momentum() {
+    let mut system = test_utils::argon_system();
+    Boltzmann::new(300.0).apply(&mut system);
+    let momentum = system
+        .species
+        .iter()
+        .zip(system.velocities.iter())
+        .fold(Vector3::zeros(), |momentum, (species, velocity)| {
+            momentum + species.mass() * *velocity
+        });
+    assert_relative_eq!(momentum.norm(), 0.0, epsilon = 1e-4);
+}
+
+#[test]
+fn boltzmann_seed() {
+    let mut first = test_utils::argon_system();
+    let mut second = test_utils::argon_system();
+    let boltz = Boltzmann::new(300.0).seed(7).rescale(false);
+    boltz.apply(&mut first);
+    boltz.apply(&mut second);
+    assert_eq!(first.velocities, second.velocities);
+    // without rescaling the sampled temperature only approaches the target
+    assert_relative_ne!(
+        Temperature.calculate_intrinsic(&first),
+        300.0,
+        epsilon = 1e-3
+    );
+
+    Boltzmann::new(300.0).seed(8).apply(&mut second);
+    assert_ne!(first.velocities, second.velocities);
+}
@@ -0,0 +1,1176 @@
+//! Single-file run bundles which fully describe a simulation.
+//!
+//! A run bundle packages the system, potentials, propagator settings, output configuration,
+//! and number of steps into one portable binary file. Potentials and propagators are
+//! described by the built-in types they were constructed from so the bundle can be read back
+//! into a ready to run [`Simulation`] on any machine.
+//!
+//! All floating point values are stored as little-endian `f64` regardless of the storage
+//! type Velvet was compiled with.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use nalgebra::{Matrix3, Vector3};
+
+use velvet_core::barostats::{Barostat, BerendsenBarostat, ParrinelloRahman};
+use velvet_core::config::{Configuration, ConfigurationBuilder};
+use velvet_core::errors::VelvetError;
+use velvet_core::integrators::{Integrator, Leapfrog, VelocityVerlet};
+use velvet_core::outputs::compression::create_destination;
+use velvet_core::outputs::raw::RawOutputGroupBuilder;
+use velvet_core::outputs::trajectory::XyzTrajectory;
+use velvet_core::potentials::coulomb::CoulombPotential;
+use velvet_core::potentials::types::{
+    Buckingham, DampedShiftedForce, Ewald, Harmonic, LennardJones, Mie, Morse,
+    ParticleMeshEwald, StandardCoulombic, Tabulated,
+};
+use velvet_core::potentials::{Potentials, PotentialsBuilder};
+use velvet_core::propagators::MolecularDynamics;
+use velvet_core::properties::energy::{
+    CoulombicEnergy, KineticEnergy, PairEnergy, PotentialEnergy, TotalEnergy,
+};
+use velvet_core::properties::forces::Forces;
+use velvet_core::properties::pressure::{Pressure, StressTensor};
+use velvet_core::properties::temperature::Temperature;
+use velvet_core::simulation::Simulation;
+use velvet_core::system::cell::Cell;
+use velvet_core::system::species::Species;
+use velvet_core::system::topology::{Group, Residue, Topology};
+use velvet_core::system::System;
+use velvet_core::thermostats::{Andersen, Berendsen, Bussi, NoseHoover, NoseHooverChain, NullThermostat, Thermostat};
+
+use crate::internal::Float;
+
+/// Leading bytes of every run bundle.
+const MAGIC: &[u8; 4] = b"VLVT";
+
+/// Version of the run bundle layout written by this crate.
+///
+/// The version is incremented with every change to the layout. Bundles of any earlier version
+/// can still be read and describe the same simulation as when they were written.
+///
+/// 1. Initial layout.
+/// 2. Optional barostat after the thermostat.
+/// 3. Bonds, angles, and dihedrals of the topology.
+/// 4. Optional lookup tables of the Coulombic potential.
+/// 5. Plain and extended XYZ trajectory outputs.
+/// 6. Residues of the topology.
+/// 7. Leapfrog integrator.
+/// 8. Andersen and Bussi thermostats.
+/// 9. Nose-Hoover chain thermostat.
+/// 10. Named groups of the topology.
+/// 11. Partial charges of the topology.
+const VERSION: u32 = 11;
+
+/// Complete description of a simulation which can be stored in a single file.
+#[derive(Clone, Debug)]
+pub struct RunBundle {
+    /// Initial state of the system.
+    pub system: System,
+    /// Potentials applied to the system.
+    pub potentials: PotentialsSpec,
+    /// Algorithm which advances the system.
+    pub propagator: PropagatorSpec,
+    /// Raw output groups written during the run.
+    pub outputs: Vec<OutputSpec>,
+    /// Number of steps to run.
+    pub steps: usize,
+}
+
+/// Description of a [`Potentials`] collection.
+#[derive(Clone, Debug)]
+pub struct PotentialsSpec {
+    /// Coulombic potential applied to all charged atoms.
+    pub coulomb: Option<CoulombSpec>,
+    /// Pair potentials applied between species.
+    pub pairs: Vec<PairSpec>,
+    /// Number of iterations between neighbor list rebuilds.
+    pub update_frequency: usize,
+}
+
+/// Built-in Coulombic potentials.
+#[derive(Clone, Copy, Debug)]
+pub enum CoulombKind {
+    /// Damped shifted force potential.
+    DampedShiftedForce(DampedShiftedForce),
+    /// Ewald summation.
+    Ewald(Ewald),
+    /// Smooth particle-mesh Ewald summation.
+    ParticleMeshEwald(ParticleMeshEwald),
+    /// Standard Coulombic potential.
+    StandardCoulombic(StandardCoulombic),
+}
+
+/// Description of a Coulombic potential.
+#[derive(Clone, Copy, Debug)]
+pub struct CoulombSpec {
+    /// Functional form of the potential.
+    pub potential: CoulombKind,
+    /// Cutoff radius.
+    pub cutoff: Float,
+    /// Neighbor list skin thickness.
+    pub thickness: Float,
+    /// Lookup tables which replace evaluations of the pairwise terms.
+    pub table: Option<TableSpec>,
+}
+
+/// Description of the lookup tables of a [`Tabulated`] potential.
+#[derive(Clone, Copy, Debug)]
+pub struct TableSpec {
+    /// Smallest separation covered by the tables.
+    pub inner: Float,
+    /// Largest acceptable error of the interpolated energy and force between unit charges.
+    pub accuracy: Float,
+}
+
+/// Built-in pair potentials.
+#[derive(Clone, Copy, Debug)]
+pub enum PairKind {
+    /// Buckingham potential.
+    Buckingham(Buckingham),
+    /// Harmonic potential.
+    Harmonic(Harmonic),
+    /// Lennard-Jones potential.
+    LennardJones(LennardJones),
+    /// Mie potential.
+    Mie(Mie),
+    /// Morse potential.
+    Morse(Morse),
+}
+
+/// Description of a pair potential.
+#[derive(Clone, Copy, Debug)]
+pub struct PairSpec {
+    /// Functional form of the potential.
+    pub potential: PairKind,
+    /// Species the potential applies between.
+    pub species: (Species, Species),
+    /// Cutoff radius.
+    pub cutoff: Float,
+    /// Neighbor list skin thickness.
+    pub thickness: Float,
+}
+
+/// Built-in propagators.
+#[derive(Clone, Copy, Debug)]
+pub enum PropagatorSpec {
+    /// Molecular dynamics with an integrator, a thermostat, and an optional barostat.
+    MolecularDynamics {
+        /// Algorithm which integrates the equations of motion.
+        integrator: IntegratorSpec,
+        /// Algorithm which controls the temperature of the system.
+        thermostat: ThermostatSpec,
+        /// Algorithm which controls the pressure of the system.
+        barostat: Option<BarostatSpec>,
+    },
+}
+
+/// Built-in integrators.
+#[derive(Clone, Copy, Debug)]
+pub enum IntegratorSpec {
+    /// Velocity Verlet integration algorithm.
+    VelocityVerlet {
+        /// Timestep duration.
+        timestep: Float,
+    },
+    /// Leapfrog integration algorithm.
+    Leapfrog {
+        /// Timestep duration.
+        timestep: Float,
+    },
+}
+
+/// Built-in thermostats.
+#[derive(Clone, Copy, Debug)]
+pub enum ThermostatSpec {
+    /// No temperature controls.
+    Null,
+    /// Berendsen weak coupling thermostat.
+    Berendsen {
+        /// Target temperature.
+        target: Float,
+        /// Timestep of the thermostat expressed as a multiple of the integrator's timestep.
+        tau: Float,
+    },
+    /// Nose-Hoover thermostat.
+    NoseHoover {
+        /// Target temperature.
+        target: Float,
+        /// Damping frequency expressed as a multiple of the timestep.
+        freq: Float,
+        /// Timestep duration.
+        timestep: Float,
+    },
+    /// Andersen collision thermostat.
+    Andersen {
+        /// Target temperature.
+        target: Float,
+        /// Collision frequency of each atom.
+        freq: Float,
+        /// Timestep duration.
+        timestep: Float,
+    },
+    /// Canonical sampling through velocity rescaling thermostat.
+    Bussi {
+        /// Target temperature.
+        target: Float,
+        /// Timestep of the thermostat expressed as a multiple of the integrator's timestep.
+        tau: Float,
+    },
+    /// Nose-Hoover chain thermostat with third order Suzuki-Yoshida integration.
+    NoseHooverChain {
+        /// Target temperature.
+        target: Float,
+        /// Damping frequency.
+        freq: Float,
+        /// Timestep duration.
+        timestep: Float,
+        /// Number of thermostats in the chain.
+        length: usize,
+    },
+}
+
+/// Built-in barostats.
+#[derive(Clone, Copy, Debug)]
+pub enum BarostatSpec {
+    /// Berendsen weak coupling barostat.
+    Berendsen {
+        /// Target pressure.
+        target: Float,
+        /// Isothermal compressibility of the system in inverse atmospheres.
+        compressibility: Float,
+        /// Timestep of the barostat expressed as a multiple of the integrator's timestep.
+        tau: Float,
+    },
+    /// Anisotropic Parrinello-Rahman barostat.
+    ParrinelloRahman {
+        /// Target pressure.
+        target: Float,
+        /// Isothermal compressibility of the system in inverse atmospheres.
+        compressibility: Float,
+        /// Period of the cell oscillations.
+        tau: Float,
+        /// Timestep duration.
+        timestep: Float,
+    },
+}
+
+/// Built-in properties and trajectories which can be written as raw output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropertyKind {
+    /// [`CoulombicEnergy`] property.
+    CoulombicEnergy,
+    /// [`PairEnergy`] property.
+    PairEnergy,
+    /// [`PotentialEnergy`] property.
+    PotentialEnergy,
+    /// [`KineticEnergy`] property.
+    KineticEnergy,
+    /// [`TotalEnergy`] property.
+    TotalEnergy,
+    /// [`Temperature`] property.
+    Temperature,
+    /// [`Pressure`] property.
+    Pressure,
+    /// [`StressTensor`] property.
+    StressTensor,
+    /// [`Forces`] property.
+    Forces,
+    /// Plain [`XyzTrajectory`] frames.
+    XyzTrajectory,
+    /// Extended [`XyzTrajectory`] frames.
+    ExtendedXyzTrajectory,
+}
+
+/// Description of a raw output group.
+#[derive(Clone, Debug)]
+pub struct OutputSpec {
+    /// Path of the file the outputs are written to or `None` to write to stderr.
+    pub destination: Option<String>,
+    /// Number of iterations between each output.
+    pub interval: usize,
+    /// Properties to be written.
+    pub properties: Vec<PropertyKind>,
+}
+
+impl CoulombSpec {
+    // Adds `potential` to the builder, wrapped in lookup tables if requested.
+    fn add_to<T>(&self, builder: PotentialsBuilder, potential: T) -> PotentialsBuilder
+    where
+        T: CoulombPotential + 'static,
+    {
+        match self.table {
+            None => builder.coulomb(potential, self.cutoff, self.thickness),
+            Some(table) => builder.coulomb(
+                Tabulated::new(potential, table.inner, self.cutoff, table.accuracy),
+                self.cutoff,
+                self.thickness,
+            ),
+        }
+    }
+}
+
+impl PotentialsSpec {
+    /// Returns an initialized [`Potentials`] collection.
+    pub fn build(&self) -> Potentials {
+        let mut builder = PotentialsBuilder::new().update_frequency(self.update_frequency);
+        if let Some(spec) = self.coulomb {
+            builder = match spec.potential {
+                CoulombKind::DampedShiftedForce(p) => spec.add_to(builder, p),
+                CoulombKind::Ewald(p) => spec.add_to(builder, p),
+                CoulombKind::ParticleMeshEwald(p) => spec.add_to(builder, p),
+                CoulombKind::StandardCoulombic(p) => spec.add_to(builder, p),
+            };
+        }
+        for spec in &self.pairs {
+            builder = match spec.potential {
+                PairKind::Buckingham(p) => builder.pair(p, spec.species, spec.cutoff, spec.thickness),
+                PairKind::Harmonic(p) => builder.pair(p, spec.species, spec.cutoff, spec.thickness),
+                PairKind::LennardJones(p) => builder.pair(p, spec.species, spec.cutoff, spec.thickness),
+                PairKind::Mie(p) => builder.pair(p, spec.species, spec.cutoff, spec.thickness),
+                PairKind::Morse(p) => builder.pair(p, spec.species, spec.cutoff, spec.thickness),
+            };
+        }
+        builder.build()
+    }
+}
+
+impl PropagatorSpec {
+    /// Returns an initialized [`MolecularDynamics`] propagator.
+    pub fn build(&self) -> Result<MolecularDynamics, VelvetError> {
+        match *self {
+            PropagatorSpec::MolecularDynamics {
+                integrator,
+                thermostat,
+                barostat,
+            } => {
+                let integrator: Box<dyn Integrator> = match integrator {
+                    IntegratorSpec::VelocityVerlet { timestep } => {
+                        Box::new(VelocityVerlet::new(timestep))
+                    }
+                    IntegratorSpec::Leapfrog { timestep } => Box::new(Leapfrog::new(timestep)),
+                };
+                let thermostat: Box<dyn Thermostat> = match thermostat {
+                    ThermostatSpec::Null => Box::new(NullThermostat),
+                    ThermostatSpec::Berendsen { target, tau } => {
+                        Box::new(Berendsen::new(target, tau))
+                    }
+                    ThermostatSpec::NoseHoover {
+                        target,
+                        freq,
+                        timestep,
+                    } => Box::new(NoseHoover::new(target, freq, timestep)),
+                    ThermostatSpec::Andersen {
+                        target,
+                        freq,
+                        timestep,
+                    } => Box::new(Andersen::new(target, freq, timestep)),
+                    ThermostatSpec::Bussi { target, tau } => Box::new(Bussi::new(target, tau)),
+                    ThermostatSpec::NoseHooverChain {
+                        target,
+                        freq,
+                        timestep,
+                        length,
+                    } => Box::new(NoseHooverChain::new(target, freq, timestep).chain_length(length)?),
+                };
+                let md = MolecularDynamics::new(integrator, thermostat);
+                match barostat {
+                    None => Ok(md),
+                    Some(spec) => {
+                        let barostat: Box<dyn Barostat> = match spec {
+                            BarostatSpec::Berendsen {
+                                target,
+                                compressibility,
+                                tau,
+                            } => Box::new(BerendsenBarostat::new(target, compressibility, tau)),
+                            BarostatSpec::ParrinelloRahman {
+                                target,
+                                compressibility,
+                                tau,
+                                timestep,
+                            } => Box::new(ParrinelloRahman::new(
+                                target,
+                                compressibility,
+                                tau,
+                                timestep,
+                            )),
+                        };
+                        Ok(md.with_barostat(barostat))
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl OutputSpec {
+    /// Returns a [`RawOutputGroupBuilder`] populated with the described outputs.
+    ///
+    /// The destination file is created if it is not stderr and compressed if its extension is `.gz` or `.zst`.
+    pub fn builder(&self) -> io::Result<RawOutputGroupBuilder> {
+        let mut builder = RawOutputGroupBuilder::new().interval(self.interval);
+        if let Some(path) = &self.destination {
+            builder = builder.destination(create_destination(path)?);
+        }
+        for property in &self.properties {
+            builder = match property {
+                PropertyKind::CoulombicEnergy => builder.output(CoulombicEnergy),
+                PropertyKind::PairEnergy => builder.output(PairEnergy),
+                PropertyKind::PotentialEnergy => builder.output(PotentialEnergy),
+                PropertyKind::KineticEnergy => builder.output(KineticEnergy),
+                PropertyKind::TotalEnergy => builder.output(TotalEnergy),
+                PropertyKind::Temperature => builder.output(Temperature),
+                PropertyKind::Pressure => builder.output(Pressure),
+                PropertyKind::StressTensor => builder.output(StressTensor),
+                PropertyKind::Forces => builder.output(Forces),
+                PropertyKind::XyzTrajectory => builder.output(XyzTrajectory::plain()),
+                PropertyKind::ExtendedXyzTrajectory => builder.output(XyzTrajectory::extended()),
+            };
+        }
+        Ok(builder)
+    }
+}
+
+impl RunBundle {
+    /// Returns a [`ConfigurationBuilder`] populated with the outputs described by the bundle.
+    ///
+    /// Options which are not part of the bundle, such as periodic checkpoints, can be added
+    /// before it is built.
+    pub fn configuration_builder(&self) -> io::Result<ConfigurationBuilder> {
+        let mut builder = ConfigurationBuilder::new();
+        for output in &self.outputs {
+            builder = builder.raw_output_group(output.builder()?.build());
+        }
+        Ok(builder)
+    }
+
+    /// Returns the output [`Configuration`] described by the bundle.
+    pub fn configuration(&self) -> io::Result<Configuration> {
+        Ok(self.configuration_builder()?.build())
+    }
+
+    /// Returns a [`Simulation`] ready to run for [`steps`](RunBundle::steps) iterations.
+    pub fn simulation(&self) -> Result<Simulation, VelvetError> {
+        Ok(Simulation::new(
+            self.system.clone(),
+            self.potentials.build(),
+            self.propagator.build()?,
+            self.configuration()?,
+        ))
+    }
+
+    /// Returns a [`Simulation`] which resumes the bundle from the checkpoint at `path`.
+    ///
+    /// The system and step counter are read from the checkpoint, so the remaining steps of the
+    /// bundle are [`steps`](RunBundle::steps) less [`Simulation::step`].
+    pub fn resume<P: AsRef<Path>>(&self, path: P, config: Configuration) -> Result<Simulation, VelvetError> {
+        Simulation::from_checkpoint(path, self.potentials.build(), self.propagator.build()?, config)
+    }
+
+    /// Writes the bundle in binary format.
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut enc = Encoder(writer);
+        enc.0.write_all(MAGIC)?;
+        enc.u32(VERSION)?;
+        enc.u64(self.steps as u64)?;
+
+        // collect every species referenced by the system or the pair potentials
+        let mut table: Vec<Species> = Vec::new();
+        let pair_species = self
+            .potentials
+            .pairs
+            .iter()
+            .flat_map(|spec| vec![spec.species.0, spec.species.1]);
+        for species in self.system.species.iter().copied().chain(pair_species) {
+            if !table.contains(&species) {
+                table.push(species);
+            }
+        }
+        let index: HashMap<u128, u64> = table
+            .iter()
+            .enumerate()
+            .map(|(i, species)| (species.id(), i as u64))
+            .collect();
+
+        // system
+        enc.u64(table.len() as u64)?;
+        for species in &table {
+            enc.u128(species.id())?;
+            enc.float(species.mass())?;
+            enc.float(species.charge())?;
+        }
+        enc.matrix(&self.system.cell.matrix())?;
+        enc.u64(self.system.size as u64)?;
+        for ((species, pos), vel) in self
+            .system
+            .species
+            .iter()
+            .zip(self.system.positions.iter())
+            .zip(self.system.velocities.iter())
+        {
+            enc.u64(index[&species.id()])?;
+            enc.vector(pos)?;
+            enc.vector(vel)?;
+        }
+        enc.indices(&self.system.topology.bonds)?;
+        enc.indices(&self.system.topology.angles)?;
+        enc.indices(&self.system.topology.dihedrals)?;
+        enc.u64(self.system.topology.residues.len() as u64)?;
+        for residue in &self.system.topology.residues {
+            enc.string(&residue.name)?;
+            enc.indices(&residue.atoms.iter().map(|&i| [i]).collect::<Vec<_>>())?;
+        }
+        enc.u64(self.system.topology.groups.len() as u64)?;
+        for group in &self.system.topology.groups {
+            enc.string(&group.name)?;
+            enc.indices(&group.atoms.iter().map(|&i| [i]).collect::<Vec<_>>())?;
+        }
+        enc.u64(self.system.topology.charges.len() as u64)?;
+        enc.floats(&self.system.topology.charges)?;
+
+        // potentials
+        enc.u64(self.potentials.update_frequency as u64)?;
+        match &self.potentials.coulomb {
+            None => enc.u8(0)?,
+            Some(spec) => {
+                match spec.potential {
+                    CoulombKind::DampedShiftedForce(p) => {
+                        enc.u8(1)?;
+                        enc.floats(&[p.alpha, p.cutoff])?;
+                    }
+                    CoulombKind::Ewald(p) => {
+                        enc.u8(2)?;
+                        enc.float(p.alpha)?;
+                        enc.u64(p.kmax as u64)?;
+                    }
+                    CoulombKind::ParticleMeshEwald(p) => {
+                        enc.u8(3)?;
+                        enc.floats(&[p.alpha, p.spacing])?;
+                        enc.u64(p.order as u64)?;
+                    }
+                    CoulombKind::StandardCoulombic(p) => {
+                        enc.u8(4)?;
+                        enc.float(p.dielectric)?;
+                    }
+                }
+                enc.floats(&[spec.cutoff, spec.thickness])?;
+                match spec.table {
+                    None => enc.u8(0)?,
+                    Some(table) => {
+                        enc.u8(1)?;
+                        enc.floats(&[table.inner, table.accuracy])?;
+                    }
+                }
+            }
+        }
+        enc.u64(self.potentials.pairs.len() as u64)?;
+        for spec in &self.potentials.pairs {
+            match spec.potential {
+                PairKind::Buckingham(p) => {
+                    enc.u8(1)?;
+                    enc.floats(&[p.a, p.rho, p.c])?;
+                }
+                PairKind::Harmonic(p) => {
+                    enc.u8(2)?;
+                    enc.floats(&[p.k, p.x0])?;
+                }
+                PairKind::LennardJones(p) => {
+                    enc.u8(3)?;
+                    enc.floats(&[p.epsilon, p.sigma])?;
+                }
+                PairKind::Mie(p) => {
+                    enc.u8(4)?;
+                    enc.floats(&[p.epsilon, p.sigma, p.gamma_a, p.gamma_r])?;
+                }
+                PairKind::Morse(p) => {
+                    enc.u8(5)?;
+                    enc.floats(&[p.a, p.d_e, p.r_e])?;
+                }
+            }
+            enc.u64(index[&spec.species.0.id()])?;
+            enc.u64(index[&spec.species.1.id()])?;
+            enc.floats(&[spec.cutoff, spec.thickness])?;
+        }
+
+        // propagator
+        match self.propagator {
+            PropagatorSpec::MolecularDynamics {
+                integrator,
+                thermostat,
+                barostat,
+            } => {
+                enc.u8(1)?;
+                match integrator {
+                    IntegratorSpec::VelocityVerlet { timestep } => {
+                        enc.u8(1)?;
+                        enc.float(timestep)?;
+                    }
+                    IntegratorSpec::Leapfrog { timestep } => {
+                        enc.u8(2)?;
+                        enc.float(timestep)?;
+                    }
+                }
+                match thermostat {
+                    ThermostatSpec::Null => enc.u8(0)?,
+                    ThermostatSpec::Berendsen { target, tau } => {
+                        enc.u8(1)?;
+                        enc.floats(&[target, tau])?;
+                    }
+                    ThermostatSpec::NoseHoover {
+                        target,
+                        freq,
+                        timestep,
+                    } => {
+                        enc.u8(2)?;
+                        enc.floats(&[target, freq, timestep])?;
+                    }
+                    ThermostatSpec::Andersen {
+                        target,
+                        freq,
+                        timestep,
+                    } => {
+                        enc.u8(3)?;
+                        enc.floats(&[target, freq, timestep])?;
+                    }
+                    ThermostatSpec::Bussi { target, tau } => {
+                        enc.u8(4)?;
+                        enc.floats(&[target, tau])?;
+                    }
+                    ThermostatSpec::NoseHooverChain {
+                        target,
+                        freq,
+                        timestep,
+                        length,
+                    } => {
+                        enc.u8(5)?;
+                        enc.floats(&[target, freq, timestep])?;
+                        enc.u64(length as u64)?;
+                    }
+                }
+                match barostat {
+                    None => enc.u8(0)?,
+                    Some(BarostatSpec::Berendsen {
+                        target,
+                        compressibility,
+                        tau,
+                    }) => {
+                        enc.u8(1)?;
+                        enc.floats(&[target, compressibility, tau])?;
+                    }
+                    Some(BarostatSpec::ParrinelloRahman {
+                        target,
+                        compressibility,
+                        tau,
+                        timestep,
+                    }) => {
+                        enc.u8(2)?;
+                        enc.floats(&[target, compressibility, tau, timestep])?;
+                    }
+                }
+            }
+        }
+
+        // outputs
+        enc.u64(self.outputs.len() as u64)?;
+        for output in &self.outputs {
+            match &output.destination {
+                None => enc.u8(0)?,
+                Some(path) => {
+                    enc.u8(1)?;
+                    enc.string(path)?;
+                }
+            }
+            enc.u64(output.interval as u64)?;
+            enc.u64(output.properties.len() as u64)?;
+            for property in &output.properties {
+                enc.u8(*property as u8)?;
+            }
+        }
+        enc.0.flush()
+    }
+
+    /// Reads a bundle from binary format.
+    pub fn read<R: Read>(reader: R) -> io::Result<RunBundle> {
+        let mut dec = Decoder(reader);
+        let mut magic = [0u8; 4];
+        dec.0.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a velvet run bundle"));
+        }
+        let version = dec.u32()?;
+        if version == 0 || version > VERSION {
+            return Err(invalid(&format!("unsupported run bundle version {}", version)));
+        }
+        // fails if a tag was introduced after the version of the bundle
+        let since = |introduced: u32, what: &str| -> io::Result<()> {
+            if version < introduced {
+                return Err(invalid(&format!("unknown {} in run bundle version {}", what, version)));
+            }
+            Ok(())
+        };
+        let steps = dec.usize()?;
+
+        // system
+        // lengths are never trusted for allocations, a corrupt length fails at the end of the data
+        let n_species = dec.usize()?;
+        let mut table = Vec::new();
+        for _ in 0..n_species {
+            let id = dec.u128()?;
+            let mass = dec.float()?;
+            let charge = dec.float()?;
+            table.push(Species::from_id(id, mass, charge));
+        }
+        let lookup = |i: usize| -> io::Result<Species> {
+            table
+                .get(i)
+                .copied()
+                .ok_or_else(|| invalid("species index out of range"))
+        };
+        let matrix = dec.matrix()?;
+        if matrix.iter().any(|x| !x.is_finite()) || matrix.try_inverse().is_none() {
+            return Err(invalid("lattice vectors are not linearly independent"));
+        }
+        let cell = Cell::from_matrix(matrix);
+        let size = dec.usize()?;
+        let mut species = Vec::new();
+        let mut positions = Vec::new();
+        let mut velocities = Vec::new();
+        for _ in 0..size {
+            species.push(lookup(dec.usize()?)?);
+            positions.push(dec.vector()?);
+            velocities.push(dec.vector()?);
+        }
+        let (bonds, angles, dihedrals) = if version >= 3 {
+            (dec.indices(size)?, dec.indices(size)?, dec.indices(size)?)
+        } else {
+            (Vec::new(), Vec::new(), Vec::new())
+        };
+        let mut residues = Vec::new();
+        for _ in 0..if version >= 6 { dec.usize()? } else { 0 } {
+            let name = dec.string()?;
+            let atoms: Vec<[usize; 1]> = dec.indices(size)?;
+            residues.push(Residue {
+                name,
+                atoms: atoms.iter().map(|[i]| *i).collect(),
+            });
+        }
+        let mut groups = Vec::new();
+        for _ in 0..if version >= 10 { dec.usize()? } else { 0 } {
+            let name = dec.string()?;
+            let atoms: Vec<[usize; 1]> = dec.indices(size)?;
+            groups.push(Group {
+                name,
+                atoms: atoms.iter().map(|[i]| *i).collect(),
+            });
+        }
+        let mut charges = Vec::new();
+        for _ in 0..if version >= 11 { dec.usize()? } else { 0 } {
+            charges.push(dec.float()?);
+        }
+        if !charges.is_empty() && charges.len() != size {
+            return Err(invalid("partial charges do not match the number of atoms"));
+        }
+        let topology = Topology {
+            bonds,
+            angles,
+            dihedrals,
+            residues,
+            groups,
+            charges,
+            // recorded again when the propagator is set up
+            constraints: Vec::new(),
+            frozen: Vec::new(),
+        };
+        let system = System {
+            size,
+            cell,
+            species,
+            positions,
+            velocities,
+            topology,
+        };
+
+        // potentials
+        let update_frequency = dec.usize()?;
+        if update_frequency == 0 {
+            return Err(invalid("neighbor list update frequency must be positive"));
+        }
+        let coulomb = match dec.u8()? {
+            0 => None,
+            tag => {
+                let potential = match tag {
+                    1 => {
+                        let alpha = dec.float()?;
+                        let cutoff = dec.float()?;
+                        CoulombKind::DampedShiftedForce(DampedShiftedForce::new(alpha, cutoff))
+                    }
+                    2 => {
+                        let alpha = dec.float()?;
+                        let kmax = dec.usize()?;
+                        CoulombKind::Ewald(Ewald::new(alpha, kmax))
+                    }
+                    3 => {
+                        let alpha = dec.float()?;
+                        let spacing = dec.float()?;
+                        let order = dec.usize()?;
+                        if order < 2 {
+                            return Err(invalid("particle mesh interpolation order must be at least 2"));
+                        }
+                        CoulombKind::ParticleMeshEwald(ParticleMeshEwald::new(alpha, spacing, order))
+                    }
+                    4 => CoulombKind::StandardCoulombic(StandardCoulombic::new(dec.float()?)),
+                    _ => return Err(invalid("unknown coulomb potential")),
+                };
+                let cutoff = dec.float()?;
+                let thickness = dec.float()?;
+                let table = match if version >= 4 { dec.u8()? } else { 0 } {
+                    0 => None,
+                    1 => {
+                        let [inner, accuracy] = dec.floats()?;
+                        if !(inner > 0.0 && inner < cutoff) {
+                            return Err(invalid("lookup tables must start between zero and the cutoff"));
+                        }
+                        Some(TableSpec { inner, accuracy })
+                    }
+                    _ => return Err(invalid("unknown lookup table")),
+                };
+                Some(CoulombSpec {
+                    potential,
+                    cutoff,
+                    thickness,
+                    table,
+                })
+            }
+        };
+        let n_pairs = dec.usize()?;
+        let mut pairs = Vec::new();
+        for _ in 0..n_pairs {
+            let potential = match dec.u8()? {
+                1 => {
+                    let [a, rho, c] = dec.floats()?;
+                    PairKind::Buckingham(Buckingham::new(a, rho, c))
+                }
+                2 => {
+                    let [k, x0] = dec.floats()?;
+                    PairKind::Harmonic(Harmonic::new(k, x0))
+                }
+                3 => {
+                    let [epsilon, sigma] = dec.floats()?;
+                    PairKind::LennardJones(LennardJones::new(epsilon, sigma))
+                }
+                4 => {
+                    let [epsilon, sigma, gamma_a, gamma_r] = dec.floats()?;
+                    PairKind::Mie(Mie::new(epsilon, sigma, gamma_a, gamma_r))
+                }
+                5 => {
+                    let [a, d_e, r_e] = dec.floats()?;
+                    PairKind::Morse(Morse::new(a, d_e, r_e))
+                }
+                _ => return Err(invalid("unknown pair potential")),
+            };
+            let species = (lookup(dec.usize()?)?, lookup(dec.usize()?)?);
+            let [cutoff, thickness] = dec.floats()?;
+            pairs.push(PairSpec {
+                potential,
+                species,
+                cutoff,
+                thickness,
+            });
+        }
+        let potentials = PotentialsSpec {
+            coulomb,
+            pairs,
+            update_frequency,
+        };
+
+        // propagator
+        let propagator = match dec.u8()? {
+            1 => {
+                let integrator = match dec.u8()? {
+                    1 => IntegratorSpec::VelocityVerlet {
+                        timestep: dec.float()?,
+                    },
+                    2 => {
+                        since(7, "integrator")?;
+                        IntegratorSpec::Leapfrog {
+                            timestep: dec.float()?,
+                        }
+                    }
+                    _ => return Err(invalid("unknown integrator")),
+                };
+                let thermostat = match dec.u8()? {
+                    0 => ThermostatSpec::Null,
+                    1 => {
+                        let [target, tau] = dec.floats()?;
+                        ThermostatSpec::Berendsen { target, tau }
+                    }
+                    2 => {
+                        let [target, freq, timestep] = dec.floats()?;
+                        ThermostatSpec::NoseHoover {
+                            target,
+                            freq,
+                            timestep,
+                        }
+                    }
+                    3 => {
+                        since(8, "thermostat")?;
+                        let [target, freq, timestep] = dec.floats()?;
+                        ThermostatSpec::Andersen {
+                            target,
+                            freq,
+                            timestep,
+                        }
+                    }
+                    4 => {
+                        since(8, "thermostat")?;
+                        let [target, tau] = dec.floats()?;
+                        ThermostatSpec::Bussi { target, tau }
+                    }
+                    5 => {
+                        since(9, "thermostat")?;
+                        let [target, freq, timestep] = dec.floats()?;
+                        let length = dec.usize()?;
+                        if length == 0 {
+                            return Err(invalid("a Nose-Hoover chain needs at least one thermostat"));
+                        }
+                        ThermostatSpec::NoseHooverChain {
+                            target,
+                            freq,
+                            timestep,
+                            length,
+                        }
+                    }
+                    _ => return Err(invalid("unknown thermostat")),
+                };
+                let barostat = match if version >= 2 { dec.u8()? } else { 0 } {
+                    0 => None,
+                    1 => {
+                        let [target, compressibility, tau] = dec.floats()?;
+                        Some(BarostatSpec::Berendsen {
+                            target,
+                            compressibility,
+                            tau,
+                        })
+                    }
+                    2 => {
+                        let [target, compressibility, tau, timestep] = dec.floats()?;
+                        Some(BarostatSpec::ParrinelloRahman {
+                            target,
+                            compressibility,
+                            tau,
+                            timestep,
+                        })
+                    }
+                    _ => return Err(invalid("unknown barostat")),
+                };
+                PropagatorSpec::MolecularDynamics {
+                    integrator,
+                    thermostat,
+                    barostat,
+                }
+            }
+            _ => return Err(invalid("unknown propagator")),
+        };
+
+        // outputs
+        let n_outputs = dec.usize()?;
+        let mut outputs = Vec::new();
+        for _ in 0..n_outputs {
+            let destination = match dec.u8()? {
+                0 => None,
+                1 => Some(dec.string()?),
+                _ => return Err(invalid("unknown output destination")),
+            };
+            let interval = dec.usize()?;
+            if interval == 0 {
+                return Err(invalid("output interval must be positive"));
+            }
+            let n_properties = dec.usize()?;
+            let mut properties = Vec::new();
+            for _ in 0..n_properties {
+                let property = PropertyKind::from_tag(dec.u8()?)?;
+                if let PropertyKind::XyzTrajectory | PropertyKind::ExtendedXyzTrajectory = property {
+                    since(5, "property")?;
+                }
+                properties.push(property);
+            }
+            outputs.push(OutputSpec {
+                destination,
+                interval,
+                properties,
+            });
+        }
+
+        Ok(RunBundle {
+            system,
+            potentials,
+            propagator,
+            outputs,
+            steps,
+        })
+    }
+
+    /// Writes the bundle to a file at `path`.
+    pub fn write_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    /// Reads a bundle from a file at `path`.
+    pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<RunBundle> {
+        RunBundle::read(BufReader::new(File::open(path)?))
+    }
+}
+
+impl PropertyKind {
+    fn from_tag(tag: u8) -> io::Result<PropertyKind> {
+        let kind = match tag {
+            0 => PropertyKind::CoulombicEnergy,
+            1 => PropertyKind::PairEnergy,
+            2 => PropertyKind::PotentialEnergy,
+            3 => PropertyKind::KineticEnergy,
+            4 => PropertyKind::TotalEnergy,
+            5 => PropertyKind::Temperature,
+            6 => PropertyKind::Pressure,
+            7 => PropertyKind::StressTensor,
+            8 => PropertyKind::Forces,
+            9 => PropertyKind::XyzTrajectory,
+            10 => PropertyKind::ExtendedXyzTrajectory,
+            _ => return Err(invalid("unknown property")),
+        };
+        Ok(kind)
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Little-endian primitive writer.
+struct Encoder<W: Write>(W);
+
+impl<W: Write> Encoder<W> {
+    fn u8(&mut self, value: u8) -> io::Result<()> {
+        self.0.write_all(&[value])
+    }
+
+    fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn u128(&mut self, value: u128) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    // double precision is stored regardless of `Float`
+    #[allow(clippy::unnecessary_cast)]
+    fn float(&mut self, value: Float) -> io::Result<()> {
+        self.0.write_all(&(value as f64).to_le_bytes())
+    }
+
+    fn floats(&mut self, values: &[Float]) -> io::Result<()> {
+        values.iter().try_for_each(|value| self.float(*value))
+    }
+
+    fn vector(&mut self, vector: &Vector3<Float>) -> io::Result<()> {
+        self.floats(vector.as_slice())
+    }
+
+    fn matrix(&mut self, matrix: &Matrix3<Float>) -> io::Result<()> {
+        self.floats(matrix.as_slice())
+    }
+
+    fn indices<const N: usize>(&mut self, entries: &[[usize; N]]) -> io::Result<()> {
+        self.u64(entries.len() as u64)?;
+        entries
+            .iter()
+            .flatten()
+            .try_for_each(|index| self.u64(*index as u64))
+    }
+
+    fn string(&mut self, value: &str) -> io::Result<()> {
+        self.u64(value.len() as u64)?;
+        self.0.write_all(value.as_bytes())
+    }
+}
+
+// Little-endian primitive reader.
+struct Decoder<R: Read>(R);
+
+impl<R: Read> Decoder<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.0.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn usize(&mut self) -> io::Result<usize> {
+        usize::try_from(u64::from_le_bytes(self.bytes()?)).map_err(|_| invalid("length out of range"))
+    }
+
+    fn u128(&mut self) -> io::Result<u128> {
+        Ok(u128::from_le_bytes(self.bytes()?))
+    }
+
+    fn float(&mut self) -> io::Result<Float> {
+        Ok(f64::from_le_bytes(self.bytes()?) as Float)
+    }
+
+    fn floats<const N: usize>(&mut self) -> io::Result<[Float; N]> {
+        let mut values = [0.0; N];
+        for value in values.iter_mut() {
+            *value = self.float()?;
+        }
+        Ok(values)
+    }
+
+    fn vector(&mut self) -> io::Result<Vector3<Float>> {
+        let [x, y, z] = self.floats()?;
+        Ok(Vector3::new(x, y, z))
+    }
+
+    fn matrix(&mut self) -> io::Result<Matrix3<Float>> {
+        let values: [Float; 9] = self.floats()?;
+        Ok(Matrix3::from_column_slice(&values))
+    }
+
+    fn indices<const N: usize>(&mut self, size: usize) -> io::Result<Vec<[usize; N]>> {
+        let len = self.usize()?;
+        let mut entries = Vec::new();
+        for _ in 0..len {
+            let mut entry = [0; N];
+            for index in entry.iter_mut() {
+                *index = self.usize()?;
+                if *index >= size {
+                    return Err(invalid("topology index out of range"));
+                }
+            }
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        let len = self.usize()? as u64;
+        let mut buf = Vec::new();
+        if (&mut self.0).take(len).read_to_end(&mut buf)? as u64 != len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated string"));
+        }
+        String::from_utf8(buf).map_err(|_| invalid("string is not valid UTF-8"))
+    }
+}
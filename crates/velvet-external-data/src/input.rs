@@ -0,0 +1,537 @@
+//! Declarative TOML run files which describe a simulation without writing Rust.
+//!
+//! A run file names the structure file of the system and lists the potentials, integrator,
+//! thermostat, barostat, and outputs of a simulation along with the number of steps to run.
+//! It is read into a [`RunBundle`], which builds the ready to run
+//! [`Simulation`](velvet_core::simulation::Simulation).
+//!
+//! ```toml
+//! steps = 10000
+//!
+//! [system]
+//! file = "argon.xyz"
+//!
+//! [potentials]
+//! update_frequency = 10
+//!
+//! [[potentials.pair]]
+//! species = ["Ar", "Ar"]
+//! potential = "lennard_jones"
+//! epsilon = 0.238
+//! sigma = 3.4
+//! cutoff = 8.5
+//! thickness = 1.0
+//!
+//! [integrator]
+//! type = "velocity_verlet"
+//! timestep = 1.0
+//!
+//! [thermostat]
+//! type = "bussi"
+//! target = 300.0
+//! tau = 100.0
+//!
+//! [[outputs]]
+//! file = "thermo.txt"
+//! interval = 100
+//! properties = ["temperature", "potential_energy"]
+//! ```
+//!
+//! # Sections
+//!
+//! * `steps` - Number of steps to run.
+//! * `[system]` - `file` is the path of the structure relative to the run file. Its `format` is
+//!   one of `xyz`, `pdb`, `gro`, `pqr`, `lammps`, or `poscar` and is inferred from the extension
+//!   when omitted. `infer_bonds` applies to PDB files.
+//! * `[potentials]` - Optional `update_frequency` of the neighbor lists, which defaults to 1.
+//! * `[[potentials.pair]]` - Pair potential between the two elements of `species` with a
+//!   `cutoff` and skin `thickness`. The `potential` is `buckingham` (`a`, `rho`, `c`),
+//!   `harmonic` (`k`, `x0`), `lennard_jones` (`epsilon`, `sigma`), `mie` (`epsilon`, `sigma`,
+//!   `gamma_a`, `gamma_r`), or `morse` (`a`, `d_e`, `r_e`).
+//! * `[potentials.coulomb]` - Coulombic potential with a `cutoff` and skin `thickness`. The
+//!   `potential` is `damped_shifted_force` (`alpha`), `ewald` (`alpha`, `kmax`),
+//!   `particle_mesh_ewald` (`alpha`, `spacing`, `order`), or `standard` (`dielectric`). An
+//!   optional `table = { inner = 0.5, accuracy = 1e-5 }` replaces it with lookup tables.
+//! * `[integrator]` - `type` is `velocity_verlet` or `leapfrog` with a `timestep`.
+//! * `[thermostat]` - Optional. `type` is `berendsen` (`target`, `tau`), `bussi` (`target`,
+//!   `tau`), `nose_hoover` (`target`, `freq`), `nose_hoover_chain` (`target`, `freq`,
+//!   `length`), or `andersen` (`target`, `freq`). Thermostats which need a timestep use the one
+//!   of the integrator.
+//! * `[barostat]` - Optional. `type` is `berendsen` (`target`, `compressibility`, `tau`) or
+//!   `parrinello_rahman` (`target`, `compressibility`, `tau`).
+//! * `[[outputs]]` - Raw output group written to `file`, or to stderr when omitted, every
+//!   `interval` steps. `properties` lists any of `coulombic_energy`, `pair_energy`,
+//!   `potential_energy`, `kinetic_energy`, `total_energy`, `temperature`, `pressure`,
+//!   `stress_tensor`, `forces`, `xyz_trajectory`, and `extended_xyz_trajectory`.
+//!
+//! Unknown sections and keys are reported as errors so that a misspelled parameter is never
+//! silently replaced by a default.
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+use toml::value::{Table, Value};
+use velvet_core::errors::VelvetError;
+use velvet_core::potentials::types::{
+    Buckingham, DampedShiftedForce, Ewald, Harmonic, LennardJones, Mie, Morse, ParticleMeshEwald, StandardCoulombic,
+};
+use velvet_core::system::elements::Element;
+use velvet_core::system::species::Species;
+use velvet_core::system::System;
+
+use crate::bundle::*;
+use crate::internal::Float;
+use crate::structures::gro::Gro;
+use crate::structures::lammps::LammpsData;
+use crate::structures::pdb::Pdb;
+use crate::structures::poscar::Poscar;
+use crate::structures::pqr::Pqr;
+use crate::structures::xyz::ExtendedXyz;
+use crate::structures::StructureFormat;
+
+fn error<T: Into<String>>(message: T) -> VelvetError {
+    VelvetError::parse("TOML", message)
+}
+
+// Parses a TOML document into its root table.
+fn parse_toml(text: &str) -> Result<Table, VelvetError> {
+    toml::from_str(text).map_err(|err| error(err.to_string()))
+}
+
+// Table of a run file section with the name used in error messages.
+struct Section<'a> {
+    name: String,
+    table: &'a Table,
+}
+
+impl<'a> Section<'a> {
+    fn new(name: &str, table: &'a Table, allowed: &[&str]) -> Result<Section<'a>, VelvetError> {
+        if let Some(key) = table.keys().find(|key| !allowed.contains(&key.as_str())) {
+            return Err(error(format!("Unknown key `{}` in [{}]", key, name)));
+        }
+        Ok(Section {
+            name: name.to_string(),
+            table,
+        })
+    }
+
+    fn get(&self, key: &str) -> Result<&'a Value, VelvetError> {
+        self.table
+            .get(key)
+            .ok_or_else(|| error(format!("Missing key `{}` in [{}]", key, self.name)))
+    }
+
+    fn invalid(&self, key: &str, expected: &str) -> VelvetError {
+        error(format!("Expected {} for `{}` in [{}]", expected, key, self.name))
+    }
+
+    fn float(&self, key: &str) -> Result<Float, VelvetError> {
+        match self.get(key)? {
+            Value::Float(x) => Ok(*x as Float),
+            Value::Integer(n) => Ok(*n as Float),
+            _ => Err(self.invalid(key, "a number")),
+        }
+    }
+
+    fn usize(&self, key: &str) -> Result<usize, VelvetError> {
+        match self.get(key)? {
+            Value::Integer(n) if *n >= 0 => Ok(*n as usize),
+            _ => Err(self.invalid(key, "a non-negative integer")),
+        }
+    }
+
+    fn usize_or(&self, key: &str, default: usize) -> Result<usize, VelvetError> {
+        if self.table.contains_key(key) {
+            self.usize(key)
+        } else {
+            Ok(default)
+        }
+    }
+
+    fn string(&self, key: &str) -> Result<&'a str, VelvetError> {
+        match self.get(key)? {
+            Value::String(s) => Ok(s),
+            _ => Err(self.invalid(key, "a string")),
+        }
+    }
+
+    fn optional_string(&self, key: &str) -> Result<Option<&'a str>, VelvetError> {
+        if self.table.contains_key(key) {
+            self.string(key).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn strings(&self, key: &str) -> Result<Vec<&'a str>, VelvetError> {
+        match self.get(key)? {
+            Value::Array(values) => values
+                .iter()
+                .map(|value| match value {
+                    Value::String(s) => Ok(s.as_str()),
+                    _ => Err(self.invalid(key, "an array of strings")),
+                })
+                .collect(),
+            _ => Err(self.invalid(key, "an array of strings")),
+        }
+    }
+
+    fn boolean_or(&self, key: &str, default: bool) -> Result<bool, VelvetError> {
+        match self.table.get(key) {
+            None => Ok(default),
+            Some(Value::Boolean(b)) => Ok(*b),
+            Some(_) => Err(self.invalid(key, "a boolean")),
+        }
+    }
+
+    fn unknown(&self, key: &str, value: &str) -> VelvetError {
+        error(format!("Unknown {} `{}` in [{}]", key, value, self.name))
+    }
+}
+
+// Returns the tables of `key` in `table`, which may be a single table or an array of tables.
+fn tables<'a>(table: &'a Table, key: &str) -> Result<Vec<&'a Table>, VelvetError> {
+    match table.get(key) {
+        None => Ok(Vec::new()),
+        Some(Value::Table(inner)) => Ok(vec![inner]),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| match value {
+                Value::Table(inner) => Ok(inner),
+                _ => Err(error(format!("Expected tables in `{}`", key))),
+            })
+            .collect(),
+        Some(_) => Err(error(format!("Expected a table for `{}`", key))),
+    }
+}
+
+fn table<'a>(root: &'a Table, key: &str) -> Result<Option<&'a Table>, VelvetError> {
+    match root.get(key) {
+        None => Ok(None),
+        Some(Value::Table(inner)) => Ok(Some(inner)),
+        Some(_) => Err(error(format!("Expected a table for `{}`", key))),
+    }
+}
+
+fn read_system(section: &Section, directory: &Path) -> Result<System, VelvetError> {
+    let file = section.string("file")?;
+    let path = directory.join(file);
+    let name = path
+        .file_name()
+        .map_or(String::new(), |name| name.to_string_lossy().to_ascii_lowercase());
+    let extension = path
+        .extension()
+        .map_or(String::new(), |ext| ext.to_string_lossy().to_ascii_lowercase());
+    let format = match section.optional_string("format")? {
+        Some(format) => format.to_string(),
+        None => match extension.as_str() {
+            "xyz" | "extxyz" => "xyz".to_string(),
+            "data" | "lmp" => "lammps".to_string(),
+            "pdb" | "gro" | "pqr" | "poscar" => extension.clone(),
+            "vasp" => "poscar".to_string(),
+            _ if name.starts_with("poscar") || name.starts_with("contcar") => "poscar".to_string(),
+            _ => return Err(error(format!("Unable to infer the format of `{}`", file))),
+        },
+    };
+    let path = path.to_string_lossy();
+    match format.as_str() {
+        "xyz" => ExtendedXyz.parse_system_from_file(path),
+        "pdb" => Pdb {
+            infer_bonds: section.boolean_or("infer_bonds", false)?,
+        }
+        .parse_system_from_file(path),
+        "gro" => Gro.parse_system_from_file(path),
+        "pqr" => Pqr.parse_system_from_file(path),
+        "lammps" => LammpsData.parse_system_from_file(path),
+        "poscar" => Poscar.parse_system_from_file(path),
+        other => Err(section.unknown("format", other)),
+    }
+}
+
+fn species(section: &Section) -> Result<(Species, Species), VelvetError> {
+    let symbols = section.strings("species")?;
+    if symbols.len() != 2 {
+        return Err(section.invalid("species", "two element symbols"));
+    }
+    let species = |symbol: &str| {
+        Element::from_str(symbol)
+            .map(Species::from_element)
+            .map_err(|_| section.unknown("element", symbol))
+    };
+    Ok((species(symbols[0])?, species(symbols[1])?))
+}
+
+fn pair(table: &Table) -> Result<PairSpec, VelvetError> {
+    let common = ["species", "potential", "cutoff", "thickness"];
+    let kind = Section::new(
+        "potentials.pair",
+        table,
+        &[
+            &common[..],
+            &[
+                "a", "rho", "c", "k", "x0", "epsilon", "sigma", "gamma_a", "gamma_r", "d_e", "r_e",
+            ],
+        ]
+        .concat(),
+    )?;
+    let name = kind.string("potential")?;
+    let parameters: &[&str] = match name {
+        "buckingham" => &["a", "rho", "c"],
+        "harmonic" => &["k", "x0"],
+        "lennard_jones" => &["epsilon", "sigma"],
+        "mie" => &["epsilon", "sigma", "gamma_a", "gamma_r"],
+        "morse" => &["a", "d_e", "r_e"],
+        other => return Err(kind.unknown("potential", other)),
+    };
+    let section = Section::new(
+        &format!("potentials.pair {}", name),
+        table,
+        &[&common[..], parameters].concat(),
+    )?;
+    let f = |key| section.float(key);
+    let potential = match name {
+        "buckingham" => PairKind::Buckingham(Buckingham::new(f("a")?, f("rho")?, f("c")?)),
+        "harmonic" => PairKind::Harmonic(Harmonic::new(f("k")?, f("x0")?)),
+        "lennard_jones" => PairKind::LennardJones(LennardJones::new(f("epsilon")?, f("sigma")?)),
+        "mie" => PairKind::Mie(Mie::new(f("epsilon")?, f("sigma")?, f("gamma_a")?, f("gamma_r")?)),
+        _ => PairKind::Morse(Morse::new(f("a")?, f("d_e")?, f("r_e")?)),
+    };
+    Ok(PairSpec {
+        potential,
+        species: species(&section)?,
+        cutoff: section.float("cutoff")?,
+        thickness: section.float("thickness")?,
+    })
+}
+
+fn coulomb(table: &Table) -> Result<CoulombSpec, VelvetError> {
+    let common = ["potential", "cutoff", "thickness", "table"];
+    let kind = Section::new(
+        "potentials.coulomb",
+        table,
+        &[&common[..], &["alpha", "kmax", "spacing", "order", "dielectric"]].concat(),
+    )?;
+    let name = kind.string("potential")?;
+    let parameters: &[&str] = match name {
+        "damped_shifted_force" => &["alpha"],
+        "ewald" => &["alpha", "kmax"],
+        "particle_mesh_ewald" => &["alpha", "spacing", "order"],
+        "standard" => &["dielectric"],
+        other => return Err(kind.unknown("potential", other)),
+    };
+    let section = Section::new("potentials.coulomb", table, &[&common[..], parameters].concat())?;
+    let cutoff = section.float("cutoff")?;
+    let potential = match name {
+        "damped_shifted_force" => {
+            CoulombKind::DampedShiftedForce(DampedShiftedForce::new(section.float("alpha")?, cutoff))
+        }
+        "ewald" => CoulombKind::Ewald(Ewald::new(section.float("alpha")?, section.usize("kmax")?)),
+        "particle_mesh_ewald" => CoulombKind::ParticleMeshEwald(ParticleMeshEwald::new(
+            section.float("alpha")?,
+            section.float("spacing")?,
+            section.usize("order")?,
+        )),
+        _ => CoulombKind::StandardCoulombic(StandardCoulombic::new(section.float("dielectric")?)),
+    };
+    let table = match section.table.get("table") {
+        None => None,
+        Some(Value::Table(inner)) => {
+            let lookup = Section::new("potentials.coulomb.table", inner, &["inner", "accuracy"])?;
+            Some(TableSpec {
+                inner: lookup.float("inner")?,
+                accuracy: lookup.float("accuracy")?,
+            })
+        }
+        Some(_) => return Err(section.invalid("table", "a table")),
+    };
+    Ok(CoulombSpec {
+        potential,
+        cutoff,
+        thickness: section.float("thickness")?,
+        table,
+    })
+}
+
+fn propagator(root: &Table) -> Result<PropagatorSpec, VelvetError> {
+    let integrator = table(root, "integrator")?.ok_or_else(|| error("Missing section [integrator]"))?;
+    let integrator = Section::new("integrator", integrator, &["type", "timestep"])?;
+    let timestep = integrator.float("timestep")?;
+    let integrator = match integrator.string("type")? {
+        "velocity_verlet" => IntegratorSpec::VelocityVerlet { timestep },
+        "leapfrog" => IntegratorSpec::Leapfrog { timestep },
+        other => return Err(integrator.unknown("type", other)),
+    };
+
+    let thermostat = match table(root, "thermostat")? {
+        None => ThermostatSpec::Null,
+        Some(inner) => {
+            let kind = Section::new("thermostat", inner, &["type", "target", "tau", "freq", "length"])?;
+            match kind.string("type")? {
+                "none" => {
+                    Section::new("thermostat", inner, &["type"])?;
+                    ThermostatSpec::Null
+                }
+                "berendsen" | "bussi" => {
+                    let section = Section::new("thermostat", inner, &["type", "target", "tau"])?;
+                    let (target, tau) = (section.float("target")?, section.float("tau")?);
+                    if kind.string("type")? == "bussi" {
+                        ThermostatSpec::Bussi { target, tau }
+                    } else {
+                        ThermostatSpec::Berendsen { target, tau }
+                    }
+                }
+                "nose_hoover" | "andersen" => {
+                    let section = Section::new("thermostat", inner, &["type", "target", "freq"])?;
+                    let (target, freq) = (section.float("target")?, section.float("freq")?);
+                    if kind.string("type")? == "andersen" {
+                        ThermostatSpec::Andersen { target, freq, timestep }
+                    } else {
+                        ThermostatSpec::NoseHoover { target, freq, timestep }
+                    }
+                }
+                "nose_hoover_chain" => {
+                    let section = Section::new("thermostat", inner, &["type", "target", "freq", "length"])?;
+                    ThermostatSpec::NoseHooverChain {
+                        target: section.float("target")?,
+                        freq: section.float("freq")?,
+                        timestep,
+                        length: section.usize_or("length", 3)?,
+                    }
+                }
+                other => return Err(kind.unknown("type", other)),
+            }
+        }
+    };
+
+    let barostat = match table(root, "barostat")? {
+        None => None,
+        Some(inner) => {
+            let section = Section::new("barostat", inner, &["type", "target", "compressibility", "tau"])?;
+            let (target, compressibility, tau) = (
+                section.float("target")?,
+                section.float("compressibility")?,
+                section.float("tau")?,
+            );
+            Some(match section.string("type")? {
+                "berendsen" => BarostatSpec::Berendsen {
+                    target,
+                    compressibility,
+                    tau,
+                },
+                "parrinello_rahman" => BarostatSpec::ParrinelloRahman {
+                    target,
+                    compressibility,
+                    tau,
+                    timestep,
+                },
+                other => return Err(section.unknown("type", other)),
+            })
+        }
+    };
+
+    Ok(PropagatorSpec::MolecularDynamics {
+        integrator,
+        thermostat,
+        barostat,
+    })
+}
+
+fn output(table: &Table, directory: &Path) -> Result<OutputSpec, VelvetError> {
+    let section = Section::new("outputs", table, &["file", "interval", "properties"])?;
+    let properties = section
+        .strings("properties")?
+        .into_iter()
+        .map(|name| {
+            Ok(match name {
+                "coulombic_energy" => PropertyKind::CoulombicEnergy,
+                "pair_energy" => PropertyKind::PairEnergy,
+                "potential_energy" => PropertyKind::PotentialEnergy,
+                "kinetic_energy" => PropertyKind::KineticEnergy,
+                "total_energy" => PropertyKind::TotalEnergy,
+                "temperature" => PropertyKind::Temperature,
+                "pressure" => PropertyKind::Pressure,
+                "stress_tensor" => PropertyKind::StressTensor,
+                "forces" => PropertyKind::Forces,
+                "xyz_trajectory" => PropertyKind::XyzTrajectory,
+                "extended_xyz_trajectory" => PropertyKind::ExtendedXyzTrajectory,
+                other => return Err(section.unknown("property", other)),
+            })
+        })
+        .collect::<Result<_, VelvetError>>()?;
+    Ok(OutputSpec {
+        destination: section
+            .optional_string("file")?
+            .map(|file| directory.join(file).to_string_lossy().into_owned()),
+        interval: section.usize_or("interval", 1)?.max(1),
+        properties,
+    })
+}
+
+impl RunBundle {
+    /// Returns the [`RunBundle`] described by a TOML run file.
+    ///
+    /// Paths in the run file are relative to `directory`.
+    pub fn from_toml(text: &str, directory: &Path) -> Result<RunBundle, VelvetError> {
+        let root = parse_toml(text)?;
+        let sections = [
+            "steps",
+            "system",
+            "potentials",
+            "integrator",
+            "thermostat",
+            "barostat",
+            "outputs",
+        ];
+        Section::new("root", &root, &sections)?;
+        let steps = match root.get("steps") {
+            Some(Value::Integer(n)) if *n >= 0 => *n as usize,
+            Some(_) => return Err(error("Expected a non-negative integer for `steps`")),
+            None => return Err(error("Missing key `steps`")),
+        };
+
+        let system = table(&root, "system")?.ok_or_else(|| error("Missing section [system]"))?;
+        let system = read_system(
+            &Section::new("system", system, &["file", "format", "infer_bonds"])?,
+            directory,
+        )?;
+
+        let empty = Table::new();
+        let potentials = table(&root, "potentials")?.unwrap_or(&empty);
+        let section = Section::new("potentials", potentials, &["update_frequency", "pair", "coulomb"])?;
+        let coulomb = match table(potentials, "coulomb")? {
+            None => None,
+            Some(inner) => Some(coulomb(inner)?),
+        };
+        let potentials = PotentialsSpec {
+            coulomb,
+            pairs: tables(potentials, "pair")?
+                .into_iter()
+                .map(pair)
+                .collect::<Result<_, _>>()?,
+            update_frequency: section.usize_or("update_frequency", 1)?.max(1),
+        };
+
+        let outputs = tables(&root, "outputs")?
+            .into_iter()
+            .map(|table| output(table, directory))
+            .collect::<Result<_, _>>()?;
+
+        Ok(RunBundle {
+            system,
+            potentials,
+            propagator: propagator(&root)?,
+            outputs,
+            steps,
+        })
+    }
+
+    /// Reads the [`RunBundle`] described by the TOML run file at `path`.
+    ///
+    /// Paths in the run file are relative to the directory which contains it.
+    pub fn read_toml_file<P: AsRef<Path>>(path: P) -> Result<RunBundle, VelvetError> {
+        let text = fs::read_to_string(&path)?;
+        let directory = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+        RunBundle::from_toml(&text, directory)
+    }
+}
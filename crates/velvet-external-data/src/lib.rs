@@ -1,9 +1,21 @@
 //! Utilities to import and export external data formats.
 
+pub mod bundle;
+pub mod input;
 mod internal;
+pub mod potentials;
 pub mod structures;
 
 pub mod prelude {
+    pub use super::bundle::*;
+    pub use super::potentials::eam::*;
+    pub use super::structures::dftb::*;
+    pub use super::structures::espresso::*;
+    pub use super::structures::gro::*;
+    pub use super::structures::lammps::*;
+    pub use super::structures::pdb::*;
     pub use super::structures::poscar::*;
+    pub use super::structures::pqr::*;
+    pub use super::structures::xyz::*;
     pub use super::structures::*;
 }
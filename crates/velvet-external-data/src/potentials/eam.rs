@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+
+/// Energy of one electronvolt in kcal/mol.
+const EV: Float = 23.060548;
+
+/// Product of the Hartree energy in eV and the Bohr radius in angstroms used by `funcfl` effective charges.
+const HARTREE_BOHR: Float = 27.2 * 0.529;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// Whitespace separated tokens of the tabulated section of a file.
+struct Tokens<'a>(std::str::SplitWhitespace<'a>);
+
+impl<'a> Tokens<'a> {
+    fn next<T: FromStr>(&mut self, what: &str) -> io::Result<T> {
+        let token = self
+            .0
+            .next()
+            .ok_or_else(|| invalid(&format!("unexpected end of file while reading {}", what)))?;
+        token
+            .parse()
+            .map_err(|_| invalid(&format!("invalid {} '{}'", what, token)))
+    }
+
+    fn values(&mut self, count: usize, what: &str) -> io::Result<Vec<Float>> {
+        (0..count).map(|_| self.next(what)).collect()
+    }
+}
+
+// Grid of the tabulated functions shared by every species in a file.
+struct Grid {
+    nrho: usize,
+    drho: Float,
+    nr: usize,
+    dr: Float,
+    cutoff: Float,
+}
+
+impl Grid {
+    fn read(tokens: &mut Tokens) -> io::Result<Grid> {
+        Ok(Grid {
+            nrho: tokens.next("number of densities")?,
+            drho: tokens.next("density spacing")?,
+            nr: tokens.next("number of separations")?,
+            dr: tokens.next("separation spacing")?,
+            cutoff: tokens.next("cutoff")?,
+        })
+    }
+
+    // Returns pair energies in kcal/mol from values of `r * phi(r)` in eV angstroms.
+    fn pair_energies(&self, scaled: &[Float]) -> Vec<Float> {
+        let mut energies: Vec<Float> = scaled
+            .iter()
+            .enumerate()
+            .map(|(i, value)| if i == 0 { 0.0 } else { EV * value / (i as Float * self.dr) })
+            .collect();
+        // the energy at zero separation is extrapolated since r * phi(r) vanishes there
+        if energies.len() > 2 {
+            energies[0] = 2.0 * energies[1] - energies[2];
+        }
+        energies
+    }
+}
+
+fn to_kcal(values: Vec<Float>) -> Vec<Float> {
+    values.into_iter().map(|value| EV * value).collect()
+}
+
+/// Reads an [`EmbeddedAtom`] potential for several elements from the multi-element `setfl` format.
+///
+/// The `setfl` format is used by the `eam/alloy` pair style of LAMMPS. Energies are converted
+/// from eV to kcal/mol and each element becomes the [`Species`] constructed from it.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let potential = read_setfl("\
+///     comment
+///     comment
+///     comment
+///     1 Cu
+///     3 1.0 3 2.0 4.0
+///     29 63.546 3.615 fcc
+///     0.0 -1.0 -1.4
+///     1.0 0.1 0.0
+///     9.0 0.5 0.0
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(potential.species().len(), 1);
+/// assert_eq!(potential.cutoff(), 4.0);
+/// ```
+pub fn read_setfl<R: Read>(mut reader: R) -> io::Result<EmbeddedAtom> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    // the first three lines are comments
+    let mut lines = contents.splitn(4, '\n');
+    for _ in 0..3 {
+        lines.next().ok_or_else(|| invalid("missing comment lines"))?;
+    }
+    let mut tokens = Tokens(lines.next().unwrap_or("").split_whitespace());
+
+    let count: usize = tokens.next("number of elements")?;
+    let species = (0..count)
+        .map(|_| {
+            let symbol: String = tokens.next("element symbol")?;
+            let element = Element::from_str(&symbol).map_err(|_| invalid(&format!("unknown element '{}'", symbol)))?;
+            Ok(Species::from_element(element))
+        })
+        .collect::<io::Result<Vec<Species>>>()?;
+    let grid = Grid::read(&mut tokens)?;
+
+    let mut potential = EmbeddedAtom::new(species.clone(), grid.cutoff);
+    for &sp in &species {
+        // atomic number, mass, lattice constant, and lattice type are not needed
+        for _ in 0..4 {
+            tokens.next::<String>("element properties")?;
+        }
+        let embedding = tokens.values(grid.nrho, "embedding energy")?;
+        let density = tokens.values(grid.nr, "electron density")?;
+        potential = potential
+            .embedding(sp, grid.drho, to_kcal(embedding))
+            .density(sp, grid.dr, density);
+    }
+    for i in 0..count {
+        for j in 0..=i {
+            let scaled = tokens.values(grid.nr, "pair energy")?;
+            potential = potential.pair((species[i], species[j]), grid.dr, grid.pair_energies(&scaled));
+        }
+    }
+    Ok(potential)
+}
+
+/// Reads an [`EmbeddedAtom`] potential for a single element from the `funcfl` format.
+///
+/// The `funcfl` format is used by the `eam` pair style of LAMMPS. The pair energy is derived
+/// from the tabulated effective charge `Z(r)` as `27.2 * 0.529 * Z(r)^2 / r`. Energies are
+/// converted from eV to kcal/mol and the element becomes the [`Species`] constructed from it.
+///
+/// # Examples
+///
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let potential = read_funcfl("\
+///     comment
+///     29 63.546 3.615 fcc
+///     3 1.0 3 2.0 4.0
+///     0.0 -1.0 -1.4
+///     1.0 0.5 0.0
+///     1.0 0.1 0.0
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(potential.species().len(), 1);
+/// ```
+pub fn read_funcfl<R: Read>(mut reader: R) -> io::Result<EmbeddedAtom> {
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    // the first line is a comment
+    let mut lines = contents.splitn(2, '\n');
+    lines.next().ok_or_else(|| invalid("missing comment line"))?;
+    let mut tokens = Tokens(lines.next().unwrap_or("").split_whitespace());
+
+    let number: u8 = tokens.next("atomic number")?;
+    let element = Element::from_number(number).ok_or_else(|| invalid(&format!("unknown atomic number {}", number)))?;
+    let species = Species::from_element(element);
+    // mass, lattice constant, and lattice type are not needed
+    for _ in 0..3 {
+        tokens.next::<String>("element properties")?;
+    }
+    let grid = Grid::read(&mut tokens)?;
+    let embedding = tokens.values(grid.nrho, "embedding energy")?;
+    let charge = tokens.values(grid.nr, "effective charge")?;
+    let density = tokens.values(grid.nr, "electron density")?;
+    let scaled: Vec<Float> = charge.iter().map(|z| HARTREE_BOHR * z * z).collect();
+
+    Ok(EmbeddedAtom::new(vec![species], grid.cutoff)
+        .embedding(species, grid.drho, to_kcal(embedding))
+        .density(species, grid.dr, density)
+        .pair((species, species), grid.dr, grid.pair_energies(&scaled)))
+}
+
+/// Reads an [`EmbeddedAtom`] potential from the `setfl` file at `path`.
+pub fn load_setfl<P: AsRef<Path>>(path: P) -> io::Result<EmbeddedAtom> {
+    read_setfl(File::open(path)?)
+}
+
+/// Reads an [`EmbeddedAtom`] potential from the `funcfl` file at `path`.
+pub fn load_funcfl<P: AsRef<Path>>(path: P) -> io::Result<EmbeddedAtom> {
+    read_funcfl(File::open(path)?)
+}
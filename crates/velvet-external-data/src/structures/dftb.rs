@@ -0,0 +1,179 @@
+use std::fmt::Write as _;
+use std::io::Read;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+use velvet_core::errors::VelvetError;
+use velvet_core::outputs::trajectory::species_labels;
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+use crate::structures::{padded_cell, StructureFormat};
+
+/// DFTB+ gen geometry format.
+///
+/// Cluster (`C`), supercell (`S`), and fractional (`F`) geometries are read, with species
+/// constructed from the chemical symbols of the type line. Clusters are placed in an
+/// orthorhombic cell which spans the atoms with 10 angstroms of padding on each side. The
+/// format does not store velocities so every atom starts at rest.
+///
+/// Systems are written as supercells in cartesian coordinates, ready to be included in the
+/// `Geometry = GenFormat { ... }` block of a `dftb_in.hsd` input for a single point
+/// calculation. Custom species are labeled `X1`, `X2`, etc. in order of appearance.
+///
+/// # Examples
+///
+/// Construct a [`System`](velvet_core::system::System) from gen data.
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let system = DftbGen.parse_system_from_reader("\
+/// 2 F
+/// Si
+/// 1 1 0.00 0.00 0.00
+/// 2 1 0.25 0.25 0.25
+/// 0.0 0.0 0.0
+/// 0.000 2.715 2.715
+/// 2.715 0.000 2.715
+/// 2.715 2.715 0.000
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(system.size, 2);
+/// assert!((system.positions[1].x - 1.3575).abs() < 1e-5);
+/// ```
+pub struct DftbGen;
+
+/// Constructs a [`System`] from the gen file at `filename`.
+pub fn load_gen<T: AsRef<str>>(filename: T) -> Result<System, VelvetError> {
+    DftbGen.parse_system_from_file(filename)
+}
+
+/// Writes `system` to a gen file at `filename`.
+pub fn write_gen<T: AsRef<str>>(system: &System, filename: T) -> Result<(), VelvetError> {
+    DftbGen.write_file_from_system(system, filename)
+}
+
+fn parse<T: FromStr>(token: &str) -> Result<T, VelvetError> {
+    token
+        .parse()
+        .map_err(|_| VelvetError::parse("gen", format!("Invalid value `{}`", token)))
+}
+
+// Reads the first three tokens of `line` as a vector.
+fn parse_vector(line: &str) -> Result<Vector3<Float>, VelvetError> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 3 {
+        return Err(VelvetError::parse("gen", format!("Expected a vector in `{}`", line)));
+    }
+    Ok(Vector3::new(parse(tokens[0])?, parse(tokens[1])?, parse(tokens[2])?))
+}
+
+impl StructureFormat for DftbGen {
+    fn parse_system_from_reader<T: Read>(&self, mut reader: T) -> Result<System, VelvetError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        // comments begin with `#` and blank lines are ignored
+        let mut lines = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty());
+        let missing = |what: &str| VelvetError::parse("gen", format!("Missing {}", what));
+
+        let header: Vec<&str> = lines
+            .next()
+            .ok_or_else(|| missing("header"))?
+            .split_whitespace()
+            .collect();
+        let size: usize = parse(header[0])?;
+        let kind = header
+            .get(1)
+            .ok_or_else(|| missing("geometry type"))?
+            .to_ascii_uppercase();
+        if !["C", "S", "F"].contains(&kind.as_str()) {
+            return Err(VelvetError::parse("gen", format!("Unknown geometry type `{}`", kind)));
+        }
+        let types: Vec<Species> = lines
+            .next()
+            .ok_or_else(|| missing("species"))?
+            .split_whitespace()
+            .map(|symbol| {
+                Element::from_str(symbol)
+                    .map(Species::from_element)
+                    .map_err(|_| VelvetError::parse("gen", format!("Unknown element `{}`", symbol)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        let mut species = Vec::with_capacity(size);
+        let mut coordinates = Vec::with_capacity(size);
+        for _ in 0..size {
+            let line = lines
+                .next()
+                .ok_or_else(|| VelvetError::parse("gen", format!("Expected {} atoms", size)))?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() < 5 {
+                return Err(VelvetError::parse("gen", format!("Truncated atom record `{}`", line)));
+            }
+            let index: usize = parse(tokens[1])?;
+            let sp = index
+                .checked_sub(1)
+                .and_then(|index| types.get(index))
+                .ok_or_else(|| VelvetError::parse("gen", format!("Invalid species index `{}`", tokens[1])))?;
+            species.push(*sp);
+            coordinates.push(Vector3::new(parse(tokens[2])?, parse(tokens[3])?, parse(tokens[4])?));
+        }
+
+        let (cell, positions) = if kind == "C" {
+            (padded_cell(&coordinates), coordinates)
+        } else {
+            // the origin line precedes the lattice vectors and does not move the atoms
+            lines.next().ok_or_else(|| missing("origin"))?;
+            let mut vectors = Vec::with_capacity(3);
+            for _ in 0..3 {
+                vectors.push(parse_vector(lines.next().ok_or_else(|| missing("lattice vectors"))?)?);
+            }
+            let cell = Cell::from_vectors(vectors[0], vectors[1], vectors[2]);
+            let positions = if kind == "F" {
+                coordinates
+                    .iter()
+                    .map(|fractional| cell.cartesian(fractional))
+                    .collect()
+            } else {
+                coordinates
+            };
+            (cell, positions)
+        };
+
+        Ok(System {
+            size,
+            cell,
+            species,
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology::default(),
+        })
+    }
+
+    fn write_str_from_system(&self, system: &System) -> String {
+        let labels = species_labels(system);
+        let mut types: Vec<&str> = Vec::new();
+        for label in &labels {
+            if !types.contains(&label.as_str()) {
+                types.push(label);
+            }
+        }
+        let mut s = String::new();
+        writeln!(s, "{} S", system.size).unwrap();
+        writeln!(s, "{}", types.join(" ")).unwrap();
+        for (i, label) in labels.iter().enumerate() {
+            let pos = system.positions[i];
+            let index = types.iter().position(|t| *t == label.as_str()).unwrap() + 1;
+            writeln!(s, "{} {} {} {} {}", i + 1, index, pos[0], pos[1], pos[2]).unwrap();
+        }
+        writeln!(s, "0.0 0.0 0.0").unwrap();
+        let matrix = system.cell.matrix();
+        for col in 0..3 {
+            writeln!(s, "{} {} {}", matrix[(0, col)], matrix[(1, col)], matrix[(2, col)]).unwrap();
+        }
+        s
+    }
+}
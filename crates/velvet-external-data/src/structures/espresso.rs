@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Read;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+use velvet_core::errors::VelvetError;
+use velvet_core::outputs::trajectory::species_labels;
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+use crate::structures::StructureFormat;
+
+/// Conversion factor from bohr to angstroms.
+const BOHR_TO_ANGSTROM: Float = 0.529177;
+
+/// Quantum ESPRESSO `pw.x` input format.
+///
+/// Systems are written as the `ATOMIC_SPECIES`, `CELL_PARAMETERS`, and `ATOMIC_POSITIONS`
+/// cards of a `pw.x` input in angstroms, preceded by a comment with the `ibrav`, `nat`, and
+/// `ntyp` values for the `&SYSTEM` namelist. Together with the `&CONTROL`, `&SYSTEM`, and
+/// `&ELECTRONS` namelists of a calculation they form a complete single point input. Each
+/// species refers to a pseudopotential file named after its label, such as `Ar.UPF`, which can
+/// be renamed to match the files in `pseudo_dir`. Custom species are labeled `X1`, `X2`, etc.
+/// in order of appearance.
+///
+/// The same cards are read from any `pw.x` input whose cell and positions are given in
+/// angstroms, bohr, or, for positions, crystal coordinates. Species are constructed from the
+/// chemical symbol which begins each label, such as `Fe` for `Fe1`, or from the mass of custom
+/// labels. The format does not store velocities so every atom starts at rest.
+///
+/// # Examples
+///
+/// Construct a [`System`](velvet_core::system::System) from `pw.x` input data.
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let system = PwInput.parse_system_from_reader("\
+/// &CONTROL
+///   calculation = 'scf'
+/// /
+/// ATOMIC_SPECIES
+/// Mg 24.305 Mg.UPF
+/// O 15.999 O.UPF
+/// CELL_PARAMETERS angstrom
+/// 4.21 0.00 0.00
+/// 0.00 4.21 0.00
+/// 0.00 0.00 4.21
+/// ATOMIC_POSITIONS crystal
+/// Mg 0.0 0.0 0.0
+/// O 0.5 0.5 0.5
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(system.size, 2);
+/// assert!((system.positions[1].x - 2.105).abs() < 1e-5);
+/// ```
+pub struct PwInput;
+
+/// Constructs a [`System`] from the `pw.x` input file at `filename`.
+pub fn load_pw_input<T: AsRef<str>>(filename: T) -> Result<System, VelvetError> {
+    PwInput.parse_system_from_file(filename)
+}
+
+/// Writes `system` to the cards of a `pw.x` input file at `filename`.
+pub fn write_pw_input<T: AsRef<str>>(system: &System, filename: T) -> Result<(), VelvetError> {
+    PwInput.write_file_from_system(system, filename)
+}
+
+fn parse<T: FromStr>(token: &str) -> Result<T, VelvetError> {
+    token
+        .parse()
+        .map_err(|_| VelvetError::parse("PW", format!("Invalid value `{}`", token)))
+}
+
+// Reads a species label followed by three coordinates.
+fn parse_site(line: &str) -> Result<(&str, Vector3<Float>), VelvetError> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 4 {
+        return Err(VelvetError::parse("PW", format!("Truncated atom record `{}`", line)));
+    }
+    let position = Vector3::new(parse(tokens[1])?, parse(tokens[2])?, parse(tokens[3])?);
+    Ok((tokens[0], position))
+}
+
+/// Names of the cards which may follow the namelists of a `pw.x` input.
+const CARDS: [&str; 11] = [
+    "ATOMIC_SPECIES",
+    "ATOMIC_POSITIONS",
+    "K_POINTS",
+    "ADDITIONAL_K_POINTS",
+    "CELL_PARAMETERS",
+    "CONSTRAINTS",
+    "OCCUPATIONS",
+    "ATOMIC_VELOCITIES",
+    "ATOMIC_FORCES",
+    "SOLVENTS",
+    "HUBBARD",
+];
+
+// Returns the option and the lines of the card `name`, which ends at the next card or namelist.
+fn card<'a>(lines: &'a [&'a str], name: &str) -> Option<(String, &'a [&'a str])> {
+    let keyword = |line: &str| line.split_whitespace().next().unwrap_or_default().to_ascii_uppercase();
+    let start = lines.iter().position(|line| keyword(line) == name)?;
+    let rest = &lines[start + 1..];
+    let len = rest
+        .iter()
+        .position(|line| line.starts_with('&') || CARDS.contains(&keyword(line).as_str()))
+        .unwrap_or(rest.len());
+    Some((card_option(lines[start]), &rest[..len]))
+}
+
+// Returns the lowercase option of a card header such as `ATOMIC_POSITIONS {crystal}`.
+fn card_option(header: &str) -> String {
+    header
+        .split_whitespace()
+        .nth(1)
+        .unwrap_or_default()
+        .trim_matches(|c| c == '{' || c == '}' || c == '(' || c == ')')
+        .to_ascii_lowercase()
+}
+
+// Constructs the species of a label from its leading chemical symbol or its mass.
+fn species_of(label: &str, mass: Option<Float>) -> Result<Species, VelvetError> {
+    let letters: String = label.chars().take_while(|c| c.is_ascii_alphabetic()).collect();
+    let letters = letters.get(..1).unwrap_or_default().to_ascii_uppercase()
+        + &letters.get(1..).unwrap_or_default().to_ascii_lowercase();
+    // two letter symbols take precedence, such that `Co1` is cobalt rather than carbon
+    let element = [2, 1]
+        .iter()
+        .filter(|&&n| letters.len() >= n)
+        .find_map(|&n| Element::from_str(&letters[..n]).ok());
+    match (element, mass) {
+        (Some(element), _) => Ok(Species::from_element(element)),
+        (None, Some(mass)) => Ok(Species::new(mass, 0.0)),
+        (None, None) => Err(VelvetError::parse(
+            "PW",
+            format!("Unknown species `{}` without a mass", label),
+        )),
+    }
+}
+
+impl StructureFormat for PwInput {
+    fn parse_system_from_reader<T: Read>(&self, mut reader: T) -> Result<System, VelvetError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        // comments begin with `!` or `#` and blank lines are ignored
+        let lines: Vec<&str> = text
+            .lines()
+            .map(|line| line.split(['!', '#']).next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+        let missing = |what: &str| VelvetError::parse("PW", format!("Missing {} card", what));
+        let unsupported = |option: &str| VelvetError::parse("PW", format!("Unsupported units `{}`", option));
+
+        let mut masses: HashMap<&str, Float> = HashMap::new();
+        if let Some((_, entries)) = card(&lines, "ATOMIC_SPECIES") {
+            for entry in entries {
+                let tokens: Vec<&str> = entry.split_whitespace().collect();
+                if tokens.len() < 2 {
+                    return Err(VelvetError::parse(
+                        "PW",
+                        format!("Truncated species record `{}`", entry),
+                    ));
+                }
+                masses.insert(tokens[0], parse(tokens[1])?);
+            }
+        }
+
+        let (option, rows) = card(&lines, "CELL_PARAMETERS").ok_or_else(|| missing("CELL_PARAMETERS"))?;
+        let scale = match option.as_str() {
+            "angstrom" => 1.0,
+            "bohr" => BOHR_TO_ANGSTROM,
+            _ => return Err(unsupported(&option)),
+        };
+        if rows.len() < 3 {
+            return Err(VelvetError::parse("PW", "Expected 3 lattice vectors"));
+        }
+        let mut vectors = Vec::with_capacity(3);
+        for row in &rows[..3] {
+            let tokens: Vec<&str> = row.split_whitespace().collect();
+            if tokens.len() < 3 {
+                return Err(VelvetError::parse("PW", format!("Expected a vector in `{}`", row)));
+            }
+            vectors.push(Vector3::new(parse(tokens[0])?, parse(tokens[1])?, parse(tokens[2])?) * scale);
+        }
+        let cell = Cell::from_vectors(vectors[0], vectors[1], vectors[2]);
+
+        let (option, sites) = card(&lines, "ATOMIC_POSITIONS").ok_or_else(|| missing("ATOMIC_POSITIONS"))?;
+        let mut species = Vec::with_capacity(sites.len());
+        let mut positions = Vec::with_capacity(sites.len());
+        for site in sites {
+            let (label, coordinates) = parse_site(site)?;
+            species.push(species_of(label, masses.get(label).copied())?);
+            positions.push(match option.as_str() {
+                "angstrom" => coordinates,
+                "bohr" => coordinates * BOHR_TO_ANGSTROM,
+                "crystal" => cell.cartesian(&coordinates),
+                _ => return Err(unsupported(&option)),
+            });
+        }
+
+        Ok(System {
+            size: positions.len(),
+            cell,
+            species,
+            velocities: vec![Vector3::zeros(); positions.len()],
+            positions,
+            topology: Topology::default(),
+        })
+    }
+
+    fn write_str_from_system(&self, system: &System) -> String {
+        let labels = species_labels(system);
+        let mut types: Vec<(&str, Float)> = Vec::new();
+        for (label, species) in labels.iter().zip(system.species.iter()) {
+            if !types.iter().any(|(t, _)| *t == label.as_str()) {
+                types.push((label, species.mass()));
+            }
+        }
+        let mut s = String::new();
+        writeln!(s, "! ibrav = 0, nat = {}, ntyp = {}", system.size, types.len()).unwrap();
+        writeln!(s, "ATOMIC_SPECIES").unwrap();
+        for (label, mass) in &types {
+            writeln!(s, "{} {} {}.UPF", label, mass, label).unwrap();
+        }
+        writeln!(s, "CELL_PARAMETERS angstrom").unwrap();
+        let matrix = system.cell.matrix();
+        for col in 0..3 {
+            writeln!(s, "{} {} {}", matrix[(0, col)], matrix[(1, col)], matrix[(2, col)]).unwrap();
+        }
+        writeln!(s, "ATOMIC_POSITIONS angstrom").unwrap();
+        for (label, pos) in labels.iter().zip(system.positions.iter()) {
+            writeln!(s, "{} {} {} {}", label, pos[0], pos[1], pos[2]).unwrap();
+        }
+        s
+    }
+}
@@ -0,0 +1,250 @@
+use std::fmt::Write as _;
+use std::io::Read;
+use std::str::FromStr;
+
+use nalgebra::{Matrix3, Vector3};
+use velvet_core::errors::VelvetError;
+use velvet_core::outputs::trajectory::species_labels;
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+use crate::structures::{element_from_name, StructureFormat};
+
+/// Conversion factor from nanometers to angstroms.
+const NM_TO_ANGSTROM: Float = 10.0;
+
+/// Conversion factor from nanometers/picosecond to angstroms/femtosecond.
+const NM_PS_TO_ANGSTROM_FS: Float = 0.01;
+
+/// GROMACS structure file format.
+///
+/// Positions and velocities are converted from nanometers and picoseconds to Velvet's units.
+/// Consecutive atoms sharing a residue number and name are grouped into a [`Residue`] of the
+/// system's [`Topology`]. The format does not store masses or charges so each atom's species
+/// is constructed from the [`Element`] inferred from its atom name. Two letter symbols are only used
+/// when the atom name matches the residue name, as is the convention for ions, such that
+/// `CA` in an amino acid is carbon while `CA` in a `CA` residue is calcium.
+///
+/// Atoms which do not belong to a residue are written as single atom residues named after
+/// their element.
+///
+/// # Examples
+///
+/// Construct a [`System`](velvet_core::system::System) from GRO data.
+/// ```
+/// use velvet_core::prelude::*;
+/// use velvet_external_data::prelude::*;
+///
+/// let system = Gro.parse_system_from_reader("\
+/// Water and sodium
+///     4
+///     1SOL     OW    1   0.126   1.624   1.679  0.1227 -0.0580  0.0434
+///     1SOL    HW1    2   0.190   1.661   1.747  0.8085  0.3191 -0.7791
+///     1SOL    HW2    3   0.177   1.568   1.613 -0.9045 -2.6469  1.3180
+///     2NA      NA    4   1.200   1.000   1.000  0.0000  0.0000  0.0000
+///    3.00000   3.00000   3.00000
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(system.size, 4);
+/// assert_eq!(system.species[3], Species::from_element(Element::Na));
+/// assert_eq!(system.topology.residues[0].atoms, vec![0, 1, 2]);
+/// ```
+pub struct Gro;
+
+/// Constructs a [`System`] from the GRO file at `filename`.
+pub fn load_gro<T: AsRef<str>>(filename: T) -> Result<System, VelvetError> {
+    Gro.parse_system_from_file(filename)
+}
+
+/// Writes `system` to a GRO file at `filename`.
+pub fn write_gro<T: AsRef<str>>(system: &System, filename: T) -> Result<(), VelvetError> {
+    Gro.write_file_from_system(system, filename)
+}
+
+fn parse<T: FromStr>(token: &str) -> Result<T, VelvetError> {
+    token
+        .trim()
+        .parse()
+        .map_err(|_| VelvetError::parse("GRO", format!("Invalid value `{}`", token)))
+}
+
+// Returns the field of `line` spanning `start..end` or an error if the line is too short.
+fn field(line: &str, start: usize, end: usize) -> Result<&str, VelvetError> {
+    line.get(start..end.min(line.len()))
+        .filter(|field| !field.is_empty())
+        .ok_or_else(|| VelvetError::parse("GRO", format!("Truncated atom record `{}`", line)))
+}
+
+impl StructureFormat for Gro {
+    fn parse_system_from_reader<T: Read>(&self, mut reader: T) -> Result<System, VelvetError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let mut lines = text.lines();
+        let missing = |what: &str| VelvetError::parse("GRO", format!("Missing {}", what));
+
+        // the first line is the title
+        lines.next().ok_or_else(|| missing("title"))?;
+        let size: usize = parse(lines.next().ok_or_else(|| missing("atom count"))?)?;
+
+        let mut species = Vec::with_capacity(size);
+        let mut positions = Vec::with_capacity(size);
+        let mut velocities = Vec::with_capacity(size);
+        let mut residues: Vec<Residue> = Vec::new();
+        let mut previous: Option<(&str, &str)> = None;
+        for i in 0..size {
+            let line = lines
+                .next()
+                .ok_or_else(|| VelvetError::parse("GRO", format!("Expected {} atoms", size)))?;
+            let number = field(line, 0, 5)?;
+            let residue = field(line, 5, 10)?.trim();
+            let atom = field(line, 10, 15)?.trim();
+
+            // the precision of the coordinates is set by the spacing of the decimal points
+            let coordinates = field(line, 20, line.len())?;
+            let invalid = || VelvetError::parse("GRO", format!("Invalid coordinates `{}`", coordinates));
+            let mut points = coordinates.match_indices('.').map(|(index, _)| index);
+            let width = match (points.next(), points.next()) {
+                (Some(first), Some(second)) => second - first,
+                _ => return Err(invalid()),
+            };
+            // velocities follow the positions in fields of the same width
+            let value = |k: usize| -> Result<Option<Float>, VelvetError> {
+                coordinates
+                    .get(k * width..((k + 1) * width).min(coordinates.len()))
+                    .filter(|token| !token.trim().is_empty())
+                    .map(parse)
+                    .transpose()
+            };
+            let position = match (value(0)?, value(1)?, value(2)?) {
+                (Some(x), Some(y), Some(z)) => Vector3::new(x, y, z),
+                _ => return Err(invalid()),
+            };
+            let velocity = match (value(3)?, value(4)?, value(5)?) {
+                (Some(x), Some(y), Some(z)) => Vector3::new(x, y, z) * NM_PS_TO_ANGSTROM_FS,
+                _ => Vector3::zeros(),
+            };
+
+            let element = element_from_name(atom, residue).ok_or_else(|| {
+                VelvetError::parse("GRO", format!("Unable to infer the element of atom `{}`", atom))
+            })?;
+            species.push(Species::from_element(element));
+            positions.push(position * NM_TO_ANGSTROM);
+            velocities.push(velocity);
+
+            // consecutive atoms with the same residue number and name form one residue
+            if previous != Some((number, residue)) {
+                residues.push(Residue {
+                    name: residue.to_string(),
+                    atoms: Vec::new(),
+                });
+                previous = Some((number, residue));
+            }
+            residues.last_mut().unwrap().atoms.push(i);
+        }
+
+        let values: Vec<Float> = lines
+            .next()
+            .ok_or_else(|| missing("box vectors"))?
+            .split_whitespace()
+            .map(parse)
+            .collect::<Result<_, _>>()?;
+        let mut v: [Float; 9] = [0.0; 9];
+        match values.len() {
+            3 | 9 => v[..values.len()].copy_from_slice(&values),
+            n => {
+                let message = format!("Expected 3 or 9 box vector components, found {}", n);
+                return Err(VelvetError::parse("GRO", message));
+            }
+        }
+        // components are ordered v1(x) v2(y) v3(z) v1(y) v1(z) v2(x) v2(z) v3(x) v3(y)
+        let matrix = Matrix3::new(v[0], v[5], v[7], v[3], v[1], v[8], v[4], v[6], v[2]);
+
+        Ok(System {
+            size,
+            cell: Cell::from_matrix(matrix * NM_TO_ANGSTROM),
+            species,
+            positions,
+            velocities,
+            topology: Topology {
+                residues,
+                ..Topology::default()
+            },
+        })
+    }
+
+    fn write_str_from_system(&self, system: &System) -> String {
+        // GROMACS requires the 'a' vector along x and the 'b' vector in the xy plane
+        let cell = Cell::triclinic(
+            system.cell.a(),
+            system.cell.b(),
+            system.cell.c(),
+            system.cell.alpha(),
+            system.cell.beta(),
+            system.cell.gamma(),
+        );
+        let matrix = cell.matrix() / NM_TO_ANGSTROM;
+        let rotation = cell.matrix() * system.cell.inverse_matrix();
+
+        let labels = species_labels(system);
+        let mut membership: Vec<Option<usize>> = vec![None; system.size];
+        for (index, residue) in system.topology.residues.iter().enumerate() {
+            residue.atoms.iter().for_each(|&i| membership[i] = Some(index));
+        }
+
+        let mut s = String::new();
+        writeln!(s, "GRO file written by Velvet").unwrap();
+        writeln!(s, "{:>5}", system.size).unwrap();
+        let mut number = 0;
+        for i in 0..system.size {
+            // a new residue begins whenever membership changes or the atom is unassigned
+            if i == 0 || membership[i].is_none() || membership[i] != membership[i - 1] {
+                number += 1;
+            }
+            let residue = match membership[i] {
+                Some(index) => system.topology.residues[index].name.as_str(),
+                None => labels[i].as_str(),
+            };
+            let pos = cell.cartesian(&system.cell.fractional(&system.positions[i])) / NM_TO_ANGSTROM;
+            let vel = rotation * system.velocities[i] / NM_PS_TO_ANGSTROM_FS;
+            writeln!(
+                s,
+                "{:>5}{:<5.5}{:>5.5}{:>5}{:>8.3}{:>8.3}{:>8.3}{:>8.4}{:>8.4}{:>8.4}",
+                number % 100_000,
+                residue,
+                labels[i],
+                (i + 1) % 100_000,
+                pos[0],
+                pos[1],
+                pos[2],
+                vel[0],
+                vel[1],
+                vel[2]
+            )
+            .unwrap();
+        }
+
+        write!(
+            s,
+            "{:>10.5}{:>10.5}{:>10.5}",
+            matrix[(0, 0)],
+            matrix[(1, 1)],
+            matrix[(2, 2)]
+        )
+        .unwrap();
+        if !cell.is_orthorhombic() {
+            write!(
+                s,
+                "{:>10.5}{:>10.5}{:>10.5}{:>10.5}{:>10.5}{:>10.5}",
+                0.0,
+                0.0,
+                matrix[(0, 1)],
+                0.0,
+                matrix[(0, 2)],
+                matrix[(1, 2)]
+            )
+            .unwrap();
+        }
+        writeln!(s).unwrap();
+        s
+    }
+}
@@ -0,0 +1,368 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Read;
+
+use nalgebra::{Matrix3, Vector3};
+use velvet_core::errors::VelvetError;
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+use crate::structures::StructureFormat;
+
+/// LAMMPS data file format.
+///
+/// Atoms are read in the `full`, `charge`, or `atomic` atom styles and written in the `full`
+/// style. Each atom type becomes a [`Species`] with the charge of its first atom, and the charge
+/// of every atom is kept as a partial charge of the system's [`Topology`]. Bonds,
+/// angles, and dihedrals populate the system's [`Topology`] while their LAMMPS types are
+/// discarded, as are any force field coefficient sections.
+///
+/// # Examples
+///
+/// Construct a [`System`](velvet_core::system::System) from LAMMPS data.
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let system = LammpsData.parse_system_from_reader("\
+///     Water molecule
+///
+///     3 atoms
+///     2 bonds
+///     2 atom types
+///     1 bond types
+///
+///     0.0 10.0 xlo xhi
+///     0.0 10.0 ylo yhi
+///     0.0 10.0 zlo zhi
+///
+///     Masses
+///
+///     1 15.9994
+///     2 1.008
+///
+///     Atoms # full
+///
+///     1 1 1 -0.834 5.0 5.0 5.0
+///     2 1 2 0.417 5.9572 5.0 5.0
+///     3 1 2 0.417 4.7600 5.9266 5.0
+///
+///     Bonds
+///
+///     1 1 1 2
+///     2 1 1 3
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(system.size, 3);
+/// assert_eq!(system.topology.bonds, vec![[0, 1], [0, 2]]);
+/// ```
+pub struct LammpsData;
+
+/// Constructs a [`System`] from the LAMMPS data file at `filename`.
+pub fn load_lammps_data<T: AsRef<str>>(filename: T) -> Result<System, VelvetError> {
+    LammpsData.parse_system_from_file(filename)
+}
+
+/// Writes `system` to a LAMMPS data file at `filename`.
+pub fn write_lammps_data<T: AsRef<str>>(system: &System, filename: T) -> Result<(), VelvetError> {
+    LammpsData.write_file_from_system(system, filename)
+}
+
+// Columns of the `Atoms` section for each supported atom style.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AtomStyle {
+    Atomic,
+    Charge,
+    Full,
+}
+
+impl AtomStyle {
+    fn from_hint(hint: &str) -> Option<AtomStyle> {
+        match hint {
+            "atomic" => Some(AtomStyle::Atomic),
+            "charge" => Some(AtomStyle::Charge),
+            "full" => Some(AtomStyle::Full),
+            _ => None,
+        }
+    }
+
+    // Infers the style from the number of columns with or without trailing image flags.
+    fn from_columns(columns: usize) -> Result<AtomStyle, VelvetError> {
+        match columns {
+            5 | 8 => Ok(AtomStyle::Atomic),
+            6 | 9 => Ok(AtomStyle::Charge),
+            7 | 10 => Ok(AtomStyle::Full),
+            _ => {
+                let message = format!("Unrecognized atom style with {} columns", columns);
+                Err(VelvetError::parse("LAMMPS", message))
+            }
+        }
+    }
+
+    // Returns the column of the atom type, the column of the charge, and the first position column.
+    fn columns(&self) -> (usize, Option<usize>, usize) {
+        match self {
+            AtomStyle::Atomic => (1, None, 2),
+            AtomStyle::Charge => (1, Some(2), 3),
+            AtomStyle::Full => (2, Some(3), 4),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(token: &str) -> Result<T, VelvetError> {
+    token
+        .parse()
+        .map_err(|_| VelvetError::parse("LAMMPS", format!("Invalid value `{}`", token)))
+}
+
+// Parses column `k` of a line or returns an error if the line is too short.
+fn value<T: std::str::FromStr>(tokens: &[&str], k: usize) -> Result<T, VelvetError> {
+    let token = tokens
+        .get(k)
+        .ok_or_else(|| VelvetError::parse("LAMMPS", format!("Truncated line `{}`", tokens.join(" "))))?;
+    parse(token)
+}
+
+impl StructureFormat for LammpsData {
+    fn parse_system_from_reader<T: Read>(&self, mut reader: T) -> Result<System, VelvetError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut size = 0;
+        let (mut lo, mut hi) = (Vector3::zeros(), Vector3::from_element(1.0));
+        let mut tilt: (Float, Float, Float) = (0.0, 0.0, 0.0);
+        let mut masses: HashMap<usize, Float> = HashMap::new();
+        let mut atoms: Vec<(usize, usize, Float, Vector3<Float>)> = Vec::new();
+        let mut velocities: HashMap<usize, Vector3<Float>> = HashMap::new();
+        let mut bonds: Vec<[usize; 2]> = Vec::new();
+        let mut angles: Vec<[usize; 3]> = Vec::new();
+        let mut dihedrals: Vec<[usize; 4]> = Vec::new();
+        let mut style: Option<AtomStyle> = None;
+        let mut section: Option<&str> = None;
+
+        // the first line is always a comment
+        for raw in text.lines().skip(1) {
+            let mut parts = raw.splitn(2, '#');
+            let line = parts.next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+            // section headers are the only lines which begin with a letter
+            if line.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                let name = line.split_whitespace().next().unwrap();
+                if name == "Atoms" {
+                    style = parts.next().and_then(|hint| AtomStyle::from_hint(hint.trim()));
+                }
+                section = Some(name);
+                continue;
+            }
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match section {
+                None => match tokens.as_slice() {
+                    [n, "atoms"] => size = parse(n)?,
+                    [l, h, "xlo", "xhi"] => {
+                        lo[0] = parse(l)?;
+                        hi[0] = parse(h)?;
+                    }
+                    [l, h, "ylo", "yhi"] => {
+                        lo[1] = parse(l)?;
+                        hi[1] = parse(h)?;
+                    }
+                    [l, h, "zlo", "zhi"] => {
+                        lo[2] = parse(l)?;
+                        hi[2] = parse(h)?;
+                    }
+                    [xy, xz, yz, "xy", "xz", "yz"] => tilt = (parse(xy)?, parse(xz)?, parse(yz)?),
+                    // remaining counts are implied by the section contents
+                    _ => {}
+                },
+                Some("Masses") => {
+                    masses.insert(value(&tokens, 0)?, value(&tokens, 1)?);
+                }
+                Some("Atoms") => {
+                    if style.is_none() {
+                        style = Some(AtomStyle::from_columns(tokens.len())?);
+                    }
+                    let (type_col, charge_col, pos_col) = style.unwrap().columns();
+                    let charge = match charge_col {
+                        Some(col) => value(&tokens, col)?,
+                        None => 0.0,
+                    };
+                    let position = Vector3::new(
+                        value(&tokens, pos_col)?,
+                        value(&tokens, pos_col + 1)?,
+                        value(&tokens, pos_col + 2)?,
+                    );
+                    atoms.push((value(&tokens, 0)?, value(&tokens, type_col)?, charge, position - lo));
+                }
+                Some("Velocities") => {
+                    let velocity = Vector3::new(value(&tokens, 1)?, value(&tokens, 2)?, value(&tokens, 3)?);
+                    velocities.insert(value(&tokens, 0)?, velocity);
+                }
+                Some("Bonds") => bonds.push([value(&tokens, 2)?, value(&tokens, 3)?]),
+                Some("Angles") => angles.push([value(&tokens, 2)?, value(&tokens, 3)?, value(&tokens, 4)?]),
+                Some("Dihedrals") => dihedrals.push([
+                    value(&tokens, 2)?,
+                    value(&tokens, 3)?,
+                    value(&tokens, 4)?,
+                    value(&tokens, 5)?,
+                ]),
+                // force field coefficients and impropers have no counterpart in Velvet
+                Some(_) => {}
+            }
+        }
+
+        let lengths = hi - lo;
+        let matrix = Matrix3::new(
+            lengths[0], tilt.0, tilt.1, 0.0, lengths[1], tilt.2, 0.0, 0.0, lengths[2],
+        );
+        let cell = Cell::from_matrix(matrix);
+
+        if atoms.len() != size {
+            let message = format!("Expected {} atoms, found {}", size, atoms.len());
+            return Err(VelvetError::parse("LAMMPS", message));
+        }
+
+        // atoms are ordered by their ID and LAMMPS IDs are translated to indices
+        atoms.sort_by_key(|atom| atom.0);
+        let index: HashMap<usize, usize> = atoms.iter().enumerate().map(|(i, atom)| (atom.0, i)).collect();
+        let translate = |id: &usize| -> Result<usize, VelvetError> {
+            index
+                .get(id)
+                .copied()
+                .ok_or_else(|| VelvetError::parse("LAMMPS", format!("Unknown atom ID {}", id)))
+        };
+
+        let mut species_map: HashMap<usize, Species> = HashMap::new();
+        let species = atoms
+            .iter()
+            .map(|&(_, atom_type, charge, _)| {
+                if let Some(species) = species_map.get(&atom_type) {
+                    return Ok(*species);
+                }
+                let mass = *masses
+                    .get(&atom_type)
+                    .ok_or_else(|| VelvetError::parse("LAMMPS", format!("Missing mass for atom type {}", atom_type)))?;
+                Ok(*species_map.entry(atom_type).or_insert_with(|| Species::new(mass, charge)))
+            })
+            .collect::<Result<_, VelvetError>>()?;
+
+        Ok(System {
+            size,
+            cell,
+            species,
+            positions: atoms.iter().map(|atom| atom.3).collect(),
+            velocities: atoms
+                .iter()
+                .map(|atom| velocities.get(&atom.0).copied().unwrap_or_else(Vector3::zeros))
+                .collect(),
+            topology: Topology {
+                bonds: bonds
+                    .iter()
+                    .map(|b| Ok([translate(&b[0])?, translate(&b[1])?]))
+                    .collect::<Result<_, VelvetError>>()?,
+                angles: angles
+                    .iter()
+                    .map(|a| Ok([translate(&a[0])?, translate(&a[1])?, translate(&a[2])?]))
+                    .collect::<Result<_, VelvetError>>()?,
+                dihedrals: dihedrals
+                    .iter()
+                    .map(|d| Ok([translate(&d[0])?, translate(&d[1])?, translate(&d[2])?, translate(&d[3])?]))
+                    .collect::<Result<_, VelvetError>>()?,
+                charges: match style.and_then(|style| style.columns().1) {
+                    Some(_) => atoms.iter().map(|atom| atom.2).collect(),
+                    None => Vec::new(),
+                },
+                ..Topology::default()
+            },
+        })
+    }
+
+    fn write_str_from_system(&self, system: &System) -> String {
+        // LAMMPS requires the 'a' vector along x and the 'b' vector in the xy plane
+        let cell = Cell::triclinic(
+            system.cell.a(),
+            system.cell.b(),
+            system.cell.c(),
+            system.cell.alpha(),
+            system.cell.beta(),
+            system.cell.gamma(),
+        );
+        let matrix = cell.matrix();
+
+        // atom types are assigned to species in order of appearance
+        let mut types: Vec<Species> = Vec::new();
+        for species in &system.species {
+            if !types.contains(species) {
+                types.push(*species);
+            }
+        }
+        let type_of = |species: &Species| types.iter().position(|s| s == species).unwrap() + 1;
+
+        let topology = &system.topology;
+        let mut s = String::new();
+        writeln!(s, "LAMMPS data file written by Velvet\n").unwrap();
+        writeln!(s, "{} atoms", system.size).unwrap();
+        writeln!(s, "{} bonds", topology.bonds.len()).unwrap();
+        writeln!(s, "{} angles", topology.angles.len()).unwrap();
+        writeln!(s, "{} dihedrals\n", topology.dihedrals.len()).unwrap();
+        writeln!(s, "{} atom types", types.len()).unwrap();
+        writeln!(s, "{} bond types", usize::from(!topology.bonds.is_empty())).unwrap();
+        writeln!(s, "{} angle types", usize::from(!topology.angles.is_empty())).unwrap();
+        writeln!(s, "{} dihedral types\n", usize::from(!topology.dihedrals.is_empty())).unwrap();
+        writeln!(s, "0.0 {} xlo xhi", matrix[(0, 0)]).unwrap();
+        writeln!(s, "0.0 {} ylo yhi", matrix[(1, 1)]).unwrap();
+        writeln!(s, "0.0 {} zlo zhi", matrix[(2, 2)]).unwrap();
+        if !cell.is_orthorhombic() {
+            writeln!(s, "{} {} {} xy xz yz", matrix[(0, 1)], matrix[(0, 2)], matrix[(1, 2)]).unwrap();
+        }
+
+        writeln!(s, "\nMasses\n").unwrap();
+        for (i, species) in types.iter().enumerate() {
+            writeln!(s, "{} {}", i + 1, species.mass()).unwrap();
+        }
+
+        writeln!(s, "\nAtoms # full\n").unwrap();
+        for (i, (species, pos)) in system.species.iter().zip(system.positions.iter()).enumerate() {
+            let pos = cell.cartesian(&system.cell.fractional(pos));
+            writeln!(
+                s,
+                "{} 0 {} {} {} {} {}",
+                i + 1,
+                type_of(species),
+                system.charge(i),
+                pos[0],
+                pos[1],
+                pos[2]
+            )
+            .unwrap();
+        }
+
+        // velocities are rotated into the same frame as the positions
+        let rotation = matrix * system.cell.inverse_matrix();
+        writeln!(s, "\nVelocities\n").unwrap();
+        for (i, vel) in system.velocities.iter().enumerate() {
+            let vel = rotation * vel;
+            writeln!(s, "{} {} {} {}", i + 1, vel[0], vel[1], vel[2]).unwrap();
+        }
+
+        if !topology.bonds.is_empty() {
+            writeln!(s, "\nBonds\n").unwrap();
+            for (i, [a, b]) in topology.bonds.iter().enumerate() {
+                writeln!(s, "{} 1 {} {}", i + 1, a + 1, b + 1).unwrap();
+            }
+        }
+        if !topology.angles.is_empty() {
+            writeln!(s, "\nAngles\n").unwrap();
+            for (i, [a, b, c]) in topology.angles.iter().enumerate() {
+                writeln!(s, "{} 1 {} {} {}", i + 1, a + 1, b + 1, c + 1).unwrap();
+            }
+        }
+        if !topology.dihedrals.is_empty() {
+            writeln!(s, "\nDihedrals\n").unwrap();
+            for (i, [a, b, c, d]) in topology.dihedrals.iter().enumerate() {
+                writeln!(s, "{} 1 {} {} {} {}", i + 1, a + 1, b + 1, c + 1, d + 1).unwrap();
+            }
+        }
+        s
+    }
+}
@@ -1,23 +1,96 @@
+pub mod dftb;
+pub mod espresso;
+pub mod gro;
+pub mod lammps;
+pub mod pdb;
 pub mod poscar;
+pub mod pqr;
+pub mod xyz;
 
 use std::fs::File;
 use std::io::Write;
+use std::str::FromStr;
 
+use nalgebra::Vector3;
+use velvet_core::errors::VelvetError;
+use velvet_core::outputs::compression::create_destination;
+use velvet_core::system::cell::Cell;
+use velvet_core::system::elements::Element;
 use velvet_core::system::System;
 
+use crate::internal::Float;
+
+/// Padding added around the atoms of formats which do not define a unit cell.
+const PADDING: Float = 10.0;
+
 pub trait StructureFormat {
-    fn parse_system_from_file<T: AsRef<str>>(&self, filename: T) -> System {
-        let file = File::open(filename.as_ref()).unwrap();
+    fn parse_system_from_file<T: AsRef<str>>(&self, filename: T) -> Result<System, VelvetError> {
+        let file = File::open(filename.as_ref())?;
         self.parse_system_from_reader(file)
     }
 
-    fn parse_system_from_reader<T: std::io::Read>(&self, reader: T) -> System;
+    /// Constructs a [`System`] from formatted data, or returns an error if the data is malformed
+    /// or its connectivity references atoms which do not exist.
+    fn parse_system_from_reader<T: std::io::Read>(&self, reader: T) -> Result<System, VelvetError>;
 
-    fn write_file_from_system<T: AsRef<str>>(&self, system: &System, filename: T) {
+    /// Writes `system` to `filename`, compressed if its extension is `.gz` or `.zst`.
+    fn write_file_from_system<T: AsRef<str>>(&self, system: &System, filename: T) -> Result<(), VelvetError> {
         let s = self.write_str_from_system(system);
-        let mut file = File::create(filename.as_ref()).unwrap();
-        file.write_all(s.as_bytes()).unwrap()
+        let mut file = create_destination(filename.as_ref())?;
+        file.write_all(s.as_bytes())?;
+        Ok(())
     }
 
-    fn write_str_from_system(&self, system: &System) -> &str;
+    fn write_str_from_system(&self, system: &System) -> String;
+}
+
+// Infers the element of an atom from its name and the name of its residue.
+//
+// Two letter symbols are only used when the atom name matches the residue name, as is the
+// convention for ions, such that `CA` in an amino acid is carbon while `CA` in a `CA` residue
+// is calcium.
+pub(crate) fn element_from_name(atom: &str, residue: &str) -> Option<Element> {
+    let letters: String = atom.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    let symbol = if letters.len() == 2 && letters.eq_ignore_ascii_case(residue) {
+        letters[..1].to_ascii_uppercase() + &letters[1..].to_ascii_lowercase()
+    } else {
+        letters.get(..1).unwrap_or_default().to_ascii_uppercase()
+    };
+    Element::from_str(&symbol).ok()
+}
+
+// Returns the residue name and number of each atom for PDB style records.
+//
+// Residues are numbered from 1 in order of appearance and a new residue begins whenever the
+// membership of consecutive atoms changes. Atoms outside of any residue form single atom
+// residues named after their label.
+pub(crate) fn atom_residues<'a>(system: &'a System, labels: &'a [String]) -> Vec<(&'a str, usize)> {
+    let mut membership: Vec<Option<usize>> = vec![None; system.size];
+    for (index, residue) in system.topology.residues.iter().enumerate() {
+        residue.atoms.iter().for_each(|&i| membership[i] = Some(index));
+    }
+    let mut number = 0;
+    (0..system.size)
+        .map(|i| {
+            if i == 0 || membership[i].is_none() || membership[i] != membership[i - 1] {
+                number += 1;
+            }
+            let name = match membership[i] {
+                Some(index) => system.topology.residues[index].name.as_str(),
+                None => labels[i].as_str(),
+            };
+            (name, number)
+        })
+        .collect()
+}
+
+// Returns an orthorhombic cell which spans the positions with padding on each side.
+pub(crate) fn padded_cell(positions: &[Vector3<Float>]) -> Cell {
+    let (min, max) = positions.iter().fold(
+        (Vector3::from_element(Float::MAX), Vector3::from_element(Float::MIN)),
+        |(min, max), pos| (min.inf(pos), max.sup(pos)),
+    );
+    let lengths = if positions.is_empty() { Vector3::zeros() } else { max - min };
+    let lengths = lengths.add_scalar(2.0 * PADDING);
+    Cell::triclinic(lengths[0], lengths[1], lengths[2], 90.0, 90.0, 90.0)
 }
@@ -0,0 +1,319 @@
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::io::Read;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+use velvet_core::errors::VelvetError;
+use velvet_core::outputs::trajectory::species_labels;
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+use crate::structures::{atom_residues, padded_cell, StructureFormat};
+
+/// Tolerance added to the sum of covalent radii when inferring bonds.
+const BOND_TOLERANCE: Float = 0.45;
+
+/// Protein data bank file format.
+///
+/// `ATOM` and `HETATM` records of the first model are read along with the `CRYST1` unit cell
+/// and `CONECT` bonds. Species are constructed from the element column, or from the first two
+/// columns of the atom name when the element column is empty. Consecutive atoms sharing a
+/// chain, residue number, and residue name are grouped into a [`Residue`] of the system's
+/// [`Topology`].
+///
+/// Atoms with a formal charge column, such as `1-` or `2+`, are given that charge with
+/// [`assign_charges`] while other atoms keep the default charge of their element. Partial
+/// charges are read from the [`Pqr`](crate::structures::pqr::Pqr) variant of the format.
+///
+/// Files without a `CRYST1` record, or with the placeholder 1 angstrom cubic cell, are placed
+/// in an orthorhombic cell which spans the atoms with 10 angstroms of padding on each side.
+///
+/// When `infer_bonds` is set and the file has no `CONECT` records, atoms closer than the sum
+/// of their covalent radii plus 0.45 angstroms are bonded.
+///
+/// Systems are written with a `CRYST1` record, an `ATOM` record for each atom, and `CONECT`
+/// records for the bonds of the topology. The cell is rotated so that its `a` vector lies along
+/// x and its `b` vector in the xy plane, as the format requires. Atoms which do not belong to a
+/// residue are written as single atom residues named after their element, and formal charges
+/// are written for atoms whose charge is a nonzero integer other than the default charge of
+/// their element. Custom species have no element and are labeled `X1`, `X2`, etc., which can
+/// not be read back.
+///
+/// # Examples
+///
+/// Construct a [`System`](velvet_core::system::System) from PDB data.
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let system = Pdb { infer_bonds: true }.parse_system_from_reader("\
+/// HETATM    1  O   HOH A   1       0.000   0.000   0.000  1.00  0.00           O
+/// HETATM    2  H1  HOH A   1       0.957   0.000   0.000  1.00  0.00           H
+/// HETATM    3  H2  HOH A   1      -0.240   0.927   0.000  1.00  0.00           H
+/// END
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(system.size, 3);
+/// assert_eq!(system.topology.bonds, vec![[0, 1], [0, 2]]);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pdb {
+    /// Infer bonds from covalent radii if the file has no `CONECT` records.
+    pub infer_bonds: bool,
+}
+
+/// Constructs a [`System`] from the PDB file at `filename`.
+///
+/// Bonds are inferred from covalent radii if `infer_bonds` is set and the file has no `CONECT` records.
+pub fn load_pdb<T: AsRef<str>>(filename: T, infer_bonds: bool) -> Result<System, VelvetError> {
+    Pdb { infer_bonds }.parse_system_from_file(filename)
+}
+
+fn parse<T: FromStr>(token: &str) -> Result<T, VelvetError> {
+    token
+        .trim()
+        .parse()
+        .map_err(|_| VelvetError::parse("PDB", format!("Invalid value `{}`", token)))
+}
+
+// Returns the 1-indexed, inclusive columns `start..=end` of `line` or an empty string.
+fn columns(line: &str, start: usize, end: usize) -> &str {
+    line.get(start - 1..end.min(line.len())).unwrap_or("")
+}
+
+// Reads the element column or falls back to the element encoded in the atom name.
+fn element_of(line: &str) -> Result<Element, VelvetError> {
+    let column = columns(line, 77, 78).trim();
+    let symbol = if !column.is_empty() {
+        column.to_string()
+    } else {
+        // names of single letter elements begin in the second column
+        let name = columns(line, 13, 16);
+        match name.chars().next() {
+            Some(c) if c.is_ascii_alphabetic() => name.get(..2).unwrap_or(name).to_string(),
+            _ => name.get(1..2).unwrap_or("").to_string(),
+        }
+    };
+    let symbol = match symbol.len() {
+        2 => symbol[..1].to_ascii_uppercase() + &symbol[1..].to_ascii_lowercase(),
+        _ => symbol.to_ascii_uppercase(),
+    };
+    Element::from_str(&symbol)
+        .map_err(|_| VelvetError::parse("PDB", format!("Unable to determine the element of record `{}`", line)))
+}
+
+// Reads the formal charge column, written as `2+` or `1-`, if present.
+fn formal_charge(line: &str) -> Result<Option<Float>, VelvetError> {
+    let column = columns(line, 79, 80).trim();
+    if column.is_empty() {
+        return Ok(None);
+    }
+    let (magnitude, sign) = match column.find(['+', '-']) {
+        Some(0) => (&column[1..], &column[..1]),
+        Some(k) => (&column[..k], &column[k..]),
+        None => return Err(VelvetError::parse("PDB", format!("Invalid formal charge `{}`", column))),
+    };
+    let magnitude: Float = if magnitude.is_empty() { 1.0 } else { parse(magnitude)? };
+    Ok(Some(if sign == "-" { -magnitude } else { magnitude }))
+}
+
+impl StructureFormat for Pdb {
+    fn parse_system_from_reader<T: Read>(&self, mut reader: T) -> Result<System, VelvetError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut cell: Option<Cell> = None;
+        let mut serials: HashMap<usize, usize> = HashMap::new();
+        let mut elements: Vec<Element> = Vec::new();
+        let mut positions: Vec<Vector3<Float>> = Vec::new();
+        let mut charges: Vec<Option<Float>> = Vec::new();
+        let mut residues: Vec<Residue> = Vec::new();
+        let mut previous: Option<&str> = None;
+        let mut connections: Vec<(usize, usize)> = Vec::new();
+        let mut in_model = true;
+
+        for line in text.lines() {
+            match columns(line, 1, 6).trim_end() {
+                "CRYST1" => {
+                    let lengths: [Float; 3] = [
+                        parse(columns(line, 7, 15))?,
+                        parse(columns(line, 16, 24))?,
+                        parse(columns(line, 25, 33))?,
+                    ];
+                    // cryo-EM and NMR structures use a 1 angstrom cube as a placeholder
+                    if lengths.iter().any(|&length| length != 1.0) {
+                        cell = Some(Cell::triclinic(
+                            lengths[0],
+                            lengths[1],
+                            lengths[2],
+                            parse(columns(line, 34, 40))?,
+                            parse(columns(line, 41, 47))?,
+                            parse(columns(line, 48, 54))?,
+                        ));
+                    }
+                }
+                "ATOM" | "HETATM" if in_model => {
+                    let index = positions.len();
+                    let serial = columns(line, 7, 11).trim();
+                    if !serial.is_empty() {
+                        serials.insert(parse(serial)?, index);
+                    }
+                    elements.push(element_of(line)?);
+                    charges.push(formal_charge(line)?);
+                    positions.push(Vector3::new(
+                        parse(columns(line, 31, 38))?,
+                        parse(columns(line, 39, 46))?,
+                        parse(columns(line, 47, 54))?,
+                    ));
+                    // residue name, chain, residue number, and insertion code
+                    let residue = columns(line, 18, 27);
+                    if previous != Some(residue) {
+                        residues.push(Residue {
+                            name: columns(line, 18, 20).trim().to_string(),
+                            atoms: Vec::new(),
+                        });
+                        previous = Some(residue);
+                    }
+                    residues.last_mut().unwrap().atoms.push(index);
+                }
+                "ENDMDL" => in_model = false,
+                "CONECT" => {
+                    let atom: usize = parse(columns(line, 7, 11))?;
+                    for start in [12, 17, 22, 27].iter() {
+                        let bonded = columns(line, *start, start + 4).trim();
+                        if !bonded.is_empty() {
+                            connections.push((atom, parse(bonded)?));
+                        }
+                    }
+                }
+                "END" => break,
+                _ => {}
+            }
+        }
+
+        let size = positions.len();
+        let cell = cell.unwrap_or_else(|| padded_cell(&positions));
+
+        // bonds are stored once with the lower index first
+        let mut bonds: BTreeSet<[usize; 2]> = BTreeSet::new();
+        let index_of = |serial: &usize| -> Result<usize, VelvetError> {
+            serials
+                .get(serial)
+                .copied()
+                .ok_or_else(|| VelvetError::parse("PDB", format!("Unknown atom serial {}", serial)))
+        };
+        for (a, b) in connections.iter() {
+            let (i, j) = (index_of(a)?, index_of(b)?);
+            bonds.insert([i.min(j), i.max(j)]);
+        }
+        if self.infer_bonds && connections.is_empty() {
+            for i in 0..size {
+                for j in (i + 1)..size {
+                    let limit = elements[i].covalent_radius()
+                        + elements[j].covalent_radius()
+                        + BOND_TOLERANCE;
+                    if cell.distance(&positions[i], &positions[j]) < limit {
+                        bonds.insert([i, j]);
+                    }
+                }
+            }
+        }
+
+        let mut system = System {
+            size,
+            cell,
+            species: elements.into_iter().map(Species::from_element).collect(),
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology {
+                bonds: bonds.into_iter().collect(),
+                residues,
+                ..Topology::default()
+            },
+        };
+        if charges.iter().any(Option::is_some) {
+            let charges: Vec<Float> = charges
+                .iter()
+                .zip(system.species.iter())
+                .map(|(charge, species)| charge.unwrap_or_else(|| species.charge()))
+                .collect();
+            assign_charges(&mut system, &charges);
+        }
+        Ok(system)
+    }
+
+    fn write_str_from_system(&self, system: &System) -> String {
+        // PDB requires the 'a' vector along x and the 'b' vector in the xy plane
+        let cell = Cell::triclinic(
+            system.cell.a(),
+            system.cell.b(),
+            system.cell.c(),
+            system.cell.alpha(),
+            system.cell.beta(),
+            system.cell.gamma(),
+        );
+
+        let labels = species_labels(system);
+        let residues = atom_residues(system, &labels);
+
+        let mut s = String::new();
+        writeln!(
+            s,
+            "CRYST1{:>9.3}{:>9.3}{:>9.3}{:>7.2}{:>7.2}{:>7.2} P 1           1",
+            cell.a(),
+            cell.b(),
+            cell.c(),
+            cell.alpha(),
+            cell.beta(),
+            cell.gamma()
+        )
+        .unwrap();
+        for (i, &(residue, number)) in residues.iter().enumerate() {
+            // names of single letter elements begin in the second column
+            let name = match labels[i].len() {
+                1 => format!(" {}", labels[i]),
+                _ => labels[i].clone(),
+            };
+            let element = system.species[i].element();
+            let symbol = element.map(|element| element.symbol().to_ascii_uppercase()).unwrap_or_default();
+            let charge = system.charge(i);
+            let default = element.map(|element| element.charge()).unwrap_or(0.0);
+            let formal = if charge != default && charge != 0.0 && charge.fract() == 0.0 {
+                format!("{}{}", charge.abs(), if charge < 0.0 { '-' } else { '+' })
+            } else {
+                String::new()
+            };
+            let pos = cell.cartesian(&system.cell.fractional(&system.positions[i]));
+            writeln!(
+                s,
+                "ATOM  {:>5} {:<4.4} {:>3.3} A{:>4}    {:>8.3}{:>8.3}{:>8.3}  1.00  0.00          {:>2}{:<2}",
+                (i + 1) % 100_000,
+                name,
+                residue,
+                number % 10_000,
+                pos[0],
+                pos[1],
+                pos[2],
+                symbol,
+                formal
+            )
+            .unwrap();
+        }
+
+        // each atom lists its bonded neighbors four at a time
+        let mut neighbors: Vec<Vec<usize>> = vec![Vec::new(); system.size];
+        for &[i, j] in system.topology.bonds.iter() {
+            neighbors[i].push(j);
+            neighbors[j].push(i);
+        }
+        for (i, bonded) in neighbors.iter().enumerate() {
+            for chunk in bonded.chunks(4) {
+                write!(s, "CONECT{:>5}", i + 1).unwrap();
+                chunk.iter().for_each(|j| write!(s, "{:>5}", j + 1).unwrap());
+                writeln!(s).unwrap();
+            }
+        }
+        writeln!(s, "END").unwrap();
+        s
+    }
+}
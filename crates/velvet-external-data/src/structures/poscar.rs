@@ -25,20 +25,20 @@ use crate::structures::StructureFormat;
 ///     Direct
 ///     0.00 0.00 0.00
 ///     0.25 0.25 0.25
-/// ".as_bytes());
+/// ".as_bytes()).unwrap();
 ///
 /// assert_eq!(system.size, 2);
 /// ```
 pub struct Poscar;
 
 impl StructureFormat for Poscar {
-    fn write_str_from_system(&self, system: &System) -> &str {
+    fn write_str_from_system(&self, _: &System) -> String {
         unimplemented!()
     }
 
-    fn parse_system_from_reader<T: std::io::Read>(&self, reader: T) -> System {
+    fn parse_system_from_reader<T: std::io::Read>(&self, reader: T) -> Result<System, VelvetError> {
         let buf = std::io::BufReader::new(reader);
-        let poscar = vasp_poscar::Poscar::from_reader(buf).unwrap();
+        let poscar = vasp_poscar::Poscar::from_reader(buf).map_err(|err| VelvetError::parse("POSCAR", err.to_string()))?;
 
         // Alias for the system size.
         let size = poscar.num_sites();
@@ -59,13 +59,14 @@ impl StructureFormat for Poscar {
         let cell = Cell::from_matrix(matrix);
 
         let species: Vec<Species> = match poscar.site_symbols() {
-            Some(symbols) => symbols.fold(Vec::new(), |mut accumulator, symbol| {
-                let element = Element::from_str(symbol).unwrap();
-                let sp = Species::from_element(element);
-                accumulator.push(sp);
-                accumulator
-            }),
-            None => panic!("Missing chemical species."),
+            Some(symbols) => symbols
+                .map(|symbol| {
+                    Element::from_str(symbol)
+                        .map(Species::from_element)
+                        .map_err(|_| VelvetError::parse("POSCAR", format!("Unknown element `{}`", symbol)))
+                })
+                .collect::<Result<_, _>>()?,
+            None => return Err(VelvetError::parse("POSCAR", "Missing chemical species")),
         };
 
         // Set system positions.
@@ -83,12 +84,13 @@ impl StructureFormat for Poscar {
             None => vec![Vector3::zeros(); positions.len()],
         };
 
-        System {
+        Ok(System {
             size,
             cell,
             species,
             positions,
             velocities,
-        }
+            topology: Topology::default(),
+        })
     }
 }
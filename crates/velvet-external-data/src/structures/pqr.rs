@@ -0,0 +1,143 @@
+use std::fmt::Write as _;
+use std::io::Read;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+use velvet_core::errors::VelvetError;
+use velvet_core::outputs::trajectory::species_labels;
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+use crate::structures::{atom_residues, element_from_name, padded_cell, StructureFormat};
+
+/// PQR file format written by tools such as PDB2PQR and APBS.
+///
+/// PQR files are PDB files in which the occupancy and temperature factor columns are replaced
+/// by the partial charge and radius of each atom, and the fields are separated by whitespace
+/// rather than fixed columns. `ATOM` and `HETATM` records are read and any radii are discarded.
+/// Each atom's species is constructed from the [`Element`] inferred from its atom name, using
+/// two letter symbols only when the atom name matches the residue name as is the convention
+/// for ions, and its partial charge is set with [`assign_charges`].
+///
+/// Consecutive atoms sharing a chain, residue number, and residue name are grouped into a
+/// [`Residue`] of the system's [`Topology`]. The format does not define a unit cell so atoms
+/// are placed in an orthorhombic cell which spans them with 10 angstroms of padding on each side.
+///
+/// Systems are written with an `ATOM` record for each atom holding its charge and a radius of
+/// zero, since Velvet does not track atomic radii. Atoms which do not belong to a residue are
+/// written as single atom residues named after their element, which lets ions with two letter
+/// symbols be read back. Custom species have no element and are labeled `X1`, `X2`, etc.,
+/// which can not be read back.
+///
+/// # Examples
+///
+/// Construct a [`System`](velvet_core::system::System) from PQR data.
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let system = Pqr.parse_system_from_reader("\
+/// ATOM      1  OW  SOL     1       0.000   0.000   0.000 -0.8340 1.7683
+/// ATOM      2  HW1 SOL     1       0.957   0.000   0.000  0.4170 0.0000
+/// ATOM      3  HW2 SOL     1      -0.240   0.927   0.000  0.4170 0.0000
+/// END
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(system.size, 3);
+/// assert_eq!(system.charge(0), -0.834);
+/// ```
+pub struct Pqr;
+
+/// Constructs a [`System`] from the PQR file at `filename`.
+pub fn load_pqr<T: AsRef<str>>(filename: T) -> Result<System, VelvetError> {
+    Pqr.parse_system_from_file(filename)
+}
+
+fn parse<T: FromStr>(token: &str) -> Result<T, VelvetError> {
+    token
+        .parse()
+        .map_err(|_| VelvetError::parse("PQR", format!("Invalid value `{}`", token)))
+}
+
+impl StructureFormat for Pqr {
+    fn parse_system_from_reader<T: Read>(&self, mut reader: T) -> Result<System, VelvetError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+
+        let mut species: Vec<Species> = Vec::new();
+        let mut positions: Vec<Vector3<Float>> = Vec::new();
+        let mut charges: Vec<Float> = Vec::new();
+        let mut residues: Vec<Residue> = Vec::new();
+        let mut previous: Option<Vec<&str>> = None;
+
+        for line in text.lines() {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            match tokens.first() {
+                Some(&"ATOM") | Some(&"HETATM") => {}
+                Some(&"END") | Some(&"ENDMDL") => break,
+                _ => continue,
+            }
+            // record, serial, name, residue name, optional chain, residue number, x, y, z, charge, and radius
+            if tokens.len() < 10 {
+                return Err(VelvetError::parse("PQR", format!("Truncated atom record `{}`", line)));
+            }
+            let (name, residue_name) = (tokens[2], tokens[3]);
+            let values = &tokens[tokens.len() - 5..];
+            let element = element_from_name(name, residue_name).ok_or_else(|| {
+                VelvetError::parse("PQR", format!("Unable to infer the element of atom `{}`", name))
+            })?;
+            species.push(Species::from_element(element));
+            positions.push(Vector3::new(parse(values[0])?, parse(values[1])?, parse(values[2])?));
+            charges.push(parse(values[3])?);
+
+            // residue name, chain, and residue number
+            let residue = tokens[3..tokens.len() - 5].to_vec();
+            if previous.as_ref() != Some(&residue) {
+                residues.push(Residue {
+                    name: residue_name.to_string(),
+                    atoms: Vec::new(),
+                });
+                previous = Some(residue);
+            }
+            residues.last_mut().unwrap().atoms.push(positions.len() - 1);
+        }
+
+        let size = positions.len();
+        let mut system = System {
+            size,
+            cell: padded_cell(&positions),
+            species,
+            positions,
+            velocities: vec![Vector3::zeros(); size],
+            topology: Topology {
+                residues,
+                ..Topology::default()
+            },
+        };
+        assign_charges(&mut system, &charges);
+        Ok(system)
+    }
+
+    fn write_str_from_system(&self, system: &System) -> String {
+        let labels = species_labels(system);
+        let mut s = String::new();
+        for (i, &(residue, number)) in atom_residues(system, &labels).iter().enumerate() {
+            let pos = system.positions[i];
+            writeln!(
+                s,
+                "ATOM  {:>5} {:<4} {:>3} {:>5}    {:>8.3} {:>8.3} {:>8.3} {:>7.4} {:>6.4}",
+                i + 1,
+                labels[i],
+                residue,
+                number,
+                pos[0],
+                pos[1],
+                pos[2],
+                system.charge(i),
+                0.0
+            )
+            .unwrap();
+        }
+        writeln!(s, "END").unwrap();
+        s
+    }
+}
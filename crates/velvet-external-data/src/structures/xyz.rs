@@ -0,0 +1,287 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::Read;
+use std::str::FromStr;
+
+use nalgebra::Vector3;
+use velvet_core::errors::VelvetError;
+use velvet_core::outputs::trajectory::species_labels;
+use velvet_core::prelude::*;
+
+use crate::internal::Float;
+use crate::structures::{padded_cell, StructureFormat};
+
+/// Extended XYZ file format.
+///
+/// The first frame is read with the columns named by the `Properties` key of its comment line,
+/// which defaults to `species:S:1:pos:R:3` for plain XYZ files. Velocities are read from a
+/// `velo` or `velocities` column, and partial charges from a `charge`, `charges`, or
+/// `initial_charges` column are given to each atom with [`assign_charges`]. Other columns are
+/// ignored. Species labels are chemical symbols, or any other label when a `mass` or `masses`
+/// column defines the mass of each custom species.
+///
+/// Frames without a `Lattice` key are placed in an orthorhombic cell which spans the atoms with
+/// 10 angstroms of padding on each side.
+///
+/// Systems are written with their lattice, positions, velocities, masses, and charges. Custom
+/// species are labeled `X1`, `X2`, etc. in order of appearance.
+///
+/// # Examples
+///
+/// Construct a [`System`](velvet_core::system::System) from extended XYZ data.
+/// ```
+/// use velvet_external_data::prelude::*;
+///
+/// let system = ExtendedXyz.parse_system_from_reader("\
+/// 3
+/// Lattice=\"10.0 0.0 0.0 0.0 10.0 0.0 0.0 0.0 10.0\" Properties=species:S:1:pos:R:3:charge:R:1
+/// O 0.000 0.000 0.000 -0.834
+/// H 0.957 0.000 0.000 0.417
+/// H -0.240 0.927 0.000 0.417
+/// ".as_bytes()).unwrap();
+///
+/// assert_eq!(system.size, 3);
+/// assert_eq!(system.charge(1), 0.417);
+/// ```
+pub struct ExtendedXyz;
+
+/// Constructs a [`System`] from the first frame of the extended XYZ file at `filename`.
+pub fn load_xyz<T: AsRef<str>>(filename: T) -> Result<System, VelvetError> {
+    ExtendedXyz.parse_system_from_file(filename)
+}
+
+/// Writes `system` to an extended XYZ file at `filename`.
+pub fn write_xyz<T: AsRef<str>>(system: &System, filename: T) -> Result<(), VelvetError> {
+    ExtendedXyz.write_file_from_system(system, filename)
+}
+
+/// Reads every frame of the extended XYZ file at `filename` as references for a
+/// [`FitProblem`](velvet_core::fitting::FitProblem).
+///
+/// The reference energy of each frame is read from the `energy` key of its comment line and the
+/// reference forces from a `forces` or `force` column, as written by ASE, and are `None` when a
+/// frame lacks them. Species with the same mass and charge are shared between frames so a single
+/// model applies to all of them.
+pub fn load_references<T: AsRef<str>>(filename: T) -> Result<Vec<Reference>, VelvetError> {
+    parse_references(File::open(filename.as_ref())?)
+}
+
+/// Reads every frame of extended XYZ data as references, see [`load_references`].
+pub fn parse_references<R: Read>(mut reader: R) -> Result<Vec<Reference>, VelvetError> {
+    let mut text = String::new();
+    reader.read_to_string(&mut text)?;
+    let lines: Vec<&str> = text.lines().collect();
+    let mut references = Vec::new();
+    let mut known: Vec<Species> = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        if lines[start].trim().is_empty() {
+            start += 1;
+            continue;
+        }
+        let size: usize = parse(lines[start].trim())?;
+        let end = (start + size + 2).min(lines.len());
+        let frame = &lines[start..end];
+        let mut system = ExtendedXyz.parse_system_from_reader(frame.join("\n").as_bytes())?;
+        for species in system.species.iter_mut() {
+            match known
+                .iter()
+                .find(|k| k.mass() == species.mass() && k.charge() == species.charge())
+            {
+                Some(k) => *species = *k,
+                None => known.push(*species),
+            }
+        }
+
+        let pairs = comment_pairs(frame.get(1).copied().unwrap_or(""));
+        let energy = pairs.get("energy").map(|value| parse(value)).transpose()?;
+        let columns = property_columns(pairs.get("properties").map_or("species:S:1:pos:R:3", String::as_str))?;
+        let forces = match ["forces", "force"].iter().find_map(|name| columns.get(*name)) {
+            Some(&(col, _)) => Some(
+                frame[2..]
+                    .iter()
+                    .map(|line| {
+                        let tokens: Vec<&str> = line.split_whitespace().collect();
+                        Ok(Vector3::new(parse(tokens[col])?, parse(tokens[col + 1])?, parse(tokens[col + 2])?))
+                    })
+                    .collect::<Result<Vec<_>, VelvetError>>()?,
+            ),
+            None => None,
+        };
+        references.push(Reference { system, energy, forces });
+        start = end;
+    }
+    Ok(references)
+}
+
+fn parse<T: FromStr>(token: &str) -> Result<T, VelvetError> {
+    token
+        .parse()
+        .map_err(|_| VelvetError::parse("XYZ", format!("Invalid value `{}`", token)))
+}
+
+// Splits the comment line into `key=value` pairs with lowercase keys and unquoted values.
+fn comment_pairs(comment: &str) -> HashMap<String, String> {
+    let mut pairs = HashMap::new();
+    let mut rest = comment.trim_start();
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| c == '=' || c.is_whitespace()).unwrap_or(rest.len());
+        let key = rest[..end].to_ascii_lowercase();
+        rest = &rest[end..];
+        let value = if let Some(stripped) = rest.strip_prefix('=') {
+            let (value, remaining) = match stripped.strip_prefix('"') {
+                Some(quoted) => {
+                    let end = quoted.find('"').unwrap_or(quoted.len());
+                    (&quoted[..end], quoted.get(end + 1..).unwrap_or(""))
+                }
+                None => {
+                    let end = stripped.find(char::is_whitespace).unwrap_or(stripped.len());
+                    (&stripped[..end], &stripped[end..])
+                }
+            };
+            rest = remaining;
+            value.to_string()
+        } else {
+            // keys without a value are flags
+            "T".to_string()
+        };
+        pairs.insert(key, value);
+        rest = rest.trim_start();
+    }
+    pairs
+}
+
+// Returns the first column and number of columns of each entry of the `Properties` key.
+fn property_columns(properties: &str) -> Result<HashMap<String, (usize, usize)>, VelvetError> {
+    let fields: Vec<&str> = properties.split(':').collect();
+    if !fields.len().is_multiple_of(3) {
+        return Err(VelvetError::parse("XYZ", format!("Invalid properties `{}`", properties)));
+    }
+    let mut columns = HashMap::new();
+    let mut start = 0;
+    for entry in fields.chunks(3) {
+        let count: usize = parse(entry[2])?;
+        columns.insert(entry[0].to_ascii_lowercase(), (start, count));
+        start += count;
+    }
+    Ok(columns)
+}
+
+impl StructureFormat for ExtendedXyz {
+    fn parse_system_from_reader<T: Read>(&self, mut reader: T) -> Result<System, VelvetError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let mut lines = text.lines();
+        let missing = |what: &str| VelvetError::parse("XYZ", format!("Missing {}", what));
+
+        let size: usize = parse(lines.next().ok_or_else(|| missing("atom count"))?.trim())?;
+        let pairs = comment_pairs(lines.next().unwrap_or(""));
+        let columns = property_columns(pairs.get("properties").map_or("species:S:1:pos:R:3", String::as_str))?;
+        let find = |names: &[&str]| names.iter().find_map(|name| columns.get(*name).copied());
+        let (species_col, _) = find(&["species"]).ok_or_else(|| missing("species column"))?;
+        let (pos_col, _) = find(&["pos", "positions"]).ok_or_else(|| missing("position column"))?;
+        let velocity_col = find(&["velo", "velocities"]).map(|column| column.0);
+        let charge_col = find(&["charge", "charges", "initial_charges"]).map(|column| column.0);
+        let mass_col = find(&["mass", "masses"]).map(|column| column.0);
+
+        let mut species = Vec::with_capacity(size);
+        let mut positions = Vec::with_capacity(size);
+        let mut velocities = Vec::with_capacity(size);
+        let mut charges = Vec::with_capacity(size);
+        let mut custom: HashMap<String, Species> = HashMap::new();
+        for _ in 0..size {
+            let line = lines.next().ok_or_else(|| missing("atoms"))?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            if tokens.len() < columns.values().map(|(start, count)| start + count).max().unwrap_or(0) {
+                return Err(VelvetError::parse("XYZ", format!("Truncated atom record `{}`", line)));
+            }
+            let vector = |col: usize| -> Result<Vector3<Float>, VelvetError> {
+                Ok(Vector3::new(parse(tokens[col])?, parse(tokens[col + 1])?, parse(tokens[col + 2])?))
+            };
+
+            let label = tokens[species_col];
+            let sp = match Element::from_str(label) {
+                Ok(element) => Species::from_element(element),
+                Err(_) => match custom.get(label) {
+                    Some(sp) => *sp,
+                    None => {
+                        let col = mass_col.ok_or_else(|| {
+                            VelvetError::parse("XYZ", format!("Unknown species `{}` without a mass", label))
+                        })?;
+                        *custom.entry(label.to_string()).or_insert(Species::new(parse(tokens[col])?, 0.0))
+                    }
+                },
+            };
+            charges.push(match charge_col {
+                Some(col) => parse(tokens[col])?,
+                None => sp.charge(),
+            });
+            species.push(sp);
+            positions.push(vector(pos_col)?);
+            velocities.push(match velocity_col {
+                Some(col) => vector(col)?,
+                None => Vector3::zeros(),
+            });
+        }
+
+        let cell = match pairs.get("lattice") {
+            Some(lattice) => {
+                let values: Vec<Float> = lattice.split_whitespace().map(parse).collect::<Result<_, _>>()?;
+                if values.len() != 9 {
+                    return Err(VelvetError::parse("XYZ", format!("Invalid lattice `{}`", lattice)));
+                }
+                let vector = |k: usize| Vector3::new(values[3 * k], values[3 * k + 1], values[3 * k + 2]);
+                Cell::from_vectors(vector(0), vector(1), vector(2))
+            }
+            None => padded_cell(&positions),
+        };
+
+        let mut system = System {
+            size,
+            cell,
+            species,
+            positions,
+            velocities,
+            topology: Topology::default(),
+        };
+        if charge_col.is_some() {
+            assign_charges(&mut system, &charges);
+        }
+        Ok(system)
+    }
+
+    fn write_str_from_system(&self, system: &System) -> String {
+        let matrix = system.cell.matrix();
+        let lattice: Vec<String> = (0..3)
+            .flat_map(|col| (0..3).map(move |row| matrix[(row, col)].to_string()))
+            .collect();
+        let mut s = String::new();
+        writeln!(s, "{}", system.size).unwrap();
+        writeln!(
+            s,
+            "Lattice=\"{}\" Properties=species:S:1:pos:R:3:velo:R:3:mass:R:1:charge:R:1 pbc=\"T T T\"",
+            lattice.join(" ")
+        )
+        .unwrap();
+        let labels = species_labels(system);
+        for (i, label) in labels.iter().enumerate() {
+            let (pos, vel, species) = (system.positions[i], system.velocities[i], system.species[i]);
+            writeln!(
+                s,
+                "{} {} {} {} {} {} {} {} {}",
+                label,
+                pos[0],
+                pos[1],
+                pos[2],
+                vel[0],
+                vel[1],
+                vel[2],
+                species.mass(),
+                system.charge(i)
+            )
+            .unwrap();
+        }
+        s
+    }
+}
@@ -0,0 +1,199 @@
+use nalgebra::Vector3;
+
+use velvet_core::prelude::*;
+use velvet_external_data::bundle::*;
+
+// Returns a path in the temporary directory which is unique to the test `name`.
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("velvet-bundle-{}-{}", name, std::process::id()))
+}
+
+fn argon_bundle(name: &str) -> RunBundle {
+    let argon = Species::from_element(Element::Ar);
+    let system = System {
+        size: 2,
+        cell: Cell::cubic(20.0),
+        species: vec![argon, argon],
+        positions: vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new(9.0, 5.0, 5.0)],
+        velocities: vec![Vector3::new(0.001, 0.0, 0.0), Vector3::zeros()],
+        topology: Topology {
+            bonds: vec![[0, 1]],
+            residues: vec![Residue {
+                name: "AR2".to_string(),
+                atoms: vec![0, 1],
+            }],
+            groups: vec![Group {
+                name: "fixed".to_string(),
+                atoms: vec![1],
+            }],
+            charges: vec![0.1, -0.1],
+            ..Topology::default()
+        },
+    };
+    RunBundle {
+        system,
+        potentials: PotentialsSpec {
+            coulomb: None,
+            pairs: vec![PairSpec {
+                potential: PairKind::LennardJones(LennardJones::new(0.238, 3.4)),
+                species: (argon, argon),
+                cutoff: 8.5,
+                thickness: 1.0,
+            }],
+            update_frequency: 2,
+        },
+        propagator: PropagatorSpec::MolecularDynamics {
+            integrator: IntegratorSpec::VelocityVerlet { timestep: 1.0 },
+            thermostat: ThermostatSpec::Berendsen {
+                target: 300.0,
+                tau: 2.0,
+            },
+            barostat: Some(BarostatSpec::Berendsen {
+                target: 1.0,
+                compressibility: 4.5e-5,
+                tau: 100.0,
+            }),
+        },
+        outputs: vec![OutputSpec {
+            destination: Some(temp_path(name).with_extension("txt").to_string_lossy().into_owned()),
+            interval: 5,
+            properties: vec![
+                PropertyKind::TotalEnergy,
+                PropertyKind::Pressure,
+                PropertyKind::ExtendedXyzTrajectory,
+            ],
+        }],
+        steps: 10,
+    }
+}
+
+#[test]
+fn round_trip() {
+    let bundle = argon_bundle("round-trip");
+    let mut buffer = Vec::new();
+    bundle.write(&mut buffer).unwrap();
+    let restored = RunBundle::read(buffer.as_slice()).unwrap();
+
+    assert_eq!(restored.steps, bundle.steps);
+    assert_eq!(restored.system.size, bundle.system.size);
+    assert_eq!(restored.system.species, bundle.system.species);
+    assert_eq!(restored.system.positions, bundle.system.positions);
+    assert_eq!(restored.system.velocities, bundle.system.velocities);
+    assert_eq!(restored.system.cell.matrix(), bundle.system.cell.matrix());
+    assert_eq!(restored.system.topology, bundle.system.topology);
+    assert_eq!(restored.potentials.update_frequency, 2);
+    assert_eq!(restored.potentials.pairs.len(), 1);
+    assert_eq!(restored.outputs[0].destination, bundle.outputs[0].destination);
+    assert_eq!(restored.outputs[0].properties, bundle.outputs[0].properties);
+
+    // the restored bundle describes an equivalent simulation
+    let potentials = restored.potentials.build();
+    let mut expected = bundle.potentials.build();
+    expected.setup(&bundle.system);
+    let mut actual = potentials;
+    actual.setup(&restored.system);
+    assert_eq!(
+        PotentialEnergy.calculate(&restored.system, &actual),
+        PotentialEnergy.calculate(&bundle.system, &expected)
+    );
+    let mut simulation = restored.simulation().unwrap();
+    simulation.run(restored.steps).unwrap();
+    assert!(simulation.system().positions[0][0] > 5.0);
+    std::fs::remove_file(bundle.outputs[0].destination.as_ref().unwrap()).unwrap();
+}
+
+#[test]
+fn reject_invalid() {
+    assert!(RunBundle::read(&b"POSCAR"[..]).is_err());
+
+    // truncated bundles are reported rather than partially read
+    let mut buffer = Vec::new();
+    argon_bundle("reject-invalid").write(&mut buffer).unwrap();
+    buffer.truncate(buffer.len() / 2);
+    assert!(RunBundle::read(buffer.as_slice()).is_err());
+
+    // corrupt lengths fail at the end of the data instead of allocating
+    let mut buffer = Vec::new();
+    argon_bundle("reject-invalid").write(&mut buffer).unwrap();
+    buffer[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+    assert!(RunBundle::read(buffer.as_slice()).is_err());
+}
+
+#[test]
+fn reject_invalid_potentials() {
+    let mut bundle = argon_bundle("reject-invalid-potentials");
+    bundle.potentials.coulomb = Some(CoulombSpec {
+        potential: CoulombKind::ParticleMeshEwald(ParticleMeshEwald::new(0.25, 1.0, 4)),
+        cutoff: 8.5,
+        thickness: 1.0,
+        table: None,
+    });
+    let mut buffer = Vec::new();
+    bundle.write(&mut buffer).unwrap();
+    assert!(RunBundle::read(buffer.as_slice()).is_ok());
+
+    // the interpolation order follows the tag, splitting parameter, and mesh spacing
+    let mut pattern = vec![3u8];
+    pattern.extend_from_slice(&0.25f64.to_le_bytes());
+    pattern.extend_from_slice(&1.0f64.to_le_bytes());
+    let start = buffer
+        .windows(pattern.len())
+        .position(|window| window == pattern.as_slice())
+        .unwrap()
+        + pattern.len();
+    buffer[start..start + 8].copy_from_slice(&1u64.to_le_bytes());
+    assert!(RunBundle::read(buffer.as_slice()).is_err());
+}
+
+#[test]
+fn read_earlier_versions() {
+    let mut bundle = argon_bundle("read-earlier-versions");
+    bundle.system.topology = Topology::default();
+    bundle.outputs[0].properties = vec![PropertyKind::TotalEnergy];
+    let mut buffer = Vec::new();
+    bundle.write(&mut buffer).unwrap();
+
+    // newer versions than the reader knows about are rejected
+    let mut future = buffer.clone();
+    future[4..8].copy_from_slice(&12u32.to_le_bytes());
+    assert!(RunBundle::read(future.as_slice()).is_err());
+
+    // version 2 predates the six empty topology lengths which follow the velocities
+    let species = 4 + 4 + 8 + 8 + 32;
+    let atoms = species + 72 + 8 + 2 * 56;
+    let mut earlier = buffer[..atoms].to_vec();
+    earlier.extend_from_slice(&buffer[atoms + 48..]);
+    earlier[4..8].copy_from_slice(&2u32.to_le_bytes());
+    let restored = RunBundle::read(earlier.as_slice()).unwrap();
+    assert_eq!(restored.system.positions, bundle.system.positions);
+    assert_eq!(restored.system.topology, Topology::default());
+    assert_eq!(restored.potentials.pairs.len(), 1);
+    assert_eq!(restored.outputs[0].properties, bundle.outputs[0].properties);
+
+    // trajectory outputs were introduced in version 5
+    let tag = earlier.len() - 1;
+    earlier[tag] = PropertyKind::ExtendedXyzTrajectory as u8;
+    assert!(RunBundle::read(earlier.as_slice()).is_err());
+}
+
+#[test]
+fn resume_from_checkpoint() {
+    let bundle = argon_bundle("resume");
+    let path = temp_path("resume").with_extension("chk");
+    let config = bundle.configuration_builder().unwrap().checkpoint(&path, 4).build();
+    let mut simulation = Simulation::new(
+        bundle.system.clone(),
+        bundle.potentials.build(),
+        bundle.propagator.build().unwrap(),
+        config,
+    );
+    simulation.run(6).unwrap();
+
+    // the resumed run continues from the last checkpoint
+    let mut resumed = bundle.resume(&path, bundle.configuration().unwrap()).unwrap();
+    assert_eq!(resumed.step(), 4);
+    resumed.run(bundle.steps - resumed.step()).unwrap();
+    assert_eq!(resumed.step(), bundle.steps);
+    std::fs::remove_file(path).unwrap();
+    std::fs::remove_file(bundle.outputs[0].destination.as_ref().unwrap()).unwrap();
+}
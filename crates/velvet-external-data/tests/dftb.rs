@@ -0,0 +1,47 @@
+use approx::*;
+use velvet_core::prelude::*;
+use velvet_external_data::prelude::*;
+use velvet_test_utils as test_utils;
+
+static WATER: &str = "\
+# water molecule in the gas phase
+3 C
+O H
+1 1  0.000  0.000  0.000
+2 2  0.957  0.000  0.000
+3 2 -0.240  0.927  0.000
+";
+
+#[test]
+fn import_cluster() {
+    let system = DftbGen.parse_system_from_reader(WATER.as_bytes()).unwrap();
+    assert_eq!(system.size, 3);
+    assert_eq!(system.species[0], Species::from_element(Element::O));
+    assert_eq!(system.species[1], system.species[2]);
+    assert_relative_eq!(system.positions[2][1], 0.927, epsilon = 1e-6);
+
+    // clusters are padded by 10 angstroms on each side
+    assert!(system.cell.is_orthorhombic());
+    assert_relative_eq!(system.cell.a(), 21.197, epsilon = 1e-4);
+}
+
+#[test]
+fn invalid_species_index() {
+    let text = WATER.replace("3 2 -0.240", "3 3 -0.240");
+    assert!(DftbGen.parse_system_from_reader(text.as_bytes()).is_err());
+}
+
+#[test]
+fn round_trip() {
+    let system = test_utils::magnesium_oxide_system();
+    let text = DftbGen.write_str_from_system(&system);
+    assert!(text.starts_with(&format!("{} S\n", system.size)));
+    let restored = DftbGen.parse_system_from_reader(text.as_bytes()).unwrap();
+
+    assert_eq!(restored.size, system.size);
+    assert_eq!(restored.species, system.species);
+    assert_relative_eq!(restored.cell.matrix(), system.cell.matrix(), epsilon = 1e-4);
+    for (a, b) in restored.positions.iter().zip(system.positions.iter()) {
+        assert_relative_eq!(a, b, epsilon = 1e-4);
+    }
+}
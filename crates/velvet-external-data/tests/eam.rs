@@ -0,0 +1,92 @@
+use std::fmt::Write;
+
+use approx::*;
+use nalgebra::Vector3;
+use velvet_core::prelude::*;
+use velvet_external_data::prelude::*;
+
+// Energy of one electronvolt in kcal/mol.
+const EV: f64 = 23.060548;
+
+fn samples<F: Fn(f64) -> f64>(f: F, count: usize, step: f64) -> String {
+    (0..count).fold(String::new(), |mut s, i| {
+        writeln!(s, "{:.8e}", f(i as f64 * step)).unwrap();
+        s
+    })
+}
+
+// Copper and nickel alloy with linear embedding energies and exponential densities.
+fn alloy() -> String {
+    let mut setfl = String::from("analytic alloy\nfor testing\nonly\n2 Cu Ni\n501 0.01 501 0.01 5.0\n");
+    setfl += "29 63.546 3.615 fcc\n";
+    setfl += &samples(|rho| -rho, 501, 0.01);
+    setfl += &samples(|r| (-r).exp(), 501, 0.01);
+    setfl += "28 58.693 3.524 fcc\n";
+    setfl += &samples(|rho| -2.0 * rho, 501, 0.01);
+    setfl += &samples(|r| 2.0 * (-r).exp(), 501, 0.01);
+    // pair energies as r * phi(r) for Cu-Cu, Ni-Cu, and Ni-Ni
+    setfl += &samples(|r| r * (-2.0 * r).exp(), 501, 0.01);
+    setfl += &samples(|r| 3.0 * r * (-2.0 * r).exp(), 501, 0.01);
+    setfl += &samples(|r| 2.0 * r * (-2.0 * r).exp(), 501, 0.01);
+    setfl
+}
+
+fn dimer(a: Species, b: Species, r: f64) -> System {
+    System {
+        size: 2,
+        cell: Cell::cubic(30.0),
+        species: vec![a, b],
+        positions: vec![Vector3::new(5.0, 5.0, 5.0), Vector3::new((5.0 + r) as _, 5.0, 5.0)],
+        velocities: vec![Vector3::zeros(); 2],
+        topology: Topology::default(),
+    }
+}
+
+// reference energies are compared in double precision regardless of `Float`
+#[allow(clippy::unnecessary_cast)]
+fn energy(potential: EmbeddedAtom, system: &System) -> f64 {
+    let mut potentials = PotentialsBuilder::new().embedded_atom(potential, 1.0).build();
+    potentials.setup(system);
+    PotentialEnergy.calculate(system, &potentials) as f64
+}
+
+#[test]
+fn import_setfl() {
+    let potential = read_setfl(alloy().as_bytes()).unwrap();
+    let copper = Species::from_element(Element::Cu);
+    let nickel = Species::from_element(Element::Ni);
+    assert_eq!(potential.species(), &[copper, nickel]);
+    assert_relative_eq!(potential.cutoff() as f64, 5.0);
+
+    // each atom is embedded in the density of the other
+    let r: f64 = 2.5;
+    let expected = -(2.0 * (-r).exp()) - 2.0 * (-r).exp() + 3.0 * (-2.0 * r).exp();
+    let system = dimer(copper, nickel, r);
+    assert_relative_eq!(energy(potential, &system), EV * expected, epsilon = 1e-3);
+}
+
+#[test]
+fn import_funcfl() {
+    let mut funcfl = String::from("analytic copper\n29 63.546 3.615 fcc\n501 0.01 501 0.01 5.0\n");
+    funcfl += &samples(|rho| -rho, 501, 0.01);
+    funcfl += &samples(|r| (-r).exp(), 501, 0.01);
+    funcfl += &samples(|r| (-r).exp(), 501, 0.01);
+    let potential = read_funcfl(funcfl.as_bytes()).unwrap();
+    let copper = Species::from_element(Element::Cu);
+    assert_eq!(potential.species(), &[copper]);
+
+    // the pair energy follows from the effective charge
+    let r: f64 = 2.5;
+    let expected = -2.0 * (-r).exp() + 27.2 * 0.529 * (-2.0 * r).exp() / r;
+    let system = dimer(copper, copper, r);
+    assert_relative_eq!(energy(potential, &system), EV * expected, epsilon = 1e-3);
+}
+
+#[test]
+fn reject_invalid() {
+    assert!(read_setfl("comment\ncomment\ncomment\n1 Xx\n".as_bytes()).is_err());
+    // truncated tables
+    let truncated: String = alloy().lines().take(600).map(|line| format!("{}\n", line)).collect();
+    assert!(read_setfl(truncated.as_bytes()).is_err());
+    assert!(read_funcfl("comment\n29 63.546 3.615 fcc\n501 0.01".as_bytes()).is_err());
+}
@@ -0,0 +1,65 @@
+use approx::*;
+use velvet_core::prelude::*;
+use velvet_external_data::prelude::*;
+use velvet_test_utils as test_utils;
+
+static IRON_OXIDE: &str = "\
+&CONTROL
+  calculation = 'scf' ! single point
+/
+&SYSTEM
+  ibrav = 0, nat = 3, ntyp = 3
+  ecutwfc = 40.0
+/
+ATOMIC_SPECIES
+Fe1 55.845 Fe.UPF
+O   15.999 O.UPF
+Dm  2.0    H.UPF
+CELL_PARAMETERS {bohr}
+  8.0 0.0 0.0
+  0.0 8.0 0.0
+  0.0 0.0 8.0
+ATOMIC_POSITIONS {angstrom}
+Fe1 0.0 0.0 0.0
+O   2.0 0.0 0.0
+Dm  0.0 2.0 0.0
+K_POINTS automatic
+  4 4 4 0 0 0
+";
+
+#[test]
+fn import_iron_oxide() {
+    let system = PwInput.parse_system_from_reader(IRON_OXIDE.as_bytes()).unwrap();
+    assert_eq!(system.size, 3);
+    assert_relative_eq!(system.cell.a(), 8.0 * 0.529177, epsilon = 1e-4);
+    assert_relative_eq!(system.positions[1][0], 2.0);
+
+    // labels begin with a chemical symbol unless the species defines its own mass
+    assert_eq!(system.species[0], Species::from_element(Element::Fe));
+    assert_eq!(system.species[1], Species::from_element(Element::O));
+    assert_relative_eq!(system.species[2].mass(), 2.0);
+}
+
+#[test]
+fn unsupported_units() {
+    let text = IRON_OXIDE.replace("ATOMIC_POSITIONS {angstrom}", "ATOMIC_POSITIONS {alat}");
+    assert!(PwInput.parse_system_from_reader(text.as_bytes()).is_err());
+    let text = IRON_OXIDE.replace("CELL_PARAMETERS {bohr}", "CELL_PARAMETERS {alat}");
+    assert!(PwInput.parse_system_from_reader(text.as_bytes()).is_err());
+}
+
+#[test]
+fn round_trip() {
+    let system = test_utils::magnesium_oxide_system();
+    let text = PwInput.write_str_from_system(&system);
+    assert!(text.contains(&format!("nat = {}, ntyp = 2", system.size)));
+    assert!(text.contains("\nO 15.999 O.UPF\n"));
+    let restored = PwInput.parse_system_from_reader(text.as_bytes()).unwrap();
+
+    assert_eq!(restored.size, system.size);
+    assert_eq!(restored.species, system.species);
+    assert_relative_eq!(restored.cell.matrix(), system.cell.matrix(), epsilon = 1e-4);
+    for (a, b) in restored.positions.iter().zip(system.positions.iter()) {
+        assert_relative_eq!(a, b, epsilon = 1e-4);
+    }
+}
@@ -0,0 +1,59 @@
+use approx::*;
+use velvet_core::prelude::*;
+use velvet_external_data::prelude::*;
+
+static PEPTIDE: &str = "\
+Glycine fragment with a calcium ion in a triclinic box
+    6
+    1GLY      N    1   0.100   0.200   0.300  0.1000 -0.2000  0.3000
+    1GLY     CA    2   0.245   0.200   0.300  0.0000  0.0000  0.0000
+    1GLY      C    3   0.300   0.340   0.300  0.0000  0.0000  0.0000
+    1GLY      O    4   0.420   0.360   0.300  0.0000  0.0000  0.0000
+    2CA      CA    5   1.500   1.500   1.500  0.0000  0.0000  0.0000
+    3CA      CA    6   2.000   1.500   1.500  0.0000  0.0000  0.0000
+   3.00000   3.00000   3.00000   0.00000   0.00000   1.00000   0.00000   0.50000  -0.50000
+";
+
+#[test]
+fn import_peptide() {
+    let system = Gro.parse_system_from_reader(PEPTIDE.as_bytes()).unwrap();
+    assert_eq!(system.size, 6);
+
+    // atom names map to elements with ions identified by their residue
+    assert_eq!(system.species[1], Species::from_element(Element::C));
+    assert_eq!(system.species[3], Species::from_element(Element::O));
+    assert_eq!(system.species[4], Species::from_element(Element::Ca));
+
+    // units are converted to angstroms and angstroms/femtosecond
+    assert_relative_eq!(system.positions[0][1], 2.0, epsilon = 1e-5);
+    assert_relative_eq!(system.velocities[0][2], 0.003, epsilon = 1e-7);
+
+    let matrix = system.cell.matrix();
+    assert_relative_eq!(matrix[(0, 1)], 10.0, epsilon = 1e-5);
+    assert_relative_eq!(matrix[(0, 2)], 5.0, epsilon = 1e-5);
+    assert_relative_eq!(matrix[(1, 2)], -5.0, epsilon = 1e-5);
+
+    let residues = &system.topology.residues;
+    assert_eq!(residues.len(), 3);
+    assert_eq!(residues[0].name, "GLY");
+    assert_eq!(residues[0].atoms, vec![0, 1, 2, 3]);
+    assert_eq!(residues[2].atoms, vec![5]);
+}
+
+#[test]
+fn round_trip() {
+    let system = Gro.parse_system_from_reader(PEPTIDE.as_bytes()).unwrap();
+    let text = Gro.write_str_from_system(&system);
+    let restored = Gro.parse_system_from_reader(text.as_bytes()).unwrap();
+
+    assert_eq!(restored.size, system.size);
+    assert_eq!(restored.species, system.species);
+    assert_eq!(restored.topology, system.topology);
+    assert_relative_eq!(restored.cell.matrix(), system.cell.matrix(), epsilon = 1e-4);
+    for (a, b) in restored.positions.iter().zip(system.positions.iter()) {
+        assert_relative_eq!(a, b, epsilon = 1e-4);
+    }
+    for (a, b) in restored.velocities.iter().zip(system.velocities.iter()) {
+        assert_relative_eq!(a, b, epsilon = 1e-6);
+    }
+}
@@ -0,0 +1,84 @@
+use std::path::Path;
+
+use velvet_external_data::bundle::*;
+use velvet_test_utils as test_utils;
+
+const ARGON_RUN: &str = r#"
+steps = 10 # short run
+
+[system]
+file = "Ar.poscar"
+
+[potentials]
+update_frequency = 2
+
+[[potentials.pair]]
+species = ["Ar", "Ar"]
+potential = "lennard_jones"
+epsilon = 0.238
+sigma = 3.4
+cutoff = 8.5
+thickness = 1.0
+
+[integrator]
+type = "velocity_verlet"
+timestep = 1.0
+
+[thermostat]
+type = "bussi"
+target = 300.0
+tau = 100.0
+
+[[outputs]]
+interval = 5
+properties = ["temperature", "potential_energy"]
+"#;
+
+fn resources() -> String {
+    test_utils::resources_path("")
+}
+
+#[test]
+fn argon_run() {
+    let bundle = RunBundle::from_toml(ARGON_RUN, Path::new(&resources())).unwrap();
+    assert_eq!(bundle.steps, 10);
+    assert_eq!(bundle.potentials.update_frequency, 2);
+    assert_eq!(bundle.potentials.pairs.len(), 1);
+    assert_eq!(bundle.outputs[0].destination, None);
+    assert_eq!(
+        bundle.outputs[0].properties,
+        vec![PropertyKind::Temperature, PropertyKind::PotentialEnergy]
+    );
+    match bundle.propagator {
+        PropagatorSpec::MolecularDynamics {
+            integrator: IntegratorSpec::VelocityVerlet { timestep },
+            thermostat: ThermostatSpec::Bussi { target, .. },
+            barostat: None,
+        } => {
+            assert_eq!(timestep, 1.0);
+            assert_eq!(target, 300.0);
+        }
+        other => panic!("unexpected propagator {:?}", other),
+    }
+
+    let mut simulation = bundle.simulation().unwrap();
+    simulation.run(bundle.steps).unwrap();
+}
+
+#[test]
+fn reject_invalid() {
+    let directory = resources();
+    let directory = Path::new(&directory);
+    // misspelled parameters are reported rather than replaced by defaults
+    let misspelled = ARGON_RUN.replace("epsilon", "epsilom");
+    assert!(RunBundle::from_toml(&misspelled, directory).is_err());
+    let unknown = ARGON_RUN.replace("bussi", "bussy");
+    assert!(RunBundle::from_toml(&unknown, directory).is_err());
+    let missing = ARGON_RUN.replace("[integrator]", "[integrators]");
+    assert!(RunBundle::from_toml(&missing, directory).is_err());
+    // malformed documents are reported by the parser
+    let duplicate = ARGON_RUN.replace("update_frequency = 2", "update_frequency = 2\nupdate_frequency = 3");
+    assert!(RunBundle::from_toml(&duplicate, directory).is_err());
+    let unterminated = ARGON_RUN.replace("\"Ar.poscar\"", "\"Ar.poscar");
+    assert!(RunBundle::from_toml(&unterminated, directory).is_err());
+}
@@ -0,0 +1,110 @@
+use approx::*;
+use velvet_external_data::prelude::*;
+
+static BUTANE: &str = "\
+Butane united atom chain in a triclinic cell
+
+4 atoms
+3 bonds
+2 angles
+1 dihedrals
+
+2 atom types
+1 bond types
+1 angle types
+1 dihedral types
+
+-5.0 5.0 xlo xhi
+-6.0 6.0 ylo yhi
+-7.0 7.0 zlo zhi
+1.0 0.5 -0.5 xy xz yz
+
+Masses
+
+1 15.035 # CH3
+2 14.027 # CH2
+
+Pair Coeffs # lj/cut
+
+1 0.195 3.75
+2 0.091 3.95
+
+Atoms # charge
+
+4 1 0.0 1.9 0.0 0.0 0 0 0
+1 1 0.0 -1.9 0.0 0.0 0 0 0
+2 2 0.1 -0.6 0.8 0.0 0 0 0
+3 2 -0.1 0.6 0.0 0.0 0 0 0
+
+Velocities
+
+1 0.001 0.0 0.0
+3 0.0 -0.002 0.0
+
+Bonds
+
+1 1 1 2
+2 1 2 3
+3 1 3 4
+
+Angles
+
+1 1 1 2 3
+2 1 2 3 4
+
+Dihedrals
+
+1 1 1 2 3 4
+";
+
+#[test]
+fn import_butane() {
+    let system = LammpsData.parse_system_from_reader(BUTANE.as_bytes()).unwrap();
+    assert_eq!(system.size, 4);
+    assert!(!system.cell.is_orthorhombic());
+    assert_relative_eq!(system.cell.volume(), 10.0 * 12.0 * 14.0, epsilon = 1e-2);
+
+    // atoms are sorted by ID and shifted to the cell origin
+    assert_relative_eq!(system.positions[0][0], 3.1, epsilon = 1e-5);
+    assert_relative_eq!(system.positions[3][1], 6.0, epsilon = 1e-5);
+    assert_relative_eq!(system.velocities[0][0], 0.001);
+    assert_eq!(system.velocities[1], nalgebra::Vector3::zeros());
+
+    // atoms of a type share a species and keep their own charges
+    assert_eq!(system.species[0], system.species[3]);
+    assert_eq!(system.species[1], system.species[2]);
+    assert_relative_eq!(system.species[0].mass(), 15.035);
+    assert_relative_eq!(system.charge(2), -0.1);
+
+    assert_eq!(system.topology.bonds, vec![[0, 1], [1, 2], [2, 3]]);
+    assert_eq!(system.topology.angles, vec![[0, 1, 2], [1, 2, 3]]);
+    assert_eq!(system.topology.dihedrals, vec![[0, 1, 2, 3]]);
+}
+
+#[test]
+fn round_trip() {
+    let system = LammpsData.parse_system_from_reader(BUTANE.as_bytes()).unwrap();
+    let text = LammpsData.write_str_from_system(&system);
+    let restored = LammpsData.parse_system_from_reader(text.as_bytes()).unwrap();
+
+    assert_eq!(restored.size, system.size);
+    assert_eq!(restored.topology, system.topology);
+    assert_relative_eq!(
+        (restored.cell.matrix() - system.cell.matrix()).norm(),
+        0.0,
+        epsilon = 1e-4
+    );
+    for i in 0..system.size {
+        assert_relative_eq!((restored.positions[i] - system.positions[i]).norm(), 0.0, epsilon = 1e-4);
+        assert_relative_eq!((restored.velocities[i] - system.velocities[i]).norm(), 0.0, epsilon = 1e-6);
+        assert_relative_eq!(restored.species[i].mass(), system.species[i].mass());
+        assert_relative_eq!(restored.charge(i), system.charge(i));
+    }
+}
+
+#[test]
+fn unknown_atom_id() {
+    let data = BUTANE.replace("3 1 3 4\n", "3 1 3 9\n");
+    let err = LammpsData.parse_system_from_reader(data.as_bytes()).unwrap_err();
+    assert_eq!(err.to_string(), "Unknown atom ID 9 in LAMMPS data");
+}
@@ -0,0 +1,90 @@
+use approx::*;
+use velvet_core::prelude::*;
+use velvet_external_data::prelude::*;
+
+static ETHANOL: &str = "\
+HEADER    ETHANOL WITH A SODIUM ION
+CRYST1   20.000   21.000   22.000  90.00  90.00 120.00 P 1           1
+HETATM    1  C1  EOH A   1       0.000   0.000   0.000  1.00  0.00           C
+HETATM    2  C2  EOH A   1       1.520   0.000   0.000  1.00  0.00           C
+HETATM    3  O   EOH A   1       2.000   1.350   0.000  1.00  0.00           O
+HETATM    4  HO  EOH A   1       2.950   1.350   0.000  1.00  0.00           H
+HETATM    5 NA    NA A   2       8.000   8.000   8.000  1.00  0.00          NA
+CONECT    1    2
+CONECT    2    1    3
+CONECT    3    2    4
+CONECT    4    3
+END
+";
+
+#[test]
+fn import_ethanol() {
+    let system = Pdb::default().parse_system_from_reader(ETHANOL.as_bytes()).unwrap();
+    assert_eq!(system.size, 5);
+    assert_eq!(system.species[2], Species::from_element(Element::O));
+    assert_eq!(system.species[4], Species::from_element(Element::Na));
+    assert_relative_eq!(system.positions[2][1], 1.35, epsilon = 1e-5);
+
+    assert_relative_eq!(system.cell.b(), 21.0, epsilon = 1e-4);
+    assert_relative_eq!(system.cell.gamma(), 120.0, epsilon = 1e-3);
+
+    // duplicate CONECT entries collapse into single bonds
+    assert_eq!(system.topology.bonds, vec![[0, 1], [1, 2], [2, 3]]);
+    let residues = &system.topology.residues;
+    assert_eq!(residues.len(), 2);
+    assert_eq!(residues[0].name, "EOH");
+    assert_eq!(residues[1].atoms, vec![4]);
+}
+
+#[test]
+fn infer_bonds() {
+    // strip the cell, element columns, and connectivity
+    let stripped: String = ETHANOL
+        .lines()
+        .filter(|line| line.starts_with("HETATM"))
+        .map(|line| format!("{}\n", &line[..66]))
+        .collect();
+    let system = Pdb { infer_bonds: true }.parse_system_from_reader(stripped.as_bytes()).unwrap();
+    assert_eq!(system.topology.bonds, vec![[0, 1], [1, 2], [2, 3]]);
+    // atom names which begin in the first column hold two letter symbols
+    assert_eq!(system.species[4], Species::from_element(Element::Na));
+    // the padded cell spans every atom
+    assert_relative_eq!(system.cell.a(), 28.0, epsilon = 1e-4);
+    assert!(system.cell.is_orthorhombic());
+
+    let unbonded = Pdb::default().parse_system_from_reader(stripped.as_bytes()).unwrap();
+    assert!(unbonded.topology.bonds.is_empty());
+}
+
+#[test]
+fn formal_charges() {
+    // deprotonated oxygen is charged while other atoms keep the default charges of their elements
+    let charged: String = ETHANOL
+        .lines()
+        .map(|line| match line.starts_with("HETATM    3") {
+            true => format!("{}1-\n", line),
+            false => format!("{}\n", line),
+        })
+        .collect();
+    let system = Pdb::default().parse_system_from_reader(charged.as_bytes()).unwrap();
+    assert_eq!(system.species[2], Species::from_element(Element::O));
+    assert_relative_eq!(system.charge(2), -1.0);
+    assert_relative_eq!(system.charge(4), Element::Na.charge());
+}
+
+#[test]
+fn round_trip() {
+    let charged = ETHANOL.replace("           O\n", "           O1-\n");
+    let system = Pdb::default().parse_system_from_reader(charged.as_bytes()).unwrap();
+    let text = Pdb::default().write_str_from_system(&system);
+    let restored = Pdb::default().parse_system_from_reader(text.as_bytes()).unwrap();
+
+    assert_eq!(restored.size, system.size);
+    assert_eq!(restored.species, system.species);
+    assert_relative_eq!(restored.charge(2), -1.0);
+    assert_eq!(restored.topology, system.topology);
+    assert_relative_eq!(restored.cell.matrix(), system.cell.matrix(), epsilon = 1e-3);
+    for (a, b) in restored.positions.iter().zip(system.positions.iter()) {
+        assert_relative_eq!(a, b, epsilon = 1e-3);
+    }
+}
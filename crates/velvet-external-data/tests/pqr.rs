@@ -0,0 +1,51 @@
+use approx::*;
+use velvet_core::prelude::*;
+use velvet_external_data::prelude::*;
+use velvet_test_utils::Float;
+
+static SALT_WATER: &str = "\
+REMARK   1 PQR file with a chain identifier on the ion
+ATOM      1  OW  SOL     1       1.000   1.000   1.000 -0.8340 1.7683
+ATOM      2  HW1 SOL     1       1.957   1.000   1.000  0.4170 0.0000
+ATOM      3  HW2 SOL     1       0.760   1.927   1.000  0.4170 0.0000
+ATOM      4  OW  SOL     2       4.000   1.000   1.000 -0.8340 1.7683
+ATOM      5  HW1 SOL     2       4.957   1.000   1.000  0.4170 0.0000
+ATOM      6  HW2 SOL     2       3.760   1.927   1.000  0.4170 0.0000
+HETATM    7  NA   NA A   3       8.000   8.000   8.000  1.0000 1.8680
+END
+";
+
+#[test]
+fn import_salt_water() {
+    let system = Pqr.parse_system_from_reader(SALT_WATER.as_bytes()).unwrap();
+    assert_eq!(system.size, 7);
+
+    // species are keyed by element and the partial charges belong to the atoms
+    assert_eq!(system.species[0], Species::from_element(Element::O));
+    assert_eq!(system.species[6], Species::from_element(Element::Na));
+    assert_relative_eq!(system.charge(0), -0.834);
+    assert_relative_eq!(system.charge(5), 0.417);
+    let total: Float = system.charges().iter().sum();
+    assert_relative_eq!(total, 1.0, epsilon = 1e-5);
+
+    let residues = &system.topology.residues;
+    assert_eq!(residues.len(), 3);
+    assert_eq!(residues[1].atoms, vec![3, 4, 5]);
+    assert_eq!(residues[2].name, "NA");
+    assert_relative_eq!(system.positions[3][0], 4.0, epsilon = 1e-5);
+    assert_relative_eq!(system.cell.a(), 27.24, epsilon = 1e-4);
+}
+
+#[test]
+fn round_trip() {
+    let system = Pqr.parse_system_from_reader(SALT_WATER.as_bytes()).unwrap();
+    let text = Pqr.write_str_from_system(&system);
+    let restored = Pqr.parse_system_from_reader(text.as_bytes()).unwrap();
+    assert_eq!(restored.size, system.size);
+    assert_eq!(restored.species, system.species);
+    assert_eq!(restored.topology.residues, system.topology.residues);
+    for i in 0..system.size {
+        assert_relative_eq!(restored.charge(i), system.charge(i), epsilon = 1e-4);
+        assert_relative_eq!(restored.positions[i], system.positions[i], epsilon = 1e-3);
+    }
+}
@@ -0,0 +1,97 @@
+use approx::*;
+use velvet_core::prelude::*;
+use velvet_external_data::prelude::*;
+use velvet_test_utils::Float;
+
+static METHANOL: &str = "\
+6
+Lattice=\"12.0 0.0 0.0 1.0 12.0 0.0 0.0 0.0 12.0\" Properties=species:S:1:pos:R:3:initial_charges:R:1:forces:R:3 energy=-1.5 pbc=\"T T T\"
+C  0.000  0.000  0.000  0.145 0.0 0.0 0.0
+O  1.430  0.000  0.000 -0.683 0.0 0.0 0.0
+H -0.360  1.030  0.000  0.040 0.0 0.0 0.0
+H -0.360 -0.510  0.890  0.040 0.0 0.0 0.0
+H -0.360 -0.510 -0.890  0.040 0.0 0.0 0.0
+H  1.750  0.900  0.000  0.418 0.0 0.0 0.0
+";
+
+#[test]
+fn import_methanol() {
+    let system = ExtendedXyz.parse_system_from_reader(METHANOL.as_bytes()).unwrap();
+    assert_eq!(system.size, 6);
+    assert!(!system.cell.is_orthorhombic());
+    assert_relative_eq!(system.cell.b(), Float::sqrt(145.0), epsilon = 1e-4);
+    assert_relative_eq!(system.positions[1][0], 1.43, epsilon = 1e-5);
+
+    // the hydroxyl and methyl hydrogens share a species but not a charge
+    assert_eq!(system.species[1], Species::from_element(Element::O));
+    assert_eq!(system.species[2], system.species[5]);
+    assert_eq!(system.species[5], Species::from_element(Element::H));
+    assert_relative_eq!(system.charge(5), 0.418);
+    assert_relative_eq!(system.charge(1), -0.683);
+}
+
+#[test]
+fn plain_xyz() {
+    let system = ExtendedXyz.parse_system_from_reader("2\nargon dimer\nAr 0.0 0.0 0.0\nAr 3.8 0.0 0.0\n".as_bytes()).unwrap();
+    assert_eq!(system.species[1], Species::from_element(Element::Ar));
+    assert_relative_eq!(system.charge(1), Element::Ar.charge());
+    assert!(system.topology.charges.is_empty());
+    // the padded cell spans every atom
+    assert_relative_eq!(system.cell.a(), 23.8, epsilon = 1e-4);
+}
+
+#[test]
+fn round_trip() {
+    let mut system = ExtendedXyz.parse_system_from_reader(METHANOL.as_bytes()).unwrap();
+    system.velocities[2] = nalgebra::Vector3::new(0.001, -0.002, 0.0);
+    let text = ExtendedXyz.write_str_from_system(&system);
+    let restored = ExtendedXyz.parse_system_from_reader(text.as_bytes()).unwrap();
+
+    assert_eq!(restored.size, system.size);
+    assert_relative_eq!(
+        (restored.cell.matrix() - system.cell.matrix()).norm(),
+        0.0,
+        epsilon = 1e-4
+    );
+    assert_eq!(restored.species, system.species);
+    for i in 0..system.size {
+        assert_relative_eq!((restored.positions[i] - system.positions[i]).norm(), 0.0, epsilon = 1e-5);
+        assert_relative_eq!((restored.velocities[i] - system.velocities[i]).norm(), 0.0, epsilon = 1e-7);
+        assert_relative_eq!(restored.species[i].mass(), system.species[i].mass());
+        assert_relative_eq!(restored.charge(i), system.charge(i));
+    }
+}
+
+#[test]
+fn malformed_xyz() {
+    let err = ExtendedXyz
+        .parse_system_from_reader("2\nargon dimer\nAr 0.0 0.0 0.0\nAr 3.8 zero 0.0\n".as_bytes())
+        .unwrap_err();
+    assert_eq!(err.to_string(), "Invalid value `zero` in XYZ data");
+    let err = ExtendedXyz.parse_system_from_reader("2\nargon dimer\nAr 0.0 0.0 0.0\n".as_bytes()).unwrap_err();
+    assert!(matches!(err, VelvetError::Parse { format: "XYZ", .. }));
+    assert!(load_xyz("missing.xyz").is_err());
+}
+
+#[test]
+fn references() {
+    let text = "\
+2
+Properties=species:S:1:pos:R:3:forces:R:3 energy=-0.2
+Ar 0.0 0.0 0.0 -0.1 0.0 0.0
+Ar 3.8 0.0 0.0 0.1 0.0 0.0
+3
+Properties=species:S:1:pos:R:3
+Ar 0.0 0.0 0.0
+Ar 4.0 0.0 0.0
+Ar 0.0 4.0 0.0
+";
+    let references = parse_references(text.as_bytes()).unwrap();
+    assert_eq!(references.len(), 2);
+    assert_relative_eq!(references[0].energy.unwrap(), -0.2);
+    assert_relative_eq!(references[0].forces.as_ref().unwrap()[1].x, 0.1);
+    assert_eq!(references[1].system.size, 3);
+    assert!(references[1].energy.is_none() && references[1].forces.is_none());
+    assert_eq!(references[0].system.species[0], references[1].system.species[2]);
+    assert!(parse_references("2\nenergy=low\nAr 0.0 0.0 0.0\nAr 3.8 0.0 0.0\n".as_bytes()).is_err());
+}
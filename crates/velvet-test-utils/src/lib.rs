@@ -1,22 +1,81 @@
+use nalgebra::Vector3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use velvet_core::prelude::*;
 use velvet_external_data::prelude::*;
 
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+
 static UPDATE_FREQUENCY: usize = 5;
 
 pub fn argon_system() -> System {
-    Poscar.parse_system_from_file(resources_path("Ar.poscar"))
+    Poscar.parse_system_from_file(resources_path("Ar.poscar")).unwrap()
 }
 
 pub fn binary_gas_system() -> System {
-    Poscar.parse_system_from_file(resources_path("ArXe.poscar"))
+    Poscar.parse_system_from_file(resources_path("ArXe.poscar")).unwrap()
 }
 
 pub fn magnesium_oxide_system() -> System {
-    Poscar.parse_system_from_file(resources_path("MgO.poscar"))
+    Poscar.parse_system_from_file(resources_path("MgO.poscar")).unwrap()
 }
 
 pub fn xenon_system() -> System {
-    Poscar.parse_system_from_file(resources_path("Xe.poscar"))
+    Poscar.parse_system_from_file(resources_path("Xe.poscar")).unwrap()
+}
+
+// fcc argon crystal of `repeats` unit cells along each axis with every atom displaced by up to
+// `displacement` along each axis and seeded starting velocities of about 20 K
+pub fn argon_crystal(repeats: usize, displacement: Float) -> System {
+    let a: Float = 5.26;
+    let basis = [[0.0, 0.0, 0.0], [0.5, 0.5, 0.0], [0.5, 0.0, 0.5], [0.0, 0.5, 0.5]];
+    let mut positions = Vec::new();
+    for i in 0..repeats {
+        for j in 0..repeats {
+            for k in 0..repeats {
+                for b in basis.iter() {
+                    positions.push(Vector3::new(b[0] + i as Float, b[1] + j as Float, b[2] + k as Float) * a);
+                }
+            }
+        }
+    }
+    let mut rng = StdRng::seed_from_u64(20);
+    let velocities = (0..positions.len())
+        .map(|_| Vector3::new(rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0), rng.gen_range(-1.0, 1.0)) * 1e-3)
+        .collect();
+    let cell = Cell::cubic(repeats as Float * a);
+    if displacement > 0.0 {
+        for pos in positions.iter_mut() {
+            *pos += Vector3::new(
+                rng.gen_range(-displacement, displacement),
+                rng.gen_range(-displacement, displacement),
+                rng.gen_range(-displacement, displacement),
+            );
+            cell.wrap_vector(pos);
+        }
+    }
+    let argon = Species::from_element(Element::Ar);
+    System {
+        size: positions.len(),
+        cell,
+        species: vec![argon; positions.len()],
+        velocities,
+        positions,
+        topology: Topology::default(),
+    }
+}
+
+pub fn argon_crystal_potentials(cutoff: Float) -> Potentials {
+    let argon = Species::from_element(Element::Ar);
+    let thickness = 1.0;
+    let lj = LennardJones::new(0.238, 3.4);
+    PotentialsBuilder::new()
+        .pair(lj, (argon, argon), cutoff, thickness)
+        .build()
 }
 
 pub fn argon_potentials() -> Potentials {
@@ -4,7 +4,7 @@ use velvet::prelude::*;
 
 fn main() {
     // Load the argon gas system from a POSCAR formatted file.
-    let mut system = Poscar.parse_system_from_file("resources/test/Ar.poscar");
+    let mut system = Poscar.parse_system_from_file("resources/test/Ar.poscar").unwrap();
 
     // Initialize the system temperature using a Boltzmann velocity distribution.
     let boltz = Boltzmann::new(300.0);
@@ -26,22 +26,26 @@ fn main() {
     // Run MD with no thermostat to simulate the NVE ensemble.
     let md = MolecularDynamics::new(velocity_verlet, NullThermostat);
 
-    // Create an output group which writes scalar properties to a file for post-processing.
-    let file_group = RawOutputGroupBuilder::new()
-        .destination(std::fs::File::create("argon.txt").unwrap())
+    // Create an output group which prints a table of thermodynamic properties and mirrors it to
+    // a CSV file for post-processing.
+    let thermo = ThermoOutput::new(100)
+        .column(PotentialEnergy)
+        .column(KineticEnergy)
+        .column(TotalEnergy)
+        .column(Temperature)
+        .csv(std::fs::File::create("argon.csv").unwrap());
+    let thermo_group = RawOutputGroupBuilder::new()
+        .destination(std::io::stdout())
         .interval(100)
-        .output(PotentialEnergy)
-        .output(KineticEnergy)
-        .output(TotalEnergy)
-        .output(Temperature)
+        .output(thermo)
         .build();
 
     // Build the configuration.
     let config = ConfigurationBuilder::new()
-        .raw_output_group(file_group)
+        .raw_output_group(thermo_group)
         .build();
 
     // Run the simulation.
     let mut sim = Simulation::new(system, potentials, md, config);
-    sim.run(250_000);
+    sim.run(250_000).unwrap();
 }
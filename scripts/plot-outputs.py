@@ -14,6 +14,8 @@ LABELS = {
 def generate_plots(args, fmt, properties):
     if fmt == "raw":
         _generate_plots_raw(args, properties)
+    elif fmt == "csv":
+        _generate_plots_csv(args, properties)
     elif fmt == "hdf5":
         raise NotImplementedError("HDF5 format is not yet implemented")
     else:
@@ -54,6 +56,26 @@ def _generate_plots_raw(args, properties):
     _generate_plots_inner(args, formatted_data)
 
 
+def _generate_plots_csv(args, properties):
+    # read the table written by a thermo output
+    with open(args.src, "r") as f:
+        lines = [line.strip() for line in f.readlines() if line.strip()]
+    header = lines[0].split(",")
+    rows = [[float(value) for value in line.split(",")] for line in lines[1:]]
+
+    # the step column gives the iteration of each row
+    steps = [row[header.index("step")] for row in rows]
+    formatted_data = {}
+    for key in properties:
+        if key not in header:
+            raise ValueError("column not found: {}".format(key))
+        column = header.index(key)
+        formatted_data[key] = {"x": steps, "y": [row[column] for row in rows]}
+
+    # generate plots
+    _generate_plots_inner(args, formatted_data)
+
+
 def _generate_plots_hdf5(args, properties):
     pass
 
@@ -88,6 +110,8 @@ if __name__ == "__main__":
     fmt = "raw"
     if args.src.endswith("h5") or args.src.endswith("hdf5"):
         fmt = "hdf5"
+    elif args.src.endswith("csv"):
+        fmt = "csv"
     
     # determine desired plots from flags
     properties = []
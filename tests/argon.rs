@@ -8,12 +8,13 @@ static ITERATIONS: usize = 10_000;
 
 #[test]
 #[serial]
+#[allow(clippy::unnecessary_mut_passed)]
 fn nve() {
     let system = test_utils::argon_system();
     let potentials = test_utils::argon_potentials();
     let mut sim = test_utils::nve_simulation(system, potentials);
 
-    sim.run(ITERATIONS);
+    sim.run(ITERATIONS).unwrap();
     let (mut system, potentials) = sim.consume();
 
     let pe_target = -3135.0;
@@ -40,12 +41,13 @@ fn nve() {
 
 #[test]
 #[serial]
+#[allow(clippy::unnecessary_mut_passed)]
 fn nvt() {
     let system = test_utils::argon_system();
     let potentials = test_utils::argon_potentials();
     let mut sim = test_utils::nvt_simulation(system, potentials);
 
-    sim.run(ITERATIONS);
+    sim.run(ITERATIONS).unwrap();
     let (mut system, potentials) = sim.consume();
 
     let pe_target = -3095.0;